@@ -0,0 +1,282 @@
+//! Stable interface for extensions of the Mirai server.
+//!
+//! The server crate is reorganised regularly and makes no promises about where its types live. Native and WASM
+//! extensions should depend on this crate instead, which only re-exports the types that extensions need under
+//! paths that do not change when the server is refactored.
+//!
+//! # Stability
+//!
+//! This crate follows semantic versioning independently of the server. Moving or renaming a type inside the
+//! server is absorbed here and never requires a new major version. Items are only removed or changed in an
+//! incompatible way in a major release. Extensions can compare their required version against [`VERSION`] when
+//! they are loaded.
+//!
+//! Anything that is not reachable from this crate is internal and may change at any time. The server, its world
+//! and its players are exposed through handles that wrap the internal types and only offer a small set of
+//! methods, so that the internals can change without breaking extensions.
+
+#![doc(html_favicon_url = "https://github.com/teampathfinders/mirai/blob/master/resources/logo.png?raw=true")]
+#![doc(html_logo_url = "https://github.com/teampathfinders/mirai/blob/master/resources/logo.png?raw=true")]
+#![warn(missing_docs, clippy::nursery)]
+
+/// Version of the extension API.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Registration and execution of commands.
+pub mod command {
+    pub use mirai::command::{CommandHandler, CommandTarget, Context, HandlerOutput, HandlerResult, ParsedArgument, ParsedCommand};
+    pub use proto::bedrock::{Command, CommandDataType, CommandEnum, CommandOverload, CommandParameter, CommandPermissionLevel};
+}
+
+/// Events emitted by the server.
+pub mod event {
+    pub use mirai::afk::AfkEvent;
+    pub use mirai::net::{QuitEvent, QuitReason};
+    pub use mirai::report::Report;
+}
+
+/// Reports filed against players.
+pub mod report {
+    pub use mirai::report::{FileBackend, Report, ReportBackend, ReportContext, ReportedPlayer};
+}
+
+/// Players connected to the server.
+pub mod player {
+    use std::fmt;
+    use std::sync::Arc;
+
+    use mirai::net::BedrockClient;
+    use proto::bedrock::{TextData, TextMessage};
+
+    use crate::world::Dimension;
+
+    pub use proto::bedrock::{DisconnectReason, PermissionLevel};
+    pub use proto::uuid::Uuid;
+
+    /// Handle to a connected player.
+    ///
+    /// Handles are cheap to clone. A handle can outlive the connection of its player, in which case methods
+    /// that need the connection return an error.
+    #[derive(Clone)]
+    pub struct PlayerHandle(Arc<BedrockClient>);
+
+    impl PlayerHandle {
+        /// Name of the player.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the player has not logged in yet.
+        pub fn name(&self) -> anyhow::Result<String> {
+            self.0.name().map(str::to_owned)
+        }
+
+        /// XUID of the player's Xbox account.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the player has not logged in yet.
+        pub fn xuid(&self) -> anyhow::Result<u64> {
+            self.0.xuid()
+        }
+
+        /// UUID of the player.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the player has not logged in yet.
+        pub fn uuid(&self) -> anyhow::Result<Uuid> {
+            self.0.uuid().copied()
+        }
+
+        /// Dimension that the player is currently in.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the player has not spawned yet.
+        pub fn dimension(&self) -> anyhow::Result<Dimension> {
+            self.0.dimension()
+        }
+
+        /// Shows a system message in the player's chat.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the message could not be sent.
+        pub fn send_message(&self, message: &str) -> anyhow::Result<()> {
+            self.0.send(TextMessage { data: TextData::System { message }, needs_translation: false, xuid: 0, platform_chat_id: "" })
+        }
+
+        /// Disconnects the player, showing them the given message.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the disconnect packet could not be sent.
+        pub fn kick(&self, message: &str, reason: DisconnectReason) -> anyhow::Result<()> {
+            self.0.kick_with_reason(message, reason)
+        }
+    }
+
+    impl fmt::Debug for PlayerHandle {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("PlayerHandle").field(&self.0.name().unwrap_or("<unknown>")).finish()
+        }
+    }
+
+    #[doc(hidden)]
+    impl From<Arc<BedrockClient>> for PlayerHandle {
+        fn from(client: Arc<BedrockClient>) -> Self {
+            Self(client)
+        }
+    }
+}
+
+/// The world that the server hosts.
+pub mod world {
+    use std::fmt;
+    use std::sync::Arc;
+
+    use mirai::level::service::Service;
+
+    pub use mirai::level::rule::{Rule, RuleValue};
+    pub use proto::types::Dimension;
+    pub use util::{BlockPosition, Vector};
+
+    /// Handle to the world.
+    ///
+    /// Handles are cheap to clone and all refer to the same world.
+    #[derive(Clone)]
+    pub struct WorldHandle(Arc<Service>);
+
+    impl WorldHandle {
+        /// Amount of ticks that have passed since the world was created.
+        pub fn time(&self) -> i64 {
+            self.0.time().get()
+        }
+
+        /// Sets the amount of ticks that have passed since the world was created.
+        pub fn set_time(&self, ticks: i64) {
+            self.0.time().set(ticks);
+        }
+
+        /// Time within the current day, in ticks.
+        pub fn time_of_day(&self) -> i64 {
+            self.0.time().time_of_day()
+        }
+
+        /// Current value of a gamerule.
+        pub fn gamerule<R: Rule>(&self) -> R::Value
+        where
+            RuleValue: From<R::Value>,
+        {
+            self.0.gamerule::<R>()
+        }
+
+        /// Changes a gamerule, returning its previous value.
+        pub fn set_gamerule<R: Rule>(&self, value: R::Value) -> R::Value
+        where
+            RuleValue: From<R::Value>,
+        {
+            self.0.set_gamerule::<R>(value)
+        }
+
+        /// Name of the block at the given position, or `None` if that part of the world has not been generated.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the world could not be read.
+        pub fn block(&self, position: Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<Option<String>> {
+            self.0.block_name(&position, dimension)
+        }
+    }
+
+    impl fmt::Debug for WorldHandle {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("WorldHandle").finish_non_exhaustive()
+        }
+    }
+
+    #[doc(hidden)]
+    impl From<Arc<Service>> for WorldHandle {
+        fn from(service: Arc<Service>) -> Self {
+            Self(service)
+        }
+    }
+}
+
+/// The server itself.
+pub mod server {
+    use std::fmt;
+    use std::sync::Arc;
+
+    use mirai::instance::Instance;
+    use proto::bedrock::{TextData, TextMessage};
+
+    use crate::player::PlayerHandle;
+    use crate::world::WorldHandle;
+
+    /// Handle to the running server.
+    ///
+    /// Handles are cheap to clone and all refer to the same server.
+    #[derive(Clone)]
+    pub struct Server(Arc<Instance>);
+
+    impl Server {
+        /// Name of the server as shown in the server list.
+        pub fn name(&self) -> &str {
+            self.0.config().name()
+        }
+
+        /// The world hosted by this server.
+        pub fn world(&self) -> WorldHandle {
+            WorldHandle::from(Arc::clone(self.0.level()))
+        }
+
+        /// Every player that has finished logging in.
+        pub fn players(&self) -> Vec<PlayerHandle> {
+            self.0.clients().connected().into_iter().map(PlayerHandle::from).collect()
+        }
+
+        /// Looks up a connected player by name.
+        pub fn player(&self, name: &str) -> Option<PlayerHandle> {
+            self.0.clients().by_username(name).map(PlayerHandle::from)
+        }
+
+        /// Looks up a connected player by the XUID of their Xbox account.
+        pub fn player_by_xuid(&self, xuid: u64) -> Option<PlayerHandle> {
+            self.0.clients().by_xuid(xuid).map(PlayerHandle::from)
+        }
+
+        /// Shows a system message in the chat of every player.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the message could not be broadcast.
+        pub fn broadcast_message(&self, message: &str) -> anyhow::Result<()> {
+            self.0.clients().broadcast(TextMessage { data: TextData::System { message }, needs_translation: false, xuid: 0, platform_chat_id: "" })
+        }
+    }
+
+    impl fmt::Debug for Server {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("Server").field(&self.name()).finish()
+        }
+    }
+
+    #[doc(hidden)]
+    impl From<Arc<Instance>> for Server {
+        fn from(instance: Arc<Instance>) -> Self {
+            Self(instance)
+        }
+    }
+}
+
+/// Commonly used items.
+pub mod prelude {
+    pub use crate::command::{Command, Context, HandlerOutput, HandlerResult, ParsedCommand};
+    pub use crate::player::PlayerHandle;
+    pub use crate::server::Server;
+    pub use crate::world::{Dimension, Vector, WorldHandle};
+}
+
+#[cfg(test)]
+mod test;
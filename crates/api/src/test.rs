@@ -0,0 +1,30 @@
+use crate::player::PlayerHandle;
+use crate::server::Server;
+use crate::world::WorldHandle;
+
+/// Extensions keep handles around in their own tasks.
+fn assert_handle<T: Clone + Send + Sync + std::fmt::Debug + 'static>() {}
+
+#[test]
+fn handles_are_shareable() {
+    assert_handle::<Server>();
+    assert_handle::<PlayerHandle>();
+    assert_handle::<WorldHandle>();
+}
+
+#[test]
+fn prelude_exposes_handles() {
+    use crate::prelude::*;
+
+    // The prelude must name the handles rather than the internal types they wrap.
+    let _: Option<fn(&Server) -> WorldHandle> = Some(Server::world);
+    let _: Option<fn(&Server) -> Vec<PlayerHandle>> = Some(Server::players);
+    let _: Option<fn(&WorldHandle) -> i64> = Some(WorldHandle::time);
+}
+
+#[test]
+fn version() {
+    let parts: Vec<&str> = crate::VERSION.split('.').collect();
+    assert_eq!(parts.len(), 3, "version is not in semver format");
+    assert!(parts.iter().all(|part| part.parse::<u32>().is_ok()), "version contains a non-numeric part");
+}
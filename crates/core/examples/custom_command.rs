@@ -0,0 +1,110 @@
+//! Registers a custom command that accepts target selectors.
+//!
+//! Run with `cargo run --example custom_command` and execute `/wave @a` or `/wave <player>` in game.
+
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::Arc;
+
+use mirai::command::{CommandTarget, Context, HandlerOutput, HandlerResult, ParsedArgument, ParsedCommand};
+use mirai::instance::Instance;
+use mirai::net::BedrockClient;
+use proto::bedrock::{Command, CommandDataType, CommandOverload, CommandParameter, CommandPermissionLevel, TextData, TextMessage};
+use util::Joinable;
+
+/// Syntax of the `wave` command.
+fn wave_command() -> Command {
+    Command {
+        aliases: vec!["hello".to_owned()],
+        description: "Waves at other players".to_owned(),
+        name: "wave".to_owned(),
+        overloads: vec![CommandOverload {
+            parameters: vec![CommandParameter {
+                name: "target".to_owned(),
+                command_enum: None,
+                data_type: CommandDataType::Target,
+                optional: false,
+                options: 0,
+                suffix: String::new(),
+            }],
+        }],
+        permission_level: CommandPermissionLevel::Normal,
+    }
+}
+
+/// Resolves a target selector to the players it refers to.
+fn resolve(target: &CommandTarget, ctx: &Context) -> Result<Vec<Arc<BedrockClient>>, String> {
+    Ok(match target {
+        CommandTarget::AllPlayers | CommandTarget::AllEntities => ctx.instance.clients().connected(),
+        CommandTarget::Yourself => vec![Arc::clone(&ctx.caller)],
+        CommandTarget::SpecificPlayer(name) => {
+            let client = ctx.instance.clients().by_username(name).ok_or_else(|| format!("{name} is not online"))?;
+            vec![client]
+        }
+        CommandTarget::ClosestPlayer | CommandTarget::RandomPlayer => return Err("This selector is not supported".to_owned()),
+    })
+}
+
+/// Handler of the `wave` command.
+fn handle_wave(input: ParsedCommand, ctx: &Context) -> HandlerResult {
+    let Some(target) = input.parameters.get("target").and_then(ParsedArgument::as_target) else {
+        return HandlerOutput::new().message("No target was specified").error();
+    };
+
+    let targets = match resolve(target, ctx) {
+        Ok(targets) => targets,
+        Err(message) => return HandlerOutput::new().message(message).error(),
+    };
+
+    let name = ctx.caller.name().unwrap_or("Someone");
+    let message = format!("{name} waves at you");
+    for client in &targets {
+        let result = client.send(TextMessage {
+            data: TextData::Raw { message: &message },
+            needs_translation: false,
+            xuid: 0,
+            platform_chat_id: "",
+        });
+
+        if let Err(err) = result {
+            return HandlerOutput::new().message(format!("Failed to wave: {err:#}")).error();
+        }
+    }
+
+    HandlerOutput::new().message(format!("Waved at {} players", targets.len())).success()
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let instance = Instance::builder()
+        .ipv4_addr(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 19132))
+        .build()
+        .await?;
+
+    // Commands can be registered at any time, clients receive the new command list when they join.
+    instance.commands().register(wave_command(), handle_wave)?;
+
+    instance.start()?;
+    instance.join().await
+}
+
+#[test]
+fn wave_syntax() {
+    let syntax = wave_command();
+
+    let Ok(parsed) = ParsedCommand::default_parser(&syntax, "/wave @a") else {
+        panic!("selector was rejected");
+    };
+    assert_eq!(parsed.parameters.get("target").and_then(ParsedArgument::as_target), Some(&CommandTarget::AllPlayers));
+
+    let Ok(parsed) = ParsedCommand::default_parser(&syntax, "/wave Steve") else {
+        panic!("player name was rejected");
+    };
+    assert_eq!(
+        parsed.parameters.get("target").and_then(ParsedArgument::as_target),
+        Some(&CommandTarget::SpecificPlayer("Steve".to_owned()))
+    );
+
+    assert!(ParsedCommand::default_parser(&syntax, "/wave").is_err(), "missing target was accepted");
+}
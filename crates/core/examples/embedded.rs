@@ -0,0 +1,28 @@
+//! Runs a minimal server embedded in another application.
+//!
+//! Run with `cargo run --example embedded`, the server can then be joined at `localhost:19132`.
+
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::time::Duration;
+
+use mirai::afk::AfkAction;
+use mirai::instance::Instance;
+use util::Joinable;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let instance = Instance::builder()
+        .ipv4_addr(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 19132))
+        .level_path("resources/level")
+        .afk_timeout(Some(Duration::from_secs(10 * 60)))
+        .afk_action(AfkAction::Kick)
+        .build()
+        .await?;
+
+    instance.start()?;
+
+    // The server shuts down by itself when it receives Ctrl+C or the `/stop` command is executed.
+    instance.join().await
+}
@@ -0,0 +1,108 @@
+//! Sends a form to a player and handles the response.
+//!
+//! Run with `cargo run --example form` and execute `/survey` in game.
+
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::Arc;
+
+use mirai::command::{Context, HandlerOutput, HandlerResult, ParsedCommand};
+use mirai::forms::{Custom, Input, Response, Slider, Toggle};
+use mirai::instance::Instance;
+use mirai::net::BedrockClient;
+use proto::bedrock::{Command, CommandOverload, CommandPermissionLevel, TextData, TextMessage};
+use util::Joinable;
+
+/// Syntax of the `survey` command.
+fn survey_command() -> Command {
+    Command {
+        aliases: Vec::new(),
+        description: "Opens a short survey".to_owned(),
+        name: "survey".to_owned(),
+        overloads: vec![CommandOverload { parameters: Vec::new() }],
+        permission_level: CommandPermissionLevel::Normal,
+    }
+}
+
+/// Creates the survey form.
+fn survey() -> Custom<'static> {
+    Custom::new()
+        .title("Survey")
+        .with("name", Input::new().label("What should we call you?").placeholder("Steve"))
+        .with("rating", Slider::new().label("How much do you like the server?").min(0).max(10).step(1).default(5))
+        .with("newsletter", Toggle::new().label("Receive announcements").default(true))
+}
+
+/// Sends a chat message to a single player.
+fn reply(client: &BedrockClient, message: &str) -> anyhow::Result<()> {
+    client.send(TextMessage {
+        data: TextData::Raw { message },
+        needs_translation: false,
+        xuid: 0,
+        platform_chat_id: "",
+    })
+}
+
+/// Summarises the response of a player.
+fn summarise(response: &Response) -> anyhow::Result<String> {
+    if let Ok(reason) = response.as_cancelled() {
+        return Ok(format!("The survey was closed ({reason:?})"));
+    }
+
+    let body = response.as_body()?.as_custom()?;
+    let name = body.get("name").map_or(Ok(""), |value| value.as_str())?;
+    let rating = body.get("rating").map_or(Ok(0.0), |value| value.as_float())?;
+
+    Ok(format!("Thanks {name}, you rated the server {rating}/10"))
+}
+
+/// Handler of the `survey` command.
+fn handle_survey(_input: ParsedCommand, ctx: &Context) -> HandlerResult {
+    // Subscribing returns a receiver instead of blocking the command handler until the form is submitted.
+    let receiver = match ctx.caller.forms().subscribe(&ctx.caller, survey()) {
+        Ok(receiver) => receiver,
+        Err(err) => return HandlerOutput::new().message(format!("Failed to open survey: {err:#}")).error(),
+    };
+
+    let caller = Arc::clone(&ctx.caller);
+    tokio::spawn(async move {
+        let Ok(response) = receiver.await else {
+            // The player disconnected before responding.
+            return;
+        };
+
+        let result = summarise(&response).and_then(|summary| reply(&caller, &summary));
+        if let Err(err) = result {
+            tracing::error!("Failed to handle survey response: {err:#}");
+        }
+    });
+
+    HandlerOutput::new().success()
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let instance = Instance::builder()
+        .ipv4_addr(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 19132))
+        .build()
+        .await?;
+
+    instance.commands().register(survey_command(), handle_survey)?;
+
+    instance.start()?;
+    instance.join().await
+}
+
+#[test]
+fn survey_form() {
+    use proto::bedrock::CancelReason;
+
+    let form = serde_json::to_value(survey()).unwrap();
+    assert_eq!(form["type"], "custom_form");
+    assert_eq!(form["title"], "Survey");
+    assert_eq!(form["content"].as_array().map(Vec::len), Some(3));
+
+    let summary = summarise(&Response::Cancelled(CancelReason::Busy)).unwrap();
+    assert_eq!(summary, "The survey was closed (Busy)");
+}
@@ -0,0 +1,93 @@
+//! Inspects incoming packets using a packet interceptor.
+//!
+//! Run with `cargo run --example interceptor`. Chat messages containing a blocked word are dropped before the
+//! server handles them and every packet is counted.
+
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use mirai::instance::Instance;
+use mirai::net::{BedrockClient, Interception, PacketInterceptor};
+use proto::bedrock::{ConnectedPacket, TextData, TextMessage};
+use util::{Deserialize, Joinable};
+
+/// Drops chat messages that contain any of the blocked words and counts incoming packets.
+struct ChatFilter {
+    /// Words that are not allowed in chat, in lowercase.
+    blocked: Vec<&'static str>,
+    /// Amount of packets that have been received.
+    received: AtomicU64,
+}
+
+impl ChatFilter {
+    /// Decides what happens to the packet with ID `id`.
+    fn check(&self, id: u32, packet: &[u8]) -> Interception {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        if id != TextMessage::ID {
+            return Interception::Pass
+        }
+
+        // Malformed packets are passed on, the server rejects them itself.
+        let Ok(TextMessage { data: TextData::Chat { message, .. }, .. }) = TextMessage::deserialize(packet) else {
+            return Interception::Pass
+        };
+
+        let message = message.to_lowercase();
+        if self.blocked.iter().any(|word| message.contains(word)) {
+            Interception::Drop
+        } else {
+            Interception::Pass
+        }
+    }
+}
+
+impl PacketInterceptor for ChatFilter {
+    fn inbound(&self, client: &BedrockClient, id: u32, packet: &[u8]) -> Interception {
+        let verdict = self.check(id, packet);
+        if verdict == Interception::Drop {
+            tracing::info!("Dropped a chat message from {}", client.name().unwrap_or("<unknown>"));
+        }
+        verdict
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let filter = ChatFilter { blocked: vec!["griefing"], received: AtomicU64::new(0) };
+    let instance = Instance::builder()
+        .ipv4_addr(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 19132))
+        .interceptor(filter)
+        .build()
+        .await?;
+
+    instance.start()?;
+    instance.join().await
+}
+
+#[test]
+fn chat_filter() {
+    use util::Serialize;
+
+    let filter = ChatFilter { blocked: vec!["griefing"], received: AtomicU64::new(0) };
+    let chat = |message| {
+        let packet = TextMessage {
+            data: TextData::Chat { source: "Steve", message },
+            needs_translation: false,
+            xuid: 0,
+            platform_chat_id: "",
+        };
+
+        let Ok(encoded) = packet.serialize() else { panic!("failed to serialize message") };
+        filter.check(TextMessage::ID, encoded.as_ref())
+    };
+
+    assert_eq!(chat("Hello there"), Interception::Pass);
+    assert_eq!(chat("Who is GRIEFING my house?"), Interception::Drop);
+
+    // Other packets are never dropped, even if they cannot be decoded as a chat message.
+    assert_eq!(filter.check(TextMessage::ID, &[]), Interception::Pass);
+    assert_eq!(filter.check(0x90, b"griefing"), Interception::Pass);
+    assert_eq!(filter.received.load(Ordering::Relaxed), 4);
+}
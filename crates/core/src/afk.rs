@@ -0,0 +1,231 @@
+//! Detection of idle players.
+//!
+//! Every player keeps track of when it last performed meaningful input, such as moving, looking around, chatting or
+//! interacting with the world. Players that have not done anything for the configured [`timeout`](AfkConfig::timeout)
+//! are marked as away from keyboard and the configured [`AfkAction`] is applied.
+//!
+//! Plugins that want different behaviour can set the action to [`AfkAction::Mark`] and
+//! [subscribe](crate::instance::Instance::afk_events) to the [`AfkEvent`]s instead.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use proto::bedrock::DisconnectReason;
+use tokio_util::sync::CancellationToken;
+
+use crate::clock::{SharedClock, SystemClock};
+use crate::instance::Instance;
+use crate::net::BedrockClient;
+
+/// How often the monitor checks for idle players.
+const MONITOR_INTERVAL: Duration = Duration::from_secs(1);
+/// Rotations smaller than this many degrees are not considered input.
+///
+/// Clients send slightly different rotations while standing still due to floating point errors.
+const ROTATION_THRESHOLD: f32 = 0.1;
+/// Message shown to players that are kicked for being idle.
+const KICK_MESSAGE: &str = "You have been idle for too long";
+
+/// What happens to players once they are marked as AFK.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum AfkAction {
+    /// Only mark the player as AFK and emit an [`AfkEvent`].
+    #[default]
+    Mark,
+    /// Kick the player from the server.
+    Kick,
+}
+
+/// AFK detection settings.
+#[derive(Debug, Clone)]
+pub struct AfkConfig {
+    /// How long a player has to be idle before they are marked as AFK.
+    ///
+    /// Setting this to `None` disables AFK detection.
+    pub timeout: Option<Duration>,
+    /// Action performed when a player is marked as AFK.
+    pub action: AfkAction,
+    /// Whether AFK players are left out when counting the players required to skip the night.
+    pub exclude_from_sleep: bool,
+}
+
+impl Default for AfkConfig {
+    fn default() -> Self {
+        Self { timeout: Some(Duration::from_secs(5 * 60)), action: AfkAction::Mark, exclude_from_sleep: true }
+    }
+}
+
+/// Emitted when the AFK status of a player changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AfkEvent {
+    /// The player has been idle for longer than the timeout.
+    Away {
+        /// XUID of the player.
+        xuid: u64,
+        /// Username of the player.
+        name: String,
+    },
+    /// The player performed input again after being AFK.
+    Returned {
+        /// XUID of the player.
+        xuid: u64,
+        /// Username of the player.
+        name: String,
+    },
+}
+
+/// Input related state of a player.
+#[derive(Debug)]
+struct Activity {
+    /// When the last meaningful input was received.
+    last_input: Instant,
+    /// Last known pitch and yaw of the player.
+    rotation: [f32; 2],
+}
+
+/// Tracks the activity of a single player.
+#[derive(Debug)]
+pub struct AfkTracker {
+    activity: Mutex<Activity>,
+    afk: AtomicBool,
+    clock: SharedClock,
+}
+
+impl AfkTracker {
+    /// Creates a tracker for a player that has just performed input.
+    pub fn new() -> AfkTracker {
+        AfkTracker::with_clock(SystemClock::shared())
+    }
+
+    /// Creates a tracker that reads the time from the given clock.
+    pub fn with_clock(clock: SharedClock) -> AfkTracker {
+        AfkTracker {
+            activity: Mutex::new(Activity { last_input: clock.now(), rotation: [0.0; 2] }),
+            afk: AtomicBool::new(false),
+            clock,
+        }
+    }
+
+    /// Registers meaningful input.
+    ///
+    /// Returns whether the player was AFK before this input.
+    pub fn record_input(&self) -> bool {
+        self.activity.lock().last_input = self.clock.now();
+        self.afk.swap(false, Ordering::AcqRel)
+    }
+
+    /// Registers the current rotation of the player.
+    ///
+    /// This only counts as input if the rotation has changed.
+    /// Returns whether the player was AFK before this input.
+    pub fn record_rotation(&self, pitch: f32, yaw: f32) -> bool {
+        let mut activity = self.activity.lock();
+        let [old_pitch, old_yaw] = activity.rotation;
+        if (pitch - old_pitch).abs() < ROTATION_THRESHOLD && (yaw - old_yaw).abs() < ROTATION_THRESHOLD {
+            return false
+        }
+
+        activity.rotation = [pitch, yaw];
+        activity.last_input = self.clock.now();
+        drop(activity);
+
+        self.afk.swap(false, Ordering::AcqRel)
+    }
+
+    /// How long the player has been idle for.
+    pub fn idle_time(&self) -> Duration {
+        self.clock.elapsed(self.activity.lock().last_input)
+    }
+
+    /// Whether the player is currently marked as AFK.
+    pub fn is_afk(&self) -> bool {
+        self.afk.load(Ordering::Acquire)
+    }
+
+    /// Marks the player as AFK if they have been idle for at least `timeout`.
+    ///
+    /// Returns whether the player has just become AFK.
+    pub fn check(&self, timeout: Duration) -> bool {
+        !self.is_afk() && self.idle_time() >= timeout && !self.afk.swap(true, Ordering::AcqRel)
+    }
+}
+
+impl Default for AfkTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BedrockClient {
+    /// Whether this player is currently away from keyboard.
+    pub fn is_afk(&self) -> bool {
+        self.afk.is_afk()
+    }
+
+    /// How long this player has been idle for.
+    pub fn idle_time(&self) -> Duration {
+        self.afk.idle_time()
+    }
+
+    /// Registers meaningful input from the player.
+    pub(crate) fn record_input(&self) {
+        if self.afk.record_input() {
+            self.on_returned();
+        }
+    }
+
+    /// Registers the rotation of the player, which only counts as input if it has changed.
+    pub(crate) fn record_rotation(&self, pitch: f32, yaw: f32) {
+        if self.afk.record_rotation(pitch, yaw) {
+            self.on_returned();
+        }
+    }
+
+    /// Emits an [`AfkEvent::Returned`] event.
+    fn on_returned(&self) {
+        let (Ok(xuid), Ok(name)) = (self.xuid(), self.name()) else {
+            return
+        };
+
+        tracing::debug!("{name} is no longer AFK");
+        self.instance().emit_afk_event(AfkEvent::Returned { xuid, name: name.to_owned() });
+    }
+}
+
+/// Periodically marks idle players as AFK until the token is cancelled.
+pub(crate) async fn monitor(instance: Arc<Instance>, token: CancellationToken) {
+    let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            () = token.cancelled() => break
+        }
+
+        let config = instance.config().afk();
+        let Some(timeout) = config.timeout else {
+            continue
+        };
+
+        for client in instance.clients().connected() {
+            if !client.afk.check(timeout) {
+                continue
+            }
+
+            let (Ok(xuid), Ok(name)) = (client.xuid(), client.name()) else {
+                continue
+            };
+
+            tracing::debug!("{name} is now AFK");
+            instance.emit_afk_event(AfkEvent::Away { xuid, name: name.to_owned() });
+
+            if config.action == AfkAction::Kick {
+                if let Err(err) = client.kick_with_reason(KICK_MESSAGE, DisconnectReason::KickedForIdle) {
+                    tracing::error!("Failed to kick idle player: {err:#}");
+                }
+            }
+        }
+    }
+}
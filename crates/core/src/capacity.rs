@@ -0,0 +1,84 @@
+//! Metrics used for capacity planning.
+//!
+//! Packets pass through several queues on their way through the server: the forward channel of a connection, the
+//! RakNet order channels, the send queues and, for chunk requests, the level region streams. Under load the queue
+//! in front of the slowest stage grows first, so comparing their depths shows which stage saturates. Drop and
+//! timeout counters show where work is lost once a queue is full.
+//!
+//! Queue depths are sampled periodically and summed over all connections. Use [`register`] to add all metrics to
+//! a Prometheus registry.
+
+use std::sync::atomic::AtomicI64;
+use std::sync::Arc;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::registry::Registry;
+use tokio_util::sync::CancellationToken;
+
+use crate::instance::Instance;
+
+lazy_static! {
+    #[doc(hidden)]
+    pub static ref SEND_QUEUE_DEPTH_METRIC: Gauge::<i64, AtomicI64> = Gauge::default();
+    #[doc(hidden)]
+    pub static ref ORDER_QUEUE_DEPTH_METRIC: Gauge::<i64, AtomicI64> = Gauge::default();
+    #[doc(hidden)]
+    pub static ref FORWARD_QUEUE_DEPTH_METRIC: Gauge::<i64, AtomicI64> = Gauge::default();
+}
+
+/// Interval at which the queue depths are sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Updates the queue depth gauges with the current state of all connections.
+pub fn sample(instance: &Instance) {
+    let clients = instance.clients();
+    let stats = clients.network_stats();
+
+    SEND_QUEUE_DEPTH_METRIC.set(stats.send_queue_depth as i64);
+    ORDER_QUEUE_DEPTH_METRIC.set(stats.order_queue_depth as i64);
+    FORWARD_QUEUE_DEPTH_METRIC.set(clients.forward_queue_depth() as i64);
+}
+
+/// Periodically samples the queue depths until the server shuts down.
+pub(crate) async fn monitor(instance: Arc<Instance>, token: CancellationToken) {
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => sample(&instance),
+            () = token.cancelled() => break
+        }
+    }
+}
+
+/// Registers all server metrics.
+///
+/// Metrics are named `<subsystem>_<quantity>`. Gauges of queues end in `_depth`, counters of lost work end in
+/// `_dropped` or `_timeouts`.
+pub fn register(registry: &mut Registry) {
+    let raknet = registry.sub_registry_with_prefix("raknet");
+    raknet.register("send_queue_depth", "Frames waiting in the send queues", SEND_QUEUE_DEPTH_METRIC.clone());
+    raknet.register("order_queue_depth", "Frames waiting in the order channels for earlier frames", ORDER_QUEUE_DEPTH_METRIC.clone());
+    raknet.register("packets", "Packets received", raknet::TOTAL_PACKETS_METRIC.clone());
+    raknet.register("empty_datagrams_dropped", "Datagrams without content that were discarded", raknet::EMPTY_DATAGRAMS_METRIC.clone());
+    raknet.register("rate_limited_dropped", "Datagrams discarded by the rate limiter", raknet::RATE_LIMITED_METRIC.clone());
+    raknet.register("compounds_dropped", "Incomplete compounds discarded after they expired", raknet::EXPIRED_COMPOUNDS_METRIC.clone());
+    raknet.register("limit_disconnects", "Clients disconnected for exceeding a protocol limit", raknet::LIMIT_DISCONNECTS_METRIC.clone());
+    raknet.register("session_timeouts", "Sessions disconnected because they stopped responding", raknet::SESSION_TIMEOUTS_METRIC.clone());
+
+    let net = registry.sub_registry_with_prefix("net");
+    net.register("forward_queue_depth", "Packets waiting in the forward channels", FORWARD_QUEUE_DEPTH_METRIC.clone());
+    net.register("forward_timeouts", "Packets that could not be forwarded to a connection in time", crate::net::FORWARD_TIMEOUTS_METRIC.clone());
+    net.register("dropped_forwards", "Packets that were dropped because a connection was not keeping up", crate::net::DROPPED_FORWARDS_METRIC.clone());
+    net.register("broadcasts_dropped", "Broadcasts skipped by connections that fell behind", crate::net::DROPPED_BROADCASTS_METRIC.clone());
+    net.register("packets_by_type", "Game packets by direction and packet ID", crate::net::traffic::PACKETS_BY_TYPE_METRIC.clone());
+    net.register("packet_bytes_by_type", "Bytes of game packets by direction and packet ID", crate::net::traffic::PACKET_BYTES_BY_TYPE_METRIC.clone());
+
+    let level = registry.sub_registry_with_prefix("level");
+    level.register("region_queue_depth", "Requested subchunks that have not been received yet", crate::level::io::stream::REGION_QUEUE_DEPTH_METRIC.clone());
+    level.register("block_updates", "Block updates", crate::level::stats::BLOCK_UPDATES_METRIC.clone());
+    level.register("tracked_chunks", "Chunks with statistics", crate::level::stats::TRACKED_CHUNKS_METRIC.clone());
+    level.register("throttled", "Whether background work is throttled", crate::level::throttle::THROTTLED_METRIC.clone());
+    level.register("slow_ticks", "Ticks that exceeded their budget", crate::level::throttle::SLOW_TICKS_METRIC.clone());
+}
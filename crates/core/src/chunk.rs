@@ -26,7 +26,7 @@ impl ChunkManager {
 
         let clone = manager.clone();
         let (sender, receiver) = tokio::sync::oneshot::channel();
-        tokio::spawn(async move {
+        util::task::spawn("chunk::autosave", async move {
             clone.autosave_job(sender, autosave_interval).await
         });
 
@@ -0,0 +1,117 @@
+//! Source of time for the server.
+//!
+//! Services that compare points in time, such as [cooldowns](crate::cooldown) and [AFK detection](crate::afk),
+//! read the time from a [`Clock`] instead of calling [`Instant::now`] directly. The server uses the [`SystemClock`],
+//! while tests can use a [`ManualClock`] to make time pass instantly and deterministically:
+//!
+//! ```ignore
+//! let clock = Arc::new(ManualClock::new());
+//! let cooldowns = Cooldowns::with_clock(Arc::<ManualClock>::clone(&clock));
+//!
+//! cooldowns.set(xuid, "myplugin:teleport", Duration::from_secs(30));
+//! clock.advance(Duration::from_secs(30));
+//! assert!(!cooldowns.is_active(xuid, "myplugin:teleport"));
+//! ```
+//!
+//! Tasks that wait using Tokio timers, such as autosaving, can be tested using Tokio's paused time instead.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use parking_lot::Mutex;
+
+use crate::level::throttle::TICK_INTERVAL;
+
+/// A clock shared between services.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Provides the current time.
+pub trait Clock: Send + Sync + Debug {
+    /// Current point in monotonic time.
+    fn now(&self) -> Instant;
+
+    /// Current wall clock time.
+    ///
+    /// This should only be used for timestamps that are stored or shown to users.
+    fn system_time(&self) -> SystemTime;
+
+    /// Time that has passed since the given point in time.
+    #[inline]
+    fn elapsed(&self, since: Instant) -> Duration {
+        self.now().saturating_duration_since(since)
+    }
+
+    /// Amount of full game ticks that have passed since the given point in time.
+    #[inline]
+    fn ticks_since(&self, since: Instant) -> u64 {
+        (self.elapsed(since).as_nanos() / TICK_INTERVAL.as_nanos()) as u64
+    }
+}
+
+/// The real clock of the system.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SystemClock;
+
+impl SystemClock {
+    /// Creates a shared system clock.
+    pub fn shared() -> SharedClock {
+        Arc::new(SystemClock)
+    }
+}
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    #[inline]
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves forward when it is advanced.
+#[derive(Debug)]
+pub struct ManualClock {
+    /// Monotonic time at which the clock was created.
+    start: Instant,
+    /// Wall clock time at which the clock was created.
+    start_time: SystemTime,
+    /// How far the clock has been advanced.
+    offset: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Creates a clock that starts at the current time.
+    pub fn new() -> ManualClock {
+        ManualClock { start: Instant::now(), start_time: SystemTime::now(), offset: Mutex::new(Duration::ZERO) }
+    }
+
+    /// Moves the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        *self.offset.lock() += duration;
+    }
+
+    /// Moves the clock forward by the given amount of game ticks.
+    pub fn advance_ticks(&self, ticks: u32) {
+        self.advance(TICK_INTERVAL * ticks);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + *self.offset.lock()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.start_time + *self.offset.lock()
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use proto::bedrock::{Command, CommandDataType, CommandOverload, CommandParameter, CommandPermissionLevel, TextData, TextMessage};
+
+use crate::cooldown::actions;
+use crate::report::{ReportContext, ReportedPlayer};
+
+use super::{Context, HandlerOutput, HandlerResult, ParsedArgument, ParsedCommand, Service};
+
+/// Message shown to players that are kicked without a reason.
+const DEFAULT_KICK_MESSAGE: &str = "Kicked by an operator";
+/// How long players have to wait between reports.
+const REPORT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Registers the built-in administration commands.
+///
+/// These are `/list`, `/kick`, `/say` and `/stop`, which behave like their vanilla counterparts,
+/// and `/report`, which files a [report](crate::report) against another player.
+pub fn register_builtin(service: &Service) -> anyhow::Result<()> {
+    service.register(list_command(), handle_list)?;
+    service.register(kick_command(), handle_kick)?;
+    service.register(report_command(), handle_report)?;
+    service.register(say_command(), handle_say)?;
+    service.register(stop_command(), handle_stop)
+}
+
+/// Creates a command parameter.
+fn parameter(name: &str, data_type: CommandDataType, optional: bool) -> CommandParameter {
+    CommandParameter {
+        name: name.to_owned(),
+        command_enum: None,
+        data_type,
+        optional,
+        options: 0,
+        suffix: "".to_owned(),
+    }
+}
+
+/// Syntax of the `list` command.
+fn list_command() -> Command {
+    Command {
+        aliases: Vec::new(),
+        description: "Lists players on the server".to_owned(),
+        name: "list".to_owned(),
+        overloads: vec![CommandOverload { parameters: Vec::new() }],
+        permission_level: CommandPermissionLevel::Normal,
+    }
+}
+
+/// Handler of the `list` command.
+fn handle_list(_input: ParsedCommand, ctx: &Context) -> HandlerResult {
+    let clients = ctx.instance.clients();
+    let names = clients
+        .connected()
+        .iter()
+        .filter_map(|client| client.name().ok().map(str::to_owned))
+        .collect::<Vec<_>>();
+
+    HandlerOutput::new()
+        .message(format!(
+            "There are {}/{} players online:\n{}",
+            names.len(),
+            clients.max_connections(),
+            names.join(", ")
+        ))
+        .success()
+}
+
+/// Syntax of the `kick` command.
+pub fn kick_command() -> Command {
+    Command {
+        aliases: Vec::new(),
+        description: "Kicks a player from the server".to_owned(),
+        name: "kick".to_owned(),
+        overloads: vec![CommandOverload {
+            parameters: vec![
+                parameter("player", CommandDataType::String, false),
+                parameter("reason", CommandDataType::Message, true),
+            ],
+        }],
+        permission_level: CommandPermissionLevel::GameDirectors,
+    }
+}
+
+/// Handler of the `kick` command.
+fn handle_kick(input: ParsedCommand, ctx: &Context) -> HandlerResult {
+    let Some(name) = input.parameters.get("player").and_then(ParsedArgument::as_string) else {
+        return HandlerOutput::new().message("No player was specified").error();
+    };
+
+    let Some(client) = ctx.instance.clients().by_username(name) else {
+        return HandlerOutput::new().message(format!("{name} is not online")).error();
+    };
+
+    let reason = input.parameters.get("reason").and_then(ParsedArgument::as_string).unwrap_or(DEFAULT_KICK_MESSAGE);
+    if let Err(err) = client.kick(reason) {
+        return HandlerOutput::new().message(format!("Failed to kick {name}: {err:#}")).error();
+    }
+
+    HandlerOutput::new().message(format!("Kicked {name} from the game: {reason}")).success()
+}
+
+/// Syntax of the `report` command.
+fn report_command() -> Command {
+    Command {
+        aliases: Vec::new(),
+        description: "Reports a player to the moderators".to_owned(),
+        name: "report".to_owned(),
+        overloads: vec![CommandOverload {
+            parameters: vec![
+                parameter("player", CommandDataType::String, false),
+                parameter("reason", CommandDataType::Message, false),
+            ],
+        }],
+        permission_level: CommandPermissionLevel::Normal,
+    }
+}
+
+/// Handler of the `report` command.
+fn handle_report(input: ParsedCommand, ctx: &Context) -> HandlerResult {
+    let Some(name) = input.parameters.get("player").and_then(ParsedArgument::as_string) else {
+        return HandlerOutput::new().message("No player was specified").error();
+    };
+
+    let Some(reason) = input.parameters.get("reason").and_then(ParsedArgument::as_string) else {
+        return HandlerOutput::new().message("No reason was specified").error();
+    };
+
+    let Some(target) = ctx.instance.clients().by_username(name) else {
+        return HandlerOutput::new().message(format!("{name} is not online")).error();
+    };
+
+    let Ok(reporter) = ReportedPlayer::from_client(&ctx.caller) else {
+        return HandlerOutput::new().message("Only players can file reports").error();
+    };
+
+    if reporter.xuid == target.xuid().unwrap_or_default() {
+        return HandlerOutput::new().message("You cannot report yourself").error();
+    }
+
+    if let Err(remaining) = ctx.instance.cooldowns().try_acquire(reporter.xuid, actions::REPORT, REPORT_COOLDOWN) {
+        return HandlerOutput::new()
+            .message(format!("You can file another report in {} seconds", remaining.as_secs() + 1))
+            .error();
+    }
+
+    let context = ReportContext { reporter: Some(reporter), details: String::new() };
+    if let Err(err) = ctx.instance.report(&target, reason, context) {
+        tracing::error!("Failed to submit report: {err:#}");
+        return HandlerOutput::new().message("Your report could not be submitted").error();
+    }
+
+    HandlerOutput::new().message(format!("Thank you, {name} has been reported to the moderators")).success()
+}
+
+/// Syntax of the `say` command.
+fn say_command() -> Command {
+    Command {
+        aliases: Vec::new(),
+        description: "Sends a message to all players".to_owned(),
+        name: "say".to_owned(),
+        overloads: vec![CommandOverload {
+            parameters: vec![parameter("message", CommandDataType::Message, false)],
+        }],
+        permission_level: CommandPermissionLevel::GameDirectors,
+    }
+}
+
+/// Handler of the `say` command.
+fn handle_say(input: ParsedCommand, ctx: &Context) -> HandlerResult {
+    let Some(message) = input.parameters.get("message").and_then(ParsedArgument::as_string) else {
+        return HandlerOutput::new().message("No message was specified").error();
+    };
+
+    let source = ctx.caller.name().unwrap_or("Server");
+    let result = ctx.instance.clients().broadcast(TextMessage {
+        data: TextData::Announcement { source, message },
+        needs_translation: false,
+        xuid: 0,
+        platform_chat_id: "",
+    });
+
+    match result {
+        Ok(()) => HandlerOutput::new().success(),
+        Err(err) => HandlerOutput::new().message(format!("Failed to send message: {err:#}")).error(),
+    }
+}
+
+/// Syntax of the `stop` command.
+pub fn stop_command() -> Command {
+    Command {
+        aliases: vec!["shutdown".to_owned()],
+        description: "Stops the server".to_owned(),
+        name: "stop".to_owned(),
+        overloads: vec![CommandOverload { parameters: Vec::new() }],
+        permission_level: CommandPermissionLevel::Admin,
+    }
+}
+
+/// Handler of the `stop` command.
+///
+/// All players are disconnected and the level is saved before the server exits.
+fn handle_stop(_input: ParsedCommand, ctx: &Context) -> HandlerResult {
+    if ctx.instance.shutdown().is_none() {
+        return HandlerOutput::new().message("Server is already stopping").error();
+    }
+
+    HandlerOutput::new().message("Stopping the server").success()
+}
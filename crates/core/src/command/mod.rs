@@ -2,6 +2,7 @@
 
 use ::util::glob_export;
 
+glob_export!(builtin);
 glob_export!(service);
 glob_export!(handler);
 glob_export!(parser);
@@ -208,6 +208,10 @@ fn parse_overload(overload: &CommandOverload, mut parts: Split<char>)
         let value = match parameter.data_type {
             CommandDataType::String => ParsedArgument::String(part.into()),
             CommandDataType::Target => ParsedArgument::Target(part.into()),
+            // Messages contain spaces, so they take up the rest of the input.
+            CommandDataType::Message | CommandDataType::RawText => {
+                ParsedArgument::String(std::iter::once(part).chain(parts.by_ref()).collect::<Vec<_>>().join(" "))
+            }
             CommandDataType::Int => {
                 let result = part.parse();
                 if let Ok(value) = result {
@@ -3,12 +3,12 @@ use std::{sync::{Arc, OnceLock, Weak}, time::Duration};
 use anyhow::Context as _;
 use dashmap::DashMap;
 use parking_lot::RwLock;
-use proto::bedrock::{AvailableCommands, Command, DynamicEnumAction, UpdateDynamicEnum};
+use proto::bedrock::{AvailableCommands, Command, CommandPermissionLevel, DynamicEnumAction, UpdateDynamicEnum};
 use tokio::sync::{mpsc, oneshot};
 use tokio_util::sync::CancellationToken;
 use util::Joinable;
 
-use crate::{instance::Instance, net::BedrockClient};
+use crate::{instance::Instance, net::{BedrockClient, PlayerData}};
 
 use super::{CommandHandler, Context, HandlerImpl, HandlerOutput, HandlerResult, ParseResult, ParsedCommand, ParserHandlerImpl};
 
@@ -35,7 +35,10 @@ pub struct Service {
 
     /// Up to date [`AvailableCommands`] packet that can be sent to new users.
     available: RwLock<AvailableCommands<'static>>,
-    registry: DashMap<String, Arc<dyn CommandHandler>>
+    /// Handlers indexed by both command names and aliases.
+    registry: DashMap<String, Arc<dyn CommandHandler>>,
+    /// Maps aliases to the name of the command they refer to.
+    aliases: DashMap<String, String>
 }
 
 impl Service {
@@ -46,13 +49,14 @@ impl Service {
             instance_token: token, sender,
             shutdown_token: CancellationToken::new(),
             registry: DashMap::new(),
+            aliases: DashMap::new(),
             dynamic_enums: DashMap::new(),
             available: RwLock::new(AvailableCommands::empty()),
             instance: OnceLock::new()
         });
 
         let clone = Arc::clone(&service);
-        tokio::spawn(async move {
+        util::task::spawn("command::service", async move {
             clone.service_job(receiver).await
         });
 
@@ -102,14 +106,20 @@ impl Service {
 
     /// Registers a raw handler with this service.
     /// 
-    /// This function returns an error if the service failed to notify clients 
-    /// of an updated command list.
+    /// If a command with the same name already exists, it is replaced. This can be used to override built-in commands.
+    /// 
+    /// This function returns an error if the name or one of the aliases of the command is already used by
+    /// another command or alias, or if the service failed to notify clients of an updated command list.
     pub fn register_handler(&self, handler: Arc<dyn CommandHandler>) -> anyhow::Result<()> {
         let structure = handler.structure();
+        self.check_conflicts(structure)?;
+        self.remove_command(&structure.name);
+
         self.available.write().commands.push(structure.clone());
 
         for alias in &structure.aliases {
             self.registry.insert(alias.clone(), Arc::clone(&handler));
+            self.aliases.insert(alias.clone(), structure.name.clone());
         }
 
         for overload in &structure.overloads {
@@ -122,7 +132,7 @@ impl Service {
         }
 
         self.registry.insert(structure.name.clone(), handler);
-        self.instance().clients().broadcast(self.available_commands())
+        self.broadcast_commands()
     }
 
     /// Registers a new command with the default syntax parser. 
@@ -165,13 +175,138 @@ impl Service {
         self.register_handler(handler)
     }
 
-    /// Removes a command from the registry and returns its handler.
+    /// Removes a command and all of its aliases from the registry and returns its handler.
     /// 
     /// This function does not accept command aliases, you should use the original name of the command.
-    pub fn unregister<S: AsRef<str>>(&self, name: S) -> Option<Arc<dyn CommandHandler>> {
-        todo!("Remove function from commands packet");
+    /// 
+    /// This function returns an error if the service failed to notify clients 
+    /// of an updated command list.
+    pub fn unregister<S: AsRef<str>>(&self, name: S) -> anyhow::Result<Option<Arc<dyn CommandHandler>>> {
+        let name = name.as_ref();
+        if self.aliases.contains_key(name) {
+            return Ok(None)
+        }
+
+        let handler = self.remove_command(name);
+        if handler.is_some() {
+            self.broadcast_commands()?;
+        }
+
+        Ok(handler)
+    }
+
+    /// Returns the handler of the given command.
+    /// 
+    /// Both command names and aliases are accepted.
+    pub fn get<S: AsRef<str>>(&self, name: S) -> Option<Arc<dyn CommandHandler>> {
+        self.registry.get(name.as_ref()).map(|handler| Arc::clone(handler.value()))
+    }
+
+    /// Whether a command or alias with the given name exists.
+    pub fn contains<S: AsRef<str>>(&self, name: S) -> bool {
+        self.registry.contains_key(name.as_ref())
+    }
+
+    /// Returns the name of the command that the given alias refers to.
+    pub fn resolve_alias<S: AsRef<str>>(&self, alias: S) -> Option<String> {
+        self.aliases.get(alias.as_ref()).map(|name| name.value().clone())
+    }
+
+    /// Adds an alias to an existing command.
+    /// 
+    /// This function returns an error if the command does not exist, if the alias is already in use
+    /// or if the service failed to notify clients of an updated command list.
+    pub fn add_alias<N: AsRef<str>, A: Into<String>>(&self, name: N, alias: A) -> anyhow::Result<()> {
+        let (name, alias) = (name.as_ref(), alias.into());
+        if self.aliases.contains_key(name) {
+            anyhow::bail!("Cannot add an alias to alias {name}, use the name of the command instead");
+        }
+
+        let Some(handler) = self.get(name) else {
+            anyhow::bail!("Command {name} does not exist");
+        };
+
+        if self.registry.contains_key(&alias) {
+            anyhow::bail!("A command or alias named {alias} already exists");
+        }
+
+        {
+            let mut available = self.available.write();
+            if let Some(command) = available.commands.iter_mut().find(|command| command.name == name) {
+                command.aliases.push(alias.clone());
+            }
+        }
+
+        self.registry.insert(alias.clone(), handler);
+        self.aliases.insert(alias, name.to_owned());
+        self.broadcast_commands()
+    }
+
+    /// Removes an alias, returning whether it existed.
+    /// 
+    /// This function returns an error if the service failed to notify clients 
+    /// of an updated command list.
+    pub fn remove_alias<S: AsRef<str>>(&self, alias: S) -> anyhow::Result<bool> {
+        if self.detach_alias(alias.as_ref()).is_none() {
+            return Ok(false)
+        }
+
+        self.broadcast_commands()?;
+        Ok(true)
+    }
+
+    /// Returns an iterator over the structures of all registered commands.
+    /// 
+    /// Aliases are not listed separately, they can be found in [`Command::aliases`].
+    /// The iterator operates on a snapshot, commands registered while iterating are not included.
+    pub fn commands(&self) -> impl Iterator<Item = Command> {
+        let commands = self.available.read().commands.to_vec();
+        commands.into_iter()
+    }
+
+    /// Ensures that registering `structure` does not take over the name of another command or alias.
+    fn check_conflicts(&self, structure: &Command) -> anyhow::Result<()> {
+        check_conflicts(structure, |name| self.resolve_alias(name), |name| self.registry.contains_key(name))
+    }
+
+    /// Removes an alias without notifying clients, returning the name of the command it referred to.
+    fn detach_alias(&self, alias: &str) -> Option<String> {
+        let (_, name) = self.aliases.remove(alias)?;
+        self.registry.remove(alias);
+
+        let mut available = self.available.write();
+        if let Some(command) = available.commands.iter_mut().find(|command| command.name == name) {
+            command.aliases.retain(|a| a != alias);
+        }
+        drop(available);
+
+        Some(name)
+    }
+
+    /// Removes a command and its aliases without notifying clients.
+    fn remove_command(&self, name: &str) -> Option<Arc<dyn CommandHandler>> {
+        let (_, handler) = self.registry.remove(name)?;
+
+        self.aliases.retain(|alias, target| {
+            let matches = target == name;
+            if matches {
+                self.registry.remove(alias);
+            }
+
+            !matches
+        });
+
+        let mut available = self.available.write();
+        let commands = available.commands.iter().filter(|command| command.name != name).cloned().collect::<Vec<_>>();
+        available.commands = commands.into();
+        drop(available);
+
+        Some(handler)
+    }
 
-        self.registry.remove(name.as_ref()).map(|(_, v)| v)
+    /// Sends the current command list to all clients.
+    fn broadcast_commands(&self) -> anyhow::Result<()> {
+        self.instance().clients().broadcast(self.available_commands())
     }
 
     /// Request execution of a command.
@@ -215,12 +350,14 @@ impl Service {
             chars.as_str()
         };
         
-        let Some(handler) = self.registry.get(command_name) else {
-            return Err(HandlerOutput {
-                message: format!("Unknown command {command_name}. Make sure the command exists and you have permission to use it.").into(),
-                parameters: Vec::new()
-            })
+        // Clone the handler so that the registry is not locked while the command runs.
+        // Handlers are allowed to modify the registry.
+        let Some(handler) = self.get(command_name) else {
+            return Err(unknown_command(command_name))
         };
+
+        let level = ctx.caller.player().map_or(CommandPermissionLevel::Normal, PlayerData::command_permission_level);
+        check_permission(handler.structure(), level)?;
         
         handler.call(command, ctx)
     }
@@ -236,7 +373,7 @@ impl Service {
                     };
 
                     let clone = Arc::clone(&self);
-                    tokio::spawn(async move {
+                    util::task::spawn("command::execute", async move {
                         let Some(instance) = clone.instance.get() else {
                             tracing::error!("Command service instance was not set");
                             return;
@@ -269,9 +406,57 @@ impl Service {
     }
 }
 
+/// Checks whether a caller with the given permission level is allowed to run the command.
+///
+/// Like the vanilla server, commands that the caller is not allowed to run are reported as unknown.
+pub fn check_permission(command: &Command, level: CommandPermissionLevel) -> Result<(), HandlerOutput> {
+    if level >= command.permission_level {
+        Ok(())
+    } else {
+        Err(unknown_command(&command.name))
+    }
+}
+
+/// Error returned for commands that do not exist or that the caller does not have permission for.
+fn unknown_command(name: &str) -> HandlerOutput {
+    HandlerOutput {
+        message: format!("Unknown command {name}. Make sure the command exists and you have permission to use it.").into(),
+        parameters: Vec::new()
+    }
+}
+
 impl Joinable for Service {
     async fn join(&self) -> anyhow::Result<()> {
         self.shutdown_token.cancelled().await;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Ensures that registering `structure` does not take over the name of another command or alias.
+///
+/// `resolve_alias` returns the command that an alias refers to and `is_registered` whether a command or alias
+/// with the given name exists. The command that is being replaced, if any, and its aliases do not count as conflicts.
+pub fn check_conflicts<A, R>(structure: &Command, resolve_alias: A, is_registered: R) -> anyhow::Result<()>
+where
+    A: Fn(&str) -> Option<String>,
+    R: Fn(&str) -> bool,
+{
+    let name = &structure.name;
+    if let Some(target) = resolve_alias(name) {
+        anyhow::bail!("Cannot register command {name}, it is already an alias of {target}");
+    }
+
+    for alias in &structure.aliases {
+        if alias == name {
+            anyhow::bail!("Command {name} cannot be an alias of itself");
+        }
+
+        match resolve_alias(alias) {
+            Some(target) if target != *name => anyhow::bail!("Alias {alias} of {name} is already an alias of {target}"),
+            None if is_registered(alias) => anyhow::bail!("Alias {alias} of {name} is already a command"),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
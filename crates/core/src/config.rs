@@ -2,16 +2,27 @@
 
 use std::{
     net::{SocketAddrV4, SocketAddrV6},
+    path::PathBuf,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use proto::bedrock::{CompressionAlgorithm, ThrottleSettings};
+use proto::raknet::RAKNET_VERSION;
+use raknet::{BackpressurePolicy, CompoundLimits, DEFAULT_ACK_INTERVAL, DEFAULT_KEEPALIVE_INTERVAL, DEFAULT_MAX_BATCH_FRAMES, DEFAULT_SESSION_TIMEOUT, MAX_ORDER_CHANNELS, SendWeights};
 use util::CowString;
 
+use crate::afk::AfkConfig;
+use crate::clock::{SharedClock, SystemClock};
+use crate::cooldown::CooldownConfig;
 use crate::instance::{Instance, IPV4_LOCAL_ADDR};
+use crate::level::seed::SeedPrivacy;
+use crate::net::PacketInterceptor;
+use crate::report::ReportBackend;
+use crate::restart::RestartConfig;
 
 /// Compression related settings.
 pub struct Compression {
@@ -19,12 +30,110 @@ pub struct Compression {
     pub algorithm: CompressionAlgorithm,
     /// Packets above this size threshold will be compressed.
     pub threshold: u16,
+    /// Whether to use preset dictionaries for clients that support them.
+    ///
+    /// This has no effect unless the `compression-dictionary` feature is enabled.
+    pub dictionaries: bool,
+}
+
+impl Compression {
+    /// Whether preset dictionaries are used for a client that supports the given dictionary version.
+    #[cfg(feature = "compression-dictionary")]
+    pub const fn use_dictionaries(&self, client_version: u32) -> bool {
+        self.dictionaries && client_version == crate::net::dictionary::DICTIONARY_VERSION
+    }
+}
+
+/// Network related settings.
+pub struct NetConfig {
+    /// Amount of unconnected packets that a single IP address is allowed to send per second.
+    ///
+    /// Unconnected packets are used for server list pings and the connection handshake.
+    /// Excess packets are silently ignored. Setting this to 0 disables the limit.
+    pub unconnected_rate: u32,
+    /// Amount of unconnected packets that an IP address can send at once before it is limited.
+    pub unconnected_burst: u32,
+    /// Whether clients have to echo a cookie during the handshake.
+    ///
+    /// This proves that the client owns its source address before any state is created for it,
+    /// which prevents the server from being used for reflection attacks.
+    pub handshake_cookies: bool,
+    /// Limits on the fragmented packets that are buffered for each client.
+    pub compounds: CompoundLimits,
+    /// Maximum amount of frames in a single batch. Clients that send larger batches are disconnected.
+    pub max_batch_frames: usize,
+    /// RakNet protocol versions that clients are allowed to connect with.
+    ///
+    /// Clients using any other version are sent an `IncompatibleProtocol` reply containing the newest of these versions.
+    pub raknet_versions: Vec<u8>,
+    /// Amount of order channels that clients are allowed to use.
+    ///
+    /// Bedrock clients only use the first channel. Frames on other channels are rejected as malformed.
+    pub order_channels: usize,
+    /// How often received packets are acknowledged.
+    ///
+    /// Shorter intervals reduce retransmissions on lossy connections at the cost of more ACK datagrams.
+    pub ack_interval: Duration,
+    /// Maximum amount of bytes per second that is sent to a single client.
+    ///
+    /// This prevents a single client downloading chunks or resource packs from saturating the uplink of the server.
+    /// Setting this to 0 disables the limit.
+    pub send_rate: u64,
+    /// Share of the send budget that each packet priority receives while a connection is congested.
+    pub send_weights: SendWeights,
+    /// Path of a Unix domain socket that the server additionally listens on.
+    ///
+    /// This is intended for proxies running on the same host, which avoids the overhead of the loopback
+    /// interface. Local sockets are only supported on Unix platforms.
+    pub local_socket: Option<PathBuf>,
+    /// How long a client can be unresponsive before it is disconnected.
+    pub session_timeout: Duration,
+    /// How long a client can be idle before the server pings it to check whether it is still alive.
+    ///
+    /// Setting this to 0 disables keepalive pings.
+    pub keepalive_interval: Duration,
+    /// Whether clients are allowed to move their connection to a new address.
+    ///
+    /// Clients that reconnect from a new address with the GUID of an existing connection continue that connection
+    /// once they have answered a verification ping. GUIDs are chosen by clients, so this is disabled by default.
+    /// Encrypted connections are never migrated, since the ping cannot prove that the client holds the key.
+    pub connection_migration: bool,
+    /// Amount of UDP sockets that each address is served by.
+    ///
+    /// With more than one socket, every socket is bound with `SO_REUSEPORT` and gets its own receiver task. The kernel
+    /// distributes clients over the sockets by their source address, which spreads the work of receiving packets
+    /// over multiple cores. This is only supported on Linux.
+    pub socket_shards: usize,
+    /// What happens to datagrams of a client that is not keeping up with the datagrams it is sent.
+    pub backpressure: BackpressurePolicy,
+    /// Directory that the traffic of every connection is written to.
+    ///
+    /// Each connection gets its own pcapng file that can be opened in Wireshark. This is meant for debugging
+    /// protocol issues and should not be enabled on busy servers.
+    pub capture_dir: Option<PathBuf>,
+    /// Whether game packets are also captured after they have been decrypted and decompressed.
+    ///
+    /// This has no effect unless [`capture_dir`](Self::capture_dir) is set.
+    pub capture_payloads: bool,
+    /// RakNet GUID of the server, a random GUID is generated if this is not set.
+    pub guid: Option<u64>,
+    /// Whether game packets sent to a client during a tick are combined into a single batch.
+    ///
+    /// This is what vanilla servers do. It reduces the framing, compression and encryption overhead of small
+    /// packets such as attribute and block updates.
+    pub packet_batching: bool,
 }
 
 /// Configuration of the level
 pub struct LevelConfig {
     /// The path to the level.
     pub path: String,
+    /// Which seed is sent to clients.
+    ///
+    /// The level is always generated using the real seed.
+    pub seed_privacy: SeedPrivacy,
+    /// Secret that is mixed into the seeds sent to clients, a random secret is generated if this is not set.
+    pub seed_secret: Option<u64>,
 }
 
 /// A callback for the message of the day.
@@ -52,10 +161,36 @@ pub struct Config {
     ///
     /// Any client that requests a higher render distance will be capped to this value.
     pub(super) max_render_distance: AtomicUsize,
+    /// MTU of the network interface that the server is listening on.
+    ///
+    /// Connections never use a larger MTU than this, which prevents IP fragmentation.
+    pub(super) max_mtu: u16,
+    /// Network settings.
+    pub(super) net: NetConfig,
+    /// AFK detection settings.
+    pub(super) afk: AfkConfig,
+    /// Chat and command cooldowns.
+    pub(super) cooldowns: CooldownConfig,
+    /// Automatic restart settings.
+    pub(super) restarts: RestartConfig,
     /// Level configuration
     pub(super) level: LevelConfig,
     /// Callback that generates a new message of the day.
     pub(super) motd_callback: MotdCallback,
+    /// Backend that reports are submitted to, reports are written to the level directory if this is not set.
+    pub(super) report_backend: Option<Arc<dyn ReportBackend>>,
+    /// Interceptors that incoming game packets are passed through, in order of registration.
+    pub(super) interceptors: Vec<Arc<dyn PacketInterceptor>>,
+    /// Clock that services read the time from.
+    pub(super) clock: SharedClock,
+    /// XUIDs of the players that are operators.
+    ///
+    /// Operators can run administration commands, all other players can only run commands
+    /// with the [`Normal`](proto::bedrock::CommandPermissionLevel::Normal) permission level.
+    pub(super) operators: Vec<u64>,
+    /// Webhook settings, notifications are only sent if this is set.
+    #[cfg(feature = "webhooks")]
+    pub(super) webhooks: Option<crate::webhook::WebhookConfig>,
 }
 
 impl Config {
@@ -67,16 +202,49 @@ impl Config {
             compression: Compression {
                 algorithm: CompressionAlgorithm::Flate,
                 threshold: 1,
+                dictionaries: false,
             },
             throttling: ThrottleSettings {
                 enabled: false,
                 scalar: 0.0,
                 threshold: 0,
             },
-            level: LevelConfig { path: String::from("resources\\level") },
+            max_mtu: raknet::MAX_MTU,
+            net: NetConfig {
+                unconnected_rate: 20,
+                unconnected_burst: 40,
+                handshake_cookies: true,
+                compounds: CompoundLimits::default(),
+                max_batch_frames: DEFAULT_MAX_BATCH_FRAMES,
+                raknet_versions: vec![RAKNET_VERSION],
+                order_channels: MAX_ORDER_CHANNELS,
+                ack_interval: DEFAULT_ACK_INTERVAL,
+                send_rate: 0,
+                send_weights: SendWeights::default(),
+                local_socket: None,
+                session_timeout: DEFAULT_SESSION_TIMEOUT,
+                keepalive_interval: DEFAULT_KEEPALIVE_INTERVAL,
+                connection_migration: false,
+                socket_shards: 1,
+                backpressure: BackpressurePolicy::default(),
+                capture_dir: None,
+                capture_payloads: false,
+                guid: None,
+                packet_batching: true,
+            },
+            afk: AfkConfig::default(),
+            cooldowns: CooldownConfig::default(),
+            restarts: RestartConfig::default(),
+            level: LevelConfig { path: String::from("resources\\level"), seed_privacy: SeedPrivacy::Hide, seed_secret: None },
             max_connections: AtomicUsize::new(10),
             max_render_distance: AtomicUsize::new(12),
             motd_callback: Box::new(|_| "Powered by Mirai".into()),
+            report_backend: None,
+            interceptors: Vec::new(),
+            clock: SystemClock::shared(),
+            operators: Vec::new(),
+            #[cfg(feature = "webhooks")]
+            webhooks: None,
         }
     }
 
@@ -104,6 +272,18 @@ impl Config {
         &self.compression
     }
 
+    /// Returns the MTU of the network interface.
+    #[inline]
+    pub const fn max_mtu(&self) -> u16 {
+        self.max_mtu
+    }
+
+    /// Returns the network settings.
+    #[inline]
+    pub const fn net(&self) -> &NetConfig {
+        &self.net
+    }
+
     /// Returns the current client throttling settings.
     #[inline]
     pub const fn throttling(&self) -> &ThrottleSettings {
@@ -141,9 +321,27 @@ impl Config {
         self.max_render_distance.store(max, Ordering::Relaxed);
     }
 
+    /// Returns the AFK detection settings.
+    #[inline]
+    pub const fn afk(&self) -> &AfkConfig {
+        &self.afk
+    }
+
+    /// Returns the chat and command cooldowns.
+    #[inline]
+    pub const fn cooldowns(&self) -> &CooldownConfig {
+        &self.cooldowns
+    }
+
     /// Returns the level configuration.
     #[inline]
     pub const fn level(&self) -> &LevelConfig {
         &self.level
     }
+
+    /// Whether the player with the given XUID is an operator.
+    #[inline]
+    pub fn is_operator(&self, xuid: u64) -> bool {
+        self.operators.contains(&xuid)
+    }
 }
@@ -0,0 +1,214 @@
+//! Per-player cooldowns and rate limits.
+//!
+//! Any subsystem that needs to limit how often a player can perform an action should use the
+//! shared [`Cooldowns`] service of the [`Instance`](crate::instance::Instance) rather than keeping its own timers.
+//!
+//! ```ignore
+//! if let Err(remaining) = instance.cooldowns().try_acquire(xuid, "myplugin:teleport", Duration::from_secs(30)) {
+//!     // Tell the player to wait `remaining`.
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use dashmap::DashMap;
+
+use crate::clock::{SharedClock, SystemClock};
+
+/// Cooldown identifiers used by the server itself.
+///
+/// Plugins should namespace their own identifiers to prevent collisions, i.e. `myplugin:action`.
+pub mod actions {
+    /// Sending a chat message.
+    pub const CHAT: &str = "mirai:chat";
+    /// Executing a command.
+    pub const COMMAND: &str = "mirai:command";
+    /// Performing an emote.
+    pub const EMOTE: &str = "mirai:emote";
+    /// Interacting with an entity.
+    pub const INTERACT: &str = "mirai:interact";
+    /// Travelling through a portal.
+    pub const PORTAL: &str = "mirai:portal";
+    /// Reporting another player.
+    pub const REPORT: &str = "mirai:report";
+}
+
+/// Cooldowns that the server applies to players.
+#[derive(Debug, Clone)]
+pub struct CooldownConfig {
+    /// Minimum time between two chat messages sent by the same player.
+    pub chat: Duration,
+    /// Minimum time between two commands executed by the same player.
+    pub command: Duration,
+}
+
+impl Default for CooldownConfig {
+    fn default() -> Self {
+        Self { chat: Duration::from_millis(250), command: Duration::from_millis(100) }
+    }
+}
+
+/// Cooldowns that last at least this long are written to disk when [`Cooldowns::save`] is called.
+/// Shorter cooldowns are not worth keeping across restarts.
+pub const PERSIST_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// Keeps track of cooldowns for every (player, action) pair.
+///
+/// Players are identified by their XUID so that cooldowns survive reconnects.
+/// Expiry times are tracked using a monotonic clock and are therefore unaffected by changes to the system time.
+#[derive(Debug)]
+pub struct Cooldowns {
+    entries: DashMap<u64, HashMap<String, Instant>>,
+    clock: SharedClock,
+}
+
+impl Cooldowns {
+    /// Creates an empty cooldown service.
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock::shared())
+    }
+
+    /// Creates an empty cooldown service that reads the time from the given clock.
+    pub fn with_clock(clock: SharedClock) -> Self {
+        Self { entries: DashMap::new(), clock }
+    }
+
+    /// Starts a cooldown for the given action if there is no cooldown active yet.
+    ///
+    /// # Errors
+    ///
+    /// If the action is still on cooldown, the remaining duration is returned instead.
+    pub fn try_acquire(&self, xuid: u64, action: &str, duration: Duration) -> Result<(), Duration> {
+        let now = self.clock.now();
+        let mut player = self.entries.entry(xuid).or_default();
+
+        let result = match player.get_mut(action) {
+            Some(expiry) if *expiry > now => Err(*expiry - now),
+            Some(expiry) => {
+                *expiry = now + duration;
+                Ok(())
+            }
+            None => {
+                player.insert(action.to_owned(), now + duration);
+                Ok(())
+            }
+        };
+        drop(player);
+
+        result
+    }
+
+    /// Starts a cooldown for the given action, overwriting any existing cooldown.
+    pub fn set(&self, xuid: u64, action: &str, duration: Duration) {
+        self.entries.entry(xuid).or_default().insert(action.to_owned(), self.clock.now() + duration);
+    }
+
+    /// Returns the remaining duration of the cooldown, or `None` if the action is not on cooldown.
+    pub fn remaining(&self, xuid: u64, action: &str) -> Option<Duration> {
+        let expiry = self.entries.get(&xuid)?.get(action).copied()?;
+        expiry.checked_duration_since(self.clock.now()).filter(|d| !d.is_zero())
+    }
+
+    /// Whether the given action is currently on cooldown.
+    pub fn is_active(&self, xuid: u64, action: &str) -> bool {
+        self.remaining(xuid, action).is_some()
+    }
+
+    /// Removes the cooldown for the given action.
+    pub fn reset(&self, xuid: u64, action: &str) {
+        if let Some(mut player) = self.entries.get_mut(&xuid) {
+            player.remove(action);
+        }
+    }
+
+    /// Removes all cooldowns of the given player.
+    pub fn reset_player(&self, xuid: u64) {
+        self.entries.remove(&xuid);
+    }
+
+    /// Removes all cooldowns that have expired.
+    pub fn purge_expired(&self) {
+        let now = self.clock.now();
+        self.entries.retain(|_, player| {
+            player.retain(|_, expiry| *expiry > now);
+            !player.is_empty()
+        });
+    }
+
+    /// Writes all cooldowns with more than [`PERSIST_THRESHOLD`] remaining to the given file.
+    ///
+    /// Because [`Instant`]s cannot be stored, the expiry times are converted to UNIX timestamps.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let now = self.clock.now();
+        let unix_now = self.clock.system_time().duration_since(UNIX_EPOCH)?;
+
+        let mut root = serde_json::Map::new();
+        for kv in &self.entries {
+            let mut player = serde_json::Map::new();
+            for (action, expiry) in kv.value() {
+                let remaining = expiry.saturating_duration_since(now);
+                if remaining >= PERSIST_THRESHOLD {
+                    player.insert(action.clone(), serde_json::Value::from((unix_now + remaining).as_secs()));
+                }
+            }
+
+            if !player.is_empty() {
+                root.insert(kv.key().to_string(), serde_json::Value::Object(player));
+            }
+        }
+
+        std::fs::write(path, serde_json::to_vec(&serde_json::Value::Object(root))?)?;
+        Ok(())
+    }
+
+    /// Loads cooldowns that were previously written using [`save`](Self::save).
+    ///
+    /// Cooldowns that expired while the server was offline are skipped.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::load_with_clock(path, SystemClock::shared())
+    }
+
+    /// Loads cooldowns like [`load`](Self::load), reading the time from the given clock.
+    pub fn load_with_clock<P: AsRef<Path>>(path: P, clock: SharedClock) -> anyhow::Result<Self> {
+        let now = clock.now();
+        let unix_now = clock.system_time().duration_since(UNIX_EPOCH)?.as_secs();
+
+        let data = std::fs::read(path)?;
+        let serde_json::Value::Object(root) = serde_json::from_slice(&data)? else {
+            anyhow::bail!("Cooldown file does not contain a JSON object");
+        };
+
+        let cooldowns = Self::with_clock(clock);
+        for (xuid, player) in root {
+            let xuid: u64 = xuid.parse()?;
+            let serde_json::Value::Object(player) = player else {
+                anyhow::bail!("Cooldowns of player {xuid} are not a JSON object");
+            };
+
+            let mut map = HashMap::with_capacity(player.len());
+            for (action, expiry) in player {
+                let Some(expiry) = expiry.as_u64() else {
+                    anyhow::bail!("Expiry time of cooldown {action} is not a valid timestamp");
+                };
+
+                if expiry > unix_now {
+                    map.insert(action, now + Duration::from_secs(expiry - unix_now));
+                }
+            }
+
+            if !map.is_empty() {
+                cooldowns.entries.insert(xuid, map);
+            }
+        }
+
+        Ok(cooldowns)
+    }
+}
+
+impl Default for Cooldowns {
+    fn default() -> Self {
+        Self::new()
+    }
+}
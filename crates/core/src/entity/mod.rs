@@ -0,0 +1,5 @@
+//! Server-side simulated entities.
+
+use util::glob_export;
+
+glob_export!(projectile);
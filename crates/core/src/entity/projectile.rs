@@ -0,0 +1,195 @@
+use util::Vector;
+
+/// Amount of ticks after which a projectile that has not hit anything is removed.
+const MAX_PROJECTILE_AGE: u32 = 1200;
+/// Damage dealt to the owner of an ender pearl when they are teleported.
+const ENDER_PEARL_DAMAGE: f32 = 5.0;
+/// Base damage of an arrow, which is multiplied by its speed on impact.
+const ARROW_BASE_DAMAGE: f32 = 2.0;
+
+/// The kinds of projectiles that are simulated by the server.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProjectileKind {
+    /// An arrow fired from a bow or crossbow.
+    Arrow,
+    /// A thrown snowball.
+    Snowball,
+    /// A thrown ender pearl, which teleports its owner on impact.
+    EnderPearl,
+}
+
+impl ProjectileKind {
+    /// Downwards acceleration in blocks per tick squared.
+    pub const fn gravity(self) -> f32 {
+        match self {
+            Self::Arrow => 0.05,
+            Self::Snowball | Self::EnderPearl => 0.03,
+        }
+    }
+
+    /// Fraction of the velocity that is lost every tick due to air resistance.
+    pub const fn drag(self) -> f32 {
+        0.01
+    }
+
+    /// Identifier of the entity type in the vanilla game.
+    pub const fn identifier(self) -> &'static str {
+        match self {
+            Self::Arrow => "minecraft:arrow",
+            Self::Snowball => "minecraft:snowball",
+            Self::EnderPearl => "minecraft:ender_pearl",
+        }
+    }
+}
+
+/// Queries that projectiles perform against the world to detect collisions.
+///
+/// The level implements this through [`LevelPhysics`](crate::level::physics::LevelPhysics). Both methods receive the segment that the projectile travelled along during the current tick
+/// and should return the *first* point of impact along that segment.
+pub trait PhysicsQuery {
+    /// Returns the point at which the segment first intersects a solid block.
+    fn intersect_block(&self, from: &Vector<f32, 3>, to: &Vector<f32, 3>) -> Option<Vector<f32, 3>>;
+
+    /// Returns the runtime ID of the first entity that the segment intersects, together with the point of impact.
+    ///
+    /// `exclude` contains the runtime ID of the entity that should be ignored, which is
+    /// used to prevent projectiles from hitting their owner right after being fired.
+    fn intersect_entity(&self, from: &Vector<f32, 3>, to: &Vector<f32, 3>, exclude: Option<u64>) -> Option<(u64, Vector<f32, 3>)>;
+}
+
+/// Effect that should be applied as the result of a projectile hitting something.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HitEffect {
+    /// Damages the given entity.
+    Damage {
+        /// Runtime ID of the damaged entity.
+        target: u64,
+        /// Amount of damage in half hearts.
+        amount: f32,
+    },
+    /// Teleports the given entity.
+    Teleport {
+        /// Runtime ID of the teleported entity.
+        target: u64,
+        /// Position to teleport to.
+        position: Vector<f32, 3>,
+    },
+}
+
+/// Result of simulating a single tick of a projectile.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectileState {
+    /// The projectile is still in flight.
+    Flying,
+    /// The projectile hit a block or entity and should be removed.
+    ///
+    /// The effects should be applied by the caller in order.
+    Hit(Vec<HitEffect>),
+    /// The projectile has existed for too long and should be removed.
+    Expired,
+}
+
+/// A projectile that has its trajectory simulated by the server.
+#[derive(Debug, Clone)]
+pub struct Projectile {
+    /// The type of projectile.
+    pub kind: ProjectileKind,
+    /// Runtime ID of the entity that fired the projectile, if any.
+    pub owner: Option<u64>,
+    /// Current position.
+    pub position: Vector<f32, 3>,
+    /// Current velocity in blocks per tick.
+    pub velocity: Vector<f32, 3>,
+    /// Amount of ticks that the projectile has existed for.
+    age: u32,
+}
+
+impl Projectile {
+    /// Creates a new projectile at the given position.
+    pub const fn new(kind: ProjectileKind, owner: Option<u64>, position: Vector<f32, 3>, velocity: Vector<f32, 3>) -> Self {
+        Self { kind, owner, position, velocity, age: 0 }
+    }
+
+    /// Amount of ticks that the projectile has existed for.
+    pub const fn age(&self) -> u32 {
+        self.age
+    }
+
+    /// Current speed in blocks per tick.
+    pub fn speed(&self) -> f32 {
+        let (x, y, z) = (self.velocity.x, self.velocity.y, self.velocity.z);
+        z.mul_add(z, x.mul_add(x, y * y)).sqrt()
+    }
+
+    /// Advances the simulation by a single tick.
+    ///
+    /// This moves the projectile along its trajectory and checks whether it hit a block or entity on the way.
+    pub fn tick<Q: PhysicsQuery>(&mut self, query: &Q) -> ProjectileState {
+        self.age += 1;
+        if self.age > MAX_PROJECTILE_AGE {
+            return ProjectileState::Expired;
+        }
+
+        let from = self.position.clone();
+        let to = Vector::from([from.x + self.velocity.x, from.y + self.velocity.y, from.z + self.velocity.z]);
+
+        // Entities are checked first, but only count if they are closer than the block that was hit.
+        let block_hit = query.intersect_block(&from, &to);
+        let entity_hit = query
+            .intersect_entity(&from, block_hit.as_ref().unwrap_or(&to), self.owner)
+            .filter(|(_, at)| block_hit.as_ref().map_or(true, |block| distance_sq(&from, at) <= distance_sq(&from, block)));
+
+        if let Some((target, at)) = entity_hit {
+            let effects = self.hit_effects(Some(target), &at);
+            self.position = at;
+            return ProjectileState::Hit(effects);
+        }
+
+        if let Some(at) = block_hit {
+            let effects = self.hit_effects(None, &at);
+            self.position = at;
+            return ProjectileState::Hit(effects);
+        }
+
+        self.position = to;
+
+        let retained = 1.0 - self.kind.drag();
+        self.velocity.x *= retained;
+        self.velocity.y = self.velocity.y.mul_add(retained, -self.kind.gravity());
+        self.velocity.z *= retained;
+
+        ProjectileState::Flying
+    }
+
+    /// Determines the effects of hitting something at the given position.
+    fn hit_effects(&self, target: Option<u64>, at: &Vector<f32, 3>) -> Vec<HitEffect> {
+        let mut effects = Vec::new();
+        match self.kind {
+            ProjectileKind::Arrow => {
+                if let Some(target) = target {
+                    effects.push(HitEffect::Damage { target, amount: (self.speed() * ARROW_BASE_DAMAGE).ceil() });
+                }
+            }
+            // Snowballs only knock back entities, which does not deal any damage.
+            ProjectileKind::Snowball => {
+                if let Some(target) = target {
+                    effects.push(HitEffect::Damage { target, amount: 0.0 });
+                }
+            }
+            ProjectileKind::EnderPearl => {
+                if let Some(owner) = self.owner {
+                    effects.push(HitEffect::Teleport { target: owner, position: at.clone() });
+                    effects.push(HitEffect::Damage { target: owner, amount: ENDER_PEARL_DAMAGE });
+                }
+            }
+        }
+
+        effects
+    }
+}
+
+#[inline]
+fn distance_sq(a: &Vector<f32, 3>, b: &Vector<f32, 3>) -> f32 {
+    let (dx, dy, dz) = (a.x - b.x, a.y - b.y, a.z - b.z);
+    dz.mul_add(dz, dx.mul_add(dx, dy * dy))
+}
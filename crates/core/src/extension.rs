@@ -0,0 +1,247 @@
+//! Cache of precompiled extension modules.
+//!
+//! Compiling a WebAssembly module is slow, so the compiled artifact is written to disk and reused whenever the same
+//! module is loaded again. Artifacts are keyed by the content hash of the module rather than its file name, which means
+//! that an updated module never reuses the artifact of the previous version.
+//!
+//! Artifacts are only valid for the runtime version that produced them. The cache directory records this version and
+//! is cleared when it changes. The directory is limited in size: once it grows too large, the artifacts that were used
+//! the longest ago are removed. Every artifact is stored together with its own digest, which is verified before it is
+//! handed to the runtime, so that a truncated or tampered file is recompiled instead of loaded.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs::{File, FileTimes};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+
+/// Name of the file in the cache directory that contains the runtime version.
+pub const VERSION_FILE: &str = "runtime-version";
+
+/// File extension of precompiled artifacts.
+const ARTIFACT_EXTENSION: &str = "cwasm";
+
+/// Size of the digest that precedes every artifact.
+const DIGEST_SIZE: usize = 32;
+
+/// Computes the cache key of a module.
+///
+/// This is the hex-encoded SHA-256 hash of the module, which also makes it safe to use as a file name.
+pub fn cache_key(module: &[u8]) -> String {
+    Sha256::digest(module).iter().fold(String::with_capacity(DIGEST_SIZE * 2), |mut key, byte| {
+        // Writing to a string cannot fail.
+        let _: std::fmt::Result = write!(key, "{byte:02x}");
+        key
+    })
+}
+
+/// An artifact stored in the cache.
+#[derive(Debug)]
+struct Entry {
+    /// Size of the artifact file in bytes.
+    size: u64,
+    /// Value of the use counter when the artifact was last stored or loaded.
+    last_used: u64,
+}
+
+/// Artifacts in the cache, by cache key.
+#[derive(Debug, Default)]
+struct Entries {
+    artifacts: HashMap<String, Entry>,
+    /// Incremented whenever an artifact is used, which orders them by recency.
+    counter: u64,
+}
+
+impl Entries {
+    /// Marks the artifact with the given key as used.
+    fn touch(&mut self, key: &str, size: u64) {
+        self.counter += 1;
+        self.artifacts.insert(key.to_owned(), Entry { size, last_used: self.counter });
+    }
+
+    /// Total size of all artifacts.
+    fn size(&self) -> u64 {
+        self.artifacts.values().map(|entry| entry.size).sum()
+    }
+}
+
+/// Stores precompiled extension modules on disk.
+#[derive(Debug)]
+pub struct ModuleCache {
+    directory: PathBuf,
+    max_size: u64,
+    entries: Mutex<Entries>,
+}
+
+impl ModuleCache {
+    /// Opens the cache in `directory`, creating it if it does not exist.
+    ///
+    /// `runtime_version` identifies the runtime that compiles the modules. If the cache was written by a different
+    /// version, all of its artifacts are removed. The artifacts are pruned to `max_size` bytes, with the modification
+    /// time of every artifact deciding which ones were used the longest ago.
+    pub fn open<P: AsRef<Path>>(directory: P, runtime_version: &str, max_size: u64) -> anyhow::Result<ModuleCache> {
+        let directory = directory.as_ref().to_owned();
+        std::fs::create_dir_all(&directory)?;
+
+        let version_path = directory.join(VERSION_FILE);
+        let stored_version = match std::fs::read_to_string(&version_path) {
+            Ok(version) => Some(version),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        let outdated = stored_version.as_deref() != Some(runtime_version);
+        if outdated {
+            tracing::info!("Extension runtime version changed to {runtime_version}, clearing module cache");
+        }
+
+        let mut artifacts = Vec::new();
+        for dir_entry in std::fs::read_dir(&directory)? {
+            let dir_entry = dir_entry?;
+            let path = dir_entry.path();
+            if path.file_name().is_some_and(|name| name == VERSION_FILE) {
+                continue
+            }
+
+            // Leftover temporary files are removed along with outdated artifacts.
+            let key = path.file_stem().and_then(|stem| stem.to_str()).map(str::to_owned);
+            let is_artifact = path.extension().is_some_and(|extension| extension == ARTIFACT_EXTENSION);
+            match key {
+                Some(key) if is_artifact && !outdated => {
+                    let metadata = dir_entry.metadata()?;
+                    artifacts.push((metadata.modified()?, key, metadata.len()));
+                }
+                _ if dir_entry.file_type()?.is_file() => std::fs::remove_file(&path)?,
+                _ => {}
+            }
+        }
+
+        if outdated {
+            std::fs::write(&version_path, runtime_version)?;
+        }
+
+        let mut entries = Entries::default();
+        artifacts.sort_unstable();
+        for (_, key, size) in artifacts {
+            entries.touch(&key, size);
+        }
+
+        let cache = ModuleCache { directory, max_size, entries: Mutex::new(entries) };
+        cache.prune()?;
+
+        Ok(cache)
+    }
+
+    /// Loads the precompiled artifact of `module`.
+    ///
+    /// Returns `None` if the module has not been cached yet. Artifacts that fail verification are removed and also
+    /// reported as missing, after which the module should be compiled and stored again.
+    pub fn load(&self, module: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let key = cache_key(module);
+        let path = self.artifact_path(&key);
+
+        let mut data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                self.entries.lock().artifacts.remove(&key);
+                return Ok(None)
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let verified = data.len() >= DIGEST_SIZE && Sha256::digest(&data[DIGEST_SIZE..]).as_slice() == &data[..DIGEST_SIZE];
+        if !verified {
+            tracing::warn!("Precompiled module {key} is corrupted, it will be recompiled");
+
+            self.entries.lock().artifacts.remove(&key);
+            std::fs::remove_file(&path)?;
+            return Ok(None)
+        }
+
+        // The modification time orders the artifacts by recency when the cache is opened again.
+        File::options().write(true).open(&path)?.set_times(FileTimes::new().set_modified(SystemTime::now()))?;
+        self.entries.lock().touch(&key, data.len() as u64);
+
+        data.drain(..DIGEST_SIZE);
+        Ok(Some(data))
+    }
+
+    /// Stores the precompiled `artifact` of `module` and prunes the cache if it has grown too large.
+    ///
+    /// Artifacts that are larger than the cache itself are not stored.
+    pub fn store(&self, module: &[u8], artifact: &[u8]) -> anyhow::Result<()> {
+        let size = (DIGEST_SIZE + artifact.len()) as u64;
+        if size > self.max_size {
+            tracing::debug!("Precompiled module of {size} bytes does not fit in the module cache");
+            return Ok(())
+        }
+
+        let key = cache_key(module);
+        let mut data = Vec::with_capacity(DIGEST_SIZE + artifact.len());
+        data.extend_from_slice(&Sha256::digest(artifact));
+        data.extend_from_slice(artifact);
+
+        // Write to a temporary file first so that an interrupted write never leaves a partial artifact behind.
+        let temporary = self.directory.join(format!("{key}.tmp"));
+        std::fs::write(&temporary, &data)?;
+        std::fs::rename(&temporary, self.artifact_path(&key))?;
+
+        self.entries.lock().touch(&key, size);
+        self.prune()?;
+
+        Ok(())
+    }
+
+    /// Removes the least recently used artifacts until the cache fits within its maximum size.
+    ///
+    /// Returns the amount of bytes that were removed.
+    pub fn prune(&self) -> anyhow::Result<u64> {
+        let mut entries = self.entries.lock();
+
+        let mut size = entries.size();
+        let mut removed = 0;
+        while size > self.max_size {
+            let Some(key) = entries.artifacts.iter().min_by_key(|(_, entry)| entry.last_used).map(|(key, _)| key.clone()) else {
+                break
+            };
+
+            if let Some(entry) = entries.artifacts.remove(&key) {
+                size -= entry.size;
+                removed += entry.size;
+            }
+
+            match std::fs::remove_file(self.artifact_path(&key)) {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        drop(entries);
+
+        if removed > 0 {
+            tracing::debug!("Pruned {removed} bytes from the module cache");
+        }
+
+        Ok(removed)
+    }
+
+    /// Whether an artifact of `module` is stored.
+    ///
+    /// This does not verify the artifact, use [`load`](Self::load) for that.
+    pub fn contains(&self, module: &[u8]) -> bool {
+        self.entries.lock().artifacts.contains_key(&cache_key(module))
+    }
+
+    /// Total size of all stored artifacts in bytes.
+    pub fn size(&self) -> u64 {
+        self.entries.lock().size()
+    }
+
+    /// Path of the artifact with the given cache key.
+    fn artifact_path(&self, key: &str) -> PathBuf {
+        self.directory.join(key).with_extension(ARTIFACT_EXTENSION)
+    }
+}
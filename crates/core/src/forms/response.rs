@@ -10,7 +10,7 @@ use dashmap::DashMap;
 use proto::bedrock::{CancelReason, FormRequest, FormResponseData};
 use tokio::sync::oneshot;
 
-use crate::{forms::Content, net::BedrockClient};
+use crate::{forms::Content, net::{BedrockClient, Feature}};
 
 use super::{FormDesc, SubmittableForm};
 
@@ -233,14 +233,26 @@ impl Subscriber {
     }
 
     /// Submits a form to the user and returns a receiver that will receive the response.
+    ///
+    /// This fails if the kind of form has been disabled for the capabilities of the user.
     pub fn subscribe<F: SubmittableForm>(&self, user: &BedrockClient, form: F) -> anyhow::Result<oneshot::Receiver<Response>> {
         let data = serde_json::to_string(&form)?;
-        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let desc = form.into_desc();
+
+        let feature = match desc {
+            FormDesc::Custom(_) => Feature::CustomForms,
+            FormDesc::Menu => Feature::MenuForms,
+            FormDesc::Modal => Feature::ModalForms,
+        };
+        if !user.is_feature_enabled(feature) {
+            anyhow::bail!("{feature:?} have been disabled for this client");
+        }
 
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
         user.send(FormRequest { data: &data, id })?;
 
         let (sender, receiver) = oneshot::channel();
-        self.subscribed.insert(id, (sender, form.into_desc()));
+        self.subscribed.insert(id, (sender, desc));
 
         Ok(receiver)
     }
@@ -3,32 +3,36 @@
 use anyhow::Context;
 
 use parking_lot::RwLock;
-use raknet::RakNetCreateDescription;
+use raknet::{BackpressurePolicy, Capture, CompoundLimits, DatagramBatch, Job, JobScheduler, ListenOptions, OfflineHandshake, RakNetCreateDescription, ReceiveBatch, SendWeights, Socket};
+use tokio::sync::broadcast;
 use tokio::task::JoinHandle;
 
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
-use tokio::net::UdpSocket;
 
 use tokio_util::sync::CancellationToken;
 
-use util::{CowString, Deserialize, Joinable, RVec, ReserveTo, Serialize};
+use util::{CowString, Joinable, RVec};
 
+use crate::afk::{AfkAction, AfkEvent};
 use crate::command::{self, HandlerOutput, HandlerResult, ParsedCommand};
-use crate::config::Config;
-use crate::net::{Clients, ForwardablePacket};
-use level::{BlockStates, CreativeItems, ItemNetworkIds};
+use crate::config::{Config, NetConfig};
+use crate::clock::SharedClock;
+use crate::cooldown::Cooldowns;
+use crate::item::Registries;
+use crate::level::seed::SeedPrivacy;
+use crate::net::{Clients, FeatureGates, ForwardablePacket, PacketInterceptor, QuitEvent};
+use crate::net::traffic::TrafficStats;
+use crate::report::{FileBackend, Report, ReportBackend};
+use crate::restart::{RestartConfig, RestartScheduler};
 use proto::bedrock::{
     Command, CommandDataType, CommandEnum, CommandOverload, CommandParameter, CommandPermissionLevel, CreditsStatus, CreditsUpdate, MovePlayer,
     MovementMode, TeleportCause, CLIENT_VERSION_STRING, PROTOCOL_VERSION,
 };
-use proto::raknet::{
-    IncompatibleProtocol, OpenConnectionReply1, OpenConnectionReply2, OpenConnectionRequest1, OpenConnectionRequest2, UnconnectedPing,
-    UnconnectedPong, RAKNET_VERSION,
-};
 
 /// Local IPv4 address
 pub const IPV4_LOCAL_ADDR: Ipv4Addr = Ipv4Addr::UNSPECIFIED;
@@ -36,12 +40,26 @@ pub const IPV4_LOCAL_ADDR: Ipv4Addr = Ipv4Addr::UNSPECIFIED;
 pub const IPV6_LOCAL_ADDR: Ipv6Addr = Ipv6Addr::UNSPECIFIED;
 /// Size of the UDP receive buffer.
 const RECV_BUF_SIZE: usize = 2048;
+/// Maximum amount of datagrams that are received per wakeup of the network receiver.
+const RECV_BATCH_SIZE: usize = 32;
 /// Refresh rate of the server's metadata.
 /// This data is displayed in the server menu.
 const METADATA_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+/// Name of the file in the level directory that long cooldowns are persisted to.
+const COOLDOWN_FILE: &str = "cooldowns.json";
+/// How often expired cooldowns are removed.
+const COOLDOWN_PURGE_INTERVAL: Duration = Duration::from_secs(60);
+/// Amount of AFK events that subscribers can lag behind before events are dropped.
+const AFK_EVENT_CAPACITY: usize = 16;
+/// Amount of quit events that subscribers can lag behind before events are dropped.
+const QUIT_EVENT_CAPACITY: usize = 16;
+/// Name of the file in the level directory that reports are written to by default.
+const REPORT_FILE: &str = "reports.jsonl";
+/// Amount of report events that subscribers can lag behind before events are dropped.
+const REPORT_EVENT_CAPACITY: usize = 16;
 
 /// Configures and instance and constructs it.
-pub struct InstanceBuilder(Config);
+pub struct InstanceBuilder(pub(crate) Config);
 
 impl InstanceBuilder {
     /// Creates a new instance builder. This is the same as calling [`builder`](Instance::builder) on [`Instance`].
@@ -55,6 +73,51 @@ impl InstanceBuilder {
         self
     }
 
+    /// Sets which seed is sent to clients.
+    ///
+    /// By default clients receive a seed of 0 so that the real seed cannot be used to locate structures or bases.
+    pub const fn seed_privacy(mut self, privacy: SeedPrivacy) -> InstanceBuilder {
+        self.0.level.seed_privacy = privacy;
+        self
+    }
+
+    /// Sets the secret that is mixed into the seeds sent to clients.
+    ///
+    /// By default a new secret is generated on every start, which changes the seeds that clients receive with
+    /// [`SeedPrivacy::PerClient`]. A persisted secret keeps them stable across restarts.
+    pub const fn seed_secret(mut self, secret: u64) -> InstanceBuilder {
+        self.0.level.seed_secret = Some(secret);
+        self
+    }
+
+    /// Sets the name of the server.
+    ///
+    /// This appears at the top of the player list and as the title for LAN broadcasted games.
+    pub fn name<S: Into<CowString<'static>>>(mut self, name: S) -> InstanceBuilder {
+        self.0.name = name.into();
+        self
+    }
+
+    /// Sets the maximum amount of players that can be connected at the same time.
+    pub fn max_connections(self, max: usize) -> InstanceBuilder {
+        self.0.set_max_connections(max);
+        self
+    }
+
+    /// Sets the maximum render distance that clients are allowed to use.
+    pub fn max_render_distance(self, max: usize) -> InstanceBuilder {
+        self.0.set_max_render_distance(max);
+        self
+    }
+
+    /// Sets the RakNet GUID that the server advertises.
+    ///
+    /// By default a new GUID is generated on every start.
+    pub const fn raknet_guid(mut self, guid: u64) -> InstanceBuilder {
+        self.0.net.guid = Some(guid);
+        self
+    }
+
     /// Sets the IPv4 address of the instance.
     pub fn ipv4_addr<A: Into<SocketAddrV4>>(mut self, addr: A) -> InstanceBuilder {
         self.0.ipv4_addr = addr.into();
@@ -67,26 +130,274 @@ impl InstanceBuilder {
         self
     }
 
+    /// Sets the MTU of the network interface that the server is listening on.
+    ///
+    /// This is clamped to the range supported by RakNet. Use this if the server is behind a link with
+    /// a small MTU, such as a VPN or tunnel.
+    pub const fn max_mtu(mut self, mtu: u16) -> InstanceBuilder {
+        self.0.max_mtu = mtu;
+        self
+    }
+
+    /// Sets the amount of unconnected packets that a single IP address is allowed to send per second,
+    /// and how many it can send at once.
+    ///
+    /// A rate of 0 disables the limit.
+    pub const fn unconnected_rate_limit(mut self, rate: u32, burst: u32) -> InstanceBuilder {
+        self.0.net.unconnected_rate = rate;
+        self.0.net.unconnected_burst = burst;
+        self
+    }
+
+    /// Sets whether clients have to echo a cookie during the RakNet handshake.
+    ///
+    /// This is enabled by default and should only be disabled for clients that do not support cookies.
+    pub const fn handshake_cookies(mut self, enabled: bool) -> InstanceBuilder {
+        self.0.net.handshake_cookies = enabled;
+        self
+    }
+
+    /// Sets the limits on fragmented packets that are buffered for each client.
+    pub const fn compound_limits(mut self, limits: CompoundLimits) -> InstanceBuilder {
+        self.0.net.compounds = limits;
+        self
+    }
+
+    /// Sets the RakNet protocol versions that clients are allowed to connect with.
+    ///
+    /// By default only [`RAKNET_VERSION`] is accepted.
+    pub fn raknet_versions<I: IntoIterator<Item = u8>>(mut self, versions: I) -> InstanceBuilder {
+        self.0.net.raknet_versions = versions.into_iter().collect();
+        self
+    }
+
+    /// Sets the backend that reports are submitted to.
+    ///
+    /// By default, reports are appended to a file in the level directory.
+    pub fn report_backend<B: ReportBackend + 'static>(mut self, backend: B) -> InstanceBuilder {
+        self.0.report_backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Adds an interceptor that sees every game packet sent by clients before it is handled.
+    ///
+    /// Interceptors run in the order that they were added in.
+    pub fn interceptor<I: PacketInterceptor + 'static>(mut self, interceptor: I) -> InstanceBuilder {
+        self.0.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Sets whether preset compression dictionaries are used for clients that support them.
+    ///
+    /// This is disabled by default and has no effect unless the `compression-dictionary` feature is enabled.
+    pub const fn compression_dictionaries(mut self, enabled: bool) -> InstanceBuilder {
+        self.0.compression.dictionaries = enabled;
+        self
+    }
+
+    /// Sets the clock that services such as cooldowns and AFK detection read the time from.
+    ///
+    /// This is intended for tests, which can use a [`ManualClock`](crate::clock::ManualClock) to control the passage
+    /// of time. By default the [`SystemClock`](crate::clock::SystemClock) is used.
+    pub fn clock(mut self, clock: SharedClock) -> InstanceBuilder {
+        self.0.clock = clock;
+        self
+    }
+
+    /// Makes the player with the given XUID an operator.
+    ///
+    /// Operators are allowed to run administration commands such as `stop` and `kick`.
+    /// By default, nobody is an operator.
+    pub fn operator(mut self, xuid: u64) -> InstanceBuilder {
+        self.0.operators.push(xuid);
+        self
+    }
+
+    /// Sets when the server restarts automatically and how players are warned.
+    ///
+    /// By default the server only restarts when an operator schedules a restart using the `restart` command.
+    pub fn restarts(mut self, config: RestartConfig) -> InstanceBuilder {
+        self.0.restarts = config;
+        self
+    }
+
+    /// Enables posting notifications about the server to webhooks.
+    #[cfg(feature = "webhooks")]
+    pub fn webhooks(mut self, config: crate::webhook::WebhookConfig) -> InstanceBuilder {
+        self.0.webhooks = Some(config);
+        self
+    }
+
+    /// Sets the maximum amount of frames in a single batch.
+    ///
+    /// Clients that send batches with more frames are disconnected.
+    pub const fn max_batch_frames(mut self, count: usize) -> InstanceBuilder {
+        self.0.net.max_batch_frames = count;
+        self
+    }
+
+    /// Sets the amount of order channels that clients are allowed to use.
+    ///
+    /// The count is clamped to the range `1..=MAX_ORDER_CHANNELS`.
+    pub const fn order_channels(mut self, count: usize) -> InstanceBuilder {
+        self.0.net.order_channels = count;
+        self
+    }
+
+    /// Sets how often received packets are acknowledged.
+    ///
+    /// The interval is rounded down to a multiple of the RakNet tick interval of 50 ms.
+    pub const fn ack_interval(mut self, interval: Duration) -> InstanceBuilder {
+        self.0.net.ack_interval = interval;
+        self
+    }
+
+    /// Sets the maximum amount of bytes per second that is sent to a single client.
+    ///
+    /// A rate of 0 disables the limit, which is the default.
+    pub const fn send_rate(mut self, bytes_per_second: u64) -> InstanceBuilder {
+        self.0.net.send_rate = bytes_per_second;
+        self
+    }
+
+    /// Sets the share of the send budget that each packet priority receives while a connection is congested.
+    ///
+    /// The default weights are 4, 2 and 1 for high, medium and low priority packets.
+    pub const fn send_weights(mut self, weights: SendWeights) -> InstanceBuilder {
+        self.0.net.send_weights = weights;
+        self
+    }
+
+    /// Sets how long a client can be unresponsive before it is disconnected.
+    ///
+    /// The default is 5 seconds.
+    pub const fn session_timeout(mut self, timeout: Duration) -> InstanceBuilder {
+        self.0.net.session_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a client can be idle before the server pings it to check whether it is still alive.
+    ///
+    /// This should be well below the session timeout. An interval of 0 disables keepalive pings.
+    pub const fn keepalive_interval(mut self, interval: Duration) -> InstanceBuilder {
+        self.0.net.keepalive_interval = interval;
+        self
+    }
+
+    /// Sets whether clients can move their connection to a new address.
+    ///
+    /// This allows mobile players to survive switching between networks. It is disabled by default.
+    pub const fn connection_migration(mut self, enabled: bool) -> InstanceBuilder {
+        self.0.net.connection_migration = enabled;
+        self
+    }
+
+    /// Sets the amount of UDP sockets that each address is served by.
+    ///
+    /// Servers with thousands of players can use this to receive packets on multiple cores.
+    /// Sharding is only supported on Linux and disabled by default.
+    pub const fn socket_shards(mut self, count: usize) -> InstanceBuilder {
+        self.0.net.socket_shards = count;
+        self
+    }
+
+    /// Sets what happens to datagrams of a client whose forward queue is full.
+    ///
+    /// By default, datagrams wait briefly for room and clients that stay backed up for too long are disconnected.
+    pub const fn backpressure(mut self, policy: BackpressurePolicy) -> InstanceBuilder {
+        self.0.net.backpressure = policy;
+        self
+    }
+
+    /// Sets whether game packets sent during a tick are combined into a single batch.
+    ///
+    /// This is enabled by default. Disabling it sends every packet in its own batch.
+    pub const fn packet_batching(mut self, enabled: bool) -> InstanceBuilder {
+        self.0.net.packet_batching = enabled;
+        self
+    }
+
+    /// Writes the traffic of every connection to a pcapng file in the given directory.
+    ///
+    /// If `payloads` is enabled, game packets are also recorded after decryption.
+    pub fn capture<P: Into<PathBuf>>(mut self, dir: P, payloads: bool) -> InstanceBuilder {
+        self.0.net.capture_dir = Some(dir.into());
+        self.0.net.capture_payloads = payloads;
+        self
+    }
+
+    /// Additionally listens on a Unix domain socket at the given path.
+    ///
+    /// This allows a proxy on the same host to connect without going through the loopback interface.
+    /// Building the instance fails on platforms that do not support Unix domain sockets.
+    pub fn local_socket<P: Into<PathBuf>>(mut self, path: P) -> InstanceBuilder {
+        self.0.net.local_socket = Some(path.into());
+        self
+    }
+
+    /// Sets how long players have to be idle before they are marked as AFK.
+    ///
+    /// `None` disables AFK detection.
+    pub const fn afk_timeout(mut self, timeout: Option<Duration>) -> InstanceBuilder {
+        self.0.afk.timeout = timeout;
+        self
+    }
+
+    /// Sets what happens to players that are marked as AFK.
+    pub const fn afk_action(mut self, action: AfkAction) -> InstanceBuilder {
+        self.0.afk.action = action;
+        self
+    }
+
+    /// Sets the minimum time between two chat messages sent by the same player.
+    ///
+    /// Messages sent during the cooldown are dropped. [`Duration::ZERO`] disables the cooldown.
+    pub const fn chat_cooldown(mut self, cooldown: Duration) -> InstanceBuilder {
+        self.0.cooldowns.chat = cooldown;
+        self
+    }
+
+    /// Sets the minimum time between two commands executed by the same player.
+    ///
+    /// Commands executed during the cooldown are ignored. [`Duration::ZERO`] disables the cooldown.
+    pub const fn command_cooldown(mut self, cooldown: Duration) -> InstanceBuilder {
+        self.0.cooldowns.command = cooldown;
+        self
+    }
+
     /// Produces an [`Instance`] with the configured options, consuming the builder.
-    pub async fn build(self) -> anyhow::Result<Arc<Instance>> {
+    pub async fn build(mut self) -> anyhow::Result<Arc<Instance>> {
         tracing::info!(
             "Mirai server v{} (rev. {}) built for MCBE {CLIENT_VERSION_STRING} (prot. {PROTOCOL_VERSION})",
             Instance::SERVER_VERSION,
             Instance::GIT_REV
         );
 
-        let item_network_ids = ItemNetworkIds::new()?;
-        let block_states = BlockStates::new()?;
-        let creative_items = CreativeItems::new(&item_network_ids, &block_states)?;
+        let registries = Registries::vanilla()?;
+
+        let shards = self.0.net.socket_shards;
+        let ipv4_sockets = raknet::bind_sharded(self.0.ipv4_addr.into(), shards)
+            .await
+            .context("Unable to create IPv4 UDP socket")?;
+        let ipv6_sockets = match self.0.ipv6_addr {
+            Some(addr) => raknet::bind_sharded(addr.into(), shards).await.context("Unable to create IPv6 UDP socket")?,
+            None => Vec::new(),
+        };
 
-        let ipv4_socket = UdpSocket::bind(self.0.ipv4_addr).await.context("Unable to create IPv4 UDP socket")?;
-        let ipv6_socket = match self.0.ipv6_addr {
-            Some(addr) => Some(UdpSocket::bind(addr).await.context("Unable to create IPv6 UDP socket")?),
+        #[cfg(unix)]
+        let local_socket = match &self.0.net.local_socket {
+            Some(path) => Some(Socket::from(raknet::LocalSocket::bind(path).context("Unable to create local socket")?)),
+            None => None,
+        };
+        #[cfg(not(unix))]
+        let local_socket = match &self.0.net.local_socket {
+            Some(_) => anyhow::bail!("Local sockets are only supported on Unix platforms"),
             None => None,
         };
 
-        let ipv4_socket = Arc::new(ipv4_socket);
-        let ipv6_socket = ipv6_socket.map(Arc::new);
+        let ipv4_sockets = ipv4_sockets.into_iter().map(|s| Arc::new(Socket::from(s))).collect();
+        let ipv6_sockets = ipv6_sockets.into_iter().map(|s| Arc::new(Socket::from(s))).collect();
+        let local_socket = local_socket.map(Arc::new);
 
         let running_token = CancellationToken::new();
 
@@ -94,28 +405,58 @@ impl InstanceBuilder {
         let level_service = crate::level::service::Service::new(crate::level::service::ServiceOptions {
             instance_token: running_token.clone(),
             level_path: self.0.level.path.clone(),
+            seed_secret: self.0.level.seed_secret,
         })?;
 
         let user_map = Arc::new(Clients::new(Arc::clone(&command_service), Arc::clone(&level_service)));
+        let cooldown_path = Path::new(&self.0.level.path).join(COOLDOWN_FILE);
+        let cooldowns = if cooldown_path.exists() {
+            Cooldowns::load_with_clock(&cooldown_path, Arc::clone(&self.0.clock)).context("Unable to load persisted cooldowns")?
+        } else {
+            Cooldowns::with_clock(Arc::clone(&self.0.clock))
+        };
+
+        let report_backend = self.0.report_backend.take().unwrap_or_else(|| {
+            Arc::new(FileBackend::new(Path::new(&self.0.level.path).join(REPORT_FILE)))
+        });
+
+        let handshake = OfflineHandshake::new(&ListenOptions {
+            guid: self.0.net.guid.unwrap_or_else(rand::random),
+            versions: self.0.net.raknet_versions.clone(),
+            max_mtu: self.0.max_mtu,
+            handshake_cookies: self.0.net.handshake_cookies,
+            unconnected_rate: self.0.net.unconnected_rate,
+            unconnected_burst: self.0.net.unconnected_burst,
+            ..ListenOptions::default()
+        });
         let user_map = Arc::new(Clients::new(Arc::clone(&command_service), Arc::clone(&level_service)));
         let instance = Instance {
-            ipv4_socket,
-            ipv6_socket,
+            ipv4_sockets,
+            ipv6_sockets,
+            local_socket,
             clients: user_map,
             command_service,
             level_service,
+            cooldowns,
+            traffic: TrafficStats::new(Arc::clone(&self.0.clock)),
+            restarts: RestartScheduler::new(self.0.restarts.clone(), Arc::clone(&self.0.clock)),
+            restart_requested: AtomicBool::new(false),
+            feature_gates: FeatureGates::new(),
+            handshake,
+            afk_events: broadcast::channel(AFK_EVENT_CAPACITY).0,
+            quit_events: broadcast::channel(QUIT_EVENT_CAPACITY).0,
+            report_events: broadcast::channel(REPORT_EVENT_CAPACITY).0,
+            report_backend,
+            jobs: JobScheduler::new(running_token.clone()),
             config: self.0,
 
-            raknet_guid: rand::random(),
             current_motd: RwLock::new(String::new()),
             running_token,
             shutdown_token: CancellationToken::new(),
             startup_token: CancellationToken::new(),
 
             // Data
-            creative_items,
-            block_states,
-            item_network_ids,
+            registries: RwLock::new(Arc::new(registries)),
         };
 
         let instance = Arc::new(instance);
@@ -138,16 +479,40 @@ impl Default for InstanceBuilder {
 /// For example, the [`SessionManager`] is the first thing that is shut down to kick all the players from
 /// the server before continuing with the shutdown.
 pub struct Instance {
-    /// IPv4 UDP socket
-    ipv4_socket: Arc<UdpSocket>,
-    /// IPv6 UDP socket.
-    ipv6_socket: Option<Arc<UdpSocket>>,
+    /// IPv4 UDP sockets, this contains multiple sockets if sharding is enabled.
+    ipv4_sockets: Vec<Arc<Socket>>,
+    /// IPv6 UDP sockets, this is empty if IPv6 is disabled.
+    ipv6_sockets: Vec<Arc<Socket>>,
+    /// Unix domain socket used by proxies on the same host.
+    local_socket: Option<Arc<Socket>>,
     /// Service that manages all player sessions.
     clients: Arc<Clients>,
     /// Keeps track of all available commands.
     command_service: Arc<crate::command::Service>,
     /// Keeps track of the level state.
     level_service: Arc<crate::level::service::Service>,
+    /// Per-player cooldowns shared by all subsystems.
+    cooldowns: Cooldowns,
+    /// Packets sent and received by all sessions combined.
+    traffic: TrafficStats,
+    /// Keeps track of upcoming restarts.
+    restarts: RestartScheduler,
+    /// Whether the server should be started again after it has shut down.
+    restart_requested: AtomicBool,
+    /// Features that are disabled for clients with certain capabilities.
+    feature_gates: FeatureGates,
+    /// Answers unconnected packets and negotiates new connections.
+    handshake: OfflineHandshake,
+    /// Notifies subscribers of players becoming AFK or returning.
+    afk_events: broadcast::Sender<AfkEvent>,
+    /// Notifies subscribers of players leaving the server.
+    quit_events: broadcast::Sender<QuitEvent>,
+    /// Notifies subscribers of reports filed against players.
+    report_events: broadcast::Sender<Report>,
+    /// Backend that reports are submitted to.
+    report_backend: Arc<dyn ReportBackend>,
+    /// Background jobs, these are stopped in dependency order when the server shuts down.
+    jobs: Arc<JobScheduler>,
     /// Keeps track of the current configuration of the server.
     config: Config,
     /// Cancelled when the server has started up successfully.
@@ -156,14 +521,11 @@ pub struct Instance {
     running_token: CancellationToken,
     /// Cancelled when the server has fully shut down.
     shutdown_token: CancellationToken,
-    /// The RakNet GUID of the server. This is literally just randomly generated on startup.
-    raknet_guid: u64,
     /// The current message of the day. Update every [`METADATA_REFRESH_INTERVAL`] seconds.
     current_motd: RwLock<String>,
 
-    pub creative_items: CreativeItems,
-    pub block_states: BlockStates,
-    pub item_network_ids: ItemNetworkIds,
+    /// The item and block registries, replaced when a data pack is reloaded.
+    pub(crate) registries: RwLock<Arc<Registries>>,
 }
 
 impl Instance {
@@ -199,12 +561,111 @@ impl Instance {
         &self.level_service
     }
 
+    /// Gets the cooldown service of this instance.
+    #[inline]
+    pub const fn cooldowns(&self) -> &Cooldowns {
+        &self.cooldowns
+    }
+
+    /// Gets the current item and block registries.
+    ///
+    /// The returned registries are not affected by later [reloads](Self::reload_registries).
+    #[inline]
+    pub fn registries(&self) -> Arc<Registries> {
+        Arc::clone(&self.registries.read())
+    }
+
+    /// Gets the packets sent and received by all sessions combined.
+    #[inline]
+    pub const fn traffic(&self) -> &TrafficStats {
+        &self.traffic
+    }
+
+    /// Gets the scheduler of automatic restarts.
+    #[inline]
+    pub const fn restarts(&self) -> &RestartScheduler {
+        &self.restarts
+    }
+
+    /// Whether the server shut down to restart.
+    ///
+    /// The process should exit with [`RESTART_EXIT_CODE`](crate::restart::RESTART_EXIT_CODE) in that case.
+    #[inline]
+    pub fn restart_requested(&self) -> bool {
+        self.restart_requested.load(Ordering::Acquire)
+    }
+
+    /// Sets whether the server should be started again after it has shut down.
+    #[inline]
+    pub(crate) fn set_restart_requested(&self, restart: bool) {
+        self.restart_requested.store(restart, Ordering::Release);
+    }
+
+    /// Gets the scheduler that runs the background jobs of this instance.
+    ///
+    /// Jobs registered here are stopped before the level and command services shut down.
+    #[inline]
+    pub const fn jobs(&self) -> &Arc<JobScheduler> {
+        &self.jobs
+    }
+
+    /// Gets the feature gates of this instance.
+    #[inline]
+    pub const fn feature_gates(&self) -> &FeatureGates {
+        &self.feature_gates
+    }
+
     /// Gets the client list of this instance.
     #[inline]
     pub const fn clients(&self) -> &Arc<crate::net::Clients> {
         &self.clients
     }
 
+    /// Subscribes to changes in the AFK status of players.
+    pub fn afk_events(&self) -> broadcast::Receiver<AfkEvent> {
+        self.afk_events.subscribe()
+    }
+
+    /// Notifies all subscribers of an AFK status change.
+    pub(crate) fn emit_afk_event(&self, event: AfkEvent) {
+        // Sending only fails if there are no subscribers.
+        let _: Result<usize, broadcast::error::SendError<AfkEvent>> = self.afk_events.send(event);
+    }
+
+    /// Subscribes to players leaving the server.
+    pub fn quit_events(&self) -> broadcast::Receiver<QuitEvent> {
+        self.quit_events.subscribe()
+    }
+
+    /// Notifies all subscribers of a player that left.
+    pub(crate) fn emit_quit_event(&self, event: QuitEvent) {
+        // Sending only fails if there are no subscribers.
+        let _: Result<usize, broadcast::error::SendError<QuitEvent>> = self.quit_events.send(event);
+    }
+
+    /// Subscribes to reports filed against players.
+    pub fn report_events(&self) -> broadcast::Receiver<Report> {
+        self.report_events.subscribe()
+    }
+
+    /// Notifies all subscribers of a filed report.
+    pub(crate) fn emit_report_event(&self, report: Report) {
+        // Sending only fails if there are no subscribers.
+        let _: Result<usize, broadcast::error::SendError<Report>> = self.report_events.send(report);
+    }
+
+    /// Gets the backend that reports are submitted to.
+    #[inline]
+    pub fn report_backend(&self) -> &dyn ReportBackend {
+        self.report_backend.as_ref()
+    }
+
+    /// Gets the clock that services read the time from.
+    #[inline]
+    pub fn clock(&self) -> &SharedClock {
+        &self.config.clock
+    }
+
     /// Refreshes the message of the day by calling the generating function again.
     pub fn refresh_motd(self: &Arc<Instance>) {
         let motd: CowString<'_> = (self.config.motd_callback)(self);
@@ -215,7 +676,7 @@ impl Instance {
             CLIENT_VERSION_STRING,
             self.clients.total_connected(),
             self.config.max_connections(),
-            self.raknet_guid,
+            self.handshake.guid(),
             self.config.name.as_str(),
             self.config.ipv4_addr.port(),
             self.config.ipv6_addr.map(|addr| addr.port()).unwrap_or(0)
@@ -239,7 +700,7 @@ impl Instance {
         }
 
         let this = Arc::clone(self);
-        let handle = tokio::spawn(async move {
+        let handle = util::task::spawn("instance::shutdown", async move {
             let handle = this.clients.shutdown();
             match handle.await {
                 Ok(_) => (),
@@ -251,6 +712,7 @@ impl Instance {
             // Wait for user map to shut down before cancelling general token.
             this.running_token.cancel();
 
+            this.jobs.join().await?;
             this.level_service.join().await?;
             this.command_service.join().await?;
 
@@ -266,6 +728,86 @@ impl Instance {
         Some(handle)
     }
 
+    /// Spawns a network receiver for every socket that the server listens on.
+    fn spawn_receivers(self: &Arc<Instance>) {
+        for (shard, socket) in self.ipv4_sockets.iter().enumerate() {
+            let socket = Arc::clone(socket);
+            let this = Arc::clone(self);
+
+            util::task::spawn_owned("instance::receiver", format!("ipv4#{shard}"), Instance::net_receiver(this, socket));
+        }
+        tracing::info!("IPv4 listener ready");
+
+        for (shard, socket) in self.ipv6_sockets.iter().enumerate() {
+            let socket = Arc::clone(socket);
+            let this = Arc::clone(self);
+
+            util::task::spawn_owned("instance::receiver", format!("ipv6#{shard}"), Instance::net_receiver(this, socket));
+        }
+        if !self.ipv6_sockets.is_empty() {
+            tracing::info!("IPv6 listener ready");
+        }
+
+        if let Some(local_socket) = &self.local_socket {
+            let socket = Arc::clone(local_socket);
+            let this = Arc::clone(self);
+
+            util::task::spawn_owned("instance::receiver", "local", Instance::net_receiver(this, socket));
+            tracing::info!("Local listener ready");
+        }
+    }
+
+    /// Registers and starts the background jobs of the server.
+    fn register_jobs(self: &Arc<Instance>) -> anyhow::Result<()> {
+        let this = Arc::clone(self);
+        self.jobs.register(Job::task("afk::monitor", |token| async move {
+            crate::afk::monitor(this, token).await;
+            Ok(())
+        }))?;
+
+        let this = Arc::clone(self);
+        self.jobs.register(Job::task("level::sleep", |token| async move {
+            crate::level::sleep::monitor(this, token).await;
+            Ok(())
+        }))?;
+
+        let this = Arc::clone(self);
+        self.jobs.register(Job::task("capacity::monitor", |token| async move {
+            crate::capacity::monitor(this, token).await;
+            Ok(())
+        }))?;
+
+        let this = Arc::clone(self);
+        let persist = Arc::clone(self);
+        self.jobs.register(
+            Job::periodic("cooldown::purge", COOLDOWN_PURGE_INTERVAL, move || {
+                this.cooldowns.purge_expired();
+                std::future::ready(Ok(()))
+            })
+            .on_shutdown(move || async move {
+                let path = Path::new(&persist.config.level.path).join(COOLDOWN_FILE);
+                persist.cooldowns.save(path).context("Failed to persist cooldowns")
+            }),
+        )?;
+
+        let this = Arc::clone(self);
+        self.jobs.register(Job::task("restart::monitor", |token| async move {
+            crate::restart::monitor(this, token).await;
+            Ok(())
+        }))?;
+
+        #[cfg(feature = "webhooks")]
+        if let Some(config) = self.config.webhooks.clone() {
+            let this = Arc::clone(self);
+            self.jobs.register(Job::task("webhook::notifier", |token| async move {
+                crate::webhook::run(this, config, token).await;
+                Ok(())
+            }))?;
+        }
+
+        self.jobs.start()
+    }
+
     /// Starts the server and immediately returns when the server has successfully started
     pub fn start(self: &Arc<Instance>) -> anyhow::Result<()> {
         self.clients.set_instance(self)?;
@@ -301,23 +843,7 @@ impl Instance {
         //     }
         // )?;
 
-        self.command_service.register(
-            Command {
-                aliases: vec!["shutdown".to_owned()],
-                description: "Shuts down the server".to_owned(),
-                name: "shutdown".to_owned(),
-                overloads: vec![CommandOverload { parameters: Vec::new() }],
-                permission_level: CommandPermissionLevel::Normal,
-            },
-            |_input, ctx| {
-                ctx.instance.shutdown();
-
-                Ok(command::HandlerOutput {
-                    message: CowString::new("Server is shutting down"),
-                    parameters: Vec::new(),
-                })
-            },
-        )?;
+        command::register_builtin(&self.command_service)?;
 
         self.command_service.register(
             Command {
@@ -429,25 +955,21 @@ impl Instance {
             create_fn,
         )?;
 
-        {
-            let socket = Arc::clone(&self.ipv4_socket);
-            let this = Arc::clone(self);
+        #[cfg(feature = "profiling")]
+        self.command_service.register(crate::profiling::command(), crate::profiling::handle_command)?;
+        self.command_service.register(crate::level::stats::command(), crate::level::stats::handle_command)?;
+        self.command_service.register(crate::level::seed::command(), crate::level::seed::handle_command)?;
+        self.command_service.register(crate::tasks::command(), crate::tasks::handle_command)?;
+        self.command_service.register(crate::net::traffic::command(), crate::net::traffic::handle_command)?;
+        self.command_service.register(crate::restart::command(), crate::restart::handle_command)?;
 
-            tokio::spawn(Instance::net_receiver(this, socket));
-            tracing::info!("IPv4 listener ready");
-        }
+        self.spawn_receivers();
 
-        if let Some(ipv6_socket) = &self.ipv6_socket {
-            let socket = Arc::clone(ipv6_socket);
-            let this = Arc::clone(self);
-
-            tokio::spawn(Instance::net_receiver(this, socket));
-            tracing::info!("IPv6 listener ready");
-        }
+        self.register_jobs()?;
 
         {
             let this = Arc::clone(self);
-            tokio::spawn(async move {
+            util::task::spawn("instance::signal", async move {
                 if let Err(err) = tokio::signal::ctrl_c().await {
                     tracing::error!("Failed to create Ctrl-C signal handler: {err:#}");
                 } else {
@@ -461,174 +983,107 @@ impl Instance {
         Ok(())
     }
 
-    /// Generates a response to the [`UnconnectedPing`] packet with [`UnconnectedPong`].
-    #[inline]
-    #[tracing::instrument(
-        skip_all,
-        name = "Instance::process_unconnected_ping",
-        fields(
-            %packet.addr
-        )
-    )]
-    fn process_unconnected_ping(mut packet: ForwardablePacket, server_guid: u64, metadata: &str) -> anyhow::Result<ForwardablePacket> {
-        let ping = UnconnectedPing::deserialize(packet.buf.as_ref())?;
-        let pong = UnconnectedPong { time: ping.time, server_guid, metadata };
-
-        #[cfg(trace_raknet)]
-        tracing::debug!("{ping:?}");
-
-        packet.buf.clear();
-        packet.buf.reserve_to(pong.size_hint());
-        pong.serialize_into(&mut packet.buf)?;
-
-        let packet = ForwardablePacket { buf: packet.buf, addr: packet.addr };
-
-        Ok(packet)
-    }
-
-    /// Generates a response to the [`OpenConnectionRequest1`] packet with [`OpenConnectionReply1`].
-    #[inline]
-    #[tracing::instrument(
-        skip_all,
-        name = "Instance::process_open_connection_request1",
-        fields(
-            %packet.addr
-        )
-    )]
-    fn process_open_connection_request1(mut packet: ForwardablePacket, server_guid: u64) -> anyhow::Result<ForwardablePacket> {
-        let request = OpenConnectionRequest1::deserialize(packet.buf.as_ref())?;
-
-        #[cfg(trace_raknet)]
-        tracing::debug!("{request:?}");
-
-        packet.buf.clear();
-        if request.protocol_version != RAKNET_VERSION {
-            let reply = IncompatibleProtocol { server_guid };
-
-            packet.buf.clear();
-            packet.buf.reserve_to(reply.size_hint());
-            reply.serialize_into(&mut packet.buf)?;
-        } else {
-            let reply = OpenConnectionReply1 { mtu: request.mtu, server_guid };
-
-            packet.buf.clear();
-            packet.buf.reserve_to(reply.size_hint());
-            reply.serialize_into(&mut packet.buf)?;
+    /// Creates the session of a client that has completed the offline handshake and returns its MTU.
+    ///
+    /// If the client is already connected on another address, its existing connection is migrated instead.
+    fn open_session(&self, address: SocketAddr, guid: u64, requested_mtu: u16, udp_socket: Arc<Socket>, outgoing: Arc<DatagramBatch>) -> anyhow::Result<u16> {
+        let net = self.config.net();
+        let mtu = self.handshake.negotiate_mtu(address, requested_mtu)?;
+
+        if net.connection_migration && self.clients.begin_migration(address, guid) {
+            // The existing connection moves to the new address instead of creating a new one.
+            return Ok(mtu)
         }
 
-        Ok(packet)
-    }
-
-    /// Responds to the [`OpenConnectionRequest2`] packet with [`OpenConnectionReply2`].
-    /// This is also when a session is created for the client.
-    /// From this point, all packets are encoded in a [`Frame`](crate::raknet::Frame).
-    #[inline]
-    #[tracing::instrument(
-        skip_all,
-        name = "Instance::process_open_connection_request2",
-        fields(
-            %packet.addr
-        )
-    )]
-    fn process_open_connection_request2(
-        mut packet: ForwardablePacket,
-        udp_socket: Arc<UdpSocket>,
-        user_manager: Arc<Clients>,
-        server_guid: u64,
-    ) -> anyhow::Result<ForwardablePacket> {
-        let request = OpenConnectionRequest2::deserialize(packet.buf.as_ref())?;
-        let reply = OpenConnectionReply2 {
-            server_guid,
-            mtu: request.mtu,
-            client_address: packet.addr,
-        };
-
-        #[cfg(trace_raknet)]
-        tracing::debug!("{request:?}");
-
-        packet.buf.clear();
-        packet.buf.reserve_to(reply.size_hint());
-        reply.serialize_into(&mut packet.buf)?;
-
-        user_manager.insert(RakNetCreateDescription {
-            address: packet.addr,
-            guid: request.client_guid,
-            mtu: request.mtu,
+        let capture = open_capture(net, &udp_socket, address, guid);
+        self.clients.insert(RakNetCreateDescription {
+            address,
+            guid,
+            mtu,
             socket: udp_socket,
-        });
-
-        Ok(packet)
+            outgoing,
+            compound_limits: net.compounds,
+            max_batch_frames: net.max_batch_frames,
+            order_channels: net.order_channels,
+            ack_interval: net.ack_interval,
+            send_rate: net.send_rate,
+            send_weights: net.send_weights,
+            session_timeout: net.session_timeout,
+            keepalive_interval: net.keepalive_interval,
+            capture,
+        }, net.backpressure);
+
+        Ok(mtu)
     }
 
     /// Receives raknet from IPv4 clients and adds them to the receive queue
-    async fn net_receiver(self: Arc<Instance>, udp_socket: Arc<UdpSocket>) {
+    async fn net_receiver(self: Arc<Instance>, udp_socket: Arc<Socket>) {
         // This is heap-allocated because stack data is stored inline in tasks.
-        // If it were to be stack-allocated, Tokio would have to copy the entire buffer each time
+        // If it were to be stack-allocated, Tokio would have to copy the entire buffers each time
         // the task is moved across threads.
-        let mut recv_buf = vec![0u8; RECV_BUF_SIZE];
+        let mut batch = ReceiveBatch::new(RECV_BATCH_SIZE, RECV_BUF_SIZE);
+        // Shared by all clients on this socket, so that their datagrams are sent together.
+        let outgoing = Arc::new(DatagramBatch::new());
 
         loop {
-            let (n, address) = tokio::select! {
-                r = udp_socket.recv_from(&mut recv_buf) => {
-                    match r {
-                        Ok(r) => r,
-                        Err(e) => {
-                            tracing::error!("Failed to receive UDP packet from client: {e}");
-                            continue
-                        }
+            tokio::select! {
+                r = batch.recv(&udp_socket) => {
+                    if let Err(e) = r {
+                        tracing::error!("Failed to receive UDP packet from client: {e}");
+                        continue
                     }
                 },
                 _ = self.running_token.cancelled() => break
             };
 
-            let packet = ForwardablePacket {
-                buf: RVec::alloc_from_slice(&recv_buf[..n]),
-                addr: address,
-            };
-
-            if packet.is_unconnected() {
-                let udp_socket = Arc::clone(&udp_socket);
-                let session_manager = Arc::clone(&self.clients);
-                let metadata = self.current_motd.read().clone();
-
-                let this = Arc::clone(&self);
-                tokio::spawn(async move {
-                    let Some(id) = packet.packet_id() else {
-                        tracing::warn!("Unconnected packet was empty");
-                        return;
-                    };
-
-                    let pk_result = match id {
-                        UnconnectedPing::ID => Instance::process_unconnected_ping(packet, this.raknet_guid, &metadata),
-                        OpenConnectionRequest1::ID => Instance::process_open_connection_request1(packet, this.raknet_guid),
-                        OpenConnectionRequest2::ID => {
-                            Instance::process_open_connection_request2(packet, Arc::clone(&udp_socket), session_manager, this.raknet_guid)
-                        }
-                        _ => {
-                            tracing::error!("Invalid unconnected packet ID: {id:x}");
-                            return;
-                        }
-                    };
-
-                    match pk_result {
-                        Ok(packet) => match udp_socket.send_to(packet.buf.as_ref(), packet.addr).await {
-                            Ok(_) => (),
-                            Err(e) => {
-                                tracing::error!("Unable to send unconnected packet to client: {e}");
-                            }
-                        },
-                        Err(e) => {
-                            tracing::error!("{e}");
-                        }
-                    }
-                });
-            } else if let Err(e) = self.clients.forward(packet).await {
-                tracing::error!("{e:#}");
+            for (datagram, address) in batch.iter() {
+                self.handle_datagram(&udp_socket, &outgoing, datagram, address).await;
             }
         }
 
         tracing::info!("Network receiver closed");
     }
+
+    /// Forwards a received datagram to its connection or handles it as an unconnected packet.
+    async fn handle_datagram(self: &Arc<Instance>, udp_socket: &Arc<Socket>, outgoing: &Arc<DatagramBatch>, datagram: &[u8], address: SocketAddr) {
+        if !raknet::accept_datagram(datagram, address) {
+            return
+        }
+
+        let packet = ForwardablePacket {
+            buf: RVec::alloc_from_slice(datagram),
+            addr: address,
+        };
+
+        if packet.is_unconnected() {
+            // Every unconnected packet spawns a task, so floods have to be stopped before that happens.
+            if !self.handshake.allow(address.ip()) {
+                tracing::trace!("Ignoring unconnected packet from rate limited address {address}");
+                return
+            }
+
+            let udp_socket = Arc::clone(udp_socket);
+            let outgoing = Arc::clone(outgoing);
+
+            let this = Arc::clone(self);
+            util::task::spawn("instance::unconnected", async move {
+                let reply = this.handshake.handle(packet.buf.as_ref(), address, &this.current_motd.read(), |guid, mtu| {
+                    this.open_session(address, guid, mtu, Arc::clone(&udp_socket), outgoing)
+                });
+
+                match reply {
+                    Ok(reply) => {
+                        if let Err(e) = udp_socket.send_to(&reply, address).await {
+                            tracing::error!("Unable to send unconnected packet to client: {e}");
+                        }
+                    }
+                    Err(e) => tracing::error!("{e:#}"),
+                }
+            });
+        } else if let Err(e) = self.clients.forward(packet).await {
+            tracing::error!("{e:#}");
+        }
+    }
 }
 
 impl Joinable for Instance {
@@ -640,3 +1095,21 @@ impl Joinable for Instance {
         Ok(())
     }
 }
+
+/// Creates the capture file of a new connection if capturing is enabled.
+fn open_capture(net: &NetConfig, socket: &Socket, address: SocketAddr, guid: u64) -> Option<Capture> {
+    let dir = net.capture_dir.as_ref()?;
+    let name = format!("{}-{guid}.pcapng", address.to_string().replace([':', '[', ']'], "_"));
+
+    let capture = socket
+        .local_addr()
+        .and_then(|local| Capture::create(dir.join(&name), local));
+
+    match capture {
+        Ok(capture) => Some(capture.with_payloads(net.capture_payloads)),
+        Err(err) => {
+            tracing::warn!("Unable to capture traffic of {address}: {err}");
+            None
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use proto::bedrock::ItemInstance;
+
+/// Name of the NBT tag that stores how much damage an item has taken.
+pub const DAMAGE_TAG: &str = "Damage";
+/// Name of the NBT tag that stores the enchantments of an item.
+const ENCHANTMENT_TAG: &str = "ench";
+/// ID of the Unbreaking enchantment.
+const UNBREAKING_ID: i16 = 17;
+
+/// Action that caused an item to lose durability.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DamageSource {
+    /// A block was broken using the item.
+    BlockBreak,
+    /// An entity was attacked using the item.
+    Attack,
+}
+
+/// What happened to an item after damage was applied to it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DurabilityOutcome {
+    /// The item does not have durability or the damage was absorbed by Unbreaking.
+    Unchanged,
+    /// The item lost durability.
+    Damaged {
+        /// Remaining durability of the item.
+        remaining: u16,
+    },
+    /// The item ran out of durability and should be removed.
+    Broken,
+}
+
+/// Returns the maximum durability of the item with the given identifier.
+///
+/// Items that cannot be damaged return `None`.
+pub fn max_durability(name: &str) -> Option<u16> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+
+    let durability = match name {
+        "bow" => 384,
+        "crossbow" => 465,
+        "fishing_rod" => 384,
+        "flint_and_steel" => 64,
+        "shears" => 238,
+        "shield" => 336,
+        "trident" => 250,
+        "elytra" => 432,
+        "carrot_on_a_stick" => 25,
+        "warped_fungus_on_a_stick" => 100,
+        "mace" => 500,
+        _ => {
+            let (material, tool) = name.rsplit_once('_')?;
+            if !matches!(tool, "sword" | "pickaxe" | "axe" | "shovel" | "hoe") {
+                return None;
+            }
+
+            match material {
+                "wooden" => 59,
+                "stone" => 131,
+                "iron" => 250,
+                "golden" => 32,
+                "diamond" => 1561,
+                "netherite" => 2031,
+                _ => return None,
+            }
+        }
+    };
+
+    Some(durability)
+}
+
+/// Returns the amount of durability that the given item loses when it is used for the given action.
+///
+/// Tools are designed for breaking blocks and swords for fighting.
+/// Using them for the other purpose wears them down twice as fast.
+pub fn damage_cost(name: &str, source: DamageSource) -> u16 {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    let is_sword = name.ends_with("_sword") || name == "trident" || name == "mace";
+    let is_tool = ["_pickaxe", "_axe", "_shovel", "_hoe"].iter().any(|suffix| name.ends_with(suffix)) || name == "shears";
+
+    match source {
+        DamageSource::Attack if is_sword => 1,
+        DamageSource::Attack if is_tool => 2,
+        DamageSource::BlockBreak if is_tool => 1,
+        DamageSource::BlockBreak if is_sword => 2,
+        _ => 0,
+    }
+}
+
+/// Returns the damage that the item has taken so far.
+pub fn item_damage(item: &ItemInstance) -> u16 {
+    item.nbt
+        .get(DAMAGE_TAG)
+        .and_then(nbt::Value::as_i32)
+        .map_or(0, |damage| damage.clamp(0, u16::MAX as i32) as u16)
+}
+
+/// Returns the level of the Unbreaking enchantment on the item, or 0 if it is not enchanted with it.
+pub fn unbreaking_level(item: &ItemInstance) -> u16 {
+    let Some(enchantments) = item.nbt.get(ENCHANTMENT_TAG).and_then(nbt::Value::as_list) else {
+        return 0;
+    };
+
+    enchantments
+        .iter()
+        .filter_map(nbt::Value::as_compound)
+        .find(|ench| ench.get("id").and_then(nbt::Value::as_i16) == Some(UNBREAKING_ID))
+        .and_then(|ench| ench.get("lvl").and_then(nbt::Value::as_i16))
+        .map_or(0, |lvl| lvl.max(0) as u16)
+}
+
+/// Damages the item by the given amount, taking the Unbreaking enchantment into account.
+///
+/// The new damage value is stored in the item's NBT.
+pub fn apply_damage(item: &mut ItemInstance, max_durability: u16, amount: u16) -> DurabilityOutcome {
+    // Unbreaking gives every point of damage a chance of `1 / (level + 1)` to actually be applied.
+    let unbreaking = unbreaking_level(item);
+    let applied = (0..amount).filter(|_| rand::random::<u16>() % (unbreaking + 1) == 0).count() as u16;
+    if applied == 0 {
+        return DurabilityOutcome::Unchanged;
+    }
+
+    let damage = item_damage(item).saturating_add(applied);
+    if damage >= max_durability {
+        return DurabilityOutcome::Broken;
+    }
+
+    item.nbt.insert(DAMAGE_TAG.to_owned(), nbt::Value::Int(damage as i32));
+    DurabilityOutcome::Damaged { remaining: max_durability - damage }
+}
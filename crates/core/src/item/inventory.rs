@@ -0,0 +1,121 @@
+use parking_lot::Mutex;
+use proto::bedrock::{ItemInstance, TransactionAction, TransactionSourceType, WindowId};
+
+/// Amount of slots in the main inventory of a player, including the hotbar.
+pub const INVENTORY_SIZE: usize = 36;
+
+/// An item stored by the server.
+///
+/// Unlike [`ItemInstance`], this owns all of its data so that it can be kept around after the packet
+/// it was received in has been dropped.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StoredItem {
+    /// Network ID of the item, 0 for air.
+    pub network_id: i32,
+    /// Amount of items in the stack.
+    pub count: u16,
+    /// Auxiliary value of the item.
+    pub metadata: u32,
+    /// Block runtime ID of block items.
+    pub block_runtime_id: i32,
+    /// NBT data of the item, such as its damage and enchantments.
+    pub nbt: nbt::Compound,
+    /// Blocks this item can be placed on in adventure mode.
+    pub can_place_on: Vec<String>,
+    /// Blocks this item can break in adventure mode.
+    pub can_destroy: Vec<String>,
+}
+
+impl StoredItem {
+    /// Copies the item sent by a client.
+    ///
+    /// The stack ID and blocking tick are not stored, they are not used by client-authoritative inventories.
+    pub fn from_instance(item: &ItemInstance) -> StoredItem {
+        StoredItem {
+            network_id: item.network_id,
+            count: item.count,
+            metadata: item.metadata,
+            block_runtime_id: item.block_runtime_id,
+            nbt: item.nbt.clone(),
+            can_place_on: item.can_place_on.iter().map(|&name| name.to_owned()).collect(),
+            can_destroy: item.can_destroy.iter().map(|&name| name.to_owned()).collect(),
+        }
+    }
+
+    /// Creates an item that can be sent to a client.
+    pub fn as_instance(&self) -> ItemInstance<'_> {
+        ItemInstance {
+            network_id: self.network_id,
+            count: self.count,
+            metadata: self.metadata,
+            stack_id: None,
+            block_runtime_id: self.block_runtime_id,
+            nbt: self.nbt.clone(),
+            can_place_on: self.can_place_on.iter().map(String::as_str).collect(),
+            can_destroy: self.can_destroy.iter().map(String::as_str).collect(),
+            blocking_tick: 0,
+        }
+    }
+}
+
+/// Contents of a player's main inventory as known to the server.
+///
+/// The inventory is client-authoritative, so it is updated using the transactions that the client sends.
+/// Every change is checked against the item that the server expects to be in the slot. Actions that do not
+/// match are rejected, which prevents clients from making up items that they do not actually own.
+#[derive(Debug)]
+pub struct Inventory {
+    slots: Mutex<Vec<StoredItem>>,
+}
+
+impl Inventory {
+    /// Creates an empty inventory.
+    pub fn new() -> Inventory {
+        Inventory { slots: Mutex::new(vec![StoredItem::default(); INVENTORY_SIZE]) }
+    }
+
+    /// Returns a copy of the item in the given slot, or `None` if the slot does not exist.
+    pub fn get(&self, slot: u32) -> Option<StoredItem> {
+        self.slots.lock().get(slot as usize).cloned()
+    }
+
+    /// Replaces the item in the given slot, returning `false` if the slot does not exist.
+    pub fn set(&self, slot: u32, item: StoredItem) -> bool {
+        self.slots.lock().get_mut(slot as usize).map(|stored| *stored = item).is_some()
+    }
+
+    /// Applies the actions of a transaction that modify this inventory.
+    ///
+    /// Either all actions are applied or none of them. If the old item of an action does not match the item
+    /// that is stored in its slot, the slots of the mismatched actions are returned so that they can be sent
+    /// to the client again.
+    pub fn apply(&self, actions: &[TransactionAction]) -> Result<(), Vec<u32>> {
+        let mut slots = self.slots.lock();
+        let actions = actions
+            .iter()
+            .filter(|action| matches!(action.source_type, TransactionSourceType::Container { inventory_id: WindowId::Inventory }));
+
+        let mismatched: Vec<u32> = actions
+            .clone()
+            .filter(|action| slots.get(action.slot as usize).map_or(true, |stored| *stored != StoredItem::from_instance(&action.old_item)))
+            .map(|action| action.slot)
+            .collect();
+
+        if !mismatched.is_empty() {
+            return Err(mismatched)
+        }
+
+        for action in actions {
+            slots[action.slot as usize] = StoredItem::from_instance(&action.new_item);
+        }
+        drop(slots);
+
+        Ok(())
+    }
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
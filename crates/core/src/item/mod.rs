@@ -1,3 +1,7 @@
 //! Everything related to items in Minecraft.
 
 use util::glob_export;
+
+glob_export!(durability);
+glob_export!(inventory);
+glob_export!(registry);
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use level::{BlockStates, CreativeItems, ItemNetworkIds};
+use parking_lot::RwLock;
+use proto::bedrock::CreativeContent;
+
+use crate::instance::Instance;
+
+/// The item and block registries of the server.
+pub struct Registries {
+    /// Maps item names to network IDs.
+    pub items: ItemNetworkIds,
+    /// Maps block states to runtime IDs.
+    pub blocks: BlockStates,
+    /// Items shown in the creative inventory.
+    pub creative: CreativeItems,
+}
+
+impl Registries {
+    /// Loads the vanilla registries that are embedded in the server.
+    pub fn vanilla() -> anyhow::Result<Registries> {
+        let items = ItemNetworkIds::new()?;
+        let blocks = BlockStates::new()?;
+        let creative = CreativeItems::new(&items, &blocks)?;
+
+        Ok(Registries { items, blocks, creative })
+    }
+
+    /// Loads registries from the files of a data pack.
+    pub fn load(pack: &DataPack) -> anyhow::Result<Registries> {
+        let items = ItemNetworkIds::from_nbt(&pack.item_ids)?;
+        let blocks = BlockStates::from_nbt(&pack.block_states)?;
+        let creative = CreativeItems::from_nbt(&pack.creative_items, &items, &blocks)?;
+
+        Ok(Registries { items, blocks, creative })
+    }
+
+    /// Verifies that `updated` can replace these registries while players are online.
+    ///
+    /// Clients only receive the item list and custom block properties when they join, so the items and block states
+    /// must stay exactly the same. Only the creative inventory can change.
+    pub fn check_compatible(&self, updated: &Registries) -> anyhow::Result<()> {
+        self.items.check_compatible(&updated.items)?;
+        self.blocks.check_compatible(&updated.blocks)
+    }
+
+    /// Loads the registries of `pack` and installs them in `slot` if they are compatible with the current ones.
+    ///
+    /// Incompatible registries are refused with an error and leave `slot` untouched.
+    pub fn replace(slot: &RwLock<Arc<Registries>>, pack: &DataPack) -> anyhow::Result<Arc<Registries>> {
+        let updated = Registries::load(pack)?;
+
+        let mut current = slot.write();
+        current.check_compatible(&updated)?;
+
+        let updated = Arc::new(updated);
+        *current = Arc::clone(&updated);
+        drop(current);
+
+        Ok(updated)
+    }
+}
+
+impl std::fmt::Debug for Registries {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Registries")
+            .field("items", &self.items.len())
+            .field("blocks", &self.blocks.len())
+            .field("creative", &self.creative.stacks.len())
+            .finish()
+    }
+}
+
+/// Raw registry files of a data pack, all in network NBT format.
+#[derive(Debug, Clone, Default)]
+pub struct DataPack {
+    /// Compound of item names and their network IDs.
+    pub item_ids: Vec<u8>,
+    /// Palette entries of every block state, in runtime ID order.
+    pub block_states: Vec<u8>,
+    /// List of items shown in the creative inventory.
+    pub creative_items: Vec<u8>,
+}
+
+impl Instance {
+    /// Replaces the item and block registries with those of an updated data pack.
+    ///
+    /// The new registries are only installed if they are compatible with the current ones, meaning that no
+    /// item or block state was added, removed or assigned a different ID. Changes that would require
+    /// clients to rejoin are refused with an error and leave the current registries untouched.
+    ///
+    /// After the swap, online players receive the new creative inventory and command list.
+    pub fn reload_registries(&self, pack: &DataPack) -> anyhow::Result<Arc<Registries>> {
+        let updated = Registries::replace(&self.registries, pack)?;
+
+        tracing::info!(
+            "Reloaded registries: {} items, {} block states, {} creative items",
+            updated.items.len(),
+            updated.blocks.len(),
+            updated.creative.stacks.len()
+        );
+
+        self.clients().broadcast(CreativeContent { items: &updated.creative.stacks })?;
+        self.clients().broadcast(self.commands().available_commands())?;
+
+        Ok(updated)
+    }
+}
@@ -0,0 +1,41 @@
+//! Periodic saving of the level.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Amount of ticks between two autosaves, which is five minutes.
+pub const AUTOSAVE_INTERVAL: u64 = 5 * 60 * 20;
+
+/// Decides when the level is saved.
+#[derive(Debug)]
+pub struct Autosave {
+    /// Amount of ticks between two saves.
+    interval: u64,
+    /// Amount of ticks since the last save.
+    elapsed: AtomicU64,
+}
+
+impl Autosave {
+    /// Creates a schedule that saves every `interval` ticks.
+    pub const fn new(interval: u64) -> Autosave {
+        Autosave { interval, elapsed: AtomicU64::new(0) }
+    }
+
+    /// Advances the schedule by one tick, returning whether the level should be saved now.
+    ///
+    /// A save that is due while saves are `deferred` is postponed until they are allowed again.
+    pub fn tick(&self, deferred: bool) -> bool {
+        let elapsed = self.elapsed.fetch_add(1, Ordering::Relaxed) + 1;
+        if elapsed < self.interval || deferred {
+            return false
+        }
+
+        self.elapsed.store(0, Ordering::Relaxed);
+        true
+    }
+}
+
+impl Default for Autosave {
+    fn default() -> Autosave {
+        Autosave::new(AUTOSAVE_INTERVAL)
+    }
+}
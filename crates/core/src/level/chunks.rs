@@ -0,0 +1,197 @@
+//! Chunks that are simulated because players are nearby.
+//!
+//! Every player keeps the chunks within [`SIMULATION_DISTANCE`] of their position loaded. Loaded chunks are
+//! ticked by the level service and unloaded once the last player that kept them loaded has moved away.
+//! The actors in a chunk are read from disk when it is loaded and written back when it is unloaded.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use dashmap::DashMap;
+use level::provider::ActorData;
+use proto::types::Dimension;
+use util::Vector;
+
+use super::stats::ChunkStats;
+
+/// Actors in a chunk, by unique ID.
+pub type ChunkActors = Vec<(i64, ActorData)>;
+
+/// Distance in chunks around a player within which chunks are loaded and ticked.
+pub const SIMULATION_DISTANCE: i32 = 4;
+
+/// State of a single loaded chunk.
+#[derive(Debug, Default)]
+struct LoadedChunk {
+    /// Amount of players that keep this chunk loaded.
+    viewers: usize,
+    /// Actors in this chunk.
+    actors: ChunkActors,
+}
+
+/// Keeps track of the chunks that are loaded and ticks them.
+#[derive(Debug, Default)]
+pub struct LoadedChunks {
+    chunks: DashMap<(Dimension, [i32; 2]), LoadedChunk>,
+}
+
+impl LoadedChunks {
+    /// Adds a viewer to a chunk, returning whether the chunk was not loaded before.
+    pub fn retain(&self, dimension: Dimension, coordinates: [i32; 2]) -> bool {
+        let mut chunk = self.chunks.entry((dimension, coordinates)).or_default();
+        chunk.viewers += 1;
+        chunk.viewers == 1
+    }
+
+    /// Removes a viewer from a chunk.
+    ///
+    /// If this was the last viewer, the chunk is unloaded and its actors are returned so that they can be saved.
+    pub fn release(&self, dimension: Dimension, coordinates: [i32; 2]) -> Option<ChunkActors> {
+        let key = (dimension, coordinates);
+        let mut chunk = self.chunks.get_mut(&key)?;
+
+        chunk.viewers = chunk.viewers.saturating_sub(1);
+        let unused = chunk.viewers == 0;
+        drop(chunk);
+
+        if !unused {
+            return None
+        }
+
+        // Another viewer might have retained the chunk in the meantime.
+        self.chunks.remove_if(&key, |_, chunk| chunk.viewers == 0).map(|(_, chunk)| chunk.actors)
+    }
+
+    /// Sets the actors of a loaded chunk, returning whether the chunk is loaded.
+    pub fn set_actors(&self, dimension: Dimension, coordinates: [i32; 2], actors: ChunkActors) -> bool {
+        let Some(mut chunk) = self.chunks.get_mut(&(dimension, coordinates)) else {
+            return false
+        };
+
+        chunk.actors = actors;
+        true
+    }
+
+    /// Returns a copy of the actors in a loaded chunk.
+    pub fn actors(&self, dimension: Dimension, coordinates: [i32; 2]) -> Option<ChunkActors> {
+        self.chunks.get(&(dimension, coordinates)).map(|chunk| chunk.actors.clone())
+    }
+
+    /// Returns a copy of the actors in every loaded chunk, used to save them without unloading the chunks.
+    pub fn all_actors(&self) -> Vec<(Dimension, [i32; 2], ChunkActors)> {
+        self.chunks.iter().map(|chunk| (chunk.key().0, chunk.key().1, chunk.actors.clone())).collect()
+    }
+
+    /// Whether the given chunk is loaded.
+    pub fn contains(&self, dimension: Dimension, coordinates: [i32; 2]) -> bool {
+        self.chunks.contains_key(&(dimension, coordinates))
+    }
+
+    /// Amount of loaded chunks.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Whether no chunks are loaded.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Ticks every loaded chunk and records how long each of them took.
+    pub fn tick(&self, stats: &ChunkStats) {
+        for chunk in &self.chunks {
+            let start = Instant::now();
+            let (dimension, [x, z]) = *chunk.key();
+
+            // Random ticks and scheduled block updates are not implemented yet, so ticking a chunk currently
+            // only keeps its statistics up to date.
+            stats.set_entities(dimension, Vector::from([x, z]), chunk.actors.len());
+            stats.record_tick(dimension, Vector::from([x, z]), start.elapsed());
+        }
+    }
+}
+
+/// Whether `coordinates` is within simulation distance of a viewer in the chunk `center`.
+pub fn is_simulated(center: (Dimension, [i32; 2]), dimension: Dimension, coordinates: [i32; 2]) -> bool {
+    center.0 == dimension
+        && (coordinates[0] - center.1[0]).abs() <= SIMULATION_DISTANCE
+        && (coordinates[1] - center.1[1]).abs() <= SIMULATION_DISTANCE
+}
+
+/// Chunks within simulation distance of the given chunk.
+pub fn simulated_area(center: [i32; 2]) -> impl Iterator<Item = [i32; 2]> {
+    let range = -SIMULATION_DISTANCE..=SIMULATION_DISTANCE;
+    range.clone().flat_map(move |dx| range.clone().map(move |dz| [center[0] + dx, center[1] + dz]))
+}
+
+/// Chunks that still have to be sent to a player, closest first.
+#[derive(Debug, Default)]
+pub struct ChunkQueue {
+    /// Dimension, center chunk and radius of the area whose chunks have been queued.
+    view: Option<(Dimension, [i32; 2], i32)>,
+    pending: VecDeque<[i32; 2]>,
+}
+
+impl ChunkQueue {
+    /// Creates an empty queue for a player without a position.
+    pub const fn new() -> ChunkQueue {
+        ChunkQueue { view: None, pending: VecDeque::new() }
+    }
+
+    /// Moves the view of the player.
+    ///
+    /// Chunks that came into view are queued and queued chunks that left the view are dropped.
+    /// Moving to another dimension queues the whole view again.
+    pub fn update(&mut self, dimension: Dimension, center: [i32; 2], radius: u16) {
+        let radius = i32::from(radius);
+        let old = self.view.replace((dimension, center, radius));
+        if old == self.view {
+            return
+        }
+
+        let in_view = |view: (Dimension, [i32; 2], i32), coordinates: [i32; 2]| {
+            view.0 == dimension && distance_squared(view.1, coordinates) <= view.2 * view.2
+        };
+
+        self.pending.retain(|coordinates| in_view((dimension, center, radius), *coordinates));
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                let coordinates = [center[0] + dx, center[1] + dz];
+                if in_view((dimension, center, radius), coordinates) && !old.is_some_and(|old| in_view(old, coordinates)) {
+                    self.pending.push_back(coordinates);
+                }
+            }
+        }
+
+        self.pending.make_contiguous().sort_by_key(|coordinates| distance_squared(center, *coordinates));
+    }
+
+    /// Takes up to `limit` chunks that should be sent now, along with their dimension.
+    pub fn take(&mut self, limit: usize) -> Option<(Dimension, Vec<[i32; 2]>)> {
+        let (dimension, ..) = self.view?;
+        let count = limit.min(self.pending.len());
+
+        Some((dimension, self.pending.drain(..count).collect()))
+    }
+
+    /// Dimension and radius of the current view, if the player has a position yet.
+    pub fn view(&self) -> Option<(Dimension, [i32; 2], u16)> {
+        self.view.map(|(dimension, center, radius)| (dimension, center, radius as u16))
+    }
+
+    /// Amount of chunks waiting to be sent.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether all chunks in view have been sent.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Squared distance between two chunks.
+const fn distance_squared(a: [i32; 2], b: [i32; 2]) -> i32 {
+    let (dx, dz) = (a[0] - b[0], a[1] - b[1]);
+    dx * dx + dz * dz
+}
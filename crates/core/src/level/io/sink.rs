@@ -149,7 +149,7 @@ impl Collector {
         let state = FlushState::new();
         let shutdown_token = CancellationToken::new();
 
-        tokio::spawn(Collector::collection(
+        util::task::spawn("level::collector", Collector::collection(
             instance_token.clone(),
             shutdown_token.clone(),
             consumer,
@@ -1,13 +1,21 @@
 use std::{
     pin::Pin,
+    sync::atomic::AtomicI64,
     task::{ready, Context, Poll},
 };
 
 use futures::Stream;
+use lazy_static::lazy_static;
 use level::SubChunk;
+use prometheus_client::metrics::gauge::Gauge;
 use tokio::sync::mpsc;
 use util::Vector;
 
+lazy_static! {
+    #[doc(hidden)]
+    pub static ref REGION_QUEUE_DEPTH_METRIC: Gauge::<i64, AtomicI64> = Gauge::default();
+}
+
 /// A unique identifier for a specific subchunk.
 ///
 /// First 6 bits are the vertical index,
@@ -64,7 +72,8 @@ pub struct RegionStream {
 
 impl RegionStream {
     #[inline]
-    pub const fn from_receiver(inner: mpsc::Receiver<IndexedSubChunk>, len: usize) -> RegionStream {
+    pub fn from_receiver(inner: mpsc::Receiver<IndexedSubChunk>, len: usize) -> RegionStream {
+        REGION_QUEUE_DEPTH_METRIC.inc_by(len as i64);
         RegionStream { inner, len }
     }
 
@@ -79,6 +88,13 @@ impl RegionStream {
     }
 }
 
+impl Drop for RegionStream {
+    fn drop(&mut self) {
+        // Subchunks that were never received no longer count as pending.
+        REGION_QUEUE_DEPTH_METRIC.dec_by(self.len as i64);
+    }
+}
+
 impl Stream for RegionStream {
     type Item = IndexedSubChunk;
 
@@ -88,6 +104,7 @@ impl Stream for RegionStream {
 
         if ready.is_some() {
             self.len -= 1;
+            REGION_QUEUE_DEPTH_METRIC.dec();
         }
 
         Poll::Ready(ready)
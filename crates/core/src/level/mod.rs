@@ -1,9 +1,19 @@
 //! Implements basic Minecraft level functionality.
 
+pub mod autosave;
+pub mod chunks;
 pub mod io;
 pub mod net;
+pub mod physics;
+pub mod portal;
+pub mod replicator;
 pub mod rule;
+pub mod seed;
 pub mod service;
+pub mod sleep;
+pub mod stats;
+pub mod throttle;
+pub mod time;
 pub mod viewer;
 
 pub use service::*;
@@ -0,0 +1,192 @@
+//! Collision queries against the level, used to simulate projectiles.
+
+use proto::types::Dimension;
+use util::Vector;
+
+use crate::entity::PhysicsQuery;
+
+use super::Service;
+
+/// Width of the hitbox of a player in blocks.
+pub const PLAYER_WIDTH: f32 = 0.6;
+/// Height of the hitbox of a player in blocks.
+pub const PLAYER_HEIGHT: f32 = 1.8;
+
+/// Blocks that projectiles fly through.
+const PASSABLE_BLOCKS: &[&str] = &[
+    "minecraft:air",
+    "minecraft:water",
+    "minecraft:flowing_water",
+    "minecraft:lava",
+    "minecraft:flowing_lava",
+    "minecraft:portal",
+];
+
+/// Whether a projectile collides with a block with the given name.
+///
+/// Blocks in parts of the level that have not been generated are reported as `None` and are not solid.
+pub fn is_solid(name: Option<&str>) -> bool {
+    name.is_some_and(|name| !PASSABLE_BLOCKS.contains(&name))
+}
+
+/// Box around an entity that can be hit by projectiles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hitbox {
+    /// Runtime ID of the entity.
+    pub runtime_id: u64,
+    /// Corner with the lowest coordinates.
+    pub min: Vector<f32, 3>,
+    /// Corner with the highest coordinates.
+    pub max: Vector<f32, 3>,
+}
+
+impl Hitbox {
+    /// Hitbox of a player standing at the given position.
+    pub fn player(runtime_id: u64, feet: &Vector<f32, 3>) -> Hitbox {
+        let half = PLAYER_WIDTH / 2.0;
+        Hitbox {
+            runtime_id,
+            min: Vector::from([feet.x - half, feet.y, feet.z - half]),
+            max: Vector::from([feet.x + half, feet.y + PLAYER_HEIGHT, feet.z + half]),
+        }
+    }
+
+    /// Returns the fraction of the segment from `from` to `to` at which it enters this box.
+    pub fn intersect(&self, from: &Vector<f32, 3>, to: &Vector<f32, 3>) -> Option<f32> {
+        let origin = [from.x, from.y, from.z];
+        let direction = [to.x - from.x, to.y - from.y, to.z - from.z];
+        let (min, max) = ([self.min.x, self.min.y, self.min.z], [self.max.x, self.max.y, self.max.z]);
+
+        let (mut enter, mut exit) = (0.0f32, 1.0f32);
+        for axis in 0..3 {
+            if direction[axis] == 0.0 {
+                if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                    return None
+                }
+                continue
+            }
+
+            let near = (min[axis] - origin[axis]) / direction[axis];
+            let far = (max[axis] - origin[axis]) / direction[axis];
+            enter = enter.max(near.min(far));
+            exit = exit.min(near.max(far));
+        }
+
+        (enter <= exit).then_some(enter)
+    }
+}
+
+/// Walks through the blocks along the segment from `from` to `to` and returns the point at which it first enters
+/// a block for which `solid` returns `true`.
+pub fn first_solid_block<F>(from: &Vector<f32, 3>, to: &Vector<f32, 3>, mut solid: F) -> Option<Vector<f32, 3>>
+where
+    F: FnMut(&Vector<i32, 3>) -> bool,
+{
+    let origin = [from.x, from.y, from.z];
+    let direction = [to.x - from.x, to.y - from.y, to.z - from.z];
+
+    let mut block = [from.x.floor() as i32, from.y.floor() as i32, from.z.floor() as i32];
+    if solid(&Vector::from(block)) {
+        return Some(from.clone())
+    }
+
+    let mut step = [0; 3];
+    let mut next = [f32::INFINITY; 3];
+    let mut delta = [f32::INFINITY; 3];
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1;
+            next[axis] = (block[axis] as f32 + 1.0 - origin[axis]) / direction[axis];
+            delta[axis] = 1.0 / direction[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1;
+            next[axis] = (block[axis] as f32 - origin[axis]) / direction[axis];
+            delta[axis] = -1.0 / direction[axis];
+        }
+    }
+
+    loop {
+        let axis = if next[0] <= next[1] && next[0] <= next[2] {
+            0
+        } else if next[1] <= next[2] {
+            1
+        } else {
+            2
+        };
+
+        let t = next[axis];
+        if t > 1.0 {
+            return None
+        }
+
+        block[axis] += step[axis];
+        next[axis] += delta[axis];
+
+        if solid(&Vector::from(block)) {
+            return Some(Vector::from([
+                direction[0].mul_add(t, origin[0]),
+                direction[1].mul_add(t, origin[1]),
+                direction[2].mul_add(t, origin[2]),
+            ]))
+        }
+    }
+}
+
+/// Answers the collision queries of projectiles using the blocks in the level and a set of entity hitboxes.
+pub struct LevelPhysics<F> {
+    solid: F,
+    entities: Vec<Hitbox>,
+}
+
+impl<F> LevelPhysics<F>
+where
+    F: Fn(&Vector<i32, 3>) -> bool,
+{
+    /// Creates a query that uses `solid` to determine which blocks can be hit.
+    pub const fn new(solid: F, entities: Vec<Hitbox>) -> LevelPhysics<F> {
+        LevelPhysics { solid, entities }
+    }
+}
+
+impl<F> PhysicsQuery for LevelPhysics<F>
+where
+    F: Fn(&Vector<i32, 3>) -> bool,
+{
+    fn intersect_block(&self, from: &Vector<f32, 3>, to: &Vector<f32, 3>) -> Option<Vector<f32, 3>> {
+        first_solid_block(from, to, &self.solid)
+    }
+
+    fn intersect_entity(&self, from: &Vector<f32, 3>, to: &Vector<f32, 3>, exclude: Option<u64>) -> Option<(u64, Vector<f32, 3>)> {
+        let (target, t) = self
+            .entities
+            .iter()
+            .filter(|hitbox| Some(hitbox.runtime_id) != exclude)
+            .filter_map(|hitbox| hitbox.intersect(from, to).map(|t| (hitbox.runtime_id, t)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+        let at = Vector::from([
+            (to.x - from.x).mul_add(t, from.x),
+            (to.y - from.y).mul_add(t, from.y),
+            (to.z - from.z).mul_add(t, from.z),
+        ]);
+
+        Some((target, at))
+    }
+}
+
+impl Service {
+    /// Creates a collision query for projectiles in the given dimension.
+    ///
+    /// Blocks are read from the level, `entities` contains the hitboxes of the entities that can be hit.
+    pub fn physics(&self, dimension: Dimension, entities: Vec<Hitbox>) -> LevelPhysics<impl Fn(&Vector<i32, 3>) -> bool + '_> {
+        let solid = move |block: &Vector<i32, 3>| match self.block_name(block, dimension) {
+            Ok(name) => is_solid(name.as_deref()),
+            Err(err) => {
+                tracing::warn!("Failed to read block at {block:?} for collision: {err:#}");
+                false
+            }
+        };
+
+        LevelPhysics::new(solid, entities)
+    }
+}
@@ -0,0 +1,201 @@
+//! Nether portal linking between the Overworld and the Nether.
+
+use std::collections::HashMap;
+
+use level::{PaletteEntry, SubChunk};
+use proto::types::Dimension;
+use util::Vector;
+
+use super::Service;
+
+/// Identifier of the nether portal block.
+pub const PORTAL_BLOCK: &str = "minecraft:portal";
+/// Identifier of the block that portal frames are made of.
+pub const FRAME_BLOCK: &str = "minecraft:obsidian";
+/// Factor by which horizontal distances in the Nether are scaled compared to the Overworld.
+pub const NETHER_SCALE: f32 = 8.0;
+/// Horizontal radius in blocks that is searched for an existing portal in the Overworld.
+const OVERWORLD_SEARCH_RADIUS: i32 = 128;
+/// Horizontal radius in blocks that is searched for an existing portal in the Nether.
+const NETHER_SEARCH_RADIUS: i32 = 16;
+
+/// Returns the range of subchunk indices that contain blocks in the given dimension.
+const fn subchunk_range(dimension: Dimension) -> (i32, i32) {
+    match dimension {
+        Dimension::Overworld => (-4, 19),
+        Dimension::Nether => (0, 7),
+        Dimension::End => (0, 15),
+    }
+}
+
+/// Maps a position in one dimension to the corresponding position in another.
+///
+/// Horizontal coordinates are divided by [`NETHER_SCALE`] when travelling to the Nether and multiplied
+/// when travelling back. The vertical coordinate is clamped to the build limits of the destination.
+pub fn scale_position(position: &Vector<f32, 3>, from: Dimension, to: Dimension) -> Vector<f32, 3> {
+    let scale = match (from, to) {
+        (Dimension::Overworld, Dimension::Nether) => 1.0 / NETHER_SCALE,
+        (Dimension::Nether, Dimension::Overworld) => NETHER_SCALE,
+        _ => 1.0,
+    };
+
+    let (min, max) = subchunk_range(to);
+    let y = position.y.clamp((min * 16) as f32, (max * 16 + 15) as f32);
+
+    Vector::from([position.x * scale, y, position.z * scale])
+}
+
+/// Blocks that make up a new portal whose lowest opening block on the negative X side is at `position`.
+///
+/// The portal is aligned along the X axis and consists of a 4 by 5 obsidian frame around a 2 by 3 opening.
+/// Obsidian ledges are placed on both sides of the opening with air above them, so that the player does not
+/// arrive in a wall or above a drop.
+pub fn portal_blocks(position: &Vector<i32, 3>) -> Vec<(Vector<i32, 3>, PaletteEntry)> {
+    let mut blocks = Vec::new();
+
+    for dx in -1..=2 {
+        for dy in -1..=3 {
+            let block = Vector::from([position.x + dx, position.y + dy, position.z]);
+            let opening = (0..=1).contains(&dx) && (0..=2).contains(&dy);
+
+            if opening {
+                let mut portal = PaletteEntry::new(PORTAL_BLOCK);
+                portal.states.insert("portal_axis".to_owned(), nbt::Value::String("x".to_owned()));
+                blocks.push((block, portal));
+            } else {
+                blocks.push((block, PaletteEntry::new(FRAME_BLOCK)));
+            }
+        }
+    }
+
+    for dz in [-1, 1] {
+        for dx in 0..=1 {
+            blocks.push((Vector::from([position.x + dx, position.y - 1, position.z + dz]), PaletteEntry::new(FRAME_BLOCK)));
+            for dy in 0..=2 {
+                blocks.push((Vector::from([position.x + dx, position.y + dy, position.z + dz]), PaletteEntry::air()));
+            }
+        }
+    }
+
+    blocks
+}
+
+/// Location that a portal leads to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortalTarget {
+    /// An existing portal block was found near the destination.
+    Existing(Vector<i32, 3>),
+    /// No portal exists near the destination yet and a new one should be built at this position.
+    Create(Vector<i32, 3>),
+}
+
+impl PortalTarget {
+    /// Position of the portal block.
+    pub const fn position(&self) -> &Vector<i32, 3> {
+        match self {
+            Self::Existing(pos) | Self::Create(pos) => pos,
+        }
+    }
+}
+
+impl Service {
+    /// Returns the name of the block at the given position.
+    ///
+    /// Positions in subchunks that have not been generated are reported as `None`.
+    pub fn block_name(&self, position: &Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<Option<String>> {
+        let subchunk = self
+            .provider
+            .subchunk([position.x.div_euclid(16), position.y.div_euclid(16), position.z.div_euclid(16)], dimension)?;
+
+        let Some(subchunk) = subchunk else { return Ok(None) };
+        let Some(layer) = subchunk.layer(0) else { return Ok(None) };
+
+        let local = Vector::from([
+            position.x.rem_euclid(16) as u8,
+            position.y.rem_euclid(16) as u8,
+            position.z.rem_euclid(16) as u8,
+        ]);
+
+        Ok(layer.get(local).map(|entry| entry.name.clone()))
+    }
+
+    /// Finds the portal that a player travelling from `position` in `from` should arrive at.
+    ///
+    /// The nearest existing portal within the vanilla search radius of the destination is used.
+    /// If none exists, the scaled position itself is returned as a location to build a new portal.
+    pub fn find_portal_target(&self, position: &Vector<f32, 3>, from: Dimension, to: Dimension) -> anyhow::Result<PortalTarget> {
+        let scaled = scale_position(position, from, to);
+        let center = Vector::from([scaled.x.floor() as i32, scaled.y.floor() as i32, scaled.z.floor() as i32]);
+
+        let radius = if to == Dimension::Nether { NETHER_SEARCH_RADIUS } else { OVERWORLD_SEARCH_RADIUS };
+        let (min_y, max_y) = subchunk_range(to);
+
+        let mut nearest: Option<(i64, Vector<i32, 3>)> = None;
+        for cx in (center.x - radius).div_euclid(16)..=(center.x + radius).div_euclid(16) {
+            for cz in (center.z - radius).div_euclid(16)..=(center.z + radius).div_euclid(16) {
+                for cy in min_y..=max_y {
+                    let Some(subchunk) = self.provider.subchunk([cx, cy, cz], to)? else { continue };
+                    let Some(layer) = subchunk.layer(0) else { continue };
+
+                    // Most subchunks do not contain any portal blocks at all, skip them without checking every block.
+                    let Some(portal_index) = layer.palette.iter().position(|entry| entry.name == PORTAL_BLOCK) else {
+                        continue;
+                    };
+
+                    for (offset, index) in layer.indices.iter().enumerate() {
+                        if *index as usize != portal_index {
+                            continue;
+                        }
+
+                        let local = level::from_offset(offset);
+                        let block = Vector::from([cx * 16 + local.x as i32, cy * 16 + local.y as i32, cz * 16 + local.z as i32]);
+
+                        let (dx, dy, dz) = ((block.x - center.x) as i64, (block.y - center.y) as i64, (block.z - center.z) as i64);
+                        if dx.abs() > radius as i64 || dz.abs() > radius as i64 {
+                            continue;
+                        }
+
+                        let distance = dx * dx + dy * dy + dz * dz;
+                        if nearest.as_ref().map_or(true, |(best, _)| distance < *best) {
+                            nearest = Some((distance, block));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(match nearest {
+            Some((_, block)) => PortalTarget::Existing(block),
+            // Keep the frame, which extends one block below and three above the opening, within the build limits.
+            None => PortalTarget::Create(Vector::from([center.x, center.y.clamp(min_y * 16 + 1, max_y * 16 + 12), center.z])),
+        })
+    }
+
+    /// Builds a new portal at the given position, see [`portal_blocks`] for its layout.
+    ///
+    /// Subchunks that have not been generated yet are created.
+    pub fn build_portal(&self, position: &Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<()> {
+        let mut subchunks: HashMap<[i32; 3], Vec<(Vector<u8, 3>, PaletteEntry)>> = HashMap::new();
+        for (block, entry) in portal_blocks(position) {
+            let coordinates = [block.x.div_euclid(16), block.y.div_euclid(16), block.z.div_euclid(16)];
+            let local = Vector::from([block.x.rem_euclid(16) as u8, block.y.rem_euclid(16) as u8, block.z.rem_euclid(16) as u8]);
+
+            subchunks.entry(coordinates).or_default().push((local, entry));
+        }
+
+        for (coordinates, blocks) in subchunks {
+            let mut subchunk = self.provider.subchunk(coordinates, dimension)?.unwrap_or_else(|| SubChunk::empty(coordinates[1] as i8));
+            let Some(layer) = subchunk.layer_mut(0) else {
+                anyhow::bail!("Subchunk {coordinates:?} has no block layer");
+            };
+
+            for (local, entry) in blocks {
+                layer.set(local, entry);
+            }
+
+            self.provider.save_subchunk(coordinates, dimension, &subchunk)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+//! Chunk replication across sessions.
+//!
+//! Clients that support the blob cache keep chunk data on disk between sessions. The [`Replicator`] remembers the
+//! content hash of every chunk that was sent to a player, so that chunks which have not changed since the player
+//! last received them are sent as a hash only. The client loads those chunks from its own cache, or reports them as
+//! missing if they were evicted, in which case the full data is sent after all.
+//!
+//! Players are identified by their XUID, so the history survives reconnects. It is written to the level directory
+//! when the level is saved and loaded again on startup, which allows players to skip chunks on the next day as well.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use dashmap::DashMap;
+use proto::types::Dimension;
+
+/// Maximum amount of chunks remembered per player.
+///
+/// Once a player has received more chunks than this, the chunks that were sent the longest ago are forgotten.
+pub const MAX_TRACKED_CHUNKS: usize = 8192;
+
+/// Computes the content hash of a chunk payload.
+///
+/// This uses 64-bit FNV-1a rather than the standard library hasher, since hashes are written to disk and must remain
+/// the same across Rust versions.
+pub fn content_hash(payload: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    payload.iter().fold(OFFSET_BASIS, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(PRIME))
+}
+
+/// Chunks that have been sent to a single player.
+#[derive(Debug, Default)]
+struct History {
+    /// Content hash and send sequence number of every chunk, by dimension and coordinates.
+    chunks: HashMap<(Dimension, [i32; 2]), (u64, u64)>,
+    /// Sequence number of the next chunk that is sent.
+    next: u64,
+}
+
+impl History {
+    /// Forgets the oldest half of the chunks if more than [`MAX_TRACKED_CHUNKS`] are remembered.
+    ///
+    /// Removing half at once keeps the cost of this low, since it only happens once every few thousand chunks.
+    fn prune(&mut self) {
+        if self.chunks.len() <= MAX_TRACKED_CHUNKS {
+            return
+        }
+
+        let cutoff = self.next.saturating_sub((MAX_TRACKED_CHUNKS / 2) as u64);
+        self.chunks.retain(|_, (_, sequence)| *sequence >= cutoff);
+    }
+}
+
+/// Keeps track of the chunk contents that every player has received.
+#[derive(Debug, Default)]
+pub struct Replicator {
+    players: DashMap<u64, History>,
+}
+
+impl Replicator {
+    /// Creates a replicator that does not know about any players.
+    pub fn new() -> Replicator {
+        Replicator::default()
+    }
+
+    /// Whether the player with the given XUID has last received this chunk with the same content hash.
+    pub fn is_unchanged(&self, xuid: u64, dimension: Dimension, coordinates: [i32; 2], hash: u64) -> bool {
+        self.players
+            .get(&xuid)
+            .and_then(|history| history.chunks.get(&(dimension, coordinates)).map(|&(sent, _)| sent == hash))
+            .unwrap_or(false)
+    }
+
+    /// Remembers that the player with the given XUID has received the chunk with the given content hash.
+    pub fn record(&self, xuid: u64, dimension: Dimension, coordinates: [i32; 2], hash: u64) {
+        let mut history = self.players.entry(xuid).or_default();
+        let sequence = history.next;
+        history.next += 1;
+        history.chunks.insert((dimension, coordinates), (hash, sequence));
+        history.prune();
+    }
+
+    /// Amount of chunks that are remembered for the player with the given XUID.
+    pub fn tracked(&self, xuid: u64) -> usize {
+        self.players.get(&xuid).map_or(0, |history| history.chunks.len())
+    }
+
+    /// Writes the history of every player to the file at `path`.
+    ///
+    /// Chunks are written as `[dimension, x, z, hash]` arrays, oldest first, so that the order in which they were
+    /// sent is restored by [`load`](Self::load).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let mut root = serde_json::Map::new();
+        for kv in &self.players {
+            let mut chunks: Vec<_> = kv.value().chunks.iter().collect();
+            chunks.sort_unstable_by_key(|(_, (_, sequence))| *sequence);
+
+            let chunks = chunks
+                .into_iter()
+                .map(|(&(dimension, [x, z]), &(hash, _))| serde_json::json!([dimension as u32, x, z, hash]))
+                .collect();
+
+            root.insert(kv.key().to_string(), serde_json::Value::Array(chunks));
+        }
+
+        std::fs::write(path, serde_json::to_vec(&serde_json::Value::Object(root))?)?;
+        Ok(())
+    }
+
+    /// Loads a history that was previously written using [`save`](Self::save).
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Replicator> {
+        let data = std::fs::read(path)?;
+        let serde_json::Value::Object(root) = serde_json::from_slice(&data)? else {
+            anyhow::bail!("Replication file does not contain a JSON object");
+        };
+
+        let replicator = Replicator::new();
+        for (xuid, chunks) in root {
+            let xuid: u64 = xuid.parse()?;
+            let serde_json::Value::Array(chunks) = chunks else {
+                anyhow::bail!("Chunks of player {xuid} are not a JSON array");
+            };
+
+            for chunk in chunks {
+                let parsed = chunk.as_array().and_then(|fields| match fields.as_slice() {
+                    [dimension, x, z, hash] => Some((
+                        u32::try_from(dimension.as_u64()?).ok()?,
+                        i32::try_from(x.as_i64()?).ok()?,
+                        i32::try_from(z.as_i64()?).ok()?,
+                        hash.as_u64()?,
+                    )),
+                    _ => None,
+                });
+
+                let Some((dimension, x, z, hash)) = parsed else {
+                    anyhow::bail!("Chunk {chunk} of player {xuid} is malformed");
+                };
+
+                replicator.record(xuid, Dimension::try_from(dimension)?, [x, z], hash);
+            }
+        }
+
+        Ok(replicator)
+    }
+}
@@ -0,0 +1,92 @@
+//! Keeps the world seed private.
+//!
+//! The seed is sent to every client in the [`StartGame`](proto::bedrock::StartGame) packet, but anyone that knows it
+//! can locate structures, ores and bases using seed cracking tools. The client does not need the real seed, so
+//! the server can send a different one while it keeps generating the world using the true seed.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use serde::Deserialize;
+use proto::bedrock::{Command, CommandOverload, CommandPermissionLevel};
+
+use crate::command::{self, HandlerOutput, HandlerResult, ParsedCommand};
+
+/// Which seed is sent to clients.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SeedPrivacy {
+    /// Send the real seed of the world.
+    Reveal,
+    /// Send a seed of 0.
+    #[default]
+    Hide,
+    /// Send a different seed to every client.
+    ///
+    /// The seed is derived from the real seed, the identity of the client and a secret that is generated on startup.
+    /// A client receives the same seed every time it joins while the server is running, but seeds cannot be compared
+    /// between clients to find the real one.
+    PerClient,
+}
+
+/// The seed of the world.
+#[derive(Debug)]
+pub struct WorldSeed {
+    /// The real seed used for generation.
+    seed: i64,
+    /// Secret that is mixed into the seeds sent to clients.
+    secret: u64,
+}
+
+impl WorldSeed {
+    /// Creates a new seed with a random secret.
+    pub fn new(seed: i64) -> WorldSeed {
+        WorldSeed { seed, secret: rand::random() }
+    }
+
+    /// Creates a new seed with the given secret.
+    pub const fn with_secret(seed: i64, secret: u64) -> WorldSeed {
+        WorldSeed { seed, secret }
+    }
+
+    /// The real seed of the world.
+    #[inline]
+    pub const fn value(&self) -> i64 {
+        self.seed
+    }
+
+    /// The seed that should be sent to a client with the given XUID.
+    pub fn for_client(&self, privacy: SeedPrivacy, xuid: u64) -> u64 {
+        match privacy {
+            SeedPrivacy::Reveal => self.seed as u64,
+            SeedPrivacy::Hide => 0,
+            SeedPrivacy::PerClient => {
+                let mut hasher = DefaultHasher::new();
+                self.secret.hash(&mut hasher);
+                self.seed.hash(&mut hasher);
+                xuid.hash(&mut hasher);
+
+                let seed = hasher.finish();
+                if seed == self.seed as u64 { !seed } else { seed }
+            }
+        }
+    }
+}
+
+/// Syntax of the `seed` command.
+pub(crate) fn command() -> Command {
+    Command {
+        aliases: Vec::new(),
+        description: "Shows the real seed of the world".to_owned(),
+        name: "seed".to_owned(),
+        overloads: vec![CommandOverload { parameters: Vec::new() }],
+        permission_level: CommandPermissionLevel::Admin,
+    }
+}
+
+/// Handler of the `seed` command.
+///
+/// Only admins can view the seed, which is enforced by the command service before this is called.
+pub(crate) fn handle_command(_input: ParsedCommand, ctx: &command::Context) -> HandlerResult {
+    HandlerOutput::new().message(format!("Seed: {}", ctx.instance.level().seed().value())).success()
+}
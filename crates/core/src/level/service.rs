@@ -2,33 +2,48 @@ use super::io::sink::RegionSink;
 use super::io::stream::{IndexedSubChunk, RegionIndex};
 use std::{
     any::TypeId,
+    path::{Path, PathBuf},
     sync::{Arc, OnceLock, Weak},
+    time::Instant,
 };
 
 use dashmap::DashMap;
-use level::{provider::Provider, SubChunk};
+use level::{provider::{ActorData, Provider}, SubChunk};
 use proto::types::Dimension;
 use rayon::iter::ParallelIterator;
 use tokio::sync::mpsc::{self, error::SendError};
+use tokio::time::MissedTickBehavior;
 use tokio_util::sync::CancellationToken;
 use util::{Joinable, Vector};
 
 use crate::instance::Instance;
 
 use super::{
+    autosave::Autosave,
+    chunks::{self, LoadedChunks},
     io::{region::Region, sink::Collector, stream::RegionStream},
-    rule::{Rule, RuleValue},
+    replicator::Replicator,
+    rule::{DaylightCycle, Rule, RuleValue},
+    sleep::Sleepers,
+    stats::ChunkStats,
+    throttle::{Throttle, TICK_INTERVAL},
+    seed::WorldSeed,
+    time::WorldTime,
 };
 
 pub struct ServiceOptions {
     pub instance_token: CancellationToken,
     pub level_path: String,
+    /// Secret mixed into the seeds sent to clients, generated randomly if not set.
+    pub seed_secret: Option<u64>,
 }
 
 /// Threshold for the service to switch from singular to batching mode.
 /// Any requests with more chunks than specified in this threshold will be processed
 /// with a parallel iterator and threadpool.
 const REGION_PARALLEL_THRESHOLD: usize = 100;
+/// Name of the file in the level directory that the chunks sent to each player are stored in.
+const REPLICATION_FILE: &str = "replication.json";
 
 /// Manages the world of the server.
 pub struct Service {
@@ -46,11 +61,42 @@ pub struct Service {
     /// Current gamerule values.
     /// The gamerules are stored by TypeId to allow for user-defined gamerules.
     gamerules: DashMap<TypeId, RuleValue>,
+    /// Reduces background work when the server is overloaded.
+    throttle: Throttle,
+    /// Current time in the level.
+    time: WorldTime,
+    /// Seed that the level was generated with.
+    seed: WorldSeed,
+    /// Players that are currently asleep.
+    sleepers: Sleepers,
+    /// Activity counters of every chunk.
+    chunk_stats: ChunkStats,
+    /// Chunks that are simulated because players are nearby.
+    loaded_chunks: LoadedChunks,
+    /// Decides when the level is saved.
+    autosave: Autosave,
+    /// Chunks that have been sent to each player.
+    replicator: Replicator,
+    /// Location that the replicator is saved to.
+    replication_path: PathBuf,
 }
 
 impl Service {
     pub(crate) fn new(options: ServiceOptions) -> anyhow::Result<Arc<Service>> {
         let provider = Arc::new(level::provider::Provider::open(&options.level_path)?);
+        let time = provider.settings().map_or(0, |settings| settings.time);
+        let seed = provider.settings().map_or(0, |settings| settings.random_seed);
+
+        // Losing the history only means that players receive every chunk again.
+        let replication_path = Path::new(&options.level_path).join(REPLICATION_FILE);
+        let replicator = if replication_path.exists() {
+            Replicator::load(&replication_path).unwrap_or_else(|err| {
+                tracing::warn!("Unable to load chunk replication history: {err:#}");
+                Replicator::new()
+            })
+        } else {
+            Replicator::new()
+        };
 
         let service = Arc::new(Service {
             collector: Collector::new(Arc::clone(&provider), options.instance_token.clone(), 100),
@@ -59,7 +105,19 @@ impl Service {
             instance: OnceLock::new(),
             provider,
             gamerules: DashMap::new(),
+            throttle: Throttle::new(),
+            time: WorldTime::new(time),
+            seed: options.seed_secret.map_or_else(|| WorldSeed::new(seed), |secret| WorldSeed::with_secret(seed, secret)),
+            sleepers: Sleepers::default(),
+            chunk_stats: ChunkStats::default(),
+            loaded_chunks: LoadedChunks::default(),
+            autosave: Autosave::default(),
+            replicator,
+            replication_path,
         });
+
+        util::task::spawn("level::ticker", Service::ticker(Arc::downgrade(&service), service.instance_token.clone()));
+
         Ok(service)
     }
 
@@ -70,12 +128,171 @@ impl Service {
             .map_err(|_| anyhow::anyhow!("Level service instance was already set"))
     }
 
+    /// Returns the throttling state of the level.
+    ///
+    /// Background tasks such as chunk sending and autosaving should check this before doing any work.
+    #[inline]
+    pub const fn throttle(&self) -> &Throttle {
+        &self.throttle
+    }
+
+    /// Returns the current time in the level.
+    #[inline]
+    pub const fn time(&self) -> &WorldTime {
+        &self.time
+    }
+
+    /// Returns the seed of the level.
+    #[inline]
+    pub const fn seed(&self) -> &WorldSeed {
+        &self.seed
+    }
+
+    /// Returns the players that are currently asleep.
+    #[inline]
+    pub const fn sleepers(&self) -> &Sleepers {
+        &self.sleepers
+    }
+
+    /// Returns the chunks that have been sent to each player.
+    #[inline]
+    pub const fn replicator(&self) -> &Replicator {
+        &self.replicator
+    }
+
+    /// Returns the activity counters of every chunk.
+    #[inline]
+    pub const fn chunk_stats(&self) -> &ChunkStats {
+        &self.chunk_stats
+    }
+
+    /// Returns the chunks that are currently loaded.
+    #[inline]
+    pub const fn loaded_chunks(&self) -> &LoadedChunks {
+        &self.loaded_chunks
+    }
+
+    /// Moves the simulated area of a viewer from the chunk `old` to the chunk `new`.
+    ///
+    /// Chunks that enter the area are loaded and chunks that no viewer is near anymore are unloaded.
+    /// `None` means that the viewer has no area, such as before it has spawned or after it left.
+    pub fn move_viewer(&self, old: Option<(Dimension, [i32; 2])>, new: Option<(Dimension, [i32; 2])>) {
+        if old == new {
+            return
+        }
+
+        if let Some((dimension, center)) = new {
+            for coordinates in chunks::simulated_area(center) {
+                if !old.is_some_and(|old| chunks::is_simulated(old, dimension, coordinates)) && self.loaded_chunks.retain(dimension, coordinates) {
+                    self.on_chunk_load(dimension, coordinates);
+                }
+            }
+        }
+
+        if let Some((dimension, center)) = old {
+            for coordinates in chunks::simulated_area(center) {
+                if new.is_some_and(|new| chunks::is_simulated(new, dimension, coordinates)) {
+                    continue
+                }
+
+                if let Some(actors) = self.loaded_chunks.release(dimension, coordinates) {
+                    if let Err(err) = self.save_actors(Vector::from(coordinates), dimension, &actors) {
+                        tracing::error!("Failed to save actors of chunk {coordinates:?} in {dimension:?}: {err:#}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the actors of a chunk that has just been loaded.
+    fn on_chunk_load(&self, dimension: Dimension, coordinates: [i32; 2]) {
+        match self.load_actors(Vector::from(coordinates), dimension) {
+            Ok(actors) => {
+                self.loaded_chunks.set_actors(dimension, coordinates, actors);
+            }
+            Err(err) => tracing::error!("Failed to load actors of chunk {coordinates:?} in {dimension:?}: {err:#}"),
+        }
+    }
+
+    /// Sends the chunks that came into view of each player, limited by the [`Throttle`].
+    fn send_chunks(&self) {
+        let Some(instance) = self.instance.get().and_then(Weak::upgrade) else {
+            return
+        };
+
+        let limit = self.throttle.chunk_sends_per_tick();
+        for client in instance.clients().connected() {
+            if let Err(err) = client.send_queued_chunks(limit) {
+                tracing::error!("Failed to send chunks: {err:#}");
+            }
+        }
+    }
+
+    /// Writes the state of the level that is kept in memory to disk.
+    ///
+    /// This runs periodically unless autosaves are deferred by the [`Throttle`], and when the service shuts down.
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.provider.save_time(self.time.get())?;
+        self.replicator.save(&self.replication_path)?;
+        self.save_loaded_actors()
+    }
+
+    /// Saves the actors of every loaded chunk without unloading them.
+    pub fn save_loaded_actors(&self) -> anyhow::Result<()> {
+        for (dimension, coordinates, actors) in self.loaded_chunks.all_actors() {
+            self.save_actors(Vector::from(coordinates), dimension, &actors)?;
+        }
+
+        Ok(())
+    }
+
+    /// Measures the duration of each tick and feeds it to the [`Throttle`].
+    ///
+    /// This also advances the time of day, unless the [`DaylightCycle`] gamerule is disabled, and ticks the
+    /// loaded chunks.
+    async fn ticker(service: Weak<Service>, instance_token: CancellationToken) {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut last_tick = Instant::now();
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let Some(service) = service.upgrade() else { break };
+
+                    let now = Instant::now();
+                    service.throttle.record_tick(now - last_tick);
+                    last_tick = now;
+
+                    if service.gamerule::<DaylightCycle>() {
+                        service.time.advance();
+                    }
+
+                    service.loaded_chunks.tick(&service.chunk_stats);
+                    service.send_chunks();
+
+                    if service.autosave.tick(service.throttle.autosave_deferred()) {
+                        let service = Arc::clone(&service);
+                        tokio::task::spawn_blocking(move || {
+                            if let Err(err) = service.save() {
+                                tracing::error!("Autosave failed: {err:#}");
+                            }
+                        });
+                    }
+                },
+                _ = instance_token.cancelled() => break
+            }
+        }
+    }
+
     /// Requests chunks using the specified region iterator.
+    ///
+    /// Large regions are processed in parallel, unless the server is currently throttled.
     pub fn region<R: Region>(self: &Arc<Service>, region: R) -> RegionStream
     where
         R::IntoIter: Send,
     {
-        if region.len() >= REGION_PARALLEL_THRESHOLD {
+        if region.len() >= REGION_PARALLEL_THRESHOLD && !self.throttle.is_throttled() {
             self.request_parallel_region(region)
         } else {
             self.request_sequential_region(region)
@@ -162,6 +379,24 @@ impl Service {
         }
     }
 
+    /// Loads all actors stored in the given chunk.
+    ///
+    /// This is called when a chunk is loaded so that dropped items, mobs and other actors persist across restarts.
+    pub fn load_actors(&self, coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<Vec<(i64, ActorData)>> {
+        let actors = self.provider.actors(coordinates.clone(), dimension)?;
+        self.chunk_stats.set_entities(dimension, coordinates, actors.len());
+
+        Ok(actors)
+    }
+
+    /// Replaces the actors stored in the given chunk.
+    ///
+    /// This is called when a chunk is unloaded and when the level service shuts down.
+    pub fn save_actors(&self, coordinates: Vector<i32, 2>, dimension: Dimension, actors: &[(i64, ActorData)]) -> anyhow::Result<()> {
+        self.chunk_stats.set_entities(dimension, coordinates.clone(), actors.len());
+        self.provider.save_actors(coordinates, dimension, actors)
+    }
+
     /// Sets the value of the given gamerule, returning the old value.
     ///
     /// Instead of referring to the gamerules by name, I decided to use generics instead.
@@ -179,7 +414,7 @@ impl Service {
         let value = RuleValue::from(value);
         let old = self.gamerules.insert(TypeId::of::<R>(), value);
 
-        let Some(old) = old else { return R::Value::default() };
+        let Some(old) = old else { return R::default() };
 
         old.into()
     }
@@ -199,7 +434,7 @@ impl Service {
         RuleValue: From<R::Value>, // Ensure that the gamerule has a valid value type.
     {
         let Some(kv) = self.gamerules.get(&TypeId::of::<R>()) else {
-            return R::Value::default();
+            return R::default();
         };
 
         (*kv.value()).into()
@@ -208,6 +443,7 @@ impl Service {
 
 impl Joinable for Service {
     async fn join(&self) -> anyhow::Result<()> {
+        self.save()?;
         self.collector.join().await?;
 
         Ok(())
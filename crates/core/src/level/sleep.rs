@@ -0,0 +1,235 @@
+//! Skipping the night by sleeping.
+//!
+//! Players that lie down in a bed are registered as sleepers of their dimension. Once enough players in the overworld
+//! are asleep, as determined by the [`PlayersSleepingPercentage`] gamerule, and they have been sleeping for a few
+//! seconds, the time is advanced to the next morning and everyone is woken up.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use proto::bedrock::{Animate, AnimateAction, LevelEvent, LevelEventType, SetTime};
+use proto::types::Dimension;
+use raknet::Recipients;
+use tokio_util::sync::CancellationToken;
+use util::Vector;
+
+use crate::instance::Instance;
+use crate::net::BedrockClient;
+
+use super::rule::PlayersSleepingPercentage;
+
+/// How often the monitor checks whether the night can be skipped.
+const MONITOR_INTERVAL: Duration = Duration::from_millis(250);
+/// How long the required amount of players has to be asleep before the night is skipped.
+///
+/// This matches the 100 ticks that vanilla waits.
+const SLEEP_DURATION: Duration = Duration::from_secs(5);
+
+/// Keeps track of the players that are currently asleep.
+#[derive(Debug, Default)]
+pub struct Sleepers {
+    /// Dimension of every sleeping player, indexed by XUID.
+    sleeping: DashMap<u64, Dimension>,
+}
+
+impl Sleepers {
+    /// Registers a player as asleep.
+    ///
+    /// Returns `false` if the player was already sleeping.
+    pub fn start(&self, xuid: u64, dimension: Dimension) -> bool {
+        self.sleeping.insert(xuid, dimension).is_none()
+    }
+
+    /// Wakes up a player.
+    ///
+    /// Returns `false` if the player was not sleeping.
+    pub fn stop(&self, xuid: u64) -> bool {
+        self.sleeping.remove(&xuid).is_some()
+    }
+
+    /// Whether the given player is currently asleep.
+    pub fn is_sleeping(&self, xuid: u64) -> bool {
+        self.sleeping.contains_key(&xuid)
+    }
+
+    /// Amount of players sleeping in the given dimension.
+    pub fn count(&self, dimension: Dimension) -> usize {
+        self.sleeping.iter().filter(|r| *r.value() == dimension).count()
+    }
+
+    /// Wakes up all players in the given dimension, returning their XUIDs.
+    pub fn wake_all(&self, dimension: Dimension) -> Vec<u64> {
+        let woken = self.sleeping.iter().filter(|r| *r.value() == dimension).map(|r| *r.key()).collect::<Vec<_>>();
+        for xuid in &woken {
+            self.sleeping.remove(xuid);
+        }
+
+        woken
+    }
+
+    /// Only keeps the sleepers for which the predicate returns `true`.
+    pub fn retain<F: FnMut(u64) -> bool>(&self, mut predicate: F) {
+        self.sleeping.retain(|xuid, _| predicate(*xuid));
+    }
+}
+
+/// Amount of players that have to sleep to skip the night, given the amount of players that could sleep
+/// and the value of the [`PlayersSleepingPercentage`] gamerule.
+///
+/// At least one player always has to sleep.
+pub fn required_sleepers(players: usize, percentage: i32) -> usize {
+    let percentage = percentage.clamp(0, 100) as usize;
+    (players * percentage).div_ceil(100).max(1)
+}
+
+/// Progress towards skipping the night in a dimension.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+struct SleepStatus {
+    /// Amount of players that are asleep.
+    sleeping: usize,
+    /// Amount of players that need to be asleep.
+    required: usize,
+}
+
+impl SleepStatus {
+    /// Determines the status of the given dimension.
+    ///
+    /// AFK players are not required to sleep if the server is configured to exclude them.
+    fn of(instance: &Instance, clients: &[Arc<BedrockClient>], dimension: Dimension) -> SleepStatus {
+        let sleepers = instance.level().sleepers();
+        let exclude_afk = instance.config().afk().exclude_from_sleep;
+
+        let mut players = 0;
+        let mut sleeping = 0;
+        for client in clients {
+            if !client.dimension().is_ok_and(|d| d == dimension) {
+                continue
+            }
+
+            let Ok(xuid) = client.xuid() else { continue };
+            if sleepers.is_sleeping(xuid) {
+                sleeping += 1;
+            } else if exclude_afk && client.is_afk() {
+                continue
+            }
+
+            players += 1;
+        }
+
+        let percentage = instance.level().gamerule::<PlayersSleepingPercentage>();
+        SleepStatus { sleeping, required: required_sleepers(players, percentage) }
+    }
+
+    /// Whether enough players are asleep to skip the night.
+    const fn is_met(self) -> bool {
+        self.sleeping > 0 && self.sleeping >= self.required
+    }
+
+    /// Data of the [`LevelEventType::SleepingPlayers`] event, which contains both counts.
+    const fn event_data(self) -> i32 {
+        (self.required << 16 | self.sleeping) as i32
+    }
+}
+
+impl BedrockClient {
+    /// Puts the player to sleep.
+    ///
+    /// Sleeping is only possible in the overworld at night.
+    pub(crate) fn start_sleeping(&self) -> anyhow::Result<()> {
+        let dimension = self.dimension()?;
+        let instance = self.instance();
+        let level = instance.level();
+
+        if dimension != Dimension::Overworld || !level.time().is_night() {
+            tracing::debug!("{} attempted to sleep outside of the overworld night", self.name()?);
+            return Ok(())
+        }
+
+        level.sleepers().start(self.xuid()?, dimension);
+        Ok(())
+    }
+
+    /// Wakes the player up.
+    pub(crate) fn stop_sleeping(&self) -> anyhow::Result<()> {
+        self.instance().level().sleepers().stop(self.xuid()?);
+        Ok(())
+    }
+}
+
+/// Sends the amount of sleeping players to every player in the dimension.
+fn notify(instance: &Instance, dimension: Dimension, status: SleepStatus) {
+    let event = LevelEvent {
+        event_type: LevelEventType::SleepingPlayers,
+        position: Vector::from([0.0, 0.0, 0.0]),
+        event_data: status.event_data(),
+    };
+
+    if let Err(err) = instance.clients().broadcast_to(event, Recipients::in_dimension(dimension)) {
+        tracing::error!("Failed to send sleeping players: {err:#}");
+    }
+}
+
+/// Advances the time to the next morning and wakes up all sleeping players.
+fn skip_night(instance: &Instance, clients: &[Arc<BedrockClient>]) -> anyhow::Result<()> {
+    let level = instance.level();
+    let morning = level.time().skip_to_morning();
+    tracing::debug!("Enough players are asleep, skipping the night");
+
+    instance.clients().broadcast(SetTime { time: morning as i32 })?;
+
+    let woken = level.sleepers().wake_all(Dimension::Overworld);
+    for client in clients {
+        if !client.xuid().is_ok_and(|xuid| woken.contains(&xuid)) {
+            continue
+        }
+
+        instance.clients().broadcast(Animate {
+            action_type: AnimateAction::StopSleep,
+            runtime_id: client.runtime_id()?,
+            rowing_time: 0.0,
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Checks whether enough players are sleeping to skip the night and keeps players informed of the progress.
+pub(crate) async fn monitor(instance: Arc<Instance>, token: CancellationToken) {
+    let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+    let mut last_status = SleepStatus::default();
+    let mut ready_since = None;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            () = token.cancelled() => break
+        }
+
+        let clients = instance.clients().connected();
+
+        // Players that disconnect while sleeping should not keep counting towards the total.
+        instance.level().sleepers().retain(|xuid| clients.iter().any(|c| c.xuid().is_ok_and(|x| x == xuid)));
+
+        let status = SleepStatus::of(&instance, &clients, Dimension::Overworld);
+        if status != last_status {
+            notify(&instance, Dimension::Overworld, status);
+            last_status = status;
+        }
+
+        if !status.is_met() {
+            ready_since = None;
+            continue
+        }
+
+        let since = *ready_since.get_or_insert_with(Instant::now);
+        if since.elapsed() < SLEEP_DURATION {
+            continue
+        }
+
+        ready_since = None;
+        if let Err(err) = skip_night(&instance, &clients) {
+            tracing::error!("Failed to skip the night: {err:#}");
+        }
+    }
+}
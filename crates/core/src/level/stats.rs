@@ -0,0 +1,197 @@
+//! Per-chunk statistics used to find hot spots in the world.
+//!
+//! Every chunk that sees activity gets a set of counters that are updated incrementally as the server runs.
+//! Owners can use the `chunkstats` command to find the chunks with the most block updates, entities or tick cost,
+//! which usually points to lag machines and dense farms.
+
+use std::sync::atomic::{AtomicI64, AtomicU64};
+use std::time::Duration;
+
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use prometheus_client::metrics::{counter::Counter, gauge::Gauge};
+use proto::bedrock::{Command, CommandDataType, CommandOverload, CommandParameter, CommandPermissionLevel};
+use proto::types::Dimension;
+use util::{BlockPosition, Vector};
+
+use crate::command::{self, HandlerOutput, HandlerResult, ParsedCommand};
+
+lazy_static! {
+    #[doc(hidden)]
+    pub static ref BLOCK_UPDATES_METRIC: Counter::<u64, AtomicU64> = Counter::default();
+    #[doc(hidden)]
+    pub static ref TRACKED_CHUNKS_METRIC: Gauge::<i64, AtomicI64> = Gauge::default();
+}
+
+/// Weight of the newest sample in the average tick cost.
+const TICK_COST_SMOOTHING: f64 = 0.1;
+/// Amount of chunks listed by the `chunkstats` command.
+const LISTED_CHUNKS: usize = 10;
+
+/// Counters of a single chunk.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ChunkCounters {
+    /// Amount of blocks that have been placed or broken in the chunk.
+    pub block_updates: u64,
+    /// Amount of entities that were in the chunk when it was last saved or loaded.
+    pub entities: usize,
+    /// Amount of times the chunk has been ticked.
+    pub ticks: u64,
+    /// Exponential moving average of the time spent ticking the chunk, in microseconds.
+    pub tick_cost: f64,
+}
+
+/// Counter that chunks can be ordered by.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChunkStat {
+    /// Order by [`ChunkCounters::block_updates`].
+    BlockUpdates,
+    /// Order by [`ChunkCounters::entities`].
+    Entities,
+    /// Order by [`ChunkCounters::tick_cost`].
+    TickCost,
+}
+
+impl ChunkStat {
+    /// Value of this counter in the given chunk.
+    const fn of(self, counters: &ChunkCounters) -> f64 {
+        match self {
+            Self::BlockUpdates => counters.block_updates as f64,
+            Self::Entities => counters.entities as f64,
+            Self::TickCost => counters.tick_cost,
+        }
+    }
+}
+
+/// Statistics of every chunk that has seen activity, indexed by dimension and chunk coordinates.
+#[derive(Debug, Default)]
+pub struct ChunkStats {
+    chunks: DashMap<(Dimension, [i32; 2]), ChunkCounters>,
+}
+
+impl ChunkStats {
+    /// Updates the counters of a chunk, creating them if the chunk was not tracked yet.
+    fn update<F: FnOnce(&mut ChunkCounters)>(&self, dimension: Dimension, coordinates: Vector<i32, 2>, f: F) {
+        let mut entry = self.chunks.entry((dimension, [coordinates.x, coordinates.y])).or_insert_with(|| {
+            TRACKED_CHUNKS_METRIC.inc();
+            ChunkCounters::default()
+        });
+
+        f(&mut entry);
+    }
+
+    /// Records a block that was placed or broken at the given position.
+    pub fn record_block_update(&self, dimension: Dimension, position: &BlockPosition) {
+        BLOCK_UPDATES_METRIC.inc();
+        self.update(dimension, chunk_of(position), |c| c.block_updates += 1);
+    }
+
+    /// Sets the amount of entities in a chunk.
+    pub fn set_entities(&self, dimension: Dimension, coordinates: Vector<i32, 2>, entities: usize) {
+        self.update(dimension, coordinates, |c| c.entities = entities);
+    }
+
+    /// Records how long it took to tick a chunk.
+    pub fn record_tick(&self, dimension: Dimension, coordinates: Vector<i32, 2>, elapsed: Duration) {
+        let cost = elapsed.as_secs_f64() * 1_000_000.0;
+        self.update(dimension, coordinates, |c| {
+            c.tick_cost = if c.ticks == 0 { cost } else { (cost - c.tick_cost).mul_add(TICK_COST_SMOOTHING, c.tick_cost) };
+            c.ticks += 1;
+        });
+    }
+
+    /// Returns the counters of a chunk, if it has seen any activity.
+    pub fn get(&self, dimension: Dimension, coordinates: Vector<i32, 2>) -> Option<ChunkCounters> {
+        self.chunks.get(&(dimension, [coordinates.x, coordinates.y])).map(|r| *r)
+    }
+
+    /// Returns the `limit` chunks with the highest value of the given counter, highest first.
+    pub fn hottest(&self, stat: ChunkStat, limit: usize) -> Vec<(Dimension, Vector<i32, 2>, ChunkCounters)> {
+        let mut chunks = self
+            .chunks
+            .iter()
+            .map(|r| (r.key().0, Vector::from(r.key().1), *r.value()))
+            .collect::<Vec<_>>();
+
+        chunks.sort_by(|a, b| stat.of(&b.2).total_cmp(&stat.of(&a.2)));
+        chunks.truncate(limit);
+        chunks
+    }
+
+    /// Amount of chunks that are being tracked.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Whether no chunks are being tracked.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Clears all statistics.
+    pub fn reset(&self) {
+        self.chunks.clear();
+        TRACKED_CHUNKS_METRIC.set(0);
+    }
+}
+
+/// Coordinates of the chunk that contains the given block.
+fn chunk_of(position: &BlockPosition) -> Vector<i32, 2> {
+    Vector::from([position.x >> 4, position.z >> 4])
+}
+
+/// Syntax of the `chunkstats` command.
+pub(crate) fn command() -> Command {
+    Command {
+        aliases: Vec::new(),
+        description: "Lists the chunks with the most activity".to_owned(),
+        name: "chunkstats".to_owned(),
+        overloads: vec![CommandOverload {
+            parameters: vec![CommandParameter {
+                name: "order".to_owned(),
+                command_enum: None,
+                data_type: CommandDataType::String,
+                optional: true,
+                options: 0,
+                suffix: "".to_owned(),
+            }],
+        }],
+        permission_level: CommandPermissionLevel::Admin,
+    }
+}
+
+/// Handler of the `chunkstats` command.
+///
+/// The chunks are ordered by block updates by default, `entities`, `cost` and `reset` are accepted as well.
+pub(crate) fn handle_command(input: ParsedCommand, ctx: &command::Context) -> HandlerResult {
+    let stats = ctx.instance.level().chunk_stats();
+    let stat = match input.parameters.get("order").and_then(command::ParsedArgument::as_string) {
+        None | Some("updates") => ChunkStat::BlockUpdates,
+        Some("entities") => ChunkStat::Entities,
+        Some("cost") => ChunkStat::TickCost,
+        Some("reset") => {
+            stats.reset();
+            return HandlerOutput::new().message("Chunk statistics have been reset").success()
+        }
+        Some(other) => {
+            return HandlerOutput::new()
+                .message(format!("Unknown order '{other}', expected updates, entities, cost or reset"))
+                .error()
+        }
+    };
+
+    let hottest = stats.hottest(stat, LISTED_CHUNKS);
+    if hottest.is_empty() {
+        return HandlerOutput::new().message("No chunk activity has been recorded yet").success()
+    }
+
+    let mut message = format!("Top {} of {} tracked chunks:", hottest.len(), stats.len());
+    for (dimension, coordinates, counters) in hottest {
+        message.push_str(&format!(
+            "\n{dimension:?} [{}, {}]: {} block updates, {} entities, {:.1} us/tick",
+            coordinates.x, coordinates.y, counters.block_updates, counters.entities, counters.tick_cost
+        ));
+    }
+
+    HandlerOutput::new().message(message).success()
+}
@@ -0,0 +1,107 @@
+//! Throttling of background level work when the server is overloaded.
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use lazy_static::lazy_static;
+use prometheus_client::metrics::{counter::Counter, gauge::Gauge};
+
+lazy_static! {
+    #[doc(hidden)]
+    pub static ref THROTTLED_METRIC: Gauge::<i64, AtomicI64> = Gauge::default();
+    #[doc(hidden)]
+    pub static ref SLOW_TICKS_METRIC: Counter::<u64, AtomicU64> = Counter::default();
+}
+
+/// Interval at which the level is ticked.
+pub const TICK_INTERVAL: Duration = Duration::from_millis(1000 / 20);
+/// Maximum duration of a single tick, measured as the time between two consecutive ticks.
+/// Ticks that take longer than this are considered slow.
+///
+/// This leaves a bit of room on top of [`TICK_INTERVAL`] to account for timer jitter.
+pub const TICK_BUDGET: Duration = Duration::from_millis(60);
+/// Amount of consecutive slow ticks after which background work is throttled.
+const THROTTLE_AFTER: u32 = 20;
+/// Amount of consecutive ticks within budget after which throttling is lifted again.
+///
+/// This is deliberately longer than [`THROTTLE_AFTER`] to prevent the server from rapidly
+/// switching between states when it is close to its limit.
+const RECOVER_AFTER: u32 = 100;
+/// Maximum amount of chunks sent to a single client per tick under normal conditions.
+const CHUNK_SENDS_PER_TICK: usize = 16;
+/// Maximum amount of chunks sent to a single client per tick while throttled.
+const THROTTLED_CHUNK_SENDS_PER_TICK: usize = 4;
+
+/// Keeps track of tick durations and decides whether background work should be reduced.
+///
+/// While throttled, fewer chunks are sent per tick and autosaves are deferred until the server has recovered.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    /// Whether background work is currently throttled.
+    throttled: AtomicBool,
+    /// Amount of consecutive ticks that exceeded the budget.
+    slow_streak: AtomicU32,
+    /// Amount of consecutive ticks that stayed within budget.
+    fast_streak: AtomicU32,
+}
+
+impl Throttle {
+    /// Creates a new, unthrottled state.
+    pub const fn new() -> Self {
+        Self {
+            throttled: AtomicBool::new(false),
+            slow_streak: AtomicU32::new(0),
+            fast_streak: AtomicU32::new(0),
+        }
+    }
+
+    /// Records the duration of a tick and updates the throttling state accordingly.
+    pub fn record_tick(&self, elapsed: Duration) {
+        if elapsed > TICK_BUDGET {
+            SLOW_TICKS_METRIC.inc();
+            self.fast_streak.store(0, Ordering::Relaxed);
+
+            let streak = self.slow_streak.fetch_add(1, Ordering::Relaxed) + 1;
+            if streak >= THROTTLE_AFTER && !self.throttled.swap(true, Ordering::Relaxed) {
+                THROTTLED_METRIC.set(1);
+                tracing::warn!(
+                    "Server is overloaded ({} ms tick, budget is {} ms). Throttling background level work",
+                    elapsed.as_millis(),
+                    TICK_BUDGET.as_millis()
+                );
+            }
+        } else {
+            self.slow_streak.store(0, Ordering::Relaxed);
+
+            let streak = self.fast_streak.fetch_add(1, Ordering::Relaxed) + 1;
+            if streak >= RECOVER_AFTER && self.throttled.swap(false, Ordering::Relaxed) {
+                THROTTLED_METRIC.set(0);
+                tracing::info!("Server has recovered. Resuming background level work");
+            }
+        }
+    }
+
+    /// Whether background work is currently throttled.
+    #[inline]
+    pub fn is_throttled(&self) -> bool {
+        self.throttled.load(Ordering::Relaxed)
+    }
+
+    /// Whether autosaves should be postponed.
+    #[inline]
+    pub fn autosave_deferred(&self) -> bool {
+        self.is_throttled()
+    }
+
+    /// Maximum amount of chunks that should be sent to a single client this tick.
+    #[inline]
+    pub fn chunk_sends_per_tick(&self) -> usize {
+        if self.is_throttled() {
+            THROTTLED_CHUNK_SENDS_PER_TICK
+        } else {
+            CHUNK_SENDS_PER_TICK
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Time of day of the level.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// Amount of ticks in a full day.
+pub const DAY_LENGTH: i64 = 24_000;
+/// Time of day at which players are allowed to start sleeping.
+pub const NIGHT_START: i64 = 12_542;
+/// Time of day after which players can no longer sleep.
+pub const NIGHT_END: i64 = 23_459;
+
+/// Keeps track of the time in the level.
+///
+/// The time is counted in ticks since the creation of the level and advances by one every tick while the
+/// [`DaylightCycle`](super::rule::DaylightCycle) gamerule is enabled.
+#[derive(Debug, Default)]
+pub struct WorldTime {
+    ticks: AtomicI64,
+}
+
+impl WorldTime {
+    /// Creates a new clock starting at the given time.
+    pub const fn new(ticks: i64) -> WorldTime {
+        WorldTime { ticks: AtomicI64::new(ticks) }
+    }
+
+    /// Total amount of ticks since the creation of the level.
+    #[inline]
+    pub fn get(&self) -> i64 {
+        self.ticks.load(Ordering::Relaxed)
+    }
+
+    /// Sets the current time.
+    #[inline]
+    pub fn set(&self, ticks: i64) {
+        self.ticks.store(ticks, Ordering::Relaxed);
+    }
+
+    /// Advances the time by a single tick.
+    #[inline]
+    pub(super) fn advance(&self) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Time within the current day, in the range `0..DAY_LENGTH`.
+    #[inline]
+    pub fn time_of_day(&self) -> i64 {
+        self.get().rem_euclid(DAY_LENGTH)
+    }
+
+    /// Whether it is currently late enough for players to sleep.
+    #[inline]
+    pub fn is_night(&self) -> bool {
+        (NIGHT_START..=NIGHT_END).contains(&self.time_of_day())
+    }
+
+    /// Skips ahead to the start of the next day, returning the new time.
+    pub fn skip_to_morning(&self) -> i64 {
+        let morning = (self.get().div_euclid(DAY_LENGTH) + 1) * DAY_LENGTH;
+        self.set(morning);
+
+        morning
+    }
+}
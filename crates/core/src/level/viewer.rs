@@ -9,12 +9,14 @@ use std::{
 use futures::{future, StreamExt};
 use level::SubChunk;
 use nohash_hasher::BuildNoHashHasher;
+use parking_lot::Mutex;
 use proto::{
     bedrock::{HeightmapType, SubChunkEntry, SubChunkResponse, SubChunkResult},
     types::Dimension,
 };
 use util::Vector;
 
+use super::chunks::ChunkQueue;
 use super::io::point::PointRegion;
 use super::io::r#box::BoxRegion;
 use super::net::column::ChunkColumn;
@@ -30,6 +32,10 @@ pub struct Viewer {
     // The current position of this viewer in chunk coordinates.
     current_x: AtomicI32,
     current_z: AtomicI32,
+    /// Chunk around which this viewer keeps chunks loaded, if it has a position yet.
+    simulated: Mutex<Option<(Dimension, [i32; 2])>>,
+    /// Chunks in view that have not been sent yet.
+    queue: Mutex<ChunkQueue>,
 }
 
 impl Viewer {
@@ -39,29 +45,63 @@ impl Viewer {
             radius: AtomicU16::new(0),
             current_x: AtomicI32::new(0),
             current_z: AtomicI32::new(0),
+            simulated: Mutex::new(None),
+            queue: Mutex::new(ChunkQueue::new()),
         }
     }
 
     /// Updates the position of this viewer.
-    pub fn update_position(&self, position: Vector<f32, 2>) {
+    ///
+    /// `position` contains the X and Z coordinates of the player. Chunks around the new position are loaded.
+    pub fn update_position(&self, position: Vector<f32, 2>, dimension: Dimension) {
         // Transform player coordinates to chunk coordinates.
-        let chunk_x = (position.x / 16.0).ceil() as i32;
-        let chunk_z = (position.y / 16.0).ceil() as i32;
+        let chunk_x = (position.x.floor() as i32) >> 4;
+        let chunk_z = (position.y.floor() as i32) >> 4;
 
         self.current_x.store(chunk_x, Ordering::Relaxed);
         self.current_z.store(chunk_z, Ordering::Relaxed);
 
+        let mut simulated = self.simulated.lock();
+        let old = simulated.replace((dimension, [chunk_x, chunk_z]));
+        self.service.move_viewer(old, *simulated);
+        drop(simulated);
+
+        self.queue.lock().update(dimension, [chunk_x, chunk_z], self.radius());
+
         // Update view if required
         self.on_view_update();
     }
 
+    /// The render distance of this viewer in chunks.
+    #[inline]
+    pub fn radius(&self) -> u16 {
+        self.radius.load(Ordering::Relaxed)
+    }
+
+    /// The chunk that this viewer is currently in.
+    #[inline]
+    pub fn chunk(&self) -> (i32, i32) {
+        (self.current_x.load(Ordering::Relaxed), self.current_z.load(Ordering::Relaxed))
+    }
+
     /// Updates the render distance of this viewer
     #[inline]
     pub fn update_radius(&self, radius: u16) {
         self.radius.store(radius, Ordering::Relaxed);
+
+        let mut queue = self.queue.lock();
+        if let Some((dimension, center, _)) = queue.view() {
+            queue.update(dimension, center, radius);
+        }
+        drop(queue);
         self.on_view_update();
     }
 
+    /// Takes up to `limit` chunks in view that have not been sent yet.
+    pub fn next_chunks(&self, limit: usize) -> Option<(Dimension, Vec<[i32; 2]>)> {
+        self.queue.lock().take(limit)
+    }
+
     fn create_entry(&self, base: Vector<i32, 3>, offset: ChunkOffset, full_chunk: &ChunkColumn) -> anyhow::Result<SubChunkEntry> {
         let absolute_y = base.y + offset.y as i32;
         let subchunk_index = full_chunk.y_to_index(absolute_y as i16);
@@ -163,3 +203,9 @@ impl Viewer {
         // });
     }
 }
+
+impl Drop for Viewer {
+    fn drop(&mut self) {
+        self.service.move_viewer(self.simulated.get_mut().take(), None);
+    }
+}
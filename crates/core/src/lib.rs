@@ -46,13 +46,27 @@
 #![allow(dead_code)]
 #![allow(clippy::use_self)]
 
+pub mod afk;
+pub mod capacity;
+pub mod clock;
 pub mod command;
 pub mod config;
+pub mod cooldown;
+pub mod entity;
+pub mod extension;
 pub mod forms;
 pub mod instance;
 pub mod item;
 pub mod level;
 pub mod net;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod report;
+pub mod restart;
+pub mod setup;
+pub mod tasks;
+#[cfg(feature = "webhooks")]
+pub mod webhook;
 
 #[cfg(test)]
 mod test;
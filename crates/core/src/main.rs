@@ -1,16 +1,31 @@
 #![allow(dead_code)]
 
-use std::net::SocketAddrV4;
-use std::str::FromStr;
+use std::path::Path;
 use std::sync::atomic::{AtomicU16, Ordering};
 
 use anyhow::Context;
 use tokio::runtime;
 
 use mirai::instance::Instance;
+use mirai::setup;
 use util::Joinable;
 
 fn main() -> anyhow::Result<()> {
+    let config_path = Path::new(setup::CONFIG_FILE);
+    let setup = setup::load_or_create(config_path)?;
+    if setup.first_run {
+        println!("{}\n", setup::next_steps(&setup, config_path));
+    }
+
+    if !setup.config.accept_eula {
+        if !setup::prompt_eula()? {
+            println!("The server cannot start until the EULA has been accepted in {}", config_path.display());
+            return Ok(())
+        }
+
+        setup::accept_eula(config_path)?;
+    }
+
     let runtime = runtime::Builder::new_multi_thread()
         .enable_io()
         .enable_time()
@@ -21,24 +36,32 @@ fn main() -> anyhow::Result<()> {
         .build()
         .expect("Failed to build runtime");
 
-    init_logging().context("Unable to initialise logging")?;
+    init_logging(&setup.config.log_level).context("Unable to initialise logging")?;
 
-    let builder = Instance::builder().ipv4_addr(SocketAddrV4::from_str("0.0.0.0:19132").unwrap());
+    let builder = setup.config.apply(Instance::builder());
 
-    runtime.block_on(async move {
+    let restart = runtime.block_on(async move {
         let instance = builder.build().await?;
         if let Err(err) = instance.start() {
             tracing::error!("Failed to start server: {err:#}");
             return Err(err);
         }
 
-        instance.join().await
-    })
+        instance.join().await?;
+        Ok(instance.restart_requested())
+    })?;
+
+    if restart {
+        // Let the supervisor know that the server should be started again.
+        std::process::exit(mirai::restart::RESTART_EXIT_CODE);
+    }
+
+    Ok(())
 }
 
 /// Initialises logging with tokio-console.
 #[cfg(feature = "tokio-console")]
-fn init_logging() -> anyhow::Result<()> {
+fn init_logging(_default_level: &str) -> anyhow::Result<()> {
     use std::time::Duration;
     use tracing_subscriber::layer::SubscriberExt;
     use tracing_subscriber::util::SubscriberInitExt;
@@ -63,14 +86,14 @@ fn init_logging() -> anyhow::Result<()> {
 
 /// Initialises logging without tokio-console.
 #[cfg(not(feature = "tokio-console"))]
-fn init_logging() -> anyhow::Result<()> {
+fn init_logging(default_level: &str) -> anyhow::Result<()> {
     use tracing_subscriber::layer::SubscriberExt;
     use tracing_subscriber::util::SubscriberInitExt;
     use tracing_subscriber::EnvFilter;
 
     let requested_level = std::env::vars()
         .find_map(|(k, v)| if k == "LOG_LEVEL" { Some(v) } else { None })
-        .unwrap_or(String::from("info"));
+        .unwrap_or_else(|| default_level.to_owned());
 
     let env_filter = EnvFilter::new(format!("mirai={requested_level}"));
 
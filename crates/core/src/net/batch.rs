@@ -0,0 +1,113 @@
+//! Batching of game packets.
+//!
+//! A single `0xfe` payload can contain multiple game packets, each prefixed by its length. Vanilla servers collect
+//! the packets of a tick into one payload, which is then compressed and encrypted once and usually fits in a single
+//! frame. Sending every packet in its own payload wastes bandwidth on headers for small packets such as attribute
+//! and block updates.
+//!
+//! Packets are collected until the batch would no longer fit in a frame, a packet with a custom send configuration
+//! is sent, or the batch is flushed. The receiver of the client flushes the batch after handling every incoming
+//! packet and once per tick for packets that were sent by other tasks.
+
+use std::time::Duration;
+
+use raknet::DEFAULT_SEND_CONFIG;
+use util::RVec;
+
+use super::BedrockClient;
+
+/// Interval at which packets sent outside of the receiver are flushed.
+pub(super) const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+/// Size of the IPv4 and UDP headers, which are included in the MTU.
+const IP_UDP_HEADER: usize = 20 + 8;
+/// Size of the header of a datagram: the datagram ID and the sequence number.
+const DATAGRAM_HEADER: usize = 1 + 3;
+/// Largest possible size of the header of a frame.
+///
+/// This consists of the flags, the body length, the reliable, sequence and order indices, the order channel and
+/// the compound size, ID and index.
+const MAX_FRAME_HEADER: usize = 1 + 2 + 3 + 3 + 3 + 1 + 4 + 2 + 4;
+/// Bytes of a payload that are not part of the batch: the packet ID, compression algorithm and checksum.
+const PAYLOAD_OVERHEAD: usize = 1 + 1 + 8;
+
+/// Maximum size of an uncompressed batch for a connection with the given MTU.
+///
+/// This ensures that a batch fits in a single frame, even if it cannot be compressed.
+pub const fn batch_limit(mtu: u16) -> usize {
+    (mtu as usize).saturating_sub(IP_UDP_HEADER + DATAGRAM_HEADER + MAX_FRAME_HEADER + PAYLOAD_OVERHEAD)
+}
+
+/// Where a packet goes when it is added to a batch.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BatchPlacement {
+    /// Whether the batch has to be sent before the packet is handled.
+    pub flush: bool,
+    /// Whether the packet is too large to share a frame and is sent in a payload of its own.
+    pub alone: bool,
+}
+
+impl BatchPlacement {
+    /// Determines where a packet of `packet` bytes goes when the batch already contains `batched` bytes.
+    pub const fn of(batched: usize, packet: usize, limit: usize) -> BatchPlacement {
+        BatchPlacement { flush: batched != 0 && batched + packet > limit, alone: packet >= limit }
+    }
+}
+
+impl BedrockClient {
+    /// Maximum size of an uncompressed batch for this client.
+    pub(super) fn batch_limit(&self) -> usize {
+        batch_limit(self.raknet.mtu)
+    }
+
+    /// Whether game packets are currently collected into batches.
+    ///
+    /// Batching only starts once the login has set up compression and encryption. Before that, every packet
+    /// has to be sent before the next step of the handshake changes how the packets after it are encoded.
+    fn is_batching(&self) -> bool {
+        self.should_decompress.get() && self.encryptor.get().is_some() && self.instance().config().net().packet_batching
+    }
+
+    /// Adds a serialized game packet, prefixed by its length, to the batch.
+    ///
+    /// The batch is sent first if the packet does not fit in it anymore. Packets that are too large to share
+    /// a frame with other packets are sent on their own.
+    pub(super) fn queue_serialized(&self, packet: RVec) -> anyhow::Result<()> {
+        if !self.is_batching() {
+            return self.send_serialized(packet, DEFAULT_SEND_CONFIG)
+        }
+
+        let mut batch = self.batch.lock();
+        let placement = BatchPlacement::of(batch.len(), packet.len(), self.batch_limit());
+        if placement.flush {
+            self.send_batch(&mut batch)?;
+        }
+
+        if placement.alone {
+            return self.send_payload(&packet, DEFAULT_SEND_CONFIG)
+        }
+
+        batch.extend_from_slice(&packet);
+        drop(batch);
+
+        Ok(())
+    }
+
+    /// Sends all packets that are waiting in the batch.
+    pub fn flush_batch(&self) -> anyhow::Result<()> {
+        let mut batch = self.batch.lock();
+        self.send_batch(&mut batch)
+    }
+
+    /// Sends the given batch and clears it.
+    ///
+    /// The lock on the batch should be held while sending to ensure that no other packets are sent in between.
+    pub(super) fn send_batch(&self, batch: &mut RVec) -> anyhow::Result<()> {
+        if batch.is_empty() {
+            return Ok(())
+        }
+
+        let result = self.send_payload(batch, DEFAULT_SEND_CONFIG);
+        batch.clear();
+        result
+    }
+}
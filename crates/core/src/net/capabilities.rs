@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use dashmap::DashMap;
+use proto::bedrock::{DeviceOS, InputMode, UiProfile};
+use proto::crypto::BedrockClientInfo;
+
+use super::BedrockClient;
+
+/// Property of a client that server features can be gated on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// The client is running on a console (Xbox, PlayStation or Switch).
+    Console,
+    /// The client is running on a phone or tablet.
+    Mobile,
+    /// The client is running on a desktop operating system.
+    Desktop,
+    /// The client is currently using touch controls.
+    Touch,
+    /// The client is currently using a controller.
+    Gamepad,
+    /// The client is currently using a mouse and keyboard.
+    Mouse,
+    /// The client uses the Pocket Edition UI profile.
+    PocketUi,
+}
+
+/// Server feature that can be disabled for clients with certain capabilities.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// Custom forms containing input elements.
+    CustomForms,
+    /// Menu forms containing a list of buttons.
+    MenuForms,
+    /// Modal forms containing two buttons.
+    ModalForms,
+    /// Feature defined by a plugin or other server code.
+    Named(&'static str),
+}
+
+/// Capabilities of a client, parsed from the client info sent during login.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ClientCapabilities {
+    /// Operating system of the device.
+    pub device_os: DeviceOS,
+    /// UI profile selected by the client.
+    pub ui_profile: UiProfile,
+    /// Input mode that the client was using when it joined, if it is known.
+    pub input_mode: Option<InputMode>,
+    /// GUI scale setting of the client.
+    pub gui_scale: i32,
+}
+
+impl ClientCapabilities {
+    /// Parses the capabilities from the client info.
+    pub fn from_client_info(info: &BedrockClientInfo) -> Self {
+        Self {
+            device_os: info.build_platform,
+            ui_profile: info.ui_profile,
+            input_mode: InputMode::try_from(info.current_input_mode).ok(),
+            gui_scale: info.gui_scale,
+        }
+    }
+
+    /// Whether the client is running on a console.
+    pub const fn is_console(&self) -> bool {
+        matches!(self.device_os, DeviceOS::Xbox | DeviceOS::PlayStation | DeviceOS::Nx)
+    }
+
+    /// Whether the client is running on a phone or tablet.
+    pub const fn is_mobile(&self) -> bool {
+        matches!(self.device_os, DeviceOS::Android | DeviceOS::Ios | DeviceOS::FireOS | DeviceOS::WindowsPhone)
+    }
+
+    /// Whether the client is running on a desktop operating system.
+    pub const fn is_desktop(&self) -> bool {
+        matches!(self.device_os, DeviceOS::Win10 | DeviceOS::Win32 | DeviceOS::Osx | DeviceOS::Linux)
+    }
+
+    /// Whether the client is using touch controls.
+    pub const fn is_touch(&self) -> bool {
+        matches!(self.input_mode, Some(InputMode::Touch))
+    }
+
+    /// Whether the client has the given capability.
+    pub const fn has(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Console => self.is_console(),
+            Capability::Mobile => self.is_mobile(),
+            Capability::Desktop => self.is_desktop(),
+            Capability::Touch => self.is_touch(),
+            Capability::Gamepad => matches!(self.input_mode, Some(InputMode::Gamepad)),
+            Capability::Mouse => matches!(self.input_mode, Some(InputMode::Mouse)),
+            Capability::PocketUi => matches!(self.ui_profile, UiProfile::Pocket),
+        }
+    }
+}
+
+/// Decides which features are available to a client based on its capabilities.
+///
+/// All features are enabled by default. A feature can be disabled for every client that has a certain
+/// capability, for example to avoid sending complex custom forms to console players. Movement validation can
+/// also be relaxed for specific capabilities, touch controls tend to produce less consistent movement.
+#[derive(Debug, Default)]
+pub struct FeatureGates {
+    /// Capabilities for which a feature is disabled.
+    disabled: DashMap<Feature, HashSet<Capability>>,
+    /// Multipliers applied to the movement limits for clients with a capability.
+    movement_tolerance: DashMap<Capability, f32>,
+}
+
+impl FeatureGates {
+    /// Creates a set of gates with every feature enabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Disables a feature for clients with the given capability.
+    pub fn disable(&self, feature: Feature, capability: Capability) {
+        self.disabled.entry(feature).or_default().insert(capability);
+    }
+
+    /// Enables a feature again for clients with the given capability.
+    pub fn enable(&self, feature: Feature, capability: Capability) {
+        if let Some(mut set) = self.disabled.get_mut(&feature) {
+            set.remove(&capability);
+        }
+    }
+
+    /// Whether the feature is enabled for a client with the given capabilities.
+    pub fn is_enabled(&self, feature: Feature, capabilities: &ClientCapabilities) -> bool {
+        self.disabled
+            .get(&feature)
+            .map_or(true, |set| !set.iter().any(|c| capabilities.has(*c)))
+    }
+
+    /// Sets the multiplier applied to movement limits for clients with the given capability.
+    ///
+    /// A multiplier above 1 makes validation more lenient.
+    pub fn set_movement_tolerance(&self, capability: Capability, multiplier: f32) {
+        self.movement_tolerance.insert(capability, multiplier);
+    }
+
+    /// Multiplier applied to movement limits for a client with the given capabilities.
+    ///
+    /// If multiple capabilities have a tolerance, the most lenient one is used.
+    pub fn movement_tolerance(&self, capabilities: &ClientCapabilities) -> f32 {
+        self.movement_tolerance
+            .iter()
+            .filter(|r| capabilities.has(*r.key()))
+            .map(|r| *r.value())
+            .fold(1.0, f32::max)
+    }
+}
+
+impl BedrockClient {
+    /// Returns the client info that was sent during login.
+    #[inline]
+    pub fn client_info(&self) -> anyhow::Result<&BedrockClientInfo> {
+        self.client_info.get().ok_or_else(|| anyhow::anyhow!("Client info unknown: user has not logged in yet"))
+    }
+
+    /// Returns the capabilities of the client.
+    pub fn capabilities(&self) -> anyhow::Result<ClientCapabilities> {
+        self.client_info().map(ClientCapabilities::from_client_info)
+    }
+
+    /// Whether a feature is enabled for this client.
+    ///
+    /// Features are always enabled for clients that have not logged in yet.
+    pub fn is_feature_enabled(&self, feature: Feature) -> bool {
+        self.capabilities().map_or(true, |c| self.instance().feature_gates().is_enabled(feature, &c))
+    }
+
+    /// Multiplier applied to the movement limits of this client.
+    pub(super) fn movement_tolerance(&self) -> f32 {
+        self.capabilities().map_or(1.0, |c| self.instance().feature_gates().movement_tolerance(&c))
+    }
+}
@@ -1,30 +1,85 @@
+use std::collections::HashMap;
 use std::io::{Read, Write};
 
 use std::sync::{Arc, OnceLock, Weak};
 use std::sync::atomic::{
-    AtomicBool, AtomicI64, AtomicU32, Ordering
+    AtomicBool, AtomicI64, Ordering
 };
 use std::time::{Instant, Duration};
 
 use anyhow::Context;
 use flate2::Compression;
 use flate2::write::DeflateEncoder;
-use parking_lot::RwLock;
-use raknet::{BroadcastPacket, Frame, FrameBatch, RakNetClient, RakNetCommand, SendConfig, DEFAULT_SEND_CONFIG};
+use parking_lot::{Mutex, RwLock};
+use raknet::{BroadcastPacket, Direction, Frame, FrameBatch, RakNetClient, RakNetCommand, RecipientInfo, Recipients, Reliability, SendConfig, SendPriority, SessionStats};
 use tokio::sync::{broadcast, mpsc};
-use proto::bedrock::{Animate, CacheStatus, ChunkRadiusRequest, ClientToServerHandshake, CommandPermissionLevel, CommandRequest, CompressionAlgorithm, ConnectedPacket, ContainerClose, Disconnect, DisconnectReason, FormResponseData, GameMode, Header, Interact, InventoryTransaction, Login, MobEquipment, MovePlayer, PermissionLevel, PlayerAction, PlayerAuthInput, RequestAbility, RequestNetworkSettings, ResourcePackClientResponse, SetInventoryOptions, SetLocalPlayerAsInitialized, SettingsCommand, Skin, TextMessage, TickSync, UpdateSkin, ViolationWarning, CONNECTED_PACKET_ID};
+use proto::bedrock::{AbilityData, AddPlayer, Animate, CacheBlobStatus, CacheStatus, ChunkRadiusRequest, ClientToServerHandshake, CommandPermissionLevel, CommandRequest, CompressionAlgorithm, ConnectedPacket, ContainerClose, Disconnect, DisconnectReason, EntityLink, FormResponseData, GameMode, Header, Interact, InventoryTransaction, LevelChunk, Login, MobEquipment, MovePlayer, PermissionLevel, PlayerAction, PlayerAuthInput, RequestAbility, RequestNetworkSettings, ResourcePackClientResponse, SetInventoryOptions, SetLocalPlayerAsInitialized, SettingsCommand, Skin, SubChunkRequestMode, TextMessage, TickSync, UpdateSkin, ViolationWarning, CONNECTED_PACKET_ID};
 use proto::crypto::{Encryptor, BedrockIdentity, BedrockClientInfo};
+use proto::types::{Angle, HeadYaw};
 use proto::uuid::Uuid;
 
 use tokio_util::sync::CancellationToken;
 use util::{AtomicFlag, BinaryRead, BinaryWrite, Deserialize, Joinable, RVec, pool, Serialize, Vector};
 
+use crate::afk::AfkTracker;
+use crate::clock::SystemClock;
 use crate::forms;
 use crate::instance::Instance;
+use crate::item::Inventory;
+use crate::level::replicator;
 use crate::level::Viewer;
+use crate::report::ChatHistory;
+
+use super::{Admission, Interception, LoginGate, LoginState, Mount, PortalState, QuitReason, TickModel};
+use super::traffic::{self, TrafficStats};
 
 const REQUEST_TIMEOUT: Duration = Duration::from_millis(50);
 
+/// Reliability and priority of a game packet, used by [`BedrockClient::send_with`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PacketConfig {
+    /// How reliably the packet is delivered.
+    pub reliability: Reliability,
+    /// How soon the packet is sent.
+    pub priority: SendPriority,
+}
+
+impl PacketConfig {
+    /// Reliable ordered with medium priority, which is used by [`BedrockClient::send`].
+    pub const DEFAULT: PacketConfig = PacketConfig { reliability: Reliability::ReliableOrdered, priority: SendPriority::Medium };
+    /// Reliable ordered with high priority, for packets that should be sent without waiting for the next tick.
+    pub const URGENT: PacketConfig = PacketConfig { reliability: Reliability::ReliableOrdered, priority: SendPriority::High };
+
+    /// Returns this configuration with a different priority.
+    #[inline]
+    pub const fn with_priority(mut self, priority: SendPriority) -> PacketConfig {
+        self.priority = priority;
+        self
+    }
+
+    /// Returns this configuration with a different reliability.
+    #[inline]
+    pub const fn with_reliability(mut self, reliability: Reliability) -> PacketConfig {
+        self.reliability = reliability;
+        self
+    }
+
+    /// Whether packets can be sent with this configuration.
+    ///
+    /// The encryption of game packets depends on the order that they were sent in, so only
+    /// [`Reliability::ReliableOrdered`] can be used once encryption has been enabled.
+    #[inline]
+    pub const fn is_allowed(self, encrypted: bool) -> bool {
+        !encrypted || matches!(self.reliability, Reliability::ReliableOrdered)
+    }
+}
+
+impl Default for PacketConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
 /// Represents a user connected to the server.
 pub struct BedrockClient {
     pub(super) encryptor: OnceLock<Encryptor>,
@@ -32,14 +87,30 @@ pub struct BedrockClient {
     pub(super) client_info: OnceLock<BedrockClientInfo>,
     pub(super) viewer: Viewer,
 
-    /// Next packet that the server is expecting to receive.
-    pub(crate) expected: AtomicU32,
+    /// Restricts the packets that can be sent while logging in.
+    pub(crate) login: LoginGate,
     /// Whether compression has been configured.
     pub(crate) should_decompress: AtomicFlag,
+    /// Whether preset compression dictionaries have been negotiated.
+    pub(crate) use_dictionaries: AtomicFlag,
     /// Whether the client supports the blob cache.
     pub(crate) supports_cache: AtomicBool,
+    /// Chunk payloads that were sent as a blob hash, in case the client reports them missing from its cache.
+    pub(crate) pending_blobs: Mutex<HashMap<u64, RVec>>,
     pub(crate) raknet: Arc<RakNetClient>,
+    /// Game packets waiting to be sent together in a single batch.
+    pub(crate) batch: Mutex<RVec>,
     pub(crate) player: OnceLock<PlayerData>,
+    /// Why the client left, set by whichever side initiated the disconnect.
+    pub(crate) quit: OnceLock<QuitReason>,
+    /// Keeps track of when the player last performed meaningful input.
+    pub(crate) afk: AfkTracker,
+    /// Recent chat messages, included in reports against the player.
+    pub(crate) chat_history: ChatHistory,
+    /// Packets sent and received by this session.
+    pub(crate) traffic: TrafficStats,
+    /// Estimate of the simulation tick of the client.
+    pub(crate) ticks: TickModel,
 
     pub(crate) forms: forms::Subscriber,
     pub(crate) commands: Arc<crate::command::Service>,
@@ -61,15 +132,24 @@ impl BedrockClient {
         broadcast: broadcast::Sender<BroadcastPacket>,
         instance: Weak<Instance>
     ) -> Arc<Self> {
+        let clock = instance.upgrade().map_or_else(SystemClock::shared, |instance| Arc::clone(instance.clock()));
         let client = Arc::new(Self {
             encryptor: OnceLock::new(),
             identity: OnceLock::new(),
             client_info: OnceLock::new(),
-            expected: AtomicU32::new(RequestNetworkSettings::ID),
+            login: LoginGate::new(),
             should_decompress: AtomicFlag::new(),
+            use_dictionaries: AtomicFlag::new(),
             supports_cache: AtomicBool::new(false),
+            pending_blobs: Mutex::new(HashMap::new()),
             raknet,
+            batch: Mutex::new(RVec::alloc()),
             player: OnceLock::new(),
+            quit: OnceLock::new(),
+            afk: AfkTracker::with_clock(Arc::clone(&clock)),
+            chat_history: ChatHistory::new(),
+            traffic: TrafficStats::new(clock),
+            ticks: TickModel::new(),
             forms: forms::Subscriber::new(),
             commands,
             broadcast,
@@ -79,7 +159,7 @@ impl BedrockClient {
         });
 
         let this = Arc::clone(&client);
-        tokio::spawn(async move {
+        util::task::spawn_owned("client::receiver", client.raknet.address(), async move {
             this.receiver(receiver).await;
         });
 
@@ -91,11 +171,12 @@ impl BedrockClient {
         skip_all,
         name = "BedrockUser::receiver",
         fields(
-            address = %self.raknet.address
+            address = %self.raknet.address()
         )
     )]
     async fn receiver(self: &Arc<Self>, mut receiver: mpsc::Receiver<RakNetCommand>) {
         let mut broadcast = self.broadcast.subscribe();
+        let mut batch_interval = tokio::time::interval(super::BATCH_INTERVAL);
         
         let mut should_run = true;
         while should_run {
@@ -111,6 +192,11 @@ impl BedrockClient {
                             if let Err(err) = self.handle_encrypted_frame(packet).await {
                                 tracing::error!("Failed to handle protocol packet: {err:#}");
                             }
+
+                            // Send the responses without waiting for the next tick.
+                            if let Err(err) = self.flush_batch() {
+                                tracing::error!("Failed to flush packet batch: {err:#}");
+                            }
                         },
                         RakNetCommand::BudgetExhausted => {
                             if let Err(err) = self.kick_with_reason("Exhausted request budget", DisconnectReason::NotAllowed) {
@@ -120,16 +206,28 @@ impl BedrockClient {
                             }
                         },
                         RakNetCommand::Disconnected => {
-                            tracing::warn!("Raknet has reported a disconnect status, destroying user");
+                            tracing::debug!("RakNet has reported a disconnect status, destroying user");
                             break
                         }
                     }
                 },
                 packet = broadcast.recv() => {
-                    if let Ok(packet) = packet {
-                        if let Err(err) = self.handle_broadcast(packet) {
-                            tracing::error!("Failed to handle broadcast: {err:#}");
+                    match packet {
+                        Ok(packet) => {
+                            if let Err(err) = self.handle_broadcast(packet) {
+                                tracing::error!("Failed to handle broadcast: {err:#}");
+                            }
                         }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            super::DROPPED_BROADCASTS_METRIC.inc_by(skipped);
+                            tracing::warn!("Client fell behind on broadcasts, {skipped} packets were skipped");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => ()
+                    }
+                },
+                _ = batch_interval.tick() => {
+                    if let Err(err) = self.flush_batch() {
+                        tracing::error!("Failed to flush packet batch: {err:#}");
                     }
                 },
                 // Use `should_run` variable to trigger one final processing run when shutting down.
@@ -137,7 +235,7 @@ impl BedrockClient {
             };
         }
 
-        tracing::info!("{} has disconnected", self.name().unwrap_or("<unknown>"));
+        self.on_disconnected();
 
         tracing::info!(
             "Requests: {} | Returns: {} | Allocations: {}",
@@ -157,7 +255,7 @@ impl BedrockClient {
     /// Handles a packet broadcasted by another user.
     #[allow(clippy::unwrap_in_result)]
     fn handle_broadcast(&self, packet: BroadcastPacket) -> anyhow::Result<()> {
-        let should_send = packet.sender.map(|sender| sender != self.raknet.address).unwrap_or(true);
+        let should_send = packet.recipients.includes(self.raknet.address(), || self.recipient_info());
         if should_send {
             let header = Header {
                 id: packet.id, sender_subclient: 0, target_subclient: 0
@@ -175,7 +273,8 @@ impl BedrockClient {
             full.write_var_u32(body.len() as u32)?;
             full.write_all(&body)?;
 
-            self.send_serialized(full, DEFAULT_SEND_CONFIG)?;
+            self.record_traffic(Direction::Outbound, packet.id, full.len());
+            self.queue_serialized(full)?;
         }
 
         Ok(())
@@ -211,15 +310,19 @@ impl BedrockClient {
         )
     )]
     pub fn kick_with_reason(&self, message: &str, reason: DisconnectReason) -> anyhow::Result<()> {
+        self.record_quit(QuitReason::Kicked { reason, message: message.to_owned() });
+
         let disconnect_packet = Disconnect {
             reason, message, hide_message: false
         };
         self.send(disconnect_packet)?;
+        self.flush_batch()?;
 
         tracing::info!("User has been kicked");
 
         // Force the session to shut down. Without this, the client could just ignore the disconnect packet.
-        self.raknet.active.cancel();
+        // The session lingers until the client has acknowledged the disconnect packet.
+        self.raknet.disconnect();
         Ok(())
     }
 
@@ -237,14 +340,112 @@ impl BedrockClient {
         &self,
         packet: P,
     ) -> anyhow::Result<()> {
-        self.broadcast.send(BroadcastPacket::new(packet, Some(self.raknet.address))?)?;
+        self.broadcast.send(BroadcastPacket::new(packet, Some(self.raknet.address()))?)?;
+        Ok(())
+    }
+
+    /// Sends a packet to the initialised sessions selected by `recipients`.
+    pub fn broadcast_to<P: ConnectedPacket + Serialize>(&self, packet: P, recipients: Recipients) -> anyhow::Result<()> {
+        self.broadcast.send(BroadcastPacket::with_recipients(packet, recipients)?)?;
+        Ok(())
+    }
+
+    /// State of this session that broadcast filters match on.
+    ///
+    /// Returns `None` if the client has not spawned yet.
+    pub fn recipient_info(&self) -> Option<RecipientInfo> {
+        let player = self.player().ok()?;
+        let (chunk_x, chunk_z) = self.viewer.chunk();
+
+        Some(RecipientInfo {
+            address: self.raknet.address(),
+            dimension: self.dimension().ok()?,
+            permission_level: player.permission_level(),
+            chunk_x,
+            chunk_z,
+            render_distance: self.viewer.radius(),
+        })
+    }
+
+    /// Sends up to `limit` of the chunks that have come into view of this client.
+    ///
+    /// Only the chunk columns are sent, the client requests their sub chunks afterwards. If the client supports the
+    /// blob cache, columns that have not changed since it last received them are sent as a hash only, see
+    /// [`Replicator`](crate::level::replicator::Replicator).
+    pub fn send_queued_chunks(&self, limit: usize) -> anyhow::Result<()> {
+        let Some((dimension, chunks)) = self.viewer.next_chunks(limit) else {
+            return Ok(())
+        };
+
+        let replicator = self.viewer.service.replicator();
+        let xuid = self.xuid().ok().filter(|_| self.supports_cache.load(Ordering::Relaxed));
+        for [x, z] in chunks {
+            // No border blocks.
+            let payload = RVec::alloc_from_slice(&[0]);
+            let hash = replicator::content_hash(payload.as_ref());
+
+            let (blob_hashes, raw_payload) = match xuid {
+                Some(xuid) if replicator.is_unchanged(xuid, dimension, [x, z], hash) => {
+                    self.pending_blobs.lock().insert(hash, payload);
+                    (Some(vec![hash]), RVec::alloc())
+                }
+                Some(xuid) => {
+                    replicator.record(xuid, dimension, [x, z], hash);
+                    (None, payload)
+                }
+                None => (None, payload),
+            };
+
+            self.send(LevelChunk {
+                coordinates: Vector::from([x, z]),
+                dimension,
+                request_mode: SubChunkRequestMode::Limitless,
+                highest_sub_chunk: 0,
+                sub_chunk_count: 0,
+                blob_hashes,
+                raw_payload,
+            })?;
+        }
+
         Ok(())
     }
 
     /// Sends a game packet with default settings
     /// (reliable ordered and medium priority)
-    #[allow(clippy::unwrap_in_result, clippy::missing_panics_doc)]
+    ///
+    /// Once the client has logged in, the packet is added to the batch of the current tick instead of being sent
+    /// immediately.
     pub fn send<T: ConnectedPacket + Serialize>(&self, packet: T) -> anyhow::Result<()> {
+        let full = Self::serialize_packet(&packet)?;
+
+        self.record_traffic(Direction::Outbound, T::ID, full.len());
+        self.queue_serialized(full)
+    }
+
+    /// Sends a game packet with custom reliability and priority.
+    ///
+    /// Packets with the [default](PacketConfig::DEFAULT) configuration are batched like [`send`](Self::send),
+    /// others are sent on their own after the packets that are waiting in the batch.
+    ///
+    /// Encryption requires every packet to arrive in order, so this returns an error if a reliability other than
+    /// [`Reliability::ReliableOrdered`] is requested after encryption has been enabled.
+    pub fn send_with<T: ConnectedPacket + Serialize>(&self, packet: T, config: PacketConfig) -> anyhow::Result<()> {
+        if config == PacketConfig::DEFAULT {
+            return self.send(packet)
+        }
+
+        if !config.is_allowed(self.encryptor.get().is_some()) {
+            anyhow::bail!("Packet {:#04x} cannot be sent {:?} over an encrypted connection", T::ID, config.reliability);
+        }
+
+        let full = Self::serialize_packet(&packet)?;
+        self.record_traffic(Direction::Outbound, T::ID, full.len());
+        self.send_serialized(full, SendConfig { reliability: config.reliability, priority: config.priority, channel: 0 })
+    }
+
+    /// Serializes a game packet, prefixed by its length.
+    #[allow(clippy::unwrap_in_result, clippy::missing_panics_doc)]
+    fn serialize_packet<T: ConnectedPacket + Serialize>(packet: &T) -> anyhow::Result<RVec> {
         let header = Header {
             id: T::ID, sender_subclient: 0, target_subclient: 0
         };
@@ -263,14 +464,43 @@ impl BedrockClient {
         full.write_var_u32(body.len() as u32)?;
         full.write_all(&body)?;
 
-        self.send_serialized(full, DEFAULT_SEND_CONFIG)
+        Ok(full)
+    }
+
+    /// Gets the packets sent and received by this session.
+    #[inline]
+    pub const fn traffic(&self) -> &TrafficStats {
+        &self.traffic
+    }
+
+    /// Records a packet in the traffic statistics of this session and the server.
+    fn record_traffic(&self, direction: Direction, id: u32, bytes: usize) {
+        self.traffic.record(direction, id, bytes);
+        if let Some(instance) = self.instance.upgrade() {
+            instance.traffic().record(direction, id, bytes);
+        }
+
+        traffic::record_metrics(direction, id, bytes);
     }
 
     /// Sends a game packet with custom reliability and priority
+    ///
+    /// The packet is sent on its own, after the packets that are waiting in the batch.
     pub fn send_serialized<B>(&self, packet: B, config: SendConfig) -> anyhow::Result<()>
         where
             B: AsRef<[u8]>
     {
+        let mut batch = self.batch.lock();
+        self.send_batch(&mut batch)?;
+        self.send_payload(packet.as_ref(), config)
+    }
+
+    /// Compresses and encrypts a payload of one or more game packets and passes it to RakNet.
+    pub(super) fn send_payload(&self, packet: &[u8], config: SendConfig) -> anyhow::Result<()> {
+        if let Some(capture) = self.raknet.capture.as_ref().filter(|c| c.captures_payloads()) {
+            capture.record_payload(Direction::Outbound, packet);
+        }
+
         let mut out;
         if self.should_decompress.get() {
             let (algorithm, threshold) = {
@@ -279,38 +509,33 @@ impl BedrockClient {
                 (compression.algorithm, compression.threshold)
             };
 
-            if packet.as_ref().len() > threshold as usize {
+            if packet.len() > threshold as usize {
                 // Compress packet
                 match algorithm {
                     CompressionAlgorithm::Snappy => {
                         unimplemented!("Snappy compression");
                     }
+                    #[cfg(feature = "compression-dictionary")]
+                    CompressionAlgorithm::Flate if self.use_dictionaries.get() => {
+                        out = Self::compress_with_dictionary(packet)?;
+                    }
                     CompressionAlgorithm::Flate => {
-                        let writer_inner = RVec::alloc_with_capacity(packet.as_ref().len());
-                        let mut writer = DeflateEncoder::new(writer_inner, Compression::best());
-
-                        writer.write_all(packet.as_ref())?;
-                        let compressed_body = writer.finish()?;
-
-                        out = RVec::alloc_with_capacity(1 + 1 + compressed_body.len());
-                        out.write_u8(CONNECTED_PACKET_ID)?;
-                        out.write_u8(algorithm as u8)?;
-                        out.write_all(&compressed_body)?;
+                        out = Self::compress_flate(packet)?;
                     }
                 }
             } else {
                 // Also reserve capacity for checksum even if encryption is disabled,
                 // preventing allocations.
-                out = RVec::alloc_with_capacity(1 + packet.as_ref().len() + 8);
+                out = RVec::alloc_with_capacity(1 + packet.len() + 8);
                 out.write_u8(CONNECTED_PACKET_ID)?;
-                out.write_all(packet.as_ref())?;
+                out.write_all(packet)?;
             }
         } else {
             // Also reserve capacity for checksum even if encryption is disabled,
             // preventing allocations.
-            out = RVec::alloc_with_capacity(1 + packet.as_ref().len() + 8);
+            out = RVec::alloc_with_capacity(1 + packet.len() + 8);
             out.write_u8(CONNECTED_PACKET_ID)?;
-            out.write_all(packet.as_ref())?;
+            out.write_all(packet)?;
         };
 
         let chunk_max_size = self.raknet.mtu as usize
@@ -327,6 +552,22 @@ impl BedrockClient {
         Ok(())
     }
 
+    /// Compresses a packet using deflate and prepends the required headers.
+    pub(super) fn compress_flate(packet: &[u8]) -> anyhow::Result<RVec> {
+        let writer_inner = RVec::alloc_with_capacity(packet.len());
+        let mut writer = DeflateEncoder::new(writer_inner, Compression::best());
+
+        writer.write_all(packet)?;
+        let compressed_body = writer.finish()?;
+
+        let mut out = RVec::alloc_with_capacity(1 + 1 + compressed_body.len());
+        out.write_u8(CONNECTED_PACKET_ID)?;
+        out.write_u8(CompressionAlgorithm::Flate as u8)?;
+        out.write_all(&compressed_body)?;
+
+        Ok(out)
+    }
+
     /// Handles a received encrypted frame.
     /// 
     /// This is the first function that is called when a packet is received from the RakNet processing layer.
@@ -335,7 +576,7 @@ impl BedrockClient {
     /// After processing, this function sends the processed packet to [`handle_frame_body`](Self::handle_frame_body)
     /// function,
     async fn handle_encrypted_frame(self: &Arc<Self>, mut packet: RVec) -> anyhow::Result<()> {
-        if packet[0] != 0xfe {
+        if packet.first() != Some(&0xfe) {
             anyhow::bail!("First byte in a Bedrock proto packet should be 0xfe");
         }
 
@@ -346,12 +587,22 @@ impl BedrockClient {
             encryptor.decrypt(&mut packet).context("Failed to decrypt packet")?;
         }
 
+        let Some(&algorithm) = packet.first() else {
+            anyhow::bail!("Bedrock packet does not contain a body");
+        };
+
         let out = if self.should_decompress.get() {
-            if packet[0] == 0xff {
+            if algorithm == 0xff {
                 packet.remove(0);
                 self.handle_frame_body(packet).await
             } else {
-                let algorithm = CompressionAlgorithm::try_from(packet[0])?;
+                #[cfg(feature = "compression-dictionary")]
+                if let Some(class) = self.dictionary_class(algorithm) {
+                    let decompressed = super::dictionary::decompress(class, &packet[1..])?;
+                    return self.handle_frame_body(decompressed).await;
+                }
+
+                let algorithm = CompressionAlgorithm::try_from(algorithm)?;
                 packet.remove(0);
 
                 match algorithm {
@@ -385,28 +636,65 @@ impl BedrockClient {
         )
     )]
     async fn handle_frame_body(self: &Arc<Self>, mut packet: RVec) -> anyhow::Result<()> {
+        if let Some(capture) = self.raknet.capture.as_ref().filter(|c| c.captures_payloads()) {
+            capture.record_payload(Direction::Inbound, &packet);
+        }
+
         let start_len = packet.len();
         let mut reader: &[u8] = packet.as_ref();
         let _length = reader.read_var_u32()?;
         let header = Header::deserialize_from(&mut reader)?;
+        self.record_traffic(Direction::Inbound, header.id, start_len);
 
         let remaining = reader.remaining();
         packet.drain(0..(start_len - remaining));
-        
-        let expected = self.expected();
-        if expected != u32::MAX && header.id != expected {
-            // Server received an unexpected packet.
-            tracing::warn!(
-                "Client sent unexpected packet while logging in (expected {:#04x}, got {:#04x})",
-                expected, header.id
-            );
-            
-            self.kick_with_reason("Unexpected packet", DisconnectReason::UnexpectedPacket)?;
+
+        let packet = match self.login.admit(header.id, packet) {
+            Admission::Process(packet) => packet,
+            Admission::Deferred => {
+                tracing::debug!("Deferring packet {:#04x} that arrived before the login state allows it", header.id);
+                return Ok(())
+            }
+            Admission::Rejected => {
+                // Server received an unexpected packet.
+                tracing::warn!(
+                    "Client sent unexpected packet while logging in (state {:?}, got {:#04x})",
+                    self.login.state(), header.id
+                );
+
+                return self.kick_with_reason("Unexpected packet", DisconnectReason::UnexpectedPacket)
+            }
+        };
+
+        self.handle_game_packet(header.id, packet).await?;
+
+        // Packets that arrived early may have become valid now that the state has advanced.
+        while let Some((id, packet)) = self.login.take_ready() {
+            self.handle_game_packet(id, packet).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Processes a single game packet that has passed the login gate.
+    async fn handle_game_packet(self: &Arc<Self>, id: u32, packet: RVec) -> anyhow::Result<()> {
+        let instance = self.instance();
+        let interceptors = &instance.config().interceptors;
+        if interceptors.iter().any(|interceptor| interceptor.inbound(self, id, packet.as_ref()) == Interception::Drop) {
+            tracing::trace!("Packet {id:#04x} was dropped by an interceptor");
+            return Ok(())
+        }
+
+        if matches!(
+            id,
+            TextMessage::ID | CommandRequest::ID | InventoryTransaction::ID | Interact::ID | Animate::ID | PlayerAction::ID | MobEquipment::ID
+        ) {
+            self.record_input();
         }
 
         let this = Arc::clone(self);
         let future = async move {
-            match header.id {
+            match id {
                 SetInventoryOptions::ID => this.handle_inventory_options(packet).context("while handling SetInventoryOptions"),
                 MobEquipment::ID => this.handle_mob_equipment(packet).context("while handling MobEquipment"),
                 InventoryTransaction::ID => this.handle_inventory_transaction(packet).context("while handling InventoryTransaction"),
@@ -419,6 +707,7 @@ impl BedrockClient {
                     this.handle_client_to_server_handshake(packet).context("while handling ClientToServerHandshake")
                 }
                 CacheStatus::ID => this.handle_cache_status(packet).context("while handling CacheStatus"),
+                CacheBlobStatus::ID => this.handle_cache_blob_status(packet).context("while handling CacheBlobStatus"),
                 ResourcePackClientResponse::ID => {
                     this.handle_resource_client_response(packet).context("while handling ResourcePackClientResponse")
                 }
@@ -443,6 +732,7 @@ impl BedrockClient {
                 ContainerClose::ID => this.handle_container_close(packet),
                 FormResponseData::ID => this.handle_form_response(packet),
                 TickSync::ID => this.handle_tick_sync(packet),
+                Disconnect::ID => this.handle_disconnect(packet).context("while handling Disconnect"),
                 id => anyhow::bail!("Invalid game packet: {id:#04x}"),
             }
         };
@@ -462,6 +752,12 @@ impl BedrockClient {
         &self.forms
     }
 
+    /// Returns the network statistics of the connection of this user.
+    #[inline]
+    pub fn network_stats(&self) -> SessionStats {
+        self.raknet.stats()
+    }
+
     /// This function panics if the identity was not set.
     #[inline]
     pub fn identity(&self) -> anyhow::Result<&BedrockIdentity> {
@@ -498,24 +794,58 @@ impl BedrockClient {
         self.encryptor.get().ok_or_else(|| anyhow::anyhow!("Encryption handshake has not been performed yet"))
     }
 
-    /// Returns the next expected packet for this session.
-    /// The expected packet will be [`u32::MAX`] if the user is fully
-    /// initialized and therefore doesn't follow a strict packet order anymore.
+    /// Returns the login state of this session.
+    /// Packets are no longer restricted once the state is [`LoginState::InGame`].
     #[inline]
-    pub fn expected(&self) -> u32 {
-        self.expected.load(Ordering::SeqCst)
+    pub fn login_state(&self) -> LoginState {
+        self.login.state()
     }
 
     /// Returns whether the user is fully initialized.
     #[inline]
     pub fn initialized(&self) -> bool {
-        self.expected() == u32::MAX
+        self.login_state() == LoginState::InGame
     }
 
     /// This functions panic if the player data was not initialized.
     pub fn player(&self) -> anyhow::Result<&PlayerData> {
         self.player.get().ok_or_else(|| anyhow::anyhow!("Player data unavailable"))
     }
+
+    /// Links between this player and other entities, such as the vehicle it is riding.
+    pub fn entity_links(&self) -> anyhow::Result<Vec<EntityLink>> {
+        let player = self.player()?;
+        let mount = player.mount.lock();
+
+        Ok(mount.iter().map(|mount| mount.link(player.runtime_id() as i64)).collect())
+    }
+
+    /// Creates an [`AddPlayer`] packet that spawns this player for other clients.
+    ///
+    /// All fields are derived from the identity, device and player data of this client. The links can be
+    /// obtained from [`entity_links`](Self::entity_links).
+    pub fn add_player_packet<'a>(&'a self, links: &'a [EntityLink]) -> anyhow::Result<AddPlayer<'a>> {
+        let identity = self.identity()?;
+        let info = self.client_info()?;
+        let player = self.player()?;
+
+        Ok(AddPlayer {
+            pitch: Angle::from_degrees(player.rotation.x),
+            yaw: Angle::from_degrees(player.rotation.y),
+            head_yaw: HeadYaw::from_degrees(player.rotation.z),
+            links,
+            device_id: &info.device_id,
+            device_os: info.build_platform,
+            ..AddPlayer::new(
+                identity.uuid,
+                &identity.name,
+                player.runtime_id(),
+                player.position(),
+                player.gamemode(),
+                player.ability_data()
+            )
+        })
+    }
 }
 
 impl Joinable for BedrockClient {
@@ -535,8 +865,10 @@ impl Joinable for BedrockClient {
 pub struct PlayerData {
     /// Whether the player's inventory is currently open.
     pub is_inventory_open: AtomicBool,
-    /// Position of the player.
-    pub position: Vector<f32, 3>,
+    /// Contents of the player's inventory.
+    pub inventory: Inventory,
+    /// Position of the player, updated with every movement of the client.
+    pub position: Mutex<Vector<f32, 3>>,
     /// Rotation of the player.
     /// x and y components are general rotation.
     /// z component is head yaw.
@@ -551,23 +883,45 @@ pub struct PlayerData {
     pub skin: RwLock<Skin>,
     /// Runtime ID.
     pub runtime_id: u64,
+    /// The vehicle that the player is currently riding.
+    pub mount: Mutex<Option<Mount>>,
+    /// Current dimension and portal progress of the player.
+    pub portal: Mutex<PortalState>,
 }
 
 impl PlayerData {
     /// Creates a new player data struct.
+    ///
+    /// Players start with the permissions of a regular member, use [`operator`](Self::operator) to promote them.
     pub fn new(skin: Skin) -> Self {
         Self {
             is_inventory_open: AtomicBool::new(false),
-            position: Vector::from([0.0, 50.0, 0.0]),
+            inventory: Inventory::new(),
+            position: Mutex::new(Vector::from([0.0, 50.0, 0.0])),
             rotation: Vector::from([0.0; 3]),
             game_mode: GameMode::Creative,
             permission_level: PermissionLevel::Member,
-            command_permission_level: CommandPermissionLevel::Owner,
+            command_permission_level: CommandPermissionLevel::Normal,
             skin: RwLock::new(skin),
-            runtime_id: 1
+            runtime_id: 1,
+            mount: Mutex::new(None),
+            portal: Mutex::new(PortalState::default())
         }
     }
 
+    /// Gives the player operator permissions, allowing it to run administration commands.
+    #[must_use]
+    pub const fn operator(mut self) -> Self {
+        self.permission_level = PermissionLevel::Operator;
+        self.command_permission_level = CommandPermissionLevel::Admin;
+        self
+    }
+
+    /// The current position of the player.
+    pub fn position(&self) -> Vector<f32, 3> {
+        self.position.lock().clone()
+    }
+
     /// The gamemode the player is currently in.
     pub const fn gamemode(&self) -> GameMode {
         self.game_mode
@@ -587,4 +941,9 @@ impl PlayerData {
     pub const fn command_permission_level(&self) -> CommandPermissionLevel {
         self.command_permission_level
     }
+
+    /// The abilities of the player, derived from its game mode and permission level.
+    pub fn ability_data(&self) -> AbilityData {
+        AbilityData::new(self.runtime_id, self.game_mode, self.permission_level, self.command_permission_level)
+    }
 }
\ No newline at end of file
@@ -1,16 +1,17 @@
 use std::net::SocketAddr;
+use std::sync::atomic::AtomicU64;
 use std::sync::{Arc, OnceLock, Weak};
-use std::time::Duration;
 
-use anyhow::Context;
 use dashmap::DashMap;
+use lazy_static::lazy_static;
+use prometheus_client::metrics::counter::Counter;
 
 use proto::uuid::Uuid;
-use raknet::{BroadcastPacket, RakNetCreateDescription, RakNetClient};
-use proto::bedrock::{ConnectedPacket, Disconnect, DisconnectReason};
+use raknet::{forward_channel, BackpressurePolicy, BroadcastPacket, ForwardSender, Forwarded, RakNetCreateDescription, RakNetClient, Recipients, SessionStats};
+use proto::bedrock::{ConnectedPacket, DisconnectReason};
 use util::{RVec, Joinable, Serialize};
 
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::broadcast;
 use tokio::task::{JoinHandle, JoinSet};
 use tokio_util::sync::CancellationToken;
 
@@ -18,22 +19,46 @@ use crate::instance::Instance;
 
 use super::{ForwardablePacket, BedrockClient};
 
+lazy_static! {
+    #[doc(hidden)]
+    pub static ref FORWARD_TIMEOUTS_METRIC: Counter::<u64, AtomicU64> = Counter::default();
+    #[doc(hidden)]
+    pub static ref DROPPED_BROADCASTS_METRIC: Counter::<u64, AtomicU64> = Counter::default();
+    #[doc(hidden)]
+    pub static ref DROPPED_FORWARDS_METRIC: Counter::<u64, AtomicU64> = Counter::default();
+}
+
 const BROADCAST_CHANNEL_CAPACITY: usize = 5;
-const FORWARD_TIMEOUT: Duration = Duration::from_millis(10);
 
 /// Contains the user state itself and a method to contact the user.
 pub struct UserMapEntry<T> {
-    channel: mpsc::Sender<RVec>,
+    channel: ForwardSender,
     state: Arc<T>
 }
 
 impl<T> UserMapEntry<T> {
     /// Forwards a packet to the user for processing.
+    ///
+    /// What happens if the user is not keeping up is determined by the [`BackpressurePolicy`](raknet::BackpressurePolicy)
+    /// of the connection.
     #[inline]
     #[allow(clippy::future_not_send)]
-    pub async fn forward(&self, packet: RVec) -> anyhow::Result<()> {
-        self.channel.send_timeout(packet, FORWARD_TIMEOUT).await.context("Server-side client timed out")?;
-        Ok(())
+    pub async fn forward(&self, packet: RVec) -> Forwarded {
+        let forwarded = self.channel.send(packet).await;
+        if forwarded.is_dropped() {
+            DROPPED_FORWARDS_METRIC.inc();
+        }
+        if matches!(forwarded, Forwarded::TimedOut | Forwarded::Overloaded) {
+            FORWARD_TIMEOUTS_METRIC.inc();
+        }
+
+        forwarded
+    }
+
+    /// Amount of packets waiting to be processed by the user.
+    #[inline]
+    pub fn queue_depth(&self) -> usize {
+        self.channel.len()
     }
 }
 
@@ -47,6 +72,14 @@ pub struct Clients {
     
     connecting_map: Arc<DashMap<SocketAddr, UserMapEntry<RakNetClient>>>,
     connected_map: Arc<DashMap<SocketAddr, UserMapEntry<BedrockClient>>>,
+    /// Connections that have been closed but are still waiting for the client to acknowledge their final packets.
+    ///
+    /// Datagrams from these clients are still forwarded so that the acknowledgements arrive.
+    lingering: Arc<DashMap<SocketAddr, ForwardSender>>,
+    /// Connections that are moving to a new address, indexed by the new address.
+    ///
+    /// The value is the address that the connection is still stored under.
+    migrations: DashMap<SocketAddr, SocketAddr>,
     /// Channel that sends a packet to all connected sessions.
     broadcast: broadcast::Sender<BroadcastPacket>,
 
@@ -67,6 +100,8 @@ impl Clients {
             shutdown_token: CancellationToken::new(),
             connecting_map, 
             connected_map, 
+            lingering: Arc::new(DashMap::new()),
+            migrations: DashMap::new(),
             broadcast, 
             commands, 
             level,
@@ -75,8 +110,8 @@ impl Clients {
     }   
 
     /// Inserts a user into the map.
-    pub(crate) fn insert(&self, info: RakNetCreateDescription) {
-        let (tx, rx) = mpsc::channel(BROADCAST_CHANNEL_CAPACITY);
+    pub(crate) fn insert(&self, info: RakNetCreateDescription, backpressure: BackpressurePolicy) {
+        let (tx, rx) = forward_channel(BROADCAST_CHANNEL_CAPACITY, backpressure);
 
         let address = info.address;
         let (state, state_rx) = 
@@ -95,7 +130,7 @@ impl Clients {
         // Callback to move the client from the connecting map to the connected map.
         // This is done when the Raknet layer attempts to send a message to the Bedrock layer
         // signalling that the Raknet connection is fully set up.
-        tokio::spawn(async move {
+        util::task::spawn_owned("clients::promote", address, async move {
             if let Some((_, raknet_user)) = connecting_map.remove(&address) {
                 let bedrock_user = UserMapEntry {
                     channel: raknet_user.channel, state: BedrockClient::new(
@@ -116,12 +151,30 @@ impl Clients {
 
         let connecting_map = Arc::clone(&self.connecting_map);
         let connected_map = Arc::clone(&self.connected_map);
+        let lingering = Arc::clone(&self.lingering);
         let state_clone = Arc::clone(&state);
 
-        tokio::spawn(async move {
+        util::task::spawn_owned("clients::cleanup", address, async move {
             state_clone.active.cancelled().await;
-            connected_map.remove(&state_clone.address);
-            connecting_map.remove(&state_clone.address);
+
+            // The connection may have migrated, in which case a new client could be using the original address.
+            let mut channel = None;
+            for key in [address, state_clone.address()] {
+                if let Some((_, entry)) = connected_map.remove_if(&key, |_, entry| Arc::ptr_eq(&entry.state.raknet, &state_clone)) {
+                    channel = Some(entry.channel);
+                }
+                if let Some((_, entry)) = connecting_map.remove_if(&key, |_, entry| Arc::ptr_eq(&entry.state, &state_clone)) {
+                    channel = Some(entry.channel);
+                }
+            }
+
+            // Keep forwarding datagrams until the session has received the acknowledgements of its final packets.
+            let Some(channel) = channel else { return };
+            let address = state_clone.address();
+            lingering.insert(address, channel.clone());
+
+            state_clone.shutdown_token.cancelled().await;
+            lingering.remove_if(&address, |_, other| other.same_channel(&channel));
         });
 
         self.connecting_map.insert(address, UserMapEntry {
@@ -161,22 +214,102 @@ impl Clients {
     }
 
     /// Attempts to retrieve the user with the given username.
+    ///
+    /// Usernames are compared case-insensitively, like in vanilla commands.
     pub fn by_username<S: AsRef<str>>(&self, username: S) -> Option<Arc<BedrockClient>> {
-        todo!()
+        let username = username.as_ref();
+        self.connected_map
+            .iter()
+            .find(|r| r.value().state.name().is_ok_and(|name| name.eq_ignore_ascii_case(username)))
+            .map(|r| Arc::clone(&r.value().state))
+    }
+
+    /// Returns all users that are fully connected to the server.
+    pub fn connected(&self) -> Vec<Arc<BedrockClient>> {
+        self.connected_map.iter().map(|r| Arc::clone(&r.value().state)).collect()
+    }
+
+    /// Starts migrating the connection with the given GUID to a new address.
+    ///
+    /// Returns `false` if there is no connected client with this GUID on another address.
+    /// Encrypted connections are never migrated, see [`RakNetClient::lock_migration`].
+    pub(crate) fn begin_migration(&self, address: SocketAddr, guid: u64) -> bool {
+        let Some(raknet) = self
+            .connected_map
+            .iter()
+            .find(|r| r.value().state.raknet.guid == guid && *r.key() != address && !r.value().state.raknet.is_migration_locked())
+            .map(|r| Arc::clone(&r.value().state.raknet))
+        else {
+            return false
+        };
+
+        self.migrations.insert(address, raknet.address());
+        util::task::spawn_owned("clients::migrate", address, async move {
+            if let Err(err) = raknet.begin_migration(address).await {
+                tracing::warn!("Unable to verify migration to {address}: {err:#}");
+            }
+        });
+
+        true
+    }
+
+    /// Determines which address a datagram from the given address should be forwarded to.
+    ///
+    /// Datagrams from the new address of a migrating connection are forwarded to the connection under its old
+    /// address. Once the migration has been verified, the connection is moved to its new address.
+    fn resolve_migration(&self, address: SocketAddr) -> SocketAddr {
+        let Some(old) = self.migrations.get(&address).map(|r| *r) else {
+            return address
+        };
+
+        let raknet = self.connected_map.get(&old).map(|r| Arc::clone(&r.value().state.raknet));
+        match raknet {
+            Some(raknet) if raknet.address() == address => {
+                if let Some((_, entry)) = self.connected_map.remove(&old) {
+                    self.connected_map.insert(address, entry);
+                }
+                self.migrations.remove(&address);
+
+                address
+            }
+            Some(raknet) if raknet.migration_target() == Some(address) => old,
+            _ => {
+                // The migration has failed or expired.
+                self.migrations.remove(&address);
+                address
+            }
+        }
     }
 
     /// Forwards a packet to a user within the map.
-    pub(crate) async fn forward(&self, packet: ForwardablePacket) -> anyhow::Result<()> {
+    pub(crate) async fn forward(&self, mut packet: ForwardablePacket) -> anyhow::Result<()> {
+        if !self.migrations.is_empty() {
+            packet.addr = self.resolve_migration(packet.addr);
+        }
+
         if let Some(user) = self.connected_map.get(&packet.addr) {
-            return user.channel.send_timeout(packet.buf, FORWARD_TIMEOUT)
-                .await
-                .context("Forwarding packet to user timed out")
+            if user.forward(packet.buf).await == Forwarded::Overloaded {
+                tracing::warn!("Client is not keeping up with its packets, disconnecting them...");
+                user.state.raknet.disconnect();
+            }
+
+            return Ok(())
         }
 
         if let Some(user) = self.connecting_map.get(&packet.addr) {
-            return user.channel.send_timeout(packet.buf, FORWARD_TIMEOUT)
-                .await
-                .context("Forwarding packet to connecting user timed out")
+            if user.forward(packet.buf).await == Forwarded::Overloaded {
+                tracing::warn!("Connecting client is not keeping up with its packets, disconnecting them...");
+                user.state.disconnect();
+            }
+
+            return Ok(())
+        }
+
+        if let Some(channel) = self.lingering.get(&packet.addr) {
+            // The session is closing, so there is no point in waiting if it is busy.
+            if channel.try_send(packet.buf).is_dropped() {
+                DROPPED_FORWARDS_METRIC.inc();
+            }
         }
 
         Ok(())
@@ -192,6 +325,15 @@ impl Clients {
         Ok(())
     }
 
+    /// Broadcasts the given packet to the clients selected by `recipients`.
+    pub fn broadcast_to<T: ConnectedPacket + Serialize>(&self, packet: T, recipients: Recipients) -> anyhow::Result<()> {
+        if self.broadcast.receiver_count() != 0 {
+            self.broadcast.send(BroadcastPacket::with_recipients(packet, recipients)?)?;
+        }
+
+        Ok(())
+    }
+
     /// How many clients are currently in the process of logging in.
     #[inline]
     pub fn total_connecting(&self) -> usize {
@@ -204,6 +346,22 @@ impl Clients {
         self.connected_map.len()
     }
 
+    /// Sum of the network statistics of all connections, including those that are still logging in.
+    pub fn network_stats(&self) -> SessionStats {
+        let connecting = self.connecting_map.iter().map(|r| r.value().state.stats());
+        let connected = self.connected_map.iter().map(|r| r.value().state.network_stats());
+
+        connecting.chain(connected).sum()
+    }
+
+    /// Amount of packets waiting in the forward channels of all connections.
+    pub fn forward_queue_depth(&self) -> usize {
+        let connecting = self.connecting_map.iter().map(|r| r.value().queue_depth());
+        let connected = self.connected_map.iter().map(|r| r.value().queue_depth());
+
+        connecting.chain(connected).sum()
+    }
+
     /// Maximum amount of concurrently connected users.
     pub fn max_connections(&self) -> usize {
         self.instance().config().max_connections()
@@ -214,7 +372,7 @@ impl Clients {
     /// This function returns a handle that can be used to await shutdown.
     pub(crate) fn shutdown(self: &Arc<Clients>) -> JoinHandle<anyhow::Result<()>> {
         let this = Arc::clone(self);
-        tokio::spawn(async move {
+        util::task::spawn("clients::shutdown", async move {
             tracing::info!("Disconnecting all clients");
 
             // Ignore result because it can only fail if there are no receivers remaining.
@@ -238,12 +396,10 @@ impl Clients {
             });
 
             this.connected_map.retain(|_, user| {
-                let _: anyhow::Result<()> = user.state.send(Disconnect {
-                    hide_message: false,
-                    message: "Server shutting down",
-                    reason: DisconnectReason::Shutdown
-                });
-                user.state.raknet.active.cancel();
+                if let Err(err) = user.state.kick_with_reason("Server shutting down", DisconnectReason::Shutdown) {
+                    tracing::warn!("Failed to send shutdown message: {err:#}");
+                    user.state.raknet.disconnect();
+                }
 
                 let clone = Arc::clone(&user.state);
                 join_set.spawn(async move { clone.join().await });
@@ -0,0 +1,249 @@
+//! Preset deflate dictionaries for packets that share a lot of bytes.
+//!
+//! Packets such as movement updates and chunk data are small and highly repetitive. Deflate cannot
+//! exploit this within a single packet, but priming the compressor with a dictionary containing the
+//! common prefixes of a packet class allows those prefixes to be replaced by back references.
+//!
+//! Vanilla clients do not support this. Dictionaries are only used for clients that advertise the
+//! same [`DICTIONARY_VERSION`] in their login data, such as proxies and custom clients.
+//! All other packets and clients fall back to regular deflate compression.
+
+use std::io::Write;
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use lazy_static::lazy_static;
+use proto::bedrock::{ConnectedPacket, Header, CONNECTED_PACKET_ID, LevelChunk, MovePlayer, MovementMode, PlayerAuthInput, SubChunkResponse, TeleportCause};
+use proto::types::{Angle, HeadYaw};
+use util::{BinaryRead, BinaryWrite, Deserialize, RVec, Serialize, Vector};
+
+use crate::net::BedrockClient;
+
+/// Version of the dictionaries implemented by the server.
+///
+/// This must be increased whenever the contents of a dictionary change, since both sides need
+/// to use exactly the same dictionary.
+pub const DICTIONARY_VERSION: u32 = 1;
+/// Compression algorithm IDs starting at this value indicate dictionary compression.
+/// The class of the packet is added to this value.
+const CLASS_ALGORITHM_OFFSET: u8 = 0x10;
+/// Decompressed packets larger than this are rejected to prevent decompression bombs.
+const MAX_DECOMPRESSED_SIZE: usize = 8 * 1024 * 1024;
+
+lazy_static! {
+    static ref MOVEMENT_DICTIONARY: Vec<u8> = movement_dictionary();
+    static ref CHUNK_DICTIONARY: Vec<u8> = chunk_dictionary();
+}
+
+/// A group of packets that share a dictionary.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PacketClass {
+    /// Player and entity movement.
+    Movement,
+    /// Chunk and sub chunk data.
+    Chunk,
+}
+
+impl PacketClass {
+    /// Returns the class of the packet with the given ID, if it has a dictionary.
+    pub const fn of(id: u32) -> Option<Self> {
+        match id {
+            MovePlayer::ID | PlayerAuthInput::ID => Some(Self::Movement),
+            LevelChunk::ID | SubChunkResponse::ID => Some(Self::Chunk),
+            _ => None,
+        }
+    }
+
+    /// Determines the class of a serialized game packet by reading its header.
+    pub fn of_serialized(mut packet: &[u8]) -> Option<Self> {
+        packet.read_var_u32().ok()?;
+        let header = Header::deserialize_from(&mut packet).ok()?;
+
+        Self::of(header.id)
+    }
+
+    /// The compression algorithm ID that indicates this class in the packet header.
+    pub const fn algorithm_id(self) -> u8 {
+        CLASS_ALGORITHM_OFFSET + self as u8
+    }
+
+    /// Returns the class that is indicated by the given compression algorithm ID.
+    pub const fn from_algorithm_id(id: u8) -> Option<Self> {
+        match id.wrapping_sub(CLASS_ALGORITHM_OFFSET) {
+            0 => Some(Self::Movement),
+            1 => Some(Self::Chunk),
+            _ => None,
+        }
+    }
+
+    /// The preset dictionary of this class.
+    pub fn dictionary(self) -> &'static [u8] {
+        match self {
+            Self::Movement => &MOVEMENT_DICTIONARY,
+            Self::Chunk => &CHUNK_DICTIONARY,
+        }
+    }
+}
+
+/// Compresses a packet using raw deflate, primed with the dictionary of the given class.
+pub fn compress(class: PacketClass, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut compressor = Compress::new(Compression::best(), false);
+    compressor.set_dictionary(class.dictionary())?;
+
+    let mut out = Vec::with_capacity(data.len() / 2 + 64);
+    loop {
+        let consumed = compressor.total_in() as usize;
+        if compressor.compress_vec(&data[consumed..], &mut out, FlushCompress::Finish)? == Status::StreamEnd {
+            return Ok(out);
+        }
+
+        out.reserve(out.capacity());
+    }
+}
+
+/// Decompresses a packet that was compressed with [`compress`].
+pub fn decompress(class: PacketClass, data: &[u8]) -> anyhow::Result<RVec> {
+    let mut decompressor = Decompress::new(false);
+    decompressor.set_dictionary(class.dictionary())?;
+
+    let mut out = RVec::alloc_with_capacity(data.len() * 2);
+    loop {
+        let consumed = decompressor.total_in() as usize;
+        let produced = decompressor.total_out();
+
+        let status = decompressor.decompress_vec(&data[consumed..], &mut out, FlushDecompress::Finish)?;
+        if status == Status::StreamEnd {
+            return Ok(out);
+        }
+
+        if out.len() > MAX_DECOMPRESSED_SIZE {
+            anyhow::bail!("Decompressed packet exceeds the maximum size of {MAX_DECOMPRESSED_SIZE} bytes");
+        }
+
+        if decompressor.total_in() as usize == data.len() && decompressor.total_out() == produced {
+            anyhow::bail!("Compressed packet is truncated");
+        }
+
+        let capacity = out.capacity();
+        out.reserve(capacity);
+    }
+}
+
+/// Appends a serialized packet and its header to the dictionary.
+fn sample<P: ConnectedPacket + Serialize>(dictionary: &mut Vec<u8>, packet: &P) {
+    let header = Header { id: P::ID, sender_subclient: 0, target_subclient: 0 };
+
+    let mut body = Vec::new();
+    if header.serialize_into(&mut body).is_err() || packet.serialize_into(&mut body).is_err() {
+        return;
+    }
+
+    // The length prefix differs for every packet, the rest is what the dictionary is for.
+    dictionary.extend_from_slice(&body);
+}
+
+/// Builds the movement dictionary from a set of typical movement packets.
+///
+/// Deflate prefers recent matches, so the most common packets are placed at the end.
+fn movement_dictionary() -> Vec<u8> {
+    let mut dictionary = Vec::new();
+    for (mode, on_ground) in [
+        (MovementMode::Teleport, false),
+        (MovementMode::Reset, true),
+        (MovementMode::Normal, false),
+        (MovementMode::Normal, true),
+    ] {
+        sample(
+            &mut dictionary,
+            &MovePlayer {
+                runtime_id: 1,
+                translation: Vector::from([0.5, 64.0 + 1.62, 0.5]),
+                pitch: Angle::from_degrees(0.0),
+                yaw: Angle::from_degrees(90.0),
+                head_yaw: HeadYaw::from_degrees(90.0),
+                mode,
+                on_ground,
+                ridden_runtime_id: 0,
+                teleport_cause: TeleportCause::Unknown,
+                teleport_source_type: 0,
+                tick: 0,
+            },
+        );
+    }
+
+    dictionary
+}
+
+/// Builds the chunk dictionary.
+///
+/// Chunk payloads mainly consist of palettes and block entity NBT, which repeat the same identifiers and keys.
+fn chunk_dictionary() -> Vec<u8> {
+    const STRINGS: &[&str] = &[
+        "minecraft:chest", "minecraft:sign", "minecraft:furnace", "minecraft:hopper",
+        "Items", "Slot", "Count", "Damage", "Name", "Block", "WasPickedUp", "FrontText", "BackText", "Text",
+        "isMovable", "CustomName", "id", "x", "y", "z",
+        "minecraft:water", "minecraft:deepslate", "minecraft:stone", "minecraft:dirt", "minecraft:grass_block", "minecraft:air",
+    ];
+
+    let mut dictionary = Vec::new();
+    for string in STRINGS {
+        // Network NBT prefixes strings with their length as a varint, which fits in a single byte for these.
+        dictionary.push(string.len() as u8);
+        dictionary.extend_from_slice(string.as_bytes());
+    }
+
+    // Empty sub chunks, heightmaps and biomes contain long runs of zeroes.
+    dictionary.extend_from_slice(&[0; 64]);
+    dictionary
+}
+
+impl BedrockClient {
+    /// Compresses a packet with the dictionary of its class.
+    ///
+    /// Packets without a dictionary, and packets that fail to compress, fall back to regular deflate compression.
+    pub(super) fn compress_with_dictionary(packet: &[u8]) -> anyhow::Result<RVec> {
+        let Some(class) = PacketClass::of_serialized(packet) else {
+            return Self::compress_flate(packet);
+        };
+
+        let compressed_body = match compress(class, packet) {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::warn!("Dictionary compression failed, falling back to deflate: {err:#}");
+                return Self::compress_flate(packet);
+            }
+        };
+
+        let mut out = RVec::alloc_with_capacity(1 + 1 + compressed_body.len());
+        out.write_u8(CONNECTED_PACKET_ID)?;
+        out.write_u8(class.algorithm_id())?;
+        out.write_all(&compressed_body)?;
+
+        Ok(out)
+    }
+
+    /// Returns the packet class indicated by a compression algorithm ID.
+    ///
+    /// Dictionary algorithm IDs are only accepted if dictionaries were negotiated during login.
+    pub(super) fn dictionary_class(&self, algorithm: u8) -> Option<PacketClass> {
+        if !self.use_dictionaries.get() {
+            return None;
+        }
+
+        PacketClass::from_algorithm_id(algorithm)
+    }
+
+    /// Enables dictionaries if they are enabled in the config and the client supports the same version.
+    pub(super) fn negotiate_dictionaries(&self, client_version: u32) {
+        let instance = self.instance();
+        let compression = instance.config().compression();
+        if compression.use_dictionaries(client_version) {
+            self.use_dictionaries.set();
+            tracing::debug!("Enabled compression dictionaries version {DICTIONARY_VERSION}");
+        } else if compression.dictionaries && client_version != 0 {
+            tracing::debug!(
+                "Client supports compression dictionaries version {client_version}, server uses {DICTIONARY_VERSION}. Falling back to deflate"
+            );
+        }
+    }
+}
@@ -1,23 +1,26 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use futures::{future, StreamExt};
 use level::{BiomeEncoding, BiomeStorage, Biomes, SubChunk, SubStorage};
 use proto::{
     bedrock::{
-        Animate, CommandOutput, CommandOutputMessage, CommandOutputType, CommandRequest, DisconnectReason, FormResponseData, HeightmapType,
-        HudElement, HudVisibility, InventoryTransaction, ItemInstance, LevelChunk, MobEquipment, NetworkChunkPublisherUpdate, PlayerAuthInput,
-        RequestAbility, SetHud, SetInventoryOptions, SettingsCommand, SubChunkEntry, SubChunkRequestMode, SubChunkResponse, SubChunkResult, TextData,
-        TextMessage, TickSync, TransactionAction, TransactionSourceType, TransactionType, UpdateSkin, WindowId,
+        Animate, CommandOutput, CommandOutputMessage, CommandOutputType, CommandRequest, DisconnectReason, FormResponseData, GameMode, HeightmapType,
+        HudElement, HudVisibility, InventorySlot, InventoryTransaction, ItemInstance, LevelChunk, MobEquipment, NetworkChunkPublisherUpdate,
+        PlaySound, PlayerAuthInput, RequestAbility, SetHud, SetInventoryOptions, SettingsCommand, SubChunkEntry, SubChunkRequestMode, SubChunkResponse,
+        SubChunkResult, TextData, TextMessage, TickSync, TransactionAction, TransactionSourceType, TransactionType, UpdateSkin, UseItemAction,
+        UseOnEntityAction, WindowId,
     },
     types::Dimension,
 };
 
-use util::{BinaryRead, BinaryWrite, CowSlice, Deserialize, RVec};
+use util::{BinaryRead, BinaryWrite, CowSlice, Deserialize, RVec, Vector};
 
+use crate::cooldown::actions;
+use crate::item::{self, DamageSource, DurabilityOutcome, StoredItem};
 use crate::level::io::r#box::BoxRegion;
 use crate::level::io::stream::IndexedSubChunk;
 
-use super::BedrockClient;
+use super::{millis_to_ticks, BedrockClient};
 
 impl BedrockClient {
     /// Handles a mob equipment packet.
@@ -44,6 +47,28 @@ impl BedrockClient {
     pub fn handle_inventory_transaction(&self, packet: RVec) -> anyhow::Result<()> {
         let transaction = InventoryTransaction::deserialize(packet.as_ref())?;
         tracing::debug!("{transaction:?}");
+
+        if let TransactionType::Use { action_type: UseItemAction::BreakBlock | UseItemAction::ClickBlock, block_position, .. } =
+            &transaction.transaction_type
+        {
+            self.instance().level().chunk_stats().record_block_update(self.dimension()?, block_position);
+        }
+
+        let player = self.player()?;
+        if let Err(slots) = player.inventory.apply(&transaction.actions) {
+            tracing::debug!("Rejecting inventory transaction, slots {slots:?} do not match the server's inventory");
+            return self.resend_inventory_slots(&slots);
+        }
+
+        match &transaction.transaction_type {
+            TransactionType::Use { action_type: UseItemAction::BreakBlock, hotbar_slot, .. } => {
+                self.apply_item_damage(*hotbar_slot, DamageSource::BlockBreak)?;
+            }
+            TransactionType::UseOnEntity { action_type: UseOnEntityAction::Attack, hotbar_slot, .. } => {
+                self.apply_item_damage(*hotbar_slot, DamageSource::Attack)?;
+            }
+            _ => (),
+        }
         // let action = &transaction.actions[0];
         // let item = &action.new_item;
 
@@ -91,6 +116,66 @@ impl BedrockClient {
         Ok(())
     }
 
+    /// Sends the server's version of the given inventory slots to the client.
+    fn resend_inventory_slots(&self, slots: &[u32]) -> anyhow::Result<()> {
+        let player = self.player()?;
+        for &slot in slots {
+            let item = player.inventory.get(slot).unwrap_or_default();
+            self.send(InventorySlot { window_id: WindowId::Inventory, slot, item: item.as_instance() })?;
+        }
+
+        Ok(())
+    }
+
+    /// Wears down the item in the given hotbar slot and synchronises the new state with the client.
+    ///
+    /// The item is taken from the server's inventory rather than the held item sent by the client.
+    /// Items that run out of durability are removed from the inventory and play the breaking sound.
+    fn apply_item_damage(&self, hotbar_slot: i32, source: DamageSource) -> anyhow::Result<()> {
+        let player = self.player()?;
+        if player.gamemode() == GameMode::Creative {
+            return Ok(());
+        }
+
+        let slot = u32::try_from(hotbar_slot)?;
+        let Some(stack) = player.inventory.get(slot) else {
+            anyhow::bail!("Hotbar slot {hotbar_slot} does not exist");
+        };
+
+        let instance = self.instance();
+        let registries = instance.registries();
+        let Some(name) = registries.items.get_name(stack.network_id) else {
+            return Ok(());
+        };
+
+        let Some(max) = item::max_durability(name) else {
+            return Ok(());
+        };
+
+        let mut item = stack.as_instance();
+        let outcome = item::apply_damage(&mut item, max, item::damage_cost(name, source));
+
+        match outcome {
+            DurabilityOutcome::Unchanged => Ok(()),
+            DurabilityOutcome::Damaged { .. } => {
+                player.inventory.set(slot, StoredItem::from_instance(&item));
+                self.send(InventorySlot { window_id: WindowId::Inventory, slot, item })
+            }
+            DurabilityOutcome::Broken => {
+                player.inventory.set(slot, StoredItem::default());
+                self.send(InventorySlot { window_id: WindowId::Inventory, slot, item: ItemInstance::air() })?;
+                // Sound positions are encoded as fixed point numbers with 3 fractional bits.
+                let position = player.position();
+                self.send(PlaySound {
+                    name: "random.break",
+                    position: Vector::from([(position.x * 8.0) as i32, (position.y * 8.0) as i32, (position.z * 8.0) as i32]),
+                    volume: 1.0,
+                    pitch: 1.0,
+                })
+            }
+        }
+    }
+
     /// Handles a [`SettingsCommand`] packet used to adjust a world setting.
     pub fn handle_settings_command(&self, packet: RVec) -> anyhow::Result<()> {
         let request = SettingsCommand::deserialize(packet.as_ref())?;
@@ -100,15 +185,22 @@ impl BedrockClient {
     }
 
     /// Handles a [`TickSync`] packet used to synchronise ticks between the client and server.
+    ///
+    /// The client tick also serves as a sample for the tick model of the connection.
     pub fn handle_tick_sync(&self, packet: RVec) -> anyhow::Result<()> {
-        let _request = TickSync::deserialize(packet.as_ref())?;
-        // TODO: Implement tick synchronisation
-        Ok(())
-        // let response = TickSync {
-        //     request_tick: request.request_tick,
-        //     response_tick: self.level.
-        // };
-        // self.send(response)
+        let request = TickSync::deserialize(packet.as_ref())?;
+
+        let now = self.raknet.latency.timestamp();
+        self.record_client_tick(request.request_tick, now);
+
+        self.send(TickSync { request_tick: request.request_tick, response_tick: millis_to_ticks(now) })
+    }
+
+    /// Feeds a tick reported by the client into the tick model.
+    fn record_client_tick(&self, tick: u64, received: i64) {
+        if let Some(rtt) = self.raknet.latency() {
+            self.ticks.record(tick, received, rtt);
+        }
     }
 
     /// Handles a [`TextMessage`] packet sent when a client wants to send a chat message.
@@ -132,6 +224,14 @@ impl BedrockClient {
                 return self.kick_with_reason("Illegal packet modifications detected", DisconnectReason::BadPacket);
             }
 
+            let cooldown = self.instance().config().cooldowns().chat;
+            if let Err(remaining) = self.instance().cooldowns().try_acquire(self.xuid()?, actions::CHAT, cooldown) {
+                tracing::debug!("Dropping chat message, sender is on cooldown for another {remaining:?}");
+                return Ok(());
+            }
+
+            self.chat_history.record(message);
+
             // We must also return the packet to the client that sent it.
             // Otherwise their message won't be displayed in their own chat.
             self.broadcast(request)
@@ -144,13 +244,31 @@ impl BedrockClient {
 
     /// Handles a [`PlayerAuthInput`] packet. These are sent every tick and are used
     /// for server authoritative player movement.
-    pub fn handle_auth_input(&self, packet: RVec) -> anyhow::Result<()> {
+    pub fn handle_auth_input(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
         let input = PlayerAuthInput::deserialize(packet.as_ref())?;
         if input.input_data.0 != 0 {
             // tracing::debug!("{:?}", input.input_data);
         }
+
+        // A client that runs ahead of its own tick rate is speeding up its simulation.
+        let now = self.raknet.latency.timestamp();
+        if self.ticks.is_ahead(input.tick, now) {
+            tracing::debug!("Dropping input for tick {}, client is ahead of its tick rate", input.tick);
+            return Ok(());
+        }
+        self.record_client_tick(input.tick, now);
+
+        // Auth input is sent every tick, even if the player is not doing anything.
+        if input.moved.x != 0.0 || input.moved.y != 0.0 || input.item_transaction.is_some() || input.block_actions.is_some() {
+            self.record_input();
+        } else {
+            self.record_rotation(input.pitch, input.yaw);
+        }
         
-        Ok(())
+        *self.player()?.position.lock() = input.position.clone();
+        self.viewer.update_position(Vector::from([input.position.x, input.position.z]), self.dimension()?);
+        self.handle_portal_movement(&input.position)?;
+        self.handle_vehicle_input(&input)
     }
 
     /// Handles an [`UpdateSkin`] packet.
@@ -189,7 +307,7 @@ impl BedrockClient {
                         can_place_on: vec![],
                         count: 12,
                         metadata: 0,
-                        nbt: HashMap::new(),
+                        nbt: nbt::Compound::new(),
                         stack_id: None,
                     },
                 },
@@ -205,7 +323,7 @@ impl BedrockClient {
                         can_place_on: vec![],
                         count: 12,
                         metadata: 0,
-                        nbt: HashMap::new(),
+                        nbt: nbt::Compound::new(),
                         stack_id: None,
                     },
                 },
@@ -230,7 +348,7 @@ impl BedrockClient {
                         can_place_on: vec![],
                         count: 12,
                         metadata: 0,
-                        nbt: HashMap::new(),
+                        nbt: nbt::Compound::new(),
                         stack_id: None,
                     },
                 },
@@ -246,7 +364,7 @@ impl BedrockClient {
                         can_place_on: vec![],
                         count: 12,
                         metadata: 0,
-                        nbt: HashMap::new(),
+                        nbt: nbt::Compound::new(),
                         stack_id: None,
                     },
                 },
@@ -366,7 +484,7 @@ impl BedrockClient {
 
         // Command execution could take several ticks, await the result in a separate task
         // to avoid blocking the request handler.
-        tokio::spawn(async move {
+        util::task::spawn("command::request", async move {
             let request = match CommandRequest::deserialize(packet.as_ref()) {
                 Ok(req) => req,
                 Err(err) => {
@@ -376,6 +494,14 @@ impl BedrockClient {
             };
             tracing::Span::current().record("command", request.command);
 
+            if let Ok(xuid) = self.xuid() {
+                let cooldown = self.instance().config().cooldowns().command;
+                if let Err(remaining) = self.instance().cooldowns().try_acquire(xuid, actions::COMMAND, cooldown) {
+                    tracing::debug!("Ignoring command request, sender is on cooldown for another {remaining:?}");
+                    return;
+                }
+            }
+
             let receiver = match self.commands.execute(Arc::clone(&self), request.command.to_owned()).await {
                 Ok(r) => r,
                 Err(e) => {
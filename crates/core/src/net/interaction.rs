@@ -1,6 +1,6 @@
 use std::sync::atomic::Ordering;
 
-use proto::bedrock::{ABILITY_FLYING, AbilityData, AbilityLayer, AbilityType, ContainerClose, ContainerOpen, ContainerType, GameMode, Interact, InteractAction, INVENTORY_WINDOW_ID, MovePlayer, PlayerAction, PlayerActionType, UpdateAbilities, ABILITY_FLAG_END};
+use proto::bedrock::{ABILITY_FLYING, ContainerClose, ContainerOpen, ContainerType, GameMode, Interact, InteractAction, INVENTORY_WINDOW_ID, MovePlayer, PlayerAction, PlayerActionType, UpdateAbilities};
 use util::{RVec, Deserialize};
 
 use super::BedrockClient;
@@ -9,6 +9,9 @@ impl BedrockClient {
     /// Handles an [`Interact`] packet.
     pub fn handle_interaction(&self, packet: RVec) -> anyhow::Result<()> {
         let request = Interact::deserialize(packet.as_ref())?;
+        if request.action == InteractAction::LeaveVehicle {
+            return self.handle_leave_vehicle(&request);
+        }
       
         if request.action == InteractAction::OpenInventory && !self.player()?.is_inventory_open.fetch_or(true, Ordering::Relaxed) {
             self.send(ContainerOpen {
@@ -39,7 +42,9 @@ impl BedrockClient {
 
     /// Handles a [`MovePlayer`] packet.
     pub fn handle_move_player(&self, packet: RVec) -> anyhow::Result<()> {
-        let _request = MovePlayer::deserialize(packet.as_ref())?;
+        let request = MovePlayer::deserialize(packet.as_ref())?;
+        self.record_input();
+        self.record_rotation(request.pitch.degrees(), request.yaw.degrees());
 
         Ok(())
         // self.replicator.move_player(self.xuid(), &request).await?;
@@ -56,6 +61,9 @@ impl BedrockClient {
         match request.action {
             PlayerActionType::StartFlying => self.action_start_flying(request),
             PlayerActionType::StopFlying => self.action_stop_flying(request),
+            PlayerActionType::DimensionChangeAcknowledgement => self.handle_dimension_change_ack(),
+            PlayerActionType::StartSleeping => self.start_sleeping(),
+            PlayerActionType::StopSleeping => self.stop_sleeping(),
             _ => Ok(())
         }
     }
@@ -70,22 +78,9 @@ impl BedrockClient {
         // Only allow flying if the player is in the correct gamemode.
         let gamemode = player.gamemode();
         if gamemode == GameMode::Creative || gamemode == GameMode::SurvivalSpectator {
-            self.send(UpdateAbilities(
-                AbilityData {
-                    command_permission_level: player.command_permission_level(),
-                    permission_level: player.permission_level(),
-                    unique_id: player.runtime_id(),
-                    layers: vec![
-                        AbilityLayer {
-                            fly_speed: 0.05,
-                            walk_speed: 0.1,
-                            values: ABILITY_FLYING,
-                            abilities: ABILITY_FLAG_END - 1,
-                            ability_type: AbilityType::Base
-                        }
-                    ]
-                },
-            ))?;
+            let mut abilities = player.ability_data();
+            abilities.set(ABILITY_FLYING, true);
+            self.send(UpdateAbilities(abilities))?;
         }
 
         Ok(())
@@ -95,22 +90,9 @@ impl BedrockClient {
     fn action_stop_flying(&self, _action: PlayerAction) -> anyhow::Result<()> {
         let player = self.player()?;
 
-        self.send(UpdateAbilities(
-            AbilityData {
-                command_permission_level: player.command_permission_level,
-                permission_level: player.permission_level(),
-                unique_id: player.runtime_id(),
-                layers: vec![
-                    AbilityLayer {
-                        fly_speed: 0.05,
-                        walk_speed: 0.1,
-                        values: 0,
-                        abilities: ABILITY_FLAG_END - 1,
-                        ability_type: AbilityType::Base
-                    }
-                ]
-            }
-        ))?;
+        let mut abilities = player.ability_data();
+        abilities.set(ABILITY_FLYING, false);
+        self.send(UpdateAbilities(abilities))?;
 
         Ok(())
     }
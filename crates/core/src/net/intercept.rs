@@ -0,0 +1,29 @@
+//! Interception of incoming game packets.
+//!
+//! Interceptors see every game packet sent by a client before the server handles it. This allows embedders to
+//! inspect packets that the server does not expose through other APIs, or to filter packets entirely.
+
+use super::BedrockClient;
+
+/// Decision of an interceptor about an incoming packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interception {
+    /// The packet is handled as usual.
+    Pass,
+    /// The packet is silently discarded. Later interceptors do not see it either.
+    Drop,
+}
+
+/// Inspects incoming game packets before they are handled.
+///
+/// Interceptors are registered using [`InstanceBuilder::interceptor`](crate::instance::InstanceBuilder::interceptor)
+/// and run in the order that they were registered in.
+pub trait PacketInterceptor: Send + Sync {
+    /// Called for every game packet received from `client`.
+    ///
+    /// `packet` contains the body of the packet with ID `id`, which can be decoded using the
+    /// [`Deserialize`](util::Deserialize) implementation of the packet. This is called from the receiver task of
+    /// the client, so it should not block. Packets sent during login are intercepted too, in which case the
+    /// identity of the client is not known yet.
+    fn inbound(&self, client: &BedrockClient, id: u32, packet: &[u8]) -> Interception;
+}
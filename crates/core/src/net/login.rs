@@ -1,7 +1,7 @@
 use level::PaletteEntry;
 use proto::bedrock::{
-    BiomeDefinitionList, BroadcastIntent, CacheStatus, ChatRestrictionLevel, ChunkRadiusReply, ChunkRadiusRequest, ClientToServerHandshake,
-    ConnectedPacket, CreativeContent, Difficulty, DisconnectReason, EditorWorldType, ExperimentData, GameMode, GameRule, HeightmapType,
+    BiomeDefinitionList, BroadcastIntent, CacheBlob, CacheBlobStatus, CacheMissResponse, CacheStatus, ChatRestrictionLevel, ChunkRadiusReply, ChunkRadiusRequest, ClientToServerHandshake,
+    CreativeContent, Difficulty, DisconnectReason, EditorWorldType, ExperimentData, GameMode, GameRule, HeightmapType,
     InventoryTransaction, ItemInstance, LevelChunk, Login, NetworkChunkPublisherUpdate, NetworkSettings, PermissionLevel, PlayStatus,
     PlayerMovementSettings, PlayerMovementType, PropertyData, RequestNetworkSettings, ResourcePackClientResponse, ResourcePackStack,
     ResourcePacksInfo, ServerToClientHandshake, SetLocalPlayerAsInitialized, SpawnBiomeType, StartGame, Status, SubChunkEntry, SubChunkRequestMode,
@@ -17,7 +17,7 @@ use util::{BlockPosition, Deserialize, RVec, Vector};
 
 use crate::net::PlayerData;
 
-use super::BedrockClient;
+use super::{BedrockClient, LoginState};
 
 impl BedrockClient {
     /// Handles a [`CacheStatus`] packet.
@@ -30,8 +30,6 @@ impl BedrockClient {
         )
     )]
     pub fn handle_cache_status(&self, packet: RVec) -> anyhow::Result<()> {
-        self.expected.store(ResourcePackClientResponse::ID, Ordering::SeqCst);
-
         let request = CacheStatus::deserialize(packet.as_ref())?;
         self.supports_cache.store(request.supports_cache, Ordering::Relaxed);
 
@@ -40,6 +38,27 @@ impl BedrockClient {
         Ok(())
     }
 
+    /// Handles a [`CacheBlobStatus`] packet.
+    ///
+    /// Blobs that the client is missing from its cache are sent in full.
+    pub fn handle_cache_blob_status(&self, packet: RVec) -> anyhow::Result<()> {
+        let status = CacheBlobStatus::deserialize(packet.as_ref())?;
+
+        let mut pending = self.pending_blobs.lock();
+        for hash in &status.hits {
+            pending.remove(hash);
+        }
+        let missing: Vec<_> = status.misses.iter().filter_map(|hash| pending.remove(hash).map(|payload| (*hash, payload))).collect();
+        drop(pending);
+
+        if missing.is_empty() {
+            return Ok(())
+        }
+
+        let blobs: Vec<_> = missing.iter().map(|(hash, payload)| CacheBlob { hash: *hash, payload: payload.as_ref() }).collect();
+        self.send(CacheMissResponse { blobs: &blobs })
+    }
+
     /// Handles a packet violation warning.
     #[tracing::instrument(
         skip_all,
@@ -69,7 +88,7 @@ impl BedrockClient {
     )]
     pub fn handle_local_initialized(&self, packet: RVec) -> anyhow::Result<()> {
         let _request = SetLocalPlayerAsInitialized::deserialize(packet.as_ref())?;
-        self.expected.store(u32::MAX, Ordering::SeqCst);
+        self.login.advance(LoginState::InGame);
 
         tracing::debug!("Player fully initialised");
 
@@ -135,7 +154,8 @@ impl BedrockClient {
                 platform_chat_id: "",
             })?;
             
-            let stack = &self.instance().creative_items.stacks[1];
+            let registries = self.instance().registries();
+            let stack = &registries.creative.stacks[1];
             tracing::debug!("stack: {stack:?}");
         }   
 
@@ -171,20 +191,21 @@ impl BedrockClient {
 
     /// Handles a [`ResourcePackClientResponse`] packet.
     pub fn handle_resource_client_response(&self, packet: RVec) -> anyhow::Result<()> {
-        self.expected.store(u32::MAX, Ordering::SeqCst);
+        self.login.advance(LoginState::InGame);
 
         let _request = ResourcePackClientResponse::deserialize(packet.as_ref())?;
         tracing::debug!("Received resource pack client response");
 
         // TODO: Implement resource packs.
 
+        let instance = self.instance();
         let start_game = StartGame {
             entity_id: 1,
             runtime_id: 1,
             game_mode: self.player()?.gamemode(),
             position: Vector::from([0.0, 6.0, 0.0]),
             rotation: Vector::from([0.0, 0.0]),
-            world_seed: 0,
+            world_seed: instance.level().seed().for_client(instance.config().level().seed_privacy, self.xuid()?),
             spawn_biome_type: SpawnBiomeType::Default,
             custom_biome_name: "plains",
             dimension: Dimension::Overworld,
@@ -214,7 +235,7 @@ impl BedrockClient {
             experiments_previously_enabled: false,
             bonus_chest_enabled: false,
             starter_map_enabled: false,
-            permission_level: PermissionLevel::Operator,
+            permission_level: self.player().map_or(PermissionLevel::Member, PlayerData::permission_level),
             server_chunk_tick_range: 12,
             has_locked_behavior_pack: false,
             has_locked_resource_pack: false,
@@ -239,7 +260,7 @@ impl BedrockClient {
                 rewind_history_size: 0,
                 server_authoritative_breaking: true,
             },
-            time: 0,
+            time: self.instance().level().time().get(),
             enchantment_seed: 0,
             // block_properties: &[BlockEntry {
             //     name: "minecraft:bedrock".to_owned(),
@@ -264,10 +285,11 @@ impl BedrockClient {
         let available_commands = self.commands.available_commands();
         self.send(available_commands)?;
 
-        tracing::debug!("{:?}", self.instance().creative_items.stacks);
+        let registries = self.instance().registries();
+        tracing::debug!("{:?}", registries.creative.stacks);
 
         let creative_content = CreativeContent {
-            items: &self.instance().creative_items.stacks,
+            items: &registries.creative.stacks,
         };
         self.send(creative_content)?;
 
@@ -287,7 +309,7 @@ impl BedrockClient {
         )
     )]
     pub fn handle_client_to_server_handshake(&self, packet: RVec) -> anyhow::Result<()> {
-        self.expected.store(CacheStatus::ID, Ordering::SeqCst);
+        self.login.advance(LoginState::ResourcePacks);
 
         ClientToServerHandshake::deserialize(packet.as_ref())?;
         tracing::debug!("Encryption handshake successful");
@@ -328,11 +350,11 @@ impl BedrockClient {
         name = "BedrockUser::handle_login",
         fields(
             // username is not yet known at this point.
-            address = %self.raknet.address
+            address = %self.raknet.address()
         )
     )]
     pub async fn handle_login(&self, packet: RVec) -> anyhow::Result<()> {
-        self.expected.store(ClientToServerHandshake::ID, Ordering::SeqCst);
+        self.login.advance(LoginState::Handshake);
 
         let Ok(request) = Login::deserialize(packet.as_ref()) else {
             // Kick the player when login fails. This is for security reasons.
@@ -353,6 +375,9 @@ impl BedrockClient {
             return self.kick_with_reason("Unexpected login", DisconnectReason::UnexpectedPacket);
         }
 
+        #[cfg(feature = "compression-dictionary")]
+        self.negotiate_dictionaries(request.client_info.compression_dictionary_version);
+
         if self.client_info.set(request.client_info).is_err() {
             tracing::error!("Client info was already set");
             return self.kick_with_reason("Unexpected login", DisconnectReason::UnexpectedPacket);
@@ -368,8 +393,15 @@ impl BedrockClient {
             tracing::warn!("Client unexpectedly sent a second login packet");
             return self.kick_with_reason("Unexpected login", DisconnectReason::UnexpectedPacket);
         }
+        // A migration cannot prove that the client knows the encryption key.
+        self.raknet.lock_migration();
+
+        let mut player = PlayerData::new(request.skin);
+        if self.xuid().is_ok_and(|xuid| self.instance().config().is_operator(xuid)) {
+            player = player.operator();
+        }
 
-        if self.player.set(PlayerData::new(request.skin)).is_err() {
+        if self.player.set(player).is_err() {
             anyhow::bail!("Player data was already set");
         };
 
@@ -381,11 +413,11 @@ impl BedrockClient {
         skip_all,
         name = "BedrockUser::handle_network_settings_request",
         fields(
-            address = %self.raknet.address
+            address = %self.raknet.address()
         )
     )]
     pub fn handle_network_settings_request(&self, packet: RVec) -> anyhow::Result<()> {
-        self.expected.store(Login::ID, Ordering::SeqCst);
+        self.login.advance(LoginState::Login);
 
         let request = RequestNetworkSettings::deserialize(packet.as_ref())?;
         if request.protocol_version != PROTOCOL_VERSION {
@@ -5,10 +5,24 @@
 
 use ::util::glob_export;
 
+pub use raknet::{Reliability, SendPriority};
+
 glob_export!(level);
+glob_export!(batch);
 glob_export!(client);
 glob_export!(clients);
 glob_export!(login);
 glob_export!(interaction);
 glob_export!(handlers);
+glob_export!(portal);
+glob_export!(quit);
+glob_export!(riding);
 glob_export!(forwardable);
+glob_export!(capabilities);
+glob_export!(state);
+glob_export!(ticks);
+glob_export!(intercept);
+
+pub mod traffic;
+#[cfg(feature = "compression-dictionary")]
+pub mod dictionary;
@@ -0,0 +1,160 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use proto::bedrock::{ChangeDimension, GameMode, NetworkChunkPublisherUpdate};
+use proto::types::Dimension;
+use util::Vector;
+
+use crate::cooldown::actions;
+use crate::level::portal::{PortalTarget, PORTAL_BLOCK};
+
+use super::BedrockClient;
+
+/// Amount of ticks a survival player has to stand in a portal before being teleported.
+const SURVIVAL_PORTAL_TICKS: u32 = 80;
+/// Time after a teleport during which portals are ignored.
+///
+/// Without this, the player would immediately be sent back when arriving in the destination portal.
+const PORTAL_COOLDOWN: Duration = Duration::from_secs(15);
+/// Distance between the eyes and feet of a player.
+const PLAYER_EYE_HEIGHT: f32 = 1.62;
+
+/// Tracks the dimension of a player and their progress through a portal.
+#[derive(Debug)]
+pub struct PortalState {
+    /// Dimension that the player is currently in.
+    pub dimension: Dimension,
+    /// Block that the player was standing in during the last check.
+    block: Option<Vector<i32, 3>>,
+    /// Whether that block is a portal.
+    in_portal: bool,
+    /// Amount of consecutive ticks spent inside of a portal.
+    ticks: u32,
+    /// Whether the client has not yet acknowledged a dimension change.
+    transferring: bool,
+}
+
+impl Default for PortalState {
+    fn default() -> Self {
+        Self { dimension: Dimension::Overworld, block: None, in_portal: false, ticks: 0, transferring: false }
+    }
+}
+
+impl BedrockClient {
+    /// Checks whether the player is standing in a portal and transfers them to the linked dimension
+    /// once they have stood in it long enough.
+    ///
+    /// Finding or building the destination portal reads many subchunks, so it runs on a blocking thread
+    /// instead of delaying the processing of the player's input.
+    pub(super) fn handle_portal_movement(self: &Arc<Self>, position: &Vector<f32, 3>) -> anyhow::Result<()> {
+        let player = self.player()?;
+        let instance = self.instance();
+
+        let feet = Vector::from([position.x.floor() as i32, (position.y - PLAYER_EYE_HEIGHT).floor() as i32, position.z.floor() as i32]);
+
+        let mut state = player.portal.lock();
+        if state.transferring || state.dimension == Dimension::End {
+            return Ok(());
+        }
+
+        // Only query the level when the player has moved into a different block.
+        if state.block.as_ref() != Some(&feet) {
+            let block = instance.level().block_name(&feet, state.dimension)?;
+            state.in_portal = block.as_deref() == Some(PORTAL_BLOCK);
+            state.block = Some(feet);
+        }
+
+        if !state.in_portal {
+            state.ticks = 0;
+            return Ok(());
+        }
+
+        state.ticks += 1;
+        let required = if player.gamemode() == GameMode::Creative { 1 } else { SURVIVAL_PORTAL_TICKS };
+        if state.ticks < required {
+            return Ok(());
+        }
+        state.ticks = 0;
+
+        let xuid = self.xuid()?;
+        if instance.cooldowns().try_acquire(xuid, actions::PORTAL, PORTAL_COOLDOWN).is_err() {
+            return Ok(());
+        }
+
+        let from = state.dimension;
+        let to = if from == Dimension::Nether { Dimension::Overworld } else { Dimension::Nether };
+        // Ignore the portal while the destination is being prepared.
+        state.transferring = true;
+        drop(state);
+
+        let client = Arc::clone(self);
+        let position = position.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = client.travel_through_portal(&position, from, to) {
+                tracing::error!("Failed to travel through portal: {err:#}");
+                if let Ok(player) = client.player() {
+                    player.portal.lock().transferring = false;
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Finds or builds the portal that a player at `position` arrives at and sends them there.
+    fn travel_through_portal(&self, position: &Vector<f32, 3>, from: Dimension, to: Dimension) -> anyhow::Result<()> {
+        let instance = self.instance();
+
+        let target = instance.level().find_portal_target(position, from, to)?;
+        if let PortalTarget::Create(position) = &target {
+            tracing::debug!("No portal found near destination, building one at {position:?}");
+            instance.level().build_portal(position, to)?;
+        }
+
+        let destination = target.position();
+        let destination = Vector::from([
+            destination.x as f32 + 0.5,
+            destination.y as f32 + PLAYER_EYE_HEIGHT,
+            destination.z as f32 + 0.5,
+        ]);
+
+        self.change_dimension(to, destination)
+    }
+
+    /// Transfers the player to another dimension.
+    ///
+    /// The client shows a loading screen until it has received the chunks around the destination
+    /// and acknowledges the change. Those chunks are queued immediately and the first of them are sent
+    /// right after the dimension change.
+    pub fn change_dimension(&self, dimension: Dimension, position: Vector<f32, 3>) -> anyhow::Result<()> {
+        let player = self.player()?;
+        {
+            let mut state = player.portal.lock();
+            state.dimension = dimension;
+            state.block = None;
+            state.in_portal = false;
+            state.transferring = true;
+        }
+
+        let center = Vector::from([position.x.floor() as i32, position.y.floor() as i32, position.z.floor() as i32]);
+        self.send(ChangeDimension { dimension, position: position.clone(), respawn: false })?;
+
+        self.viewer.update_position(Vector::from([position.x, position.z]), dimension);
+        // The publisher radius is measured in blocks.
+        self.send(NetworkChunkPublisherUpdate { position: center, radius: u32::from(self.viewer.radius()) * 16 })?;
+        self.send_queued_chunks(self.instance().level().throttle().chunk_sends_per_tick())
+    }
+
+    /// Handles the acknowledgement of a dimension change sent by the client.
+    pub(super) fn handle_dimension_change_ack(&self) -> anyhow::Result<()> {
+        let player = self.player()?;
+        player.portal.lock().transferring = false;
+
+        Ok(())
+    }
+
+    /// The dimension that the player is currently in.
+    pub fn dimension(&self) -> anyhow::Result<Dimension> {
+        Ok(self.player()?.portal.lock().dimension)
+    }
+}
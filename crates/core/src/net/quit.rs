@@ -0,0 +1,125 @@
+//! Disconnection of clients.
+//!
+//! A connection can be closed by the server, by the client sending a [`Disconnect`] packet, by the client closing
+//! the RakNet connection or by the client no longer responding. Whichever happens first is recorded as the
+//! [`QuitReason`]. The rest of the teardown is the same in every case: the RakNet session is closed, the remaining
+//! packets are flushed and a [`QuitEvent`] is emitted once the client has fully disconnected.
+
+use std::fmt;
+use std::net::SocketAddr;
+
+use proto::bedrock::{Disconnect, DisconnectReason};
+use raknet::CloseReason;
+use util::{Deserialize, RVec};
+
+use super::BedrockClient;
+
+/// Why a client left the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuitReason {
+    /// The client sent a [`Disconnect`] packet.
+    Left {
+        /// Reason given by the client.
+        reason: DisconnectReason,
+        /// Message given by the client, this is usually empty.
+        message: String,
+    },
+    /// The server disconnected the client.
+    Kicked {
+        /// Reason sent to the client.
+        reason: DisconnectReason,
+        /// Message shown to the client.
+        message: String,
+    },
+    /// The client closed the connection without sending a [`Disconnect`] packet.
+    Closed,
+    /// The server closed the connection without sending a [`Disconnect`] packet.
+    Dropped,
+    /// The client stopped responding.
+    TimedOut,
+}
+
+impl From<CloseReason> for QuitReason {
+    fn from(reason: CloseReason) -> QuitReason {
+        match reason {
+            CloseReason::Local => QuitReason::Dropped,
+            CloseReason::Peer => QuitReason::Closed,
+            CloseReason::TimedOut => QuitReason::TimedOut,
+        }
+    }
+}
+
+impl fmt::Display for QuitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuitReason::Left { reason, message } if message.is_empty() => write!(f, "left ({reason:?})"),
+            QuitReason::Left { reason, message } => write!(f, "left ({reason:?}): {message}"),
+            QuitReason::Kicked { reason, message } => write!(f, "kicked ({reason:?}): {message}"),
+            QuitReason::Closed => f.write_str("closed the connection"),
+            QuitReason::Dropped => f.write_str("was dropped by the server"),
+            QuitReason::TimedOut => f.write_str("timed out"),
+        }
+    }
+}
+
+/// Emitted when a player that has logged in disconnects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuitEvent {
+    /// XUID of the player.
+    pub xuid: u64,
+    /// Username of the player.
+    pub name: String,
+    /// Address that the player was connected from.
+    pub address: SocketAddr,
+    /// Why the player left.
+    pub reason: QuitReason,
+}
+
+impl BedrockClient {
+    /// Handles a [`Disconnect`] packet sent by the client.
+    pub fn handle_disconnect(&self, packet: RVec) -> anyhow::Result<()> {
+        let request = Disconnect::deserialize(packet.as_ref())?;
+
+        self.record_quit(QuitReason::Left { reason: request.reason, message: request.message.to_owned() });
+        self.raknet.disconnect();
+
+        Ok(())
+    }
+
+    /// Why the client left, or `None` if it is still connected.
+    pub fn quit_reason(&self) -> Option<&QuitReason> {
+        self.quit.get()
+    }
+
+    /// Records why the client is leaving, unless a reason has already been recorded.
+    pub(super) fn record_quit(&self, reason: QuitReason) {
+        let _: Result<(), QuitReason> = self.quit.set(reason);
+    }
+
+    /// Tears down the client once its receiver has stopped.
+    ///
+    /// This is called exactly once, regardless of which side initiated the disconnect.
+    pub(super) fn on_disconnected(&self) {
+        if let Err(err) = self.flush_batch() {
+            tracing::error!("Failed to flush packet batch: {err:#}");
+        }
+
+        let reason = self.quit.get_or_init(|| self.raknet.close_reason().map_or(QuitReason::Dropped, QuitReason::from));
+
+        // Closes the RakNet session if it was the Bedrock layer that stopped.
+        self.raknet.disconnect();
+
+        let (Ok(xuid), Ok(name)) = (self.xuid(), self.name()) else {
+            tracing::info!("{} {reason} before logging in", self.raknet.address());
+            return
+        };
+
+        tracing::info!("{name} {reason}");
+        self.instance().emit_quit_event(QuitEvent {
+            xuid,
+            name: name.to_owned(),
+            address: self.raknet.address(),
+            reason: reason.clone(),
+        });
+    }
+}
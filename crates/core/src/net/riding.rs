@@ -0,0 +1,289 @@
+use std::sync::Arc;
+
+use proto::bedrock::{DisconnectReason, EntityLink, EntityLinkType, Interact, PlayerAuthInput, SetActorLink};
+use util::Vector;
+
+use super::BedrockClient;
+
+/// Maximum distance in blocks that a rider is allowed to move in a single tick.
+///
+/// Vanilla vehicles never come close to this, anything above it is treated as an attempt
+/// to teleport using a vehicle.
+const MAX_RIDER_MOVEMENT_PER_TICK: f32 = 4.0;
+/// Maximum distance in blocks between the last known riding position and the position
+/// the client reports when it leaves its vehicle.
+const MAX_DISMOUNT_DISTANCE: f32 = 8.0;
+
+/// Inputs performed by a rider during a single tick.
+///
+/// These are extracted from the [`PlayerAuthInput`] packets sent by the client and forwarded to
+/// the [`VehicleController`] of the vehicle that the client is riding.
+#[derive(Debug, Clone)]
+pub struct VehicleInput {
+    /// The direction the rider wants to move in.
+    pub moved: Vector<f32, 2>,
+    /// The direction the rider wants to move in, but with analogue input.
+    pub analogue_moved: Vector<f32, 2>,
+    /// Rotation of the vehicle as predicted by the client.
+    /// This is only available for vehicles that the client predicts the movement of, such as boats.
+    pub vehicle_rotation: Option<Vector<f32, 2>>,
+    /// Whether the rider is jumping.
+    pub jumping: bool,
+    /// Whether the rider is sneaking.
+    pub sneaking: bool,
+    /// Whether the rider is paddling with the left paddle.
+    pub paddling_left: bool,
+    /// Whether the rider is paddling with the right paddle.
+    pub paddling_right: bool,
+}
+
+/// Controls the behaviour of a vehicle.
+///
+/// This can be implemented to create custom mounts. Every tick that a client spends riding the vehicle,
+/// its inputs are forwarded to the controller.
+pub trait VehicleController: Send + Sync {
+    /// Called every tick with the inputs of the rider.
+    fn on_input(&self, rider: &BedrockClient, input: &VehicleInput) -> anyhow::Result<()>;
+
+    /// Called when the rider leaves the vehicle.
+    fn on_dismount(&self, _rider: &BedrockClient) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reason why an action of a rider was rejected.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RidingViolation {
+    /// The rider referred to a vehicle that it is not riding.
+    WrongVehicle,
+    /// The rider moved further than its vehicle allows.
+    TooFar,
+}
+
+/// Describes the vehicle that a client is riding.
+pub struct Mount {
+    /// Unique ID of the vehicle entity.
+    pub vehicle_id: i64,
+    /// Runtime ID of the vehicle entity, which clients use to refer to it in [`Interact`] packets.
+    pub vehicle_runtime_id: u64,
+    /// Whether the client controls the vehicle or is merely a passenger.
+    ///
+    /// This must be either [`EntityLinkType::Rider`] or [`EntityLinkType::Passenger`].
+    pub link_type: EntityLinkType,
+    /// Offset of the seat relative to the position of the vehicle.
+    pub seat_offset: Vector<f32, 3>,
+    /// Controller that inputs of the rider are forwarded to.
+    pub controller: Option<Arc<dyn VehicleController>>,
+    /// Last position of the rider that passed validation.
+    ///
+    /// This is `None` until the first movement of the rider after mounting, since the client moves the rider
+    /// to the seat itself.
+    last_position: Option<Vector<f32, 3>>,
+}
+
+impl Mount {
+    /// Creates a new mount for the vehicle with the given unique and runtime ID.
+    pub fn new(vehicle_id: i64, vehicle_runtime_id: u64, link_type: EntityLinkType) -> Self {
+        Self {
+            vehicle_id,
+            vehicle_runtime_id,
+            link_type,
+            seat_offset: Vector::from([0.0; 3]),
+            controller: None,
+            last_position: None,
+        }
+    }
+
+    /// Sets the offset of the seat relative to the vehicle.
+    pub const fn with_seat_offset(mut self, offset: Vector<f32, 3>) -> Self {
+        self.seat_offset = offset;
+        self
+    }
+
+    /// Sets the controller that receives the inputs of the rider.
+    pub fn with_controller(mut self, controller: Arc<dyn VehicleController>) -> Self {
+        self.controller = Some(controller);
+        self
+    }
+
+    /// Creates the link between the vehicle and the given rider.
+    pub const fn link(&self, rider_entity_id: i64) -> EntityLink {
+        EntityLink::new(self.link_type, self.vehicle_id, rider_entity_id)
+    }
+
+    /// Computes the position of the seat given the position of the vehicle.
+    pub fn seat_position(&self, vehicle_position: &Vector<f32, 3>) -> Vector<f32, 3> {
+        Vector::from([
+            vehicle_position.x + self.seat_offset.x,
+            vehicle_position.y + self.seat_offset.y,
+            vehicle_position.z + self.seat_offset.z,
+        ])
+    }
+
+    /// Checks a movement of the rider to `position` in a single tick.
+    ///
+    /// `predicted_vehicle` is the unique ID of the vehicle that the client predicts the movement of, if any.
+    /// The movement limit is scaled by `tolerance`. The first movement after mounting is not limited.
+    pub fn check_movement(&self, predicted_vehicle: Option<i64>, position: &Vector<f32, 3>, tolerance: f32) -> Result<(), RidingViolation> {
+        if predicted_vehicle.is_some_and(|id| id != self.vehicle_id) {
+            return Err(RidingViolation::WrongVehicle)
+        }
+
+        if self.last_position.as_ref().is_some_and(|last| distance(last, position) > MAX_RIDER_MOVEMENT_PER_TICK * tolerance) {
+            return Err(RidingViolation::TooFar)
+        }
+
+        Ok(())
+    }
+
+    /// Checks a movement of the rider using [`check_movement`](Self::check_movement) and remembers the position
+    /// if it is valid.
+    pub fn record_movement(&mut self, predicted_vehicle: Option<i64>, position: &Vector<f32, 3>, tolerance: f32) -> Result<(), RidingViolation> {
+        self.check_movement(predicted_vehicle, position, tolerance)?;
+        self.last_position = Some(position.clone());
+        Ok(())
+    }
+
+    /// Checks a request of the rider to leave the vehicle with runtime ID `runtime_id` at `position`.
+    pub fn check_leave(&self, runtime_id: u64, position: &Vector<f32, 3>) -> Result<(), RidingViolation> {
+        if runtime_id != self.vehicle_runtime_id {
+            return Err(RidingViolation::WrongVehicle)
+        }
+
+        if self.last_position.as_ref().is_some_and(|last| distance(last, position) > MAX_DISMOUNT_DISTANCE) {
+            return Err(RidingViolation::TooFar)
+        }
+
+        Ok(())
+    }
+}
+
+/// Euclidean distance between two positions.
+#[inline]
+fn distance(a: &Vector<f32, 3>, b: &Vector<f32, 3>) -> f32 {
+    let (dx, dy, dz) = (a.x - b.x, a.y - b.y, a.z - b.z);
+    dz.mul_add(dz, dx.mul_add(dx, dy * dy)).sqrt()
+}
+
+impl BedrockClient {
+    /// Makes this client ride the given vehicle.
+    ///
+    /// If the client was already riding another vehicle, it will first be dismounted.
+    pub fn mount(&self, mount: Mount) -> anyhow::Result<()> {
+        if mount.link_type == EntityLinkType::Remove {
+            anyhow::bail!("Cannot mount a vehicle using a removal link");
+        }
+
+        self.dismount()?;
+
+        let player = self.player()?;
+        self.send(SetActorLink { link: mount.link(player.runtime_id() as i64) })?;
+
+        *player.mount.lock() = Some(mount);
+        Ok(())
+    }
+
+    /// Removes this client from the vehicle it is riding.
+    ///
+    /// This does nothing if the client is not riding anything.
+    pub fn dismount(&self) -> anyhow::Result<()> {
+        let player = self.player()?;
+        let Some(mount) = player.mount.lock().take() else {
+            return Ok(());
+        };
+
+        self.send(SetActorLink {
+            link: EntityLink::new(EntityLinkType::Remove, mount.vehicle_id, player.runtime_id() as i64),
+        })?;
+
+        if let Some(controller) = &mount.controller {
+            controller.on_dismount(self)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether this client is currently riding a vehicle.
+    pub fn is_riding(&self) -> anyhow::Result<bool> {
+        Ok(self.player()?.mount.lock().is_some())
+    }
+
+    /// Handles the riding-related part of a [`PlayerAuthInput`] packet.
+    ///
+    /// Movement is validated against the last accepted position to prevent clients from
+    /// teleporting by pretending to ride a vehicle. The limit is scaled by the movement tolerance
+    /// configured for the capabilities of the client.
+    pub(super) fn handle_vehicle_input(&self, input: &PlayerAuthInput) -> anyhow::Result<()> {
+        let player = self.player()?;
+        let mut lock = player.mount.lock();
+
+        let Some(mount) = lock.as_mut() else {
+            if input.client_predicted_vehicle.is_some() {
+                drop(lock);
+                tracing::warn!("Client is predicting the movement of a vehicle it is not riding");
+                return self.kick_with_reason("Illegal packets", DisconnectReason::BadPacket);
+            }
+
+            return Ok(());
+        };
+
+        match mount.record_movement(input.client_predicted_vehicle, &input.position, self.movement_tolerance()) {
+            Ok(()) => (),
+            Err(RidingViolation::WrongVehicle) => {
+                drop(lock);
+                tracing::warn!("Client is predicting the movement of a vehicle it is not riding");
+                return self.kick_with_reason("Illegal packets", DisconnectReason::BadPacket);
+            }
+            Err(RidingViolation::TooFar) => {
+                drop(lock);
+                tracing::warn!("Client moved too far while riding a vehicle");
+                return self.kick_with_reason("Illegal movement", DisconnectReason::BadPacket);
+            }
+        }
+
+        let Some(controller) = mount.controller.clone() else {
+            return Ok(());
+        };
+        drop(lock);
+
+        let vehicle_input = VehicleInput {
+            moved: input.moved.clone(),
+            analogue_moved: input.analogue_moved.clone(),
+            vehicle_rotation: input.vehicle_rotation.clone(),
+            jumping: input.input_data.jumping(),
+            sneaking: input.input_data.sneaking(),
+            paddling_left: input.input_data.paddling_left(),
+            paddling_right: input.input_data.paddling_right(),
+        };
+
+        controller.on_input(self, &vehicle_input)
+    }
+
+    /// Handles an [`Interact`] packet with the [`LeaveVehicle`](proto::bedrock::InteractAction::LeaveVehicle) action.
+    pub(super) fn handle_leave_vehicle(&self, request: &Interact) -> anyhow::Result<()> {
+        let player = self.player()?;
+        let lock = player.mount.lock();
+
+        let Some(mount) = lock.as_ref() else {
+            // The vehicle could have been removed by the server in the meantime.
+            return Ok(());
+        };
+
+        let checked = mount.check_leave(request.target_runtime_id, &request.position);
+        drop(lock);
+
+        match checked {
+            Ok(()) => (),
+            Err(RidingViolation::WrongVehicle) => {
+                tracing::warn!("Client attempted to leave a vehicle it is not riding");
+                return self.kick_with_reason("Illegal packets", DisconnectReason::BadPacket);
+            }
+            Err(RidingViolation::TooFar) => {
+                tracing::warn!("Client attempted to dismount too far from its vehicle");
+                return self.kick_with_reason("Illegal movement", DisconnectReason::BadPacket);
+            }
+        }
+
+        self.dismount()
+    }
+}
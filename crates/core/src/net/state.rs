@@ -0,0 +1,132 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use parking_lot::Mutex;
+use proto::bedrock::{CacheStatus, ClientToServerHandshake, ConnectedPacket, Disconnect, Login, RequestNetworkSettings, ResourcePackClientResponse};
+use util::RVec;
+
+/// Maximum amount of packets that are held back because they arrived before the state they belong to.
+const MAX_EARLY_PACKETS: usize = 4;
+
+/// Stage of the login sequence that a client is in.
+#[macros::try_from_repr]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LoginState {
+    /// Waiting for a [`RequestNetworkSettings`] packet.
+    NetworkSettings,
+    /// Waiting for a [`Login`] packet.
+    Login,
+    /// Waiting for the encryption handshake to be completed.
+    Handshake,
+    /// Waiting for the [`CacheStatus`] and [`ResourcePackClientResponse`] packets, in any order.
+    ResourcePacks,
+    /// The login sequence has been completed and packets are no longer restricted.
+    InGame,
+}
+
+impl LoginState {
+    /// Whether a packet with the given ID may be processed in this state.
+    ///
+    /// Clients can always disconnect, regardless of the state.
+    pub const fn allows(self, id: u32) -> bool {
+        if id == Disconnect::ID {
+            return true
+        }
+
+        match self {
+            Self::NetworkSettings => id == RequestNetworkSettings::ID,
+            Self::Login => id == Login::ID,
+            Self::Handshake => id == ClientToServerHandshake::ID,
+            Self::ResourcePacks => id == CacheStatus::ID || id == ResourcePackClientResponse::ID,
+            Self::InGame => true,
+        }
+    }
+
+    /// The state that follows this one.
+    pub const fn next(self) -> Option<LoginState> {
+        match self {
+            Self::NetworkSettings => Some(Self::Login),
+            Self::Login => Some(Self::Handshake),
+            Self::Handshake => Some(Self::ResourcePacks),
+            Self::ResourcePacks => Some(Self::InGame),
+            Self::InGame => None,
+        }
+    }
+}
+
+/// What should happen to a packet that was received during login.
+#[derive(Debug)]
+pub enum Admission {
+    /// The packet is valid in the current state and should be processed.
+    Process(RVec),
+    /// The packet is only valid in the next state and has been held back until then.
+    Deferred,
+    /// The packet is not valid in the current or the next state.
+    Rejected,
+}
+
+/// Decides which packets a client may send while logging in.
+///
+/// Every login state allows a set of packets instead of a single one, because clients do not always send
+/// packets in the same order. Packets that arrive slightly too early, such as a [`CacheStatus`] that is received
+/// before the encryption handshake has been processed, are buffered and replayed once the state advances.
+#[derive(Debug)]
+pub struct LoginGate {
+    state: AtomicU8,
+    /// Packets that arrived before the state they belong to.
+    early: Mutex<Vec<(u32, RVec)>>,
+}
+
+impl LoginGate {
+    /// Creates a gate for a client that has just connected.
+    pub const fn new() -> Self {
+        Self { state: AtomicU8::new(LoginState::NetworkSettings as u8), early: Mutex::new(Vec::new()) }
+    }
+
+    /// The current login state.
+    pub fn state(&self) -> LoginState {
+        LoginState::try_from(self.state.load(Ordering::SeqCst)).unwrap_or(LoginState::InGame)
+    }
+
+    /// Moves to a new state.
+    ///
+    /// The state never moves backwards, a packet handled late cannot restrict a client that has already advanced.
+    pub fn advance(&self, state: LoginState) {
+        self.state.fetch_max(state as u8, Ordering::SeqCst);
+    }
+
+    /// Decides whether a packet can be processed right now.
+    pub fn admit(&self, id: u32, packet: RVec) -> Admission {
+        let state = self.state();
+        if state.allows(id) {
+            return Admission::Process(packet)
+        }
+
+        if !state.next().is_some_and(|next| next.allows(id)) {
+            return Admission::Rejected
+        }
+
+        let mut early = self.early.lock();
+        if early.len() >= MAX_EARLY_PACKETS {
+            return Admission::Rejected
+        }
+
+        early.push((id, packet));
+        Admission::Deferred
+    }
+
+    /// Takes the first buffered packet that has become valid in the current state.
+    pub fn take_ready(&self) -> Option<(u32, RVec)> {
+        let state = self.state();
+        let mut early = self.early.lock();
+
+        let index = early.iter().position(|(id, _)| state.allows(*id))?;
+        Some(early.remove(index))
+    }
+}
+
+impl Default for LoginGate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
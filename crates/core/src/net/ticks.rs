@@ -0,0 +1,70 @@
+//! Synchronisation of the client's simulation tick with the server clock.
+//!
+//! Clients number every tick they simulate and include that number in [`TickSync`](proto::bedrock::TickSync)
+//! and [`PlayerAuthInput`](proto::bedrock::PlayerAuthInput) packets. Since the tick counter advances at a fixed rate,
+//! it acts as a clock of its own, which is modelled by converting ticks to milliseconds.
+
+use std::time::Duration;
+
+use raknet::ClockModel;
+
+use crate::level::throttle::TICK_INTERVAL;
+
+/// Length of a single tick in milliseconds.
+const TICK_MILLIS: i64 = TICK_INTERVAL.as_millis() as i64;
+/// How far ahead of the estimated client tick an input may be before it is rejected.
+///
+/// Honest clients never simulate ahead of their own clock, this only allows for estimation error.
+pub const MAX_TICK_LEAD: u64 = 20;
+
+/// Models the tick counter of a client.
+///
+/// All local timestamps are those of the connection's [`LatencyTracker`](raknet::LatencyTracker).
+#[derive(Debug, Default)]
+pub struct TickModel {
+    clock: ClockModel,
+}
+
+impl TickModel {
+    /// Creates a model without any measurements.
+    pub fn new() -> TickModel {
+        TickModel::default()
+    }
+
+    /// Records that the client was at `tick` when it sent a packet that was received at `received`.
+    pub fn record(&self, tick: u64, received: i64, rtt: Duration) {
+        self.clock.record_one_way(ticks_to_millis(tick), received, rtt);
+    }
+
+    /// Whether any ticks have been recorded yet.
+    pub fn is_synchronised(&self) -> bool {
+        self.clock.is_synchronised()
+    }
+
+    /// Tick the client is estimated to be at at the given local time, or `None` if no ticks have been recorded yet.
+    pub fn estimate(&self, local: i64) -> Option<u64> {
+        self.is_synchronised().then(|| millis_to_ticks(self.clock.to_remote(local)))
+    }
+
+    /// Local time at which the client simulated the given tick, or `None` if no ticks have been recorded yet.
+    pub fn to_local(&self, tick: u64) -> Option<i64> {
+        self.is_synchronised().then(|| self.clock.to_local(ticks_to_millis(tick)))
+    }
+
+    /// Whether the client claims to be further ahead than its tick rate allows.
+    ///
+    /// Ticks are plausible as long as no measurements exist.
+    pub fn is_ahead(&self, tick: u64, local: i64) -> bool {
+        self.estimate(local).is_some_and(|estimate| tick > estimate.saturating_add(MAX_TICK_LEAD))
+    }
+}
+
+/// Converts a tick number to milliseconds.
+pub const fn ticks_to_millis(tick: u64) -> i64 {
+    (tick as i64).saturating_mul(TICK_MILLIS)
+}
+
+/// Converts milliseconds to the number of full ticks that fit in them.
+pub const fn millis_to_ticks(millis: i64) -> u64 {
+    if millis < 0 { 0 } else { (millis / TICK_MILLIS) as u64 }
+}
@@ -0,0 +1,288 @@
+//! Packet counts and byte volumes per packet type.
+//!
+//! Traffic is tracked over rolling windows of one second and one minute, both per session and for the whole
+//! server. This makes it easy to spot which systems, such as movement spam or chunk resends, dominate the traffic
+//! before optimizing them. The server-wide counters are also exported as metrics.
+
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use prometheus_client::metrics::{counter::Counter, family::Family};
+use proto::bedrock::{Command, CommandDataType, CommandOverload, CommandParameter, CommandPermissionLevel};
+use raknet::Direction;
+
+use crate::clock::SharedClock;
+use crate::command::{self, HandlerOutput, HandlerResult, ParsedCommand};
+
+/// Labels of the per packet type metrics: the direction and the hexadecimal packet ID.
+pub type PacketLabels = Vec<(&'static str, String)>;
+
+/// Packet and byte counters of a single packet type.
+type PacketCounters = (Counter<u64, AtomicU64>, Counter<u64, AtomicU64>);
+
+lazy_static! {
+    #[doc(hidden)]
+    pub static ref PACKETS_BY_TYPE_METRIC: Family<PacketLabels, Counter::<u64, AtomicU64>> = Family::default();
+    #[doc(hidden)]
+    pub static ref PACKET_BYTES_BY_TYPE_METRIC: Family<PacketLabels, Counter::<u64, AtomicU64>> = Family::default();
+    /// Counters in the metric families by direction and packet ID, so that the labels are only built once per type.
+    static ref PACKET_COUNTERS: DashMap<(Direction, u32), PacketCounters> = DashMap::new();
+}
+
+/// Amount of packet types listed by the `nethealth` command.
+const LISTED_PACKETS: usize = 5;
+
+/// Period over which traffic is aggregated.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrafficWindow {
+    /// The last second, in buckets of 100 milliseconds.
+    Second,
+    /// The last minute, in buckets of one second.
+    Minute,
+}
+
+impl TrafficWindow {
+    /// Length of a single bucket.
+    const fn bucket_len(self) -> Duration {
+        match self {
+            Self::Second => Duration::from_millis(100),
+            Self::Minute => Duration::from_secs(1),
+        }
+    }
+
+    /// Amount of buckets that make up the window.
+    const fn buckets(self) -> usize {
+        match self {
+            Self::Second => 10,
+            Self::Minute => 60,
+        }
+    }
+}
+
+/// Amount of packets and bytes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Traffic {
+    /// Amount of packets.
+    pub packets: u64,
+    /// Total size of the packets in bytes, including their headers.
+    pub bytes: u64,
+}
+
+impl Traffic {
+    fn add(&mut self, other: Traffic) {
+        self.packets += other.packets;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Traffic over a rolling window, divided into buckets that are reused once they fall out of the window.
+#[derive(Debug, Clone)]
+struct RollingCounter {
+    window: TrafficWindow,
+    /// Index of the bucket since the start of the statistics and the traffic recorded in it.
+    buckets: Box<[(u64, Traffic)]>,
+}
+
+impl RollingCounter {
+    fn new(window: TrafficWindow) -> RollingCounter {
+        RollingCounter { window, buckets: vec![(0, Traffic::default()); window.buckets()].into_boxed_slice() }
+    }
+
+    /// Index of the bucket that contains the given point in time.
+    const fn bucket(&self, elapsed: Duration) -> u64 {
+        (elapsed.as_nanos() / self.window.bucket_len().as_nanos()) as u64
+    }
+
+    fn record(&mut self, elapsed: Duration, bytes: usize) {
+        let index = self.bucket(elapsed);
+        let len = self.buckets.len() as u64;
+
+        let (bucket, traffic) = &mut self.buckets[(index % len) as usize];
+        if *bucket != index {
+            *bucket = index;
+            *traffic = Traffic::default();
+        }
+
+        traffic.add(Traffic { packets: 1, bytes: bytes as u64 });
+    }
+
+    fn total(&self, elapsed: Duration) -> Traffic {
+        let index = self.bucket(elapsed);
+        let len = self.buckets.len() as u64;
+
+        let mut total = Traffic::default();
+        for (bucket, traffic) in &*self.buckets {
+            if *bucket <= index && *bucket + len > index {
+                total.add(*traffic);
+            }
+        }
+
+        total
+    }
+}
+
+/// Counters of a single packet type.
+#[derive(Debug, Clone)]
+struct TypeCounters {
+    second: RollingCounter,
+    minute: RollingCounter,
+}
+
+impl TypeCounters {
+    fn new() -> TypeCounters {
+        TypeCounters { second: RollingCounter::new(TrafficWindow::Second), minute: RollingCounter::new(TrafficWindow::Minute) }
+    }
+
+    const fn window(&self, window: TrafficWindow) -> &RollingCounter {
+        match window {
+            TrafficWindow::Second => &self.second,
+            TrafficWindow::Minute => &self.minute,
+        }
+    }
+}
+
+/// Packet counts and byte volumes per packet type over rolling windows.
+///
+/// Every session keeps its own statistics and the server keeps statistics of all sessions combined, which are
+/// also exported as metrics. Use the `nethealth` command to see which packet types dominate the traffic.
+#[derive(Debug)]
+pub struct TrafficStats {
+    clock: SharedClock,
+    /// Time at which the statistics were created.
+    start: Instant,
+    inbound: DashMap<u32, TypeCounters>,
+    outbound: DashMap<u32, TypeCounters>,
+}
+
+impl TrafficStats {
+    /// Creates empty statistics that read the time from the given clock.
+    pub fn new(clock: SharedClock) -> TrafficStats {
+        let start = clock.now();
+        TrafficStats { clock, start, inbound: DashMap::new(), outbound: DashMap::new() }
+    }
+
+    const fn packets(&self, direction: Direction) -> &DashMap<u32, TypeCounters> {
+        match direction {
+            Direction::Inbound => &self.inbound,
+            Direction::Outbound => &self.outbound,
+        }
+    }
+
+    /// Records a packet of the given type and size.
+    pub fn record(&self, direction: Direction, id: u32, bytes: usize) {
+        let elapsed = self.clock.elapsed(self.start);
+        let mut counters = self.packets(direction).entry(id).or_insert_with(TypeCounters::new);
+        counters.second.record(elapsed, bytes);
+        counters.minute.record(elapsed, bytes);
+    }
+
+    /// Returns the traffic of every packet type in the given window, highest byte volume first.
+    ///
+    /// Packet types without traffic in the window are left out.
+    pub fn by_type(&self, direction: Direction, window: TrafficWindow) -> Vec<(u32, Traffic)> {
+        let elapsed = self.clock.elapsed(self.start);
+        let mut packets = self
+            .packets(direction)
+            .iter()
+            .map(|r| (*r.key(), r.value().window(window).total(elapsed)))
+            .filter(|(_, traffic)| traffic.packets > 0)
+            .collect::<Vec<_>>();
+
+        packets.sort_by_key(|(_, traffic)| std::cmp::Reverse(traffic.bytes));
+        packets
+    }
+
+    /// Returns the traffic of all packet types combined in the given window.
+    pub fn total(&self, direction: Direction, window: TrafficWindow) -> Traffic {
+        let elapsed = self.clock.elapsed(self.start);
+        let mut total = Traffic::default();
+        for counters in self.packets(direction) {
+            total.add(counters.window(window).total(elapsed));
+        }
+
+        total
+    }
+}
+
+/// Records a packet in the server-wide metrics.
+pub(crate) fn record_metrics(direction: Direction, id: u32, bytes: usize) {
+    let counters = PACKET_COUNTERS.entry((direction, id)).or_insert_with(|| {
+        let labels = packet_labels(direction, id);
+        (PACKETS_BY_TYPE_METRIC.get_or_create(&labels).clone(), PACKET_BYTES_BY_TYPE_METRIC.get_or_create(&labels).clone())
+    });
+
+    counters.0.inc();
+    counters.1.inc_by(bytes as u64);
+}
+
+/// Labels of the metrics of a packet type.
+pub fn packet_labels(direction: Direction, id: u32) -> PacketLabels {
+    vec![
+        ("direction", if direction == Direction::Inbound { "inbound" } else { "outbound" }.to_owned()),
+        ("packet", format!("{id:#04x}")),
+    ]
+}
+
+/// Syntax of the `nethealth` command.
+pub(crate) fn command() -> Command {
+    Command {
+        aliases: Vec::new(),
+        description: "Shows which packet types dominate network traffic".to_owned(),
+        name: "nethealth".to_owned(),
+        overloads: vec![CommandOverload {
+            parameters: vec![CommandParameter {
+                name: "player".to_owned(),
+                command_enum: None,
+                data_type: CommandDataType::String,
+                optional: true,
+                options: 0,
+                suffix: "".to_owned(),
+            }],
+        }],
+        permission_level: CommandPermissionLevel::Admin,
+    }
+}
+
+/// Handler of the `nethealth` command.
+///
+/// Shows the traffic of the whole server by default, or of a single player if a name is given.
+pub(crate) fn handle_command(input: ParsedCommand, ctx: &command::Context) -> HandlerResult {
+    let name = input.parameters.get("player").and_then(command::ParsedArgument::as_string);
+    let client = match name {
+        None => None,
+        Some(name) => {
+            let Some(found) = ctx.instance.clients().by_username(name) else {
+                return HandlerOutput::new().message(format!("Player {name} is not online")).error()
+            };
+
+            Some(found)
+        }
+    };
+
+    let (subject, stats) = match (name, &client) {
+        (Some(name), Some(client)) => (name, &client.traffic),
+        _ => ("the server", ctx.instance.traffic()),
+    };
+
+    let mut message = format!("Traffic of {subject}:");
+    if let Some(offset) = client.as_ref().and_then(|client| client.raknet.clock_offset()) {
+        message.push_str(&format!("\nClock offset: {offset} ms"));
+    }
+    for (direction, label) in [(Direction::Inbound, "Inbound"), (Direction::Outbound, "Outbound")] {
+        let second = stats.total(direction, TrafficWindow::Second);
+        let minute = stats.total(direction, TrafficWindow::Minute);
+        message.push_str(&format!(
+            "\n{label}: {} packets/s, {} B/s (last minute: {} packets, {} B)",
+            second.packets, second.bytes, minute.packets, minute.bytes
+        ));
+
+        for (id, traffic) in stats.by_type(direction, TrafficWindow::Minute).into_iter().take(LISTED_PACKETS) {
+            let share = if minute.bytes == 0 { 0.0 } else { traffic.bytes as f64 / minute.bytes as f64 * 100.0 };
+            message.push_str(&format!("\n  {id:#04x}: {} packets, {} B ({share:.1}%)", traffic.packets, traffic.bytes));
+        }
+    }
+
+    HandlerOutput::new().message(message).success()
+}
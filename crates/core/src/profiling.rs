@@ -0,0 +1,118 @@
+//! CPU profiling of a running server.
+//!
+//! The `profile` command samples the call stacks of all server threads for a fixed duration and writes the result
+//! to a flamegraph. This makes it possible to investigate performance problems in production without having to
+//! restart the server under an external profiler.
+
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use proto::bedrock::{Command, CommandDataType, CommandOverload, CommandParameter, CommandPermissionLevel};
+
+use crate::command::{self, HandlerOutput, HandlerResult, ParsedCommand};
+
+/// Directory that flamegraphs are written to.
+const PROFILE_DIR: &str = "profiles";
+/// Amount of stack samples taken per second.
+const SAMPLE_FREQUENCY: i32 = 999;
+/// Duration of a capture if none was specified.
+const DEFAULT_DURATION: Duration = Duration::from_secs(10);
+/// Longest duration that a capture can run for.
+const MAX_DURATION: Duration = Duration::from_secs(300);
+
+/// Only a single profiler can be active at a time.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Profiles the entire process for the given duration and writes a flamegraph to the profile directory.
+///
+/// This blocks the calling thread until the capture has finished.
+/// Returns the path of the created flamegraph.
+pub fn capture(duration: Duration) -> anyhow::Result<PathBuf> {
+    if ACTIVE.swap(true, Ordering::AcqRel) {
+        anyhow::bail!("A profiler is already running");
+    }
+
+    let result = capture_inner(duration);
+    ACTIVE.store(false, Ordering::Release);
+
+    result
+}
+
+fn capture_inner(duration: Duration) -> anyhow::Result<PathBuf> {
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(SAMPLE_FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .context("Failed to start profiler")?;
+
+    std::thread::sleep(duration);
+
+    let report = guard.report().build().context("Failed to build profiling report")?;
+    drop(guard);
+
+    std::fs::create_dir_all(PROFILE_DIR).context("Failed to create profile directory")?;
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let path = PathBuf::from(PROFILE_DIR).join(format!("flamegraph-{timestamp}.svg"));
+
+    let file = File::create(&path).context("Failed to create flamegraph file")?;
+    report.flamegraph(file).context("Failed to write flamegraph")?;
+
+    Ok(path)
+}
+
+/// Starts a capture on a separate thread without blocking the caller.
+pub fn capture_in_background(duration: Duration) -> anyhow::Result<()> {
+    if ACTIVE.load(Ordering::Acquire) {
+        anyhow::bail!("A profiler is already running");
+    }
+
+    std::thread::Builder::new()
+        .name(String::from("profiler"))
+        .spawn(move || match capture(duration) {
+            Ok(path) => tracing::info!("Profile written to {}", path.display()),
+            Err(err) => tracing::error!("Profiling failed: {err:#}"),
+        })
+        .context("Failed to spawn profiler thread")?;
+
+    Ok(())
+}
+
+/// Syntax of the `profile` command.
+pub(crate) fn command() -> Command {
+    Command {
+        aliases: Vec::new(),
+        description: "Captures a CPU profile of the server".to_owned(),
+        name: "profile".to_owned(),
+        overloads: vec![CommandOverload {
+            parameters: vec![CommandParameter {
+                name: "seconds".to_owned(),
+                command_enum: None,
+                data_type: CommandDataType::Int,
+                optional: true,
+                options: 0,
+                suffix: "".to_owned(),
+            }],
+        }],
+        permission_level: CommandPermissionLevel::Admin,
+    }
+}
+
+/// Handler of the `profile` command.
+pub(crate) fn handle_command(input: ParsedCommand, _ctx: &command::Context) -> HandlerResult {
+    let duration = match input.parameters.get("seconds").and_then(command::ParsedArgument::as_int) {
+        Some(seconds) if seconds > 0 => Duration::from_secs(seconds as u64).min(MAX_DURATION),
+        Some(_) => return HandlerOutput::new().message("Duration must be positive").error(),
+        None => DEFAULT_DURATION,
+    };
+
+    match capture_in_background(duration) {
+        Ok(()) => HandlerOutput::new()
+            .message(format!("Profiling for {} seconds, the flamegraph will be written to {PROFILE_DIR}", duration.as_secs()))
+            .success(),
+        Err(err) => HandlerOutput::new().message(format!("{err:#}")).error(),
+    }
+}
@@ -0,0 +1,170 @@
+//! Player reports.
+//!
+//! Players can report each other using the `/report` command and plugins can file reports on behalf of a player or
+//! moderation tool using [`Instance::report`]. Every report captures the recent chat messages and position of the
+//! reported player, so that moderators can see what happened even after the player has left.
+//!
+//! Reports are handed to a [`ReportBackend`]. By default they are appended to a file in the level directory, but
+//! servers that forward reports to an external moderation system can install their own backend. Subscribers of
+//! [`Instance::report_events`] are notified of every report, regardless of the backend.
+
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use util::Vector;
+
+use crate::instance::Instance;
+use crate::net::{BedrockClient, PlayerData};
+
+/// Amount of chat messages of each player that are kept for reports.
+pub const CHAT_HISTORY_SIZE: usize = 10;
+
+/// A player involved in a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportedPlayer {
+    /// XUID of the player.
+    pub xuid: u64,
+    /// Username of the player.
+    pub name: String,
+}
+
+impl ReportedPlayer {
+    /// Identifies the given client, failing if it has not logged in yet.
+    pub fn from_client(client: &BedrockClient) -> anyhow::Result<ReportedPlayer> {
+        Ok(ReportedPlayer { xuid: client.xuid()?, name: client.name()?.to_owned() })
+    }
+}
+
+/// Additional information supplied by whoever files a report.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReportContext {
+    /// Player that filed the report, or `None` if it was filed by the server or a plugin.
+    pub reporter: Option<ReportedPlayer>,
+    /// Free-form details, such as the name of the moderation tool that filed the report.
+    pub details: String,
+}
+
+/// A report filed against a player.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// Time at which the report was filed.
+    pub time: SystemTime,
+    /// The reported player.
+    pub target: ReportedPlayer,
+    /// Why the player was reported.
+    pub reason: String,
+    /// Most recent chat messages of the reported player, oldest first.
+    pub messages: Vec<String>,
+    /// Position of the reported player when the report was filed.
+    pub position: Option<Vector<f32, 3>>,
+    /// Information supplied by whoever filed the report.
+    pub context: ReportContext,
+}
+
+impl Report {
+    /// Converts the report to JSON.
+    pub fn to_json(&self) -> serde_json::Value {
+        let player = |player: &ReportedPlayer| serde_json::json!({ "xuid": player.xuid.to_string(), "name": player.name });
+
+        serde_json::json!({
+            "time": self.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            "target": player(&self.target),
+            "reason": self.reason,
+            "messages": self.messages,
+            "position": self.position.as_ref().map(|pos| [pos.x, pos.y, pos.z]),
+            "reporter": self.context.reporter.as_ref().map(player),
+            "details": self.context.details,
+        })
+    }
+}
+
+/// Stores or forwards filed reports.
+pub trait ReportBackend: Send + Sync {
+    /// Submits a report.
+    ///
+    /// This is called from the task that handled the report, so backends that perform network requests
+    /// should hand the report off to a task of their own.
+    fn submit(&self, report: &Report) -> anyhow::Result<()>;
+}
+
+/// Appends reports to a file, one JSON object per line.
+#[derive(Debug)]
+pub struct FileBackend {
+    /// Location of the file.
+    path: PathBuf,
+    /// Prevents concurrent reports from interleaving their lines.
+    lock: Mutex<()>,
+}
+
+impl FileBackend {
+    /// Creates a backend that appends to the file at the given path. The file is created if it does not exist.
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileBackend {
+        FileBackend { path: path.into(), lock: Mutex::new(()) }
+    }
+}
+
+impl ReportBackend for FileBackend {
+    fn submit(&self, report: &Report) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(&report.to_json())?;
+        line.push(b'\n');
+
+        let _guard = self.lock.lock();
+        OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(&line)?;
+
+        Ok(())
+    }
+}
+
+/// Keeps track of the most recent chat messages of a player.
+#[derive(Debug, Default)]
+pub struct ChatHistory {
+    messages: Mutex<VecDeque<String>>,
+}
+
+impl ChatHistory {
+    /// Creates an empty history.
+    pub fn new() -> ChatHistory {
+        ChatHistory::default()
+    }
+
+    /// Records a chat message, forgetting the oldest one if the history is full.
+    pub fn record(&self, message: &str) {
+        let mut messages = self.messages.lock();
+        if messages.len() == CHAT_HISTORY_SIZE {
+            messages.pop_front();
+        }
+        messages.push_back(message.to_owned());
+    }
+
+    /// Returns the recorded messages, oldest first.
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().iter().cloned().collect()
+    }
+}
+
+impl Instance {
+    /// Files a report against a player.
+    ///
+    /// The report is submitted to the configured [`ReportBackend`] and sent to the subscribers of
+    /// [`report_events`](Self::report_events).
+    pub fn report(&self, target: &BedrockClient, reason: &str, context: ReportContext) -> anyhow::Result<Report> {
+        let report = Report {
+            time: SystemTime::now(),
+            target: ReportedPlayer::from_client(target)?,
+            reason: reason.to_owned(),
+            messages: target.chat_history.messages(),
+            position: target.player().ok().map(PlayerData::position),
+            context,
+        };
+
+        tracing::info!("{} was reported: {reason}", report.target.name);
+        self.emit_report_event(report.clone());
+        self.report_backend().submit(&report)?;
+
+        Ok(report)
+    }
+}
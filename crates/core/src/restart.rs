@@ -0,0 +1,420 @@
+//! Automatic restarts.
+//!
+//! The server can be restarted or stopped at fixed times of day and after it has been running for a while. Players
+//! are warned ahead of time through chat and titles, after which the server goes through the regular
+//! [shutdown](crate::instance::Instance::shutdown) path so that the level and cooldowns are saved.
+//!
+//! The server cannot start itself again. A restart exits the process with [`RESTART_EXIT_CODE`] instead, which a
+//! supervisor such as systemd or a wrapper script should use to start the server again.
+//!
+//! Operators can use the `restart` command to see when the next restart happens, cancel it or schedule one manually.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use proto::bedrock::{Command, CommandDataType, CommandOverload, CommandParameter, CommandPermissionLevel, SetTitle, TextData, TextMessage, TitleAction};
+use tokio_util::sync::CancellationToken;
+
+use crate::clock::SharedClock;
+use crate::command::{self, HandlerOutput, HandlerResult, ParsedArgument, ParsedCommand};
+use crate::instance::Instance;
+
+/// Exit code of the process after a restart.
+pub const RESTART_EXIT_CODE: i32 = 75;
+/// How often the monitor checks whether a warning should be sent.
+const MONITOR_INTERVAL: Duration = Duration::from_secs(1);
+/// Length of a day in seconds.
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// What happens when a scheduled restart is due.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RestartAction {
+    /// Shut down and exit with [`RESTART_EXIT_CODE`].
+    #[default]
+    Restart,
+    /// Shut down and exit normally.
+    Stop,
+}
+
+impl RestartAction {
+    /// Describes the action in messages to players.
+    const fn verb(self) -> &'static str {
+        match self {
+            Self::Restart => "restart",
+            Self::Stop => "stop",
+        }
+    }
+}
+
+/// A time of day in UTC.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimeOfDay {
+    hour: u8,
+    minute: u8,
+}
+
+impl TimeOfDay {
+    /// Creates a time of day, returning `None` if the hour or minute is out of range.
+    pub const fn new(hour: u8, minute: u8) -> Option<TimeOfDay> {
+        if hour < 24 && minute < 60 {
+            Some(TimeOfDay { hour, minute })
+        } else {
+            None
+        }
+    }
+
+    /// Seconds since midnight.
+    const fn seconds(self) -> u64 {
+        self.hour as u64 * 3600 + self.minute as u64 * 60
+    }
+}
+
+/// Automatic restart settings.
+#[derive(Debug, Clone)]
+pub struct RestartConfig {
+    /// Times of day at which the server restarts.
+    pub times: Vec<TimeOfDay>,
+    /// Restarts the server after it has been running for this long.
+    pub uptime: Option<Duration>,
+    /// What happens when a scheduled restart is due.
+    pub action: RestartAction,
+    /// How long before a restart players are warned.
+    pub warnings: Vec<Duration>,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        Self {
+            times: Vec::new(),
+            uptime: None,
+            action: RestartAction::Restart,
+            warnings: [900, 300, 60, 30, 10, 5, 4, 3, 2, 1].into_iter().map(Duration::from_secs).collect(),
+        }
+    }
+}
+
+/// A restart that is going to happen.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PendingRestart {
+    /// When the restart happens.
+    pub at: Instant,
+    /// What happens at that time.
+    pub action: RestartAction,
+    /// Whether the restart was scheduled by an operator rather than the configuration.
+    pub manual: bool,
+}
+
+/// Mutable state of the scheduler.
+#[derive(Debug, Default)]
+struct RestartState {
+    /// The next restart, computed from the configuration if it is not set.
+    pending: Option<PendingRestart>,
+    /// Configured restarts at or before this time have been cancelled.
+    cancelled_until: Option<Instant>,
+    /// Smallest warning that has been sent for the pending restart.
+    warned: Option<Duration>,
+    /// Whether the pending restart has been carried out.
+    triggered: bool,
+}
+
+/// Keeps track of upcoming restarts.
+#[derive(Debug)]
+pub struct RestartScheduler {
+    config: RestartConfig,
+    clock: SharedClock,
+    /// Time at which the server started.
+    started: Instant,
+    /// Wall clock time at `started`, as time since the Unix epoch.
+    ///
+    /// Times of day are converted to instants relative to this, so that the same daily restart
+    /// always maps to the same instant.
+    started_wall: Duration,
+    state: Mutex<RestartState>,
+}
+
+impl RestartScheduler {
+    /// Creates a scheduler for a server that has just started.
+    pub fn new(config: RestartConfig, clock: SharedClock) -> RestartScheduler {
+        let started = clock.now();
+        let started_wall = clock.system_time().duration_since(UNIX_EPOCH).unwrap_or_default();
+        RestartScheduler { config, clock, started, started_wall, state: Mutex::new(RestartState::default()) }
+    }
+
+    /// Returns the settings of the scheduler.
+    #[inline]
+    pub const fn config(&self) -> &RestartConfig {
+        &self.config
+    }
+
+    /// Returns the next restart, if any.
+    pub fn pending(&self) -> Option<PendingRestart> {
+        let mut state = self.state.lock();
+        self.refresh(&mut state);
+        state.pending
+    }
+
+    /// Time left until the next restart.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.pending().map(|pending| pending.at.saturating_duration_since(self.clock.now()))
+    }
+
+    /// Schedules a restart after `delay`, replacing the next restart.
+    pub fn schedule(&self, delay: Duration, action: RestartAction) -> PendingRestart {
+        let pending = PendingRestart { at: self.clock.now() + delay, action, manual: true };
+
+        let mut state = self.state.lock();
+        state.pending = Some(pending);
+        state.warned = None;
+        drop(state);
+
+        pending
+    }
+
+    /// Cancels the next restart, returning it if there was one.
+    ///
+    /// Restarts that follow from the configuration continue to happen after the cancelled one.
+    pub fn cancel(&self) -> Option<PendingRestart> {
+        let mut state = self.state.lock();
+        self.refresh(&mut state);
+
+        let cancelled = state.pending.take();
+        if let Some(configured) = cancelled.filter(|cancelled| !cancelled.manual) {
+            state.cancelled_until = Some(configured.at);
+        }
+        state.warned = None;
+        drop(state);
+
+        cancelled
+    }
+
+    /// Computes the next restart from the configuration if none is pending.
+    fn refresh(&self, state: &mut RestartState) {
+        if state.pending.is_none() && !state.triggered {
+            state.pending = self.next_configured(state.cancelled_until).map(|at| PendingRestart {
+                at,
+                action: self.config.action,
+                manual: false,
+            });
+        }
+    }
+
+    /// Earliest configured restart, skipping restarts at or before `cancelled_until`.
+    fn next_configured(&self, cancelled_until: Option<Instant>) -> Option<Instant> {
+        let is_cancelled = |at: Instant| cancelled_until.is_some_and(|until| at <= until);
+
+        let uptime = self.config.uptime.filter(|uptime| !uptime.is_zero()).map(|uptime| {
+            let mut at = self.started + uptime;
+            while is_cancelled(at) {
+                at += uptime;
+            }
+            at
+        });
+
+        let now = self.clock.now();
+        let today = (self.started_wall + now.saturating_duration_since(self.started)).as_secs() / SECONDS_PER_DAY;
+        let daily = self.config.times.iter().filter_map(|time| {
+            (today..)
+                .map_while(|day| day.checked_mul(SECONDS_PER_DAY))
+                .filter_map(|midnight| self.wall_to_instant(midnight + time.seconds()))
+                .find(|&at| at >= now && !is_cancelled(at))
+        });
+
+        uptime.into_iter().chain(daily).min()
+    }
+
+    /// Converts a wall clock time in seconds since the Unix epoch into an instant.
+    ///
+    /// Returns `None` for times before the server started.
+    fn wall_to_instant(&self, seconds: u64) -> Option<Instant> {
+        Duration::from_secs(seconds).checked_sub(self.started_wall).map(|offset| self.started + offset)
+    }
+
+    /// Advances the scheduler, returning what should happen now.
+    fn poll(&self) -> Option<RestartStep> {
+        let mut state = self.state.lock();
+        let step = self.step(&mut state);
+        drop(state);
+
+        step
+    }
+
+    /// Determines what should happen now, updating the state accordingly.
+    fn step(&self, state: &mut RestartState) -> Option<RestartStep> {
+        self.refresh(state);
+
+        let pending = state.pending?;
+        let remaining = pending.at.saturating_duration_since(self.clock.now());
+        if remaining.is_zero() {
+            state.triggered = true;
+            state.pending = None;
+            return Some(RestartStep::Trigger(pending.action))
+        }
+
+        let warning = self
+            .config
+            .warnings
+            .iter()
+            .copied()
+            .filter(|warning| remaining <= *warning && state.warned.map_or(true, |warned| *warning < warned))
+            .min()?;
+
+        state.warned = Some(warning);
+        Some(RestartStep::Warn(pending.action, remaining))
+    }
+}
+
+/// Something that the monitor should do.
+enum RestartStep {
+    /// Warn players that the server restarts in the given time.
+    Warn(RestartAction, Duration),
+    /// Shut down the server.
+    Trigger(RestartAction),
+}
+
+/// Formats a duration as it is shown to players, rounded up to whole seconds.
+fn format_remaining(remaining: Duration) -> String {
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    match seconds {
+        1 => "1 second".to_owned(),
+        s if s < 60 => format!("{s} seconds"),
+        s if s < 120 => "1 minute".to_owned(),
+        s => format!("{} minutes", s / 60),
+    }
+}
+
+/// Sends a restart warning to all players through chat and a title.
+fn warn(instance: &Instance, action: RestartAction, remaining: Duration) -> anyhow::Result<()> {
+    let message = format!("§eThe server will {} in {}", action.verb(), format_remaining(remaining));
+    tracing::info!("Server will {} in {}", action.verb(), format_remaining(remaining));
+
+    let clients = instance.clients();
+    clients.broadcast(TextMessage {
+        data: TextData::System { message: &message },
+        needs_translation: false,
+        xuid: 0,
+        platform_chat_id: "",
+    })?;
+
+    clients.broadcast(SetTitle {
+        action: TitleAction::SetActionBar,
+        text: &message,
+        fade_in_duration: 0,
+        remain_duration: 60,
+        fade_out_duration: 10,
+        xuid: "",
+        platform_online_id: "",
+    })
+}
+
+/// Sends warnings and shuts the server down once a restart is due, until the token is cancelled.
+pub(crate) async fn monitor(instance: Arc<Instance>, token: CancellationToken) {
+    let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            () = token.cancelled() => break
+        }
+
+        match instance.restarts().poll() {
+            Some(RestartStep::Warn(action, remaining)) => {
+                if let Err(err) = warn(&instance, action, remaining) {
+                    tracing::error!("Failed to send restart warning: {err:#}");
+                }
+            }
+            Some(RestartStep::Trigger(action)) => {
+                tracing::info!("Performing scheduled {}", action.verb());
+                instance.set_restart_requested(action == RestartAction::Restart);
+                let _: Option<_> = instance.shutdown();
+                break
+            }
+            None => {}
+        }
+    }
+}
+
+/// Syntax of the `restart` command.
+pub(crate) fn command() -> Command {
+    let parameter = |name: &str, data_type, optional| CommandParameter {
+        name: name.to_owned(),
+        command_enum: None,
+        data_type,
+        optional,
+        options: 0,
+        suffix: "".to_owned(),
+    };
+
+    Command {
+        aliases: Vec::new(),
+        description: "Shows, cancels or schedules a server restart".to_owned(),
+        name: "restart".to_owned(),
+        overloads: vec![CommandOverload {
+            parameters: vec![
+                parameter("action", CommandDataType::String, true),
+                parameter("minutes", CommandDataType::Int, true),
+            ],
+        }],
+        permission_level: CommandPermissionLevel::Admin,
+    }
+}
+
+/// Handler of the `restart` command.
+///
+/// Without arguments the next restart is shown. `cancel` cancels it, while `restart` and `stop` schedule a restart
+/// or stop after the given amount of minutes, replacing the next restart.
+pub(crate) fn handle_command(input: ParsedCommand, ctx: &command::Context) -> HandlerResult {
+    let restarts = ctx.instance.restarts();
+    let action = match input.parameters.get("action").and_then(ParsedArgument::as_string) {
+        None | Some("status") => {
+            let Some(pending) = restarts.pending() else {
+                return HandlerOutput::new().message("No restart is scheduled").success()
+            };
+
+            return HandlerOutput::new()
+                .message(format!(
+                    "The server will {} in {}{}",
+                    pending.action.verb(),
+                    format_remaining(pending.at.saturating_duration_since(ctx.instance.clock().now())),
+                    if pending.manual { " (scheduled by an operator)" } else { "" }
+                ))
+                .success()
+        }
+        Some("cancel") => {
+            let Some(cancelled) = restarts.cancel() else {
+                return HandlerOutput::new().message("No restart is scheduled").error()
+            };
+
+            let message = format!("The scheduled {} has been cancelled", cancelled.action.verb());
+            let _: anyhow::Result<()> = ctx.instance.clients().broadcast(TextMessage {
+                data: TextData::System { message: &format!("§e{message}") },
+                needs_translation: false,
+                xuid: 0,
+                platform_chat_id: "",
+            });
+
+            return HandlerOutput::new().message(message).success()
+        }
+        Some("restart") => RestartAction::Restart,
+        Some("stop") => RestartAction::Stop,
+        Some(other) => {
+            return HandlerOutput::new()
+                .message(format!("Unknown action '{other}', expected status, cancel, restart or stop"))
+                .error()
+        }
+    };
+
+    let minutes = input.parameters.get("minutes").and_then(ParsedArgument::as_int).unwrap_or(0);
+    let Ok(minutes) = u64::try_from(minutes) else {
+        return HandlerOutput::new().message("The amount of minutes cannot be negative").error()
+    };
+
+    let pending = restarts.schedule(Duration::from_secs(minutes * 60), action);
+    HandlerOutput::new()
+        .message(format!(
+            "The server will {} in {}",
+            pending.action.verb(),
+            format_remaining(pending.at.saturating_duration_since(ctx.instance.clock().now()))
+        ))
+        .success()
+}
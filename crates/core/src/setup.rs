@@ -0,0 +1,270 @@
+//! First-run setup of the server.
+//!
+//! The server is configured through a `server.toml` file in its working directory. When the file does not exist
+//! yet, [`load_or_create`] writes a commented default configuration containing a freshly generated server identity
+//! and creates an empty world, so that a new server can be started without any environment variables.
+//! The server does not start until the owner has accepted the Minecraft EULA, either in the configuration or when
+//! prompted on the terminal.
+
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::net::{SocketAddrV4, SocketAddrV6};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Deserializer};
+
+use crate::instance::InstanceBuilder;
+use crate::level::seed::SeedPrivacy;
+
+/// Default location of the configuration file.
+pub const CONFIG_FILE: &str = "server.toml";
+/// Line of the configuration that has to be changed to accept the EULA.
+const EULA_LINE: &str = "accept_eula = false";
+
+/// Configuration loaded from `server.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Whether the owner has accepted the Minecraft EULA.
+    pub accept_eula: bool,
+    /// Name of the server.
+    pub name: String,
+    /// Address of the IPv4 socket.
+    pub address: SocketAddrV4,
+    /// Address of the optional IPv6 socket.
+    pub address_v6: Option<SocketAddrV6>,
+    /// Maximum amount of players that can be online at the same time.
+    pub max_players: usize,
+    /// Maximum render distance in chunks.
+    pub max_render_distance: usize,
+    /// Default log level, used when the `LOG_LEVEL` environment variable is not set.
+    pub log_level: String,
+    /// URLs that notifications about the server are posted to.
+    ///
+    /// These are only used if the server was built with the `webhooks` feature.
+    pub webhooks: Vec<String>,
+    /// Level settings.
+    pub level: LevelSection,
+    /// Identity of the server, generated on the first start.
+    pub identity: Option<Identity>,
+}
+
+/// The `[level]` section of the configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevelSection {
+    /// Directory that the world is stored in.
+    pub path: PathBuf,
+    /// Which seed is sent to clients.
+    pub seed_privacy: SeedPrivacy,
+}
+
+/// The `[identity]` section of the configuration.
+///
+/// TOML integers are signed, so both values are stored as hexadecimal strings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Identity {
+    /// RakNet GUID that the server advertises.
+    #[serde(deserialize_with = "hex")]
+    pub guid: u64,
+    /// Secret that is mixed into the seeds sent to clients.
+    #[serde(deserialize_with = "hex")]
+    pub secret: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            accept_eula: false,
+            name: String::from("Mirai server"),
+            address: SocketAddrV4::new(std::net::Ipv4Addr::UNSPECIFIED, 19132),
+            address_v6: None,
+            max_players: 10,
+            max_render_distance: 12,
+            log_level: String::from("info"),
+            webhooks: Vec::new(),
+            level: LevelSection::default(),
+            identity: None,
+        }
+    }
+}
+
+impl Default for LevelSection {
+    fn default() -> Self {
+        Self { path: PathBuf::from("worlds/world"), seed_privacy: SeedPrivacy::Hide }
+    }
+}
+
+impl ServerConfig {
+    /// Parses a configuration.
+    pub fn parse(content: &str) -> anyhow::Result<ServerConfig> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Applies the configuration to an instance builder.
+    pub fn apply(&self, builder: InstanceBuilder) -> InstanceBuilder {
+        let mut builder = builder
+            .name(self.name.clone())
+            .ipv4_addr(self.address)
+            .max_connections(self.max_players)
+            .max_render_distance(self.max_render_distance)
+            .level_path(self.level.path.to_string_lossy())
+            .seed_privacy(self.level.seed_privacy);
+
+        if let Some(address) = self.address_v6 {
+            builder = builder.ipv6_addr(address);
+        }
+
+        #[cfg(feature = "webhooks")]
+        if !self.webhooks.is_empty() {
+            builder = builder.webhooks(crate::webhook::WebhookConfig { endpoints: self.webhooks.clone(), ..Default::default() });
+        }
+        #[cfg(not(feature = "webhooks"))]
+        if !self.webhooks.is_empty() {
+            tracing::warn!("Webhooks are configured, but the server was built without the webhooks feature");
+        }
+
+        if let Some(identity) = self.identity {
+            builder = builder.raknet_guid(identity.guid).seed_secret(identity.secret);
+        }
+
+        builder
+    }
+}
+
+/// A loaded configuration.
+#[derive(Debug)]
+pub struct Setup {
+    /// The configuration.
+    pub config: ServerConfig,
+    /// Whether this is the first start, in which case the configuration and world have just been created.
+    pub first_run: bool,
+}
+
+/// Loads the configuration at the given path, creating it and the world if it does not exist.
+pub fn load_or_create<P: AsRef<Path>>(path: P) -> anyhow::Result<Setup> {
+    let path = path.as_ref();
+    if path.exists() {
+        let content = fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))?;
+        let config = ServerConfig::parse(&content).with_context(|| format!("Invalid configuration in {}", path.display()))?;
+
+        return Ok(Setup { config, first_run: false })
+    }
+
+    let content = template(rand::random(), rand::random());
+    fs::write(path, &content).with_context(|| format!("Unable to create {}", path.display()))?;
+
+    let config = ServerConfig::parse(&content)?;
+    if !config.level.path.exists() {
+        level::provider::Provider::create(&config.level.path)
+            .with_context(|| format!("Unable to create world in {}", config.level.path.display()))?;
+    }
+
+    Ok(Setup { config, first_run: true })
+}
+
+/// Generates the default configuration with the given identity.
+pub fn template(guid: u64, secret: u64) -> String {
+    format!(
+        r#"# Mirai server configuration.
+#
+# This file was generated on the first start of the server. Restart the server after changing it.
+
+# By setting this to true you confirm that you have read and accept the Minecraft End User License Agreement,
+# which can be found at https://www.minecraft.net/eula.
+{EULA_LINE}
+
+# Name of the server, shown at the top of the player list and as the title of LAN games.
+name = "Mirai server"
+
+# Address that the server listens on.
+address = "0.0.0.0:19132"
+
+# Address that the server listens on for IPv6 clients, remove the comment to enable IPv6.
+# address_v6 = "[::]:19133"
+
+# Maximum amount of players that can be online at the same time.
+max_players = 10
+
+# Maximum render distance in chunks. Clients that request a higher distance are limited to this value.
+max_render_distance = 12
+
+# One of "error", "warn", "info", "debug" or "trace". The LOG_LEVEL environment variable takes precedence.
+log_level = "info"
+
+# Discord, Slack or other webhook URLs that are notified when the server starts or stops, a player is kicked
+# or reported and when the amount of online players reaches a milestone. Requires the webhooks feature.
+# webhooks = ["https://discord.com/api/webhooks/..."]
+
+[level]
+# Directory that the world is stored in. An empty world is created here on the first start,
+# replace it with an existing Bedrock world to use that instead.
+path = "worlds/world"
+
+# Which seed is sent to clients: "reveal" sends the real seed, "hide" sends 0 and "per-client"
+# sends a different seed to every player that cannot be used to find the real one.
+seed_privacy = "hide"
+
+[identity]
+# Generated on the first start. Keep these when moving the server so that it keeps its identity.
+guid = "{guid:016x}"
+secret = "{secret:016x}"
+"#
+    )
+}
+
+/// Instructions shown after the configuration has been created.
+pub fn next_steps(setup: &Setup, path: &Path) -> String {
+    format!(
+        "Welcome to Mirai! This appears to be the first start of the server.\n\
+        \n\
+        Created the configuration in {}\n\
+        Created an empty world in {}\n\
+        \n\
+        Next steps:\n\
+        1. Read the Minecraft EULA at https://www.minecraft.net/eula and accept it below or in the configuration.\n\
+        2. Review the configuration, especially the name and address of the server.\n\
+        3. Optionally replace the world with an existing Bedrock world.\n\
+        4. Start the server again if it is not running. Players can join at {}.",
+        path.display(),
+        setup.config.level.path.display(),
+        setup.config.address
+    )
+}
+
+/// Asks the owner to accept the EULA if the server is running in a terminal.
+///
+/// Returns `false` if the EULA was not accepted or there is no terminal to ask on.
+pub fn prompt_eula() -> io::Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Ok(false)
+    }
+
+    print!("Do you accept the Minecraft EULA (https://www.minecraft.net/eula)? [y/N] ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
+
+/// Records in the configuration that the EULA has been accepted.
+pub fn accept_eula<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)?;
+    if !content.contains(EULA_LINE) {
+        anyhow::bail!("Set accept_eula to true in {} to accept the EULA", path.display());
+    }
+
+    fs::write(path, content.replacen(EULA_LINE, "accept_eula = true", 1))?;
+    Ok(())
+}
+
+/// Deserializes a `u64` from a hexadecimal string.
+fn hex<'de, D: Deserializer<'de>>(de: D) -> Result<u64, D::Error> {
+    let value = String::deserialize(de)?;
+    u64::from_str_radix(&value, 16).map_err(serde::de::Error::custom)
+}
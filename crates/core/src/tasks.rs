@@ -0,0 +1,89 @@
+//! Overview of the tasks running on the server.
+//!
+//! Every task spawned through [`util::task`] is recorded together with its purpose, owner and spawn time. The
+//! `tasks` command lists them, which helps to find leaked tasks such as client receivers that outlive their
+//! connection. The same names are shown in tokio-console when the `tokio-console` feature is enabled.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use proto::bedrock::{Command, CommandDataType, CommandOverload, CommandParameter, CommandPermissionLevel};
+use util::task::TaskInfo;
+
+use crate::command::{self, HandlerOutput, HandlerResult, ParsedCommand};
+
+/// Maximum amount of individual tasks listed by the `tasks` command.
+const LISTED_TASKS: usize = 20;
+
+/// Amount of running tasks and the age of the oldest one, grouped by purpose.
+pub fn summary() -> BTreeMap<&'static str, (usize, Duration)> {
+    let mut summary = BTreeMap::<_, (usize, Duration)>::new();
+    for task in util::task::running() {
+        let entry = summary.entry(task.purpose).or_default();
+        entry.0 += 1;
+        entry.1 = entry.1.max(task.age());
+    }
+
+    summary
+}
+
+/// Running tasks whose purpose or owner contains the filter, oldest first.
+pub fn matching(filter: &str) -> Vec<TaskInfo> {
+    util::task::running()
+        .into_iter()
+        .filter(|task| task.purpose.contains(filter) || task.owner.as_deref().is_some_and(|o| o.contains(filter)))
+        .collect()
+}
+
+/// Syntax of the `tasks` command.
+pub(crate) fn command() -> Command {
+    Command {
+        aliases: Vec::new(),
+        description: "Lists the tasks running on the server".to_owned(),
+        name: "tasks".to_owned(),
+        overloads: vec![CommandOverload {
+            parameters: vec![CommandParameter {
+                name: "filter".to_owned(),
+                command_enum: None,
+                data_type: CommandDataType::String,
+                optional: true,
+                options: 0,
+                suffix: "".to_owned(),
+            }],
+        }],
+        permission_level: CommandPermissionLevel::Admin,
+    }
+}
+
+/// Handler of the `tasks` command.
+///
+/// Without a filter the tasks are grouped by purpose, otherwise the individual matching tasks are listed.
+pub(crate) fn handle_command(input: ParsedCommand, _ctx: &command::Context) -> HandlerResult {
+    let Some(filter) = input.parameters.get("filter").and_then(command::ParsedArgument::as_string) else {
+        let summary = summary();
+        let total = summary.values().map(|(count, _)| count).sum::<usize>();
+
+        let mut message = format!("{total} tasks running:");
+        for (purpose, (count, oldest)) in summary {
+            message.push_str(&format!("\n{purpose}: {count} (oldest {}s)", oldest.as_secs()));
+        }
+
+        return HandlerOutput::new().message(message).success()
+    };
+
+    let tasks = matching(filter);
+    if tasks.is_empty() {
+        return HandlerOutput::new().message(format!("No running tasks match '{filter}'")).success()
+    }
+
+    let mut message = format!("{} tasks match '{filter}':", tasks.len());
+    for task in tasks.iter().take(LISTED_TASKS) {
+        message.push_str(&format!("\n#{} {}", task.id, task.purpose));
+        if let Some(owner) = &task.owner {
+            message.push_str(&format!(" [{owner}]"));
+        }
+        message.push_str(&format!(" running for {}s", task.age().as_secs()));
+    }
+
+    HandlerOutput::new().message(message).success()
+}
@@ -23,3 +23,1045 @@ fn header() {
 
     assert_eq!(Header::deserialize(buffer.as_ref()).unwrap(), header);
 }
+
+#[test]
+fn message_parameter() {
+    use proto::bedrock::{Command, CommandDataType, CommandOverload, CommandParameter, CommandPermissionLevel};
+
+    use crate::command::ParsedCommand;
+
+    let parameter = |name: &str, data_type| CommandParameter {
+        name: name.to_owned(),
+        command_enum: None,
+        data_type,
+        optional: true,
+        options: 0,
+        suffix: String::new(),
+    };
+    let syntax = Command {
+        aliases: Vec::new(),
+        description: String::new(),
+        name: "kick".to_owned(),
+        overloads: vec![CommandOverload {
+            parameters: vec![parameter("player", CommandDataType::String), parameter("reason", CommandDataType::Message)],
+        }],
+        permission_level: CommandPermissionLevel::Normal,
+    };
+
+    // Messages take up the rest of the input.
+    let parsed = ParsedCommand::default_parser(&syntax, "/kick Steve no griefing allowed").unwrap();
+    assert_eq!(parsed.parameters["player"].as_string(), Some("Steve"));
+    assert_eq!(parsed.parameters["reason"].as_string(), Some("no griefing allowed"));
+}
+
+#[test]
+fn command_permissions() {
+    use proto::bedrock::CommandPermissionLevel;
+
+    use crate::command::{check_permission, kick_command, stop_command};
+
+    // Players that are not operators only have the lowest permission level.
+    assert!(check_permission(&stop_command(), CommandPermissionLevel::Normal).is_err());
+    assert!(check_permission(&kick_command(), CommandPermissionLevel::Normal).is_err());
+
+    assert!(check_permission(&stop_command(), CommandPermissionLevel::Admin).is_ok());
+    assert!(check_permission(&kick_command(), CommandPermissionLevel::Admin).is_ok());
+    assert!(check_permission(&kick_command(), CommandPermissionLevel::Owner).is_ok());
+}
+
+#[test]
+fn afk_tracker() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::afk::AfkTracker;
+    use crate::clock::ManualClock;
+
+    let clock = Arc::new(ManualClock::new());
+    let tracker = AfkTracker::with_clock(Arc::<ManualClock>::clone(&clock));
+    assert!(!tracker.check(Duration::from_secs(60)));
+
+    // Players only become AFK once.
+    clock.advance(Duration::from_secs(60));
+    assert!(tracker.check(Duration::from_secs(60)));
+    assert!(!tracker.check(Duration::from_secs(60)));
+    assert!(tracker.is_afk());
+
+    // Rotations that are too small to be intentional do not count as input.
+    assert!(!tracker.record_rotation(0.01, 0.0));
+    assert!(tracker.is_afk());
+    assert!(tracker.record_rotation(0.0, 45.0));
+    assert!(!tracker.is_afk());
+}
+
+#[test]
+fn manual_clock_cooldowns() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::clock::{Clock, ManualClock};
+    use crate::cooldown::Cooldowns;
+
+    let clock = Arc::new(ManualClock::new());
+    let cooldowns = Cooldowns::with_clock(Arc::<ManualClock>::clone(&clock));
+    let start = clock.now();
+
+    cooldowns.set(1, "test:action", Duration::from_secs(30));
+    clock.advance_ticks(20);
+    assert_eq!(clock.ticks_since(start), 20);
+    assert_eq!(cooldowns.remaining(1, "test:action"), Some(Duration::from_secs(29)));
+
+    clock.advance(Duration::from_secs(29));
+    assert!(!cooldowns.is_active(1, "test:action"));
+}
+
+#[test]
+fn configured_cooldowns() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::clock::ManualClock;
+    use crate::cooldown::{actions, Cooldowns};
+    use crate::instance::InstanceBuilder;
+
+    let builder = InstanceBuilder::new().chat_cooldown(Duration::from_secs(2)).command_cooldown(Duration::ZERO);
+    let config = builder.0.cooldowns();
+    assert_eq!(config.chat, Duration::from_secs(2));
+
+    let clock = Arc::new(ManualClock::new());
+    let cooldowns = Cooldowns::with_clock(Arc::<ManualClock>::clone(&clock));
+    assert!(cooldowns.try_acquire(1, actions::CHAT, config.chat).is_ok());
+    assert_eq!(cooldowns.try_acquire(1, actions::CHAT, config.chat), Err(Duration::from_secs(2)));
+
+    // A zero cooldown never blocks.
+    assert!(cooldowns.try_acquire(1, actions::COMMAND, config.command).is_ok());
+    assert!(cooldowns.try_acquire(1, actions::COMMAND, config.command).is_ok());
+}
+
+#[test]
+fn traffic_windows() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use raknet::Direction;
+
+    use crate::clock::ManualClock;
+    use crate::net::traffic::{Traffic, TrafficStats, TrafficWindow};
+
+    let clock = Arc::new(ManualClock::new());
+    let stats = TrafficStats::new(Arc::<ManualClock>::clone(&clock));
+
+    stats.record(Direction::Inbound, 0x90, 100);
+    stats.record(Direction::Inbound, 0x90, 100);
+    stats.record(Direction::Inbound, 0x01, 50);
+    assert_eq!(stats.total(Direction::Inbound, TrafficWindow::Second), Traffic { packets: 3, bytes: 250 });
+    assert_eq!(stats.by_type(Direction::Inbound, TrafficWindow::Second)[0], (0x90, Traffic { packets: 2, bytes: 200 }));
+    assert_eq!(stats.total(Direction::Outbound, TrafficWindow::Second), Traffic::default());
+
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(stats.total(Direction::Inbound, TrafficWindow::Second), Traffic::default());
+    assert_eq!(stats.total(Direction::Inbound, TrafficWindow::Minute).packets, 3);
+
+    clock.advance(Duration::from_secs(60));
+    assert!(stats.by_type(Direction::Inbound, TrafficWindow::Minute).is_empty(), "expired traffic was counted");
+}
+
+#[test]
+fn traffic_metrics() {
+    use proto::bedrock::CommandPermissionLevel;
+    use raknet::Direction;
+
+    use crate::command::check_permission;
+    use crate::net::traffic::{self, packet_labels, record_metrics, PACKETS_BY_TYPE_METRIC, PACKET_BYTES_BY_TYPE_METRIC};
+
+    // The ID is unused so that other tests do not affect the counters.
+    let id = 0xffff_ff01;
+    record_metrics(Direction::Outbound, id, 100);
+    record_metrics(Direction::Outbound, id, 20);
+    record_metrics(Direction::Inbound, id, 5);
+
+    let outbound = packet_labels(Direction::Outbound, id);
+    assert_eq!(PACKETS_BY_TYPE_METRIC.get_or_create(&outbound).get(), 2);
+    assert_eq!(PACKET_BYTES_BY_TYPE_METRIC.get_or_create(&outbound).get(), 120);
+    assert_eq!(PACKETS_BY_TYPE_METRIC.get_or_create(&packet_labels(Direction::Inbound, id)).get(), 1);
+
+    assert!(check_permission(&traffic::command(), CommandPermissionLevel::Normal).is_err());
+    assert!(check_permission(&traffic::command(), CommandPermissionLevel::Admin).is_ok());
+}
+
+#[test]
+fn client_ticks() {
+    use std::time::Duration;
+
+    use crate::net::{millis_to_ticks, ticks_to_millis, TickModel, MAX_TICK_LEAD};
+
+    assert_eq!(ticks_to_millis(20), 1000);
+    assert_eq!(millis_to_ticks(1049), 20);
+    assert_eq!(millis_to_ticks(-5), 0);
+
+    let ticks = TickModel::new();
+    assert_eq!(ticks.estimate(0), None);
+    assert!(!ticks.is_ahead(u64::MAX, 0), "ticks were rejected without measurements");
+
+    // The client started ticking 10 seconds before the connection and has a 100 ms round trip.
+    let rtt = Duration::from_millis(100);
+    for tick in (200..400).step_by(10) {
+        let received = ticks_to_millis(tick - 200) + 50;
+        ticks.record(tick, received, rtt);
+    }
+
+    assert!(ticks.is_synchronised());
+    let Some(estimate) = ticks.estimate(10_000) else {
+        panic!("no estimate after recording ticks");
+    };
+    assert!((estimate as i64 - 400).abs() <= 1, "estimated tick {estimate} instead of 400");
+    let Some(local) = ticks.to_local(400) else {
+        panic!("no estimate after recording ticks");
+    };
+    assert!((local - 10_000).abs() <= 50, "tick 400 was simulated at {local} instead of 10000");
+
+    assert!(!ticks.is_ahead(400 + MAX_TICK_LEAD, 10_000));
+    assert!(ticks.is_ahead(400 + MAX_TICK_LEAD + 2, 10_000), "client running ahead was not detected");
+}
+
+#[test]
+fn registry_reload() -> anyhow::Result<()> {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use level::PaletteEntry;
+    use parking_lot::RwLock;
+    use util::BinaryWrite;
+
+    use crate::item::{DataPack, Registries};
+
+    let pack = |items: &[(&str, i32)], blocks: &[&str], creative: &[&str]| -> anyhow::Result<DataPack> {
+        let mut pack = DataPack::default();
+
+        let items: HashMap<String, i32> = items.iter().map(|(name, id)| ((*name).to_owned(), *id)).collect();
+        nbt::to_var_bytes_in(&mut pack.item_ids, &items)?;
+
+        for name in blocks {
+            let entry = PaletteEntry { name: (*name).to_owned(), version: Some([1, 20, 0, 0]), states: nbt::Compound::new() };
+            nbt::to_var_bytes_in(&mut pack.block_states, &entry)?;
+        }
+
+        // Lists are not supported at the root by the serialiser, so the header of a list of compounds
+        // without a name is written by hand.
+        pack.creative_items.extend([0x09, 0x00, 0x0a]);
+        pack.creative_items.write_var_i32(creative.len() as i32)?;
+        for name in creative {
+            let item = nbt::to_var_bytes(&HashMap::from([("name", *name)]))?;
+            // Skip the type and empty name of the root compound.
+            pack.creative_items.extend_from_slice(&item[2..]);
+        }
+
+        Ok(pack)
+    };
+
+    let items = [("minecraft:shield", 1), ("minecraft:stick", 2)];
+    let blocks = ["minecraft:air", "minecraft:stone"];
+    let slot = RwLock::new(Arc::new(Registries::load(&pack(&items, &blocks, &["minecraft:shield"])?)?));
+
+    // Changing only the creative inventory is allowed.
+    let reloaded = Registries::replace(&slot, &pack(&items, &blocks, &["minecraft:shield", "minecraft:stick"])?)?;
+    assert!(Arc::ptr_eq(&reloaded, &slot.read()));
+    assert_eq!(reloaded.creative.stacks.len(), 3);
+
+    let added_item = pack(&[("minecraft:shield", 1), ("minecraft:stick", 2), ("custom:ruby", 3)], &blocks, &[])?;
+    assert!(Registries::replace(&slot, &added_item).is_err(), "added item was accepted");
+    let added_block = pack(&items, &["minecraft:air", "minecraft:stone", "custom:ruby_block"], &[])?;
+    assert!(Registries::replace(&slot, &added_block).is_err(), "added block state was accepted");
+    assert!(Arc::ptr_eq(&reloaded, &slot.read()), "refused reload replaced the registries");
+
+    Ok(())
+}
+
+#[test]
+fn restart_schedule() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::clock::ManualClock;
+    use crate::restart::{RestartAction, RestartConfig, RestartScheduler};
+
+    let clock = Arc::new(ManualClock::new());
+    let config = RestartConfig { uptime: Some(Duration::from_secs(3600)), ..RestartConfig::default() };
+    let restarts = RestartScheduler::new(config, Arc::<ManualClock>::clone(&clock));
+
+    clock.advance(Duration::from_secs(1800));
+    assert_eq!(restarts.remaining(), Some(Duration::from_secs(1800)));
+
+    // Cancelling skips to the next multiple of the uptime.
+    assert!(restarts.cancel().is_some_and(|cancelled| !cancelled.manual), "configured restart was not cancelled");
+    assert_eq!(restarts.remaining(), Some(Duration::from_secs(5400)));
+
+    let manual = restarts.schedule(Duration::from_secs(300), RestartAction::Stop);
+    assert!(manual.manual && restarts.pending() == Some(manual), "manual restart did not replace the configured one");
+
+    assert!(restarts.cancel().is_some(), "manual restart was not cancelled");
+    assert_eq!(restarts.remaining(), Some(Duration::from_secs(5400)));
+}
+
+#[test]
+fn daily_restart_cancel() {
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use proto::bedrock::CommandPermissionLevel;
+
+    use crate::clock::{Clock, ManualClock};
+    use crate::command::check_permission;
+    use crate::restart::{self, RestartConfig, RestartScheduler, TimeOfDay};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    let clock = Arc::new(ManualClock::new());
+    let hour = clock.system_time().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 3600 % 24;
+    let Some(time) = TimeOfDay::new(((hour + 2) % 24) as u8, 0) else {
+        panic!("invalid time of day");
+    };
+
+    let config = RestartConfig { times: vec![time], ..RestartConfig::default() };
+    let restarts = RestartScheduler::new(config, Arc::<ManualClock>::clone(&clock));
+
+    let Some(first) = restarts.pending() else {
+        panic!("daily restart was not scheduled");
+    };
+    let remaining = first.at - clock.now();
+    assert!(remaining > Duration::from_secs(3600) && remaining <= Duration::from_secs(7200));
+
+    // The same time of day always maps to the same instant, regardless of when it is computed.
+    clock.advance(Duration::from_millis(1500));
+    assert_eq!(restarts.pending(), Some(first));
+
+    assert_eq!(restarts.cancel(), Some(first));
+    clock.advance(Duration::from_millis(700));
+    assert_eq!(restarts.pending().map(|pending| pending.at), Some(first.at + DAY));
+
+    // Only operators can change the schedule.
+    assert!(check_permission(&restart::command(), CommandPermissionLevel::Normal).is_err());
+    assert!(check_permission(&restart::command(), CommandPermissionLevel::Admin).is_ok());
+}
+
+#[test]
+fn night_skip() {
+    use crate::level::sleep::required_sleepers;
+    use crate::level::time::{WorldTime, DAY_LENGTH};
+
+    assert_eq!(required_sleepers(10, 100), 10);
+    assert_eq!(required_sleepers(10, 25), 3);
+    // A single player always has to sleep, even if the percentage is zero.
+    assert_eq!(required_sleepers(10, 0), 1);
+    assert_eq!(required_sleepers(0, 50), 1);
+
+    let time = WorldTime::new(DAY_LENGTH + 13_000);
+    assert!(time.is_night());
+    assert_eq!(time.skip_to_morning(), 2 * DAY_LENGTH);
+    assert!(!time.is_night());
+}
+
+#[test]
+fn chunk_stats() {
+    use std::time::Duration;
+
+    use proto::types::Dimension;
+    use util::{BlockPosition, Vector};
+
+    use crate::level::stats::{ChunkStat, ChunkStats};
+
+    let stats = ChunkStats::default();
+    stats.record_block_update(Dimension::Overworld, &BlockPosition::new(-1, 64, 17));
+    stats.record_block_update(Dimension::Overworld, &BlockPosition::new(-16, 64, 31));
+    stats.set_entities(Dimension::Overworld, Vector::from([4, 4]), 120);
+    stats.record_tick(Dimension::Nether, Vector::from([0, 0]), Duration::from_micros(500));
+
+    let counters = stats.get(Dimension::Overworld, Vector::from([-1, 1]));
+    assert_eq!(counters.map(|c| c.block_updates), Some(2));
+    assert_eq!(stats.len(), 3);
+
+    let hottest = stats.hottest(ChunkStat::Entities, 1);
+    assert_eq!(hottest[0].2.entities, 120);
+    assert_eq!(stats.hottest(ChunkStat::TickCost, 1)[0].0, Dimension::Nether);
+}
+
+#[test]
+fn loaded_chunks() {
+    use proto::bedrock::CommandPermissionLevel;
+    use proto::types::Dimension;
+    use util::Vector;
+
+    use crate::command::check_permission;
+    use crate::level::chunks::{self, LoadedChunks, SIMULATION_DISTANCE};
+    use crate::level::stats::{self, ChunkStats};
+
+    let area = (2 * SIMULATION_DISTANCE + 1).pow(2) as usize;
+    assert_eq!(chunks::simulated_area([0, 0]).count(), area);
+    assert!(chunks::is_simulated((Dimension::Overworld, [0, 0]), Dimension::Overworld, [SIMULATION_DISTANCE, -SIMULATION_DISTANCE]));
+    assert!(!chunks::is_simulated((Dimension::Overworld, [0, 0]), Dimension::Nether, [0, 0]));
+
+    let loaded = LoadedChunks::default();
+    assert!(loaded.retain(Dimension::Overworld, [1, 2]));
+    assert!(!loaded.retain(Dimension::Overworld, [1, 2]), "chunk was loaded twice");
+    assert!(loaded.release(Dimension::Overworld, [1, 2]).is_none(), "chunk was unloaded while it still had a viewer");
+    assert!(loaded.retain(Dimension::Nether, [1, 2]));
+
+    let mut zombie = nbt::Compound::new();
+    zombie.insert("identifier".to_owned(), nbt::Value::String("minecraft:zombie".to_owned()));
+    let actors = vec![(7, zombie)];
+    assert!(loaded.set_actors(Dimension::Overworld, [1, 2], actors.clone()));
+    assert!(!loaded.set_actors(Dimension::Overworld, [5, 5], Vec::new()), "actors were stored in an unloaded chunk");
+
+    let stats = ChunkStats::default();
+    loaded.tick(&stats);
+    loaded.tick(&stats);
+    let counters = stats.get(Dimension::Overworld, Vector::from([1, 2]));
+    assert_eq!(counters.map(|c| (c.ticks, c.entities)), Some((2, 1)));
+    assert_eq!(stats.len(), 2);
+    assert_eq!(loaded.all_actors().len(), 2);
+
+    // Unloading the chunk hands back its actors so that they can be saved.
+    assert_eq!(loaded.release(Dimension::Overworld, [1, 2]), Some(actors));
+    assert!(!loaded.contains(Dimension::Overworld, [1, 2]));
+    assert_eq!(loaded.actors(Dimension::Overworld, [1, 2]), None);
+    assert_eq!(loaded.len(), 1);
+
+    // Resetting the statistics is part of the command, which requires administrator permissions.
+    assert!(check_permission(&stats::command(), CommandPermissionLevel::Normal).is_err());
+    assert!(check_permission(&stats::command(), CommandPermissionLevel::Admin).is_ok());
+}
+
+#[test]
+fn background_throttle() {
+    use std::time::Duration;
+
+    use proto::types::Dimension;
+
+    use crate::level::autosave::Autosave;
+    use crate::level::chunks::ChunkQueue;
+    use crate::level::throttle::{Throttle, TICK_BUDGET};
+
+    let throttle = Throttle::new();
+    let normal_sends = throttle.chunk_sends_per_tick();
+    assert!(!throttle.autosave_deferred());
+    for _ in 0..20 {
+        throttle.record_tick(TICK_BUDGET + Duration::from_millis(1));
+    }
+    assert!(throttle.is_throttled());
+    assert!(throttle.autosave_deferred());
+    assert!(throttle.chunk_sends_per_tick() < normal_sends, "chunk sends were not reduced");
+
+    // A save that is due while deferred happens as soon as saves are allowed again.
+    let autosave = Autosave::new(3);
+    assert!(!autosave.tick(false));
+    assert!(!autosave.tick(false));
+    assert!(!autosave.tick(true), "deferred save was performed");
+    assert!(autosave.tick(false), "deferred save was not performed after recovering");
+    assert!(!autosave.tick(false));
+
+    let mut queue = ChunkQueue::new();
+    assert_eq!(queue.take(4), None);
+    queue.update(Dimension::Overworld, [0, 0], 2);
+    assert_eq!(queue.len(), 13);
+
+    let Some((dimension, first)) = queue.take(throttle.chunk_sends_per_tick()) else {
+        panic!("no chunks were queued");
+    };
+    assert_eq!(dimension, Dimension::Overworld);
+    assert_eq!(first.len(), throttle.chunk_sends_per_tick());
+    assert_eq!(first[0], [0, 0], "closest chunk was not sent first");
+
+    // Moving one chunk queues the chunks that came into view and drops those that left it.
+    queue.update(Dimension::Overworld, [1, 0], 2);
+    assert!(queue.take(usize::MAX).is_some_and(|(_, chunks)| chunks.contains(&[3, 0]) && !chunks.contains(&[-2, 0])));
+
+    queue.update(Dimension::Nether, [1, 0], 2);
+    assert_eq!(queue.len(), 13, "changing dimension did not queue the whole view");
+}
+
+#[test]
+fn feature_gates() {
+    use proto::bedrock::{DeviceOS, InputMode, UiProfile};
+
+    use crate::net::{Capability, ClientCapabilities, Feature, FeatureGates};
+
+    let console = ClientCapabilities {
+        device_os: DeviceOS::Xbox,
+        ui_profile: UiProfile::Classic,
+        input_mode: Some(InputMode::Gamepad),
+        gui_scale: 0,
+    };
+    let phone = ClientCapabilities { device_os: DeviceOS::Android, input_mode: Some(InputMode::Touch), ..console };
+
+    let gates = FeatureGates::new();
+    gates.disable(Feature::CustomForms, Capability::Console);
+    gates.set_movement_tolerance(Capability::Touch, 1.5);
+
+    assert!(!gates.is_enabled(Feature::CustomForms, &console));
+    assert!(gates.is_enabled(Feature::CustomForms, &phone));
+    assert!(gates.is_enabled(Feature::MenuForms, &console));
+    assert!((gates.movement_tolerance(&phone) - 1.5).abs() < f32::EPSILON);
+    assert!((gates.movement_tolerance(&console) - 1.0).abs() < f32::EPSILON);
+
+    gates.enable(Feature::CustomForms, Capability::Console);
+    assert!(gates.is_enabled(Feature::CustomForms, &console));
+}
+
+#[test]
+fn login_gate() {
+    use proto::bedrock::{CacheStatus, ClientToServerHandshake, ConnectedPacket, Disconnect, Login, ResourcePackClientResponse, TextMessage};
+    use util::RVec;
+
+    use crate::net::{Admission, LoginGate, LoginState};
+
+    let gate = LoginGate::new();
+    gate.advance(LoginState::Handshake);
+
+    // The cache status belongs to the next state and is held back until the handshake completes.
+    assert!(matches!(gate.admit(CacheStatus::ID, RVec::alloc()), Admission::Deferred));
+    assert!(matches!(gate.admit(Login::ID, RVec::alloc()), Admission::Rejected));
+    // Clients can leave at any point.
+    assert!(matches!(gate.admit(Disconnect::ID, RVec::alloc()), Admission::Process(_)));
+    assert!(matches!(gate.admit(ClientToServerHandshake::ID, RVec::alloc()), Admission::Process(_)));
+    assert!(gate.take_ready().is_none());
+
+    gate.advance(LoginState::ResourcePacks);
+    assert_eq!(gate.take_ready().map(|(id, _)| id), Some(CacheStatus::ID));
+    assert!(matches!(gate.admit(ResourcePackClientResponse::ID, RVec::alloc()), Admission::Process(_)));
+
+    // The state never moves backwards.
+    gate.advance(LoginState::InGame);
+    gate.advance(LoginState::Login);
+    assert_eq!(gate.state(), LoginState::InGame);
+    assert!(matches!(gate.admit(TextMessage::ID, RVec::alloc()), Admission::Process(_)));
+}
+
+#[test]
+fn seed_privacy() {
+    use proto::bedrock::CommandPermissionLevel;
+
+    use crate::command::check_permission;
+    use crate::level::seed::{SeedPrivacy, WorldSeed};
+
+    let seed = WorldSeed::new(-42);
+    assert_eq!(seed.for_client(SeedPrivacy::Reveal, 1), -42i64 as u64);
+    assert_eq!(seed.for_client(SeedPrivacy::Hide, 1), 0);
+
+    let first = seed.for_client(SeedPrivacy::PerClient, 1);
+    assert_eq!(first, seed.for_client(SeedPrivacy::PerClient, 1));
+    assert_ne!(first, seed.for_client(SeedPrivacy::PerClient, 2));
+    assert_ne!(first, -42i64 as u64);
+    assert_eq!(seed.value(), -42);
+
+    // Only admins may view the real seed.
+    assert!(check_permission(&crate::level::seed::command(), CommandPermissionLevel::Normal).is_err());
+    assert!(check_permission(&crate::level::seed::command(), CommandPermissionLevel::Admin).is_ok());
+}
+
+#[test]
+fn task_registry() -> anyhow::Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    runtime.block_on(async {
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let handle = util::task::spawn_owned("test::registry", "owner", async move {
+            rx.await.ok();
+        });
+
+        let matching = crate::tasks::matching("owner");
+        assert_eq!(matching.len(), 1, "spawned task was not registered");
+        assert_eq!(matching[0].purpose, "test::registry");
+        assert_eq!(crate::tasks::summary().get("test::registry").map(|(count, _)| *count), Some(1));
+
+        tx.send(()).ok();
+        handle.await?;
+        assert!(crate::tasks::matching("test::registry").is_empty(), "finished task is still registered");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn capacity_metrics() -> anyhow::Result<()> {
+    use crate::level::io::stream::{RegionStream, REGION_QUEUE_DEPTH_METRIC};
+
+    let before = REGION_QUEUE_DEPTH_METRIC.get();
+    let (_sender, receiver) = tokio::sync::mpsc::channel(3);
+    let stream = RegionStream::from_receiver(receiver, 3);
+    assert_eq!(REGION_QUEUE_DEPTH_METRIC.get(), before + 3, "requested subchunks are not pending");
+
+    drop(stream);
+    assert_eq!(REGION_QUEUE_DEPTH_METRIC.get(), before, "dropped stream is still pending");
+
+    let mut registry = prometheus_client::registry::Registry::default();
+    crate::capacity::register(&mut registry);
+
+    let mut encoded = String::new();
+    prometheus_client::encoding::text::encode(&mut encoded, &registry)?;
+    for name in ["raknet_send_queue_depth", "raknet_order_queue_depth", "net_forward_queue_depth", "level_region_queue_depth", "net_forward_timeouts_total"] {
+        assert!(encoded.contains(name), "metric {name} is not registered");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn setup_template() -> anyhow::Result<()> {
+    use crate::level::seed::SeedPrivacy;
+    use crate::setup::ServerConfig;
+
+    let config = ServerConfig::parse(&crate::setup::template(u64::MAX, 42))?;
+    assert!(!config.accept_eula, "EULA is accepted by default");
+    assert_eq!(config.identity.map(|i| (i.guid, i.secret)), Some((u64::MAX, 42)));
+    assert_eq!(config.level.seed_privacy, SeedPrivacy::Hide);
+
+    // Every option in the template is commented and matches the defaults.
+    assert_eq!(ServerConfig { identity: None, ..config }, ServerConfig::default());
+
+    let config = ServerConfig::parse("[level]\nseed_privacy = \"per-client\"")?;
+    assert_eq!(config.level.seed_privacy, SeedPrivacy::PerClient);
+    assert!(ServerConfig::parse("unknown = 1").is_err(), "unknown options are accepted");
+
+    Ok(())
+}
+
+#[test]
+fn report_file_backend() -> anyhow::Result<()> {
+    use std::time::SystemTime;
+
+    use crate::report::{ChatHistory, FileBackend, Report, ReportBackend, ReportContext, ReportedPlayer, CHAT_HISTORY_SIZE};
+
+    let history = ChatHistory::new();
+    for i in 0..CHAT_HISTORY_SIZE + 2 {
+        history.record(&i.to_string());
+    }
+    let messages = history.messages();
+    assert_eq!(messages.len(), CHAT_HISTORY_SIZE, "history exceeds its size");
+    assert_eq!(messages.first().map(String::as_str), Some("2"), "oldest messages are not forgotten first");
+
+    let report = Report {
+        time: SystemTime::UNIX_EPOCH,
+        target: ReportedPlayer { xuid: 1, name: "Target".to_owned() },
+        reason: "Spamming".to_owned(),
+        messages,
+        position: None,
+        context: ReportContext { reporter: Some(ReportedPlayer { xuid: 2, name: "Reporter".to_owned() }), details: String::new() },
+    };
+
+    let path = std::env::temp_dir().join(format!("mirai-reports-{}.jsonl", std::process::id()));
+    let backend = FileBackend::new(&path);
+    backend.submit(&report)?;
+    backend.submit(&report)?;
+
+    let content = std::fs::read_to_string(&path)?;
+    std::fs::remove_file(&path)?;
+
+    let lines = content.lines().map(serde_json::from_str).collect::<Result<Vec<serde_json::Value>, _>>()?;
+    assert_eq!(lines.len(), 2, "reports are not appended");
+    assert_eq!(lines[0], report.to_json());
+    assert_eq!(lines[0]["reporter"]["name"], "Reporter");
+
+    Ok(())
+}
+
+#[test]
+fn chunk_replication() -> anyhow::Result<()> {
+    use proto::types::Dimension;
+
+    use crate::level::replicator::{content_hash, Replicator, MAX_TRACKED_CHUNKS};
+
+    let (plains, village) = (content_hash(b"plains"), content_hash(b"village"));
+    assert_ne!(plains, village);
+    // FNV-1a of an empty payload is the offset basis, which must never change since hashes are persisted.
+    assert_eq!(content_hash(&[]), 0xcbf2_9ce4_8422_2325);
+
+    let replicator = Replicator::new();
+    assert!(!replicator.is_unchanged(1, Dimension::Overworld, [0, 0], plains), "unknown player has chunks");
+
+    replicator.record(1, Dimension::Overworld, [0, 0], plains);
+    replicator.record(1, Dimension::Nether, [3, -4], village);
+    assert!(replicator.is_unchanged(1, Dimension::Overworld, [0, 0], plains));
+    assert!(!replicator.is_unchanged(1, Dimension::Overworld, [0, 0], village), "changed chunk is unchanged");
+    assert!(!replicator.is_unchanged(1, Dimension::End, [0, 0], plains), "dimensions are not separated");
+    assert!(!replicator.is_unchanged(2, Dimension::Overworld, [0, 0], plains), "players are not separated");
+
+    // A rejoining player, possibly after a restart, skips the chunks it already has.
+    let path = std::env::temp_dir().join(format!("mirai-replication-{}.json", std::process::id()));
+    replicator.save(&path)?;
+    let loaded = Replicator::load(&path);
+    std::fs::remove_file(&path)?;
+    let loaded = loaded?;
+
+    assert!(loaded.is_unchanged(1, Dimension::Overworld, [0, 0], plains));
+    assert!(loaded.is_unchanged(1, Dimension::Nether, [3, -4], village));
+    assert_eq!(loaded.tracked(1), 2);
+
+    // The oldest chunks are forgotten once a player has received too many.
+    for x in 0..MAX_TRACKED_CHUNKS as i32 {
+        loaded.record(1, Dimension::Overworld, [x, 1], plains);
+    }
+    assert!(loaded.tracked(1) <= MAX_TRACKED_CHUNKS);
+    assert!(!loaded.is_unchanged(1, Dimension::Overworld, [0, 0], plains), "oldest chunk was kept");
+    assert!(loaded.is_unchanged(1, Dimension::Overworld, [MAX_TRACKED_CHUNKS as i32 - 1, 1], plains), "newest chunk was forgotten");
+
+    Ok(())
+}
+
+#[test]
+fn module_cache() -> anyhow::Result<()> {
+    use crate::extension::{cache_key, ModuleCache, VERSION_FILE};
+
+    let directory = std::env::temp_dir().join(format!("mirai-modules-{}", std::process::id()));
+    let result = (|| -> anyhow::Result<()> {
+        // Every artifact takes up 32 bytes for its digest plus 8 bytes of data, so three of them fit.
+        let cache = ModuleCache::open(&directory, "1.0.0", 120)?;
+        assert_eq!(cache_key(b"first"), cache_key(b"first"));
+        assert_ne!(cache_key(b"first"), cache_key(b"second"), "keys do not depend on the content");
+        assert_eq!(cache.load(b"first")?, None);
+
+        cache.store(b"first", b"artifact")?;
+        cache.store(b"second", b"artifact")?;
+        cache.store(b"third", b"artifact")?;
+        assert_eq!(cache.size(), 120);
+        assert_eq!(cache.load(b"first")?.as_deref(), Some(b"artifact".as_slice()));
+
+        // The second module was used the longest ago, since the first one was just loaded.
+        cache.store(b"fourth", b"artifact")?;
+        assert_eq!(cache.size(), 120);
+        assert!(!cache.contains(b"second"), "least recently used artifact was kept");
+        assert!(cache.contains(b"first") && cache.contains(b"fourth"));
+
+        // Artifacts larger than the cache are never stored.
+        cache.store(b"large", &[0; 128])?;
+        assert!(!cache.contains(b"large"));
+
+        // Corrupted artifacts are removed instead of loaded.
+        let path = directory.join(cache_key(b"third")).with_extension("cwasm");
+        let mut data = std::fs::read(&path)?;
+        let Some(last) = data.last_mut() else { panic!("artifact is empty") };
+        *last ^= 1;
+        std::fs::write(&path, data)?;
+        assert_eq!(cache.load(b"third")?, None);
+        assert!(!path.exists(), "corrupted artifact was not removed");
+
+        // Artifacts survive reopening with the same runtime version, but not with a different one.
+        drop(cache);
+        let cache = ModuleCache::open(&directory, "1.0.0", 120)?;
+        assert!(cache.contains(b"first") && cache.contains(b"fourth"));
+
+        drop(cache);
+        let cache = ModuleCache::open(&directory, "2.0.0", 120)?;
+        assert_eq!(cache.size(), 0);
+        assert_eq!(cache.load(b"first")?, None);
+        assert_eq!(std::fs::read_to_string(directory.join(VERSION_FILE))?, "2.0.0");
+
+        Ok(())
+    })();
+
+    std::fs::remove_dir_all(&directory)?;
+    result
+}
+
+#[cfg(feature = "webhooks")]
+#[test]
+fn webhook_payload() {
+    use crate::webhook::Notification;
+
+    let payload = Notification::Milestone { online: 25 }.to_json("Test server");
+    assert_eq!(payload["event"], "milestone");
+    assert_eq!(payload["online"], 25);
+
+    // Discord reads the `content` field and Slack the `text` field.
+    assert_eq!(payload["content"], "25 players are online on Test server");
+    assert_eq!(payload["content"], payload["text"]);
+}
+
+#[cfg(feature = "webhooks")]
+#[test]
+fn webhook_flush() -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use crate::webhook::{Notification, Notifier, WebhookConfig};
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    runtime.block_on(async {
+        // An endpoint that answers every request.
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let endpoint = format!("http://{}/", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !String::from_utf8_lossy(&request).contains("\"stopped\"") {
+                let n = stream.read(&mut buffer).await?;
+                if n == 0 {
+                    break
+                }
+                request.extend_from_slice(&buffer[..n]);
+            }
+
+            stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await?;
+            anyhow::Ok(String::from_utf8_lossy(&request).into_owned())
+        });
+
+        let config = WebhookConfig { endpoints: vec![endpoint], ..WebhookConfig::default() };
+        let notifier = Notifier::new("Test server".to_owned(), config)?;
+        notifier.post(Notification::Stopped);
+
+        assert!(notifier.flush(Duration::from_secs(5)).await, "stop notification was not delivered in time");
+        let request = server.await??;
+        assert!(request.contains("Test server is shutting down"), "endpoint received {request:?}");
+
+        // An endpoint that never answers must not hold up shutdown.
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let endpoint = format!("http://{}/", listener.local_addr()?);
+        let _server = tokio::spawn(async move {
+            let connection = listener.accept().await;
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            drop(connection);
+        });
+
+        let config = WebhookConfig { endpoints: vec![endpoint], ..WebhookConfig::default() };
+        let notifier = Notifier::new("Test server".to_owned(), config)?;
+        notifier.post(Notification::Stopped);
+
+        assert!(!notifier.flush(Duration::from_millis(100)).await, "flush did not time out");
+        Ok(())
+    })
+}
+
+#[cfg(feature = "compression-dictionary")]
+#[test]
+fn compression_dictionaries() {
+    use crate::instance::InstanceBuilder;
+    use crate::net::dictionary::DICTIONARY_VERSION;
+
+    assert!(!InstanceBuilder::new().0.compression().use_dictionaries(DICTIONARY_VERSION), "dictionaries are enabled by default");
+
+    let builder = InstanceBuilder::new().compression_dictionaries(true);
+    let compression = builder.0.compression();
+    assert!(compression.use_dictionaries(DICTIONARY_VERSION));
+    assert!(!compression.use_dictionaries(0), "dictionaries were used for a client without support");
+    assert!(!compression.use_dictionaries(DICTIONARY_VERSION + 1), "dictionaries were used for a different version");
+}
+
+#[test]
+fn portal_frame() {
+    use crate::level::portal::{portal_blocks, FRAME_BLOCK, PORTAL_BLOCK};
+    use util::Vector;
+
+    let blocks = portal_blocks(&Vector::from([10, 64, -3]));
+    let count = |name: &str| blocks.iter().filter(|(_, entry)| entry.name == name).count();
+
+    // A 4 by 5 frame around a 2 by 3 opening, with a ledge on either side.
+    assert_eq!(count(PORTAL_BLOCK), 6);
+    assert_eq!(count(FRAME_BLOCK), 14 + 4);
+    assert_eq!(count("minecraft:air"), 12);
+
+    let block = |position: [i32; 3]| blocks.iter().find(|(block, _)| *block == Vector::from(position)).map(|(_, entry)| entry.name.as_str());
+    assert_eq!(block([10, 64, -3]), Some(PORTAL_BLOCK));
+    assert_eq!(block([11, 66, -3]), Some(PORTAL_BLOCK));
+    assert_eq!(block([9, 63, -3]), Some(FRAME_BLOCK));
+    assert_eq!(block([12, 67, -3]), Some(FRAME_BLOCK));
+    assert_eq!(block([10, 63, -2]), Some(FRAME_BLOCK));
+    assert_eq!(block([10, 64, -4]), Some("minecraft:air"));
+    assert_eq!(block([13, 64, -3]), None);
+}
+
+#[test]
+fn packet_config() {
+    use raknet::{Reliability, SendPriority};
+
+    use crate::net::PacketConfig;
+
+    // The presets must remain usable once the connection is encrypted.
+    for config in [PacketConfig::DEFAULT, PacketConfig::URGENT] {
+        assert!(config.is_allowed(false), "{config:?} is rejected without encryption");
+        assert!(config.is_allowed(true), "{config:?} is rejected with encryption");
+    }
+
+    let unordered = PacketConfig::URGENT.with_reliability(Reliability::UnreliableSequenced);
+    assert!(unordered.is_allowed(false), "unordered packets are rejected without encryption");
+    assert!(!unordered.is_allowed(true), "unordered packets are allowed with encryption");
+
+    let config = PacketConfig::DEFAULT.with_priority(SendPriority::High);
+    assert_eq!(config, PacketConfig::URGENT);
+}
+
+#[test]
+fn projectile_collisions() {
+    use util::Vector;
+
+    use crate::entity::{HitEffect, Projectile, ProjectileKind, ProjectileState};
+    use crate::level::physics::{first_solid_block, Hitbox, LevelPhysics};
+
+    // Everything below Y 64 is solid ground.
+    let ground = |block: &Vector<i32, 3>| block.y < 64;
+
+    let Some(hit) = first_solid_block(&Vector::from([0.5, 66.0, 0.5]), &Vector::from([2.5, 62.0, 0.5]), ground) else {
+        panic!("segment through the ground did not hit it");
+    };
+    assert!((hit.y - 64.0).abs() < 1e-4 && (hit.x - 1.5).abs() < 1e-4, "ground was hit at {hit:?}");
+    assert!(first_solid_block(&Vector::from([0.5, 70.0, 0.5]), &Vector::from([3.5, 65.0, 0.5]), ground).is_none(), "segment above the ground hit it");
+
+    let hitbox = Hitbox::player(2, &Vector::from([5.0, 64.0, 0.0]));
+    let Some(t) = hitbox.intersect(&Vector::from([3.0, 65.0, 0.0]), &Vector::from([7.0, 65.0, 0.0])) else {
+        panic!("segment through the player did not hit it");
+    };
+    assert!((t - 0.425).abs() < 1e-4, "player was entered at {t}");
+    assert!(hitbox.intersect(&Vector::from([3.0, 67.0, 0.0]), &Vector::from([7.0, 67.0, 0.0])).is_none(), "segment above the player hit it");
+
+    // An arrow fired by player 1 flies through its owner and hits player 2.
+    let physics = LevelPhysics::new(ground, vec![Hitbox::player(1, &Vector::from([3.0, 64.0, 0.0])), hitbox]);
+    let mut arrow = Projectile::new(ProjectileKind::Arrow, Some(1), Vector::from([3.0, 65.5, 0.0]), Vector::from([3.0, 0.0, 0.0]));
+    let ProjectileState::Hit(effects) = arrow.tick(&physics) else {
+        panic!("arrow did not hit the player in its path");
+    };
+    assert_eq!(effects, [HitEffect::Damage { target: 2, amount: 6.0 }]);
+
+    // An ender pearl teleports its owner to where it lands.
+    let mut pearl = Projectile::new(ProjectileKind::EnderPearl, Some(1), Vector::from([0.5, 66.0, 10.5]), Vector::from([0.0, -0.5, 0.0]));
+    let mut state = ProjectileState::Flying;
+    for _ in 0..10 {
+        state = pearl.tick(&physics);
+        if state != ProjectileState::Flying {
+            break
+        }
+    }
+
+    let ProjectileState::Hit(effects) = state else {
+        panic!("ender pearl did not land");
+    };
+    assert_eq!(effects[0], HitEffect::Teleport { target: 1, position: Vector::from([0.5, 64.0, 10.5]) });
+}
+
+#[test]
+fn command_alias_conflicts() {
+    use std::collections::HashMap;
+
+    use proto::bedrock::{Command, CommandPermissionLevel};
+
+    use crate::command::check_conflicts;
+
+    let command = |name: &str, aliases: &[&str]| Command {
+        aliases: aliases.iter().map(|alias| (*alias).to_owned()).collect(),
+        description: String::new(),
+        name: name.to_owned(),
+        overloads: Vec::new(),
+        permission_level: CommandPermissionLevel::Normal,
+    };
+
+    // "teleport" has the alias "tp" and "kill" has no aliases.
+    let aliases = HashMap::from([("tp".to_owned(), "teleport".to_owned())]);
+    let check = |structure: &Command| {
+        check_conflicts(structure, |name| aliases.get(name).cloned(), |name| ["teleport", "tp", "kill"].contains(&name))
+    };
+
+    assert!(check(&command("tpa", &["tp"])).is_err(), "alias replaced an alias");
+    assert!(check(&command("slay", &["kill"])).is_err(), "alias replaced a command");
+    assert!(check(&command("tp", &[])).is_err(), "command replaced an alias");
+    assert!(check(&command("home", &["home"])).is_err(), "command is an alias of itself");
+
+    // Replacing a command may keep its own aliases and add new ones.
+    assert!(check(&command("teleport", &["tp", "goto"])).is_ok(), "replacing a command was rejected");
+    assert!(check(&command("kill", &[])).is_ok(), "overriding a command was rejected");
+}
+
+#[test]
+fn batch_splitting() {
+    use crate::net::{batch_limit, BatchPlacement};
+
+    // Splits packets of the given sizes into payloads the same way the client does.
+    let split = |packets: &[usize], limit: usize| {
+        let (mut payloads, mut batch) = (Vec::new(), Vec::new());
+        for &packet in packets {
+            let placement = BatchPlacement::of(batch.iter().sum(), packet, limit);
+            if placement.flush {
+                payloads.push(std::mem::take(&mut batch));
+            }
+
+            if placement.alone {
+                payloads.push(vec![packet]);
+            } else {
+                batch.push(packet);
+            }
+        }
+        if !batch.is_empty() {
+            payloads.push(batch);
+        }
+        payloads
+    };
+
+    // Packets are collected until the next one no longer fits.
+    assert_eq!(split(&[400, 400, 400, 200, 10], 1000), [vec![400, 400], vec![400, 200, 10]]);
+    assert_eq!(split(&[500, 500, 1], 1000), [vec![500, 500], vec![1]]);
+
+    // Oversized packets are sent on their own, after the packets that were queued before them.
+    assert_eq!(split(&[100, 1000, 100], 1000), [vec![100], vec![1000], vec![100]]);
+    assert_eq!(split(&[5000], 1000), [vec![5000]]);
+
+    // A batch always fits in a single frame.
+    // 28 bytes of IP and UDP headers, a 4 byte datagram header, a 23 byte frame header and 10 bytes of payload header.
+    assert_eq!(batch_limit(1400), 1400 - 28 - 4 - 23 - 10);
+    assert_eq!(batch_limit(0), 0);
+}
+
+#[test]
+fn riding_checks() {
+    use proto::bedrock::EntityLinkType;
+    use util::Vector;
+
+    use crate::net::{Mount, RidingViolation};
+
+    // Unique and runtime IDs of an entity are unrelated.
+    let mut mount = Mount::new(-42, 7, EntityLinkType::Rider);
+
+    // The rider is moved to the seat by the client, wherever it was before mounting.
+    assert_eq!(mount.record_movement(Some(-42), &Vector::from([1000.0, 64.0, -1000.0]), 1.0), Ok(()));
+    assert_eq!(mount.record_movement(Some(-42), &Vector::from([1001.0, 64.0, -1000.0]), 1.0), Ok(()));
+
+    assert_eq!(mount.check_leave(7, &Vector::from([1002.0, 64.0, -1000.0])), Ok(()));
+    assert_eq!(mount.check_leave(42, &Vector::from([1002.0, 64.0, -1000.0])), Err(RidingViolation::WrongVehicle));
+    assert_eq!(mount.check_leave(7, &Vector::from([1007.0, 70.0, -1000.0])), Err(RidingViolation::TooFar));
+
+    assert_eq!(mount.check_movement(Some(-42), &Vector::from([1004.0, 64.0, -1000.0]), 1.0), Ok(()));
+    assert_eq!(mount.check_movement(None, &Vector::from([1004.0, 64.0, -1000.0]), 1.0), Ok(()));
+    assert_eq!(mount.check_movement(Some(7), &Vector::from([1001.0, 64.0, -1000.0]), 1.0), Err(RidingViolation::WrongVehicle));
+    assert_eq!(mount.check_movement(None, &Vector::from([1004.0, 67.0, -1000.0]), 1.0), Err(RidingViolation::TooFar));
+    assert_eq!(mount.check_movement(None, &Vector::from([1004.0, 67.0, -1000.0]), 2.0), Ok(()));
+
+    // Rejected movements are not remembered.
+    assert_eq!(mount.record_movement(None, &Vector::from([0.0; 3]), 1.0), Err(RidingViolation::TooFar));
+    assert_eq!(mount.record_movement(None, &Vector::from([1002.0, 64.0, -1000.0]), 1.0), Ok(()));
+}
+
+#[test]
+fn server_inventory() {
+    use proto::bedrock::{ItemInstance, TransactionAction, TransactionSourceType, WindowId};
+
+    use crate::item::{Inventory, StoredItem};
+
+    let pickaxe = ItemInstance { network_id: 318, count: 1, ..ItemInstance::air() };
+    let action = |slot, old_item: &ItemInstance<'static>, new_item: &ItemInstance<'static>| TransactionAction {
+        source_type: TransactionSourceType::Container { inventory_id: WindowId::Inventory },
+        slot,
+        old_item: old_item.clone(),
+        new_item: new_item.clone(),
+    };
+
+    let inventory = Inventory::new();
+    assert_eq!(inventory.apply(&[action(0, &ItemInstance::air(), &pickaxe)]), Ok(()));
+    assert_eq!(inventory.get(0), Some(StoredItem::from_instance(&pickaxe)));
+
+    // The client claims that a slot holds an item that the server does not know about.
+    let sword = ItemInstance { network_id: 316, count: 1, ..ItemInstance::air() };
+    let forged = [action(0, &pickaxe, &ItemInstance::air()), action(1, &sword, &ItemInstance::air())];
+    assert_eq!(inventory.apply(&forged), Err(vec![1]));
+    assert_eq!(inventory.get(0), Some(StoredItem::from_instance(&pickaxe)), "rejected transaction was partially applied");
+
+    assert_eq!(inventory.apply(&[action(99, &ItemInstance::air(), &sword)]), Err(vec![99]));
+    assert!(!inventory.set(99, StoredItem::from_instance(&sword)));
+    assert!(inventory.get(99).is_none());
+}
@@ -0,0 +1,275 @@
+//! Notifications posted to HTTP endpoints.
+//!
+//! The notifier subscribes to the events of the server and posts a JSON payload to every configured endpoint when
+//! the server starts or stops, a task panics, a player is kicked or reported, or the amount of online players
+//! reaches one of the configured milestones. Every payload contains both a `content` and a `text` field with a
+//! readable description of the event, which makes them compatible with Discord and Slack webhooks without a
+//! custom plugin.
+//!
+//! Delivery is best-effort: failed requests are retried with exponential backoff and dropped after
+//! [`max_attempts`](WebhookConfig::max_attempts) attempts. When the server shuts down, it waits up to
+//! [`SHUTDOWN_TIMEOUT`] for pending notifications, including the stop notification, to be delivered.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::StatusCode;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+use crate::instance::Instance;
+use crate::net::{QuitEvent, QuitReason};
+use crate::report::Report;
+
+/// How often the amount of online players is compared against the milestones.
+const MILESTONE_INTERVAL: Duration = Duration::from_secs(5);
+/// How long a single request can take before it is considered failed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long shutdown waits for pending notifications to be delivered.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Webhook settings.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// URLs that notifications are posted to.
+    pub endpoints: Vec<String>,
+    /// Amounts of online players that are announced when they are first reached.
+    pub milestones: Vec<usize>,
+    /// How many times delivery of a notification is attempted before it is dropped.
+    pub max_attempts: u32,
+    /// Delay before the first retry, this doubles after every failed attempt.
+    pub initial_backoff: Duration,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            milestones: vec![10, 25, 50, 100],
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// An event that is posted to the endpoints.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    /// The server has started.
+    Started,
+    /// The server is shutting down.
+    Stopped,
+    /// A task panicked.
+    Crashed {
+        /// The panic message.
+        message: String,
+    },
+    /// A player was kicked from the server.
+    Kicked {
+        /// Username of the player.
+        name: String,
+        /// Message that was shown to the player.
+        message: String,
+    },
+    /// A player was reported.
+    Reported(Report),
+    /// The amount of online players reached a milestone for the first time since the server started.
+    Milestone {
+        /// Amount of online players.
+        online: usize,
+    },
+}
+
+impl Notification {
+    /// Name of the event in the payload.
+    pub const fn event(&self) -> &'static str {
+        match self {
+            Notification::Started => "started",
+            Notification::Stopped => "stopped",
+            Notification::Crashed { .. } => "crashed",
+            Notification::Kicked { .. } => "kicked",
+            Notification::Reported(_) => "reported",
+            Notification::Milestone { .. } => "milestone",
+        }
+    }
+
+    /// Readable description of the event.
+    pub fn describe(&self, server: &str) -> String {
+        match self {
+            Notification::Started => format!("{server} has started"),
+            Notification::Stopped => format!("{server} is shutting down"),
+            Notification::Crashed { message } => format!("{server} encountered an error: {message}"),
+            Notification::Kicked { name, message } => format!("{name} was kicked from {server}: {message}"),
+            Notification::Reported(report) => format!("{} was reported on {server}: {}", report.target.name, report.reason),
+            Notification::Milestone { online } => format!("{online} players are online on {server}"),
+        }
+    }
+
+    /// Creates the JSON payload of the notification.
+    pub fn to_json(&self, server: &str) -> serde_json::Value {
+        let description = self.describe(server);
+        let mut payload = serde_json::json!({
+            "event": self.event(),
+            "server": server,
+            "time": SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            "content": description,
+            "text": description,
+        });
+
+        match self {
+            Notification::Reported(report) => payload["report"] = report.to_json(),
+            Notification::Milestone { online } => payload["online"] = serde_json::Value::from(*online),
+            _ => {}
+        }
+
+        payload
+    }
+}
+
+/// Converts a quit event to a notification if it is relevant to moderators.
+fn moderation_notification(event: QuitEvent) -> Option<Notification> {
+    match event.reason {
+        QuitReason::Kicked { message, .. } => Some(Notification::Kicked { name: event.name, message }),
+        _ => None,
+    }
+}
+
+/// Posts notifications to the configured endpoints until the token is cancelled.
+///
+/// Before returning, this waits for the remaining notifications to be delivered, so that the stop notification
+/// is sent before the process exits.
+pub(crate) async fn run(instance: Arc<Instance>, config: WebhookConfig, token: CancellationToken) {
+    let notifier = match Notifier::new(instance.config().name().to_owned(), config) {
+        Ok(notifier) => notifier,
+        Err(err) => {
+            tracing::error!("Failed to create webhook client: {err:#}");
+            return
+        }
+    };
+
+    let mut quits = instance.quit_events();
+    let mut reports = instance.report_events();
+    let mut crashes = install_panic_hook();
+
+    let mut milestones = notifier.config.milestones.clone();
+    milestones.sort_unstable();
+    let mut milestones = milestones.into_iter().peekable();
+    let mut interval = tokio::time::interval(MILESTONE_INTERVAL);
+
+    notifier.post(Notification::Started);
+    loop {
+        tokio::select! {
+            event = quits.recv() => match event {
+                Ok(event) => if let Some(notification) = moderation_notification(event) {
+                    notifier.post(notification);
+                },
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            report = reports.recv() => match report {
+                Ok(report) => notifier.post(Notification::Reported(report)),
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            Some(message) = crashes.recv() => notifier.post(Notification::Crashed { message }),
+            _ = interval.tick() => {
+                let online = instance.clients().total_connected();
+                let mut reached = None;
+                while let Some(milestone) = milestones.next_if(|milestone| *milestone <= online) {
+                    reached = Some(milestone);
+                }
+
+                if let Some(online) = reached {
+                    notifier.post(Notification::Milestone { online });
+                }
+            }
+            () = token.cancelled() => break
+        }
+    }
+
+    // Panics that happened right before shutdown are often the reason for it.
+    while let Ok(message) = crashes.try_recv() {
+        notifier.post(Notification::Crashed { message });
+    }
+
+    notifier.post(Notification::Stopped);
+    if !notifier.flush(SHUTDOWN_TIMEOUT).await {
+        tracing::warn!("Gave up on delivering webhook notifications after {SHUTDOWN_TIMEOUT:?}");
+    }
+}
+
+/// Reports panics to the notifier, in addition to the existing panic hook.
+fn install_panic_hook() -> mpsc::UnboundedReceiver<String> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // The receiver is gone once the notifier has stopped.
+        let _: Result<(), mpsc::error::SendError<String>> = sender.send(info.to_string());
+        previous(info);
+    }));
+
+    receiver
+}
+
+/// Delivers notifications to the endpoints.
+pub(crate) struct Notifier {
+    client: reqwest::Client,
+    server: String,
+    config: WebhookConfig,
+    /// Deliveries that are still in progress.
+    pending: TaskTracker,
+}
+
+impl Notifier {
+    /// Creates a notifier for the server with the given name.
+    pub fn new(server: String, config: WebhookConfig) -> anyhow::Result<Arc<Notifier>> {
+        let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Arc::new(Notifier { client, server, config, pending: TaskTracker::new() }))
+    }
+
+    /// Posts a notification to every endpoint in the background.
+    pub fn post(self: &Arc<Notifier>, notification: Notification) {
+        let payload = notification.to_json(&self.server);
+        for endpoint in &self.config.endpoints {
+            let this = Arc::clone(self);
+            let endpoint = endpoint.clone();
+            let payload = payload.clone();
+
+            let delivery = self.pending.track_future(async move { this.deliver(&endpoint, &payload).await });
+            util::task::spawn("webhook::deliver", delivery);
+        }
+    }
+
+    /// Waits until every posted notification has been delivered or dropped, or until the timeout expires.
+    ///
+    /// Returns whether all deliveries finished in time.
+    pub async fn flush(&self, timeout: Duration) -> bool {
+        self.pending.close();
+        tokio::time::timeout(timeout, self.pending.wait()).await.is_ok()
+    }
+
+    /// Posts a payload to an endpoint, retrying with exponential backoff if it fails.
+    async fn deliver(&self, endpoint: &str, payload: &serde_json::Value) {
+        let mut backoff = self.config.initial_backoff;
+        for attempt in 1..=self.config.max_attempts {
+            let result = self.client.post(endpoint).json(payload).send().await.and_then(reqwest::Response::error_for_status);
+            match result {
+                Ok(_) => return,
+                Err(err) if err.status().is_some_and(|status| status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS) => {
+                    tracing::warn!("Webhook endpoint rejected notification: {err:#}");
+                    return
+                }
+                Err(err) if attempt == self.config.max_attempts => {
+                    tracing::warn!("Dropping webhook notification after {attempt} attempts: {err:#}");
+                }
+                Err(err) => {
+                    tracing::debug!("Webhook request failed, retrying in {backoff:?}: {err:#}");
+                    tokio::time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                }
+            }
+        }
+    }
+}
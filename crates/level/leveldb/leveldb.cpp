@@ -47,12 +47,13 @@ struct Database
     }
 };
 
-LevelResult db_open(const char *path)
+LevelResult db_open(const char *path, int create_if_missing)
 {
     LevelResult result{};
 
     std::unique_ptr<Database> database = std::make_unique<Database>();
 
+    database->options.create_if_missing = create_if_missing != 0;
     database->options.filter_policy = leveldb::NewBloomFilterPolicy(10);
     database->options.block_cache = leveldb::NewLRUCache(40 * 1024 * 1024);
     database->options.info_log = new NoOpLogger();
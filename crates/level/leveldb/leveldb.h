@@ -26,7 +26,8 @@ struct SizedData {
 };
 
 // Open a LevelDB database.
-struct LevelResult db_open(const char *path);
+// If create_if_missing is non-zero, an empty database is created when none exists at the path.
+struct LevelResult db_open(const char *path, int create_if_missing);
 
 // Close a LevelDB database.
 // This also frees the pointers, it must no longer be used.
@@ -177,12 +177,29 @@ impl Database {
     where
         P: AsRef<str>,
     {
-        let ffi_path = CString::new(path.as_ref())?;
+        Self::open_with(path.as_ref(), false)
+    }
+
+    /// Opens the database at the specified path, creating an empty database if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the database could not be opened or created.
+    pub fn create<P>(path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<str>,
+    {
+        Self::open_with(path.as_ref(), true)
+    }
+
+    /// Opens the database, optionally creating it first.
+    fn open_with(path: &str, create_if_missing: bool) -> anyhow::Result<Self> {
+        let ffi_path = CString::new(path)?;
 
         // SAFETY: This function is guaranteed to not return exceptions.
         // It also does not modify the argument and returns a valid struct.
         unsafe {
-            let result = ffi::db_open(ffi_path.as_ptr());
+            let result = ffi::db_open(ffi_path.as_ptr(), c_int::from(create_if_missing));
             if result.status == LoadStatus::Success {
                 if result.data.is_null() {
                     tracing::error!("Received database was a null pointer despite the result being marked successful");
@@ -208,6 +225,13 @@ impl Database {
         let mut raw_key = RVec::alloc_with_capacity(key.serialized_size());
         key.serialize(&mut raw_key)?;
 
+        self.get_raw(&raw_key)
+    }
+
+    /// Loads the value stored at the given raw key.
+    ///
+    /// This is used for keys that are not tied to a chunk, such as the `actorprefix` keys.
+    pub fn get_raw(&self, raw_key: &[u8]) -> anyhow::Result<Option<Guard>> {
         // SAFETY: This function is guaranteed to not modify any arguments.
         // It also does not throw exceptions and returns a valid struct.
         //
@@ -35,7 +35,9 @@ pub struct LevelResult {
 
 extern "C" {
     /// Open a LevelDB database.
-    pub fn db_open(path: *const c_char) -> LevelResult;
+    ///
+    /// If `create_if_missing` is non-zero, an empty database is created when none exists at the path.
+    pub fn db_open(path: *const c_char, create_if_missing: c_int) -> LevelResult;
     /// Close a LevelDB database.
     /// This also frees the pointers, it must no longer be used.
     pub fn db_close(database: *mut c_void);
@@ -1,5 +1,5 @@
 use proto::types::Dimension;
-use util::{BinaryRead, BinaryWrite, Vector};
+use util::{BinaryRead, BinaryWrite, RVec, Vector};
 
 /// The `AutonomousEntities` database key.
 pub const AUTONOMOUS_ENTITIES: &[u8] = b"AutonomousEntities";
@@ -17,6 +17,35 @@ pub const SCOREBOARD: &[u8] = b"scoreboard";
 pub const SCHEDULER: &[u8] = b"schedulerWT";
 /// The `~local_player` database key.
 pub const LOCAL_PLAYER: &[u8] = b"~local_player";
+/// Prefix of the keys that store a single actor.
+pub const ACTOR_PREFIX: &[u8] = b"actorprefix";
+/// Prefix of the keys that store the list of actors in a chunk.
+pub const ACTOR_DIGEST_PREFIX: &[u8] = b"digp";
+
+/// Creates the key that the actor with the given unique ID is stored at.
+pub fn actor_key(unique_id: i64) -> anyhow::Result<RVec> {
+    let mut key = RVec::alloc_with_capacity(ACTOR_PREFIX.len() + 8);
+    key.extend_from_slice(ACTOR_PREFIX);
+    key.write_i64_le(unique_id)?;
+
+    Ok(key)
+}
+
+/// Creates the key that the actor digest of the given chunk is stored at.
+///
+/// The digest contains the unique IDs of all actors that are located in the chunk.
+pub fn actor_digest_key(coordinates: &Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<RVec> {
+    let mut key = RVec::alloc_with_capacity(ACTOR_DIGEST_PREFIX.len() + 12);
+    key.extend_from_slice(ACTOR_DIGEST_PREFIX);
+    key.write_i32_le(coordinates.x)?;
+    key.write_i32_le(coordinates.y)?;
+
+    if dimension != Dimension::Overworld {
+        key.write_i32_le(dimension as i32)?;
+    }
+
+    Ok(key)
+}
 
 /// Database key prefixes.
 ///
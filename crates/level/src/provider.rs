@@ -3,13 +3,17 @@
 use crate::biome::Biomes;
 use crate::database::Database;
 use crate::settings::LevelSettings;
-use crate::{DataKey, KeyType, SubChunk, WriteBatch};
+use crate::{actor_digest_key, actor_key, DataKey, KeyType, SubChunk, WriteBatch};
 use anyhow::anyhow;
 use proto::types::Dimension;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
-use util::BinaryRead;
+use util::{BinaryRead, BinaryWrite, RVec};
 use util::Vector;
 
+/// NBT data of a single actor.
+pub type ActorData = nbt::Compound;
+
 /// Provides world data.
 ///
 /// This is a wrapper around a database that also deserialises and serialises data.
@@ -35,6 +39,20 @@ impl Provider {
         Ok(Self { database, path: path.as_ref().to_owned() })
     }
 
+    /// Creates an empty world at the specified path, or opens it if it already exists.
+    ///
+    /// # Errors
+    ///
+    /// This method can fail if the directory or database cannot be created, or if the given path is not valid UTF-8.
+    pub fn create<P>(path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        std::fs::create_dir_all(path.as_ref())?;
+        let database = Database::create(path.as_ref().join("db").to_str().ok_or_else(|| anyhow!("Invalid level path"))?)?;
+        Ok(Self { database, path: path.as_ref().to_owned() })
+    }
+
     /// Gets the world settings, encoded in the `level.dat` file.
     ///
     /// # Errors
@@ -44,18 +62,34 @@ impl Provider {
     pub fn settings(&self) -> anyhow::Result<LevelSettings> {
         let raw = std::fs::read(self.path.join("level.dat"))?;
 
-        let mut reader = raw.as_slice();
-        let _file_version = reader.read_u32_le()?;
-        let file_size = reader.read_u32_le()?;
+        let (_file_version, settings) = nbt::read_level_dat(&mut raw.as_slice())?;
+        Ok(settings)
+    }
 
-        let remaining = reader.remaining();
-        if remaining != file_size as usize {
-            tracing::error!("Invalid `level.dat` file: header specified length of {file_size}, but found {remaining}");
-            anyhow::bail!("Invalid `level.dat` file: header specified length of {file_size} bytes, but found {remaining}");
-        }
+    /// Updates the time stored in the `level.dat` file, leaving the other settings untouched.
+    ///
+    /// Returns `false` without writing anything if the level has no `level.dat` file.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the existing file is invalid or cannot be overwritten.
+    #[tracing::instrument(skip_all, name = "Provider::save_time")]
+    pub fn save_time(&self, time: i64) -> anyhow::Result<bool> {
+        let path = self.path.join("level.dat");
+        let raw = match std::fs::read(&path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
 
-        let (settings, _) = nbt::from_le_bytes(&mut reader)?;
-        Ok(settings)
+        let (version, mut settings): (u32, nbt::Value) = nbt::read_level_dat(&mut raw.as_slice())?;
+        let compound = settings.as_compound_mut().ok_or_else(|| anyhow!("Invalid `level.dat` file: root is not a compound"))?;
+        compound.insert("Time".to_owned(), nbt::Value::Long(time));
+
+        let encoded = nbt::write_level_dat(version, &settings)?;
+        std::fs::write(path, encoded.as_slice())?;
+
+        Ok(true)
     }
 
     /// Load the version of the specified chunk.
@@ -148,6 +182,118 @@ impl Provider {
         }
     }
 
+    /// Writes a sub chunk to the database, replacing the existing one.
+    ///
+    /// # Arguments
+    ///
+    /// * `coordinates` - X and Z coordinates of the sub chunk.
+    /// * `index` - Vertical coordinate of the sub chunk.
+    /// * `dimension` - Dimension the sub chunk should be written to.
+    pub fn save_subchunk<I>(&self, coordinates: I, dimension: Dimension, subchunk: &SubChunk) -> anyhow::Result<()>
+    where
+        I: Into<Vector<i32, 3>>,
+    {
+        let coordinates = coordinates.into();
+        let key = DataKey {
+            coordinates: (coordinates.x, coordinates.z).into(),
+            dimension,
+            data: KeyType::SubChunk { index: coordinates.y as i8 },
+        };
+
+        self.database.put(key, subchunk.serialize_disk()?)
+    }
+
+    /// Loads the unique IDs of all actors stored in the specified chunk.
+    ///
+    /// Chunks without any actors return an empty list.
+    pub fn actor_ids<I>(&self, coordinates: I, dimension: Dimension) -> anyhow::Result<Vec<i64>>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let key = actor_digest_key(&coordinates.into(), dimension)?;
+        let Some(data) = self.database.get_raw(&key)? else {
+            return Ok(Vec::new());
+        };
+
+        if data.len() % 8 != 0 {
+            anyhow::bail!("Actor digest has invalid length {}, expected a multiple of 8", data.len());
+        }
+
+        let mut reader = &*data;
+        let mut ids = Vec::with_capacity(data.len() / 8);
+        while !reader.eof() {
+            ids.push(reader.read_i64_le()?);
+        }
+
+        Ok(ids)
+    }
+
+    /// Loads the actor with the given unique ID.
+    ///
+    /// This method returns `None` if the actor does not exist.
+    pub fn actor(&self, unique_id: i64) -> anyhow::Result<Option<ActorData>> {
+        let Some(data) = self.database.get_raw(&actor_key(unique_id)?)? else {
+            return Ok(None);
+        };
+
+        let (actor, _) = nbt::from_le_bytes(&mut &*data)?;
+        Ok(Some(actor))
+    }
+
+    /// Loads all actors stored in the specified chunk.
+    ///
+    /// Actors that are listed in the chunk's digest but do not exist are skipped.
+    pub fn actors<I>(&self, coordinates: I, dimension: Dimension) -> anyhow::Result<Vec<(i64, ActorData)>>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let ids = self.actor_ids(coordinates, dimension)?;
+
+        let mut actors = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(actor) = self.actor(id)? {
+                actors.push((id, actor));
+            } else {
+                tracing::warn!("Actor {id} is listed in a chunk digest but does not exist");
+            }
+        }
+
+        Ok(actors)
+    }
+
+    /// Replaces the actors stored in the specified chunk.
+    ///
+    /// Actors that were previously stored in the chunk but are not in `actors` are removed from the database.
+    /// All changes are applied atomically.
+    pub fn save_actors<I>(&self, coordinates: I, dimension: Dimension, actors: &[(i64, ActorData)]) -> anyhow::Result<()>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let coordinates = coordinates.into();
+        let previous = self.actor_ids(coordinates.clone(), dimension)?;
+        let retained: HashSet<i64> = actors.iter().map(|(id, _)| *id).collect();
+
+        let mut batch = WriteBatch::new();
+        for id in previous.into_iter().filter(|id| !retained.contains(id)) {
+            batch.delete(actor_key(id)?);
+        }
+
+        let mut digest = RVec::alloc_with_capacity(actors.len() * 8);
+        for (id, actor) in actors {
+            batch.put(actor_key(*id)?, nbt::to_le_bytes(actor)?);
+            digest.write_i64_le(*id)?;
+        }
+
+        let digest_key = actor_digest_key(&coordinates, dimension)?;
+        if actors.is_empty() {
+            batch.delete(digest_key);
+        } else {
+            batch.put(digest_key, digest);
+        }
+
+        self.database.execute(&batch)
+    }
+
     /// Create a new write batch that can optionally be used in write operations.
     #[inline]
     pub fn batch() -> WriteBatch {
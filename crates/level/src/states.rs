@@ -158,7 +158,6 @@ use std::{collections::HashMap, sync::atomic::Ordering};
 
 use nohash_hasher::{BuildNoHashHasher, IntMap};
 use proto::bedrock::{ItemStack, ItemType, SHIELD_ID};
-use util::{BinaryRead, RString};
 
 use crate::PaletteEntry;
 
@@ -169,9 +168,9 @@ struct RawCreativeItem {
     pub name: String,
     pub meta: Option<i16>,
     #[serde(default)]
-    pub nbt: HashMap<String, nbt::Value>,
+    pub nbt: nbt::Compound,
     #[serde(default)]
-    pub block_properties: HashMap<String, nbt::Value>,
+    pub block_properties: nbt::Compound,
 }
 
 pub struct CreativeItems {
@@ -180,9 +179,14 @@ pub struct CreativeItems {
 
 impl CreativeItems {
     pub fn new(item_ids: &ItemNetworkIds, block_states: &BlockStates) -> anyhow::Result<Self> {
+        Self::from_nbt(CREATIVE_ITEMS_RAW, item_ids, block_states)
+    }
+
+    /// Loads the creative items from a list of items in network NBT format.
+    pub fn from_nbt(mut raw: &[u8], item_ids: &ItemNetworkIds, block_states: &BlockStates) -> anyhow::Result<Self> {
         tracing::debug!("Loading creative items");
 
-        let nbt: Vec<RawCreativeItem> = nbt::from_var_bytes(&mut CREATIVE_ITEMS_RAW)?.0;
+        let nbt: Vec<RawCreativeItem> = nbt::from_var_bytes(&mut raw)?.0;
         let mut stacks = Vec::with_capacity(nbt.len());
 
         stacks.push(ItemStack {
@@ -191,7 +195,7 @@ impl CreativeItems {
             can_place_on: vec![],
             count: 0,
             block_runtime_id: 0,
-            nbt_data: HashMap::new(),
+            nbt_data: nbt::Compound::new(),
         });
 
         for item in nbt.into_iter().filter(|item| !item.name.contains("element")).take(10) {
@@ -253,16 +257,25 @@ pub struct ItemNetworkIds {
 impl ItemNetworkIds {
     /// Creates a new item map.
     pub fn new() -> anyhow::Result<Self> {
+        let ids = Self::from_nbt(ITEM_IDS_RAW)?;
+        SHIELD_ID.store(ids.shield_id, Ordering::Relaxed);
+
+        Ok(ids)
+    }
+
+    /// Loads an item map from a compound of item names and network IDs in network NBT format.
+    ///
+    /// Unlike [`new`](Self::new), this does not update the global shield ID.
+    pub fn from_nbt(mut raw: &[u8]) -> anyhow::Result<Self> {
         tracing::debug!("Loading item identifiers");
 
-        let nbt: HashMap<String, i32> = nbt::from_var_bytes(&mut ITEM_IDS_RAW)?.0;
+        let nbt: HashMap<String, i32> = nbt::from_var_bytes(&mut raw)?.0;
         let mut shield_id = i32::MAX;
 
         let mut name_to_id = HashMap::with_capacity(nbt.len());
         for (name, id) in &nbt {
             if name == "minecraft:shield" {
                 shield_id = *id;
-                SHIELD_ID.store(*id, Ordering::Relaxed);
             }
 
             name_to_id.insert(name.clone(), *id);
@@ -274,7 +287,9 @@ impl ItemNetworkIds {
 
         let mut id_to_name = IntMap::with_capacity_and_hasher(nbt.len(), BuildNoHashHasher::default());
         for (name, id) in nbt {
-            id_to_name.insert(id, name);
+            if let Some(existing) = id_to_name.insert(id, name) {
+                anyhow::bail!("Network ID {id} is assigned to both {existing} and {}", id_to_name[&id]);
+            }
         }
 
         Ok(Self { name_to_id, id_to_name, shield_id })
@@ -296,6 +311,38 @@ impl ItemNetworkIds {
 
         name
     }
+
+    /// Amount of registered items.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.name_to_id.len()
+    }
+
+    /// Whether no items are registered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.name_to_id.is_empty()
+    }
+
+    /// Verifies that `updated` can replace this map without clients having to rejoin.
+    ///
+    /// Clients only receive the item list when they join, so every item must keep its network ID.
+    /// Items can also not be added, since clients that are already online would not know about them.
+    pub fn check_compatible(&self, updated: &ItemNetworkIds) -> anyhow::Result<()> {
+        for (name, id) in &self.name_to_id {
+            match updated.get_id(name) {
+                Some(new_id) if new_id == *id => {}
+                Some(new_id) => anyhow::bail!("Item {name} changed network ID from {id} to {new_id}, this requires clients to rejoin"),
+                None => anyhow::bail!("Item {name} was removed, this requires clients to rejoin"),
+            }
+        }
+
+        if let Some(name) = updated.name_to_id.keys().find(|name| !self.name_to_id.contains_key(*name)) {
+            anyhow::bail!("Item {name} was added, this requires clients to rejoin");
+        }
+
+        Ok(())
+    }
 }
 
 const BLOCK_STATES_RAW: &[u8] = include_bytes!("../include/block_states.nbt");
@@ -315,19 +362,23 @@ impl BlockStates {
     ///
     /// This function panics if the deserialized state count is not equal to the expected count.
     pub fn new() -> anyhow::Result<Self> {
+        Self::from_nbt(BLOCK_STATES_RAW)
+    }
+
+    /// Loads block states from a sequence of palette entries in network NBT format.
+    ///
+    /// Runtime IDs are assigned in the order that the states appear in.
+    pub fn from_nbt(raw: &[u8]) -> anyhow::Result<Self> {
         tracing::debug!("Loading block state data");
 
         const STATE_COUNT: usize = 14127;
-        let mut reader = BLOCK_STATES_RAW;
-
         let mut states = Self {
             runtime_hashes: HashMap::with_capacity_and_hasher(STATE_COUNT, BuildNoHashHasher::default()),
             air_id: 0,
         };
 
-        while reader.remaining() > 0 {
-            // for _ in 0..10 {
-            let (item, _) = nbt::from_var_bytes(&mut reader)?;
+        for document in nbt::Documents::<nbt::Variable, PaletteEntry>::new(raw) {
+            let (item, _) = document?;
             states.register(item)?;
         }
 
@@ -356,6 +407,45 @@ impl BlockStates {
         self.air_id
     }
 
+    /// Amount of registered block states.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.runtime_hashes.len()
+    }
+
+    /// Whether no block states are registered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.runtime_hashes.is_empty()
+    }
+
+    /// Verifies that `updated` can replace these states without clients having to rejoin.
+    ///
+    /// Chunks that have already been sent refer to blocks by runtime ID, so every state must keep its ID.
+    /// States can also not be added, since clients only learn about custom blocks when they join.
+    pub fn check_compatible(&self, updated: &BlockStates) -> anyhow::Result<()> {
+        for (hash, id) in &self.runtime_hashes {
+            match updated.runtime_hashes.get(hash) {
+                Some(new_id) if new_id == id => {}
+                Some(new_id) => anyhow::bail!("Block state {id} changed runtime ID to {new_id}, this requires clients to rejoin"),
+                None => anyhow::bail!("Block state {id} was removed, this requires clients to rejoin"),
+            }
+        }
+
+        if updated.runtime_hashes.len() != self.runtime_hashes.len() {
+            anyhow::bail!(
+                "{} block states were added, this requires clients to rejoin",
+                updated.runtime_hashes.len().saturating_sub(self.runtime_hashes.len())
+            );
+        }
+
+        if updated.air_id != self.air_id {
+            anyhow::bail!("Runtime ID of air changed from {} to {}, this requires clients to rejoin", self.air_id, updated.air_id);
+        }
+
+        Ok(())
+    }
+
     pub fn register(&mut self, state: PaletteEntry) -> anyhow::Result<()> {
         // tracing::debug!("register {state:?}");
 
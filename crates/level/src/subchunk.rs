@@ -1,5 +1,4 @@
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::iter::FusedIterator;
 use std::ops::{Index, IndexMut};
@@ -75,10 +74,23 @@ pub struct PaletteEntry {
     #[serde(with = "block_version")]
     pub version: Option<[u8; 4]>,
     /// Block-specific properties.
-    pub states: HashMap<String, nbt::Value>,
+    pub states: nbt::Compound,
 }
 
 impl PaletteEntry {
+    /// Version of the block states written by this crate.
+    pub const VERSION: [u8; 4] = [1, 20, 0, 0];
+
+    /// Creates an entry for a block without any states.
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self { name: name.into(), version: Some(Self::VERSION), states: nbt::Compound::new() }
+    }
+
+    /// Creates an air block.
+    pub fn air() -> Self {
+        Self::new("minecraft:air")
+    }
+
     /// Hashes this block.
     pub fn hash(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
@@ -158,6 +170,34 @@ impl SubStorage {
     //     Some(&mut self.palette[index])
     // }
 
+    /// Replaces the block at the given position, adding it to the palette if it is not in there yet.
+    ///
+    /// An empty layer is filled with air first. Palette entries that are no longer used are kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside of the sub chunk.
+    pub fn set<V>(&mut self, pos: V, entry: PaletteEntry)
+    where
+        V: Into<Vector<u8, 3>>,
+    {
+        let pos = pos.into();
+        assert!(pos.x < 16 && pos.y < 16 && pos.z < 16, "Block position out of sub chunk bounds");
+
+        if self.palette.is_empty() {
+            self.palette.push(PaletteEntry::air());
+        }
+
+        let index = if let Some(index) = self.palette.iter().position(|existing| *existing == entry) {
+            index
+        } else {
+            self.palette.push(entry);
+            self.palette.len() - 1
+        };
+
+        self.indices[to_offset(pos)] = index as u16;
+    }
+
     /// Returns a reference to the block palette.
     pub fn palette(&self) -> &[PaletteEntry] {
         &self.palette
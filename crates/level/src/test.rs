@@ -3,7 +3,7 @@ use std::sync::Mutex;
 use proto::types::Dimension;
 use util::Vector;
 
-use crate::{database::Database, provider::Provider, SubChunk};
+use crate::{provider::Provider, PaletteEntry, SubChunk};
 
 // digp [x] [z] [?dimension]
 // contains two int32
@@ -157,3 +157,86 @@ fn subchunks() {
 //
 //     assert_eq!(entry, de);
 // }
+
+#[test]
+fn item_ids_compatibility() {
+    use std::collections::HashMap;
+
+    use crate::ItemNetworkIds;
+
+    let ids = |items: &[(&str, i32)]| {
+        let map: HashMap<String, i32> = items.iter().map(|(name, id)| ((*name).to_owned(), *id)).collect();
+        ItemNetworkIds::from_nbt(&nbt::to_var_bytes(&map).unwrap()).unwrap()
+    };
+
+    let current = ids(&[("minecraft:shield", 1), ("minecraft:stone", 2)]);
+    current.check_compatible(&ids(&[("minecraft:stone", 2), ("minecraft:shield", 1)])).unwrap();
+    assert!(current.check_compatible(&ids(&[("minecraft:shield", 1), ("minecraft:stone", 2), ("custom:ruby", 3)])).is_err(), "added item was accepted");
+    assert!(current.check_compatible(&ids(&[("minecraft:shield", 1), ("minecraft:stone", 3)])).is_err(), "renumbered item was accepted");
+    assert!(current.check_compatible(&ids(&[("minecraft:shield", 1)])).is_err(), "removed item was accepted");
+}
+
+#[test]
+fn block_states_compatibility() {
+    use crate::BlockStates;
+
+    let states = |names: &[&str]| {
+        let mut raw = Vec::new();
+        for name in names {
+            let entry = PaletteEntry { name: (*name).to_owned(), version: Some([1, 20, 0, 0]), states: nbt::Compound::new() };
+            nbt::to_var_bytes_in(&mut raw, &entry).unwrap();
+        }
+        BlockStates::from_nbt(&raw).unwrap()
+    };
+
+    let current = states(&["minecraft:air", "minecraft:stone"]);
+    current.check_compatible(&states(&["minecraft:air", "minecraft:stone"])).unwrap();
+    assert!(current.check_compatible(&states(&["minecraft:air", "minecraft:stone", "custom:ruby_block"])).is_err(), "added state was accepted");
+    assert!(current.check_compatible(&states(&["minecraft:stone", "minecraft:air"])).is_err(), "renumbered state was accepted");
+    assert!(current.check_compatible(&states(&["minecraft:air"])).is_err(), "removed state was accepted");
+}
+
+#[test]
+fn save_time() {
+    let path = std::env::temp_dir().join(format!("mirai-save-time-{}", std::process::id()));
+    let provider = Provider::create(&path).unwrap();
+
+    // Levels without a `level.dat` file are left alone.
+    assert!(!provider.save_time(100).unwrap());
+
+    let file = nbt::write_level_dat(10, &nbt::nbt!({ "LevelName": "Bedrock level", "Time": 5i64 })).unwrap();
+    std::fs::write(path.join("level.dat"), file.as_slice()).unwrap();
+
+    assert!(provider.save_time(24_000).unwrap());
+
+    let raw = std::fs::read(path.join("level.dat")).unwrap();
+    let (version, settings): (u32, nbt::Value) = nbt::read_level_dat(&mut raw.as_slice()).unwrap();
+    assert_eq!(version, 10);
+    assert_eq!(settings["Time"].as_i64(), Some(24_000));
+    assert_eq!(settings["LevelName"].as_str(), Some("Bedrock level"));
+
+    drop(provider);
+    let _: std::io::Result<()> = std::fs::remove_dir_all(path);
+}
+
+#[test]
+fn set_block() {
+    let mut subchunk = SubChunk::empty(0);
+    let Some(layer) = subchunk.layer_mut(0) else {
+        panic!("empty subchunk has no block layer");
+    };
+
+    layer.set(Vector::from([1, 2, 3]), PaletteEntry::new("minecraft:obsidian"));
+    layer.set(Vector::from([3, 2, 1]), PaletteEntry::new("minecraft:obsidian"));
+
+    // The empty layer is filled with air and both blocks share a palette entry.
+    assert_eq!(layer.palette().len(), 2);
+    assert_eq!(layer[Vector::from([0, 0, 0])].name, "minecraft:air");
+    assert_eq!(layer[Vector::from([1, 2, 3])].name, "minecraft:obsidian");
+    assert_eq!(layer[Vector::from([3, 2, 1])].name, "minecraft:obsidian");
+
+    // Replacing a block only changes that position.
+    layer.set(Vector::from([1, 2, 3]), PaletteEntry::air());
+    assert_eq!(layer[Vector::from([1, 2, 3])].name, "minecraft:air");
+    assert_eq!(layer[Vector::from([3, 2, 1])].name, "minecraft:obsidian");
+}
@@ -44,6 +44,7 @@
 use proc_macro::TokenStream;
 
 mod atomic_enum;
+mod try_from_repr;
 mod variant_count;
 
 /// Generates a new type prefixed with `Atomic` that is the same as the affected
@@ -58,3 +59,12 @@ pub fn atomic_enum(_attrs: TokenStream, item: TokenStream) -> TokenStream {
 pub fn variant_count(_attrs: TokenStream, item: TokenStream) -> TokenStream {
     variant_count::inner(item)
 }
+
+/// Implements `TryFrom` for the integer type in the `repr` attribute of a fieldless enum.
+///
+/// The conversion uses an exhaustive match over the discriminants instead of a transmute, so it supports
+/// explicitly assigned discriminants and does not require any unsafe code.
+#[proc_macro_attribute]
+pub fn try_from_repr(_attrs: TokenStream, item: TokenStream) -> TokenStream {
+    try_from_repr::inner(item)
+}
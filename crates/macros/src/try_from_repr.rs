@@ -0,0 +1,73 @@
+use proc_macro::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::{spanned::Spanned, Data, DeriveInput, Fields, Ident};
+
+/// Integer types that can be used as the representation of an enum.
+const REPR_TYPES: &[&str] = &["u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64"];
+
+/// Finds the integer type in the `repr` attribute of the enum.
+fn repr_type(input: &DeriveInput) -> Option<Ident> {
+    let mut repr = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        // Other representation hints such as `align` are not relevant and may fail to parse.
+        attr.parse_nested_meta(|meta| {
+            if let Some(ident) = meta.path.get_ident() {
+                if REPR_TYPES.contains(&ident.to_string().as_str()) {
+                    repr = Some(ident.clone());
+                }
+            }
+            Ok(())
+        }).ok();
+    }
+
+    repr
+}
+
+/// Implements `TryFrom` for the integer representation of a fieldless enum.
+pub fn inner(item: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(item as DeriveInput);
+    let Data::Enum(data) = &input.data else {
+        return TokenStream::from(quote_spanned! {
+            input.span() => compile_error!("try_from_repr can only be applied to enums")
+        });
+    };
+
+    let Some(repr) = repr_type(&input) else {
+        return TokenStream::from(quote_spanned! {
+            input.span() => compile_error!("try_from_repr requires an integer repr attribute such as #[repr(u8)]")
+        });
+    };
+
+    if let Some(variant) = data.variants.iter().find(|v| !matches!(v.fields, Fields::Unit)) {
+        return TokenStream::from(quote_spanned! {
+            variant.span() => compile_error!("try_from_repr can only be applied to enums without fields")
+        });
+    }
+
+    let ident = &input.ident;
+    let name = ident.to_string();
+
+    // Every discriminant is bound to a constant so that both implicit and explicit discriminants can be
+    // matched on. The compiler turns the exhaustive match into a range check or lookup table.
+    let consts = data.variants.iter().map(|v| format_ident!("__{}", v.ident)).collect::<Vec<_>>();
+    let variants = data.variants.iter().map(|v| &v.ident).collect::<Vec<_>>();
+
+    TokenStream::from(quote! {
+        #input
+
+        impl ::std::convert::TryFrom<#repr> for #ident {
+            type Error = ::anyhow::Error;
+
+            #[inline]
+            #[allow(non_upper_case_globals)]
+            fn try_from(value: #repr) -> ::anyhow::Result<Self> {
+                #(const #consts: #repr = #ident::#variants as #repr;)*
+
+                Ok(match value {
+                    #(#consts => Self::#variants,)*
+                    _ => ::anyhow::bail!("Invalid {} discriminant: {value}", #name)
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,218 @@
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+use crate::{Compound, Value};
+
+/// Wrapper that compares, hashes and serialises NBT in canonical form.
+///
+/// The same piece of NBT can be represented in multiple ways. Compound keys are stored in a hash map without
+/// any particular order, arrays are often written as lists of the same elements and floats can be `-0.0` or one
+/// of many NaN values. Item NBT that was built by a plugin will therefore often not be equal to the same NBT sent back
+/// by the client, which breaks stack merging and recipe matching.
+///
+/// In canonical form:
+/// * compounds are compared independent of key order and serialised with their keys sorted,
+/// * byte, int and long arrays are equal to lists containing the same elements,
+/// * `-0.0` is equal to `0.0` and all NaN values are equal to each other.
+///
+/// Integer types are never converted, a byte is not equal to an int with the same value.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt::{Canonical, Value};
+/// let a = Value::IntArray(vec![1, 2, 3]);
+/// let b = Value::List(vec![Value::Int(1), Value::Int(2), Value::Int(3)]);
+///
+/// assert_ne!(a, b);
+/// assert_eq!(Canonical(&a), Canonical(&b));
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct Canonical<T>(pub T);
+
+impl PartialEq for Canonical<&Value> {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.0.canonical_eq(rhs.0)
+    }
+}
+
+impl Eq for Canonical<&Value> {}
+
+impl Hash for Canonical<&Value> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.canonical_hash(state);
+    }
+}
+
+impl Serialize for Canonical<&Value> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Float(v) => ser.serialize_f32(normalize_f32(*v)),
+            Value::Double(v) => ser.serialize_f64(normalize_f64(*v)),
+            Value::List(list) => {
+                let mut seq = ser.serialize_seq(Some(list.len()))?;
+                for element in list {
+                    seq.serialize_element(&Canonical(element))?;
+                }
+                seq.end()
+            }
+            Value::Compound(map) => Canonical(map).serialize(ser),
+            value => value.serialize(ser),
+        }
+    }
+}
+
+impl PartialEq for Canonical<&Compound> {
+    fn eq(&self, rhs: &Self) -> bool {
+        compound_eq(self.0, rhs.0)
+    }
+}
+
+impl Eq for Canonical<&Compound> {}
+
+impl Hash for Canonical<&Compound> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        compound_hash(self.0, state);
+    }
+}
+
+impl Serialize for Canonical<&Compound> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let mut entries = self.0.iter().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|(k, _)| *k);
+
+        let mut map = ser.serialize_map(Some(entries.len()))?;
+        for (k, v) in entries {
+            map.serialize_entry(k, &Canonical(v))?;
+        }
+        map.end()
+    }
+}
+
+impl Value {
+    /// Compares two values in canonical form.
+    ///
+    /// See [`Canonical`] for the differences in representation that are ignored.
+    pub fn canonical_eq(&self, rhs: &Value) -> bool {
+        match (self, rhs) {
+            (Value::Float(lhs), Value::Float(rhs)) => normalize_f32(*lhs).to_bits() == normalize_f32(*rhs).to_bits(),
+            (Value::Double(lhs), Value::Double(rhs)) => normalize_f64(*lhs).to_bits() == normalize_f64(*rhs).to_bits(),
+            (Value::Compound(lhs), Value::Compound(rhs)) => compound_eq(lhs, rhs),
+            (lhs, rhs) => match (Sequence::of(lhs), Sequence::of(rhs)) {
+                (Some(lhs), Some(rhs)) => lhs.len() == rhs.len() && (0..lhs.len()).all(|i| lhs.get(i).canonical_eq(&rhs.get(i))),
+                _ => lhs == rhs,
+            },
+        }
+    }
+
+    /// Hashes the value in canonical form.
+    ///
+    /// Values that are equal according to [`canonical_eq`](Value::canonical_eq) produce the same hash.
+    pub fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Value::Byte(v) => state.write_i8(*v),
+            Value::Short(v) => state.write_i16(*v),
+            Value::Int(v) => state.write_i32(*v),
+            Value::Long(v) => state.write_i64(*v),
+            Value::Float(v) => state.write_u32(normalize_f32(*v).to_bits()),
+            Value::Double(v) => state.write_u64(normalize_f64(*v).to_bits()),
+            Value::String(v) => v.hash(state),
+            Value::Compound(map) => compound_hash(map, state),
+            Value::ByteArray(v) => Sequence::Bytes(v).canonical_hash(state),
+            Value::IntArray(v) => Sequence::Ints(v).canonical_hash(state),
+            Value::LongArray(v) => Sequence::Longs(v).canonical_hash(state),
+            Value::List(v) => Sequence::List(v).canonical_hash(state),
+        }
+    }
+}
+
+/// Compares two compounds in canonical form.
+///
+/// This is the comparison used for item NBT, which is stored as a map rather than a [`Value`].
+pub fn compound_eq(lhs: &Compound, rhs: &Compound) -> bool {
+    lhs.len() == rhs.len() && lhs.iter().all(|(k, v)| rhs.get(k).map_or(false, |r| v.canonical_eq(r)))
+}
+
+/// Hashes a compound in canonical form.
+pub fn compound_hash<H: Hasher>(map: &Compound, state: &mut H) {
+    let mut entries = map.iter().collect::<Vec<_>>();
+    entries.sort_unstable_by_key(|(k, _)| *k);
+
+    state.write_usize(entries.len());
+    for (k, v) in entries {
+        k.hash(state);
+        v.canonical_hash(state);
+    }
+}
+
+/// Maps `-0.0` to `0.0` and every NaN to the same NaN.
+#[inline]
+fn normalize_f32(v: f32) -> f32 {
+    if v.is_nan() {
+        f32::NAN
+    } else if v == 0.0 {
+        0.0
+    } else {
+        v
+    }
+}
+
+/// Maps `-0.0` to `0.0` and every NaN to the same NaN.
+#[inline]
+fn normalize_f64(v: f64) -> f64 {
+    if v.is_nan() {
+        f64::NAN
+    } else if v == 0.0 {
+        0.0
+    } else {
+        v
+    }
+}
+
+/// Any value that contains a sequence of elements.
+enum Sequence<'a> {
+    Bytes(&'a [u8]),
+    Ints(&'a [i32]),
+    Longs(&'a [i64]),
+    List(&'a [Value]),
+}
+
+impl<'a> Sequence<'a> {
+    fn of(value: &'a Value) -> Option<Self> {
+        Some(match value {
+            Value::ByteArray(v) => Sequence::Bytes(v),
+            Value::IntArray(v) => Sequence::Ints(v),
+            Value::LongArray(v) => Sequence::Longs(v),
+            Value::List(v) => Sequence::List(v),
+            _ => return None,
+        })
+    }
+
+    const fn len(&self) -> usize {
+        match self {
+            Sequence::Bytes(v) => v.len(),
+            Sequence::Ints(v) => v.len(),
+            Sequence::Longs(v) => v.len(),
+            Sequence::List(v) => v.len(),
+        }
+    }
+
+    fn get(&self, index: usize) -> Cow<'a, Value> {
+        match self {
+            Sequence::Bytes(v) => Cow::Owned(Value::Byte(v[index] as i8)),
+            Sequence::Ints(v) => Cow::Owned(Value::Int(v[index])),
+            Sequence::Longs(v) => Cow::Owned(Value::Long(v[index])),
+            Sequence::List(v) => Cow::Borrowed(&v[index]),
+        }
+    }
+
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for i in 0..self.len() {
+            self.get(i).canonical_hash(state);
+        }
+    }
+}
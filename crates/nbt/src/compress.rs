@@ -0,0 +1,83 @@
+//! Helpers for NBT files that are compressed with gzip or zlib.
+//!
+//! Java `.dat` files and many Bedrock artifacts store their NBT data compressed.
+//! The variant is chosen with a type parameter, such as [`BigEndian`](crate::BigEndian) for Java files.
+
+use std::io::{Read, Write};
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{stream, VariantImpl};
+
+/// Deserialises the data read from a gzip-compressed reader.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+///  struct Data {
+///     value: String
+///  }
+///
+///  let data = Data { value: "Hello, World!".to_owned() };
+///  let mut file = Vec::new();
+///
+///  nbt::to_gzip_writer::<nbt::BigEndian, _, _>(&mut file, &data).unwrap();
+///  let decoded: Data = nbt::from_gzip_reader::<nbt::BigEndian, _, _>(file.as_slice()).unwrap();
+///
+///  assert_eq!(data, decoded);
+/// # }
+/// ```
+pub fn from_gzip_reader<F, T, R>(reader: R) -> anyhow::Result<T>
+where
+    F: VariantImpl,
+    T: DeserializeOwned,
+    R: Read,
+{
+    stream::from_reader::<F, T, _>(GzDecoder::new(reader))
+}
+
+/// Deserialises the data read from a zlib-compressed reader.
+pub fn from_zlib_reader<F, T, R>(reader: R) -> anyhow::Result<T>
+where
+    F: VariantImpl,
+    T: DeserializeOwned,
+    R: Read,
+{
+    stream::from_reader::<F, T, _>(ZlibDecoder::new(reader))
+}
+
+/// Serialises the given data and writes it gzip-compressed to the writer.
+pub fn to_gzip_writer<F, W, T>(writer: W, value: &T) -> anyhow::Result<()>
+where
+    F: VariantImpl,
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    stream::to_writer::<F, _, _>(&mut encoder, value)?;
+    encoder.finish()?;
+
+    Ok(())
+}
+
+/// Serialises the given data and writes it zlib-compressed to the writer.
+pub fn to_zlib_writer<F, W, T>(writer: W, value: &T) -> anyhow::Result<()>
+where
+    F: VariantImpl,
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut encoder = ZlibEncoder::new(writer, Compression::default());
+    stream::to_writer::<F, _, _>(&mut encoder, value)?;
+    encoder.finish()?;
+
+    Ok(())
+}
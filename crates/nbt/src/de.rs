@@ -1,13 +1,16 @@
 use std::marker::PhantomData;
 
 use paste::paste;
-use serde::de::{DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::de::value::{BorrowedStrDeserializer, StrDeserializer};
+use serde::de::{DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
 use serde::{de, Deserialize};
 
-use util::bail;
-use util::BinaryRead;
+use util::{bail, BinaryRead};
 
-use crate::{BigEndian, FieldType, LittleEndian, NbtError, Variable, Variant, VariantImpl};
+use crate::stream::{Bytes, Input};
+use crate::{
+    BigEndian, FieldType, JavaNetwork, LittleEndian, NbtError, Variable, Variant, VariantImpl, INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN, VALUE_TOKEN,
+};
 
 /// Verifies that the deserialised type is equal to the expected type.
 macro_rules! is_ty {
@@ -33,26 +36,80 @@ macro_rules! forward_unsupported {
     }
 }
 
+/// Limits that protect the deserialiser against hostile input.
+///
+/// Deeply nested compounds and lists could otherwise overflow the stack,
+/// and forged lengths could make the deserialiser allocate absurd amounts of memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum amount of compounds and lists that can be nested in each other.
+    pub max_depth: usize,
+    /// Maximum amount of elements in a single list or array.
+    pub max_elements: u32,
+    /// Maximum amount of data that the decoded values can take up, in bytes.
+    ///
+    /// Every value is accounted for with the size of its contents, such as four bytes for an int or
+    /// the length of a string.
+    pub max_bytes: u64,
+}
+
+impl Limits {
+    /// Limits used for NBT that comes from the network.
+    pub const NETWORK: Limits = Limits { max_depth: 512, max_elements: 1 << 16, max_bytes: 2 * 1024 * 1024 };
+
+    /// Limits used for NBT that is read from disk.
+    ///
+    /// Only the depth is limited, files may contain large amounts of data.
+    pub const DISK: Limits = Limits { max_depth: 512, max_elements: u32::MAX, max_bytes: u64::MAX };
+
+    /// Returns the default limits of the given variant.
+    ///
+    /// The [`Variable`] and [`JavaNetwork`] variants are used by the network and use [`NETWORK`](Limits::NETWORK),
+    /// the other variants use [`DISK`](Limits::DISK).
+    pub const fn for_variant(variant: Variant) -> Limits {
+        match variant {
+            Variant::Variable | Variant::JavaNetwork => Limits::NETWORK,
+            Variant::LittleEndian | Variant::BigEndian => Limits::DISK,
+        }
+    }
+}
+
 /// NBT deserialiser.
 #[derive(Debug)]
 pub struct Deserializer<'re, 'de, F, R>
 where
-    R: BinaryRead<'de>,
+    R: Input<'de>,
     F: VariantImpl + 'de,
 {
     input: &'re mut R,
     next_ty: FieldType,
     is_key: bool,
+    limits: Limits,
+    /// Amount of compounds and lists that are currently being deserialised.
+    depth: usize,
+    /// Amount of bytes taken up by the values that have been deserialised so far.
+    bytes: u64,
+    /// Key of the current entry of every compound that is being deserialised, indexed by depth.
+    ///
+    /// This is used to add the path of a value to any error that occurs while deserialising it.
+    keys: Vec<String>,
     _marker: PhantomData<&'de F>,
 }
 
 impl<'re, 'de, F, R> Deserializer<'re, 'de, F, R>
 where
-    R: BinaryRead<'de>,
+    R: Input<'de>,
     F: VariantImpl + 'de,
 {
     /// Creates a new deserialiser, consuming the reader.
+    ///
+    /// The deserialiser uses the default [`Limits`] of the variant.
     pub fn new(input: &'re mut R) -> anyhow::Result<Self> {
+        Self::with_limits(input, Limits::for_variant(F::AS_ENUM))
+    }
+
+    /// Creates a new deserialiser with custom limits, consuming the reader.
+    pub fn with_limits(input: &'re mut R, limits: Limits) -> anyhow::Result<Self> {
         let next_ty = FieldType::try_from(input.read_u8()?)?;
         if next_ty != FieldType::Compound && next_ty != FieldType::List {
             bail!(Malformed, "Expected compound or list tag as root");
@@ -62,28 +119,139 @@ where
             input,
             next_ty,
             is_key: false,
+            limits,
+            depth: 0,
+            bytes: 0,
+            keys: Vec::new(),
             _marker: PhantomData,
         };
 
-        let _: &str = de.deserialize_raw_str()?;
+        de.skip_root_name()?;
 
         Ok(de)
     }
 
-    /// Deserialise a raw UTF-8 string.
-    fn deserialize_raw_str(&mut self) -> anyhow::Result<&str> {
-        let len = match F::AS_ENUM {
-            Variant::BigEndian => self.input.read_u16_be()? as u32,
+    /// Accounts for `bytes` bytes of decoded data, failing if this exceeds the limit.
+    fn account(&mut self, bytes: u64) -> anyhow::Result<()> {
+        self.bytes = self.bytes.saturating_add(bytes);
+        if self.bytes > self.limits.max_bytes {
+            bail!(Malformed, "NBT data exceeds the maximum size of {} bytes", self.limits.max_bytes);
+        }
+
+        Ok(())
+    }
+
+    /// Enters a compound or list, failing if this exceeds the maximum depth.
+    fn enter(&mut self) -> anyhow::Result<()> {
+        if self.depth >= self.limits.max_depth {
+            bail!(Malformed, "NBT data exceeds the maximum depth of {}", self.limits.max_depth);
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Skips the name of the root tag, which is not used.
+    ///
+    /// The [`JavaNetwork`] variant has no root name.
+    fn skip_root_name(&mut self) -> anyhow::Result<()> {
+        if F::AS_ENUM == Variant::JavaNetwork {
+            return Ok(())
+        }
+
+        let len = self.read_str_len()?;
+        self.account(len as u64)?;
+        let data = self.input.read_bytes(len as usize)?;
+        std::str::from_utf8(data.as_slice())?;
+
+        Ok(())
+    }
+
+    /// Reads the length of a string.
+    fn read_str_len(&mut self) -> anyhow::Result<u32> {
+        Ok(match F::AS_ENUM {
+            Variant::BigEndian | Variant::JavaNetwork => self.input.read_u16_be()? as u32,
             Variant::LittleEndian => self.input.read_u16_le()? as u32,
             Variant::Variable => self.input.read_var_u32()?,
-        };
+        })
+    }
 
-        let data = self.input.take_n(len as usize)?;
-        let str = std::str::from_utf8(data)?;
+    /// Reads the length of a list or array.
+    fn read_seq_len(&mut self) -> anyhow::Result<u32> {
+        Ok(match F::AS_ENUM {
+            Variant::BigEndian | Variant::JavaNetwork => self.input.read_i32_be()? as u32,
+            Variant::LittleEndian => self.input.read_i32_le()? as u32,
+            Variant::Variable => self.input.read_var_i32()? as u32,
+        })
+    }
 
-        // dbg!(str);
+    /// Advances past a value of the given type without deserialising it.
+    ///
+    /// Nothing is allocated, strings are not even validated.
+    /// The limits still apply, so corrupted data cannot make this loop for a long time.
+    fn skip_value(&mut self, ty: FieldType) -> anyhow::Result<()> {
+        let variable = F::AS_ENUM == Variant::Variable;
+        match ty {
+            FieldType::End => bail!(Malformed, "Found unexpected End tag"),
+            FieldType::Int if variable => {
+                self.input.read_var_i32()?;
+            }
+            FieldType::Long if variable => {
+                self.input.read_var_i64()?;
+            }
+            FieldType::Byte | FieldType::Short | FieldType::Int | FieldType::Long | FieldType::Float | FieldType::Double => {
+                self.input.skip(ty.payload_size() as usize)?;
+            }
+            FieldType::String => {
+                let len = self.read_str_len()?;
+                self.account(len as u64)?;
+                self.input.skip(len as usize)?;
+            }
+            FieldType::ByteArray | FieldType::IntArray | FieldType::LongArray | FieldType::List => {
+                let element_ty = match ty {
+                    FieldType::ByteArray => FieldType::Byte,
+                    FieldType::IntArray => FieldType::Int,
+                    FieldType::LongArray => FieldType::Long,
+                    _ => FieldType::try_from(self.input.read_u8()?)?,
+                };
+
+                let len = self.read_seq_len()?;
+                if len > self.limits.max_elements {
+                    bail!(Malformed, "Sequence of length {len} exceeds the maximum of {} elements", self.limits.max_elements);
+                }
+                self.account(len as u64 * element_ty.payload_size())?;
+
+                // Elements of a fixed size can be skipped all at once.
+                let size = element_ty.payload_size() as usize;
+                let varint = variable && matches!(element_ty, FieldType::Int | FieldType::Long);
+
+                self.enter()?;
+                if size > 0 && !varint {
+                    self.input.skip(len as usize * size)?;
+                } else {
+                    for _ in 0..len {
+                        self.skip_value(element_ty)?;
+                    }
+                }
+                self.depth -= 1;
+            }
+            FieldType::Compound => {
+                self.enter()?;
+                loop {
+                    let entry_ty = FieldType::try_from(self.input.read_u8()?)?;
+                    if entry_ty == FieldType::End {
+                        break
+                    }
+                    self.account(entry_ty.payload_size())?;
+
+                    self.skip_value(FieldType::String)?;
+                    self.skip_value(entry_ty)?;
+                }
+                self.depth -= 1;
+            }
+        }
 
-        Ok(str)
+        Ok(())
     }
 }
 
@@ -219,9 +387,92 @@ where
     from_bytes::<Variable, _, _>(reader)
 }
 
-impl<'de, 're, 'a, F, R> de::Deserializer<'de> for &'a mut Deserializer<'re, 'de, F, R>
+/// Reads a single object of type `T` from the given buffer.
+///
+/// This function uses the unnamed big endian format of NBT, which is used by network formats
+/// in Minecraft: Java Edition since 1.20.2.
+///
+/// On success, the deserialised object and amount of bytes read from the buffer are returned.
+#[inline]
+pub fn from_java_net_bytes<'de, T, R>(reader: &mut R) -> anyhow::Result<(T, usize)>
 where
     R: BinaryRead<'de>,
+    T: Deserialize<'de>,
+{
+    from_bytes::<JavaNetwork, _, _>(reader)
+}
+
+/// Deserialises a boolean that is stored as a byte, for use with `#[serde(deserialize_with)]`.
+///
+/// Fields of flattened structs and internally tagged enums are buffered by serde before they are deserialised.
+/// The buffer only remembers that the field is a byte, so boolean fields of these types need this function.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  #[derive(serde::Serialize, serde::Deserialize)]
+///  #[serde(tag = "id")]
+///  enum Entity {
+///     Pig {
+///         #[serde(deserialize_with = "nbt::deserialize_bool")]
+///         saddled: bool
+///     }
+///  }
+///
+///  let encoded = nbt::to_le_bytes(&Entity::Pig { saddled: true }).unwrap();
+///  let (Entity::Pig { saddled }, _) = nbt::from_le_bytes(&mut encoded.as_ref()).unwrap();
+///
+///  assert!(saddled);
+/// # }
+/// ```
+pub fn deserialize_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    /// Accepts both booleans and bytes.
+    struct BoolVisitor;
+
+    impl<'de> Visitor<'de> for BoolVisitor {
+        type Value = bool;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a boolean or byte")
+        }
+
+        #[inline]
+        fn visit_bool<E>(self, v: bool) -> Result<bool, E>
+        where
+            E: de::Error,
+        {
+            Ok(v)
+        }
+
+        #[inline]
+        fn visit_i8<E>(self, v: i8) -> Result<bool, E>
+        where
+            E: de::Error,
+        {
+            Ok(v != 0)
+        }
+
+        #[inline]
+        fn visit_u8<E>(self, v: u8) -> Result<bool, E>
+        where
+            E: de::Error,
+        {
+            Ok(v != 0)
+        }
+    }
+
+    deserializer.deserialize_any(BoolVisitor)
+}
+
+impl<'de, 're, 'a, F, R> de::Deserializer<'de> for &'a mut Deserializer<'re, 'de, F, R>
+where
+    R: Input<'de>,
     F: VariantImpl + 'a,
 {
     type Error = NbtError;
@@ -244,14 +495,13 @@ where
                 FieldType::Float => self.deserialize_f32(visitor),
                 FieldType::Double => self.deserialize_f64(visitor),
                 FieldType::ByteArray => self.deserialize_byte_buf(visitor),
-                FieldType::String => self.deserialize_string(visitor),
+                FieldType::String => self.deserialize_str(visitor),
                 FieldType::List => self.deserialize_seq(visitor),
                 FieldType::Compound => {
                     let m = self.deserialize_map(visitor);
                     m
                 }
-                FieldType::IntArray => self.deserialize_seq(visitor),
-                FieldType::LongArray => self.deserialize_seq(visitor),
+                FieldType::IntArray | FieldType::LongArray => self.deserialize_seq(visitor),
             }
         }
     }
@@ -286,7 +536,7 @@ where
         is_ty!(Short, self.next_ty);
 
         let n = match F::AS_ENUM {
-            Variant::BigEndian => self.input.read_i16_be(),
+            Variant::BigEndian | Variant::JavaNetwork => self.input.read_i16_be(),
             Variant::LittleEndian | Variant::Variable => self.input.read_i16_le(),
         }?;
 
@@ -301,7 +551,7 @@ where
         is_ty!(Int, self.next_ty);
 
         let n = match F::AS_ENUM {
-            Variant::BigEndian => self.input.read_i32_be(),
+            Variant::BigEndian | Variant::JavaNetwork => self.input.read_i32_be(),
             Variant::LittleEndian => self.input.read_i32_le(),
             Variant::Variable => self.input.read_var_i32(),
         }?;
@@ -317,7 +567,7 @@ where
         is_ty!(Long, self.next_ty);
 
         let n = match F::AS_ENUM {
-            Variant::BigEndian => self.input.read_i64_be(),
+            Variant::BigEndian | Variant::JavaNetwork => self.input.read_i64_be(),
             Variant::LittleEndian => self.input.read_i64_le(),
             Variant::Variable => self.input.read_var_i64(),
         }?;
@@ -333,7 +583,7 @@ where
         is_ty!(Float, self.next_ty);
 
         let n = match F::AS_ENUM {
-            Variant::BigEndian => self.input.read_f32_be(),
+            Variant::BigEndian | Variant::JavaNetwork => self.input.read_f32_be(),
             _ => self.input.read_f32_le(),
         }?;
 
@@ -348,30 +598,33 @@ where
         is_ty!(Double, self.next_ty);
 
         let n = match F::AS_ENUM {
-            Variant::BigEndian => self.input.read_f64_be(),
+            Variant::BigEndian | Variant::JavaNetwork => self.input.read_f64_be(),
             _ => self.input.read_f64_le(),
         }?;
 
         visitor.visit_f64(n)
     }
 
+    /// Deserialises a string that borrows from the input, if the input is held in memory.
+    ///
+    /// Types such as `&'de str` and `Cow<'de, str>` with `#[serde(borrow)]` therefore do not allocate,
+    /// owned strings are copied out of the input exactly once.
     #[inline]
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, NbtError>
     where
         V: Visitor<'de>,
     {
-        let len = match F::AS_ENUM {
-            Variant::BigEndian => self.input.read_u16_be()? as u32,
-            Variant::LittleEndian => self.input.read_u16_le()? as u32,
-            Variant::Variable => self.input.read_var_u32()?,
-        };
-
-        let data = self.input.take_n(len as usize)?;
-        let str = std::str::from_utf8(data)?;
+        if !self.is_key {
+            is_ty!(String, self.next_ty);
+        }
 
-        // dbg!(str);
+        let len = self.read_str_len()?;
 
-        visitor.visit_str(str)
+        self.account(len as u64)?;
+        match self.input.read_bytes(len as usize)? {
+            Bytes::Borrowed(data) => visitor.visit_borrowed_str(std::str::from_utf8(data)?),
+            Bytes::Transient(data) => visitor.visit_str(std::str::from_utf8(data)?),
+        }
     }
 
     #[inline]
@@ -379,20 +632,7 @@ where
     where
         V: Visitor<'de>,
     {
-        is_ty!(String, self.next_ty);
-
-        let len = match F::AS_ENUM {
-            Variant::BigEndian => self.input.read_u16_be()? as u32,
-            Variant::LittleEndian => self.input.read_u16_le()? as u32,
-            Variant::Variable => self.input.read_var_u32()?,
-        };
-
-        let data = self.input.take_n(len as usize)?;
-        let string = String::from_utf8(data.to_vec())?;
-
-        // dbg!(&string);
-
-        visitor.visit_string(string)
+        self.deserialize_str(visitor)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, NbtError>
@@ -401,14 +641,13 @@ where
     {
         is_ty!(ByteArray, self.next_ty);
 
-        let len = match F::AS_ENUM {
-            Variant::BigEndian => self.input.read_i32_be()? as u32,
-            Variant::LittleEndian => self.input.read_i32_le()? as u32,
-            Variant::Variable => self.input.read_var_i32()? as u32,
-        };
+        let len = self.read_seq_len()?;
 
-        let buf = self.input.take_n(len as usize)?;
-        visitor.visit_bytes(buf)
+        self.account(len as u64)?;
+        match self.input.read_bytes(len as usize)? {
+            Bytes::Borrowed(data) => visitor.visit_borrowed_bytes(data),
+            Bytes::Transient(data) => visitor.visit_bytes(data),
+        }
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, NbtError>
@@ -417,13 +656,10 @@ where
     {
         is_ty!(ByteArray, self.next_ty);
 
-        let len = match F::AS_ENUM {
-            Variant::BigEndian => self.input.read_i32_be()? as u32,
-            Variant::LittleEndian => self.input.read_i32_le()? as u32,
-            Variant::Variable => self.input.read_var_i32()? as u32,
-        };
+        let len = self.read_seq_len()?;
 
-        let buf = self.input.take_n(len as usize)?.to_vec();
+        self.account(len as u64)?;
+        let buf = self.input.read_bytes(len as usize)?.as_slice().to_vec();
         visitor.visit_byte_buf(buf)
     }
 
@@ -452,11 +688,20 @@ where
         bail!(Unsupported, "Deserializing unit structs is not supported")
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, _visitor: V) -> Result<V::Value, NbtError>
+    /// Only [`Value`](crate::Value) is supported, which is told apart from other types by its name.
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, NbtError>
     where
         V: Visitor<'de>,
     {
-        bail!(Unsupported, "Deserializing newtype structs is not supported")
+        if name != VALUE_TOKEN {
+            bail!(Unsupported, "Deserializing newtype structs is not supported")
+        }
+
+        match self.next_ty {
+            FieldType::IntArray if !self.is_key => visitor.visit_map(ArrayDeserializer::new(self, INT_ARRAY_TOKEN)),
+            FieldType::LongArray if !self.is_key => visitor.visit_map(ArrayDeserializer::new(self, LONG_ARRAY_TOKEN)),
+            _ => self.deserialize_any(visitor),
+        }
     }
 
     #[inline]
@@ -479,8 +724,11 @@ where
             _ => FieldType::try_from(self.input.read_u8()?)?,
         };
 
-        let de = SeqDeserializer::new(self, ty, len as u32)?;
-        visitor.visit_seq(de)
+        self.enter()?;
+        let output = SeqDeserializer::new(self, ty, len as u32).map_err(NbtError::from).and_then(|de| visitor.visit_seq(de));
+        self.depth -= 1;
+
+        output
     }
 
     fn deserialize_tuple_struct<V>(self, _name: &'static str, _len: usize, _visitor: V) -> Result<V::Value, NbtError>
@@ -497,8 +745,11 @@ where
     {
         is_ty!(Compound, self.next_ty);
 
-        let de = MapDeserializer::from(self);
-        visitor.visit_map(de)
+        self.enter()?;
+        let output = visitor.visit_map(MapDeserializer::from(&mut *self));
+        self.depth -= 1;
+
+        output
     }
 
     #[inline]
@@ -509,11 +760,17 @@ where
         self.deserialize_any(visitor)
     }
 
-    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], _visitor: V) -> Result<V::Value, NbtError>
+    /// Unit variants are stored as strings containing the variant name.
+    /// Newtype and struct variants are stored as a compound with a single entry named after the variant.
+    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, NbtError>
     where
         V: Visitor<'de>,
     {
-        bail!(Unsupported, "Deserializing enums is not supported")
+        match self.next_ty {
+            FieldType::String => visitor.visit_enum(EnumDeserializer { de: self, compound: false }),
+            FieldType::Compound => visitor.visit_enum(EnumDeserializer { de: self, compound: true }),
+            ty => bail!(Malformed, "Expected string or compound containing an enum variant, but found {ty:?}"),
+        }
     }
 
     #[inline]
@@ -524,12 +781,18 @@ where
         self.deserialize_str(visitor)
     }
 
+    /// Skips over values that are not used, such as unknown fields, without allocating.
     #[inline]
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, NbtError>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        if self.is_key {
+            return self.deserialize_any(visitor)
+        }
+
+        self.skip_value(self.next_ty)?;
+        visitor.visit_unit()
     }
 
     #[inline]
@@ -545,17 +808,19 @@ where
 #[derive(Debug)]
 struct SeqDeserializer<'a, 're, 'de: 'a, F, R>
 where
-    R: BinaryRead<'de>,
+    R: Input<'de>,
     F: VariantImpl,
 {
     de: &'a mut Deserializer<'re, 'de, F, R>,
     ty: FieldType,
+    /// Index of the next element.
+    index: u32,
     remaining: u32,
 }
 
 impl<'de, 're, 'a, F, R> SeqDeserializer<'a, 're, 'de, F, R>
 where
-    R: BinaryRead<'de>,
+    R: Input<'de>,
     F: VariantImpl,
 {
     #[inline]
@@ -565,23 +830,24 @@ where
         // ty is not read in here because the x_array types don't have a type prefix.
 
         de.next_ty = ty;
-        let remaining = match F::AS_ENUM {
-            Variant::BigEndian => de.input.read_i32_be()? as u32,
-            Variant::LittleEndian => de.input.read_i32_le()? as u32,
-            Variant::Variable => de.input.read_var_i32()? as u32,
-        };
+        let remaining = de.read_seq_len()?;
 
         if expected_len != 0 && expected_len != remaining {
             bail!(Malformed, "Expected sequence of length {expected_len}, got length {remaining}");
         }
 
-        Ok(Self { de, ty, remaining })
+        if remaining > de.limits.max_elements {
+            bail!(Malformed, "Sequence of length {remaining} exceeds the maximum of {} elements", de.limits.max_elements);
+        }
+        de.account(remaining as u64 * ty.payload_size())?;
+
+        Ok(Self { de, ty, index: 0, remaining })
     }
 }
 
 impl<'de, 're, 'a, F, R> SeqAccess<'de> for SeqDeserializer<'a, 're, 'de, F, R>
 where
-    R: BinaryRead<'de>,
+    R: Input<'de>,
     F: VariantImpl,
 {
     type Error = NbtError;
@@ -594,7 +860,10 @@ where
         if self.remaining > 0 {
             self.remaining -= 1;
 
-            let output = seed.deserialize(&mut *self.de).map(Some);
+            let index = self.index;
+            self.index += 1;
+
+            let output = seed.deserialize(&mut *self.de).map(Some).map_err(|err| err.with_index(index));
             self.de.next_ty = self.ty;
             output
         } else {
@@ -607,7 +876,7 @@ where
 #[derive(Debug)]
 struct MapDeserializer<'a, 're, 'de: 'a, F, R>
 where
-    R: BinaryRead<'de>,
+    R: Input<'de>,
     F: VariantImpl,
 {
     de: &'a mut Deserializer<'re, 'de, F, R>,
@@ -615,7 +884,7 @@ where
 
 impl<'de, 're, 'a, F, R> From<&'a mut Deserializer<'re, 'de, F, R>> for MapDeserializer<'a, 're, 'de, F, R>
 where
-    R: BinaryRead<'de>,
+    R: Input<'de>,
     F: VariantImpl,
 {
     #[inline]
@@ -626,7 +895,7 @@ where
 
 impl<'de, 're, 'a, F, R> MapAccess<'de> for MapDeserializer<'a, 're, 'de, F, R>
 where
-    R: BinaryRead<'de>,
+    R: Input<'de>,
     F: VariantImpl,
 {
     type Error = NbtError;
@@ -636,20 +905,201 @@ where
     where
         K: DeserializeSeed<'de>,
     {
-        self.de.is_key = true;
-        self.de.next_ty = FieldType::String;
-
         let next_ty = FieldType::try_from(self.de.input.read_u8()?)?;
+        self.de.next_ty = next_ty;
+        if next_ty == FieldType::End {
+            return Ok(None)
+        }
+        self.de.account(next_ty.payload_size())?;
 
-        let r = if next_ty == FieldType::End {
-            Ok(None)
-        } else {
-            seed.deserialize(&mut *self.de).map(Some)
-        };
+        let len = self.de.read_str_len()?;
+        self.de.account(len as u64)?;
+
+        // The key is remembered so that it can be added to the path of errors in the value.
+        let depth = self.de.depth;
+        if self.de.keys.len() <= depth {
+            self.de.keys.resize_with(depth + 1, String::new);
+        }
+        let key = &mut self.de.keys[depth];
+        key.clear();
+
+        match self.de.input.read_bytes(len as usize)? {
+            Bytes::Borrowed(data) => {
+                let data = std::str::from_utf8(data)?;
+                key.push_str(data);
+                seed.deserialize(BorrowedStrDeserializer::new(data)).map(Some)
+            }
+            Bytes::Transient(data) => {
+                key.push_str(std::str::from_utf8(data)?);
+                seed.deserialize(StrDeserializer::new(key)).map(Some)
+            }
+        }
+    }
+
+    #[inline]
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, NbtError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        debug_assert_ne!(self.de.next_ty, FieldType::End, "Cannot serialize end as a map field");
+
+        let depth = self.de.depth;
+        seed.deserialize(&mut *self.de).map_err(|err| err.with_key(self.de.keys.get(depth).map_or("", String::as_str)))
+    }
+}
+
+/// Deserialises enum variants.
+#[derive(Debug)]
+struct EnumDeserializer<'a, 're, 'de: 'a, F, R>
+where
+    R: Input<'de>,
+    F: VariantImpl,
+{
+    de: &'a mut Deserializer<'re, 'de, F, R>,
+    /// Whether the variant is wrapped in a compound, which means it contains data.
+    compound: bool,
+}
+
+impl<'de, 're, 'a, F, R> EnumDeserializer<'a, 're, 'de, F, R>
+where
+    R: Input<'de>,
+    F: VariantImpl,
+{
+    /// Reads the end tag of the compound that wraps the variant.
+    fn end(self) -> Result<(), NbtError> {
+        let ty = FieldType::try_from(self.de.input.read_u8()?)?;
+        if ty != FieldType::End {
+            bail!(Malformed, "Expected a single enum variant in compound, but found another {ty:?} field");
+        }
+
+        Ok(())
+    }
+}
+
+impl<'de, 're, 'a, F, R> de::EnumAccess<'de> for EnumDeserializer<'a, 're, 'de, F, R>
+where
+    R: Input<'de>,
+    F: VariantImpl,
+{
+    type Error = NbtError;
+    type Variant = Self;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, Self), NbtError>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        if !self.compound {
+            let variant = seed.deserialize(&mut *self.de)?;
+            return Ok((variant, self))
+        }
+
+        let next_ty = FieldType::try_from(self.de.input.read_u8()?)?;
+        if next_ty == FieldType::End {
+            bail!(Malformed, "Expected an enum variant, but found an empty compound");
+        }
+        self.de.account(next_ty.payload_size())?;
 
+        self.de.is_key = true;
+        self.de.next_ty = FieldType::String;
+        let variant = seed.deserialize(&mut *self.de);
         self.de.is_key = false;
         self.de.next_ty = next_ty;
-        r
+
+        Ok((variant?, self))
+    }
+}
+
+impl<'de, 're, 'a, F, R> de::VariantAccess<'de> for EnumDeserializer<'a, 're, 'de, F, R>
+where
+    R: Input<'de>,
+    F: VariantImpl,
+{
+    type Error = NbtError;
+
+    fn unit_variant(self) -> Result<(), NbtError> {
+        if self.compound {
+            bail!(Malformed, "Expected unit variant to be a string, but found a compound");
+        }
+
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> Result<S::Value, NbtError>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        if !self.compound {
+            bail!(Malformed, "Expected newtype variant to be a compound, but found a string");
+        }
+
+        let value = seed.deserialize(&mut *self.de)?;
+        self.end()?;
+
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        bail!(Unsupported, "Deserializing tuple variants is not supported")
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        if !self.compound {
+            bail!(Malformed, "Expected struct variant to be a compound, but found a string");
+        }
+
+        let value = de::Deserializer::deserialize_map(&mut *self.de, visitor)?;
+        self.end()?;
+
+        Ok(value)
+    }
+}
+
+/// Presents a typed array as a map with a single entry, whose key is the token of the array type.
+///
+/// Self-describing deserialisation cannot tell typed arrays and lists apart otherwise.
+/// Types that expect a sequence, such as `Vec<i32>`, use [`deserialize_seq`](de::Deserializer::deserialize_seq)
+/// and never see this map.
+#[derive(Debug)]
+struct ArrayDeserializer<'a, 're, 'de: 'a, F, R>
+where
+    R: Input<'de>,
+    F: VariantImpl,
+{
+    de: &'a mut Deserializer<'re, 'de, F, R>,
+    /// Key of the entry, `None` once it has been read.
+    token: Option<&'static str>,
+}
+
+impl<'de, 're, 'a, F, R> ArrayDeserializer<'a, 're, 'de, F, R>
+where
+    R: Input<'de>,
+    F: VariantImpl,
+{
+    #[inline]
+    fn new(de: &'a mut Deserializer<'re, 'de, F, R>, token: &'static str) -> Self {
+        Self { de, token: Some(token) }
+    }
+}
+
+impl<'de, 're, 'a, F, R> MapAccess<'de> for ArrayDeserializer<'a, 're, 'de, F, R>
+where
+    R: Input<'de>,
+    F: VariantImpl,
+{
+    type Error = NbtError;
+
+    #[inline]
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, NbtError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        self.token.take().map(|token| seed.deserialize(token.into_deserializer())).transpose()
     }
 
     #[inline]
@@ -657,7 +1107,34 @@ where
     where
         V: DeserializeSeed<'de>,
     {
-        debug_assert_ne!(self.de.next_ty, FieldType::End, "Cannot serialize end as a map field");
-        seed.deserialize(&mut *self.de)
+        seed.deserialize(ArrayElements(&mut *self.de))
+    }
+}
+
+/// Deserialises the elements of a typed array as a sequence, regardless of the requested type.
+struct ArrayElements<'a, 're, 'de: 'a, F, R>(&'a mut Deserializer<'re, 'de, F, R>)
+where
+    R: Input<'de>,
+    F: VariantImpl;
+
+impl<'de, 're, 'a, F, R> de::Deserializer<'de> for ArrayElements<'a, 're, 'de, F, R>
+where
+    R: Input<'de>,
+    F: VariantImpl + 'a,
+{
+    type Error = NbtError;
+
+    #[inline]
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self.0, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
     }
 }
@@ -0,0 +1,86 @@
+use std::marker::PhantomData;
+
+use serde::Deserialize;
+
+use crate::{Deserializer, Value, VariantImpl};
+
+/// Iterator over consecutive NBT documents stored in a single buffer.
+///
+/// Bedrock stores multiple root compounds back to back in some places, such as the block entities of a chunk.
+/// Every item contains the deserialised document and the amount of bytes it took up.
+/// The iterator ends once the buffer is empty or after the first error.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  let mut buffer = Vec::new();
+///  nbt::to_le_bytes_in(&mut buffer, &nbt::nbt!({ "id": "Chest" })).unwrap();
+///  nbt::to_le_bytes_in(&mut buffer, &nbt::nbt!({ "id": "Furnace" })).unwrap();
+///
+///  let ids = nbt::Documents::<nbt::LittleEndian>::new(&buffer)
+///     .map(|document| document.map(|(value, _)| value["id"].clone()))
+///     .collect::<anyhow::Result<Vec<_>>>()
+///     .unwrap();
+///
+///  assert_eq!(ids, [nbt::nbt!("Chest"), nbt::nbt!("Furnace")]);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Documents<'de, F, T = Value>
+where
+    F: VariantImpl,
+{
+    /// Remaining data that has not been deserialised yet.
+    data: &'de [u8],
+    _marker: PhantomData<(F, T)>,
+}
+
+impl<'de, F, T> Documents<'de, F, T>
+where
+    F: VariantImpl,
+{
+    /// Creates an iterator over the documents in the buffer.
+    #[inline]
+    pub const fn new(data: &'de [u8]) -> Self {
+        Self { data, _marker: PhantomData }
+    }
+
+    /// Returns the data that has not been deserialised yet.
+    #[inline]
+    pub const fn remaining(&self) -> &'de [u8] {
+        self.data
+    }
+}
+
+impl<'de, F, T> Iterator for Documents<'de, F, T>
+where
+    F: VariantImpl + 'de,
+    T: Deserialize<'de>,
+{
+    type Item = anyhow::Result<(T, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None
+        }
+
+        let mut input = self.data;
+        let result = Deserializer::<F, _>::new(&mut input).and_then(|mut de| Ok(T::deserialize(&mut de)?));
+
+        // Documents cannot be found after a malformed one, so the iterator ends.
+        let n = self.data.len() - input.len();
+        self.data = if result.is_ok() { input } else { &[] };
+
+        Some(result.map(|document| (document, n)))
+    }
+}
+
+impl<'de, F, T> std::iter::FusedIterator for Documents<'de, F, T>
+where
+    F: VariantImpl + 'de,
+    T: Deserialize<'de>,
+{
+}
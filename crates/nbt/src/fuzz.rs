@@ -0,0 +1,146 @@
+//! Support for fuzzing the serialiser and deserialiser.
+
+use anyhow::bail;
+use arbitrary::{Arbitrary, Unstructured};
+use serde::{Deserialize, Serialize};
+use util::RVec;
+
+use crate::{BigEndian, Compound, Deserializer, FieldType, JavaNetwork, LittleEndian, Serializer, Value, Variable, VariantImpl};
+
+/// Maximum amount of lists and compounds that generated values nest in each other.
+const MAX_DEPTH: usize = 16;
+
+/// Types of values that do not contain other values.
+const SCALAR_TYPES: [FieldType; 10] = [
+    FieldType::Byte,
+    FieldType::Short,
+    FieldType::Int,
+    FieldType::Long,
+    FieldType::Float,
+    FieldType::Double,
+    FieldType::ByteArray,
+    FieldType::String,
+    FieldType::IntArray,
+    FieldType::LongArray,
+];
+
+/// Generates values that can always be serialised.
+///
+/// Every element of a list has the same type, strings fit in the length prefix of every variant
+/// and lists and compounds are nested at most 16 levels deep.
+impl<'a> Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let ty = arbitrary_type(u, 0)?;
+        arbitrary_value(u, ty, 0)
+    }
+}
+
+/// Picks the type of a value at the given depth.
+fn arbitrary_type(u: &mut Unstructured<'_>, depth: usize) -> arbitrary::Result<FieldType> {
+    if depth < MAX_DEPTH && u.ratio(1, 4)? {
+        Ok(*u.choose(&[FieldType::List, FieldType::Compound])?)
+    } else {
+        Ok(*u.choose(&SCALAR_TYPES)?)
+    }
+}
+
+/// Generates a string that fits in a 16-bit length prefix.
+fn arbitrary_string(u: &mut Unstructured<'_>) -> arbitrary::Result<String> {
+    let mut string = String::arbitrary(u)?;
+    if string.len() > u16::MAX as usize {
+        let end = (0..=u16::MAX as usize).rev().find(|&i| string.is_char_boundary(i)).unwrap_or(0);
+        string.truncate(end);
+    }
+    Ok(string)
+}
+
+/// Generates a value of the given type.
+fn arbitrary_value(u: &mut Unstructured<'_>, ty: FieldType, depth: usize) -> arbitrary::Result<Value> {
+    Ok(match ty {
+        FieldType::Byte => Value::Byte(u.arbitrary()?),
+        FieldType::Short => Value::Short(u.arbitrary()?),
+        FieldType::Int => Value::Int(u.arbitrary()?),
+        FieldType::Long => Value::Long(u.arbitrary()?),
+        FieldType::Float => Value::Float(u.arbitrary()?),
+        FieldType::Double => Value::Double(u.arbitrary()?),
+        FieldType::ByteArray => Value::ByteArray(RVec::from(Vec::<u8>::arbitrary(u)?)),
+        FieldType::String => Value::String(arbitrary_string(u)?),
+        FieldType::IntArray => Value::IntArray(u.arbitrary()?),
+        FieldType::LongArray => Value::LongArray(u.arbitrary()?),
+        FieldType::List => {
+            let ty = arbitrary_type(u, depth + 1)?;
+            let len = u.arbitrary_len::<u8>()?;
+            Value::List((0..len).map(|_| arbitrary_value(u, ty, depth + 1)).collect::<arbitrary::Result<_>>()?)
+        }
+        FieldType::Compound => Value::Compound(arbitrary_compound(u, depth)?),
+        FieldType::End => unreachable!("End is never generated"),
+    })
+}
+
+/// Generates the entries of a compound at the given depth.
+fn arbitrary_compound(u: &mut Unstructured<'_>, depth: usize) -> arbitrary::Result<Compound> {
+    let len = u.arbitrary_len::<(u8, u8)>()?;
+
+    let mut map = Compound::with_capacity(len);
+    for _ in 0..len {
+        let key = arbitrary_string(u)?;
+        let ty = arbitrary_type(u, depth + 1)?;
+        map.insert(key, arbitrary_value(u, ty, depth + 1)?);
+    }
+
+    Ok(map)
+}
+
+/// Serialises and deserialises a value with every variant and checks that nothing changed.
+///
+/// NBT always has a compound at the root, so any other value is wrapped in a compound first.
+/// Values are compared in [canonical form](crate::Canonical), which means that NaN is equal to itself.
+///
+/// This is meant to be called from fuzz targets:
+///
+/// ```ignore
+/// libfuzzer_sys::fuzz_target!(|value: nbt::Value| {
+///     nbt::check_round_trip(&value).unwrap();
+/// });
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if any variant fails to serialise or deserialise the value, does not read all data back,
+/// or produces a different value.
+pub fn check_round_trip(value: &Value) -> anyhow::Result<()> {
+    let wrapped;
+    let value = if value.is_compound() {
+        value
+    } else {
+        wrapped = Value::Compound(Compound::from_iter([(String::new(), value.clone())]));
+        &wrapped
+    };
+
+    round_trip::<LittleEndian>(value)?;
+    round_trip::<BigEndian>(value)?;
+    round_trip::<Variable>(value)?;
+    round_trip::<JavaNetwork>(value)
+}
+
+/// Checks the round trip of a value with a single variant.
+fn round_trip<F>(value: &Value) -> anyhow::Result<()>
+where
+    F: VariantImpl,
+{
+    let mut ser = Serializer::<_, F>::new(RVec::alloc());
+    value.serialize(&mut ser)?;
+    let encoded = ser.into_inner();
+
+    let mut input = encoded.as_slice();
+    let decoded = Value::deserialize(&mut Deserializer::<F, _>::new(&mut input)?)?;
+
+    if !input.is_empty() {
+        bail!("{:?} deserialiser left {} of {} bytes unread", F::AS_ENUM, input.len(), encoded.len());
+    }
+    if !value.canonical_eq(&decoded) {
+        bail!("{:?} round trip changed {value:?} into {decoded:?}", F::AS_ENUM);
+    }
+
+    Ok(())
+}
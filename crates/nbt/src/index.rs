@@ -0,0 +1,164 @@
+use std::ops;
+
+use crate::Value;
+
+/// Type that can be used to index into a [`Value`].
+///
+/// Strings index into compounds and integers index into lists. This trait is sealed and
+/// implemented for [`usize`], [`str`], [`String`] and references to these types.
+pub trait ValueIndex: private::Sealed {
+    /// Returns the value at this index, if it exists.
+    #[doc(hidden)]
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value>;
+
+    /// Returns the value at this index mutably, if it exists.
+    #[doc(hidden)]
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value>;
+}
+
+impl ValueIndex for usize {
+    #[inline]
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        match value {
+            Value::List(list) => list.get(*self),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        match value {
+            Value::List(list) => list.get_mut(*self),
+            _ => None,
+        }
+    }
+}
+
+impl ValueIndex for str {
+    #[inline]
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        match value {
+            Value::Compound(map) => map.get(self),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        match value {
+            Value::Compound(map) => map.get_mut(self),
+            _ => None,
+        }
+    }
+}
+
+impl ValueIndex for String {
+    #[inline]
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        self.as_str().index_into(value)
+    }
+
+    #[inline]
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        self.as_str().index_into_mut(value)
+    }
+}
+
+impl<T> ValueIndex for &T
+where
+    T: ?Sized + ValueIndex,
+{
+    #[inline]
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        (**self).index_into(value)
+    }
+
+    #[inline]
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        (**self).index_into_mut(value)
+    }
+}
+
+impl Value {
+    /// Returns the entry of a compound or the element of a list at the given index.
+    ///
+    /// Returns `None` if this value is not a compound or list, or if the index does not exist.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use mirai_nbt as nbt;
+    /// #
+    /// # fn main() {
+    ///  let value = nbt::nbt!({ "Palette": [{ "Name": "minecraft:air" }, { "Name": "minecraft:stone" }] });
+    ///
+    ///  assert_eq!(value.get("Palette").and_then(|p| p.get(1)), Some(&nbt::nbt!({ "Name": "minecraft:stone" })));
+    ///  assert_eq!(value["Palette"][0]["Name"].as_str(), Some("minecraft:air"));
+    ///  assert_eq!(value.get("Missing"), None);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn get<I>(&self, index: I) -> Option<&Value>
+    where
+        I: ValueIndex,
+    {
+        index.index_into(self)
+    }
+
+    /// Returns the entry of a compound or the element of a list at the given index mutably.
+    ///
+    /// See [`get`](Value::get) for more information.
+    #[inline]
+    pub fn get_mut<I>(&mut self, index: I) -> Option<&mut Value>
+    where
+        I: ValueIndex,
+    {
+        index.index_into_mut(self)
+    }
+}
+
+impl<I> ops::Index<I> for Value
+where
+    I: ValueIndex,
+{
+    type Output = Value;
+
+    /// Indexes into a compound or list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this value is not a compound or list, or if the index does not exist.
+    /// Use [`get`](Value::get) for a non-panicking alternative.
+    #[inline]
+    fn index(&self, index: I) -> &Value {
+        let Some(value) = index.index_into(self) else { panic!("NBT value does not contain the given index") };
+        value
+    }
+}
+
+impl<I> ops::IndexMut<I> for Value
+where
+    I: ValueIndex,
+{
+    /// Indexes mutably into a compound or list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this value is not a compound or list, or if the index does not exist.
+    /// Use [`get_mut`](Value::get_mut) for a non-panicking alternative.
+    #[inline]
+    fn index_mut(&mut self, index: I) -> &mut Value {
+        let Some(value) = index.index_into_mut(self) else { panic!("NBT value does not contain the given index") };
+        value
+    }
+}
+
+mod private {
+    /// Prevents [`ValueIndex`](super::ValueIndex) from being implemented for types outside of this crate.
+    pub trait Sealed {}
+
+    impl Sealed for usize {}
+    impl Sealed for str {}
+    impl Sealed for String {}
+    impl<T> Sealed for &T where T: ?Sized + Sealed {}
+}
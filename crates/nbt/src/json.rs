@@ -0,0 +1,171 @@
+//! Conversion between NBT and JSON.
+
+use anyhow::{anyhow, bail};
+use serde_json::{Map, Number};
+
+use crate::{snbt, Compound, Value};
+
+/// Controls how JSON is converted to NBT by [`from_json`].
+///
+/// By default no hints are applied.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct JsonHints {
+    /// Parses strings containing a number with an SNBT type suffix, such as `"1b"`, `"2s"`, `"3L"`, `"1.5f"` or `"2.5d"`,
+    /// as a number of that type. Any other string remains a string.
+    pub suffixes: bool,
+    /// Converts decimal numbers to floats instead of doubles.
+    pub floats: bool,
+    /// Converts non-empty arrays that only contain integers to int arrays,
+    /// or long arrays if any of the elements does not fit in an int.
+    pub typed_arrays: bool,
+}
+
+/// Converts an NBT value to JSON.
+///
+/// JSON has a single number type, so the exact type of numbers is lost:
+///
+/// | NBT                       | JSON                            |
+/// |---------------------------|---------------------------------|
+/// | byte, short, int and long | integer                         |
+/// | float and double          | number, or `null` if not finite |
+/// | byte, int and long arrays | array of integers               |
+/// | string, list and compound | string, array and object        |
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  let value = nbt::nbt!({ "name": "minecraft:stone", "count": 64i8 });
+///  let json = nbt::to_json(&value);
+///
+///  assert_eq!(json, serde_json::json!({ "name": "minecraft:stone", "count": 64 }));
+/// # }
+/// ```
+pub fn to_json(value: &Value) -> serde_json::Value {
+    use serde_json::Value as Json;
+
+    match value {
+        Value::Byte(v) => Json::from(*v),
+        Value::Short(v) => Json::from(*v),
+        Value::Int(v) => Json::from(*v),
+        Value::Long(v) => Json::from(*v),
+        Value::Float(v) => Number::from_f64(*v as f64).map_or(Json::Null, Json::Number),
+        Value::Double(v) => Number::from_f64(*v).map_or(Json::Null, Json::Number),
+        Value::ByteArray(v) => v.iter().map(|b| Json::from(*b as i8)).collect(),
+        Value::IntArray(v) => v.iter().copied().map(Json::from).collect(),
+        Value::LongArray(v) => v.iter().copied().map(Json::from).collect(),
+        Value::String(v) => Json::String(v.clone()),
+        Value::List(v) => v.iter().map(to_json).collect(),
+        Value::Compound(v) => Json::Object(v.iter().map(|(k, v)| (k.clone(), to_json(v))).collect::<Map<_, _>>()),
+    }
+}
+
+/// Converts JSON to an NBT value, using the given hints to pick the types of values.
+///
+/// Without hints, integers become ints, or longs if they do not fit in an int, decimal numbers become doubles
+/// and booleans become bytes. Lists in NBT contain a single type, so numbers in an array are widened to the
+/// largest type in the array. Converting NBT to JSON and back is therefore only lossless for ints, doubles,
+/// strings and compounds and lists of these. Suffixed strings, see [`JsonHints::suffixes`], preserve the type of any number.
+///
+/// # Errors
+///
+/// Returns an error if the JSON contains `null`, an integer that does not fit in a long,
+/// or an array whose elements cannot be converted to a single type.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  let json = serde_json::json!({ "name": "minecraft:stone", "count": "64b", "heights": [1, 2] });
+///  let hints = nbt::JsonHints { suffixes: true, typed_arrays: true, ..Default::default() };
+///
+///  let value = nbt::from_json(&json, hints).unwrap();
+///  assert_eq!(value, nbt::nbt!({ "name": "minecraft:stone", "count": 64i8, "heights": vec![1, 2] }));
+/// # }
+/// ```
+pub fn from_json(json: &serde_json::Value, hints: JsonHints) -> anyhow::Result<Value> {
+    use serde_json::Value as Json;
+
+    Ok(match json {
+        Json::Null => bail!("NBT has no equivalent of `null`"),
+        Json::Bool(v) => Value::Byte(*v as i8),
+        Json::Number(v) => from_number(v, hints)?,
+        Json::String(v) if hints.suffixes => snbt::parse_suffixed(v).unwrap_or_else(|| Value::String(v.clone())),
+        Json::String(v) => Value::String(v.clone()),
+        Json::Array(v) => {
+            let list = v.iter().map(|v| from_json(v, hints)).collect::<anyhow::Result<Vec<_>>>()?;
+            from_list(list, hints)?
+        }
+        Json::Object(v) => Value::Compound(
+            v.iter()
+                .map(|(k, v)| Ok((k.clone(), from_json(v, hints)?)))
+                .collect::<anyhow::Result<Compound>>()?,
+        ),
+    })
+}
+
+/// Converts a JSON number to an int, long, float or double.
+fn from_number(number: &Number, hints: JsonHints) -> anyhow::Result<Value> {
+    if let Some(v) = number.as_i64() {
+        return Ok(i32::try_from(v).map_or(Value::Long(v), Value::Int))
+    }
+    if number.is_u64() {
+        bail!("Integer {number} does not fit in a long");
+    }
+
+    let v = number.as_f64().ok_or_else(|| anyhow!("Number {number} is not supported"))?;
+    Ok(if hints.floats { Value::Float(v as f32) } else { Value::Double(v) })
+}
+
+/// Makes sure all elements of a list have the same type, widening numbers where needed.
+fn from_list(mut list: Vec<Value>, hints: JsonHints) -> anyhow::Result<Value> {
+    // Ranks the number types by size, any other type cannot be widened.
+    let rank = |v: &Value| match v {
+        Value::Byte(_) => Some(0),
+        Value::Short(_) => Some(1),
+        Value::Int(_) => Some(2),
+        Value::Long(_) => Some(3),
+        Value::Float(_) => Some(4),
+        Value::Double(_) => Some(5),
+        _ => None,
+    };
+
+    let Some(first) = list.first() else { return Ok(Value::List(list)) };
+    if list.iter().all(|v| std::mem::discriminant(v) == std::mem::discriminant(first)) {
+        return Ok(match first {
+            Value::Int(_) if hints.typed_arrays => Value::IntArray(list.iter().filter_map(Value::as_i32).collect()),
+            Value::Long(_) if hints.typed_arrays => Value::LongArray(list.iter().filter_map(Value::as_i64).collect()),
+            _ => Value::List(list),
+        })
+    }
+
+    let Some(widest) = list.iter().map(rank).collect::<Option<Vec<_>>>().and_then(|ranks| ranks.into_iter().max()) else {
+        bail!("NBT lists cannot contain elements of different types");
+    };
+
+    for element in &mut list {
+        let (int, float) = match *element {
+            Value::Byte(v) => (v as i64, v as f64),
+            Value::Short(v) => (v as i64, v as f64),
+            Value::Int(v) => (v as i64, v as f64),
+            Value::Long(v) => (v, v as f64),
+            Value::Float(v) => (0, v as f64),
+            Value::Double(v) => (0, v),
+            _ => unreachable!("non-numeric elements were rejected"),
+        };
+
+        *element = match widest {
+            1 => Value::Short(int as i16),
+            2 => Value::Int(int as i32),
+            3 => Value::Long(int),
+            4 => Value::Float(float as f32),
+            _ => Value::Double(float),
+        };
+    }
+
+    from_list(list, hints)
+}
@@ -0,0 +1,75 @@
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use util::{BinaryRead, BinaryWrite, RVec};
+
+use crate::{from_le_bytes, to_le_bytes_in};
+
+/// Size of the header in front of the NBT data in a `level.dat` file.
+const HEADER_SIZE: usize = 8;
+
+/// Reads a Bedrock `level.dat` file.
+///
+/// The file starts with a header containing the storage version and the size of the NBT data,
+/// both as little endian integers. The data itself is stored in the little endian NBT format.
+/// The reader should contain the entire file.
+///
+/// On success, the storage version and the deserialised settings are returned.
+///
+/// # Errors
+///
+/// Returns an error if the size in the header does not match the size of the remaining data,
+/// or if the data cannot be deserialised into `T`.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  let file = nbt::write_level_dat(10, &nbt::nbt!({ "LevelName": "Bedrock level" })).unwrap();
+///  let (version, settings): (u32, nbt::Value) = nbt::read_level_dat(&mut file.as_slice()).unwrap();
+///
+///  assert_eq!(version, 10);
+///  assert_eq!(settings["LevelName"].as_str(), Some("Bedrock level"));
+/// # }
+/// ```
+pub fn read_level_dat<'de, T, R>(reader: &mut R) -> anyhow::Result<(u32, T)>
+where
+    R: BinaryRead<'de>,
+    T: Deserialize<'de>,
+{
+    let version = reader.read_u32_le()?;
+    let size = reader.read_u32_le()?;
+
+    let remaining = reader.remaining();
+    if remaining != size as usize {
+        bail!("Invalid `level.dat` file: header specified length of {size} bytes, but found {remaining}");
+    }
+
+    let (settings, _) = from_le_bytes(reader)?;
+    Ok((version, settings))
+}
+
+/// Writes a Bedrock `level.dat` file with the given storage version.
+///
+/// See [`read_level_dat`] for the format of the file.
+///
+/// # Errors
+///
+/// Returns an error if the value cannot be serialised or is larger than 4 GiB.
+pub fn write_level_dat<T>(version: u32, value: &T) -> anyhow::Result<RVec>
+where
+    T: ?Sized + Serialize,
+{
+    let mut buffer = RVec::alloc();
+    buffer.write_u32_le(version)?;
+    // The size is filled in once the data has been written.
+    buffer.write_u32_le(0)?;
+
+    to_le_bytes_in(&mut *buffer, value)?;
+
+    let size = u32::try_from(buffer.len() - HEADER_SIZE)?;
+    buffer[4..HEADER_SIZE].copy_from_slice(&size.to_le_bytes());
+
+    Ok(buffer)
+}
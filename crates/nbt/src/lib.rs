@@ -39,24 +39,58 @@
     clippy::unused_self,
     clippy::unused_async
 )]
+#![cfg_attr(feature = "forbid-unsafe", forbid(unsafe_code))]
 #![allow(dead_code)]
 #![allow(clippy::use_self)]
 
-pub use crate::de::{from_be_bytes, from_le_bytes, from_var_bytes, Deserializer};
-pub use crate::ser::{to_be_bytes, to_be_bytes_in, to_le_bytes, to_le_bytes_in, to_var_bytes, to_var_bytes_in, Serializer};
-pub use crate::value::Value;
+pub use crate::de::{deserialize_bool, from_be_bytes, from_java_net_bytes, from_le_bytes, from_var_bytes, Deserializer, Limits};
+pub use crate::ser::{
+    serialized_size, to_be_bytes, to_be_bytes_in, to_java_net_bytes, to_java_net_bytes_in, to_le_bytes, to_le_bytes_in, to_var_bytes,
+    to_var_bytes_in, Serializer,
+};
+pub use crate::canonical::{compound_eq, compound_hash, Canonical};
+pub use crate::compress::{from_gzip_reader, from_zlib_reader, to_gzip_writer, to_zlib_writer};
+pub use crate::documents::Documents;
+#[cfg(feature = "arbitrary")]
+pub use crate::fuzz::check_round_trip;
+pub use crate::index::ValueIndex;
+pub use crate::level_dat::{read_level_dat, write_level_dat};
+pub use crate::merge::{ListMerge, Patch};
+#[cfg(feature = "json")]
+pub use crate::json::{from_json, to_json, JsonHints};
+pub use crate::schema::{Field, Schema, SchemaError, SchemaErrorKind};
+pub use crate::snbt::from_snbt;
+pub use crate::stream::{from_chain, from_reader, to_writer, Bytes, Chain, Input};
+pub use crate::value::{Compound, Value};
+pub use crate::value_de::from_value;
 use anyhow::anyhow;
+use macros::try_from_repr;
 use std::fmt::{Debug, Display, Formatter};
 
 #[cfg(test)]
 mod test;
 
+mod canonical;
+mod compress;
 mod de;
+mod documents;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+mod index;
+#[cfg(feature = "json")]
+mod json;
+mod level_dat;
+mod merge;
+mod path;
+mod schema;
 mod ser;
+mod snbt;
+mod stream;
 mod value;
+mod value_de;
 
 mod private {
-    use crate::{BigEndian, LittleEndian, Variable};
+    use crate::{BigEndian, JavaNetwork, LittleEndian, Variable};
 
     /// Prevents [`VariantImpl`](super::VariantImpl) from being implemented for
     /// types outside of this crate.
@@ -65,6 +99,7 @@ mod private {
     impl Sealed for LittleEndian {}
     impl Sealed for BigEndian {}
     impl Sealed for Variable {}
+    impl Sealed for JavaNetwork {}
 }
 
 /// Implemented by all NBT variants.
@@ -89,6 +124,9 @@ pub enum Variant {
     /// (such as for strings or lists), are varints instead of shorts.
     /// The integer and long types are also varints.
     Variable,
+    /// Used by Java for NBT transferred over the network since 1.20.2.
+    /// This format is the same as [`BigEndian`], except that the root tag has no name.
+    JavaNetwork,
 }
 
 /// Used by Bedrock for data saved to disk.
@@ -117,11 +155,34 @@ impl VariantImpl for Variable {
     const AS_ENUM: Variant = Variant::Variable;
 }
 
+/// Used by Java for NBT transferred over the network since 1.20.2.
+/// This format is the same as [`BigEndian`], except that the root tag has no name.
+pub enum JavaNetwork {}
+
+impl VariantImpl for JavaNetwork {
+    const AS_ENUM: Variant = Variant::JavaNetwork;
+}
+
+/// Name of the newtype struct that marks a sequence as an int array.
+///
+/// Serde has no notion of typed arrays. Non-empty sequences of ints are detected by the serialiser,
+/// but an empty sequence has no elements to inspect, so arrays that should always be encoded as an int array
+/// are wrapped in a newtype struct with this name. When a [`Value`] is deserialised, int arrays are presented as a map
+/// with this name as its single key, so that they can be told apart from lists.
+const INT_ARRAY_TOKEN: &str = "$mirai_nbt::IntArray";
+/// Name of the newtype struct that marks a sequence as a long array, see [`INT_ARRAY_TOKEN`].
+const LONG_ARRAY_TOKEN: &str = "$mirai_nbt::LongArray";
+/// Name of the newtype struct that [`Value`] requests when it is deserialised.
+///
+/// Typed arrays are only presented as maps, see [`INT_ARRAY_TOKEN`], when this name is requested.
+/// Any other self-describing type, such as the buffer serde uses for flattened structs and internally tagged enums,
+/// receives them as plain sequences so that they can be deserialised into `Vec<i32>` and `Vec<i64>`.
+const VALUE_TOKEN: &str = "$mirai_nbt::Value";
+
 /// NBT field type
-// Compiler complains about unused enum variants even though they're constructed using a transmute.
-#[allow(dead_code)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
+#[try_from_repr]
 enum FieldType {
     /// Indicates the end of a compound tag.
     End = 0,
@@ -152,60 +213,112 @@ enum FieldType {
     LongArray = 12,
 }
 
-impl TryFrom<u8> for FieldType {
-    type Error = anyhow::Error;
-
-    fn try_from(v: u8) -> anyhow::Result<Self> {
-        const LAST_DISC: u8 = FieldType::LongArray as u8;
-        if v > LAST_DISC {
-            util::bail!(Other, "NBT field type discriminant out of range");
+impl FieldType {
+    /// Size in bytes of the value of this type, excluding any contents of variable length.
+    const fn payload_size(self) -> u64 {
+        match self {
+            Self::Byte => 1,
+            Self::Short => 2,
+            Self::Int | Self::Float => 4,
+            Self::Long | Self::Double => 8,
+            _ => 0,
         }
-
-        // SAFETY: Because `Self` is marked as `repr(u8)`, its layout is guaranteed to start
-        // with a `u8` discriminant as its first field. Additionally, the raw discriminant is verified
-        // to be in the enum's range.
-        Ok(unsafe { std::mem::transmute::<u8, FieldType>(v) })
     }
 }
 
+/// Single step of the path in an [`NbtError`].
+#[derive(Debug)]
+enum PathStep {
+    /// Entry of a compound.
+    Key(String),
+    /// Element of a list.
+    Index(u32),
+}
+
 /// An error that occurs in NBT serialisation or deserialisations
+///
+/// Errors that occur while deserialising a nested value contain the path to that value, see [`path`](NbtError::path).
 #[derive(Debug)]
-#[repr(transparent)]
-pub struct NbtError(anyhow::Error);
+pub struct NbtError {
+    error: anyhow::Error,
+    /// Steps of the path to the value that caused the error, starting with the innermost one.
+    path: Vec<PathStep>,
+}
+
+impl NbtError {
+    /// Returns the path to the value that caused this error, such as `Level.Sections[2].Palette[7].Name`.
+    ///
+    /// The path uses the syntax of [`Value::get_path`] and is empty if the error did not occur inside a compound or list.
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for step in self.path.iter().rev() {
+            match step {
+                PathStep::Key(key) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(key);
+                }
+                PathStep::Index(index) => {
+                    path.push('[');
+                    path.push_str(&index.to_string());
+                    path.push(']');
+                }
+            }
+        }
+        path
+    }
+
+    /// Adds the compound entry that contains the current path.
+    fn with_key(mut self, key: &str) -> Self {
+        self.path.push(PathStep::Key(key.to_owned()));
+        self
+    }
+
+    /// Adds the list element that contains the current path.
+    fn with_index(mut self, index: u32) -> Self {
+        self.path.push(PathStep::Index(index));
+        self
+    }
+}
 
 impl From<anyhow::Error> for NbtError {
     fn from(value: anyhow::Error) -> Self {
-        Self(value)
+        Self { error: value, path: Vec::new() }
     }
 }
 
 impl From<util::Error> for NbtError {
     fn from(value: util::Error) -> Self {
-        Self(value.into())
+        anyhow::Error::from(value).into()
     }
 }
 
 impl From<std::io::Error> for NbtError {
     fn from(value: std::io::Error) -> Self {
-        Self(value.into())
+        anyhow::Error::from(value).into()
     }
 }
 
 impl From<std::string::FromUtf8Error> for NbtError {
     fn from(value: std::string::FromUtf8Error) -> Self {
-        Self(value.into())
+        anyhow::Error::from(value).into()
     }
 }
 
 impl From<std::str::Utf8Error> for NbtError {
     fn from(value: std::str::Utf8Error) -> Self {
-        Self(value.into())
+        anyhow::Error::from(value).into()
     }
 }
 
 impl Display for NbtError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Display::fmt(&self.0, f)
+        std::fmt::Display::fmt(&self.error, f)?;
+        if !self.path.is_empty() {
+            write!(f, " at `{}`", self.path())?;
+        }
+        Ok(())
     }
 }
 
@@ -216,7 +329,7 @@ impl serde::de::Error for NbtError {
     where
         T: Display,
     {
-        Self(anyhow!(msg.to_string()))
+        anyhow!(msg.to_string()).into()
     }
 }
 
@@ -225,6 +338,6 @@ impl serde::ser::Error for NbtError {
     where
         T: Display,
     {
-        Self(anyhow!(msg.to_string()))
+        anyhow!(msg.to_string()).into()
     }
 }
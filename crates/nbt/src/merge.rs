@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail};
+
+use crate::{Compound, Value};
+
+/// Determines how lists are combined by [`Value::merge_with`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ListMerge {
+    /// The list is replaced by the other list.
+    #[default]
+    Replace,
+    /// The elements of the other list are appended to the list.
+    Append,
+    /// Elements at the same index are merged and any additional elements of the other list are appended.
+    ByIndex,
+}
+
+/// Set of changes that turns one [`Value`] into another.
+///
+/// A patch is created with [`Value::diff`] and applied with [`Value::apply`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Patch {
+    /// Replaces the entire value.
+    Replace(Value),
+    /// Changes the entries of a compound.
+    ///
+    /// Every entry is either patched or, if it is `None`, removed.
+    /// Entries that do not exist yet are created by a [`Patch::Replace`].
+    Compound(HashMap<String, Option<Patch>>),
+}
+
+impl Value {
+    /// Merges another value into this one.
+    ///
+    /// Entries of compounds are merged recursively, while lists and any other values are replaced.
+    /// See [`merge_with`](Value::merge_with) to combine lists differently.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use mirai_nbt as nbt;
+    /// #
+    /// # fn main() {
+    ///  let mut block = nbt::nbt!({ "name": "minecraft:log", "states": { "pillar_axis": "y", "old_log_type": "oak" } });
+    ///  block.merge(&nbt::nbt!({ "states": { "pillar_axis": "x" } }));
+    ///
+    ///  assert_eq!(block, nbt::nbt!({ "name": "minecraft:log", "states": { "pillar_axis": "x", "old_log_type": "oak" } }));
+    /// # }
+    /// ```
+    #[inline]
+    pub fn merge(&mut self, other: &Value) {
+        self.merge_with(other, ListMerge::default());
+    }
+
+    /// Merges another value into this one, combining lists with the given strategy.
+    ///
+    /// Entries of compounds are merged recursively. Values of different types are replaced.
+    pub fn merge_with(&mut self, other: &Value, lists: ListMerge) {
+        match (self, other) {
+            (Value::Compound(map), Value::Compound(other)) => {
+                for (key, value) in other {
+                    match map.get_mut(key) {
+                        Some(entry) => entry.merge_with(value, lists),
+                        None => {
+                            map.insert(key.clone(), value.clone());
+                        }
+                    }
+                }
+            }
+            (Value::List(list), Value::List(other)) if lists != ListMerge::Replace => {
+                let merged = if lists == ListMerge::ByIndex { list.len().min(other.len()) } else { 0 };
+                for (element, value) in list.iter_mut().zip(&other[..merged]) {
+                    element.merge_with(value, lists);
+                }
+                list.extend_from_slice(&other[merged..]);
+            }
+            (this, other) => *this = other.clone(),
+        }
+    }
+
+    /// Computes the patch that turns this value into `other`.
+    ///
+    /// Returns `None` if both values are equal.
+    /// Compounds are compared entry by entry, any other value is replaced as a whole.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use mirai_nbt as nbt;
+    /// #
+    /// # fn main() {
+    ///  let mut old = nbt::nbt!({ "name": "minecraft:stone", "version": 17_959_425 });
+    ///  let new = nbt::nbt!({ "name": "minecraft:granite", "version": 17_959_425 });
+    ///
+    ///  let patch = old.diff(&new).unwrap();
+    ///  old.apply(&patch).unwrap();
+    ///
+    ///  assert_eq!(old, new);
+    /// # }
+    /// ```
+    pub fn diff(&self, other: &Value) -> Option<Patch> {
+        let (Value::Compound(map), Value::Compound(other)) = (self, other) else {
+            return (self != other).then(|| Patch::Replace(other.clone()))
+        };
+
+        let mut entries = HashMap::new();
+        for (key, value) in map {
+            match other.get(key) {
+                Some(new) => {
+                    if let Some(patch) = value.diff(new) {
+                        entries.insert(key.clone(), Some(patch));
+                    }
+                }
+                None => {
+                    entries.insert(key.clone(), None);
+                }
+            }
+        }
+        for (key, value) in other {
+            if !map.contains_key(key) {
+                entries.insert(key.clone(), Some(Patch::Replace(value.clone())));
+            }
+        }
+
+        (!entries.is_empty()).then_some(Patch::Compound(entries))
+    }
+
+    /// Applies a patch created by [`diff`](Value::diff) to this value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the patch changes the entries of a compound that is not a compound in this value.
+    /// The value may have been partially patched in that case.
+    pub fn apply(&mut self, patch: &Patch) -> anyhow::Result<()> {
+        let entries = match patch {
+            Patch::Replace(value) => {
+                *self = value.clone();
+                return Ok(())
+            }
+            Patch::Compound(entries) => entries,
+        };
+
+        let Value::Compound(map) = self else {
+            bail!("Cannot apply compound patch to a value that is not a compound");
+        };
+
+        for (key, patch) in entries {
+            match patch {
+                Some(Patch::Replace(value)) => {
+                    map.insert(key.clone(), value.clone());
+                }
+                Some(patch) => map
+                    .get_mut(key)
+                    .ok_or_else(|| anyhow!("Cannot patch missing compound entry `{key}`"))?
+                    .apply(patch)?,
+                None => remove_entry(map, key),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes an entry from a compound without changing the order of the other entries.
+#[inline]
+fn remove_entry(map: &mut Compound, key: &str) {
+    #[cfg(feature = "preserve-order")]
+    map.shift_remove(key);
+    #[cfg(not(feature = "preserve-order"))]
+    map.remove(key);
+}
@@ -0,0 +1,101 @@
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::{from_value, Value};
+
+/// Single step of a path into a [`Value`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Step<'a> {
+    /// Entry of a compound.
+    Key(&'a str),
+    /// Element of a list.
+    Index(usize),
+}
+
+/// Splits a path such as `Level.Sections[3].Palette[0].Name` into its steps.
+///
+/// Returns `None` if the path is malformed.
+fn parse(path: &str) -> Option<Vec<Step<'_>>> {
+    let mut steps = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        if end > 0 {
+            steps.push(Step::Key(&rest[..end]));
+        } else if !rest.starts_with('[') {
+            return None
+        }
+        rest = &rest[end..];
+
+        while let Some(inner) = rest.strip_prefix('[') {
+            let close = inner.find(']')?;
+            steps.push(Step::Index(inner[..close].parse().ok()?));
+            rest = &inner[close + 1..];
+        }
+
+        if let Some(next) = rest.strip_prefix('.') {
+            if next.is_empty() {
+                return None
+            }
+            rest = next;
+        } else if !rest.is_empty() {
+            return None
+        }
+    }
+
+    Some(steps)
+}
+
+impl Value {
+    /// Looks up a nested value by its path.
+    ///
+    /// Compound entries are separated by dots and list elements are indexed with brackets,
+    /// such as in `Level.Sections[3].Palette[0].Name`.
+    /// Returns `None` if the path is malformed or does not exist.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use mirai_nbt as nbt;
+    /// #
+    /// # fn main() {
+    ///  let value = nbt::nbt!({ "Palette": [{ "Name": "minecraft:air" }, { "Name": "minecraft:stone" }] });
+    ///
+    ///  assert_eq!(value.get_path("Palette[1].Name"), Some(&nbt::nbt!("minecraft:stone")));
+    ///  assert_eq!(value.get_path("Palette[2].Name"), None);
+    /// # }
+    /// ```
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        parse(path)?.into_iter().try_fold(self, |value, step| match (value, step) {
+            (Value::Compound(map), Step::Key(key)) => map.get(key),
+            (Value::List(list), Step::Index(index)) => list.get(index),
+            _ => None,
+        })
+    }
+
+    /// Looks up a nested value by its path and returns a mutable reference to it.
+    ///
+    /// See [`get_path`](Value::get_path) for the syntax of the path.
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut Value> {
+        parse(path)?.into_iter().try_fold(self, |value, step| match (value, step) {
+            (Value::Compound(map), Step::Key(key)) => map.get_mut(key),
+            (Value::List(list), Step::Index(index)) => list.get_mut(index),
+            _ => None,
+        })
+    }
+
+    /// Looks up a nested value by its path and deserialises it into `T`.
+    ///
+    /// See [`get_path`](Value::get_path) for the syntax of the path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no value at the path or it cannot be deserialised into `T`.
+    pub fn get_as<'de, T>(&'de self, path: &str) -> anyhow::Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        let value = self.get_path(path).ok_or_else(|| anyhow!("No NBT value found at path `{path}`"))?;
+        Ok(from_value(value)?)
+    }
+}
@@ -0,0 +1,240 @@
+use std::fmt;
+
+use crate::Value;
+
+/// Expected structure of a [`Value`], checked by [`Value::validate`].
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  use nbt::{Field, Schema};
+///
+///  let schema = Schema::Compound(vec![
+///     Field::required("id", Schema::String),
+///     Field::optional("Items", Schema::list(Schema::Compound(vec![Field::required("Count", Schema::Byte)]))),
+///  ]);
+///
+///  assert!(nbt::nbt!({ "id": "Chest", "Items": [{ "Count": 1i8 }] }).validate(&schema).is_ok());
+///
+///  let errors = nbt::nbt!({ "Items": [{ "Count": 1 }] }).validate(&schema).unwrap_err();
+///  assert_eq!(errors[0].to_string(), "Missing required field `id`");
+///  assert_eq!(errors[1].to_string(), "Expected byte at `Items[0].Count`, found int");
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// Any value.
+    Any,
+    /// A signed byte.
+    Byte,
+    /// A signed short.
+    Short,
+    /// A signed int.
+    Int,
+    /// A signed long.
+    Long,
+    /// A float.
+    Float,
+    /// A double.
+    Double,
+    /// A byte array.
+    ByteArray,
+    /// A UTF-8 string.
+    String,
+    /// List where every element matches the given schema.
+    List(Box<Schema>),
+    /// Compound containing the given fields.
+    ///
+    /// Entries that are not part of the schema are allowed.
+    Compound(Vec<Field>),
+    /// An array of integers.
+    IntArray,
+    /// An array of longs.
+    LongArray,
+}
+
+impl Schema {
+    /// Creates a schema for a list where every element matches `element`.
+    #[inline]
+    pub fn list(element: Schema) -> Self {
+        Self::List(Box::new(element))
+    }
+
+    /// Name of the type of value described by this schema, used in errors.
+    const fn type_name(&self) -> &'static str {
+        match self {
+            Self::Any => "any value",
+            Self::Byte => "byte",
+            Self::Short => "short",
+            Self::Int => "int",
+            Self::Long => "long",
+            Self::Float => "float",
+            Self::Double => "double",
+            Self::ByteArray => "byte array",
+            Self::String => "string",
+            Self::List(_) => "list",
+            Self::Compound(_) => "compound",
+            Self::IntArray => "int array",
+            Self::LongArray => "long array",
+        }
+    }
+}
+
+/// Field of a [`Schema::Compound`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    /// Name of the entry.
+    pub name: String,
+    /// Schema that the value of the entry must match.
+    pub schema: Schema,
+    /// Whether the entry may be missing.
+    pub optional: bool,
+}
+
+impl Field {
+    /// Creates a field that must be present.
+    #[inline]
+    pub fn required<S>(name: S, schema: Schema) -> Self
+    where
+        S: Into<String>,
+    {
+        Self { name: name.into(), schema, optional: false }
+    }
+
+    /// Creates a field that may be missing.
+    #[inline]
+    pub fn optional<S>(name: S, schema: Schema) -> Self
+    where
+        S: Into<String>,
+    {
+        Self { name: name.into(), schema, optional: true }
+    }
+}
+
+/// Reason a value did not match its [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaErrorKind {
+    /// A required field of a compound is missing.
+    Missing,
+    /// The value has a different type than expected.
+    Type {
+        /// Type required by the schema.
+        expected: &'static str,
+        /// Type of the value.
+        found: &'static str,
+    },
+}
+
+/// Single mismatch between a value and a [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    /// Path to the offending value, in the syntax used by [`Value::get_path`].
+    ///
+    /// This is empty if the error concerns the root value.
+    pub path: String,
+    /// What went wrong.
+    pub kind: SchemaErrorKind,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            SchemaErrorKind::Missing => write!(f, "Missing required field `{}`", self.path),
+            SchemaErrorKind::Type { expected, found } if self.path.is_empty() => {
+                write!(f, "Expected {expected} at the root, found {found}")
+            }
+            SchemaErrorKind::Type { expected, found } => write!(f, "Expected {expected} at `{}`, found {found}", self.path),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl Value {
+    /// Checks whether this value matches the schema.
+    ///
+    /// All mismatches are collected rather than stopping at the first one.
+    ///
+    /// # Errors
+    ///
+    /// Returns every field that is missing or has the wrong type, along with its path.
+    pub fn validate(&self, schema: &Schema) -> Result<(), Vec<SchemaError>> {
+        let mut errors = Vec::new();
+        let mut path = String::new();
+        validate(self, schema, &mut path, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Name of the type of this value, used in errors.
+    const fn type_name(&self) -> &'static str {
+        match self {
+            Self::Byte(_) => "byte",
+            Self::Short(_) => "short",
+            Self::Int(_) => "int",
+            Self::Long(_) => "long",
+            Self::Float(_) => "float",
+            Self::Double(_) => "double",
+            Self::ByteArray(_) => "byte array",
+            Self::String(_) => "string",
+            Self::List(_) => "list",
+            Self::Compound(_) => "compound",
+            Self::IntArray(_) => "int array",
+            Self::LongArray(_) => "long array",
+        }
+    }
+}
+
+/// Validates `value` against `schema`, appending any mismatches to `errors`.
+///
+/// `path` contains the path to `value` and is restored before returning.
+fn validate(value: &Value, schema: &Schema, path: &mut String, errors: &mut Vec<SchemaError>) {
+    match (schema, value) {
+        (Schema::Any, _)
+        | (Schema::Byte, Value::Byte(_))
+        | (Schema::Short, Value::Short(_))
+        | (Schema::Int, Value::Int(_))
+        | (Schema::Long, Value::Long(_))
+        | (Schema::Float, Value::Float(_))
+        | (Schema::Double, Value::Double(_))
+        | (Schema::ByteArray, Value::ByteArray(_))
+        | (Schema::String, Value::String(_))
+        | (Schema::IntArray, Value::IntArray(_))
+        | (Schema::LongArray, Value::LongArray(_)) => {}
+        (Schema::List(element), Value::List(list)) => {
+            let len = path.len();
+            for (i, item) in list.iter().enumerate() {
+                path.push_str(&format!("[{i}]"));
+                validate(item, element, path, errors);
+                path.truncate(len);
+            }
+        }
+        (Schema::Compound(fields), Value::Compound(map)) => {
+            let len = path.len();
+            for field in fields {
+                if len > 0 {
+                    path.push('.');
+                }
+                path.push_str(&field.name);
+
+                match map.get(&field.name) {
+                    Some(entry) => validate(entry, &field.schema, path, errors),
+                    None if field.optional => {}
+                    None => errors.push(SchemaError { path: path.clone(), kind: SchemaErrorKind::Missing }),
+                }
+                path.truncate(len);
+            }
+        }
+        _ => errors.push(SchemaError {
+            path: path.clone(),
+            kind: SchemaErrorKind::Type { expected: schema.type_name(), found: value.type_name() },
+        }),
+    }
+}
@@ -1,12 +1,12 @@
 use std::marker::PhantomData;
 
 use paste::paste;
-use serde::ser::{Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeTuple};
+use serde::ser::{Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple};
 use serde::{ser, Serialize};
 
 use util::{BinaryWrite, RVec};
 
-use crate::{BigEndian, FieldType, LittleEndian, NbtError, Variable, Variant, VariantImpl};
+use crate::{BigEndian, FieldType, JavaNetwork, LittleEndian, NbtError, Variable, Variant, VariantImpl, INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN};
 
 /// Returns a `not supported` error.
 macro_rules! forward_unsupported {
@@ -131,6 +131,39 @@ where
     Ok(ser.into_inner())
 }
 
+/// Serializes the given data in the unnamed big endian format.
+///
+/// This is the format used by network formats in Minecraft: Java Edition since 1.20.2.
+///
+/// See [`to_java_net_bytes_in`] for an alternative that serializes into the given writer, instead
+/// of producing a new one.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  #[derive(serde::Serialize, serde::Deserialize)]
+///  struct Data {
+///     value: String
+///  }
+///
+///  let data = Data { value: "Hello, World!".to_owned() };
+///  let encoded = nbt::to_java_net_bytes(&data).unwrap();
+/// # }
+/// ```
+#[inline]
+pub fn to_java_net_bytes<T>(v: &T) -> anyhow::Result<RVec>
+where
+    T: ?Sized + Serialize,
+{
+    let mut ser = Serializer::<_, JavaNetwork>::new(RVec::alloc());
+
+    v.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
 /// Serializes the given data, into the given writer, in big endian format.
 ///
 /// This is the format used by Minecraft: Java Edition.
@@ -230,6 +263,85 @@ where
     Ok(())
 }
 
+/// Serializes the given data, into the given writer, in the unnamed big endian format.
+///
+/// This is the format used by network formats in Minecraft: Java Edition since 1.20.2.
+#[inline]
+pub fn to_java_net_bytes_in<W, T>(w: W, value: &T) -> anyhow::Result<()>
+where
+    T: ?Sized + Serialize,
+    W: BinaryWrite,
+{
+    let mut ser = Serializer::<W, JavaNetwork>::new(w);
+    value.serialize(&mut ser)?;
+
+    Ok(())
+}
+
+/// Computes the size of the given data when serialised in the format `F`, without writing it.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  #[derive(serde::Serialize, serde::Deserialize)]
+///  struct Data {
+///     value: String
+///  }
+///
+///  let data = Data { value: "Hello, World!".to_owned() };
+///  let size = nbt::serialized_size::<nbt::Variable, _>(&data).unwrap();
+///
+///  assert_eq!(size, nbt::to_var_bytes(&data).unwrap().len());
+/// # }
+/// ```
+#[inline]
+pub fn serialized_size<F, T>(value: &T) -> anyhow::Result<usize>
+where
+    F: VariantImpl,
+    T: ?Sized + Serialize,
+{
+    let mut ser = Serializer::<_, F>::new(SizeCounter(0));
+    value.serialize(&mut ser)?;
+
+    Ok(ser.into_inner().0)
+}
+
+/// Writer that only counts the amount of bytes written to it.
+///
+/// It holds no data, so [`AsRef`] and [`AsMut`] return empty slices.
+#[derive(Debug)]
+struct SizeCounter(usize);
+
+impl std::io::Write for SizeCounter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRef<[u8]> for SizeCounter {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &[]
+    }
+}
+
+impl AsMut<[u8]> for SizeCounter {
+    #[inline]
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+}
+
 /// NBT data serialiser.
 #[derive(Debug)]
 pub struct Serializer<W, F>
@@ -243,6 +355,14 @@ where
     is_initial: bool,
     /// Stores the length of the list that is currently being serialised.
     len: usize,
+    /// Whether the next sequence is a typed array, which has no element type.
+    array: bool,
+    /// Element types of the sequences that are currently being serialised, innermost last.
+    ///
+    /// The type of a sequence is `None` until its first element has been written.
+    element_types: Vec<Option<FieldType>>,
+    /// Whether the last sequence type that was written belongs to an empty sequence.
+    empty_seq: bool,
     _marker: PhantomData<F>,
 }
 
@@ -258,6 +378,9 @@ where
             writer: w,
             is_initial: true,
             len: 0,
+            array: false,
+            element_types: Vec::new(),
+            empty_seq: false,
             _marker: PhantomData,
         }
     }
@@ -267,6 +390,86 @@ where
     pub fn into_inner(self) -> W {
         self.writer
     }
+
+    /// Writes the type and name of the root compound if nothing has been written yet.
+    ///
+    /// The [`JavaNetwork`] variant has no root name.
+    fn begin_root(&mut self, name: &str) -> Result<(), NbtError> {
+        if self.is_initial {
+            self.writer.write_u8(FieldType::Compound as u8)?;
+            if M::AS_ENUM != Variant::JavaNetwork {
+                ser::Serializer::serialize_str(&mut *self, name)?;
+            }
+            self.is_initial = false;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the length of a sequence.
+    fn write_len(&mut self, len: usize) -> Result<(), NbtError> {
+        match M::AS_ENUM {
+            Variant::BigEndian | Variant::JavaNetwork => self.writer.write_i32_be(len as i32),
+            Variant::LittleEndian => self.writer.write_i32_le(len as i32),
+            Variant::Variable => self.writer.write_var_i32(len as i32),
+        }?;
+
+        Ok(())
+    }
+
+    /// Starts a sequence of `len` elements.
+    ///
+    /// The header of a list is written together with its first element, since that determines the element type.
+    /// Typed arrays and empty sequences have their header written right away. Empty tuples are lists, any other
+    /// empty sequence is an empty typed array unless it is an element of a list of lists.
+    fn begin_seq(&mut self, len: usize, tuple: bool) -> Result<(), NbtError> {
+        let in_list = self.element_types.last() == Some(&Some(FieldType::List));
+        self.element_types.push(None);
+        if std::mem::take(&mut self.array) {
+            self.write_len(len)?;
+            self.len = 0;
+        } else if len == 0 {
+            if tuple || in_list {
+                self.writer.write_u8(FieldType::End as u8)?;
+            }
+            self.write_len(0)?;
+            self.len = 0;
+        } else {
+            self.len = len;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that an element has the same type as the previous elements of the current sequence.
+    ///
+    /// NBT lists store a single element type, so elements of different types would make the list unreadable.
+    /// This can happen for enums that mix unit variants, which are strings, with other variants.
+    ///
+    /// Empty sequences do not have an element type of their own, so they take the type of the other elements.
+    /// Returns the type that the element should be written as.
+    fn check_element(&mut self, ty: FieldType, empty_seq: bool) -> Result<FieldType, NbtError> {
+        match self.element_types.last_mut() {
+            Some(Some(expected))
+                if empty_seq && matches!(expected, FieldType::List | FieldType::IntArray | FieldType::LongArray) =>
+            {
+                Ok(*expected)
+            }
+            Some(Some(expected)) if *expected != ty => {
+                Err(anyhow::anyhow!("List elements must have the same type, found {ty:?} in a list of {expected:?}").into())
+            }
+            Some(current) => {
+                *current = Some(ty);
+                Ok(ty)
+            }
+            None => Ok(ty),
+        }
+    }
+
+    /// Finishes the current sequence.
+    fn end_seq(&mut self) {
+        self.element_types.pop();
+    }
 }
 
 impl<'a, W, M> ser::Serializer for &'a mut Serializer<W, M>
@@ -283,7 +486,7 @@ where
     type SerializeTupleVariant = Impossible<(), NbtError>;
     type SerializeMap = Self;
     type SerializeStruct = Self;
-    type SerializeStructVariant = Impossible<(), NbtError>;
+    type SerializeStructVariant = Self;
 
     forward_unsupported!(char, u8, u16, u32, u64, i128);
 
@@ -302,7 +505,7 @@ where
     #[inline]
     fn serialize_i16(self, v: i16) -> Result<(), NbtError> {
         match M::AS_ENUM {
-            Variant::BigEndian => self.writer.write_i16_be(v)?,
+            Variant::BigEndian | Variant::JavaNetwork => self.writer.write_i16_be(v)?,
             Variant::LittleEndian | Variant::Variable => self.writer.write_i16_le(v)?,
         };
 
@@ -312,7 +515,7 @@ where
     #[inline]
     fn serialize_i32(self, v: i32) -> Result<(), NbtError> {
         match M::AS_ENUM {
-            Variant::BigEndian => self.writer.write_i32_be(v)?,
+            Variant::BigEndian | Variant::JavaNetwork => self.writer.write_i32_be(v)?,
             Variant::LittleEndian => self.writer.write_i32_le(v)?,
             Variant::Variable => self.writer.write_var_i32(v)?,
         };
@@ -323,7 +526,7 @@ where
     #[inline]
     fn serialize_i64(self, v: i64) -> Result<(), NbtError> {
         match M::AS_ENUM {
-            Variant::BigEndian => self.writer.write_i64_be(v)?,
+            Variant::BigEndian | Variant::JavaNetwork => self.writer.write_i64_be(v)?,
             Variant::LittleEndian => self.writer.write_i64_le(v)?,
             Variant::Variable => self.writer.write_var_i64(v)?,
         };
@@ -334,7 +537,7 @@ where
     #[inline]
     fn serialize_f32(self, v: f32) -> Result<(), NbtError> {
         match M::AS_ENUM {
-            Variant::BigEndian => self.writer.write_f32_be(v)?,
+            Variant::BigEndian | Variant::JavaNetwork => self.writer.write_f32_be(v)?,
             Variant::LittleEndian | Variant::Variable => self.writer.write_f32_le(v)?,
         };
 
@@ -344,7 +547,7 @@ where
     #[inline]
     fn serialize_f64(self, v: f64) -> Result<(), NbtError> {
         match M::AS_ENUM {
-            Variant::BigEndian => self.writer.write_f64_be(v)?,
+            Variant::BigEndian | Variant::JavaNetwork => self.writer.write_f64_be(v)?,
             Variant::LittleEndian | Variant::Variable => self.writer.write_f64_le(v)?,
         };
 
@@ -354,7 +557,7 @@ where
     #[inline]
     fn serialize_str(self, v: &str) -> Result<(), NbtError> {
         match M::AS_ENUM {
-            Variant::BigEndian => self.writer.write_u16_be(v.len() as u16),
+            Variant::BigEndian | Variant::JavaNetwork => self.writer.write_u16_be(v.len() as u16),
             Variant::LittleEndian => self.writer.write_u16_le(v.len() as u16),
             Variant::Variable => self.writer.write_var_u32(v.len() as u32),
         }?;
@@ -366,7 +569,7 @@ where
     #[inline]
     fn serialize_bytes(self, v: &[u8]) -> Result<(), NbtError> {
         match M::AS_ENUM {
-            Variant::BigEndian => self.writer.write_i32_be(v.len() as i32),
+            Variant::BigEndian | Variant::JavaNetwork => self.writer.write_i32_be(v.len() as i32),
             Variant::LittleEndian => self.writer.write_i32_le(v.len() as i32),
             Variant::Variable => self.writer.write_var_i32(v.len() as i32),
         }?;
@@ -391,28 +594,44 @@ where
         Err(anyhow::anyhow!("Serializing unit structs is not supported").into())
     }
 
-    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, _variant: &'static str) -> Result<(), NbtError> {
-        Err(anyhow::anyhow!("Serializing unit variants is not supported").into())
+    /// Unit variants are written as a string containing the name of the variant.
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> Result<(), NbtError> {
+        self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, _value: &T) -> Result<(), NbtError> {
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<(), NbtError> {
+        if name == INT_ARRAY_TOKEN || name == LONG_ARRAY_TOKEN {
+            self.array = true;
+            return value.serialize(self)
+        }
+
         Err(anyhow::anyhow!("Serializing newtype structs is not supported").into())
     }
 
+    /// Newtype variants are written as a compound with a single field named after the variant.
     fn serialize_newtype_variant<T: Serialize + ?Sized>(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<(), NbtError> {
-        Err(anyhow::anyhow!("Serializing newtype variants is not supported").into())
+        self.begin_root(name)?;
+
+        let ty_serializer = FieldTypeSerializer::new(self);
+        value.serialize(ty_serializer)?;
+
+        ser::Serializer::serialize_str(&mut *self, variant)?;
+        value.serialize(&mut *self)?;
+
+        self.writer.write_u8(FieldType::End as u8)?;
+        Ok(())
     }
 
     #[inline]
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
         if let Some(len) = len {
-            self.len = len;
+            self.begin_seq(len, false)?;
             Ok(self)
         } else {
             Err(anyhow::anyhow!("Sequences with a size not known upfront are not supported").into())
@@ -421,7 +640,7 @@ where
 
     #[inline]
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        self.len = len;
+        self.begin_seq(len, true)?;
         Ok(self)
     }
 
@@ -442,33 +661,29 @@ where
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
         // nbt::Value does not distinguish between maps and structs.
         // Therefore this is also needed here
-        if self.is_initial {
-            self.writer.write_u8(FieldType::Compound as u8)?;
-            self.serialize_str("")?;
-            self.is_initial = false;
-        }
-
+        self.begin_root("")?;
         Ok(self)
     }
 
     fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Self::Error> {
-        if self.is_initial {
-            self.writer.write_u8(FieldType::Compound as u8)?;
-            self.serialize_str(name)?;
-            self.is_initial = false;
-        }
-
+        self.begin_root(name)?;
         Ok(self)
     }
 
+    /// Struct variants are written as a compound with a single compound field named after the variant.
     fn serialize_struct_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        Err(anyhow::anyhow!("Serializing struct variants is not supported").into())
+        self.begin_root(name)?;
+
+        self.writer.write_u8(FieldType::Compound as u8)?;
+        ser::Serializer::serialize_str(&mut *self, variant)?;
+
+        Ok(self)
     }
 }
 
@@ -485,15 +700,15 @@ where
     where
         T: ?Sized + Serialize,
     {
+        let (ty, empty_seq) = field_type::<F, _>(element)?;
+        let ty = self.check_element(ty, empty_seq)?;
         if self.len != 0 {
-            let ty_serializer = FieldTypeSerializer::new(self);
-            element.serialize(ty_serializer)?;
+            // Sequences of ints and longs are written as typed arrays, which have no element type.
+            if !matches!(ty, FieldType::Int | FieldType::Long) {
+                self.writer.write_u8(ty as u8)?;
+            }
 
-            match F::AS_ENUM {
-                Variant::BigEndian => self.writer.write_i32_be(self.len as i32),
-                Variant::LittleEndian => self.writer.write_i32_le(self.len as i32),
-                Variant::Variable => self.writer.write_var_i32(self.len as i32),
-            }?;
+            self.write_len(self.len)?;
             self.len = 0;
         }
 
@@ -502,6 +717,7 @@ where
 
     #[inline]
     fn end(self) -> Result<(), NbtError> {
+        self.end_seq();
         Ok(())
     }
 }
@@ -519,15 +735,11 @@ where
     where
         T: ?Sized + Serialize,
     {
+        let (ty, empty_seq) = field_type::<M, _>(element)?;
+        let ty = self.check_element(ty, empty_seq)?;
         if self.len != 0 {
-            let ty_serializer = FieldTypeSerializer::new(self);
-            element.serialize(ty_serializer)?;
-
-            match M::AS_ENUM {
-                Variant::BigEndian => self.writer.write_i32_be(self.len as i32),
-                Variant::LittleEndian => self.writer.write_i32_le(self.len as i32),
-                Variant::Variable => self.writer.write_var_i32(self.len as i32),
-            }?;
+            self.writer.write_u8(ty as u8)?;
+            self.write_len(self.len)?;
             self.len = 0;
         }
 
@@ -536,6 +748,7 @@ where
 
     #[inline]
     fn end(self) -> Result<(), NbtError> {
+        self.end_seq();
         Ok(())
     }
 }
@@ -599,7 +812,7 @@ where
         if !should_skip {
             match M::AS_ENUM {
                 Variant::LittleEndian => self.writer.write_u16_le(key.len() as u16),
-                Variant::BigEndian => self.writer.write_u16_be(key.len() as u16),
+                Variant::BigEndian | Variant::JavaNetwork => self.writer.write_u16_be(key.len() as u16),
                 Variant::Variable => self.writer.write_var_u32(key.len() as u32),
             }?;
 
@@ -617,6 +830,45 @@ where
     }
 }
 
+impl<W, M> SerializeStructVariant for &mut Serializer<W, M>
+where
+    W: BinaryWrite,
+    M: VariantImpl,
+{
+    type Ok = ();
+    type Error = NbtError;
+
+    fn serialize_field<V>(&mut self, key: &'static str, value: &V) -> Result<(), NbtError>
+    where
+        V: ?Sized + Serialize,
+    {
+        SerializeStruct::serialize_field(self, key, value)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), NbtError> {
+        // Close both the variant and the compound wrapping it.
+        self.writer.write_u8(FieldType::End as u8)?;
+        self.writer.write_u8(FieldType::End as u8)?;
+        Ok(())
+    }
+}
+
+/// Returns the type of the given value and whether it is an empty sequence.
+fn field_type<F, T>(value: &T) -> Result<(FieldType, bool), NbtError>
+where
+    F: VariantImpl,
+    T: ?Sized + Serialize,
+{
+    let mut probe = Serializer::<Vec<u8>, F>::new(Vec::new());
+    value.serialize(FieldTypeSerializer::new(&mut probe))?;
+
+    match probe.writer.first() {
+        Some(ty) => Ok((FieldType::try_from(*ty)?, probe.empty_seq)),
+        None => Err(anyhow::anyhow!("Sequences cannot contain missing values").into()),
+    }
+}
+
 /// Separate serialiser that writes data types to the writer.
 ///
 /// Serde does not provide any type information, hence this exists.
@@ -647,13 +899,13 @@ where
 {
     type Ok = bool; // Whether the field should be skipped
     type Error = NbtError;
-    type SerializeSeq = Self;
+    type SerializeSeq = SeqTypeSerializer<'a, W, F>;
     type SerializeTuple = Self;
     type SerializeTupleStruct = Impossible<bool, Self::Error>;
     type SerializeTupleVariant = Impossible<bool, Self::Error>;
     type SerializeMap = Self;
     type SerializeStruct = Self;
-    type SerializeStructVariant = Impossible<bool, Self::Error>;
+    type SerializeStructVariant = Self;
 
     forward_unsupported_field!(char, u8, u16, u32, u64, i128);
 
@@ -723,11 +975,19 @@ where
     }
 
     fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, _variant: &'static str) -> Result<Self::Ok, Self::Error> {
-        Err(anyhow::anyhow!("Serializing unit variants is not supported").into())
+        self.ser.writer.write_u8(FieldType::String as u8)?;
+        Ok(false)
     }
 
-    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, _value: &T) -> Result<Self::Ok, Self::Error> {
-        Err(anyhow::anyhow!("Serializing newtype structs is not supported").into())
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, _value: &T) -> Result<Self::Ok, Self::Error> {
+        let ty = match name {
+            INT_ARRAY_TOKEN => FieldType::IntArray,
+            LONG_ARRAY_TOKEN => FieldType::LongArray,
+            _ => return Err(anyhow::anyhow!("Serializing newtype structs is not supported").into()),
+        };
+
+        self.ser.writer.write_u8(ty as u8)?;
+        Ok(false)
     }
 
     fn serialize_newtype_variant<T: Serialize + ?Sized>(
@@ -737,12 +997,13 @@ where
         _variant: &'static str,
         _value: &T,
     ) -> Result<Self::Ok, Self::Error> {
-        Err(anyhow::anyhow!("Serializing newtype variants is not supported").into())
+        self.ser.writer.write_u8(FieldType::Compound as u8)?;
+        Ok(false)
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        self.ser.writer.write_u8(FieldType::List as u8)?;
-        Ok(self)
+        // The type depends on the elements, see `SeqTypeSerializer`.
+        Ok(SeqTypeSerializer { ser: self.ser, written: false })
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
@@ -783,11 +1044,26 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        Err(anyhow::anyhow!("Serializing struct variants is not supported").into())
+        self.ser.writer.write_u8(FieldType::Compound as u8)?;
+        Ok(self)
     }
 }
 
-impl<'a, W, F> SerializeSeq for FieldTypeSerializer<'a, W, F>
+/// Writes the type of a sequence, which is determined by its first element.
+///
+/// Sequences of ints and longs, such as `Vec<i32>` and `Vec<i64>`, are typed arrays. Any other sequence is a list.
+/// Empty sequences are written as empty int arrays. Tuples and fixed-size arrays are always lists.
+struct SeqTypeSerializer<'a, W, F>
+where
+    W: BinaryWrite,
+    F: VariantImpl,
+{
+    ser: &'a mut Serializer<W, F>,
+    /// Whether the type has been written.
+    written: bool,
+}
+
+impl<'a, W, F> SerializeSeq for SeqTypeSerializer<'a, W, F>
 where
     W: BinaryWrite,
     F: VariantImpl,
@@ -796,15 +1072,33 @@ where
     type Error = NbtError;
 
     #[inline]
-    fn serialize_element<T>(&mut self, _element: &T) -> Result<(), NbtError>
+    fn serialize_element<T>(&mut self, element: &T) -> Result<(), NbtError>
     where
         T: ?Sized + Serialize,
     {
+        if !self.written {
+            let ty = match field_type::<F, _>(element)?.0 {
+                FieldType::Int => FieldType::IntArray,
+                FieldType::Long => FieldType::LongArray,
+                _ => FieldType::List,
+            };
+
+            self.ser.writer.write_u8(ty as u8)?;
+            self.written = true;
+        }
+
         Ok(())
     }
 
     #[inline]
     fn end(self) -> Result<bool, Self::Error> {
+        // The element type of an empty sequence is unknown. It is written as an empty typed array so that
+        // `Vec<i32>` and `Vec<i64>` have the same type whatever their length, see `Serializer::begin_seq`.
+        if !self.written {
+            self.ser.writer.write_u8(FieldType::IntArray as u8)?;
+            self.ser.empty_seq = true;
+        }
+
         Ok(false)
     }
 }
@@ -882,3 +1176,25 @@ where
         Ok(false)
     }
 }
+
+impl<'a, W, F> SerializeStructVariant for FieldTypeSerializer<'a, W, F>
+where
+    W: BinaryWrite,
+    F: VariantImpl,
+{
+    type Ok = bool;
+    type Error = NbtError;
+
+    #[inline]
+    fn serialize_field<V>(&mut self, _key: &'static str, _value: &V) -> Result<(), NbtError>
+    where
+        V: ?Sized + Serialize,
+    {
+        Ok(())
+    }
+
+    #[inline]
+    fn end(self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
@@ -0,0 +1,398 @@
+use std::fmt::{self, Write};
+
+use anyhow::bail;
+use util::RVec;
+
+use crate::{Compound, Value};
+
+/// Maximum nesting depth of compounds and lists, this is the same limit that Minecraft uses.
+const MAX_DEPTH: usize = 512;
+
+/// Parses stringified NBT (SNBT).
+///
+/// This supports the syntax used by Minecraft commands:
+/// * compounds such as `{name: "Steve", 'quoted key': 1}`,
+/// * lists such as `[1, 2, 3]`, whose elements must all be of the same type,
+/// * byte, int and long arrays such as `[B; 1b, 2b]`, `[I; 1, 2]` and `[L; 1L, 2L]`,
+/// * numbers with an optional `b`, `s`, `L`, `f` or `d` suffix. Numbers without a suffix are ints, or doubles
+///   if they contain a decimal point,
+/// * `true` and `false`, which are bytes,
+/// * single or double quoted strings, in which the quotes and backslash are escaped with a backslash,
+/// * unquoted strings, which consist of letters, digits and `_-.+`.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// # fn main() {
+///  let value = nbt::from_snbt("{Count: 1b, Name: \"minecraft:stone\"}").unwrap();
+///  let compound = value.as_compound().unwrap();
+///
+///  assert_eq!(compound["Count"], nbt::Value::Byte(1));
+///  assert_eq!(compound["Name"], "minecraft:stone");
+/// # }
+/// ```
+pub fn from_snbt(input: &str) -> anyhow::Result<Value> {
+    let mut parser = Parser { input, pos: 0 };
+    let value = parser.value(0)?;
+
+    parser.skip_whitespace();
+    if parser.pos != input.len() {
+        bail!("Unexpected trailing data at position {}", parser.pos)
+    }
+
+    Ok(value)
+}
+
+impl Value {
+    /// Formats the value as stringified NBT (SNBT).
+    ///
+    /// The output can be parsed again using [`from_snbt`]. Compound keys are sorted so that the output does not
+    /// depend on the order of the underlying map. SNBT cannot represent NaN or infinite floats, these are
+    /// written as is and read back as strings.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use mirai_nbt::Value;
+    /// let value = Value::List(vec![Value::Short(1), Value::Short(2)]);
+    /// assert_eq!(value.to_snbt(), "[1s,2s]");
+    /// ```
+    pub fn to_snbt(&self) -> String {
+        let mut out = String::new();
+        // Writing to a string cannot fail.
+        let _: fmt::Result = write_value(&mut out, self);
+        out
+    }
+}
+
+/// Parser state.
+struct Parser<'a> {
+    input: &'a str,
+    /// Current position in bytes.
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `c` if it is the next character, ignoring whitespace.
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> anyhow::Result<()> {
+        if !self.eat(c) {
+            bail!("Expected '{}' at position {}", c as char, self.pos)
+        }
+
+        Ok(())
+    }
+
+    fn value(&mut self, depth: usize) -> anyhow::Result<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.compound(depth + 1),
+            Some(b'[') => self.list(depth + 1),
+            Some(b'"' | b'\'') => self.quoted().map(Value::String),
+            Some(_) => {
+                let start = self.pos;
+                let token = self.unquoted();
+                if token.is_empty() {
+                    bail!("Expected value at position {start}")
+                }
+
+                Ok(parse_scalar(token))
+            }
+            None => bail!("Unexpected end of input"),
+        }
+    }
+
+    fn compound(&mut self, depth: usize) -> anyhow::Result<Value> {
+        if depth > MAX_DEPTH {
+            bail!("SNBT is nested deeper than {MAX_DEPTH} levels")
+        }
+
+        // Opening brace.
+        self.pos += 1;
+
+        let mut map = Compound::new();
+        if self.eat(b'}') {
+            return Ok(Value::Compound(map))
+        }
+
+        loop {
+            let key = self.key()?;
+            self.expect(b':')?;
+            let value = self.value(depth)?;
+            map.insert(key, value);
+
+            if !self.eat(b',') {
+                self.expect(b'}')?;
+                return Ok(Value::Compound(map))
+            }
+        }
+    }
+
+    fn key(&mut self) -> anyhow::Result<String> {
+        self.skip_whitespace();
+        if matches!(self.peek(), Some(b'"' | b'\'')) {
+            return self.quoted()
+        }
+
+        let start = self.pos;
+        let key = self.unquoted();
+        if key.is_empty() {
+            bail!("Expected key at position {start}")
+        }
+
+        Ok(key.to_owned())
+    }
+
+    fn list(&mut self, depth: usize) -> anyhow::Result<Value> {
+        if depth > MAX_DEPTH {
+            bail!("SNBT is nested deeper than {MAX_DEPTH} levels")
+        }
+
+        // Opening bracket.
+        self.pos += 1;
+        self.skip_whitespace();
+
+        // Arrays start with their element type followed by a semicolon.
+        let bytes = self.input.as_bytes();
+        if let (Some(&kind @ (b'B' | b'I' | b'L')), Some(b';')) = (bytes.get(self.pos), bytes.get(self.pos + 1)) {
+            self.pos += 2;
+            return self.array(kind)
+        }
+
+        let mut list = Vec::new();
+        if self.eat(b']') {
+            return Ok(Value::List(list))
+        }
+
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            let element = self.value(depth)?;
+            if list.first().map_or(false, |first| std::mem::discriminant(first) != std::mem::discriminant(&element)) {
+                bail!("List element at position {start} has a different type than the first element")
+            }
+            list.push(element);
+
+            if !self.eat(b',') {
+                self.expect(b']')?;
+                return Ok(Value::List(list))
+            }
+        }
+    }
+
+    fn array(&mut self, kind: u8) -> anyhow::Result<Value> {
+        let mut elements = Vec::new();
+        if !self.eat(b']') {
+            loop {
+                self.skip_whitespace();
+                let start = self.pos;
+                let element = match parse_scalar(self.unquoted()) {
+                    Value::Byte(v) => i64::from(v),
+                    Value::Short(v) => i64::from(v),
+                    Value::Int(v) => i64::from(v),
+                    Value::Long(v) => v,
+                    _ => bail!("Expected integer at position {start}"),
+                };
+                elements.push((start, element));
+
+                if !self.eat(b',') {
+                    self.expect(b']')?;
+                    break
+                }
+            }
+        }
+
+        let out_of_range = |start: usize| anyhow::anyhow!("Array element at position {start} is out of range");
+        Ok(match kind {
+            b'B' => Value::ByteArray(RVec::from(
+                elements
+                    .into_iter()
+                    .map(|(start, v)| i8::try_from(v).map(|v| v as u8).map_err(|_| out_of_range(start)))
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            )),
+            b'I' => Value::IntArray(
+                elements
+                    .into_iter()
+                    .map(|(start, v)| i32::try_from(v).map_err(|_| out_of_range(start)))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            _ => Value::LongArray(elements.into_iter().map(|(_, v)| v).collect()),
+        })
+    }
+
+    /// Reads a quoted string, starting at the opening quote.
+    fn quoted(&mut self) -> anyhow::Result<String> {
+        let start = self.pos;
+        let quote = self.input[start..].chars().next();
+        self.pos += 1;
+
+        let mut out = String::new();
+        let mut chars = self.input[self.pos..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, c @ ('\\' | '"' | '\''))) => out.push(c),
+                    _ => bail!("Invalid escape sequence at position {}", self.pos + i),
+                },
+                c if Some(c) == quote => {
+                    self.pos += i + 1;
+                    return Ok(out)
+                }
+                c => out.push(c),
+            }
+        }
+
+        bail!("Unterminated string starting at position {start}")
+    }
+
+    /// Reads characters that are allowed in unquoted strings.
+    fn unquoted(&mut self) -> &'a str {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if is_unquoted(c)) {
+            self.pos += 1;
+        }
+
+        &self.input[start..self.pos]
+    }
+}
+
+/// Whether the character can be used in unquoted strings.
+const fn is_unquoted(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'.' | b'+')
+}
+
+/// Parses an unquoted token as a boolean or number, falling back to a string.
+fn parse_scalar(token: &str) -> Value {
+    match token {
+        "true" => Value::Byte(1),
+        "false" => Value::Byte(0),
+        _ => parse_number(token).unwrap_or_else(|| Value::String(token.to_owned())),
+    }
+}
+
+/// Parses a number that has a type suffix, such as `1b` or `2.5f`.
+pub fn parse_suffixed(token: &str) -> Option<Value> {
+    match token.as_bytes().last()?.to_ascii_lowercase() {
+        b'b' | b's' | b'l' | b'f' | b'd' => parse_number(token),
+        _ => None,
+    }
+}
+
+fn parse_number(token: &str) -> Option<Value> {
+    let (body, suffix) = match token.as_bytes().last()?.to_ascii_lowercase() {
+        suffix @ (b'b' | b's' | b'l' | b'f' | b'd') => (&token[..token.len() - 1], Some(suffix)),
+        _ => (token, None),
+    };
+
+    match suffix {
+        Some(b'b') => body.parse().ok().map(Value::Byte),
+        Some(b's') => body.parse().ok().map(Value::Short),
+        Some(b'l') => body.parse().ok().map(Value::Long),
+        Some(b'f') if is_decimal(body) => body.parse().ok().map(Value::Float),
+        Some(b'd') if is_decimal(body) => body.parse().ok().map(Value::Double),
+        None => match body.parse() {
+            Ok(v) => Some(Value::Int(v)),
+            Err(_) if body.contains('.') && is_decimal(body) => body.parse().ok().map(Value::Double),
+            Err(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether the string looks like a decimal number.
+///
+/// Rust also parses words such as `inf` and `NaN` as floats, which are strings in SNBT.
+fn is_decimal(body: &str) -> bool {
+    body.bytes().any(|c| c.is_ascii_digit()) && body.bytes().all(|c| c.is_ascii_digit() || matches!(c, b'.' | b'e' | b'E' | b'+' | b'-'))
+}
+
+fn write_value<W: Write>(out: &mut W, value: &Value) -> fmt::Result {
+    match value {
+        Value::Byte(v) => write!(out, "{v}b"),
+        Value::Short(v) => write!(out, "{v}s"),
+        Value::Int(v) => write!(out, "{v}"),
+        Value::Long(v) => write!(out, "{v}L"),
+        // The debug format always includes a decimal point or exponent.
+        Value::Float(v) => write!(out, "{v:?}f"),
+        Value::Double(v) => write!(out, "{v:?}d"),
+        Value::ByteArray(v) => write_array(out, 'B', v.iter().map(|v| format!("{}b", *v as i8))),
+        Value::String(v) => write_string(out, v),
+        Value::List(list) => {
+            out.write_char('[')?;
+            for (i, element) in list.iter().enumerate() {
+                if i != 0 {
+                    out.write_char(',')?;
+                }
+                write_value(out, element)?;
+            }
+            out.write_char(']')
+        }
+        Value::Compound(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_unstable_by_key(|(k, _)| *k);
+
+            out.write_char('{')?;
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i != 0 {
+                    out.write_char(',')?;
+                }
+
+                if !k.is_empty() && k.bytes().all(is_unquoted) {
+                    out.write_str(k)?;
+                } else {
+                    write_string(out, k)?;
+                }
+                out.write_char(':')?;
+                write_value(out, v)?;
+            }
+            out.write_char('}')
+        }
+        Value::IntArray(v) => write_array(out, 'I', v.iter().map(ToString::to_string)),
+        Value::LongArray(v) => write_array(out, 'L', v.iter().map(|v| format!("{v}L"))),
+    }
+}
+
+fn write_array<W: Write, I: Iterator<Item = String>>(out: &mut W, kind: char, elements: I) -> fmt::Result {
+    write!(out, "[{kind};")?;
+    for (i, element) in elements.enumerate() {
+        if i != 0 {
+            out.write_char(',')?;
+        }
+        out.write_str(&element)?;
+    }
+    out.write_char(']')
+}
+
+/// Writes a quoted string, using single quotes if that avoids escaping.
+fn write_string<W: Write>(out: &mut W, v: &str) -> fmt::Result {
+    let quote = if v.contains('"') && !v.contains('\'') { '\'' } else { '"' };
+
+    out.write_char(quote)?;
+    for c in v.chars() {
+        if c == quote || c == '\\' {
+            out.write_char('\\')?;
+        }
+        out.write_char(c)?;
+    }
+    out.write_char(quote)
+}
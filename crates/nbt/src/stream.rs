@@ -0,0 +1,462 @@
+//! Streaming NBT over [`std::io::Read`] and [`std::io::Write`].
+//!
+//! Large files such as player data or structures can be decoded while they are being read and encoded while they
+//! are being written, without holding the entire encoded file in memory.
+//! Data that is spread over several in-memory buffers can be decoded with a [`Chain`], without concatenating it first.
+
+use std::collections::VecDeque;
+use std::io::{self, BufReader, Read, Write};
+
+use paste::paste;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use util::{bail, BinaryRead};
+
+use crate::{Deserializer, Serializer, VariantImpl};
+
+/// Amount of serialised bytes that are collected before they are passed on to the writer.
+const WRITE_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Implements the read functions for number primitives.
+macro_rules! declare_primitive_fns {
+    ($($ty: ident),+) => {
+        paste! {$(
+            #[doc = concat!("Reads a little endian [`", stringify!($ty), "`] from the input")]
+            #[inline]
+            fn [<read_ $ty _le>](&mut self) -> anyhow::Result<$ty> {
+                Ok(<$ty>::from_le_bytes(self.read_const()?))
+            }
+
+            #[doc = concat!("Reads a big endian [`", stringify!($ty), "`] from the input")]
+            #[inline]
+            fn [<read_ $ty _be>](&mut self) -> anyhow::Result<$ty> {
+                Ok(<$ty>::from_be_bytes(self.read_const()?))
+            }
+        )+}
+    }
+}
+
+/// Bytes taken from an [`Input`].
+#[derive(Debug)]
+pub enum Bytes<'de, 's> {
+    /// Bytes that live as long as the input itself and can be borrowed by the output.
+    Borrowed(&'de [u8]),
+    /// Bytes that were copied into a scratch buffer and are only valid until the next read.
+    Transient(&'s [u8]),
+}
+
+impl<'de, 's> Bytes<'de, 's> {
+    /// Returns the bytes, regardless of how long they live.
+    #[inline]
+    pub const fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Borrowed(bytes) => bytes,
+            Self::Transient(bytes) => bytes,
+        }
+    }
+}
+
+/// Source of NBT data that can be deserialised by a [`Deserializer`].
+///
+/// This is implemented for every in-memory [`BinaryRead`] buffer, which allows strings to be borrowed,
+/// and for streaming readers through [`from_reader`].
+pub trait Input<'de> {
+    declare_primitive_fns!(u16, i16, i32, i64, f32, f64);
+
+    /// Reads a single byte.
+    fn read_u8(&mut self) -> anyhow::Result<u8>;
+
+    /// Reads `N` bytes into an array.
+    fn read_const<const N: usize>(&mut self) -> anyhow::Result<[u8; N]>;
+
+    /// Takes `n` bytes out of the input.
+    fn read_bytes(&mut self, n: usize) -> anyhow::Result<Bytes<'de, '_>>;
+
+    /// Advances past `n` bytes of the input.
+    #[inline]
+    fn skip(&mut self, n: usize) -> anyhow::Result<()> {
+        self.read_bytes(n)?;
+        Ok(())
+    }
+
+    /// Reads a single signed byte.
+    #[inline]
+    fn read_i8(&mut self) -> anyhow::Result<i8> {
+        Ok(self.read_u8()? as i8)
+    }
+
+    /// Reads a boolean stored as a single byte.
+    #[inline]
+    fn read_bool(&mut self) -> anyhow::Result<bool> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    /// Reads a variable size [`u32`].
+    #[inline]
+    fn read_var_u32(&mut self) -> anyhow::Result<u32> {
+        let mut v = 0;
+        let mut i = 0;
+        while i < 35 {
+            let b = self.read_u8()?;
+            v |= ((b & 0x7f) as u32) << i;
+            if b & 0x80 == 0 {
+                return Ok(v)
+            }
+            i += 7;
+        }
+
+        bail!(Malformed, "variable 32-bit integer did not end after 5 bytes")
+    }
+
+    /// Reads a variable size [`u64`].
+    #[inline]
+    fn read_var_u64(&mut self) -> anyhow::Result<u64> {
+        let mut v = 0;
+        let mut i = 0;
+        while i < 70 {
+            let b = self.read_u8()?;
+            v |= ((b & 0x7f) as u64) << i;
+            if b & 0x80 == 0 {
+                return Ok(v)
+            }
+            i += 7;
+        }
+
+        bail!(Malformed, "variable 64-bit integer did not end after 10 bytes")
+    }
+
+    /// Reads a zigzag encoded variable size [`i32`].
+    #[inline]
+    fn read_var_i32(&mut self) -> anyhow::Result<i32> {
+        let vx = self.read_var_u32()?;
+        let v = (vx >> 1) as i32;
+
+        Ok(if vx & 1 != 0 { !v } else { v })
+    }
+
+    /// Reads a zigzag encoded variable size [`i64`].
+    #[inline]
+    fn read_var_i64(&mut self) -> anyhow::Result<i64> {
+        let vx = self.read_var_u64()?;
+        let v = (vx >> 1) as i64;
+
+        Ok(if vx & 1 != 0 { !v } else { v })
+    }
+}
+
+impl<'de, R> Input<'de> for R
+where
+    R: BinaryRead<'de>,
+{
+    #[inline]
+    fn read_u8(&mut self) -> anyhow::Result<u8> {
+        BinaryRead::read_u8(self)
+    }
+
+    #[inline]
+    fn read_const<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        self.take_const()
+    }
+
+    #[inline]
+    fn read_bytes(&mut self, n: usize) -> anyhow::Result<Bytes<'de, '_>> {
+        self.take_n(n).map(Bytes::Borrowed)
+    }
+}
+
+/// Buffered [`Input`] that reads from an [`io::Read`].
+struct IoRead<R> {
+    reader: BufReader<R>,
+    /// Holds the bytes of the last [`read_bytes`](Input::read_bytes) call.
+    scratch: Vec<u8>,
+}
+
+impl<'de, R> Input<'de> for IoRead<R>
+where
+    R: Read,
+{
+    #[inline]
+    fn read_u8(&mut self) -> anyhow::Result<u8> {
+        let [b] = self.read_const()?;
+        Ok(b)
+    }
+
+    #[inline]
+    fn read_const<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn read_bytes(&mut self, n: usize) -> anyhow::Result<Bytes<'de, '_>> {
+        // The scratch buffer grows with the data that was actually read,
+        // a corrupted length can therefore not cause a huge allocation up front.
+        self.scratch.clear();
+        (&mut self.reader).take(n as u64).read_to_end(&mut self.scratch)?;
+
+        if self.scratch.len() != n {
+            bail!(UnexpectedEof, "expected {n} remaining bytes, got {}", self.scratch.len());
+        }
+
+        Ok(Bytes::Transient(&self.scratch))
+    }
+
+    fn skip(&mut self, n: usize) -> anyhow::Result<()> {
+        // Skipped bytes are discarded directly, rather than being copied into the scratch buffer.
+        let skipped = io::copy(&mut (&mut self.reader).take(n as u64), &mut io::sink())?;
+        if skipped != n as u64 {
+            bail!(UnexpectedEof, "expected {n} remaining bytes, got {skipped}");
+        }
+
+        Ok(())
+    }
+}
+
+/// [`Input`] that reads from multiple slices as if they were a single buffer.
+///
+/// This avoids concatenating data that is spread over several buffers, such as values that were split over
+/// multiple database entries. Strings and byte arrays that lie within a single slice are borrowed, while those
+/// that cross the boundary between two slices are copied.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  let data = nbt::to_le_bytes(&nbt::nbt!({ "name": "minecraft:stone" })).unwrap();
+///  let (first, second) = data.split_at(data.len() / 2);
+///
+///  let mut chain = nbt::Chain::new([first, second]);
+///  let (value, n): (nbt::Value, usize) = nbt::from_chain::<nbt::LittleEndian, _>(&mut chain).unwrap();
+///
+///  assert_eq!(value["name"].as_str(), Some("minecraft:stone"));
+///  assert_eq!(n, data.len());
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Chain<'de> {
+    /// Slices that have not been fully read yet.
+    slices: VecDeque<&'de [u8]>,
+    /// Holds the bytes of the last [`read_bytes`](Input::read_bytes) call if they crossed slices.
+    scratch: Vec<u8>,
+}
+
+impl<'de> Chain<'de> {
+    /// Creates an input that reads the slices in order.
+    pub fn new<I>(slices: I) -> Self
+    where
+        I: IntoIterator<Item = &'de [u8]>,
+    {
+        Self { slices: slices.into_iter().filter(|slice| !slice.is_empty()).collect(), scratch: Vec::new() }
+    }
+
+    /// Returns the amount of bytes that have not been read yet.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.slices.iter().map(|slice| slice.len()).sum()
+    }
+
+    /// Copies the next `buf.len()` bytes into `buf`, crossing slices where needed.
+    fn copy_to(&mut self, mut buf: &mut [u8]) -> anyhow::Result<()> {
+        while !buf.is_empty() {
+            let Some(front) = self.slices.front_mut() else {
+                bail!(UnexpectedEof, "expected {} more bytes, but reached the end of the input", buf.len());
+            };
+
+            let n = front.len().min(buf.len());
+            let (copied, rest) = std::mem::take(&mut buf).split_at_mut(n);
+            copied.copy_from_slice(&front[..n]);
+            buf = rest;
+
+            self.advance_front(n);
+        }
+
+        Ok(())
+    }
+
+    /// Advances past `n` bytes of the first slice, removing it once it is empty.
+    #[inline]
+    fn advance_front(&mut self, n: usize) {
+        if let Some(front) = self.slices.front_mut() {
+            *front = &front[n..];
+            if front.is_empty() {
+                self.slices.pop_front();
+            }
+        }
+    }
+}
+
+impl<'de> Input<'de> for Chain<'de> {
+    #[inline]
+    fn read_u8(&mut self) -> anyhow::Result<u8> {
+        let [b] = self.read_const()?;
+        Ok(b)
+    }
+
+    #[inline]
+    fn read_const<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.copy_to(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn read_bytes(&mut self, n: usize) -> anyhow::Result<Bytes<'de, '_>> {
+        if let Some(&front) = self.slices.front() {
+            if front.len() >= n {
+                self.advance_front(n);
+                return Ok(Bytes::Borrowed(&front[..n]))
+            }
+        }
+
+        let remaining = self.remaining();
+        if remaining < n {
+            bail!(UnexpectedEof, "expected {n} remaining bytes, got {remaining}");
+        }
+
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.resize(n, 0);
+        self.copy_to(&mut scratch)?;
+        self.scratch = scratch;
+
+        Ok(Bytes::Transient(&self.scratch))
+    }
+
+    fn skip(&mut self, mut n: usize) -> anyhow::Result<()> {
+        let remaining = self.remaining();
+        if remaining < n {
+            bail!(UnexpectedEof, "expected {n} remaining bytes, got {remaining}");
+        }
+
+        while n > 0 {
+            let Some(front) = self.slices.front() else { break };
+            let skipped = front.len().min(n);
+            self.advance_front(skipped);
+            n -= skipped;
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects serialised data and passes it on to an [`io::Write`] in chunks.
+///
+/// [`AsRef`] and [`AsMut`] give access to the data that has not been passed on yet.
+struct IoWrite<W: Write> {
+    writer: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> IoWrite<W> {
+    /// Passes all collected data on to the writer.
+    fn drain(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.buf)?;
+        self.buf.clear();
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for IoWrite<W> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= WRITE_CHUNK_SIZE {
+            self.drain()?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.drain()?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> AsRef<[u8]> for IoWrite<W> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl<W: Write> AsMut<[u8]> for IoWrite<W> {
+    #[inline]
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+/// Deserialises a single object while it is being read from the reader.
+///
+/// The reader is buffered internally, so it may have been read past the end of the object.
+/// Because the data does not outlive this call, the output cannot borrow from it.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+///  struct Data {
+///     value: String
+///  }
+///
+///  let data = Data { value: "Hello, World!".to_owned() };
+///  let mut file = Vec::new();
+///
+///  nbt::to_writer::<nbt::LittleEndian, _, _>(&mut file, &data).unwrap();
+///  let decoded: Data = nbt::from_reader::<nbt::LittleEndian, _, _>(file.as_slice()).unwrap();
+///
+///  assert_eq!(data, decoded);
+/// # }
+/// ```
+pub fn from_reader<F, T, R>(reader: R) -> anyhow::Result<T>
+where
+    F: VariantImpl,
+    T: DeserializeOwned,
+    R: Read,
+{
+    let mut input = IoRead { reader: BufReader::new(reader), scratch: Vec::new() };
+    let mut deserializer = Deserializer::<F, _>::new(&mut input)?;
+
+    Ok(T::deserialize(&mut deserializer)?)
+}
+
+/// Deserialises a single object from data that is spread over multiple slices.
+///
+/// On success, the deserialised object and amount of bytes read from the chain are returned.
+/// The chain is advanced past the object, so any data that follows it can still be read.
+/// See [`Chain`] for an example.
+pub fn from_chain<'de, F, T>(chain: &mut Chain<'de>) -> anyhow::Result<(T, usize)>
+where
+    F: VariantImpl + 'de,
+    T: Deserialize<'de>,
+{
+    let start = chain.remaining();
+    let mut deserializer = Deserializer::<F, _>::new(chain)?;
+    let output = T::deserialize(&mut deserializer)?;
+
+    Ok((output, start - chain.remaining()))
+}
+
+/// Serialises the given data while writing it to the writer.
+///
+/// Data is passed on to the writer in chunks, which is flushed once the entire object has been written.
+pub fn to_writer<F, W, T>(writer: W, value: &T) -> anyhow::Result<()>
+where
+    F: VariantImpl,
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut ser = Serializer::<_, F>::new(IoWrite { writer, buf: Vec::with_capacity(WRITE_CHUNK_SIZE) });
+    value.serialize(&mut ser)?;
+    ser.into_inner().flush()?;
+
+    Ok(())
+}
@@ -1,10 +1,16 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
+use util::corpus::check_corpus;
 use util::RVec;
 
 use crate::ser::to_be_bytes;
-use crate::{from_be_bytes, from_le_bytes, from_var_bytes, to_le_bytes, to_var_bytes, Value};
+use crate::{
+    from_be_bytes, from_chain, from_gzip_reader, from_le_bytes, from_reader, from_snbt, from_var_bytes, from_zlib_reader, to_gzip_writer, to_le_bytes,
+    to_var_bytes, to_writer, to_zlib_writer, BigEndian, Chain, Compound, Deserializer, Documents, Field, FieldType, Limits, ListMerge, LittleEndian,
+    NbtError, Schema, SchemaError, SchemaErrorKind, Value, Variable,
+};
 
 const BIG_TEST_NBT: &[u8] = include_bytes!("../test/bigtest.nbt");
 const HELLO_WORLD_NBT: &[u8] = include_bytes!("../test/hello_world.nbt");
@@ -37,7 +43,7 @@ fn read_write_option() {
 
 #[test]
 fn read_write_all() {
-    let value = Value::Compound(HashMap::from([
+    let value = Value::Compound(Compound::from([
         ("byte".to_owned(), Value::Byte(42)),
         ("short".to_owned(), Value::Short(42)),
         ("int".to_owned(), Value::Int(42)),
@@ -49,13 +55,13 @@ fn read_write_all() {
         (
             "list".to_owned(),
             Value::List(vec![
-                Value::Compound(HashMap::from([("name".to_owned(), Value::String("Compound 1".to_owned()))])),
-                Value::Compound(HashMap::from([("name".to_owned(), Value::String("Compound 2".to_owned()))])),
+                Value::Compound(Compound::from([("name".to_owned(), Value::String("Compound 1".to_owned()))])),
+                Value::Compound(Compound::from([("name".to_owned(), Value::String("Compound 2".to_owned()))])),
             ]),
         ),
         (
             "compound".to_owned(),
-            Value::Compound(HashMap::from([("name".to_owned(), Value::String("Compound 3".to_owned()))])),
+            Value::Compound(Compound::from([("name".to_owned(), Value::String("Compound 3".to_owned()))])),
         ),
     ]));
 
@@ -174,3 +180,860 @@ fn read_write_player() {
     let value_encoded = to_be_bytes(&decoded2).unwrap();
     let _value_decoded: Value = from_be_bytes(&mut value_encoded.as_ref()).unwrap().0;
 }
+
+#[test]
+fn corpus_le() {
+    check_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/le"), |mut input| from_le_bytes::<Value, _>(&mut input).map(|_| ()));
+}
+
+#[test]
+fn corpus_be() {
+    check_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/be"), |mut input| from_be_bytes::<Value, _>(&mut input).map(|_| ()));
+}
+
+#[test]
+fn corpus_var() {
+    check_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/var"), |mut input| from_var_bytes::<Value, _>(&mut input).map(|_| ()));
+}
+
+#[test]
+fn canonical_comparison() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use crate::Canonical;
+
+    fn hash(value: &Value) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        Canonical(value).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let written = Value::Compound(Compound::from([
+        ("ench".to_owned(), Value::IntArray(vec![9, 3])),
+        ("damage".to_owned(), Value::Float(-0.0)),
+        ("display".to_owned(), Value::Compound(Compound::from([("Name".to_owned(), Value::String("Sword".to_owned()))]))),
+    ]));
+
+    let ser = to_var_bytes(&Canonical(&written)).unwrap();
+    let read: Value = from_var_bytes(&mut ser.as_slice()).unwrap().0;
+    assert_eq!(written, read);
+
+    // An int array is only equal to a list of ints in canonical form.
+    let mut listed = read.clone();
+    if let Value::Compound(map) = &mut listed {
+        map.insert("ench".to_owned(), Value::List(vec![Value::Int(9), Value::Int(3)]));
+    }
+    assert_ne!(written, listed);
+    assert_eq!(Canonical(&written), Canonical(&listed));
+    assert_eq!(hash(&written), hash(&listed));
+
+    let le = to_le_bytes(&Canonical(&read)).unwrap();
+    let read_le: Value = from_le_bytes(&mut le.as_slice()).unwrap().0;
+    assert_eq!(Canonical(&read_le), Canonical(&written));
+
+    // Serialisation in canonical form does not depend on the order of the keys.
+    assert_eq!(ser, to_var_bytes(&Canonical(&read)).unwrap());
+
+    assert_ne!(Canonical(&Value::Byte(1)), Canonical(&Value::Int(1)));
+    assert_eq!(Canonical(&Value::Double(f64::NAN)), Canonical(&Value::Double(-f64::NAN)));
+}
+
+#[test]
+fn snbt() {
+    let value = from_snbt(
+        r#"{
+            Name: "Steve", 'quoted key': 'it\'s', Health: 20.5f, Exp: 3000000000L, Score: -7, Scale: 1.5,
+            Pos: [1.0d, 2.0d, -3.5d], Flags: [B; 1b, 0b], Ids: [I; 1, -2], Seeds: [L; 1L],
+            Enabled: true, Block: stone, Nested: {list: [], empty: {}}
+        }"#,
+    )
+    .unwrap();
+
+    let Value::Compound(compound) = &value else { panic!("SNBT was not parsed as a compound") };
+    assert_eq!(compound["Name"], "Steve");
+    assert_eq!(compound["quoted key"], "it's");
+    assert_eq!(compound["Health"], 20.5f32);
+    assert_eq!(compound["Exp"], 3_000_000_000i64);
+    assert_eq!(compound["Score"], -7i32);
+    assert_eq!(compound["Scale"], 1.5f64);
+    assert!(matches!(&compound["Pos"], Value::List(list) if list.len() == 3), "list was not parsed");
+    assert_eq!(compound["Flags"], &[1u8, 0][..]);
+    assert_eq!(compound["Ids"], &[1i32, -2][..]);
+    assert_eq!(compound["Seeds"], &[1i64][..]);
+    assert_eq!(compound["Enabled"], 1i8);
+    assert_eq!(compound["Block"], "stone");
+
+    assert_eq!(from_snbt(&value.to_snbt()).unwrap(), value);
+    assert_eq!(Value::String(r#"say "hi""#.to_owned()).to_snbt(), r#"'say "hi"'"#);
+
+    assert!(from_snbt("[1b, 2]").is_err(), "mixed list was accepted");
+    assert!(from_snbt("[B; 300]").is_err(), "out of range byte was accepted");
+    assert!(from_snbt("{a: 1").is_err(), "unterminated compound was accepted");
+    assert!(from_snbt("{a: 1} b").is_err(), "trailing data was accepted");
+    assert!(from_snbt(&"[".repeat(1000)).is_err(), "deeply nested list was accepted");
+}
+
+#[test]
+fn value_arrays() {
+    let value = Value::Compound(Compound::from([
+        ("bytes".to_owned(), Value::ByteArray(RVec::alloc_from_slice(&[1, 2, 3]))),
+        ("ints".to_owned(), Value::IntArray(vec![1, -2, 3])),
+        ("longs".to_owned(), Value::LongArray(vec![i64::MIN, 0, i64::MAX])),
+        ("empty_ints".to_owned(), Value::IntArray(Vec::new())),
+        ("empty_list".to_owned(), Value::List(Vec::new())),
+        ("nested".to_owned(), Value::List(vec![Value::IntArray(vec![4]), Value::IntArray(vec![5, 6])])),
+    ]));
+
+    let le = to_le_bytes(&value).unwrap();
+    assert_eq!(from_le_bytes::<Value, _>(&mut le.as_slice()).unwrap().0, value);
+    let be = to_be_bytes(&value).unwrap();
+    assert_eq!(from_be_bytes::<Value, _>(&mut be.as_slice()).unwrap().0, value);
+    let var = to_var_bytes(&value).unwrap();
+    assert_eq!(from_var_bytes::<Value, _>(&mut var.as_slice()).unwrap().0, value);
+
+    // Typed arrays can still be read into plain sequences.
+    #[derive(Deserialize)]
+    struct Arrays {
+        ints: Vec<i32>,
+        longs: Vec<i64>,
+    }
+
+    let arrays: Arrays = from_le_bytes(&mut le.as_slice()).unwrap().0;
+    assert_eq!(arrays.ints, [1, -2, 3]);
+    assert_eq!(arrays.longs, [i64::MIN, 0, i64::MAX]);
+}
+
+#[test]
+fn borrowed_strings() {
+    #[derive(Serialize, Deserialize)]
+    struct Block<'a> {
+        name: &'a str,
+        #[serde(borrow)]
+        namespace: Cow<'a, str>,
+        #[serde(borrow)]
+        states: HashMap<&'a str, &'a str>,
+    }
+
+    let block = Block {
+        name: "stone",
+        namespace: Cow::Borrowed("minecraft"),
+        states: HashMap::from([("stone_type", "granite")]),
+    };
+
+    let ser = to_le_bytes(&block).unwrap();
+    let de: Block = from_le_bytes(&mut ser.as_slice()).unwrap().0;
+    assert_eq!(de.name, "stone");
+    assert!(matches!(de.namespace, Cow::Borrowed("minecraft")), "string was copied");
+    assert_eq!(de.states["stone_type"], "granite");
+
+    // The strings point into the serialised data.
+    let range = ser.as_slice().as_ptr_range();
+    assert!(range.contains(&de.name.as_ptr()), "string does not borrow from the input");
+}
+
+#[test]
+fn borrowed_bytes() {
+    #[derive(Deserialize)]
+    struct Column<'a> {
+        biomes: &'a [u8],
+    }
+
+    let value = crate::nbt!({ "biomes": Value::ByteArray(RVec::from(vec![1, 2, 3, 4])) });
+    let ser = to_le_bytes(&value).unwrap();
+    let de: Column = from_le_bytes(&mut ser.as_slice()).unwrap().0;
+    assert_eq!(de.biomes, [1, 2, 3, 4]);
+
+    let range = ser.as_slice().as_ptr_range();
+    assert!(range.contains(&de.biomes.as_ptr()), "byte array does not borrow from the input");
+
+    let de: Column = crate::from_value(&value).unwrap();
+    assert_eq!(de.biomes, [1, 2, 3, 4]);
+}
+
+#[test]
+fn enums() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Facing {
+        North,
+        South,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Component {
+        Durability(i32),
+        Name { text: String, italic: bool },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Item {
+        facing: Facing,
+        components: Vec<Component>,
+    }
+
+    let item = Item {
+        facing: Facing::South,
+        components: vec![Component::Durability(5), Component::Name { text: "Pickaxe".to_owned(), italic: true }],
+    };
+
+    let ser = to_le_bytes(&item).unwrap();
+    let de: Item = from_le_bytes(&mut ser.as_slice()).unwrap().0;
+    assert_eq!(item, de);
+
+    // Unit variants are strings and data-carrying variants are single-key compounds.
+    let value: Value = from_le_bytes(&mut ser.as_slice()).unwrap().0;
+    assert_eq!(
+        value.to_snbt(),
+        "{components:[{Durability:5},{Name:{italic:1b,text:\"Pickaxe\"}}],facing:\"South\"}"
+    );
+
+    let root = Component::Durability(1);
+    let ser = to_var_bytes(&root).unwrap();
+    let de: Component = from_var_bytes(&mut ser.as_slice()).unwrap().0;
+    assert_eq!(root, de);
+
+    // Lists have a single element type, so unit variants cannot be mixed with other variants.
+    #[derive(Serialize)]
+    enum Tag {
+        Glowing,
+        Level(i32),
+    }
+
+    #[derive(Serialize)]
+    struct Tagged {
+        tags: Vec<Tag>,
+    }
+
+    assert!(to_le_bytes(&Tagged { tags: vec![Tag::Level(1), Tag::Glowing] }).is_err());
+    assert!(to_le_bytes(&Tagged { tags: vec![Tag::Glowing, Tag::Level(1)] }).is_err());
+    assert!(to_le_bytes(&Tagged { tags: vec![Tag::Glowing, Tag::Glowing] }).is_ok());
+    assert!(to_le_bytes(&Tagged { tags: vec![Tag::Level(1), Tag::Level(2)] }).is_ok());
+}
+
+#[test]
+fn compressed() {
+    let value: Value = from_be_bytes(&mut BIG_TEST_NBT.to_vec().as_slice()).unwrap().0;
+
+    let mut gzip = Vec::new();
+    to_gzip_writer::<BigEndian, _, _>(&mut gzip, &value).unwrap();
+    let decoded: Value = from_gzip_reader::<BigEndian, _, _>(gzip.as_slice()).unwrap();
+    assert_eq!(value, decoded);
+
+    let mut zlib = Vec::new();
+    to_zlib_writer::<LittleEndian, _, _>(&mut zlib, &value).unwrap();
+    let decoded: Value = from_zlib_reader::<LittleEndian, _, _>(zlib.as_slice()).unwrap();
+    assert_eq!(value, decoded);
+
+    assert!(from_gzip_reader::<BigEndian, Value, _>(zlib.as_slice()).is_err(), "zlib data was accepted as gzip");
+}
+
+#[test]
+fn streaming() {
+    let value: Value = from_be_bytes(&mut BIG_TEST_NBT.to_vec().as_slice()).unwrap().0;
+
+    let mut file = Vec::new();
+    to_writer::<BigEndian, _, _>(&mut file, &value).unwrap();
+    assert_eq!(file.as_slice(), to_be_bytes(&value).unwrap().as_ref());
+
+    // Read the data in tiny pieces to make sure nothing relies on it being contiguous.
+    let reader = std::io::BufReader::with_capacity(3, file.as_slice());
+    let decoded: Value = from_reader::<BigEndian, _, _>(reader).unwrap();
+    assert_eq!(value, decoded);
+
+    assert!(from_reader::<BigEndian, Value, _>(&file[..file.len() / 2]).is_err(), "truncated data was accepted");
+}
+
+#[test]
+fn chained() {
+    let value: Value = from_be_bytes(&mut BIG_TEST_NBT.to_vec().as_slice()).unwrap().0;
+    let data = to_le_bytes(&value).unwrap();
+
+    // Split the data at every possible size to cross slices in the middle of every field.
+    for size in 1..64 {
+        let mut chain = Chain::new(data.chunks(size));
+        let (decoded, n): (Value, usize) = from_chain::<LittleEndian, _>(&mut chain).unwrap();
+        assert_eq!(value, decoded);
+        assert_eq!(n, data.len());
+        assert_eq!(chain.remaining(), 0);
+    }
+
+    #[derive(Deserialize)]
+    struct Block<'a> {
+        name: &'a str,
+    }
+
+    // Strings inside a single slice are borrowed, while those that cross slices cannot be.
+    let data = to_le_bytes(&crate::nbt!({ "name": "minecraft:stone" })).unwrap();
+    let (first, second) = data.split_at(data.len() - 4);
+    assert!(from_chain::<LittleEndian, Block>(&mut Chain::new([first, second])).is_err(), "split string was borrowed");
+    let (block, _) = from_chain::<LittleEndian, Block>(&mut Chain::new([&data[..3], &data[3..]])).unwrap();
+    assert_eq!(block.name, "minecraft:stone");
+
+    let mut truncated = Chain::new([&data[..4], &data[4..data.len() - 1]]);
+    assert!(from_chain::<LittleEndian, Value>(&mut truncated).is_err(), "truncated data was accepted");
+}
+
+#[test]
+fn limits() {
+    // Lists nested 1000 levels deep.
+    let mut nested = vec![FieldType::List as u8, 0];
+    for _ in 0..1000 {
+        nested.extend([FieldType::List as u8, 2]);
+    }
+    nested.extend([FieldType::End as u8, 0]);
+    assert!(from_var_bytes::<Value, _>(&mut nested.as_slice()).is_err(), "nesting was not limited");
+
+    // An int list claiming to contain a billion elements.
+    let mut forged = vec![FieldType::List as u8, 0, FieldType::Int as u8];
+    forged.extend([0x80, 0xa8, 0xd6, 0xb9, 0x07]);
+    assert!(from_var_bytes::<Value, _>(&mut forged.as_slice()).is_err(), "element count was not limited");
+
+    let value = from_snbt(r#"{name:"Steve",data:[I;1,2,3,4]}"#).unwrap();
+    let ser = to_le_bytes(&value).unwrap();
+
+    let limits = Limits { max_bytes: 16, ..Limits::DISK };
+    let mut reader = ser.as_slice();
+    let mut de = Deserializer::<LittleEndian, _>::with_limits(&mut reader, limits).unwrap();
+    assert!(Value::deserialize(&mut de).is_err(), "size was not limited");
+
+    let limits = Limits { max_bytes: 32, ..Limits::DISK };
+    let mut reader = ser.as_slice();
+    let mut de = Deserializer::<LittleEndian, _>::with_limits(&mut reader, limits).unwrap();
+    assert_eq!(Value::deserialize(&mut de).unwrap(), value);
+}
+
+#[cfg(feature = "preserve-order")]
+#[test]
+fn preserve_order() {
+    let value = from_snbt("{zebra:1b,apple:2b,mango:3b}").unwrap();
+    let ser = to_le_bytes(&value).unwrap();
+    let de: Value = from_le_bytes(&mut ser.as_slice()).unwrap().0;
+
+    let Value::Compound(map) = de else { panic!("expected a compound") };
+    assert!(map.keys().eq(["zebra", "apple", "mango"]), "order was not preserved");
+
+    // Serialising again produces the exact same bytes.
+    assert_eq!(to_le_bytes(&Value::Compound(map)).unwrap().as_ref(), ser.as_ref());
+}
+
+#[test]
+fn nbt_macro() {
+    let name = "Steve";
+    let value = crate::nbt!({
+        "name": name,
+        "health": 20.0f32,
+        "onGround": true,
+        "level": -3,
+        "pos": [1.5, 64.0, -2.5],
+        "inventory": [
+            { "id": "minecraft:stone", "count": 64u8 },
+            { "id": format!("minecraft:{}", "dirt"), "count": 1u8 },
+        ],
+        "empty": {},
+        "uuid": vec![1, 2, 3, 4],
+    });
+
+    let expected = from_snbt(
+        r#"{name:"Steve",health:20f,onGround:1b,level:-3,pos:[1.5d,64d,-2.5d],
+        inventory:[{id:"minecraft:stone",count:64b},{id:"minecraft:dirt",count:1b}],empty:{},uuid:[I;1,2,3,4]}"#,
+    )
+    .unwrap();
+    assert_eq!(value, expected);
+    assert_eq!(crate::nbt!([]), Value::List(Vec::new()));
+}
+
+#[test]
+fn path_queries() {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Entry<'a> {
+        #[serde(rename = "Name")]
+        name: &'a str,
+        #[serde(rename = "Properties")]
+        properties: Option<HashMap<String, String>>,
+    }
+
+    let mut value = crate::nbt!({
+        "Level": {
+            "Sections": [
+                { "Y": 0i8, "Palette": [{ "Name": "minecraft:air" }] },
+                { "Y": 1i8, "Palette": [{ "Name": "minecraft:stone", "Properties": { "variant": "granite" } }] },
+            ],
+            "Heights": vec![64, 65],
+        }
+    });
+
+    assert_eq!(value.get_path("Level.Sections[1].Palette[0].Name"), Some(&Value::from("minecraft:stone")));
+    assert_eq!(value.get_path("Level.Sections[2]"), None);
+    assert_eq!(value.get_path("Level.Sections.Y"), None);
+    assert_eq!(value.get_path("Level..Sections"), None);
+    assert_eq!(value.get_path("Level.Sections[x]"), None);
+
+    assert_eq!(value.get_as::<i8>("Level.Sections[1].Y").unwrap(), 1);
+    assert_eq!(value.get_as::<Vec<i32>>("Level.Heights").unwrap(), [64, 65]);
+    let entry: Entry = value.get_as("Level.Sections[1].Palette[0]").unwrap();
+    assert_eq!(entry.name, "minecraft:stone");
+    assert_eq!(entry.properties, Some(HashMap::from([("variant".to_owned(), "granite".to_owned())])));
+    assert!(value.get_as::<String>("Level.Sections[1].Y").is_err(), "byte was deserialised as a string");
+    assert!(value.get_as::<i8>("Level.Missing").is_err(), "missing value was deserialised");
+
+    let Some(y) = value.get_path_mut("Level.Sections[0].Y") else { panic!("section not found") };
+    *y = Value::Byte(-1);
+    assert_eq!(value.get_as::<i8>("Level.Sections[0].Y").unwrap(), -1);
+
+    assert_eq!(crate::from_value::<Value>(&value).unwrap(), value);
+}
+
+#[test]
+fn typed_arrays() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Chunk {
+        heightmap: Vec<i32>,
+        states: Vec<i64>,
+        empty: Vec<i32>,
+        version: [i32; 3],
+        names: Vec<String>,
+    }
+
+    let chunk = Chunk {
+        heightmap: vec![64, 65, 66],
+        states: vec![i64::MIN, 0, i64::MAX],
+        empty: Vec::new(),
+        version: [1, 20, 0],
+        names: vec!["minecraft:stone".to_owned()],
+    };
+
+    let encoded = to_be_bytes(&chunk).unwrap();
+    let mut value: Value = from_be_bytes(&mut encoded.as_ref()).unwrap().0;
+    let Some(map) = value.as_compound() else { panic!("chunk is not a compound") };
+
+    assert_eq!(map["heightmap"].as_i32_array(), Some([64, 65, 66].as_slice()));
+    assert_eq!(map["states"].as_i64_array(), Some([i64::MIN, 0, i64::MAX].as_slice()));
+    assert_eq!(map["empty"].as_i32_array(), Some([].as_slice()));
+    assert!(map["version"].is_list(), "fixed-size array is not a list");
+    assert!(map["names"].is_list(), "vector of strings is not a list");
+
+    let decoded: Chunk = from_be_bytes(&mut encoded.as_ref()).unwrap().0;
+    assert_eq!(decoded, chunk);
+
+    // Lists of ints in a value remain lists.
+    let list = crate::nbt!({ "list": [1, 2, 3] });
+    let encoded = to_be_bytes(&list).unwrap();
+    assert_eq!(from_be_bytes::<Value, _>(&mut encoded.as_ref()).unwrap().0, list);
+
+    let encoded = to_be_bytes(&value).unwrap();
+    assert_eq!(from_be_bytes::<Value, _>(&mut encoded.as_ref()).unwrap().0, value);
+
+    let Some(heightmap) = value.get_path_mut("heightmap").and_then(Value::as_i32_array_mut) else { panic!("heightmap not found") };
+    heightmap.push(67);
+    assert_eq!(value.get_as::<Vec<i32>>("heightmap").unwrap(), [64, 65, 66, 67]);
+}
+
+#[test]
+fn nested_typed_arrays() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Sections {
+        heightmaps: Vec<Vec<i32>>,
+        states: Vec<Vec<i64>>,
+        names: Vec<Vec<String>>,
+    }
+
+    let sections = Sections {
+        heightmaps: vec![vec![1], Vec::new()],
+        states: vec![vec![i64::MAX], Vec::new(), vec![0, 1]],
+        names: vec![vec!["minecraft:stone".to_owned()], Vec::new()],
+    };
+
+    let encoded = to_be_bytes(&sections).unwrap();
+    let value: Value = from_be_bytes(&mut encoded.as_ref()).unwrap().0;
+    let Some(map) = value.as_compound() else { panic!("sections is not a compound") };
+
+    let Some(heightmaps) = map["heightmaps"].as_list() else { panic!("heightmaps is not a list") };
+    assert!(heightmaps.iter().all(Value::is_i32_array), "heightmaps are not int arrays");
+    let Some(names) = map["names"].as_list() else { panic!("names is not a list") };
+    assert!(names.iter().all(Value::is_list), "names are not lists");
+
+    let decoded: Sections = from_be_bytes(&mut encoded.as_ref()).unwrap().0;
+    assert_eq!(decoded, sections);
+
+    // A leading empty vector is an int array, so it cannot be followed by lists.
+    let sections = Sections { heightmaps: Vec::new(), states: Vec::new(), names: vec![Vec::new(), vec!["minecraft:stone".to_owned()]] };
+    assert!(to_be_bytes(&sections).is_err());
+}
+
+#[test]
+fn merge_and_patch() {
+    let mut block = crate::nbt!({
+        "name": "minecraft:log",
+        "states": { "pillar_axis": "y", "old_log_type": "oak" },
+        "tags": ["wood"],
+    });
+
+    let mut merged = block.clone();
+    merged.merge(&crate::nbt!({ "states": { "pillar_axis": "x" }, "tags": ["log"] }));
+    assert_eq!(
+        merged,
+        crate::nbt!({ "name": "minecraft:log", "states": { "pillar_axis": "x", "old_log_type": "oak" }, "tags": ["log"] })
+    );
+
+    let mut appended = block.clone();
+    appended.merge_with(&crate::nbt!({ "tags": ["log"], "version": 1 }), ListMerge::Append);
+    assert_eq!(appended.get_path("tags"), Some(&crate::nbt!(["wood", "log"])));
+    assert_eq!(appended.get_path("version"), Some(&Value::Int(1)));
+
+    let mut list = crate::nbt!([{ "a": 1 }, { "b": 2 }]);
+    list.merge_with(&crate::nbt!([{ "c": 3 }, { "b": 4 }, { "d": 5 }]), ListMerge::ByIndex);
+    assert_eq!(list, crate::nbt!([{ "a": 1, "c": 3 }, { "b": 4 }, { "d": 5 }]));
+
+    let target = crate::nbt!({ "name": "minecraft:log", "states": { "pillar_axis": "z" }, "version": 2 });
+    assert_eq!(block.diff(&block), None);
+    let Some(patch) = block.diff(&target) else { panic!("values are not equal") };
+    block.apply(&patch).unwrap();
+    assert_eq!(block, target);
+
+    let Some(patch) = target.diff(&crate::nbt!({ "states": { "pillar_axis": "x" } })) else { panic!("values are not equal") };
+    assert!(Value::Int(0).apply(&patch).is_err(), "compound patch was applied to an int");
+}
+
+#[test]
+fn serialized_size() {
+    let mut data = BIG_TEST_NBT;
+    let value: Value = from_be_bytes(&mut data).unwrap().0;
+
+    assert_eq!(crate::serialized_size::<BigEndian, _>(&value).unwrap(), to_be_bytes(&value).unwrap().len());
+    assert_eq!(crate::serialized_size::<LittleEndian, _>(&value).unwrap(), to_le_bytes(&value).unwrap().len());
+    assert_eq!(crate::serialized_size::<Variable, _>(&value).unwrap(), to_var_bytes(&value).unwrap().len());
+
+    let arrays = crate::nbt!({ "ints": vec![1, 2, 3], "longs": vec![4i64, 5], "empty": [] });
+    assert_eq!(crate::serialized_size::<Variable, _>(&arrays).unwrap(), to_var_bytes(&arrays).unwrap().len());
+}
+
+#[test]
+fn java_network() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename = "")]
+    struct Text {
+        text: String,
+        bold: bool,
+    }
+
+    let text = Text { text: "Hello, World!".to_owned(), bold: true };
+    let named = to_be_bytes(&text).unwrap();
+    let unnamed = crate::to_java_net_bytes(&text).unwrap();
+
+    // Only the empty root name, which is a 16-bit length, is missing.
+    assert_eq!(unnamed.len() + 2, named.len());
+    assert_eq!(unnamed[0], named[0]);
+    assert_eq!(unnamed[1..], named[3..]);
+
+    let (decoded, n): (Text, _) = crate::from_java_net_bytes(&mut unnamed.as_ref()).unwrap();
+    assert_eq!(decoded, text);
+    assert_eq!(n, unnamed.len());
+}
+
+#[test]
+fn skip_unknown_fields() {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Small {
+        last: i32,
+    }
+
+    let value = crate::nbt!({
+        "string": "minecraft:stone",
+        "bytes": Value::ByteArray(RVec::alloc_from_slice(&[1, 2, 3])),
+        "ints": vec![i32::MIN, -1, 300],
+        "longs": vec![i64::MAX, 0],
+        "list": [{ "a": 1i8, "b": [1.5f32, 2.5f32] }, { "c": [[], ["d"]] }],
+        "empty": [],
+        "compound": { "nested": { "x": 1i16, "y": 2.0 } },
+        "last": -7,
+    });
+
+    let encoded = to_le_bytes(&value).unwrap();
+    let (small, n): (Small, _) = from_le_bytes(&mut encoded.as_ref()).unwrap();
+    assert_eq!(small, Small { last: -7 });
+    assert_eq!(n, encoded.len());
+
+    let encoded = to_var_bytes(&value).unwrap();
+    let (small, n): (Small, _) = from_var_bytes(&mut encoded.as_ref()).unwrap();
+    assert_eq!(small, Small { last: -7 });
+    assert_eq!(n, encoded.len());
+
+    let encoded = to_be_bytes(&value).unwrap();
+    let small: Small = from_reader::<BigEndian, _, _>(encoded.as_ref()).unwrap();
+    assert_eq!(small, Small { last: -7 });
+
+    // Skipped values are still subject to the limits.
+    let limits = Limits { max_elements: 2, ..Limits::NETWORK };
+    let encoded = to_var_bytes(&value).unwrap();
+    let mut input = encoded.as_ref();
+    let mut de = Deserializer::<Variable, _>::with_limits(&mut input, limits).unwrap();
+    assert!(Small::deserialize(&mut de).is_err(), "skipped list exceeded the element limit");
+}
+
+#[test]
+fn flatten_and_tagged() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Position {
+        x: i32,
+        y: i16,
+        z: i64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(tag = "id")]
+    enum Entity {
+        #[serde(rename = "minecraft:pig")]
+        Pig {
+            #[serde(deserialize_with = "crate::deserialize_bool")]
+            saddled: bool,
+            pos: Position,
+        },
+        #[serde(rename = "minecraft:sheep")]
+        Sheep { color: i8, wool: Vec<i64> },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Chunk {
+        name: String,
+        heights: Vec<i32>,
+        #[serde(deserialize_with = "crate::deserialize_bool")]
+        populated: bool,
+        entities: Vec<Entity>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Column {
+        #[serde(flatten)]
+        pos: Position,
+        #[serde(flatten)]
+        chunk: Chunk,
+        #[serde(flatten)]
+        extra: Compound,
+    }
+
+    let column = Column {
+        pos: Position { x: 1, y: 2, z: 3 },
+        chunk: Chunk {
+            name: "chunk".to_owned(),
+            heights: vec![64, 65, 66],
+            populated: true,
+            entities: vec![
+                Entity::Pig { saddled: true, pos: Position { x: 4, y: 5, z: 6 } },
+                Entity::Sheep { color: 3, wool: vec![7, 8] },
+            ],
+        },
+        extra: Compound::from_iter([("unknown".to_owned(), Value::Double(4.0))]),
+    };
+
+    let encoded = to_le_bytes(&column).unwrap();
+    let decoded: Column = from_le_bytes(&mut encoded.as_ref()).unwrap().0;
+    assert_eq!(decoded, column);
+
+    let encoded = to_var_bytes(&column).unwrap();
+    let decoded: Column = from_var_bytes(&mut encoded.as_ref()).unwrap().0;
+    assert_eq!(decoded, column);
+
+    // Values still tell typed arrays and lists apart.
+    let value: Value = from_var_bytes(&mut encoded.as_ref()).unwrap().0;
+    assert_eq!(value.get_path("heights"), Some(&Value::IntArray(vec![64, 65, 66])));
+    assert_eq!(value.get_path("entities[1].wool"), Some(&Value::LongArray(vec![7, 8])));
+    assert_eq!(crate::from_value::<Column>(&value).unwrap(), column);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json() {
+    use crate::{from_json, to_json, JsonHints};
+
+    let value = crate::nbt!({
+        "name": "minecraft:stone",
+        "count": 64i8,
+        "damage": 1.5f32,
+        "heights": vec![1, 2],
+        "tags": ["a", "b"],
+        "nan": f64::NAN,
+    });
+    let json = to_json(&value);
+    assert_eq!(
+        json,
+        serde_json::json!({ "name": "minecraft:stone", "count": 64, "damage": 1.5, "heights": [1, 2], "tags": ["a", "b"], "nan": null })
+    );
+
+    let json = serde_json::json!({ "count": 64, "big": 1i64 << 40, "mixed": [1, 2.5], "flags": [true, 300], "heights": [1, 2] });
+    assert_eq!(
+        from_json(&json, JsonHints::default()).unwrap(),
+        crate::nbt!({ "count": 64, "big": 1i64 << 40, "mixed": [1.0, 2.5], "flags": [1, 300], "heights": [1, 2] })
+    );
+
+    let hints = JsonHints { suffixes: true, floats: true, typed_arrays: true };
+    let json = serde_json::json!({ "count": "64b", "health": "20s", "time": "7L", "name": "64", "speed": 0.5, "heights": [1, 1i64 << 40] });
+    assert_eq!(
+        from_json(&json, hints).unwrap(),
+        crate::nbt!({ "count": 64i8, "health": 20i16, "time": 7i64, "name": "64", "speed": 0.5f32, "heights": vec![1i64, 1 << 40] })
+    );
+
+    assert!(from_json(&serde_json::json!({ "a": null }), hints).is_err(), "null was converted");
+    assert!(from_json(&serde_json::json!([1, "a"]), hints).is_err(), "list with different types was converted");
+    assert!(from_json(&serde_json::json!(u64::MAX), hints).is_err(), "integer larger than a long was converted");
+}
+
+#[test]
+fn index() {
+    let mut value = crate::nbt!({
+        "name": "minecraft:chest",
+        "Items": [{ "Slot": 0i8, "id": "minecraft:stone" }, { "Slot": 1i8, "id": "minecraft:dirt" }],
+    });
+
+    assert_eq!(value["name"].as_str(), Some("minecraft:chest"));
+    assert_eq!(value["Items"][1]["Slot"].as_i8(), Some(1));
+    assert_eq!(value.get("Items").and_then(|items| items.get(0)).and_then(|item| item.get("id".to_owned())), Some(&Value::from("minecraft:stone")));
+    assert_eq!(value.get("Missing"), None);
+    assert_eq!(value.get(0), None);
+    assert_eq!(value["Items"].get(2), None);
+    assert_eq!(value["Items"].get("Slot"), None);
+
+    value["Items"][0]["id"] = Value::from("minecraft:granite");
+    let Some(items) = value.get_mut("Items").and_then(Value::as_list_mut) else { panic!("items not found") };
+    items.pop();
+    let Some(map) = value.as_compound_mut() else { panic!("value is not a compound") };
+    map.insert("name".to_owned(), Value::from("minecraft:barrel"));
+
+    assert_eq!(value, crate::nbt!({ "name": "minecraft:barrel", "Items": [{ "Slot": 0i8, "id": "minecraft:granite" }] }));
+}
+
+#[test]
+#[should_panic(expected = "does not contain")]
+fn index_missing() {
+    let value = crate::nbt!({ "name": "minecraft:chest" });
+    let _: &Value = &value["Items"];
+}
+
+#[test]
+fn documents() {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct BlockEntity {
+        id: String,
+    }
+
+    let mut buffer = Vec::new();
+    for id in ["Chest", "Furnace", "Sign"] {
+        crate::to_var_bytes_in(&mut buffer, &crate::nbt!({ "id": id, "x": 1 })).unwrap();
+    }
+
+    let documents = Documents::<Variable>::new(&buffer).collect::<anyhow::Result<Vec<_>>>().unwrap();
+    assert_eq!(documents.len(), 3);
+    assert_eq!(documents.iter().map(|(_, n)| n).sum::<usize>(), buffer.len());
+    assert_eq!(documents[2].0["id"], Value::from("Sign"));
+
+    let entities = Documents::<Variable, BlockEntity>::new(&buffer).map(|document| document.map(|(entity, _)| entity.id));
+    assert_eq!(entities.collect::<anyhow::Result<Vec<_>>>().unwrap(), ["Chest", "Furnace", "Sign"]);
+
+    // The iterator ends after a malformed document.
+    buffer.push(0xff);
+    let mut documents = Documents::<Variable>::new(&buffer);
+    assert_eq!(documents.by_ref().filter(Result::is_ok).count(), 3);
+    assert!(documents.next().is_none(), "iterator continued after an error");
+}
+
+#[test]
+fn schema() {
+    let item = Schema::Compound(vec![Field::required("Name", Schema::String), Field::required("Count", Schema::Byte)]);
+    let schema = Schema::Compound(vec![
+        Field::required("id", Schema::String),
+        Field::optional("CustomName", Schema::String),
+        Field::required("Items", Schema::list(item)),
+    ]);
+
+    let chest = crate::nbt!({ "id": "Chest", "Items": [{ "Name": "minecraft:stone", "Count": 64i8 }], "x": 1 });
+    assert_eq!(chest.validate(&schema), Ok(()));
+
+    let chest = crate::nbt!({ "CustomName": 5, "Items": [{ "Name": "minecraft:stone", "Count": 64i8 }, { "Count": 1 }] });
+    let Err(errors) = chest.validate(&schema) else { panic!("invalid chest passed validation") };
+    assert_eq!(
+        errors,
+        [
+            SchemaError { path: "id".to_owned(), kind: SchemaErrorKind::Missing },
+            SchemaError { path: "CustomName".to_owned(), kind: SchemaErrorKind::Type { expected: "string", found: "int" } },
+            SchemaError { path: "Items[1].Name".to_owned(), kind: SchemaErrorKind::Missing },
+            SchemaError { path: "Items[1].Count".to_owned(), kind: SchemaErrorKind::Type { expected: "byte", found: "int" } },
+        ]
+    );
+
+    let Err(errors) = crate::nbt!([1, 2]).validate(&schema) else { panic!("list passed validation") };
+    assert_eq!(errors[0].to_string(), "Expected compound at the root, found list");
+}
+
+#[test]
+fn level_dat() {
+    let settings = crate::nbt!({ "LevelName": "Bedrock level", "StorageVersion": 10 });
+    let file = crate::write_level_dat(10, &settings).unwrap();
+    assert_eq!(u32::from_le_bytes([file[4], file[5], file[6], file[7]]) as usize, file.len() - 8);
+
+    let (version, decoded): (u32, Value) = crate::read_level_dat(&mut file.as_slice()).unwrap();
+    assert_eq!(version, 10);
+    assert_eq!(decoded, settings);
+
+    let truncated = &file[..file.len() - 1];
+    assert!(crate::read_level_dat::<Value, _>(&mut &*truncated).is_err(), "header length was not checked");
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn arbitrary_round_trip() {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    // Feeds deterministic pseudo-random data to the generator, like a fuzzer would.
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let data = (0..1 << 16)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect::<Vec<_>>();
+
+    for chunk in data.chunks(512) {
+        let value = Value::arbitrary(&mut Unstructured::new(chunk)).unwrap();
+        crate::check_round_trip(&value).unwrap();
+    }
+
+    let nan = crate::nbt!({ "list": [f32::NAN, 1.0f32], "array": vec![1i64, 2] });
+    crate::check_round_trip(&nan).unwrap();
+}
+
+#[test]
+fn error_path() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Section {
+        #[serde(rename = "Palette")]
+        palette: Vec<HashMap<String, String>>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Level {
+        #[serde(rename = "Sections")]
+        sections: Vec<Section>,
+    }
+
+    let value = crate::nbt!({
+        "Sections": [
+            { "Palette": [{ "Name": "minecraft:air" }] },
+            { "Palette": [{ "Name": "minecraft:stone" }, { "Name": 1 }] },
+        ],
+    });
+    let data = to_le_bytes(&value).unwrap();
+
+    let Err(err) = from_le_bytes::<Level, _>(&mut data.as_slice()) else { panic!("invalid palette was accepted") };
+    let Some(err) = err.downcast_ref::<NbtError>() else { panic!("error is not an NbtError") };
+    assert_eq!(err.path(), "Sections[1].Palette[1].Name");
+    assert!(err.to_string().ends_with(" at `Sections[1].Palette[1].Name`"), "path is missing from {err}");
+
+    // Errors at the root have no path.
+    let data = [FieldType::Compound as u8, 0, 0, FieldType::End as u8];
+    let Err(err) = from_le_bytes::<Level, _>(&mut data.as_slice()) else { panic!("missing field was accepted") };
+    let Some(err) = err.downcast_ref::<NbtError>() else { panic!("error is not an NbtError") };
+    assert_eq!(err.path(), "");
+}
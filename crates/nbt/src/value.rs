@@ -1,12 +1,28 @@
-use std::collections::HashMap;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
 use serde::de::{MapAccess, SeqAccess, Visitor};
-use serde::ser::{SerializeMap, SerializeSeq};
+use serde::ser::{SerializeMap, SerializeTuple};
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use util::RVec;
 
+use crate::{INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN, VALUE_TOKEN};
+
+/// Map of the entries of a [`Value::Compound`].
+///
+/// Entries are stored in a [`HashMap`](std::collections::HashMap) by default, which does not preserve their order.
+/// With the `preserve-order` feature, an [`IndexMap`](indexmap::IndexMap) is used instead
+/// so that compounds are written back in the order they were read or inserted in.
+#[cfg(not(feature = "preserve-order"))]
+pub type Compound = std::collections::HashMap<String, Value>;
+
+/// Map of the entries of a [`Value::Compound`].
+///
+/// Entries are stored in an [`IndexMap`](indexmap::IndexMap) because the `preserve-order` feature is enabled.
+/// Compounds are therefore written back in the order they were read or inserted in.
+#[cfg(feature = "preserve-order")]
+pub type Compound = indexmap::IndexMap<String, Value>;
+
 /// General NBT value type that can represent any value.
 ///
 /// In case the structure of some piece of NBT data is not known, this
@@ -36,7 +52,7 @@ pub enum Value {
     /// List of an arbitrary NBT value.
     List(Vec<Value>),
     /// Key-value map.
-    Compound(HashMap<String, Value>),
+    Compound(Compound),
     /// An array of integers.
     IntArray(Vec<i32>),
     /// An array of longs.
@@ -224,6 +240,14 @@ impl Value {
         }
     }
 
+    /// If this [`Value`] is a string, represent it as `&str`. Returns None otherwise.
+    ///
+    /// This is the same as [`as_string`](Value::as_string).
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_string()
+    }
+
     /// If this [`Value`] is a list, represent it as `&[Value]`. Returns None otherwise.
     #[inline]
     pub fn as_list(&self) -> Option<&[Value]> {
@@ -235,7 +259,25 @@ impl Value {
 
     /// If this [`Value`] is a compound/map, returns the map. Returns None otherwise.
     #[inline]
-    pub const fn as_compound(&self) -> Option<&HashMap<String, Value>> {
+    pub const fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Value::Compound(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// If this [`Value`] is a list, return a mutable reference to it. Returns None otherwise.
+    #[inline]
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// If this [`Value`] is a compound/map, return a mutable reference to the map. Returns None otherwise.
+    #[inline]
+    pub fn as_compound_mut(&mut self) -> Option<&mut Compound> {
         match self {
             Value::Compound(v) => Some(v),
             _ => None,
@@ -259,6 +301,24 @@ impl Value {
             _ => None,
         }
     }
+
+    /// If this [`Value`] is an integer array, return a mutable reference to it. Returns None otherwise.
+    #[inline]
+    pub fn as_i32_array_mut(&mut self) -> Option<&mut Vec<i32>> {
+        match self {
+            Value::IntArray(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// If this [`Value`] is a long array, return a mutable reference to it. Returns None otherwise.
+    #[inline]
+    pub fn as_i64_array_mut(&mut self) -> Option<&mut Vec<i64>> {
+        match self {
+            Value::LongArray(v) => Some(v),
+            _ => None,
+        }
+    }
 }
 
 impl PartialEq<Value> for Value {
@@ -470,23 +530,23 @@ impl<'a> PartialEq<&[Value]> for &'a mut Value {
     }
 }
 
-impl PartialEq<&HashMap<String, Value>> for Value {
+impl PartialEq<&Compound> for Value {
     #[inline]
-    fn eq(&self, rhs: &&HashMap<String, Value>) -> bool {
+    fn eq(&self, rhs: &&Compound) -> bool {
         self.as_compound().map_or(false, |lhs| lhs == *rhs)
     }
 }
 
-impl<'a> PartialEq<&HashMap<String, Value>> for &'a Value {
+impl<'a> PartialEq<&Compound> for &'a Value {
     #[inline]
-    fn eq(&self, rhs: &&HashMap<String, Value>) -> bool {
+    fn eq(&self, rhs: &&Compound) -> bool {
         self.as_compound().map_or(false, |lhs| lhs == *rhs)
     }
 }
 
-impl<'a> PartialEq<&HashMap<String, Value>> for &'a mut Value {
+impl<'a> PartialEq<&Compound> for &'a mut Value {
     #[inline]
-    fn eq(&self, rhs: &&HashMap<String, Value>) -> bool {
+    fn eq(&self, rhs: &&Compound) -> bool {
         self.as_compound().map_or(false, |lhs| lhs == *rhs)
     }
 }
@@ -533,36 +593,65 @@ impl<'a> PartialEq<&[i64]> for &'a mut Value {
     }
 }
 
+/// Implements [`From`] for types that map directly onto a variant.
+macro_rules! impl_from {
+    ($($ty: ty => $variant: ident),+) => {$(
+        impl From<$ty> for Value {
+            #[inline]
+            fn from(v: $ty) -> Value {
+                Value::$variant(v)
+            }
+        }
+    )+}
+}
+
+impl_from!(
+    i8 => Byte,
+    i16 => Short,
+    i32 => Int,
+    i64 => Long,
+    f32 => Float,
+    f64 => Double,
+    String => String,
+    Vec<Value> => List,
+    Compound => Compound,
+    Vec<i32> => IntArray,
+    Vec<i64> => LongArray
+);
+
+impl From<u8> for Value {
+    /// Stores the byte as a [`Byte`](Value::Byte), reinterpreting it as signed.
+    #[inline]
+    fn from(v: u8) -> Value {
+        Value::Byte(v as i8)
+    }
+}
+
+impl From<bool> for Value {
+    /// Stores the boolean as a [`Byte`](Value::Byte) that is either 0 or 1, which is how NBT represents booleans.
+    #[inline]
+    fn from(v: bool) -> Value {
+        Value::Byte(v as i8)
+    }
+}
+
+impl From<&str> for Value {
+    #[inline]
+    fn from(v: &str) -> Value {
+        Value::String(v.to_owned())
+    }
+}
+
 impl Hash for Value {
+    /// Hashes the value in canonical form, see [`Canonical`](crate::Canonical).
+    ///
+    /// This makes the hash of a compound independent of the order of its keys.
+    #[inline]
     fn hash<H>(&self, state: &mut H)
     where
         H: Hasher,
     {
-        match self {
-            Value::Byte(v) => state.write_i8(*v),
-            Value::Short(v) => state.write_i16(*v),
-            Value::Int(v) => state.write_i32(*v),
-            Value::Long(v) => state.write_i64(*v),
-            Value::String(v) => state.write(v.as_bytes()),
-            Value::Float(v) => {
-                // f32 does not implement Hash so simply hash the byte repr.
-                state.write(&v.to_le_bytes());
-            }
-            Value::Double(v) => {
-                // f64 does not implement Hash so simply hash the byte repr.
-                state.write(&v.to_le_bytes());
-            }
-            Value::Compound(map) => {
-                for (k, v) in map {
-                    state.write(k.as_bytes());
-                    v.hash(state);
-                }
-            }
-            Value::List(v) => Self::hash_slice(v, state),
-            Value::ByteArray(v) => u8::hash_slice(v, state),
-            Value::IntArray(v) => i32::hash_slice(v, state),
-            Value::LongArray(v) => i64::hash_slice(v, state),
-        }
+        self.canonical_hash(state);
     }
 }
 
@@ -572,21 +661,23 @@ impl<'de> Deserialize<'de> for Value {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_any(ValueVisitor)
+        deserializer.deserialize_newtype_struct(VALUE_TOKEN, ValueVisitor)
     }
 }
 
 #[inline]
-fn serialize_seq<T, S>(ser: S, seq: &[T]) -> anyhow::Result<S::Ok, S::Error>
+/// Serialises a list.
+///
+/// This uses a tuple rather than a sequence, because sequences of ints and longs are written as typed arrays.
+fn serialize_list<S>(ser: S, list: &[Value]) -> anyhow::Result<S::Ok, S::Error>
 where
-    T: Serialize,
     S: Serializer,
 {
-    let mut seq_ser = ser.serialize_seq(Some(seq.len()))?;
-    for element in seq {
-        seq_ser.serialize_element(element)?;
+    let mut tuple_ser = ser.serialize_tuple(list.len())?;
+    for element in list {
+        tuple_ser.serialize_element(element)?;
     }
-    seq_ser.end()
+    tuple_ser.end()
 }
 
 impl Serialize for Value {
@@ -603,7 +694,7 @@ impl Serialize for Value {
             Value::Double(double) => ser.serialize_f64(*double),
             Value::ByteArray(array) => ser.serialize_bytes(array),
             Value::String(string) => ser.serialize_str(string),
-            Value::List(seq) => serialize_seq(ser, seq),
+            Value::List(list) => serialize_list(ser, list),
             Value::Compound(map) => {
                 let mut map_ser = ser.serialize_map(Some(map.len()))?;
                 for (k, v) in map {
@@ -611,8 +702,8 @@ impl Serialize for Value {
                 }
                 map_ser.end()
             }
-            Value::IntArray(seq) => serialize_seq(ser, seq),
-            Value::LongArray(seq) => serialize_seq(ser, seq),
+            Value::IntArray(seq) => ser.serialize_newtype_struct(INT_ARRAY_TOKEN, seq),
+            Value::LongArray(seq) => ser.serialize_newtype_struct(LONG_ARRAY_TOKEN, seq),
         }
     }
 }
@@ -627,6 +718,15 @@ impl<'de> Visitor<'de> for ValueVisitor {
         formatter.write_str("any valid NBT value")
     }
 
+    /// Called by deserialisers that do not know about [`VALUE_TOKEN`].
+    #[inline]
+    fn visit_newtype_struct<D>(self, deserializer: D) -> anyhow::Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
     #[inline]
     fn visit_bool<E>(self, v: bool) -> anyhow::Result<Self::Value, E>
     where
@@ -729,15 +829,95 @@ impl<'de> Visitor<'de> for ValueVisitor {
     where
         A: MapAccess<'de>,
     {
-        let mut out: HashMap<String, Value> = HashMap::new();
+        let mut out = Compound::new();
         if let Some(hint) = map.size_hint() {
             out.reserve(hint);
         }
 
-        while let Some((key, value)) = map.next_entry()? {
-            out.insert(key, value);
+        while let Some(key) = map.next_key::<String>()? {
+            // Typed arrays are presented as a map with a single entry.
+            if out.is_empty() {
+                match key.as_str() {
+                    INT_ARRAY_TOKEN => return Ok(Value::IntArray(map.next_value()?)),
+                    LONG_ARRAY_TOKEN => return Ok(Value::LongArray(map.next_value()?)),
+                    _ => (),
+                }
+            }
+
+            out.insert(key, map.next_value()?);
         }
 
         Ok(Value::Compound(out))
     }
 }
+
+/// Constructs a [`Value`] from a JSON-like literal.
+///
+/// Compounds are written as `{"key": value, ...}` and lists as `[value, ...]`. Any other value is an expression
+/// that is converted with [`Value::from`], so the type of a number determines its tag: `1u8` or `true` becomes a
+/// byte, `1i16` a short and `1` an int.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  let burn = true;
+///  let value = nbt::nbt!({
+///     "name": "minecraft:bedrock",
+///     "states": {
+///         "infiniburn_bit": burn
+///     },
+///     "version": 17_959_425,
+///     "tags": ["solid", "unbreakable"]
+///  });
+///
+///  assert_eq!(value, nbt::from_snbt(r#"{name:"minecraft:bedrock",states:{infiniburn_bit:1b},version:17959425,tags:["solid","unbreakable"]}"#).unwrap());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! nbt {
+    // Collects the tokens of a compound value up to the next comma.
+    (@compound $map: ident ()) => {};
+    (@compound $map: ident () $key: tt : $($rest: tt)*) => {
+        $crate::nbt!(@entry $map ($key) () $($rest)*);
+    };
+    (@entry $map: ident ($key: tt) ($($value: tt)+) , $($rest: tt)*) => {
+        $map.insert(::std::string::String::from($key), $crate::nbt!($($value)+));
+        $crate::nbt!(@compound $map () $($rest)*);
+    };
+    (@entry $map: ident ($key: tt) ($($value: tt)+)) => {
+        $map.insert(::std::string::String::from($key), $crate::nbt!($($value)+));
+    };
+    (@entry $map: ident ($key: tt) ($($value: tt)*) $next: tt $($rest: tt)*) => {
+        $crate::nbt!(@entry $map ($key) ($($value)* $next) $($rest)*);
+    };
+
+    // Collects the tokens of a list element up to the next comma.
+    (@list [$($elems: expr,)*] ()) => {
+        ::std::vec![$($elems),*]
+    };
+    (@list [$($elems: expr,)*] ($($value: tt)+) , $($rest: tt)*) => {
+        $crate::nbt!(@list [$($elems,)* $crate::nbt!($($value)+),] () $($rest)*)
+    };
+    (@list [$($elems: expr,)*] ($($value: tt)+)) => {
+        $crate::nbt!(@list [$($elems,)* $crate::nbt!($($value)+),] ())
+    };
+    (@list [$($elems: expr,)*] ($($value: tt)*) $next: tt $($rest: tt)*) => {
+        $crate::nbt!(@list [$($elems,)*] ($($value)* $next) $($rest)*)
+    };
+
+    ({ $($tt: tt)* }) => {{
+        #[allow(unused_mut)]
+        let mut map = $crate::Compound::new();
+        $crate::nbt!(@compound map () $($tt)*);
+        $crate::Value::Compound(map)
+    }};
+    ([ $($tt: tt)* ]) => {
+        $crate::Value::List($crate::nbt!(@list [] () $($tt)*))
+    };
+    ($value: expr) => {
+        $crate::Value::from($value)
+    };
+}
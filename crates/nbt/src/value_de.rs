@@ -0,0 +1,185 @@
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::{de, Deserialize};
+
+use crate::{NbtError, Value, INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN, VALUE_TOKEN};
+
+/// Deserialises a type from a [`Value`].
+///
+/// Strings in the output can borrow from the value.
+///
+/// # Example
+///
+/// ```rust
+/// # use mirai_nbt as nbt;
+/// #
+/// # fn main() {
+///  #[derive(serde::Deserialize)]
+///  struct Block<'a> {
+///     name: &'a str,
+///     version: i32
+///  }
+///
+///  let value = nbt::nbt!({ "name": "minecraft:stone", "version": 17_959_425 });
+///  let block: Block = nbt::from_value(&value).unwrap();
+///
+///  assert_eq!(block.name, "minecraft:stone");
+/// # }
+/// ```
+pub fn from_value<'de, T>(value: &'de Value) -> Result<T, NbtError>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(value)
+}
+
+impl<'de> IntoDeserializer<'de, NbtError> for &'de Value {
+    type Deserializer = Self;
+
+    #[inline]
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &'de Value {
+    type Error = NbtError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Byte(v) => visitor.visit_i8(*v),
+            Value::Short(v) => visitor.visit_i16(*v),
+            Value::Int(v) => visitor.visit_i32(*v),
+            Value::Long(v) => visitor.visit_i64(*v),
+            Value::Float(v) => visitor.visit_f32(*v),
+            Value::Double(v) => visitor.visit_f64(*v),
+            Value::ByteArray(v) => visitor.visit_borrowed_bytes(v),
+            Value::String(v) => visitor.visit_borrowed_str(v),
+            Value::List(v) => visitor.visit_seq(SeqDeserializer::new(v.iter())),
+            Value::Compound(v) => visitor.visit_map(MapDeserializer::new(v.iter().map(|(k, v)| (k.as_str(), v)))),
+            Value::IntArray(_) | Value::LongArray(_) => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Byte(v) => visitor.visit_bool(*v != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        // Missing fields are the only way to represent `None`.
+        visitor.visit_some(self)
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        // Typed arrays are presented in the same way as by the binary deserialiser.
+        match self {
+            Value::IntArray(_) if name == VALUE_TOKEN => visitor.visit_map(TypedArray { token: Some(INT_ARRAY_TOKEN), value: self }),
+            Value::LongArray(_) if name == VALUE_TOKEN => visitor.visit_map(TypedArray { token: Some(LONG_ARRAY_TOKEN), value: self }),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::ByteArray(v) => visitor.visit_seq(SeqDeserializer::new(v.iter().map(|b| *b as i8))),
+            Value::IntArray(v) => visitor.visit_seq(SeqDeserializer::new(v.iter().copied())),
+            Value::LongArray(v) => visitor.visit_seq(SeqDeserializer::new(v.iter().copied())),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::String(v) => visitor.visit_enum(v.as_str().into_deserializer()),
+            Value::Compound(v) if v.len() == 1 => {
+                let map = MapDeserializer::new(v.iter().map(|(k, v)| (k.as_str(), v)));
+                visitor.visit_enum(de::value::MapAccessDeserializer::new(map))
+            }
+            _ => Err(de::Error::custom("expected a string or a compound with a single entry containing an enum variant")),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Presents a typed array as a map with a single entry, whose key is the token of the array type.
+struct TypedArray<'de> {
+    /// Key of the entry, `None` once it has been read.
+    token: Option<&'static str>,
+    value: &'de Value,
+}
+
+impl<'de> MapAccess<'de> for TypedArray<'de> {
+    type Error = NbtError;
+
+    #[inline]
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, NbtError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        self.token.take().map(|token| seed.deserialize(token.into_deserializer())).transpose()
+    }
+
+    #[inline]
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, NbtError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(ArrayElements(self.value))
+    }
+}
+
+/// Deserialises the elements of a typed array as a sequence, regardless of the requested type.
+struct ArrayElements<'de>(&'de Value);
+
+impl<'de> de::Deserializer<'de> for ArrayElements<'de> {
+    type Error = NbtError;
+
+    #[inline]
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, NbtError>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self.0, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
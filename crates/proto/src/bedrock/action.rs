@@ -1,4 +1,4 @@
-use macros::variant_count;
+use macros::try_from_repr;
 use util::{BinaryRead};
 use util::{BlockPosition, Deserialize};
 use crate::bedrock::ConnectedPacket;
@@ -6,7 +6,7 @@ use crate::bedrock::ConnectedPacket;
 /// Action to perform.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(i32)]
-#[variant_count]
+#[try_from_repr]
 pub enum PlayerActionType {
     /// Started breaking a block.
     StartBreak,
@@ -84,23 +84,6 @@ pub enum PlayerActionType {
     ReceivedServerData
 }
 
-impl TryFrom<i32> for PlayerActionType {
-    type Error = anyhow::Error;
-
-    fn try_from(value: i32) -> anyhow::Result<PlayerActionType> {
-        if value <= PlayerActionType::variant_count() as i32 {
-            // SAFETY: This is safe because the discriminant is in range and
-            // the representations are the same. Additionally, none of the enum members
-            // have a manually assigned value (this is ensured by the `variant_count` macro).
-            Ok(unsafe {
-                std::mem::transmute::<i32, PlayerActionType>(value)
-            })
-        } else {
-            anyhow::bail!("Player action type out of range, must be <=36, is: {value}")
-        }
-    }
-}
-
 /// Performs a player action.
 #[derive(Debug)]
 pub struct PlayerAction {
@@ -5,6 +5,7 @@ use util::{BinaryWrite};
 
 use crate::bedrock::{AbilityData, DeviceOS};
 use crate::bedrock::{ConnectedPacket, GameMode};
+use crate::types::{Angle, HeadYaw};
 
 
 /// Type of an entity link.
@@ -32,6 +33,22 @@ pub struct EntityLink {
     pub is_immediate: bool,
     /// Whether the link was initiated by the rider.
     pub is_rider_initiated: bool,
+    /// Angular velocity of the vehicle that the rider is riding.
+    pub vehicle_angular_velocity: f32,
+}
+
+impl EntityLink {
+    /// Creates a link between two entities that was initiated by the server.
+    pub const fn new(link_type: EntityLinkType, ridden_entity_id: i64, rider_entity_id: i64) -> EntityLink {
+        EntityLink {
+            link_type,
+            ridden_entity_id,
+            rider_entity_id,
+            is_immediate: false,
+            is_rider_initiated: false,
+            vehicle_angular_velocity: 0.0,
+        }
+    }
 }
 
 impl Serialize for EntityLink {
@@ -40,7 +57,8 @@ impl Serialize for EntityLink {
         writer.write_var_i64(self.rider_entity_id)?;
         writer.write_u8(self.link_type as u8)?;
         writer.write_bool(self.is_immediate)?;
-        writer.write_bool(self.is_rider_initiated)
+        writer.write_bool(self.is_rider_initiated)?;
+        writer.write_f32_le(self.vehicle_angular_velocity)
     }
 }
 
@@ -59,9 +77,12 @@ pub struct AddPlayer<'a> {
     pub position: Vector<f32, 3>,
     /// Initial velocity.
     pub velocity: Vector<f32, 3>,
-    /// Initial rotation.
-    /// The third component is head yaw.
-    pub rotation: Vector<f32, 3>,
+    /// Initial pitch.
+    pub pitch: Angle,
+    /// Initial yaw of the body.
+    pub yaw: Angle,
+    /// Initial yaw of the head.
+    pub head_yaw: HeadYaw,
     /// Game mode of the player.
     pub game_mode: GameMode,
     /// Item held by the player.
@@ -78,6 +99,37 @@ pub struct AddPlayer<'a> {
     pub device_os: DeviceOS,
 }
 
+impl<'a> AddPlayer<'a> {
+    /// Creates a packet that adds a stationary player at the given position.
+    ///
+    /// The player is not rotated, not linked to any entities and has no device information.
+    /// Use the struct update syntax to change the remaining fields.
+    pub fn new(
+        uuid: Uuid,
+        username: &'a str,
+        runtime_id: u64,
+        position: Vector<f32, 3>,
+        game_mode: GameMode,
+        ability_data: AbilityData
+    ) -> AddPlayer<'a> {
+        AddPlayer {
+            uuid,
+            username,
+            runtime_id,
+            position,
+            velocity: Vector::from([0.0; 3]),
+            pitch: Angle::default(),
+            yaw: Angle::default(),
+            head_yaw: HeadYaw::default(),
+            game_mode,
+            ability_data,
+            links: &[],
+            device_id: "",
+            device_os: DeviceOS::Dedicated,
+        }
+    }
+}
+
 impl ConnectedPacket for AddPlayer<'_> {
     const ID: u32 = 0x0c;
 }
@@ -90,7 +142,9 @@ impl Serialize for AddPlayer<'_> {
         writer.write_str("")?; // Platform chat ID
         writer.write_vecf(&self.position)?;
         writer.write_vecf(&self.velocity)?;
-        writer.write_vecf(&self.rotation)?;
+        writer.write_f32_le(self.pitch.degrees())?;
+        writer.write_f32_le(self.yaw.degrees())?;
+        writer.write_f32_le(self.head_yaw.degrees())?;
         // self.held_item.serialize(buffer)?;
         writer.write_var_i32(self.game_mode as i32)?;
         // buffer.put_metadata(&self.metadata);
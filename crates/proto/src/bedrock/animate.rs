@@ -1,6 +1,6 @@
 use util::{bail};
-use util::{BinaryRead};
-use util::Deserialize;
+use util::{BinaryRead, BinaryWrite};
+use util::{Deserialize, Serialize};
 
 use crate::bedrock::ConnectedPacket;
 
@@ -74,3 +74,16 @@ impl<'a> Deserialize<'a> for Animate {
         Ok(Self { action_type, runtime_id, rowing_time })
     }
 }
+
+impl Serialize for Animate {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_var_i32(self.action_type as i32)?;
+        writer.write_var_u64(self.runtime_id)?;
+
+        if self.action_type.is_rowing() {
+            writer.write_f32_be(self.rowing_time)?;
+        }
+
+        Ok(())
+    }
+}
@@ -21,12 +21,12 @@ impl<'a> Deserialize<'a> for CacheBlobStatus {
         let miss_count = reader.read_var_u32()?;
         let hit_count = reader.read_var_u32()?;
 
-        let mut misses = Vec::with_capacity(miss_count as usize);
+        let mut misses = Vec::with_capacity((miss_count as usize).min(reader.remaining()));
         for _ in 0..miss_count {
             misses.push(reader.read_u64_le()?);
         }
 
-        let mut hits = Vec::with_capacity(hit_count as usize);
+        let mut hits = Vec::with_capacity((hit_count as usize).min(reader.remaining()));
         for _ in 0..hit_count {
             hits.push(reader.read_u64_le()?);
         }
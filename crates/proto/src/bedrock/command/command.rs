@@ -1,10 +1,12 @@
-use macros::variant_count;
+use macros::try_from_repr;
 
 /// A permission level within the command system.
 /// Commands use permission levels separate from the standard permission levels.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+///
+/// Levels are ordered from least to most privileged.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u8)]
-#[variant_count]
+#[try_from_repr]
 pub enum CommandPermissionLevel {
     Normal,
     GameDirectors,
@@ -14,23 +16,6 @@ pub enum CommandPermissionLevel {
     Internal,
 }
 
-impl TryFrom<u8> for CommandPermissionLevel {
-    type Error = anyhow::Error;
-
-    fn try_from(value: u8) -> anyhow::Result<CommandPermissionLevel> {
-        if value <= CommandPermissionLevel::variant_count() as u8 {
-            // SAFETY: This is safe because the discriminant is in range and
-            // the representations are the same. Additionally, none of the enum members
-            // have a manually assigned value (this is ensured by the `variant_count` macro).
-            Ok(unsafe {
-                std::mem::transmute::<u8, CommandPermissionLevel>(value)
-            })
-        } else {
-            anyhow::bail!("Command permission level out of range, expect <=5, got {value}")
-        }
-    }
-}
-
 /// Used for autocompletion.
 ///
 /// This object contains the list of available options.
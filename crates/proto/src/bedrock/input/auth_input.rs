@@ -1,4 +1,4 @@
-use macros::variant_count;
+use macros::try_from_repr;
 
 use util::{Deserialize, BinaryRead, Vector, BlockPosition};
 
@@ -50,12 +50,15 @@ pub enum InputDataFlag {
     StopCrawling = 1 << 40,
     StartFlying = 1 << 41,
     StopFlying = 1 << 42,
-    AcknowledgeServerData = 1 << 43
+    AcknowledgeServerData = 1 << 43,
+    ClientPredictedVehicle = 1 << 44,
+    PaddlingLeft = 1 << 45,
+    PaddlingRight = 1 << 46
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u32)]
-#[variant_count]
+#[try_from_repr]
 pub enum PlayMode {
     Normal,
     Teaser,
@@ -68,22 +71,9 @@ pub enum PlayMode {
     ExitLevelLivingRoom
 }
 
-impl TryFrom<u32> for PlayMode {
-    type Error = anyhow::Error;
-    
-    fn try_from(v: u32) -> anyhow::Result<Self> {
-        if v <= Self::variant_count() as u32 {
-            // SAFETY: This is safe because the enum has a `u32` repr and the discriminant is in range.
-            Ok(unsafe { std::mem::transmute::<u32, Self>(v) })
-        } else {
-            anyhow::bail!("Play mode out of range ({v} > {})", Self::variant_count());
-        }
-    }
-}
-
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u32)]
-#[variant_count]
+#[try_from_repr]
 pub enum InputMode {
     Mouse = 1,
     Touch,
@@ -91,45 +81,15 @@ pub enum InputMode {
     MotionController
 }
 
-impl TryFrom<u32> for InputMode {
-    type Error = anyhow::Error;
-
-    fn try_from(v: u32) -> anyhow::Result<InputMode> {
-        if v >= 1 && v <= InputMode::variant_count() as u32 {
-            // SAFETY: This is safe because the discriminant is in range and
-            // the representations are the same. Additionally, none of the enum members
-            // have a manually assigned value (this is ensured by the `variant_count` macro).
-            Ok(unsafe { std::mem::transmute::<u32, InputMode>(v) })
-        } else {
-            anyhow::bail!("Input mode out of range")
-        }
-    }
-}
-
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(i32)]
-#[variant_count]
+#[try_from_repr]
 pub enum InteractionModel {
     Touch,
     Crosshair,
     Classic
 }
 
-impl TryFrom<i32> for InteractionModel {
-    type Error = anyhow::Error;
-
-    fn try_from(v: i32) -> anyhow::Result<InteractionModel> {
-        if v <= InteractionModel::variant_count() as i32 {
-            // SAFETY: This is safe because the discriminant is in range and
-            // the representations are the same. Additionally, none of the enum members
-            // have a manually assigned value (this is ensured by the `variant_count` macro).
-            Ok(unsafe { std::mem::transmute::<i32, InteractionModel>(v) })
-        } else {
-            anyhow::bail!("Interaction model out of range")
-        }
-    }
-}
-
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u32)]
 pub enum InventoryActionSource {
@@ -335,7 +295,7 @@ impl<'a> Deserialize<'a> for TransactionData<'a> {
         }
 
         let action_count = reader.read_var_u32()?;
-        let mut actions = Vec::with_capacity(action_count as usize);
+        let mut actions = Vec::with_capacity((action_count as usize).min(reader.remaining()));
 
         for _ in 0..action_count {
             actions.push(InventoryAction::deserialize_from(reader)?);
@@ -347,7 +307,7 @@ impl<'a> Deserialize<'a> for TransactionData<'a> {
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(i32)]
-#[variant_count]
+#[try_from_repr]
 pub enum FilterCause {
     ServerChatPublic,
     ServerChatWhisper,
@@ -365,21 +325,6 @@ pub enum FilterCause {
     SummonCommand
 }
 
-impl TryFrom<i32> for FilterCause {
-    type Error = anyhow::Error;
-
-    fn try_from(v: i32) -> anyhow::Result<FilterCause> {
-        if v <= FilterCause::variant_count() as i32 {
-            // SAFETY: This is safe because the discriminant is in range and
-            // the representations are the same. Additionally, none of the enum members
-            // have a manually assigned value (this is ensured by the `variant_count` macro).
-            Ok(unsafe { std::mem::transmute::<i32, FilterCause>(v) })
-        } else {
-            anyhow::bail!("Filter cause variant out of range ({v} >= {})", Self::variant_count())
-        }
-    }
-}
-
 #[derive(Debug)]
 pub enum ItemDescriptor<'a> {
     Invalid,
@@ -642,13 +587,13 @@ impl<'a> Deserialize<'a> for StackRequest<'a> {
         let request_id = reader.read_var_i32()?;
 
         let actions_count = reader.read_var_u32()?;
-        let mut actions = Vec::with_capacity(actions_count as usize);
+        let mut actions = Vec::with_capacity((actions_count as usize).min(reader.remaining()));
         for _ in 0..actions_count {
             actions.push(StackRequestAction::deserialize_from(reader)?);
         }
 
         let filter_count = reader.read_var_u32()?;
-        let mut filters = Vec::with_capacity(filter_count as usize);
+        let mut filters = Vec::with_capacity((filter_count as usize).min(reader.remaining()));
         for _ in 0..filter_count {
             filters.push(reader.read_str()?);
         }
@@ -722,7 +667,10 @@ impl InputData {
         StopCrawling ,
         StartFlying ,
         AcknowledgeServerData,
-        StopFlying
+        StopFlying,
+        ClientPredictedVehicle,
+        PaddlingLeft,
+        PaddlingRight
     );
 }
 
@@ -760,7 +708,12 @@ pub struct PlayerAuthInput<'a> {
     /// Item stack requests that were performed in the last tick.
     pub item_stack: Option<StackRequest<'a>>,
     /// Block actions that were performed in the last tick.
-    pub block_actions: Option<Vec<PlayerAction>>
+    pub block_actions: Option<Vec<PlayerAction>>,
+    /// Rotation of the vehicle the player is riding.
+    /// This is only set when the client is predicting the movement of its vehicle.
+    pub vehicle_rotation: Option<Vector<f32, 2>>,
+    /// Unique ID of the vehicle that the client is predicting the movement of.
+    pub client_predicted_vehicle: Option<i64>
 }
 
 impl ConnectedPacket for PlayerAuthInput<'_> {
@@ -791,11 +744,19 @@ impl<'a> Deserialize<'a> for PlayerAuthInput<'a> {
         let item_transaction = input_data.perform_item_transaction().then(|| TransactionData::deserialize_from(reader)).transpose()?;
         let item_stack = input_data.perform_item_stack_request().then(|| todo!());
         let block_actions = input_data.perform_block_actions().then(|| todo!());
+
+        let (vehicle_rotation, client_predicted_vehicle) = if input_data.client_predicted_vehicle() {
+            (Some(reader.read_vecf()?), Some(reader.read_var_i64()?))
+        } else {
+            (None, None)
+        };
+
         let analogue_moved = reader.read_vecf()?;
         
         Ok(Self {
             pitch, yaw, head_yaw, position, moved, analogue_moved, input_data, input_mode, play_mode,
-            interaction_model, gaze_direction, tick, delta, item_transaction, item_stack, block_actions
+            interaction_model, gaze_direction, tick, delta, item_transaction, item_stack, block_actions,
+            vehicle_rotation, client_predicted_vehicle
         })
     }
 }
\ No newline at end of file
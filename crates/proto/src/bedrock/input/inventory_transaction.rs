@@ -1,4 +1,4 @@
-use std::{collections::HashMap, sync::atomic::{AtomicI32, Ordering}};
+use std::sync::atomic::{AtomicI32, Ordering};
 
 use util::{BinaryRead, BinaryWrite, BlockPosition, Deserialize, RVec, Serialize, Vector};
 
@@ -380,7 +380,7 @@ pub struct ItemInstance<'a> {
     pub metadata: u32,
     pub stack_id: Option<i32>,
     pub block_runtime_id: i32,
-    pub nbt: HashMap<String, nbt::Value>,
+    pub nbt: nbt::Compound,
     pub can_place_on: Vec<&'a str>,
     pub can_destroy: Vec<&'a str>,
     pub blocking_tick: i64
@@ -388,7 +388,7 @@ pub struct ItemInstance<'a> {
 
 impl<'a> ItemInstance<'a> {
     /// This is a function instead of a constant due to the
-    /// non-constness of HashMap::new.
+    /// non-constness of Compound::new.
     #[inline(always)]
     pub fn air() -> ItemInstance<'a> {
         ItemInstance {
@@ -397,12 +397,25 @@ impl<'a> ItemInstance<'a> {
             metadata: 0,
             stack_id: None,
             block_runtime_id: 0,
-            nbt: HashMap::new(),
+            nbt: nbt::Compound::new(),
             can_place_on: vec![],
             can_destroy: vec![],
             blocking_tick: 0
         }
     }
+
+    /// Whether this item can be merged into the same stack as `other`.
+    ///
+    /// Item NBT is compared in canonical form, so differences in key order or array representation
+    /// do not prevent items from stacking. The count and stack ID are ignored.
+    pub fn stacks_with(&self, other: &ItemInstance) -> bool {
+        self.network_id == other.network_id
+            && self.metadata == other.metadata
+            && self.block_runtime_id == other.block_runtime_id
+            && self.can_place_on == other.can_place_on
+            && self.can_destroy == other.can_destroy
+            && nbt::compound_eq(&self.nbt, &other.nbt)
+    }
 }
 
 impl<'a> Serialize for ItemInstance<'a> {
@@ -504,13 +517,13 @@ impl<'a> Deserialize<'a> for ItemInstance<'a> {
             extra_reader.advance(n)?;
             nbt
         } else {
-            HashMap::new()
+            nbt::Compound::new()
         };
         // tracing::debug!("NBT: {nbt:?}");
 
         let can_place_on_len = extra_reader.read_u32_le()?;
         // tracing::debug!("Can place entries: {can_place_on_len}");
-        let mut can_place_on = Vec::with_capacity(can_place_on_len as usize);
+        let mut can_place_on = Vec::with_capacity((can_place_on_len as usize).min(extra_reader.remaining()));
         for _ in 0..can_place_on_len {
             let str_len = extra_reader.read_u16_le()?;
             let name = std::str::from_utf8(extra_reader.take_n(str_len as usize)?)?;
@@ -520,7 +533,7 @@ impl<'a> Deserialize<'a> for ItemInstance<'a> {
 
         let can_destroy_len = extra_reader.read_u32_le()?;
         // tracing::debug!("Can break entries: {can_destroy_len}");
-        let mut can_destroy = Vec::with_capacity(can_destroy_len as usize);
+        let mut can_destroy = Vec::with_capacity((can_destroy_len as usize).min(extra_reader.remaining()));
         for _ in 0..can_destroy_len {
             let str_len = extra_reader.read_u16_le()?;
             let name = std::str::from_utf8(extra_reader.take_n(str_len as usize)?)?;
@@ -587,7 +600,7 @@ impl<'a> Deserialize<'a> for InventoryTransaction<'a> {
         // tracing::debug!("legacy_request_id: {legacy_request_id}");
 
         let legacy_transaction_len = if legacy_request_id == 0 { 0 } else { reader.read_var_u32()? };
-        let mut legacy_transactions = Vec::with_capacity(legacy_transaction_len as usize);
+        let mut legacy_transactions = Vec::with_capacity((legacy_transaction_len as usize).min(reader.remaining()));
         for _ in 0..legacy_transaction_len {
             legacy_transactions.push(LegacyTransactionEntry::deserialize_from(reader)?);
         }
@@ -596,7 +609,7 @@ impl<'a> Deserialize<'a> for InventoryTransaction<'a> {
         let transaction_type = reader.read_var_u32()?;
         
         let actions_len = reader.read_var_u32()?;
-        let mut actions = Vec::with_capacity(actions_len as usize);
+        let mut actions = Vec::with_capacity((actions_len as usize).min(reader.remaining()));
         for _ in 0..actions_len {
             actions.push(TransactionAction::deserialize_from(reader)?);
         }
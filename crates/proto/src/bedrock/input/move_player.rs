@@ -3,6 +3,7 @@ use util::{Deserialize, Serialize};
 use util::{BinaryRead, BinaryWrite, size_of_varint};
 
 use crate::bedrock::ConnectedPacket;
+use crate::types::{Angle, HeadYaw};
 
 /// How the player has moved.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -69,11 +70,11 @@ pub struct MovePlayer {
     /// Where the player moved.
     pub translation: Vector<f32, 3>,
     /// Pitch of the player.
-    pub pitch: f32,
+    pub pitch: Angle,
     /// Yaw of the player.
-    pub yaw: f32,
+    pub yaw: Angle,
     /// Yaw of the head of the player.
-    pub head_yaw: f32,
+    pub head_yaw: HeadYaw,
     /// The mode that was used for movement.
     pub mode: MovementMode,
     /// Whether the player is touching the ground.
@@ -108,9 +109,9 @@ impl Serialize for MovePlayer {
     fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
         writer.write_var_u64(self.runtime_id)?;
         writer.write_vecf(&self.translation)?;
-        writer.write_f32_le(self.pitch)?;
-        writer.write_f32_le(self.yaw)?;
-        writer.write_f32_le(self.head_yaw)?;
+        writer.write_f32_le(self.pitch.degrees())?;
+        writer.write_f32_le(self.yaw.degrees())?;
+        writer.write_f32_le(self.head_yaw.degrees())?;
         writer.write_u8(self.mode as u8)?;
         writer.write_bool(self.on_ground)?;
         writer.write_var_u64(self.ridden_runtime_id)?;
@@ -128,9 +129,9 @@ impl<'a> Deserialize<'a> for MovePlayer {
     fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
         let runtime_id = reader.read_var_u64()?;
         let position = reader.read_vecf()?;
-        let pitch = reader.read_f32_le()?;
-        let yaw = reader.read_f32_le()?;
-        let head_yaw = reader.read_f32_le()?;
+        let pitch = Angle::from(reader.read_f32_le()?);
+        let yaw = Angle::from(reader.read_f32_le()?);
+        let head_yaw = HeadYaw::from(reader.read_f32_le()?);
         let mode = MovementMode::try_from(reader.read_u8()?)?;
         let on_ground = reader.read_bool()?;
         let ridden_runtime_id = reader.read_var_u64()?;
@@ -0,0 +1,26 @@
+use util::{BinaryWrite, Serialize};
+
+use crate::bedrock::{ConnectedPacket, ItemInstance, WindowId};
+
+/// Updates a single slot in one of the client's inventories.
+#[derive(Debug, Clone)]
+pub struct InventorySlot<'a> {
+    /// Inventory that contains the slot.
+    pub window_id: WindowId,
+    /// Index of the slot within the inventory.
+    pub slot: u32,
+    /// The new content of the slot.
+    pub item: ItemInstance<'a>,
+}
+
+impl<'a> ConnectedPacket for InventorySlot<'a> {
+    const ID: u32 = 0x32;
+}
+
+impl<'a> Serialize for InventorySlot<'a> {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_var_u32(Into::<i32>::into(self.window_id) as u32)?;
+        writer.write_var_u32(self.slot)?;
+        self.item.serialize_into(writer)
+    }
+}
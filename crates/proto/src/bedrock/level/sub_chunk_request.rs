@@ -19,7 +19,7 @@ impl<'a> Deserialize<'a> for SubChunkRequest {
         let position = reader.read_veci()?;
 
         let count = reader.read_u32_le()?;
-        let mut offsets = Vec::with_capacity(count as usize);
+        let mut offsets = Vec::with_capacity((count as usize).min(reader.remaining()));
         for _ in 0..count {
             offsets.push(reader.read_vecb()?);
         }
@@ -1,4 +1,3 @@
-use std::collections::HashMap;
 use std::io::Write;
 
 use util::{RString, RVec, Serialize};
@@ -76,7 +75,7 @@ pub struct ItemStack {
     pub item_type: ItemType,
     pub block_runtime_id: i32,
     pub count: u16,
-    pub nbt_data: HashMap<String, nbt::Value>,
+    pub nbt_data: nbt::Compound,
     pub can_place_on: Vec<String>,
     pub can_destroy: Vec<String>
 }
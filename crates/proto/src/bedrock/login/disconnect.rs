@@ -1,4 +1,5 @@
-use util::{BinaryWrite, VarString};
+use macros::try_from_repr;
+use util::{BinaryRead, BinaryWrite, Deserialize, VarInt, VarString};
 
 use util::Serialize;
 
@@ -15,6 +16,8 @@ pub const DISCONNECTED_BAD_PACKET: &str = "Client sent bad packet.";
 
 /// Reason why the client was disconnected.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(i32)]
+#[try_from_repr]
 pub enum DisconnectReason {
     Unknown,
     NoInternet,
@@ -104,16 +107,21 @@ pub enum DisconnectReason {
     ConnInactivityTimeout,
     StaleConnectionBeingReplaced,
     RealmsSessionNotFound,
-    BadPacket
+    BadPacket,
 }
 
 /// Sent by the server to disconnect a client.
+///
+/// Clients also send this packet when they leave the game, in which case the reason explains why.
 #[derive(Debug, Clone)]
 pub struct Disconnect<'a> {
+    /// Why the client is disconnected.
     pub reason: DisconnectReason,
     /// Whether to immediately send the client to the main menu.
     pub hide_message: bool,
     /// Message to display to the client
+    ///
+    /// This is not sent if the message is hidden.
     pub message: &'a str,
 }
 
@@ -121,20 +129,35 @@ impl ConnectedPacket for Disconnect<'_> {
     const ID: u32 = 0x05;
 
     fn serialized_size(&self) -> usize {
-        1 + self.message.var_len()
+        (self.reason as i32).var_len() + 1 + if self.hide_message { 0 } else { self.message.var_len() }
     }
 }
 
 impl Serialize for Disconnect<'_> {
     fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
-        if self.message.is_empty() {
+        if self.message.is_empty() && !self.hide_message {
             // An empty message will cause Minecraft to just ignore the disconnect packet and will
             // cause all kinds of problems.
             anyhow::bail!("Disconnect message cannot be empty");
         }
 
-        writer.write_var_i32(self.reason as i32)?; // Reason unknown
+        writer.write_var_i32(self.reason as i32)?;
         writer.write_bool(self.hide_message)?;
-        writer.write_str(self.message)
+        if !self.hide_message {
+            writer.write_str(self.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Deserialize<'a> for Disconnect<'a> {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        // Newer clients may use reasons that this version does not know about yet.
+        let reason = DisconnectReason::try_from(reader.read_var_i32()?).unwrap_or(DisconnectReason::Unknown);
+        let hide_message = reader.read_bool()?;
+        let message = if hide_message { "" } else { reader.read_str()? };
+
+        Ok(Self { reason, hide_message, message })
     }
 }
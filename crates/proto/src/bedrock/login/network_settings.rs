@@ -1,4 +1,4 @@
-use macros::variant_count;
+use macros::try_from_repr;
 use util::{BinaryWrite};
 
 use util::Serialize;
@@ -11,7 +11,7 @@ use crate::bedrock::ConnectedPacket;
 /// Flate is slow, but produces high compression ratios.
 #[derive(Debug, Copy, Clone)]
 #[repr(u8)]
-#[variant_count]
+#[try_from_repr]
 pub enum CompressionAlgorithm {
     /// The Deflate/Zlib compression algorithm.
     Flate,
@@ -22,22 +22,6 @@ pub enum CompressionAlgorithm {
     Snappy,
 }
 
-impl TryFrom<u8> for CompressionAlgorithm {
-    type Error = anyhow::Error;
-
-    fn try_from(value: u8) -> anyhow::Result<CompressionAlgorithm> {
-        if value < Self::variant_count() as u8 {    
-            // SAFETY: This is safe because the enum and value have the same representation.
-            // The check also ensures that the discriminant is in range.
-            Ok(unsafe {
-                std::mem::transmute::<u8, CompressionAlgorithm>(value)
-            })
-        } else {
-            anyhow::bail!("Compression algorithm variant out of range: {value}");
-        }
-    }
-}
-
 /// Settings for client throttling.
 ///
 /// If client throttling is enabled, the client will tick fewer players,
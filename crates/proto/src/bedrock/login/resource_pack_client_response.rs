@@ -56,7 +56,7 @@ impl<'a> Deserialize<'a> for ResourcePackClientResponse<'a> {
         let status = ResourcePackStatus::try_from(reader.read_u8()?)?;
         let length = reader.read_u16_be()?;
 
-        let mut pack_ids = Vec::with_capacity(length as usize);
+        let mut pack_ids = Vec::with_capacity((length as usize).min(reader.remaining()));
         for _ in 0..length {
             pack_ids.push(reader.read_str()?);
         }
@@ -1,7 +1,5 @@
-use std::collections::HashMap;
-
 use crate::types::Dimension;
-use macros::variant_count;
+use macros::try_from_repr;
 use util::{Serialize, Vector};
 
 use util::BlockPosition;
@@ -33,7 +31,7 @@ impl WorldGenerator {
 /// The permission level of the client.
 #[derive(Debug, Copy, Clone)]
 #[repr(u8)]
-#[variant_count]
+#[try_from_repr]
 pub enum PermissionLevel {
     /// A visitor is a player that has most permissions removed.
     Visitor,
@@ -45,23 +43,6 @@ pub enum PermissionLevel {
     Custom,
 }
 
-impl TryFrom<u8> for PermissionLevel {
-    type Error = anyhow::Error;
-
-    fn try_from(value: u8) -> anyhow::Result<PermissionLevel> {
-        if value <= PermissionLevel::variant_count() as u8 {
-            // SAFETY: This is safe because the discriminant is in range and
-            // the representations are the same. Additionally, none of the enum members
-            // have a manually assigned value (this is ensured by the `variant_count` macro).
-            Ok(unsafe {
-                std::mem::transmute::<u8, PermissionLevel>(value)
-            })
-        } else {
-            anyhow::bail!("Permission level out of range <= 3, got {value}")
-        }
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct EducationResourceURI {
     pub button_name: String,
@@ -139,17 +120,15 @@ pub struct BlockEntry {
     /// Name of the block.
     pub name: String,
     // NBT compound containing properties.
-    pub properties: HashMap<String, nbt::Value>,
+    pub properties: nbt::Compound,
 }
 
-impl BlockEntry {
-    pub const fn serialized_size(&self) -> usize {
-        1
-        // self.name.var_len() //+ self.properties.serialized_net_size("")
+impl Serialize for BlockEntry {
+    fn size_hint(&self) -> Option<usize> {
+        let properties = nbt::serialized_size::<nbt::Variable, _>(&self.properties).ok()?;
+        Some(self.name.var_len() + properties)
     }
-}
 
-impl Serialize for BlockEntry {
     fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
         // {
         //     let mut buf2 = Vec::new();
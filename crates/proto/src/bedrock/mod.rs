@@ -32,13 +32,16 @@ glob_export!(generic_level_event);
 glob_export!(header);
 glob_export!(interact);
 glob_export!(inventory_options);
+glob_export!(inventory_slot);
 glob_export!(level_event);
 glob_export!(mob_effect);
+glob_export!(move_actor);
 glob_export!(network_chunk_publisher_update);
 glob_export!(play_sound);
 glob_export!(player_list);
 glob_export!(request_ability);
 glob_export!(respawn);
+glob_export!(set_actor_link);
 glob_export!(set_hud);
 glob_export!(set_local_player_as_initialized);
 glob_export!(show_credits);
@@ -0,0 +1,62 @@
+use util::{BinaryRead, BinaryWrite, Deserialize, Serialize, Vector, size_of_varint};
+
+use crate::bedrock::ConnectedPacket;
+use crate::types::{Angle, HeadYaw};
+
+/// The entity is standing on the ground.
+pub const MOVE_ACTOR_ON_GROUND: u8 = 1 << 0;
+/// The entity was teleported rather than moved, so the client should not interpolate.
+pub const MOVE_ACTOR_TELEPORT: u8 = 1 << 1;
+/// The position is applied even if the client predicts a different one.
+pub const MOVE_ACTOR_FORCE_MOVE: u8 = 1 << 2;
+
+/// Moves an entity to an absolute position.
+///
+/// The rotation is compressed into a single byte per angle, see [`Angle::to_byte`].
+#[derive(Debug, Clone)]
+pub struct MoveActorAbsolute {
+    /// Runtime ID of the entity.
+    pub runtime_id: u64,
+    /// Combination of the `MOVE_ACTOR_*` flags.
+    pub flags: u8,
+    /// New position of the entity.
+    pub position: Vector<f32, 3>,
+    /// Pitch of the entity.
+    pub pitch: Angle,
+    /// Yaw of the body of the entity.
+    pub yaw: Angle,
+    /// Yaw of the head of the entity.
+    pub head_yaw: HeadYaw,
+}
+
+impl ConnectedPacket for MoveActorAbsolute {
+    const ID: u32 = 0x12;
+
+    fn serialized_size(&self) -> usize {
+        size_of_varint(self.runtime_id) + 1 + 3 * 4 + 3
+    }
+}
+
+impl Serialize for MoveActorAbsolute {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_var_u64(self.runtime_id)?;
+        writer.write_u8(self.flags)?;
+        writer.write_vecf(&self.position)?;
+        writer.write_u8(self.pitch.to_byte())?;
+        writer.write_u8(self.yaw.to_byte())?;
+        writer.write_u8(self.head_yaw.0.to_byte())
+    }
+}
+
+impl<'a> Deserialize<'a> for MoveActorAbsolute {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        let runtime_id = reader.read_var_u64()?;
+        let flags = reader.read_u8()?;
+        let position = reader.read_vecf()?;
+        let pitch = Angle::from_byte(reader.read_u8()?);
+        let yaw = Angle::from_byte(reader.read_u8()?);
+        let head_yaw = HeadYaw(Angle::from_byte(reader.read_u8()?));
+
+        Ok(Self { runtime_id, flags, position, pitch, yaw, head_yaw })
+    }
+}
@@ -0,0 +1,21 @@
+use util::{BinaryWrite, Serialize};
+
+use crate::bedrock::{ConnectedPacket, EntityLink};
+
+/// Links or unlinks two entities.
+/// This is used to make an entity mount or dismount another entity, such as a player entering a boat.
+#[derive(Debug, Clone)]
+pub struct SetActorLink {
+    /// The link to create or remove. See [`EntityLink`].
+    pub link: EntityLink,
+}
+
+impl ConnectedPacket for SetActorLink {
+    const ID: u32 = 0x29;
+}
+
+impl Serialize for SetActorLink {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        self.link.serialize_into(writer)
+    }
+}
@@ -545,7 +545,8 @@ impl<'a> util::Deserialize<'a> for Skin {
         let image_data = RVec::alloc_from_slice(reader.take_n(image_size as usize)?);
 
         let animation_count = reader.read_u32_le()?;
-        let mut animations = Vec::with_capacity(animation_count as usize);
+        // The count is sent by the client, so the allocation is limited by the size of the packet.
+        let mut animations = Vec::with_capacity((animation_count as usize).min(reader.remaining()));
         for _ in 0..animation_count {
             animations.push(SkinAnimation::deserialize_from(reader)?);
         }
@@ -564,14 +565,14 @@ impl<'a> util::Deserialize<'a> for Skin {
         let color = reader.read_str()?.to_owned();
 
         let persona_piece_count = reader.read_u32_le()?;
-        let mut persona_pieces = Vec::with_capacity(persona_piece_count as usize);
+        let mut persona_pieces = Vec::with_capacity((persona_piece_count as usize).min(reader.remaining()));
         for _ in 0..persona_piece_count {
             persona_pieces.push(PersonaPiece::deserialize_from(reader)?);
         }
 
         let persona_tint_count = reader.read_u32_le()?;
-        let mut persona_piece_tints = Vec::with_capacity(persona_tint_count as usize);
-        for _ in 0..persona_piece_count {
+        let mut persona_piece_tints = Vec::with_capacity((persona_tint_count as usize).min(reader.remaining()));
+        for _ in 0..persona_tint_count {
             persona_piece_tints.push(PersonaPieceTint::deserialize_from(reader)?);
         }
 
@@ -86,9 +86,20 @@ impl<'a> TextData<'a> {
     /// Returns the enum discriminant of `self`.
     #[inline]
     pub const fn discriminant(&self) -> u8 {
-        // SAFETY: This is safe due to the `repr(u8)` attribute on the enum.
-        // This means the enum is prefixed with a u8 tag.
-        unsafe { *(self as *const Self as *const u8) }
+        match self {
+            Self::Raw { .. } => 0,
+            Self::Chat { .. } => 1,
+            Self::Translation { .. } => 2,
+            Self::Popup { .. } => 3,
+            Self::JukeboxPopup { .. } => 4,
+            Self::Tip { .. } => 5,
+            Self::System { .. } => 6,
+            Self::Whisper { .. } => 7,
+            Self::Announcement { .. } => 8,
+            Self::ObjectWhisper { .. } => 9,
+            Self::Object { .. } => 10,
+            Self::ObjectAnnouncement { .. } => 11,
+        }
     }
 }
 
@@ -189,7 +200,7 @@ impl<'a> Deserialize<'a> for TextMessage<'a> {
                 message: reader.read_str()?,
                 parameters: {
                     let count = reader.read_var_u32()?;
-                    let mut params = Vec::with_capacity(count as usize);
+                    let mut params = Vec::with_capacity((count as usize).min(reader.remaining()));
                     for _ in 0..count {
                         params.push(reader.read_str()?);
                     }
@@ -201,7 +212,7 @@ impl<'a> Deserialize<'a> for TextMessage<'a> {
                 message: reader.read_str()?,
                 parameters: {
                     let count = reader.read_var_u32()?;
-                    let mut params = Vec::with_capacity(count as usize);
+                    let mut params = Vec::with_capacity((count as usize).min(reader.remaining()));
                     for _ in 0..count {
                         params.push(reader.read_str()?);
                     }
@@ -213,7 +224,7 @@ impl<'a> Deserialize<'a> for TextMessage<'a> {
                 message: reader.read_str()?,
                 parameters: {
                     let count = reader.read_var_u32()?;
-                    let mut params = Vec::with_capacity(count as usize);
+                    let mut params = Vec::with_capacity((count as usize).min(reader.remaining()));
                     for _ in 0..count {
                         params.push(reader.read_str()?);
                     }
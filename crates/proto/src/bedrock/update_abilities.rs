@@ -1,8 +1,8 @@
-use macros::variant_count;
+use macros::try_from_repr;
 use util::{BinaryRead, BinaryWrite};
 use util::{Deserialize, Serialize};
 use crate::bedrock::command::CommandPermissionLevel;
-use crate::bedrock::{ConnectedPacket, PermissionLevel};
+use crate::bedrock::{ConnectedPacket, GameMode, PermissionLevel};
 
 // #[derive(Debug)]
 // pub enum Ability {
@@ -70,10 +70,35 @@ pub const ABILITY_PRIVILEGED_BUILDER: u32 = 1 << 18;
 /// Indicates the highest value of the abilities.
 pub const ABILITY_FLAG_END: u32 = 1 << 19;
 
+/// Fly speed used by the vanilla game.
+pub const DEFAULT_FLY_SPEED: f32 = 0.05;
+/// Walk speed used by the vanilla game.
+pub const DEFAULT_WALK_SPEED: f32 = 0.1;
+
+/// Abilities that allow a client to interact with the world without modifying it.
+const ABILITIES_INTERACT: u32 =
+    ABILITY_DOORS_AND_SWITCHES | ABILITY_OPEN_CONTAINERS | ABILITY_ATTACK_PLAYERS | ABILITY_ATTACK_MOBS;
+
+/// Returns the abilities that the vanilla game grants to players in the given game mode.
+///
+/// [`GameMode::WorldDefault`] is treated as survival.
+pub const fn game_mode_abilities(game_mode: GameMode) -> u32 {
+    match game_mode {
+        GameMode::Survival | GameMode::WorldDefault => ABILITIES_INTERACT | ABILITY_BUILD | ABILITY_MINE,
+        GameMode::Creative => {
+            ABILITIES_INTERACT | ABILITY_BUILD | ABILITY_MINE | ABILITY_MAYFLY | ABILITY_INSTANT_BUILD | ABILITY_INVULNERABLE
+        }
+        GameMode::Adventure => ABILITIES_INTERACT,
+        GameMode::SurvivalSpectator | GameMode::CreativeSpectator | GameMode::Spectator => {
+            ABILITY_MAYFLY | ABILITY_FLYING | ABILITY_NOCLIP | ABILITY_INVULNERABLE
+        }
+    }
+}
+
 /// Type of ability.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u16)]
-#[variant_count]
+#[try_from_repr]
 pub enum AbilityType {
     /// No idea what this is.
     CustomCache,
@@ -87,22 +112,6 @@ pub enum AbilityType {
     Editor,
 }
 
-impl TryFrom<u16> for AbilityType {
-    type Error = anyhow::Error;
-
-    fn try_from(value: u16) -> anyhow::Result<AbilityType> {
-        if value <= AbilityType::variant_count() as u16 {
-            // SAFETY: This is safe because the enum has the correct representation and
-            // the discriminant is in range.
-            Ok(unsafe {
-                std::mem::transmute::<u16, AbilityType>(value)
-            })
-        } else {
-            anyhow::bail!("Ability type out of range <= 4, got {value}")
-        }
-    }
-}
-
 /// A single layer in the ability data.
 #[derive(Debug, Clone)]
 pub struct AbilityLayer {
@@ -118,6 +127,20 @@ pub struct AbilityLayer {
     pub walk_speed: f32,
 }
 
+impl AbilityLayer {
+    /// Creates a [`Base`](AbilityType::Base) layer that sets all abilities to the given values,
+    /// using the default speeds.
+    pub const fn base(values: u32) -> AbilityLayer {
+        AbilityLayer {
+            ability_type: AbilityType::Base,
+            abilities: ABILITY_FLAG_END - 1,
+            values,
+            fly_speed: DEFAULT_FLY_SPEED,
+            walk_speed: DEFAULT_WALK_SPEED,
+        }
+    }
+}
+
 impl Serialize for AbilityLayer {
     fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
         writer.write_u16_le(self.ability_type as u16)?;
@@ -157,6 +180,54 @@ pub struct AbilityData {
     pub layers: Vec<AbilityLayer>,
 }
 
+impl AbilityData {
+    /// Creates the ability data of a player with the vanilla abilities of its game mode.
+    ///
+    /// Operators are additionally allowed to use operator commands and teleport.
+    pub fn new(
+        unique_id: u64,
+        game_mode: GameMode,
+        permission_level: PermissionLevel,
+        command_permission_level: CommandPermissionLevel
+    ) -> AbilityData {
+        let mut values = game_mode_abilities(game_mode);
+        if matches!(permission_level, PermissionLevel::Operator) {
+            values |= ABILITY_OPERATOR_COMMANDS | ABILITY_TELEPORT;
+        }
+
+        AbilityData {
+            unique_id,
+            permission_level,
+            command_permission_level,
+            layers: vec![AbilityLayer::base(values)],
+        }
+    }
+
+    /// Enables or disables an ability in the base layer.
+    ///
+    /// A base layer is added if there is none.
+    pub fn set(&mut self, ability: u32, enabled: bool) {
+        let layer = if let Some(index) = self.layers.iter().position(|l| l.ability_type == AbilityType::Base) {
+            &mut self.layers[index]
+        } else {
+            self.layers.push(AbilityLayer::base(0));
+            let last = self.layers.len() - 1;
+            &mut self.layers[last]
+        };
+
+        if enabled {
+            layer.values |= ability;
+        } else {
+            layer.values &= !ability;
+        }
+    }
+
+    /// Whether an ability is enabled in any layer.
+    pub fn has(&self, ability: u32) -> bool {
+        self.layers.iter().any(|layer| layer.abilities & layer.values & ability != 0)
+    }
+}
+
 impl Serialize for AbilityData {
     fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
         writer.write_u64_le(self.unique_id)?; // For some reason this isn't a varint64.
@@ -56,6 +56,19 @@ pub struct BedrockClientInfo {
     /// GUI scale setting of the client.
     #[serde(rename = "GuiScale")]
     pub gui_scale: i32,
+    /// Input mode that the client was using when it joined.
+    ///
+    /// This is kept as the raw value so that unknown input modes do not prevent the client from logging in.
+    #[serde(rename = "CurrentInputMode", default)]
+    pub current_input_mode: u32,
+    /// Input mode that the device uses by default.
+    #[serde(rename = "DefaultInputMode", default)]
+    pub default_input_mode: u32,
+    /// Version of the preset compression dictionaries supported by the client.
+    ///
+    /// Vanilla clients do not send this, in which case it is 0.
+    #[serde(rename = "CompressionDictionaryVersion", default)]
+    pub compression_dictionary_version: u32,
 }
 
 /// A chain of JSON web tokens.
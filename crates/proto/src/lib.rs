@@ -39,6 +39,7 @@
     clippy::unused_self,
     clippy::unused_async
 )]
+#![cfg_attr(feature = "forbid-unsafe", forbid(unsafe_code))]
 #![allow(dead_code)]
 #![allow(clippy::use_self)]
 
@@ -47,6 +48,9 @@ pub mod crypto;
 pub mod raknet;
 pub mod types;
 
+#[cfg(test)]
+mod test;
+
 // pub mod xbox;
 
 pub use base64;
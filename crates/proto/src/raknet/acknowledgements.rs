@@ -6,7 +6,7 @@ use util::iassert;
 use util::{Deserialize, Serialize};
 
 /// Record containing IDs of confirmed raknet.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AckEntry {
     /// A single ID
     Single(u32),
@@ -37,7 +37,7 @@ fn serialize_records<W: BinaryWrite>(writer: &mut W, records: &[AckEntry]) -> an
 /// Decodes a list of acknowledgement records.
 fn deserialize_records<'a, R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Vec<AckEntry>> {
     let record_count = reader.read_u16_be()?;
-    let mut records = Vec::with_capacity(record_count as usize);
+    let mut records = Vec::with_capacity((record_count as usize).min(reader.remaining()));
 
     for _ in 0..record_count {
         let is_range = reader.read_u8()? == 0;
@@ -1,5 +1,5 @@
-use util::{BinaryRead};
-use util::Deserialize;
+use util::{BinaryRead, BinaryWrite};
+use util::{Deserialize, Serialize};
 use util::iassert;
 
 
@@ -14,6 +14,18 @@ pub struct ConnectedPing {
 impl ConnectedPing {
     /// Unique ID of this packet.
     pub const ID: u8 = 0x00;
+
+    /// Estimates the size of the packet when serialized.
+    pub const fn size_hint(&self) -> usize {
+        1 + 8
+    }
+}
+
+impl Serialize for ConnectedPing {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_u8(Self::ID)?;
+        writer.write_i64_be(self.time)
+    }
 }
 
 impl<'a> Deserialize<'a> for ConnectedPing {
@@ -1,5 +1,6 @@
-use util::BinaryWrite;
-use util::Serialize;
+use util::{BinaryRead, BinaryWrite};
+use util::{Deserialize, Serialize};
+use util::iassert;
 
 /// Sent by the server or client in response to an [`ConnectedPing`](crate::raknet::ConnectedPing) packet.
 #[derive(Debug)]
@@ -27,3 +28,14 @@ impl Serialize for ConnectedPong {
         writer.write_i64_be(self.pong_time)
     }
 }
+
+impl<'a> Deserialize<'a> for ConnectedPong {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        iassert!(reader.read_u8()? == Self::ID);
+
+        let ping_time = reader.read_i64_be()?;
+        let pong_time = reader.read_i64_be()?;
+
+        Ok(Self { ping_time, pong_time })
+    }
+}
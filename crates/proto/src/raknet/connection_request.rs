@@ -1,6 +1,6 @@
-use util::{BinaryRead};
+use util::{BinaryRead, BinaryWrite};
 use util::iassert;
-use util::Deserialize;
+use util::{Deserialize, Serialize};
 
 
 /// Sent by the client to initiate a full connection.
@@ -16,6 +16,21 @@ pub struct ConnectionRequest {
 impl ConnectionRequest {
     /// Unique ID of this packet.
     pub const ID: u8 = 0x09;
+
+    /// Estimates the size of the packet when serialized.
+    pub const fn size_hint(&self) -> usize {
+        1 + 8 + 8 + 1
+    }
+}
+
+impl Serialize for ConnectionRequest {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_u8(Self::ID)?;
+        writer.write_i64_be(self.guid)?;
+        writer.write_i64_be(self.time)?;
+        // Security is not used by Minecraft.
+        writer.write_bool(false)
+    }
 }
 
 impl<'a> Deserialize<'a> for ConnectionRequest {
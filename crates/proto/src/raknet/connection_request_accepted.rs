@@ -1,8 +1,11 @@
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 
-use util::{BinaryWrite, IPV4_MEM_SIZE, IPV6_MEM_SIZE};
+use util::iassert;
+use util::{BinaryRead, BinaryWrite, IPV4_MEM_SIZE, IPV6_MEM_SIZE};
 
-use util::Serialize;
+use util::{Deserialize, Serialize};
+
+use crate::raknet::INTERNAL_ADDRESS_COUNT;
 
 /// Sent in response to [`ConnectionRequest`](crate::raknet::ConnectionRequest).
 #[derive(Debug)]
@@ -30,11 +33,30 @@ impl Serialize for ConnectionRequestAccepted {
         writer.write_u16_be(0)?; // System index
 
         let null_addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 19132));
-        for _ in 0..20 {
-            // 20 internal IDs
+        for _ in 0..INTERNAL_ADDRESS_COUNT {
             writer.write_addr(&null_addr)?;
         }
         writer.write_i64_be(self.request_time)?;
         writer.write_i64_be(self.request_time) // Response time
     }
 }
+
+impl<'a> Deserialize<'a> for ConnectionRequestAccepted {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        iassert!(reader.read_u8()? == Self::ID);
+
+        let client_address = reader.read_addr()?;
+        reader.advance(2)?; // Skip system index
+
+        // Servers do not agree on the amount of internal addresses, only the timestamps
+        // at the end of the packet are of interest.
+        let Some(timestamps_start) = reader.remaining().checked_sub(16) else {
+            anyhow::bail!("Connection request accepted packet is missing timestamps");
+        };
+        reader.advance(timestamps_start)?;
+
+        let request_time = reader.read_i64_be()?;
+
+        Ok(Self { client_address, request_time })
+    }
+}
@@ -1,14 +1,21 @@
-use util::{BinaryWrite, Serialize};
+use util::iassert;
+use util::{BinaryRead, BinaryWrite, Deserialize, Serialize};
 
-use crate::raknet::{OFFLINE_MESSAGE_DATA, RAKNET_VERSION};
+use crate::raknet::OFFLINE_MESSAGE_DATA;
 
 /// Notifies the client that they're using a version of the Raknet protocol that is incompatible
-/// with the version used by the server ([`RAKNET_VERSION`]).
+/// with the versions accepted by the server.
 ///
 /// This packet should be sent in response to [`OpenConnectionRequest1`](crate::raknet::OpenConnectionRequest1)
-/// if the [`protocol_version`](crate::raknet::OpenConnectionRequest1::protocol_version) field does not match the server's version.
+/// if the [`protocol_version`](crate::raknet::OpenConnectionRequest1::protocol_version) field does not match
+/// any of the server's versions.
 #[derive(Debug)]
 pub struct IncompatibleProtocol {
+    /// Version of the protocol that the server uses.
+    ///
+    /// The packet only has room for a single version. Servers that accept multiple versions
+    /// should send the newest one.
+    pub protocol_version: u8,
     /// Randomly generated GUID of the server.
     /// Corresponds to the random GUID generated on startup.
     pub server_guid: u64,
@@ -26,8 +33,20 @@ impl IncompatibleProtocol {
 impl Serialize for IncompatibleProtocol {
     fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
         writer.write_u8(Self::ID)?;
-        writer.write_u8(RAKNET_VERSION)?;
+        writer.write_u8(self.protocol_version)?;
         writer.write_all(OFFLINE_MESSAGE_DATA)?;
         writer.write_u64_be(self.server_guid)
     }
 }
+
+impl<'a> Deserialize<'a> for IncompatibleProtocol {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        iassert!(reader.read_u8()? == Self::ID);
+
+        let protocol_version = reader.read_u8()?;
+        reader.advance(16)?; // Skip magic
+        let server_guid = reader.read_u64_be()?;
+
+        Ok(Self { protocol_version, server_guid })
+    }
+}
@@ -17,7 +17,9 @@ glob_export!(unconnected_pong);
 glob_export!(connected_ping);
 glob_export!(connected_pong);
 
-/// Version of Raknet that this server uses.
+/// Amount of internal addresses sent in the connection handshake.
+pub(crate) const INTERNAL_ADDRESS_COUNT: usize = 20;
+/// Version of Raknet that this server uses by default.
 pub const RAKNET_VERSION: u8 = 11;
 /// Special sequence of bytes that is contained in every unframed Raknet packet.
 pub const OFFLINE_MESSAGE_DATA: &[u8] = &[
@@ -1,20 +1,62 @@
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+
 use util::iassert;
-use util::{BinaryRead, Deserialize};
+use util::{BinaryRead, BinaryWrite, Deserialize, Serialize, IPV4_MEM_SIZE, IPV6_MEM_SIZE};
+
+use crate::raknet::INTERNAL_ADDRESS_COUNT;
 
 /// Confirms that the connection was successfully initiated.
 #[derive(Debug)]
-pub struct NewIncomingConnection;
+pub struct NewIncomingConnection {
+    /// Address of the server.
+    pub server_address: SocketAddr,
+    /// Corresponds to [`ConnectionRequestAccepted::request_time`](crate::raknet::ConnectionRequestAccepted::request_time).
+    pub request_time: i64,
+    /// Time at which this packet was sent.
+    pub time: i64,
+}
 
 impl NewIncomingConnection {
     /// Unique ID of this packet.
     pub const ID: u8 = 0x13;
+
+    /// Estimates the size of the packet when serialized.
+    pub const fn size_hint(&self) -> usize {
+        1 + IPV6_MEM_SIZE + INTERNAL_ADDRESS_COUNT * IPV4_MEM_SIZE + 8 + 8
+    }
+}
+
+impl Serialize for NewIncomingConnection {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_u8(Self::ID)?;
+        writer.write_addr(&self.server_address)?;
+
+        let null_addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+        for _ in 0..INTERNAL_ADDRESS_COUNT {
+            writer.write_addr(&null_addr)?;
+        }
+
+        writer.write_i64_be(self.request_time)?;
+        writer.write_i64_be(self.time)
+    }
 }
 
 impl<'a> Deserialize<'a> for NewIncomingConnection {
     fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
         iassert!(reader.read_u8()? == Self::ID);
 
-        // No data in this packet is used, there is no point in decoding it
-        Ok(Self)
+        let server_address = reader.read_addr()?;
+
+        // Clients do not agree on the amount of internal addresses, only the timestamps
+        // at the end of the packet are of interest.
+        let Some(timestamps_start) = reader.remaining().checked_sub(16) else {
+            anyhow::bail!("New incoming connection packet is missing timestamps");
+        };
+        reader.advance(timestamps_start)?;
+
+        let request_time = reader.read_i64_be()?;
+        let time = reader.read_i64_be()?;
+
+        Ok(Self { server_address, request_time, time })
     }
 }
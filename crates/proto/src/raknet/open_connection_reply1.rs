@@ -1,5 +1,5 @@
-use util::BinaryWrite;
-use util::Serialize;
+use util::iassert;
+use util::{BinaryRead, BinaryWrite, Deserialize, Serialize};
 
 use crate::raknet::OFFLINE_MESSAGE_DATA;
 
@@ -9,8 +9,14 @@ pub struct OpenConnectionReply1 {
     /// GUID of the server.
     /// Corresponds to the random GUID generated on startup.
     pub server_guid: u64,
+    /// Cookie that the client has to send back in its
+    /// [`OpenConnectionRequest2`](crate::raknet::OpenConnectionRequest2).
+    ///
+    /// This proves that the client can receive packets on its source address, which prevents
+    /// connections from spoofed addresses.
+    pub cookie: Option<u32>,
     /// MTU of the connection.
-    /// This should be given the same value as [`OpenConnectionRequest1::mtu`](crate::raknet::OpenConnectionRequest1::mtu).
+    /// This is the MTU of the probe, clamped to the MTU supported by the server.
     pub mtu: u16,
 }
 
@@ -20,7 +26,7 @@ impl OpenConnectionReply1 {
 
     /// Estimates the size of the packet when serialized.
     pub const fn size_hint(&self) -> usize {
-        1 + 16 + 8 + 1 + 2
+        1 + 16 + 8 + 1 + if self.cookie.is_some() { 4 } else { 0 } + 2
     }
 }
 
@@ -29,9 +35,24 @@ impl Serialize for OpenConnectionReply1 {
         writer.write_u8(Self::ID)?;
         writer.write_all(OFFLINE_MESSAGE_DATA)?;
         writer.write_u64_be(self.server_guid)?;
-        // Disable security, required for login sequence.
-        // Encryption will be enabled later on.
-        writer.write_u8(0)?;
+        // RakNet security is only used for the cookie. Encryption is enabled later on by the Bedrock protocol.
+        writer.write_bool(self.cookie.is_some())?;
+        if let Some(cookie) = self.cookie {
+            writer.write_u32_be(cookie)?;
+        }
         writer.write_u16_be(self.mtu)
     }
 }
+
+impl<'a> Deserialize<'a> for OpenConnectionReply1 {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        iassert!(reader.read_u8()? == Self::ID);
+
+        reader.advance(16)?; // Skip magic
+        let server_guid = reader.read_u64_be()?;
+        let cookie = if reader.read_bool()? { Some(reader.read_u32_be()?) } else { None };
+        let mtu = reader.read_u16_be()?;
+
+        Ok(Self { server_guid, cookie, mtu })
+    }
+}
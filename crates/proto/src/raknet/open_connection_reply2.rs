@@ -1,8 +1,9 @@
 use std::net::SocketAddr;
 
-use util::{BinaryWrite, IPV4_MEM_SIZE, IPV6_MEM_SIZE};
+use util::iassert;
+use util::{BinaryRead, BinaryWrite, IPV4_MEM_SIZE, IPV6_MEM_SIZE};
 
-use util::Serialize;
+use util::{Deserialize, Serialize};
 
 use crate::raknet::OFFLINE_MESSAGE_DATA;
 
@@ -41,3 +42,16 @@ impl Serialize for OpenConnectionReply2 {
         writer.write_bool(false)
     }
 }
+
+impl<'a> Deserialize<'a> for OpenConnectionReply2 {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        iassert!(reader.read_u8()? == Self::ID);
+
+        reader.advance(16)?; // Skip magic
+        let server_guid = reader.read_u64_be()?;
+        let client_address = reader.read_addr()?;
+        let mtu = reader.read_u16_be()?;
+
+        Ok(Self { server_guid, client_address, mtu })
+    }
+}
@@ -1,5 +1,10 @@
 use util::iassert;
-use util::{BinaryRead, Deserialize};
+use util::{BinaryRead, BinaryWrite, Deserialize, Serialize};
+
+use crate::raknet::OFFLINE_MESSAGE_DATA;
+
+/// Size of the IPv4 and UDP headers that are included in the MTU.
+const UDP_HEADER_SIZE: u16 = 28;
 
 /// Sent by the client when the users joins the server.
 #[derive(Debug)]
@@ -7,7 +12,7 @@ pub struct OpenConnectionRequest1 {
     /// Version of the Raknet protocol.
     /// Minecraft currently uses version 10.
     ///
-    /// If this does not equal the server's version (['RAKNET_VERSION'](crate::raknet::RAKNET_VERSION)),
+    /// If this is not one of the versions accepted by the server (by default only [`RAKNET_VERSION`](crate::raknet::RAKNET_VERSION)),
     /// then an [`IncompatibleProtocol`](crate::raknet::IncompatibleProtocol) packet should be sent.
     pub protocol_version: u8,
     /// Maximum Transfer Unit. Specifies the maximum size of raknet that the connection can handle.
@@ -18,13 +23,32 @@ pub struct OpenConnectionRequest1 {
 impl OpenConnectionRequest1 {
     /// Unique identifier for this packet.
     pub const ID: u8 = 0x05;
+
+    /// Size of the packet when serialized. The packet is padded to fill the entire MTU.
+    pub const fn size_hint(&self) -> usize {
+        self.mtu.saturating_sub(UDP_HEADER_SIZE) as usize
+    }
+}
+
+impl Serialize for OpenConnectionRequest1 {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_u8(Self::ID)?;
+        writer.write_all(OFFLINE_MESSAGE_DATA)?;
+        writer.write_u8(self.protocol_version)?;
+
+        let padding = self.size_hint().saturating_sub(1 + OFFLINE_MESSAGE_DATA.len() + 1);
+        writer.write_all(&vec![0; padding])?;
+
+        Ok(())
+    }
 }
 
 impl<'a> Deserialize<'a> for OpenConnectionRequest1 {
     fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
         iassert!(reader.read_u8()? == Self::ID);
 
-        let mtu = reader.remaining() as u16 + 28;
+        // The packet ID has already been read and is part of the MTU too.
+        let mtu = reader.remaining() as u16 + 1 + UDP_HEADER_SIZE;
         reader.advance(16)?; // Skip magic
         let protocol_version = reader.read_u8()?;
 
@@ -1,11 +1,21 @@
-use util::{BinaryRead};
+use std::net::SocketAddr;
+
+use util::{BinaryRead, BinaryWrite, IPV4_MEM_SIZE, IPV6_MEM_SIZE};
 use util::iassert;
-use util::Deserialize;
+use util::{Deserialize, Serialize};
+
+use crate::raknet::OFFLINE_MESSAGE_DATA;
 
+/// Size of the cookie and the security challenge flag that follows it.
+const COOKIE_SIZE: usize = 4 + 1;
 
 /// Sent by the client, in response to [`OpenConnectionReply2`](crate::raknet::OpenConnectionReply2).
 #[derive(Debug)]
 pub struct OpenConnectionRequest2 {
+    /// Cookie that was received in [`OpenConnectionReply1`](crate::raknet::OpenConnectionReply1).
+    pub cookie: Option<u32>,
+    /// Address of the server.
+    pub server_address: SocketAddr,
     /// MTU of the connection.
     pub mtu: u16,
     /// GUID of the client.
@@ -15,6 +25,28 @@ pub struct OpenConnectionRequest2 {
 impl OpenConnectionRequest2 {
     /// Unique identifier of the packet.
     pub const ID: u8 = 0x07;
+
+    /// Estimates the size of the packet when serialized.
+    pub const fn size_hint(&self) -> usize {
+        1 + 16 + if self.cookie.is_some() { COOKIE_SIZE } else { 0 }
+            + if self.server_address.is_ipv4() { IPV4_MEM_SIZE } else { IPV6_MEM_SIZE }
+            + 2 + 8
+    }
+}
+
+impl Serialize for OpenConnectionRequest2 {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_u8(Self::ID)?;
+        writer.write_all(OFFLINE_MESSAGE_DATA)?;
+        if let Some(cookie) = self.cookie {
+            writer.write_u32_be(cookie)?;
+            // The client does not request a security challenge.
+            writer.write_bool(false)?;
+        }
+        writer.write_addr(&self.server_address)?;
+        writer.write_u16_be(self.mtu)?;
+        writer.write_u64_be(self.client_guid)
+    }
 }
 
 impl<'a> Deserialize<'a> for OpenConnectionRequest2 {
@@ -22,10 +54,22 @@ impl<'a> Deserialize<'a> for OpenConnectionRequest2 {
         iassert!(reader.read_u8()? == Self::ID);
 
         reader.advance(16)?; // Skip magic
-        reader.read_addr()?; // Skip server address
+
+        // Whether the cookie is present depends on the reply that was sent earlier, which is not known here.
+        // The sizes of the packet with and without cookie never overlap, so the remaining size determines it.
+        let remaining = reader.remaining();
+        let cookie = if remaining == COOKIE_SIZE + IPV4_MEM_SIZE + 2 + 8 || remaining == COOKIE_SIZE + IPV6_MEM_SIZE + 2 + 8 {
+            let cookie = reader.read_u32_be()?;
+            reader.advance(1)?; // Skip security challenge flag
+            Some(cookie)
+        } else {
+            None
+        };
+
+        let server_address = reader.read_addr()?;
         let mtu = reader.read_u16_be()?;
         let client_guid = reader.read_u64_be()?;
 
-        Ok(Self { mtu, client_guid })
+        Ok(Self { cookie, server_address, mtu, client_guid })
     }
 }
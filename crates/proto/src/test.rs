@@ -0,0 +1,125 @@
+#![allow(clippy::unwrap_used)]
+
+use util::corpus::check_corpus;
+use util::{Deserialize, Serialize, Vector};
+
+use crate::bedrock::{Header, InputMode, InteractionModel, MoveActorAbsolute, PlayMode, Skin, TextMessage, MOVE_ACTOR_ON_GROUND};
+use crate::types::{Angle, HeadYaw};
+
+#[test]
+fn corpus_skin() {
+    check_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/skin"), |input| Skin::deserialize(input).map(|_| ()));
+}
+
+#[test]
+fn corpus_text() {
+    check_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/text"), |input| TextMessage::deserialize(input).map(|_| ()));
+}
+
+#[test]
+fn corpus_header() {
+    check_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/header"), |input| Header::deserialize(input).map(|_| ()));
+}
+
+#[test]
+fn repr_conversion() {
+    assert_eq!(PlayMode::try_from(8).unwrap(), PlayMode::ExitLevelLivingRoom);
+    assert!(PlayMode::try_from(9).is_err());
+
+    // Input modes start at one.
+    assert!(InputMode::try_from(0).is_err());
+    assert_eq!(InputMode::try_from(1).unwrap(), InputMode::Mouse);
+    assert_eq!(InputMode::try_from(4).unwrap(), InputMode::MotionController);
+    assert!(InputMode::try_from(5).is_err());
+
+    assert_eq!(InteractionModel::try_from(2).unwrap(), InteractionModel::Classic);
+    assert!(InteractionModel::try_from(3).is_err());
+    assert!(InteractionModel::try_from(-1).is_err());
+}
+
+#[test]
+fn byte_angles() {
+    assert_eq!(Angle::from_degrees(0.0).to_byte(), 0);
+    assert_eq!(Angle::from_degrees(90.0).to_byte(), 64);
+    assert_eq!(Angle::from_degrees(180.0).to_byte(), 128);
+    assert_eq!(Angle::from_degrees(-90.0).to_byte(), 192);
+    assert_eq!(Angle::from_degrees(360.0).to_byte(), 0);
+    assert_eq!(Angle::from_degrees(-0.001).to_byte(), 255);
+    assert_eq!(Angle::from_byte(64).degrees(), 90.0);
+
+    assert_eq!(Angle::from_degrees(270.0).normalized().degrees(), -90.0);
+    assert_eq!(Angle::from_degrees(-180.0).normalized().degrees(), -180.0);
+    assert_eq!(Angle::from_degrees(180.0).normalized().degrees(), -180.0);
+
+    // The rotation is written as one byte per angle after the position.
+    let packet = MoveActorAbsolute {
+        runtime_id: 1,
+        flags: MOVE_ACTOR_ON_GROUND,
+        position: Vector::from([0.0, 64.0, 0.0]),
+        pitch: Angle::from_degrees(0.0),
+        yaw: Angle::from_degrees(90.0),
+        head_yaw: HeadYaw::from_degrees(-90.0),
+    };
+    let serialized = packet.serialize().unwrap();
+    assert_eq!(
+        serialized.as_ref(),
+        &[0x01, 0x01, 0, 0, 0, 0, 0, 0, 0x80, 0x42, 0, 0, 0, 0, 0x00, 0x40, 0xc0]
+    );
+
+    let decoded = MoveActorAbsolute::deserialize(serialized.as_ref()).unwrap();
+    assert_eq!(decoded.yaw, Angle::from_degrees(90.0));
+    assert_eq!(decoded.head_yaw, HeadYaw::from_degrees(270.0));
+}
+
+#[test]
+fn disconnect_roundtrip() {
+    use crate::bedrock::{ConnectedPacket, Disconnect, DisconnectReason};
+
+    let packet = Disconnect { reason: DisconnectReason::UserLeaveGameAttempted, hide_message: false, message: "Goodbye" };
+    let serialized = packet.serialize().unwrap();
+    assert_eq!(serialized.len(), packet.serialized_size());
+
+    let decoded = Disconnect::deserialize(serialized.as_ref()).unwrap();
+    assert_eq!((decoded.reason, decoded.hide_message, decoded.message), (DisconnectReason::UserLeaveGameAttempted, false, "Goodbye"));
+
+    // Hidden messages are not sent at all.
+    let hidden = Disconnect { reason: DisconnectReason::Kicked, hide_message: true, message: "" }.serialize().unwrap();
+    assert_eq!(hidden.len(), 2);
+    assert_eq!(Disconnect::deserialize(hidden.as_ref()).unwrap().reason, DisconnectReason::Kicked);
+
+    assert_eq!(DisconnectReason::try_from(DisconnectReason::BadPacket as i32).unwrap(), DisconnectReason::BadPacket);
+    assert!(DisconnectReason::try_from(-1).is_err());
+}
+
+#[test]
+fn incompatible_protocol_roundtrip() {
+    use crate::raknet::IncompatibleProtocol;
+
+    let packet = IncompatibleProtocol { protocol_version: 11, server_guid: 0x0123_4567_89ab_cdef };
+    let serialized = packet.serialize().unwrap();
+    assert_eq!(serialized.len(), packet.size_hint());
+
+    let decoded = IncompatibleProtocol::deserialize(serialized.as_ref()).unwrap();
+    assert_eq!((decoded.protocol_version, decoded.server_guid), (11, 0x0123_4567_89ab_cdef));
+}
+
+#[test]
+fn default_abilities() {
+    use crate::bedrock::{
+        AbilityData, CommandPermissionLevel, GameMode, PermissionLevel, ABILITY_BUILD, ABILITY_FLYING, ABILITY_MAYFLY,
+        ABILITY_OPERATOR_COMMANDS,
+    };
+
+    let creative = AbilityData::new(1, GameMode::Creative, PermissionLevel::Member, CommandPermissionLevel::Normal);
+    assert!(creative.has(ABILITY_BUILD) && creative.has(ABILITY_MAYFLY));
+    assert!(!creative.has(ABILITY_OPERATOR_COMMANDS));
+
+    let mut adventure = AbilityData::new(1, GameMode::Adventure, PermissionLevel::Operator, CommandPermissionLevel::Normal);
+    assert!(!adventure.has(ABILITY_BUILD));
+    assert!(adventure.has(ABILITY_OPERATOR_COMMANDS));
+
+    adventure.set(ABILITY_FLYING, true);
+    assert!(adventure.has(ABILITY_FLYING));
+    adventure.set(ABILITY_FLYING, false);
+    assert!(!adventure.has(ABILITY_FLYING));
+}
@@ -0,0 +1,94 @@
+/// Amount of degrees that a single step of a byte angle covers.
+const DEGREES_PER_STEP: f32 = 360.0 / 256.0;
+
+/// A rotation in degrees, such as the pitch or yaw of an entity.
+///
+/// Most packets send rotations as floats, but some entity packets compress them into a single byte
+/// that covers the full circle. This type converts between both formats so that the conversion is
+/// not duplicated in every packet.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Angle(f32);
+
+impl Angle {
+    /// Creates an angle from degrees.
+    #[inline]
+    pub const fn from_degrees(degrees: f32) -> Angle {
+        Angle(degrees)
+    }
+
+    /// Creates an angle from its byte representation.
+    #[inline]
+    pub fn from_byte(byte: u8) -> Angle {
+        Angle(f32::from(byte) * DEGREES_PER_STEP)
+    }
+
+    /// The angle in degrees.
+    #[inline]
+    pub const fn degrees(self) -> f32 {
+        self.0
+    }
+
+    /// Compresses the angle into a single byte.
+    ///
+    /// The angle is wrapped into a full circle first, so negative angles are supported.
+    /// Precision is lost, the result is a multiple of 1.40625 degrees.
+    #[inline]
+    pub fn to_byte(self) -> u8 {
+        (self.0.rem_euclid(360.0) / DEGREES_PER_STEP) as u32 as u8
+    }
+
+    /// Wraps the angle into the range `[-180, 180)` that the client uses for yaw.
+    #[inline]
+    pub fn normalized(self) -> Angle {
+        Angle((self.0 + 180.0).rem_euclid(360.0) - 180.0)
+    }
+}
+
+impl From<f32> for Angle {
+    #[inline]
+    fn from(degrees: f32) -> Angle {
+        Angle(degrees)
+    }
+}
+
+impl From<Angle> for f32 {
+    #[inline]
+    fn from(angle: Angle) -> f32 {
+        angle.0
+    }
+}
+
+/// The yaw of the head of an entity.
+///
+/// This is separate from the yaw of the body because entities can look around without turning. Using a
+/// distinct type prevents the two from being swapped when building packets.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct HeadYaw(pub Angle);
+
+impl HeadYaw {
+    /// Creates a head yaw from degrees.
+    #[inline]
+    pub const fn from_degrees(degrees: f32) -> HeadYaw {
+        HeadYaw(Angle::from_degrees(degrees))
+    }
+
+    /// The head yaw in degrees.
+    #[inline]
+    pub const fn degrees(self) -> f32 {
+        self.0.degrees()
+    }
+}
+
+impl From<f32> for HeadYaw {
+    #[inline]
+    fn from(degrees: f32) -> HeadYaw {
+        HeadYaw::from_degrees(degrees)
+    }
+}
+
+impl From<Angle> for HeadYaw {
+    #[inline]
+    fn from(angle: Angle) -> HeadYaw {
+        HeadYaw(angle)
+    }
+}
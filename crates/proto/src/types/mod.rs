@@ -2,4 +2,5 @@
 
 use util::glob_export;
 
+glob_export!(angle);
 glob_export!(dimension);
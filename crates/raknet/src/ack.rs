@@ -1,8 +1,44 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
 use util::{Deserialize, BinaryRead, Serialize};
 
-use proto::raknet::{Ack, Nak};
+use proto::raknet::{Ack, AckEntry, Nak};
+
+use crate::{CloseReason, Frame, FrameBatch, RakNetClient, MAX_RETRANSMISSIONS, SESSION_TIMEOUTS_METRIC};
+
+/// Default interval at which acknowledgements are sent.
+pub const DEFAULT_ACK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Builds acknowledgement records from a list of received batch numbers.
+///
+/// The list is sorted and deduplicated in place, after which runs of consecutive numbers are merged
+/// into a single range. RakNet ranges include their upper bound.
+pub fn coalesce_acknowledgements(ids: &mut Vec<u32>) -> Vec<AckEntry> {
+    ids.sort_unstable();
+    ids.dedup();
+
+    let record = |start: u32, end: u32| if start == end { AckEntry::Single(start) } else { AckEntry::Range(start..end) };
+
+    let mut records = Vec::new();
+    let mut iter = ids.iter().copied();
+    let Some(mut start) = iter.next() else {
+        return records
+    };
 
-use crate::RakNetClient;
+    let mut end = start;
+    for id in iter {
+        if id != end + 1 {
+            records.push(record(start, end));
+            start = id;
+        }
+
+        end = id;
+    }
+
+    records.push(record(start, end));
+    records
+}
 
 impl RakNetClient {
     /// Processes an acknowledgement received from the client.
@@ -10,11 +46,29 @@ impl RakNetClient {
     /// This function unregisters the specified packet IDs from the recovery queue.
     pub fn handle_ack<'a, R: BinaryRead<'a>>(&self, reader: R) -> anyhow::Result<()> {
         let ack = Ack::deserialize(reader)?;
+        self.counters.record_ack_received();
 
         #[cfg(trace_raknet)]
         tracing::debug!("{ack:?}");
 
-        self.recovery.acknowledge(&ack.records);
+        let mut latest_rtt = None;
+        for record in &ack.records {
+            match record {
+                AckEntry::Single(id) => latest_rtt = self.congestion.on_ack(*id).or(latest_rtt),
+                // RakNet ranges include their upper bound.
+                AckEntry::Range(range) => {
+                    for id in range.start..=range.end {
+                        latest_rtt = self.congestion.on_ack(id).or(latest_rtt);
+                    }
+                }
+            }
+        }
+
+        // A single ACK can cover many batches, only count it as one measurement.
+        if let Some(rtt) = latest_rtt {
+            self.latency.record(rtt);
+        }
+        self.recovery.acknowledge(&ack.records, &self.pool);
 
         Ok(())
     }
@@ -26,24 +80,121 @@ impl RakNetClient {
     #[allow(clippy::future_not_send)]
     pub async fn handle_nak<'a, R: BinaryRead<'a>>(&self, reader: R) -> anyhow::Result<()> {
         let nak = Nak::deserialize(reader)?;
+        self.counters.record_nak_received();
         tracing::warn!("Received nak for {nak:?}");
 
+        for record in &nak.records {
+            match record {
+                AckEntry::Single(id) => self.congestion.on_loss(*id),
+                AckEntry::Range(range) => (range.start..=range.end).for_each(|id| self.congestion.on_loss(id)),
+            }
+        }
+
         let frame_batches = self.recovery.recover(&nak.records);
+        self.resend_lost(frame_batches)?;
+
+        // Retransmissions should not wait for the next tick.
+        self.submit_datagrams().await
+    }
+
+    /// Resends the reliable frames of lost batches.
+    ///
+    /// The frames of all lost batches are packed into as few new batches as possible, each with a new sequence number.
+    /// Unreliable frames were already left out when the batches were put in the recovery queue.
+    /// A new batch inherits the highest retransmission count of the lost batches whose frames it contains.
+    #[allow(clippy::iter_with_drain)] // The emptied lists are returned to the pool.
+    fn resend_lost(&self, frame_batches: Vec<(FrameBatch, u32)>) -> anyhow::Result<()> {
+        let mut serialized = self.pool.take_buffer();
+        let mut batch = FrameBatch { sequence_number: 0, frames: self.pool.take() };
+        let mut attempts = 0;
+
+        for (mut lost, lost_attempts) in frame_batches {
+            for frame in lost.frames.drain(..) {
+                let frame_size = frame.body.len() + std::mem::size_of::<Frame>();
+
+                #[allow(clippy::unwrap_used)] // Batch size_hint always returns `Some`.
+                if !batch.is_empty() && batch.size_hint().unwrap() + frame_size > self.mtu as usize {
+                    let full = std::mem::replace(&mut batch, FrameBatch { sequence_number: 0, frames: self.pool.take() });
+                    self.resend_batch(full, attempts, &mut serialized)?;
+                    attempts = 0;
+                }
 
-        let mut serialized = Vec::new();
-        for frame_batch in frame_batches {
-            frame_batch.serialize_into(&mut serialized)?;
+                batch.frames.push(frame);
+                attempts = attempts.max(lost_attempts);
+            }
+            self.pool.recycle_batch(lost);
+        }
+
+        if batch.is_empty() {
+            self.pool.recycle_batch(batch);
+        } else {
+            self.resend_batch(batch, attempts, &mut serialized)?;
+        }
+        self.pool.recycle_buffer(serialized);
+
+        Ok(())
+    }
+
+    /// Sends a batch of recovered frames under a new sequence number.
+    fn resend_batch(&self, mut batch: FrameBatch, attempts: u32, serialized: &mut Vec<u8>) -> anyhow::Result<()> {
+        batch.sequence_number = self.batch_number.fetch_add(1, Ordering::SeqCst);
+        self.retransmit(batch, attempts + 1, serialized)
+    }
+
+    /// Resends all reliable batches that have not been acknowledged within the retransmission timeout.
+    ///
+    /// The timeout doubles for every attempt, which prevents a retransmission storm when the client stops responding.
+    /// Nothing is retransmitted while the pacer budget is used up, the batches are resent by a later tick instead.
+    pub fn retransmit_timed_out(&self) -> anyhow::Result<()> {
+        if self.pacer.available() == 0 {
+            return Ok(())
+        }
 
-            self
-                .socket
-                .send_to(
-                    serialized.as_ref(), self.address,
-                )
-                .await?;
+        let timed_out = self.recovery.take_timed_out(self.congestion.rto());
+        if timed_out.is_empty() {
+            return Ok(());
+        }
+
+        tracing::trace!("Retransmitting {} timed out batches", timed_out.len());
 
-            serialized.clear();
+        let mut serialized = self.pool.take_buffer();
+        for (frame_batch, attempts) in timed_out {
+            self.congestion.on_timeout(frame_batch.sequence_number);
+            self.retransmit(frame_batch, attempts + 1, &mut serialized)?;
         }
+        self.pool.recycle_buffer(serialized);
+
+        Ok(())
+    }
+
+    /// Sends a batch again and puts it back in the recovery queue in case the retransmission is lost as well.
+    ///
+    /// Retransmissions are charged to the pacer, so that lost data does not let a connection exceed its send rate.
+    ///
+    /// Once the frames in a batch have been retransmitted [`MAX_RETRANSMISSIONS`] times, the client is considered
+    /// unreachable and the session is closed instead.
+    fn retransmit(&self, frame_batch: FrameBatch, attempts: u32, serialized: &mut Vec<u8>) -> anyhow::Result<()> {
+        if attempts > MAX_RETRANSMISSIONS {
+            self.pool.recycle_batch(frame_batch);
+            if !self.active.is_cancelled() {
+                SESSION_TIMEOUTS_METRIC.inc();
+                self.set_close_reason(CloseReason::TimedOut);
+                self.peer_closed.store(true, Ordering::Release);
+                tracing::warn!("Frames were not delivered after {MAX_RETRANSMISSIONS} retransmissions, disconnecting client...");
+                self.active.cancel();
+            }
+
+            return Ok(())
+        }
+
+        serialized.clear();
+        frame_batch.serialize_into(serialized)?;
+        self.congestion.on_retransmit(frame_batch.sequence_number, serialized.len());
+        self.pacer.consume(serialized.len());
+        self.counters.record_retransmission();
 
+        self.send_datagram(serialized.as_ref());
+        self.recovery.insert_attempt(frame_batch, attempts);
         Ok(())
     }
 }
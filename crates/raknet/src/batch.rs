@@ -0,0 +1,306 @@
+use std::net::SocketAddr;
+
+use parking_lot::Mutex;
+#[cfg(target_os = "linux")]
+use tokio::net::UdpSocket;
+
+use crate::Socket;
+
+/// Maximum amount of datagrams submitted in a single system call.
+#[cfg(target_os = "linux")]
+const MAX_BATCH_SIZE: usize = 64;
+
+/// Collects the datagrams sent over a single socket during a tick.
+///
+/// Sending every datagram with its own `send_to` call becomes a significant overhead at high player counts.
+/// Instead, datagrams are queued and submitted together using [`submit`](Self::submit). On Linux this uses
+/// `sendmmsg` to send the entire batch in a single system call, other platforms and local sockets fall back
+/// to sending the datagrams one by one.
+///
+/// A batch is shared by all connections on the same socket, so a submit also sends the datagrams
+/// that other connections queued during the same tick.
+#[derive(Debug, Default)]
+pub struct DatagramBatch {
+    pending: Mutex<Vec<(SocketAddr, Vec<u8>)>>,
+}
+
+impl DatagramBatch {
+    /// Creates an empty batch.
+    pub fn new() -> DatagramBatch {
+        DatagramBatch::default()
+    }
+
+    /// Queues a datagram for the given address.
+    pub fn push(&self, address: SocketAddr, datagram: &[u8]) {
+        self.pending.lock().push((address, datagram.to_vec()));
+    }
+
+    /// Amount of datagrams that are waiting to be sent.
+    pub fn len(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    /// Whether there are no datagrams waiting to be sent.
+    pub fn is_empty(&self) -> bool {
+        self.pending.lock().is_empty()
+    }
+
+    /// Sends all queued datagrams over the given socket.
+    ///
+    /// A datagram that fails to send does not prevent the rest of the batch from being sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error that occurred, after all other datagrams have been sent.
+    pub async fn submit(&self, socket: &Socket) -> anyhow::Result<()> {
+        let datagrams = std::mem::take(&mut *self.pending.lock());
+        if datagrams.is_empty() {
+            return Ok(())
+        }
+
+        send_batch(socket, &datagrams).await?;
+        Ok(())
+    }
+}
+
+/// A pool of buffers that multiple datagrams are received into at once.
+///
+/// Receiving datagrams one at a time means that a burst of packets requires a wakeup for every single packet.
+/// This batch drains as many datagrams as are available, up to its capacity, every time the socket becomes
+/// readable. On Linux the datagrams are received with a single `recvmmsg` call.
+#[derive(Debug)]
+pub struct ReceiveBatch {
+    /// Buffers that datagrams are received into.
+    buffers: Vec<Vec<u8>>,
+    /// Buffer index, length and source of every received datagram.
+    received: Vec<(usize, usize, SocketAddr)>,
+}
+
+impl ReceiveBatch {
+    /// Creates a batch of `capacity` buffers that can each hold a datagram of `size` bytes.
+    pub fn new(capacity: usize, size: usize) -> ReceiveBatch {
+        ReceiveBatch { buffers: vec![vec![0; size]; capacity.max(1)], received: Vec::with_capacity(capacity.max(1)) }
+    }
+
+    /// Waits for datagrams to arrive and receives as many as possible, returning the amount that was received.
+    ///
+    /// The previously received datagrams are discarded.
+    pub async fn recv(&mut self, socket: &Socket) -> std::io::Result<usize> {
+        self.received.clear();
+
+        #[cfg(target_os = "linux")]
+        if let Socket::Udp(socket) = socket {
+            let buffers = &mut self.buffers;
+            let received = &mut self.received;
+            socket.async_io(tokio::io::Interest::READABLE, || recvmmsg(socket, buffers, received)).await?;
+
+            return Ok(self.received.len())
+        }
+
+        let (n, address) = socket.recv_from(&mut self.buffers[0]).await?;
+        self.received.push((0, n, address));
+
+        // Drain everything that is already queued without waiting.
+        // Errors are reported by the next call instead, so that the datagrams received so far are not lost.
+        while self.received.len() < self.buffers.len() {
+            let index = self.received.len();
+            match socket.try_recv_from(&mut self.buffers[index]) {
+                Ok((n, address)) => self.received.push((index, n, address)),
+                Err(_) => break,
+            }
+        }
+
+        Ok(self.received.len())
+    }
+
+    /// Iterates over the datagrams that were received by the last call to [`recv`](Self::recv).
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], SocketAddr)> {
+        self.received.iter().map(|&(index, n, address)| (&self.buffers[index][..n], address))
+    }
+}
+
+/// Sends the datagrams using as few system calls as possible.
+///
+/// Failed datagrams are skipped and the first error is returned once the rest has been sent.
+async fn send_batch(socket: &Socket, datagrams: &[(SocketAddr, Vec<u8>)]) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    if let Socket::Udp(socket) = socket {
+        return send_udp_batch(socket, datagrams).await
+    }
+
+    let mut error = None;
+    for (address, datagram) in datagrams {
+        if let Err(err) = socket.send_to(datagram, *address).await {
+            error.get_or_insert(err);
+        }
+    }
+
+    error.map_or(Ok(()), Err)
+}
+
+/// Sends the datagrams using as few `sendmmsg` calls as possible.
+#[cfg(target_os = "linux")]
+async fn send_udp_batch(socket: &UdpSocket, datagrams: &[(SocketAddr, Vec<u8>)]) -> std::io::Result<()> {
+    use tokio::io::Interest;
+
+    let mut error = None;
+    for chunk in datagrams.chunks(MAX_BATCH_SIZE) {
+        let mut sent = 0;
+        while sent < chunk.len() {
+            match socket.async_io(Interest::WRITABLE, || sendmmsg(socket, &chunk[sent..])).await {
+                Ok(n) => sent += n,
+                Err(err) => {
+                    // `sendmmsg` only fails if the first datagram could not be sent, so that one is skipped.
+                    error.get_or_insert(err);
+                    sent += 1;
+                }
+            }
+        }
+    }
+
+    error.map_or(Ok(()), Err)
+}
+
+/// Converts an address to its C representation.
+#[cfg(target_os = "linux")]
+#[allow(clippy::redundant_pub_crate)] // Also used to bind sharded sockets, but should not be exported.
+#[allow(clippy::missing_const_for_fn)] // Writing through raw pointers in const functions requires Rust 1.83.
+pub(crate) fn to_sockaddr(address: SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // SAFETY: `sockaddr_storage` is a plain C struct for which all zeroes is a valid value.
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+
+    let len = match address {
+        SocketAddr::V4(v4) => {
+            let raw = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: v4.port().to_be(),
+                sin_addr: libc::in_addr { s_addr: u32::from_ne_bytes(v4.ip().octets()) },
+                sin_zero: [0; 8],
+            };
+
+            // SAFETY: `sockaddr_storage` is large enough and suitably aligned to hold any socket address.
+            unsafe { std::ptr::write(std::ptr::addr_of_mut!(storage).cast::<libc::sockaddr_in>(), raw) };
+            std::mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(v6) => {
+            let raw = libc::sockaddr_in6 {
+                sin6_family: libc::AF_INET6 as libc::sa_family_t,
+                sin6_port: v6.port().to_be(),
+                sin6_flowinfo: v6.flowinfo(),
+                sin6_addr: libc::in6_addr { s6_addr: v6.ip().octets() },
+                sin6_scope_id: v6.scope_id(),
+            };
+
+            // SAFETY: `sockaddr_storage` is large enough and suitably aligned to hold any socket address.
+            unsafe { std::ptr::write(std::ptr::addr_of_mut!(storage).cast::<libc::sockaddr_in6>(), raw) };
+            std::mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+
+    (storage, len as libc::socklen_t)
+}
+
+/// Converts a C socket address back to a Rust address.
+#[cfg(target_os = "linux")]
+fn from_sockaddr(storage: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+    match i32::from(storage.ss_family) {
+        libc::AF_INET => {
+            // SAFETY: The family indicates that the storage contains a `sockaddr_in`.
+            let raw = unsafe { &*std::ptr::addr_of!(*storage).cast::<libc::sockaddr_in>() };
+            let ip = Ipv4Addr::from(raw.sin_addr.s_addr.to_ne_bytes());
+
+            Some(SocketAddr::V4(SocketAddrV4::new(ip, u16::from_be(raw.sin_port))))
+        }
+        libc::AF_INET6 => {
+            // SAFETY: The family indicates that the storage contains a `sockaddr_in6`.
+            let raw = unsafe { &*std::ptr::addr_of!(*storage).cast::<libc::sockaddr_in6>() };
+            let ip = Ipv6Addr::from(raw.sin6_addr.s6_addr);
+
+            Some(SocketAddr::V6(SocketAddrV6::new(ip, u16::from_be(raw.sin6_port), raw.sin6_flowinfo, raw.sin6_scope_id)))
+        }
+        _ => None,
+    }
+}
+
+/// Receives as many datagrams as are available with a single `recvmmsg` call.
+#[cfg(target_os = "linux")]
+fn recvmmsg(socket: &UdpSocket, buffers: &mut [Vec<u8>], received: &mut Vec<(usize, usize, SocketAddr)>) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: `sockaddr_storage` is a plain C struct for which all zeroes is a valid value.
+    let mut names: Vec<libc::sockaddr_storage> = vec![unsafe { std::mem::zeroed() }; buffers.len()];
+    let mut iovecs = buffers
+        .iter_mut()
+        .map(|buffer| libc::iovec { iov_base: buffer.as_mut_ptr().cast(), iov_len: buffer.len() })
+        .collect::<Vec<_>>();
+
+    let mut messages = iovecs
+        .iter_mut()
+        .zip(&mut names)
+        .map(|(iovec, name)| {
+            // SAFETY: `mmsghdr` is a plain C struct for which all zeroes is a valid value.
+            let mut message: libc::mmsghdr = unsafe { std::mem::zeroed() };
+            message.msg_hdr.msg_name = std::ptr::addr_of_mut!(*name).cast();
+            message.msg_hdr.msg_namelen = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            message.msg_hdr.msg_iov = iovec;
+            message.msg_hdr.msg_iovlen = 1;
+
+            message
+        })
+        .collect::<Vec<_>>();
+
+    // SAFETY: Every message points to a valid address buffer and a single writable buffer of the given length,
+    // all of which outlive the call.
+    let count = unsafe {
+        libc::recvmmsg(socket.as_raw_fd(), messages.as_mut_ptr(), messages.len() as libc::c_uint, 0, std::ptr::null_mut())
+    };
+    if count < 0 {
+        return Err(std::io::Error::last_os_error())
+    }
+
+    for (index, (message, name)) in messages.iter().zip(&names).take(count as usize).enumerate() {
+        if let Some(address) = from_sockaddr(name) {
+            received.push((index, message.msg_len as usize, address));
+        }
+    }
+
+    Ok(())
+}
+
+/// Submits the datagrams with a single `sendmmsg` call, returning the amount of datagrams that were sent.
+#[cfg(target_os = "linux")]
+fn sendmmsg(socket: &UdpSocket, datagrams: &[(SocketAddr, Vec<u8>)]) -> std::io::Result<usize> {
+    use std::os::fd::AsRawFd;
+
+    let mut names = datagrams.iter().map(|&(address, _)| to_sockaddr(address)).collect::<Vec<_>>();
+    let mut iovecs = datagrams
+        .iter()
+        .map(|(_, datagram)| libc::iovec { iov_base: datagram.as_ptr().cast_mut().cast(), iov_len: datagram.len() })
+        .collect::<Vec<_>>();
+
+    let mut messages = iovecs
+        .iter_mut()
+        .zip(&mut names)
+        .map(|(iovec, (name, name_len))| {
+            // SAFETY: `mmsghdr` is a plain C struct for which all zeroes is a valid value.
+            let mut message: libc::mmsghdr = unsafe { std::mem::zeroed() };
+            message.msg_hdr.msg_name = std::ptr::addr_of_mut!(*name).cast();
+            message.msg_hdr.msg_namelen = *name_len;
+            message.msg_hdr.msg_iov = iovec;
+            message.msg_hdr.msg_iovlen = 1;
+
+            message
+        })
+        .collect::<Vec<_>>();
+
+    // SAFETY: Every message points to its own valid address and a single buffer, all of which outlive the call.
+    // The kernel only reads from the buffers.
+    let sent = unsafe { libc::sendmmsg(socket.as_raw_fd(), messages.as_mut_ptr(), messages.len() as libc::c_uint, 0) };
+    match sent {
+        ..0 => Err(std::io::Error::last_os_error()),
+        0 => Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "sendmmsg did not send any datagrams")),
+        sent => Ok(sent as usize),
+    }
+}
@@ -1,26 +1,109 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{fmt, net::SocketAddr, sync::Arc};
 
-use proto::bedrock::ConnectedPacket;
+use proto::bedrock::{ConnectedPacket, PermissionLevel};
+use proto::types::Dimension;
 
-use util::{RVec, Serialize};
+use util::{RVec, Serialize, Vector};
+
+/// State of a session that broadcast filters can match on.
+///
+/// This is provided by the protocol running on top of RakNet when a broadcast is received.
+#[derive(Debug, Copy, Clone)]
+pub struct RecipientInfo {
+    /// Address of the session.
+    pub address: SocketAddr,
+    /// Dimension that the player is in.
+    pub dimension: Dimension,
+    /// Permission level of the player.
+    pub permission_level: PermissionLevel,
+    /// X coordinate of the chunk that the player is in.
+    pub chunk_x: i32,
+    /// Z coordinate of the chunk that the player is in.
+    pub chunk_z: i32,
+    /// Render distance of the player in chunks.
+    pub render_distance: u16,
+}
+
+/// Predicate used by [`Recipients::Matching`].
+pub type RecipientFilter = Arc<dyn Fn(&RecipientInfo) -> bool + Send + Sync>;
+
+/// Decides which sessions receive a broadcast.
+#[derive(Clone, Default)]
+pub enum Recipients {
+    /// Every session receives the broadcast.
+    #[default]
+    All,
+    /// Every session other than the one with this address, which is usually the sender.
+    Except(SocketAddr),
+    /// Only the sessions with these addresses.
+    Only(Arc<[SocketAddr]>),
+    /// Only the sessions that match the predicate.
+    ///
+    /// Sessions that have not finished logging in never match.
+    Matching(RecipientFilter),
+}
+
+impl Recipients {
+    /// Sessions whose player is in the given dimension.
+    pub fn in_dimension(dimension: Dimension) -> Recipients {
+        Recipients::Matching(Arc::new(move |r| r.dimension == dimension))
+    }
+
+    /// Sessions whose player has at least the given permission level.
+    pub fn with_permission(level: PermissionLevel) -> Recipients {
+        Recipients::Matching(Arc::new(move |r| r.permission_level as u8 >= level as u8))
+    }
+
+    /// Sessions that have the given block position within their render distance.
+    pub fn in_range(dimension: Dimension, position: Vector<f32, 3>) -> Recipients {
+        let chunk_x = (position.x / 16.0).floor() as i32;
+        let chunk_z = (position.z / 16.0).floor() as i32;
+
+        Recipients::Matching(Arc::new(move |r| {
+            let distance = (r.chunk_x - chunk_x).abs().max((r.chunk_z - chunk_z).abs());
+            r.dimension == dimension && distance <= i32::from(r.render_distance)
+        }))
+    }
+
+    /// Whether the session with the given address should receive the broadcast.
+    ///
+    /// The state of the session is only requested when a predicate has to be evaluated.
+    pub fn includes<F>(&self, address: SocketAddr, info: F) -> bool
+    where
+        F: FnOnce() -> Option<RecipientInfo>,
+    {
+        match self {
+            Recipients::All => true,
+            Recipients::Except(except) => *except != address,
+            Recipients::Only(only) => only.contains(&address),
+            Recipients::Matching(filter) => info().is_some_and(|info| filter(&info)),
+        }
+    }
+}
+
+impl fmt::Debug for Recipients {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recipients::All => f.write_str("All"),
+            Recipients::Except(address) => f.debug_tuple("Except").field(address).finish(),
+            Recipients::Only(addresses) => f.debug_tuple("Only").field(addresses).finish(),
+            Recipients::Matching(_) => f.write_str("Matching"),
+        }
+    }
+}
 
 /// A packet that can be broadcast to other sessions.
 ///
-/// Unlike standard raknet, this packet contains an optional sender.
-/// As every session listens to a single broadcast channel, this sender field can be used
-/// to prevent a session from receiving its own broadcast.
-/// In case the session is meant to receive their own packet (such as with the [`Text`](crate::network::TextMessage) packet)
-/// this field should be set to `None`.
+/// Every session listens to a single broadcast channel and uses the [`Recipients`] of the packet to decide
+/// whether it should forward the packet to its client. This makes it possible to broadcast to every
+/// client other than the sender, to the players in a dimension or to the players near a position without
+/// having to look up the clients manually.
 ///
 /// Additionally, the actual buffer content is reference counted to allow for cheap cloning.
 #[derive(Debug, Clone)]
 pub struct BroadcastPacket {
-    /// XUID of the sender of the packet.
-    ///
-    /// If this is Some, every session that receives the broadcast will check the XUID with its own.
-    /// If it matches, the packet will not be sent.
-    /// This can be used to broadcast raknet to every client other than self.
-    pub sender: Option<SocketAddr>,
+    /// Sessions that should receive the packet.
+    pub recipients: Recipients,
     /// The ID of the packet.
     pub id: u32,
     /// Content of the packet.
@@ -32,13 +115,20 @@ pub struct BroadcastPacket {
 
 impl BroadcastPacket {
     /// Creates a new broadcast packet from the given packet.
+    ///
+    /// If a sender is given, the packet is sent to every session other than the sender.
     pub fn new<T: ConnectedPacket + Serialize>(
         packet: T,
         sender: Option<SocketAddr>,
         // algorithm: Option<CompressionAlgorithm>
     ) -> anyhow::Result<Self> {
+        Self::with_recipients(packet, sender.map_or(Recipients::All, Recipients::Except))
+    }
+
+    /// Creates a new broadcast packet that is only sent to the given recipients.
+    pub fn with_recipients<T: ConnectedPacket + Serialize>(packet: T, recipients: Recipients) -> anyhow::Result<Self> {
         Ok(Self {
-            sender,
+            recipients,
             id: T::ID,
             content: Arc::from(packet.serialize()?),
         })
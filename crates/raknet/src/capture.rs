@@ -0,0 +1,238 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+
+/// Block type of the section header block.
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d_0d0a;
+/// Block type of the interface description block.
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+/// Block type of the enhanced packet block.
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+/// Used by readers to detect the byte order of the file.
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+/// Option code of the interface name.
+const OPTION_IF_NAME: u16 = 2;
+/// Link type of raw IPv4 and IPv6 packets.
+const LINKTYPE_RAW: u16 = 101;
+/// Link type reserved for private use, used for decoded game packets.
+const LINKTYPE_USER0: u16 = 147;
+
+/// Interface that raw datagrams are recorded on.
+const DATAGRAM_INTERFACE: u32 = 0;
+/// Interface that decoded payloads are recorded on.
+const PAYLOAD_INTERFACE: u32 = 1;
+
+/// Direction of a captured packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// The packet was received from the peer.
+    Inbound,
+    /// The packet was sent to the peer.
+    Outbound,
+}
+
+/// Writes the traffic of a single connection to a pcapng file.
+///
+/// Raw datagrams are wrapped in synthesized IP and UDP headers, which allows Wireshark to dissect the RakNet
+/// layer as if the traffic had been captured on the network interface. Protocols on top of RakNet can also
+/// record their payloads after decryption using [`record_payload`](Self::record_payload), these are written to
+/// a second interface with a private link type.
+///
+/// Capturing is meant for debugging and is disabled by default. Failing to write to the file stops the capture
+/// but does not affect the connection.
+#[derive(Debug)]
+pub struct Capture {
+    writer: Mutex<BufWriter<File>>,
+    /// Address of the socket that the connection uses.
+    local: SocketAddr,
+    /// Set when a write fails, no further packets are recorded after that.
+    failed: AtomicBool,
+    /// Whether the protocol on top of RakNet should record its payloads.
+    payloads: bool,
+}
+
+impl Capture {
+    /// Creates a new capture file at the given path, replacing any existing file.
+    pub fn create<P: AsRef<Path>>(path: P, local: SocketAddr) -> io::Result<Capture> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let mut shb = Vec::with_capacity(16);
+        shb.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        shb.extend_from_slice(&1u16.to_le_bytes());
+        shb.extend_from_slice(&0u16.to_le_bytes());
+        // Section length is unknown.
+        shb.extend_from_slice(&(-1i64).to_le_bytes());
+        write_block(&mut writer, SECTION_HEADER_BLOCK, &shb)?;
+
+        write_block(&mut writer, INTERFACE_DESCRIPTION_BLOCK, &interface(LINKTYPE_RAW, "raknet"))?;
+        write_block(&mut writer, INTERFACE_DESCRIPTION_BLOCK, &interface(LINKTYPE_USER0, "payload"))?;
+
+        Ok(Capture { writer: Mutex::new(writer), local, failed: AtomicBool::new(false), payloads: false })
+    }
+
+    /// Sets whether payloads should be recorded in addition to raw datagrams.
+    #[must_use]
+    pub const fn with_payloads(mut self, enabled: bool) -> Capture {
+        self.payloads = enabled;
+        self
+    }
+
+    /// Whether payloads should be recorded using [`record_payload`](Self::record_payload).
+    #[inline]
+    pub const fn captures_payloads(&self) -> bool {
+        self.payloads
+    }
+
+    /// Records a raw datagram exchanged with the peer at the given address.
+    pub fn record_datagram(&self, direction: Direction, peer: SocketAddr, datagram: &[u8]) {
+        let (source, destination) = match direction {
+            Direction::Inbound => (peer, self.local),
+            Direction::Outbound => (self.local, peer),
+        };
+
+        self.record(DATAGRAM_INTERFACE, &ip_packet(source, destination, datagram));
+    }
+
+    /// Records a payload of a protocol running on top of RakNet.
+    ///
+    /// The first byte of the recorded packet is 0 for inbound and 1 for outbound payloads.
+    pub fn record_payload(&self, direction: Direction, payload: &[u8]) {
+        let mut packet = Vec::with_capacity(1 + payload.len());
+        packet.push(direction as u8);
+        packet.extend_from_slice(payload);
+
+        self.record(PAYLOAD_INTERFACE, &packet);
+    }
+
+    /// Writes any buffered packets to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer.lock().flush()
+    }
+
+    /// Writes an enhanced packet block.
+    fn record(&self, interface: u32, packet: &[u8]) {
+        if self.failed.load(Ordering::Relaxed) {
+            return
+        }
+
+        // Timestamps are in microseconds, the default resolution.
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_micros() as u64);
+
+        let mut body = Vec::with_capacity(20 + packet.len() + 3);
+        body.extend_from_slice(&interface.to_le_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        body.extend_from_slice(packet);
+        pad(&mut body);
+
+        let result = write_block(&mut *self.writer.lock(), ENHANCED_PACKET_BLOCK, &body);
+        if let Err(err) = result {
+            tracing::warn!("Stopping packet capture: {err}");
+            self.failed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Writes a block with the given type and body, the body must be padded to 32 bits.
+fn write_block<W: Write>(writer: &mut W, ty: u32, body: &[u8]) -> io::Result<()> {
+    let length = (12 + body.len()) as u32;
+
+    writer.write_all(&ty.to_le_bytes())?;
+    writer.write_all(&length.to_le_bytes())?;
+    writer.write_all(body)?;
+    writer.write_all(&length.to_le_bytes())
+}
+
+/// Creates the body of an interface description block.
+fn interface(link_type: u16, name: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&link_type.to_le_bytes());
+    body.extend_from_slice(&0u16.to_le_bytes());
+    // No snapshot length limit.
+    body.extend_from_slice(&0u32.to_le_bytes());
+
+    body.extend_from_slice(&OPTION_IF_NAME.to_le_bytes());
+    body.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    body.extend_from_slice(name.as_bytes());
+    pad(&mut body);
+
+    // End of options.
+    body.extend_from_slice(&[0; 4]);
+    body
+}
+
+/// Pads the buffer to a multiple of 32 bits.
+fn pad(buf: &mut Vec<u8>) {
+    buf.resize((buf.len() + 3) & !3, 0);
+}
+
+/// Wraps a datagram in an IP and UDP header.
+///
+/// IPv4 is used if both addresses are IPv4 addresses, otherwise both addresses are converted to IPv6.
+fn ip_packet(source: SocketAddr, destination: SocketAddr, datagram: &[u8]) -> Vec<u8> {
+    let udp_length = (8 + datagram.len()) as u16;
+    let mut packet = Vec::with_capacity(40 + udp_length as usize);
+
+    match (source.ip(), destination.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            packet.extend_from_slice(&[0x45, 0]);
+            packet.extend_from_slice(&(20 + udp_length).to_be_bytes());
+            // Identification and the don't fragment flag.
+            packet.extend_from_slice(&[0, 0, 0x40, 0]);
+            // Time to live, UDP and a checksum that is filled in below.
+            packet.extend_from_slice(&[64, 17, 0, 0]);
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+
+            let checksum = ipv4_checksum(&packet);
+            packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        }
+        (src, dst) => {
+            let src = match src {
+                IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+                IpAddr::V6(v6) => v6,
+            };
+            let dst = match dst {
+                IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+                IpAddr::V6(v6) => v6,
+            };
+
+            packet.extend_from_slice(&[0x60, 0, 0, 0]);
+            packet.extend_from_slice(&udp_length.to_be_bytes());
+            // UDP and the hop limit.
+            packet.extend_from_slice(&[17, 64]);
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+        }
+    }
+
+    packet.extend_from_slice(&source.port().to_be_bytes());
+    packet.extend_from_slice(&destination.port().to_be_bytes());
+    packet.extend_from_slice(&udp_length.to_be_bytes());
+    // The UDP checksum is left empty.
+    packet.extend_from_slice(&[0, 0]);
+    packet.extend_from_slice(datagram);
+
+    packet
+}
+
+/// Computes the checksum of an IPv4 header.
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks_exact(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum::<u32>();
+
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
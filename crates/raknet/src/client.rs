@@ -1,15 +1,18 @@
-use std::{net::SocketAddr, sync::{Arc, atomic::{AtomicU16, AtomicU32, AtomicU64}}, time::Instant, mem::MaybeUninit};
+use std::{net::SocketAddr, sync::{Arc, OnceLock, atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, Ordering}}, time::{Duration, Instant}};
 
 use parking_lot::{Mutex, RwLock};
 use proto::raknet::DisconnectNotification;
-use tokio::{net::UdpSocket, sync::{broadcast, mpsc, Semaphore}};
+use tokio::sync::{broadcast, mpsc, Semaphore};
 use tokio_util::sync::CancellationToken;
 use util::{RVec, Joinable};
 
-use crate::{BroadcastPacket, Compounds, OrderChannel, Recovery, Reliability, SendConfig, SendPriority, SendQueues, BUDGET_SIZE};
+use crate::{BroadcastPacket, Capture, ClockModel, DatagramBatch, ForwardReceiver, PendingMigration, Socket, CompoundLimits, Compounds, CongestionControl, FramePool, LatencyTracker, OrderChannels, Pacer, Recovery, Reliability, ReliableWindow, SendConfig, SendPriority, SendQueues, SendWeights, SessionCounters, SessionStats, BUDGET_SIZE, INTERNAL_TICK_INTERVAL};
 
-const ORDER_CHANNEL_COUNT: usize = 5;
-const OUTPUT_CHANNEL_SIZE: usize = 5;
+/// Amount of received game packets that can wait for the game layer.
+///
+/// Clients are disconnected if the game layer falls this far behind. The queue is large enough for a few ticks worth
+/// of packets, so that short stalls of the game layer do not disconnect anyone.
+pub const OUTPUT_QUEUE_SIZE: usize = 256;
 /// A command that the Raknet layer will send to its parent.
 #[derive(Debug, PartialEq, Eq)]
 pub enum RakNetCommand {
@@ -24,6 +27,17 @@ pub enum RakNetCommand {
     Received(RVec)
 }
 
+/// Why a RakNet session was closed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The server closed the connection using [`disconnect`](RakNetClient::disconnect).
+    Local,
+    /// The client sent a disconnect notification.
+    Peer,
+    /// The client stopped responding.
+    TimedOut,
+}
+
 /// Information required to create a new RakNet user.
 pub struct RakNetCreateDescription {
     /// IP address of the client.
@@ -33,25 +47,85 @@ pub struct RakNetCreateDescription {
     /// RakNet guid of the client. This is provided by the client and is therefore not
     /// a secure way to identity clients.
     pub guid: u64,
-    /// UDP socket that is connected to the client.
-    pub socket: Arc<UdpSocket>
+    /// Socket that the client connected on.
+    ///
+    /// This is either the IPv4, IPv6 or local socket, depending on which one received the handshake.
+    /// All packets to the client are sent over this socket.
+    pub socket: Arc<Socket>,
+    /// Datagrams queued for `socket` during the current tick.
+    ///
+    /// This must be the same batch for every connection on the socket, so that their datagrams are
+    /// submitted together.
+    pub outgoing: Arc<DatagramBatch>,
+    /// Limits on the fragments that are buffered for this client.
+    pub compound_limits: CompoundLimits,
+    /// Maximum amount of frames in a single batch.
+    ///
+    /// Clients that send larger batches are disconnected.
+    pub max_batch_frames: usize,
+    /// Amount of order channels that the client is allowed to use.
+    ///
+    /// Frames on channels beyond this count are rejected as malformed.
+    pub order_channels: usize,
+    /// How often received batches are acknowledged.
+    ///
+    /// This is rounded down to a multiple of [`INTERNAL_TICK_INTERVAL`].
+    pub ack_interval: Duration,
+    /// Maximum amount of bytes per second that is sent to the client.
+    ///
+    /// A rate of 0 disables the limit.
+    pub send_rate: u64,
+    /// Share of the send budget that each priority receives.
+    pub send_weights: SendWeights,
+    /// How long the client can be unresponsive before it is disconnected.
+    pub session_timeout: Duration,
+    /// How long the client can be idle before a ping is sent to check whether it is still alive.
+    ///
+    /// An interval of 0 disables keepalive pings.
+    pub keepalive_interval: Duration,
+    /// File that the traffic of this connection is written to, if capturing is enabled.
+    pub capture: Option<Capture>,
 }
 
 /// The Raknet layer of the user. This handles the entire Raknet protocol for the client.
 pub struct RakNetClient {
     /// Cancelled when the client has fully disconnected.
     pub shutdown_token: CancellationToken,
+    /// Cancelled once the connection handshake has been completed.
+    pub connected: CancellationToken,
     /// Whether the user is still active.
     /// Cancelling this token means that all pending packets will be flushed and the server will process no more
     /// packets coming from this user.
     pub active: CancellationToken,
+    /// Set once a disconnect notification has been queued for the client.
+    pub closing: AtomicBool,
+    /// Set when the client has left on its own, either by sending a disconnect notification or by timing out.
+    ///
+    /// There is no point in waiting for acknowledgements from such a client when the session shuts down.
+    pub peer_closed: AtomicBool,
+    /// Why the session was closed, set by whichever side closed it first.
+    close_reason: OnceLock<CloseReason>,
     /// Keeps track of the remaining "budget" of this user.
     /// This is used to implement rate limiting.
     pub budget: Semaphore,
     /// IP address of the user.
-    pub address: SocketAddr,
+    ///
+    /// This can change when the connection is migrated, use [`address`](Self::address) to read it.
+    address: RwLock<SocketAddr>,
+    /// Migration to a new address that is waiting for verification.
+    pub migration: Mutex<Option<PendingMigration>>,
+    /// Whether migrations have been disabled by [`lock_migration`](Self::lock_migration).
+    pub migration_locked: AtomicBool,
+    /// RakNet GUID of the client.
+    ///
+    /// This is chosen by the client, so it does not prove the identity of the client on its own.
+    pub guid: u64,
     /// Socket used for communication with this user.
-    pub socket: Arc<UdpSocket>,
+    ///
+    /// This is the socket that the client connected on, so it matches the address family of [`address`](Self::address).
+    pub socket: Arc<Socket>,
+    /// Datagrams that are sent at the end of the current tick, shared with all users on the same socket.
+    pub outgoing: Arc<DatagramBatch>,
     /// Channel that can perform inter-user packet broadcasting.
     pub broadcast: broadcast::Sender<BroadcastPacket>,
     /// Maximum transfer unit. This is maximum size of a single packet. If a packet exceeds this size
@@ -60,6 +134,12 @@ pub struct RakNetClient {
     /// Keeps track of when the last update was received from the client.
     /// This enables disconnecting users that have lost connection to the server.
     pub last_update: RwLock<Instant>,
+    /// How long the client can be unresponsive before it is disconnected.
+    pub session_timeout: Duration,
+    /// How long the client can be idle before a keepalive ping is sent.
+    pub keepalive_interval: Duration,
+    /// When the last keepalive ping was sent.
+    pub last_keepalive: Mutex<Instant>,
     /// Increased for every round of packets processed.
     pub tick: AtomicU64,
     /// This client's current batch number. It is increased for every packet batch sent.
@@ -69,20 +149,38 @@ pub struct RakNetClient {
     /// Pending acknowledgements.
     /// Wrapped in a mutex since reading this will also clear it.
     pub acknowledged: Mutex<Vec<u32>>,
+    /// Amount of ticks between acknowledgement flushes.
+    pub ack_interval: u64,
     /// Current acknowledgement index.
     /// This is increased for every reliable packet sent.
     pub acknowledge_index: AtomicU32,
     /// Current compound index. This index uniquely identifies a compound of fragments.
     pub compound_id: AtomicU16,
+    /// Maximum amount of frames in a single batch received from the client.
+    pub max_batch_frames: usize,
     /// Collection of incomplete compounds. These compounds will slowly be filled up and
     /// will be processed when all fragments have been received.
     pub compounds: Compounds,
     /// Stores packets for recovery in case of packet loss.
     pub recovery: Recovery,
-    /// Current sequence index, this is increased for every sequenced packet sent.
-    pub sequence_index: AtomicU32,
+    /// Frame lists and serialization buffers that are reused by the send path.
+    pub pool: FramePool,
+    /// Limits the amount of unacknowledged data that is sent to the client.
+    pub congestion: CongestionControl,
+    /// Limits the rate at which data is sent to the client.
+    pub pacer: Pacer,
+    /// Round trip time measurements of this connection.
+    pub latency: LatencyTracker,
+    /// Estimated offset and drift of the clock of the other side.
+    pub clock: ClockModel,
     /// Multiple channels that ensure packets are received in the right order.
-    pub order: [OrderChannel; ORDER_CHANNEL_COUNT],
+    pub order: OrderChannels,
+    /// Reliable indices that have already been received, used to discard retransmitted duplicates.
+    pub reliable_window: ReliableWindow,
+    /// Network statistics of this connection.
+    pub counters: SessionCounters,
+    /// Records the traffic of this connection for debugging.
+    pub capture: Option<Capture>,
     /// Channel used to submit packets that have been fully processed by the RakNet layer.
     /// These packets go on to be processed further by protocols running on top of RakNet
     /// such as the Minecraft Bedrock protocol.
@@ -94,68 +192,114 @@ impl RakNetClient {
     pub fn new(
         info: RakNetCreateDescription, 
         broadcast: broadcast::Sender<BroadcastPacket>,
-        forward_rx: mpsc::Receiver<RVec>
+        forward_rx: ForwardReceiver
     ) -> (Arc<Self>, mpsc::Receiver<RakNetCommand>) {
-        // SAFETY: MaybeUninit does not require initialization, so it is safe to create an array
-        // of them like this.
-        let mut order_channels: [MaybeUninit<OrderChannel>; ORDER_CHANNEL_COUNT] = unsafe {
-            MaybeUninit::uninit().assume_init()
-        };
-        
-        for channel in &mut order_channels {
-            channel.write(OrderChannel::new());
-        }
-
-        // SAFETY: This is safe because `MaybeUninit<T>` has the same memory layout as `T`.
-        // It is safe to transmute between them.
-        let order_channels = unsafe { 
-            std::mem::transmute::<
-                [MaybeUninit<OrderChannel>; ORDER_CHANNEL_COUNT], 
-                [OrderChannel; ORDER_CHANNEL_COUNT]
-            >(order_channels)
-        };
-
-        let (output_tx, output_rx) = mpsc::channel(OUTPUT_CHANNEL_SIZE);
+        let (output_tx, output_rx) = mpsc::channel(OUTPUT_QUEUE_SIZE);
 
         let state = Arc::new(RakNetClient {
             budget: Semaphore::new(BUDGET_SIZE),
             active: CancellationToken::new(),
-            address: info.address,
+            closing: AtomicBool::new(false),
+            peer_closed: AtomicBool::new(false),
+            close_reason: OnceLock::new(),
+            connected: CancellationToken::new(),
+            address: RwLock::new(info.address),
+            migration: Mutex::new(None),
+            migration_locked: AtomicBool::new(false),
+            guid: info.guid,
             last_update: RwLock::new(Instant::now()),
+            session_timeout: info.session_timeout,
+            keepalive_interval: info.keepalive_interval,
+            last_keepalive: Mutex::new(Instant::now()),
             socket: info.socket,
+            outgoing: info.outgoing,
             broadcast,
             tick: AtomicU64::new(0),
             batch_number: AtomicU32::new(0),
-            send: SendQueues::new(),
+            send: SendQueues::with_weights(info.send_weights),
             acknowledged: Mutex::new(Vec::with_capacity(5)),
+            // Acknowledgements are sent at most once per tick.
+            ack_interval: (info.ack_interval.as_millis() / INTERNAL_TICK_INTERVAL.as_millis()).max(1) as u64,
             recovery: Recovery::new(),
+            pool: FramePool::new(),
+            congestion: CongestionControl::new(info.mtu),
+            pacer: Pacer::new(info.send_rate),
+            latency: LatencyTracker::new(),
+            clock: ClockModel::new(),
             mtu: info.mtu,
             acknowledge_index: AtomicU32::new(0),
             compound_id: AtomicU16::new(0),
-            compounds: Compounds::new(),
-            sequence_index: AtomicU32::new(0),
-            order: order_channels,
+            compounds: Compounds::with_limits(info.compound_limits),
+            max_batch_frames: info.max_batch_frames,
+            order: OrderChannels::with_count(info.order_channels),
+            reliable_window: ReliableWindow::new(),
+            counters: SessionCounters::default(),
+            capture: info.capture,
             output: output_tx,
             shutdown_token: CancellationToken::new()
         });
 
-        tokio::spawn(Arc::clone(&state).receiver(forward_rx));
+        util::task::spawn_owned("raknet::receiver", state.address(), Arc::clone(&state).receiver(forward_rx));
     
         (state, output_rx)
     }
 
+    /// Address of the client.
+    #[inline]
+    pub fn address(&self) -> SocketAddr {
+        *self.address.read()
+    }
+
+    /// Moves the connection to a new address and returns the previous one.
+    ///
+    /// This does not verify that the client owns the new address, see [`begin_migration`](Self::begin_migration).
+    pub fn rebind(&self, address: SocketAddr) -> SocketAddr {
+        std::mem::replace(&mut *self.address.write(), address)
+    }
+
+    /// Returns a snapshot of the network statistics of this connection.
+    pub fn stats(&self) -> SessionStats {
+        self.counters.snapshot(self.send.len(), self.order.buffered())
+    }
+
     /// Resets the request budget of this client.
     #[inline]
     pub fn refill_budget(&self) {
         self.budget.add_permits(BUDGET_SIZE - self.budget.available_permits());
     }
 
-    /// Sends a RakNet disconnect packet to the client.
+    /// Closes the connection.
+    ///
+    /// A disconnect notification is sent to the client and the session starts shutting down. Before it is torn down,
+    /// the session lingers until the client has acknowledged all remaining reliable frames, so that packets such as
+    /// a kick message and the notification itself actually arrive.
+    ///
+    /// This is safe to call multiple times, the notification is only sent once. No notification is sent if the
+    /// client has already closed the connection itself.
     pub fn disconnect(&self) {
-        self.send_raw_buffer_with_config(vec![DisconnectNotification::ID], SendConfig {
-            reliability: Reliability::Reliable,
-            priority: SendPriority::High
-        });
+        self.set_close_reason(CloseReason::Local);
+        if !self.closing.swap(true, Ordering::AcqRel) && !self.peer_closed.load(Ordering::Acquire) {
+            self.send_raw_buffer_with_config(vec![DisconnectNotification::ID], SendConfig {
+                reliability: Reliability::Reliable,
+                priority: SendPriority::High,
+                channel: 0,
+            });
+        }
+
+        self.active.cancel();
+    }
+}
+
+impl RakNetClient {
+    /// Why the session was closed, or `None` if it is still open.
+    #[inline]
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.close_reason.get().copied()
+    }
+
+    /// Records why the session is closing, unless a reason has already been recorded.
+    pub(crate) fn set_close_reason(&self, reason: CloseReason) {
+        let _: Result<(), CloseReason> = self.close_reason.set(reason);
     }
 }
 
@@ -164,7 +308,7 @@ impl Joinable for RakNetClient {
         skip_all,
         name = "RaknetUser::join",
         fields(
-            %address = %self.address
+            %address = %self.address()
         )
     )]
     /// Waits for the client to fully disconnect.
@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::RakNetClient;
+
+/// Amount of samples that the estimate is based on.
+const MAX_CLOCK_SAMPLES: usize = 32;
+/// Samples with a round trip longer than this are too imprecise to be useful.
+const MAX_SAMPLE_RTT: Duration = Duration::from_secs(5);
+/// Largest drift that is considered plausible, in milliseconds per millisecond.
+///
+/// Even cheap crystal oscillators stay well within 1000 ppm, anything beyond that is measurement noise.
+const MAX_DRIFT: f64 = 0.001;
+
+/// A single measurement of the offset between the remote and local clock.
+#[derive(Debug, Copy, Clone)]
+struct ClockSample {
+    /// Local time at which the remote timestamp was taken.
+    local: f64,
+    /// Remote time minus local time.
+    offset: f64,
+}
+
+/// Current estimate of the remote clock.
+#[derive(Debug, Default)]
+struct ClockState {
+    samples: VecDeque<ClockSample>,
+    /// Offset at local time zero.
+    intercept: f64,
+    /// How many milliseconds the remote clock gains per local millisecond.
+    drift: f64,
+}
+
+impl ClockState {
+    /// Fits a line through the offset samples using least squares.
+    fn update(&mut self) {
+        let n = self.samples.len() as f64;
+        let mean_local = self.samples.iter().map(|s| s.local).sum::<f64>() / n;
+        let mean_offset = self.samples.iter().map(|s| s.offset).sum::<f64>() / n;
+
+        let (mut covariance, mut variance) = (0.0, 0.0);
+        for sample in &self.samples {
+            covariance += (sample.local - mean_local) * (sample.offset - mean_offset);
+            variance += (sample.local - mean_local).powi(2);
+        }
+
+        // Drift can only be estimated once the samples are spread out over time.
+        self.drift = if variance > 0.0 { (covariance / variance).clamp(-MAX_DRIFT, MAX_DRIFT) } else { 0.0 };
+        self.intercept = self.drift.mul_add(-mean_local, mean_offset);
+    }
+}
+
+/// Models the clock of the other side of a connection.
+///
+/// Timestamps sent by the peer are taken from a clock that has an unknown offset from the local clock
+/// and runs at a slightly different rate. Every ping exchange yields a measurement of the offset, which is used to
+/// estimate both the offset and the drift. All timestamps are in milliseconds, local timestamps are those of the
+/// connection's [`LatencyTracker`](crate::LatencyTracker).
+///
+/// Systems that deal with remote timestamps should convert them with [`to_local`](Self::to_local) instead
+/// of comparing them to local time directly.
+#[derive(Debug, Default)]
+pub struct ClockModel {
+    state: Mutex<ClockState>,
+}
+
+impl ClockModel {
+    /// Creates a model without any measurements.
+    pub fn new() -> ClockModel {
+        ClockModel::default()
+    }
+
+    /// Records a full round trip.
+    ///
+    /// `remote` is the time of the peer at some point between sending the request at `sent` and receiving the
+    /// reply at `received`. The peer is assumed to have replied halfway through the round trip.
+    pub fn record(&self, sent: i64, remote: i64, received: i64) {
+        let Ok(rtt) = u64::try_from(received - sent) else {
+            return
+        };
+
+        if Duration::from_millis(rtt) > MAX_SAMPLE_RTT {
+            return
+        }
+
+        let local = (sent + received) as f64 / 2.0;
+        self.push(ClockSample { local, offset: remote as f64 - local });
+    }
+
+    /// Records a timestamp that the peer sent at `remote`, which was received at `received`.
+    ///
+    /// The one-way delay is assumed to be half of the given round trip time.
+    pub fn record_one_way(&self, remote: i64, received: i64, rtt: Duration) {
+        if rtt > MAX_SAMPLE_RTT {
+            return
+        }
+
+        let local = received as f64 - rtt.as_secs_f64() * 1000.0 / 2.0;
+        self.push(ClockSample { local, offset: remote as f64 - local });
+    }
+
+    fn push(&self, sample: ClockSample) {
+        let mut state = self.state.lock();
+        if state.samples.len() == MAX_CLOCK_SAMPLES {
+            state.samples.pop_front();
+        }
+
+        state.samples.push_back(sample);
+        state.update();
+    }
+
+    /// Whether any measurements have been made yet.
+    ///
+    /// Without measurements, remote timestamps are assumed to be equal to local timestamps.
+    pub fn is_synchronised(&self) -> bool {
+        !self.state.lock().samples.is_empty()
+    }
+
+    /// Estimated remote time minus local time at the given local time.
+    pub fn offset(&self, local: i64) -> i64 {
+        let state = self.state.lock();
+        state.drift.mul_add(local as f64, state.intercept).round() as i64
+    }
+
+    /// Estimated drift of the remote clock, in milliseconds gained per local millisecond.
+    pub fn drift(&self) -> f64 {
+        self.state.lock().drift
+    }
+
+    /// Converts a remote timestamp to the local clock.
+    pub fn to_local(&self, remote: i64) -> i64 {
+        let state = self.state.lock();
+        ((remote as f64 - state.intercept) / (1.0 + state.drift)).round() as i64
+    }
+
+    /// Converts a local timestamp to the remote clock.
+    pub fn to_remote(&self, local: i64) -> i64 {
+        local + self.offset(local)
+    }
+}
+
+impl RakNetClient {
+    /// Estimated time of the client minus the time of the server in milliseconds, or `None` if no ping
+    /// exchange has completed yet.
+    ///
+    /// The offset is derived from the timestamps in [`ConnectedPing`](proto::raknet::ConnectedPing) and
+    /// [`ConnectedPong`](proto::raknet::ConnectedPong) packets.
+    pub fn clock_offset(&self) -> Option<i64> {
+        self.clock.is_synchronised().then(|| self.clock.offset(self.latency.timestamp()))
+    }
+
+    /// How many milliseconds ago the client took the given timestamp, according to the server's clock.
+    ///
+    /// Negative values indicate timestamps that lie in the future, which should not happen for honest clients
+    /// beyond the estimation error. Returns `None` if no ping exchange has completed yet.
+    pub fn timestamp_age(&self, remote: i64) -> Option<i64> {
+        self.clock.is_synchronised().then(|| self.latency.timestamp() - self.clock.to_local(remote))
+    }
+}
@@ -1,26 +1,116 @@
 use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use util::RVec;
 
-use crate::Frame;
+use crate::{Frame, LimitExceeded};
+
+/// Default maximum amount of fragments that a compound can consist of.
+///
+/// Even the largest packets sent by clients (such as a login with a custom skin) fit comfortably within this limit.
+pub const MAX_COMPOUND_SIZE: u32 = 1024;
+
+/// Limits on the fragments that are buffered for a single connection.
+///
+/// Without these, a client could send the first fragments of endless compounds and never complete them,
+/// slowly exhausting the memory of the server.
+#[derive(Debug, Copy, Clone)]
+pub struct CompoundLimits {
+    /// Maximum amount of incomplete compounds.
+    pub max_compounds: usize,
+    /// Maximum total size of all buffered fragments.
+    pub max_bytes: usize,
+    /// Maximum amount of fragments that a single compound can consist of.
+    pub max_fragments: u32,
+    /// Maximum size of a single compound once all of its fragments have been received.
+    pub max_compound_bytes: usize,
+    /// Incomplete compounds are discarded after this long.
+    pub timeout: Duration,
+}
+
+impl Default for CompoundLimits {
+    fn default() -> Self {
+        Self {
+            max_compounds: 32,
+            max_bytes: 4 * 1024 * 1024,
+            max_fragments: MAX_COMPOUND_SIZE,
+            max_compound_bytes: 2 * 1024 * 1024,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Fragments of a single compound.
+#[derive(Debug)]
+struct Compound {
+    /// Fragments received so far, indexed by their compound index.
+    fragments: Vec<Option<Frame>>,
+    /// Total size of the bodies of the received fragments.
+    bytes: usize,
+    /// When the first fragment was received.
+    created: Instant,
+}
 
 /// Keeps track of packet fragments, merging them when all fragments have been received.
 #[derive(Default, Debug)]
 pub struct Compounds {
-    compounds: DashMap<u16, Vec<Option<Frame>>>,
+    compounds: DashMap<u16, Compound>,
+    /// Total size of all buffered fragments.
+    bytes: AtomicUsize,
+    limits: CompoundLimits,
 }
 
 impl Compounds {
-    /// Creates a new collector.
+    /// Creates a new collector with the default limits.
     pub fn new() -> Compounds {
-        Compounds { compounds: DashMap::new() }
+        Compounds::with_limits(CompoundLimits::default())
+    }
+
+    /// Creates a new collector with the given limits.
+    pub fn with_limits(limits: CompoundLimits) -> Compounds {
+        Compounds { compounds: DashMap::new(), bytes: AtomicUsize::new(0), limits }
+    }
+
+    /// Amount of incomplete compounds.
+    pub fn len(&self) -> usize {
+        self.compounds.len()
+    }
+
+    /// Whether there are no incomplete compounds.
+    pub fn is_empty(&self) -> bool {
+        self.compounds.is_empty()
+    }
+
+    /// Total size of all buffered fragments.
+    pub fn buffered_bytes(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Discards all compounds that have not been completed within the timeout.
+    ///
+    /// Returns the amount of discarded compounds.
+    pub fn expire(&self) -> usize {
+        let before = self.compounds.len();
+        self.compounds.retain(|_, compound| {
+            let expired = compound.created.elapsed() > self.limits.timeout;
+            if expired {
+                self.bytes.fetch_sub(compound.bytes, Ordering::Relaxed);
+            }
+            !expired
+        });
+
+        before.saturating_sub(self.compounds.len())
     }
 
     /// Inserts a fragment into the collector.
     ///
     /// If this fragment makes the compound complete, all fragments will be merged
     /// and the completed packet will be returned.
+    ///
+    /// Fails with [`LimitExceeded`] if the fragment exceeds one of the [`CompoundLimits`].
     #[allow(clippy::unwrap_used)] // Checks are performed before unwrapping.
     #[allow(clippy::unwrap_in_result)]
     #[allow(clippy::significant_drop_tightening)] // False positive.
@@ -33,22 +123,56 @@ impl Compounds {
                 return Ok(None)
             }
 
+            if frame.compound_size > self.limits.max_fragments {
+                return Err(LimitExceeded(format!(
+                    "Compound consists of {} fragments, which exceeds the maximum of {}", frame.compound_size, self.limits.max_fragments
+                )).into())
+            }
+
             // Save compound_index, because frame is moved by the Some constructor.
             let compound_index = frame.compound_index as usize;
+            let body_len = frame.body.len();
+
+            if self.bytes.load(Ordering::Relaxed) + body_len > self.limits.max_bytes {
+                return Err(LimitExceeded(format!("Buffered fragments exceed the limit of {} bytes", self.limits.max_bytes)).into())
+            }
 
-            let mut entry = self.compounds.entry(frame.compound_id).or_insert_with(|| {
-                let mut vec = Vec::with_capacity(frame.compound_size as usize);
+            // This must be checked before the entry is locked, since counting locks all shards.
+            if !self.compounds.contains_key(&compound_id) && self.compounds.len() >= self.limits.max_compounds {
+                return Err(LimitExceeded(format!("Client exceeded the limit of {} incomplete compounds", self.limits.max_compounds)).into())
+            }
 
-                // resize_with instead of resize, because Frame does not implement Clone
-                vec.resize_with(frame.compound_size as usize, || None);
-                vec
-            });
+            let mut entry = match self.compounds.entry(compound_id) {
+                Entry::Occupied(entry) => entry.into_ref(),
+                Entry::Vacant(entry) => {
+                    let mut fragments = Vec::with_capacity(frame.compound_size as usize);
+
+                    // resize_with instead of resize, because Frame does not implement Clone
+                    fragments.resize_with(frame.compound_size as usize, || None);
+                    entry.insert(Compound { fragments, bytes: 0, created: Instant::now() })
+                }
+            };
+
+            let compound = entry.value_mut();
+            if compound.fragments.len() != frame.compound_size as usize {
+                anyhow::bail!("Fragment size {} does not match size {} of the existing compound", frame.compound_size, compound.fragments.len());
+            }
+
+            // Duplicate fragments are ignored, they have already been counted.
+            if compound.fragments[compound_index].is_some() {
+                return Ok(None)
+            }
+
+            if compound.bytes + body_len > self.limits.max_compound_bytes {
+                return Err(LimitExceeded(format!("Compound exceeds the limit of {} bytes", self.limits.max_compound_bytes)).into())
+            }
 
-            let fragments = entry.value_mut();
-            fragments[compound_index] = Some(frame);
+            compound.fragments[compound_index] = Some(frame);
+            compound.bytes += body_len;
+            self.bytes.fetch_add(body_len, Ordering::Relaxed);
 
             // Verify that the fragment index is valid
-            !fragments.iter().any(Option::is_none)
+            !compound.fragments.iter().any(Option::is_none)
         };
 
         if is_completed {
@@ -57,7 +181,8 @@ impl Compounds {
                 .remove(&compound_id)
                 .unwrap();
 
-            let fragments = &mut kv.1;
+            self.bytes.fetch_sub(kv.1.bytes, Ordering::Relaxed);
+            let fragments = &mut kv.1.fragments;
 
             // Merge all fragments
             let mut merged = RVec::alloc_with_capacity(
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Retransmission timeout used before any round trip time has been measured.
+const INITIAL_RTO: Duration = Duration::from_secs(1);
+/// Lower bound of the retransmission timeout.
+const MIN_RTO: Duration = Duration::from_millis(100);
+/// Upper bound of the retransmission timeout.
+const MAX_RTO: Duration = Duration::from_secs(3);
+/// Size of the congestion window in datagrams when a connection is created.
+const INITIAL_WINDOW: usize = 4;
+/// The window never shrinks below this many datagrams, even under heavy loss.
+const MIN_WINDOW: usize = 2;
+/// The window never grows beyond this many datagrams.
+const MAX_WINDOW: usize = 1024;
+/// Batch sequence numbers are encoded as 24-bit integers and wrap around.
+const SEQUENCE_MASK: u32 = 0x00ff_ffff;
+
+/// Recovery ends once this many batches have been sent after the recovery point.
+///
+/// This prevents an old recovery point from being compared against sequence numbers from the next wrap.
+const RECOVERY_HORIZON: u32 = 1 << 22;
+
+/// Whether the 24-bit sequence number `a` was sent before `b`, taking wrapping into account.
+///
+/// Numbers are compared within a window of half the sequence space, so `0` comes after `0xffffff`.
+#[inline]
+const fn precedes(a: u32, b: u32) -> bool {
+    let distance = b.wrapping_sub(a) & SEQUENCE_MASK;
+    distance != 0 && distance < (1 << 23)
+}
+
+/// Round trip time estimator as described in RFC 6298.
+///
+/// Keeps an exponentially weighted moving average of the round trip time and its mean deviation.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct RttEstimator {
+    /// Smoothed round trip time.
+    srtt: Option<Duration>,
+    /// Round trip time variation.
+    rttvar: Duration,
+}
+
+impl RttEstimator {
+    /// Creates an estimator without any measurements.
+    pub const fn new() -> RttEstimator {
+        RttEstimator { srtt: None, rttvar: Duration::ZERO }
+    }
+
+    /// Updates the estimate with a new measurement.
+    pub fn sample(&mut self, rtt: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            Some(srtt) => {
+                // `Duration::abs_diff` is not available on the minimum supported Rust version.
+                self.rttvar = (self.rttvar * 3 + srtt.max(rtt) - srtt.min(rtt)) / 4;
+                self.srtt = Some((srtt * 7 + rtt) / 8);
+            }
+        }
+    }
+
+    /// Smoothed round trip time, or `None` if no round trip has been measured yet.
+    pub const fn smoothed(&self) -> Option<Duration> {
+        self.srtt
+    }
+
+    /// Mean deviation of the round trip time.
+    pub const fn variance(&self) -> Duration {
+        self.rttvar
+    }
+
+    /// Retransmission timeout derived from the estimate.
+    pub fn rto(&self) -> Duration {
+        self.srtt.map_or(INITIAL_RTO, |srtt| (srtt + self.rttvar * 4).clamp(MIN_RTO, MAX_RTO))
+    }
+}
+
+/// A reliable frame batch that has been sent but not yet acknowledged.
+#[derive(Debug)]
+struct InFlight {
+    /// When the batch was (re)sent.
+    sent: Instant,
+    /// Size of the serialized batch in bytes.
+    size: usize,
+    /// Acknowledgements of retransmitted batches are ambiguous and are not used to measure the RTT.
+    retransmitted: bool,
+}
+
+#[derive(Debug)]
+struct State {
+    /// Size of the congestion window in bytes.
+    window: usize,
+    /// Slow start threshold in bytes.
+    /// The window grows exponentially below this threshold and linearly above it.
+    threshold: usize,
+    /// Batches that are still waiting for an acknowledgement, indexed by sequence number.
+    in_flight: HashMap<u32, InFlight>,
+    /// Total size of all batches in `in_flight`.
+    bytes_in_flight: usize,
+    /// Round trip time estimate used to compute the retransmission timeout.
+    rtt: RttEstimator,
+    /// Highest sequence number that has been sent so far.
+    highest_sent: u32,
+    /// Losses of batches older than this sequence number belong to a congestion event
+    /// that has already been responded to.
+    ///
+    /// This is `None` if there was no recent congestion event.
+    recovery_point: Option<u32>,
+}
+
+/// Sliding window congestion control with RTT estimation.
+///
+/// The window limits the amount of unacknowledged reliable data that can be on the wire.
+/// It starts in slow start, doubling every round trip, and switches to additive increase once it
+/// exceeds the slow start threshold. Packet loss halves the window, at most once per round trip.
+///
+/// Round trip times are estimated from acknowledgements as described in RFC 6298.
+#[derive(Debug)]
+pub struct CongestionControl {
+    /// Maximum transfer unit of the connection.
+    mtu: usize,
+    state: Mutex<State>,
+}
+
+impl CongestionControl {
+    /// Creates a new congestion controller for a connection with the given MTU.
+    pub fn new(mtu: u16) -> CongestionControl {
+        let mtu = mtu as usize;
+
+        CongestionControl {
+            mtu,
+            state: Mutex::new(State {
+                window: INITIAL_WINDOW * mtu,
+                threshold: MAX_WINDOW * mtu,
+                in_flight: HashMap::new(),
+                bytes_in_flight: 0,
+                rtt: RttEstimator::new(),
+                highest_sent: 0,
+                recovery_point: None,
+            }),
+        }
+    }
+
+    /// Current size of the congestion window in bytes.
+    pub fn window(&self) -> usize {
+        self.state.lock().window
+    }
+
+    /// Amount of unacknowledged bytes that are currently on the wire.
+    pub fn bytes_in_flight(&self) -> usize {
+        self.state.lock().bytes_in_flight
+    }
+
+    /// Amount of bytes that can be sent before the window is full.
+    ///
+    /// When nothing is in flight this is at least one MTU, so the connection can never stall.
+    pub fn available(&self) -> usize {
+        let state = self.state.lock();
+        if state.bytes_in_flight == 0 {
+            state.window.max(self.mtu)
+        } else {
+            state.window.saturating_sub(state.bytes_in_flight)
+        }
+    }
+
+    /// Smoothed round trip time, or `None` if no round trip has been measured yet.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.state.lock().rtt.smoothed()
+    }
+
+    /// Current retransmission timeout.
+    pub fn rto(&self) -> Duration {
+        self.state.lock().rtt.rto()
+    }
+
+    /// Registers a reliable batch that has just been sent.
+    pub fn on_send(&self, sequence: u32, size: usize) {
+        let sequence = sequence & SEQUENCE_MASK;
+        let mut state = self.state.lock();
+
+        state.advance_highest_sent(sequence);
+        if let Some(old) = state.in_flight.insert(sequence, InFlight { sent: Instant::now(), size, retransmitted: false }) {
+            state.bytes_in_flight -= old.size;
+        }
+        state.bytes_in_flight += size;
+    }
+
+    /// Registers a batch that has been sent again after it was lost.
+    pub fn on_retransmit(&self, sequence: u32, size: usize) {
+        let sequence = sequence & SEQUENCE_MASK;
+        let mut state = self.state.lock();
+
+        // Lost frames can be resent in a batch with a new sequence number.
+        state.advance_highest_sent(sequence);
+        if let Some(old) = state.in_flight.insert(sequence, InFlight { sent: Instant::now(), size, retransmitted: true }) {
+            state.bytes_in_flight -= old.size;
+        }
+        state.bytes_in_flight += size;
+    }
+
+    /// Processes an acknowledgement of the given batch.
+    ///
+    /// This updates the RTT estimate and grows the window.
+    /// Returns the round trip time of the batch if it could be measured.
+    pub fn on_ack(&self, sequence: u32) -> Option<Duration> {
+        let mut state = self.state.lock();
+
+        let batch = state.in_flight.remove(&(sequence & SEQUENCE_MASK))?;
+        state.bytes_in_flight -= batch.size;
+
+        let rtt = (!batch.retransmitted).then(|| batch.sent.elapsed());
+        if let Some(rtt) = rtt {
+            state.rtt.sample(rtt);
+        }
+
+        if state.window < state.threshold {
+            state.window += batch.size;
+        } else {
+            // Grows the window by roughly one MTU per round trip.
+            state.window += (self.mtu * batch.size / state.window).max(1);
+        }
+        state.window = state.window.min(MAX_WINDOW * self.mtu);
+
+        rtt
+    }
+
+    /// Processes a negative acknowledgement of the given batch.
+    pub fn on_loss(&self, sequence: u32) {
+        let sequence = sequence & SEQUENCE_MASK;
+        let mut state = self.state.lock();
+
+        if let Some(batch) = state.in_flight.remove(&sequence) {
+            state.bytes_in_flight -= batch.size;
+        }
+
+        if !state.enter_recovery(sequence) {
+            return;
+        }
+
+        state.threshold = (state.window / 2).max(MIN_WINDOW * self.mtu);
+        state.window = state.threshold;
+
+        let window = state.window;
+        drop(state);
+
+        tracing::trace!("Packet loss detected, reduced congestion window to {window} bytes");
+    }
+
+    /// Processes a batch that was not acknowledged within the retransmission timeout.
+    ///
+    /// A timeout is a much stronger sign of congestion than a NAK and therefore
+    /// collapses the window to its minimum.
+    pub fn on_timeout(&self, sequence: u32) {
+        let sequence = sequence & SEQUENCE_MASK;
+        let mut state = self.state.lock();
+
+        if let Some(batch) = state.in_flight.remove(&sequence) {
+            state.bytes_in_flight -= batch.size;
+        }
+
+        if !state.enter_recovery(sequence) {
+            return;
+        }
+
+        state.threshold = (state.window / 2).max(MIN_WINDOW * self.mtu);
+        state.window = MIN_WINDOW * self.mtu;
+        drop(state);
+
+        tracing::trace!("Batch {sequence} timed out, reduced congestion window to {} bytes", MIN_WINDOW * self.mtu);
+    }
+}
+
+impl State {
+    /// Remembers the given sequence number if it is newer than any other sent batch.
+    #[allow(clippy::missing_const_for_fn)] // Mutable references in const functions require Rust 1.83.
+    fn advance_highest_sent(&mut self, sequence: u32) {
+        if !precedes(self.highest_sent, sequence) {
+            return;
+        }
+
+        self.highest_sent = sequence;
+        if let Some(point) = self.recovery_point {
+            if sequence.wrapping_sub(point) & SEQUENCE_MASK >= RECOVERY_HORIZON && !precedes(sequence, point) {
+                self.recovery_point = None;
+            }
+        }
+    }
+
+    /// Starts a new congestion event caused by the loss of the given batch.
+    ///
+    /// Returns `false` if the batch was sent before the current recovery point,
+    /// in which case the loss was already responded to.
+    fn enter_recovery(&mut self, sequence: u32) -> bool {
+        if self.recovery_point.is_some_and(|point| precedes(sequence, point)) {
+            return false;
+        }
+
+        self.recovery_point = Some(self.highest_sent.wrapping_add(1) & SEQUENCE_MASK);
+        true
+    }
+}
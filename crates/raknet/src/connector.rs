@@ -0,0 +1,295 @@
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use proto::raknet::{
+    ConnectionRequest, ConnectionRequestAccepted, IncompatibleProtocol, NewIncomingConnection, OpenConnectionReply1, OpenConnectionReply2,
+    OpenConnectionRequest1, OpenConnectionRequest2, RAKNET_VERSION,
+};
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, mpsc};
+use util::{Deserialize, RVec, ReserveTo, Serialize};
+
+use crate::{forward_channel, BackpressurePolicy, CompoundLimits, DatagramBatch, ForwardSender, Forwarded, Socket, DEFAULT_ACK_INTERVAL, DEFAULT_MAX_BATCH_FRAMES, DEFAULT_KEEPALIVE_INTERVAL, DEFAULT_SESSION_TIMEOUT, RakNetClient, RakNetCommand, RakNetCreateDescription, SendWeights, MAX_MTU, MAX_ORDER_CHANNELS, MIN_MTU};
+
+/// Size of the buffer that datagrams from the server are received into.
+const RECV_BUF_SIZE: usize = 2048;
+/// Capacity of the channel that forwards received datagrams to the connection.
+const FORWARD_CHANNEL_SIZE: usize = 64;
+
+/// Settings used when connecting to a RakNet server.
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    /// GUID of this client.
+    pub guid: u64,
+    /// Largest MTU to probe for.
+    ///
+    /// Smaller MTUs are tried when the server does not respond to probes of this size.
+    pub max_mtu: u16,
+    /// How long to wait for each reply during the handshake.
+    pub timeout: Duration,
+    /// Amount of times every handshake packet is sent before giving up.
+    pub attempts: u32,
+    /// Limits on the fragments that are buffered for the connection.
+    pub compound_limits: CompoundLimits,
+    /// Maximum amount of frames in a single batch received from the server.
+    pub max_batch_frames: usize,
+    /// Amount of order channels that the server is allowed to use.
+    pub order_channels: usize,
+    /// How often received batches are acknowledged.
+    pub ack_interval: Duration,
+    /// Maximum amount of bytes per second that is sent to the server, 0 disables the limit.
+    pub send_rate: u64,
+    /// Share of the send budget that each priority receives.
+    pub send_weights: SendWeights,
+    /// How long the server can be unresponsive before the connection is closed.
+    pub session_timeout: Duration,
+    /// How long the connection can be idle before a keepalive ping is sent, 0 disables keepalives.
+    pub keepalive_interval: Duration,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        let guid = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+
+        Self {
+            guid,
+            max_mtu: MAX_MTU,
+            timeout: Duration::from_millis(500),
+            attempts: 4,
+            compound_limits: CompoundLimits::default(),
+            max_batch_frames: DEFAULT_MAX_BATCH_FRAMES,
+            order_channels: MAX_ORDER_CHANNELS,
+            ack_interval: DEFAULT_ACK_INTERVAL,
+            send_rate: 0,
+            send_weights: SendWeights::default(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            keepalive_interval: DEFAULT_KEEPALIVE_INTERVAL,
+        }
+    }
+}
+
+/// An outgoing connection to a RakNet server.
+///
+/// The connection uses the same reliability, ordering and congestion control machinery as
+/// connections accepted by the server. Game packets received from the server are delivered through
+/// [`receiver`](Self::receiver), packets are sent using the send functions of [`client`](Self::client).
+pub struct RakNetConnection {
+    /// State of the connection.
+    pub client: Arc<RakNetClient>,
+    /// Receives packets and events from the connection.
+    ///
+    /// The budget of the connection is enforced for servers as well, so [`RakNetCommand::BudgetExhausted`]
+    /// can be ignored if the server is trusted.
+    pub receiver: mpsc::Receiver<RakNetCommand>,
+}
+
+/// Connects to a RakNet server.
+///
+/// This performs the entire handshake: the MTU is discovered using padded `OpenConnectionRequest1` probes,
+/// after which the connection is opened with `OpenConnectionRequest2` and completed with a `ConnectionRequest`.
+pub async fn connect(address: SocketAddr, options: ConnectOptions) -> anyhow::Result<RakNetConnection> {
+    let local: SocketAddr = match address {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = Arc::new(Socket::from(UdpSocket::bind(local).await?));
+
+    connect_over(socket, address, options).await
+}
+
+/// Connects to a RakNet server on the same host using a [`LocalSocket`](crate::LocalSocket).
+///
+/// The connection is made from a socket bound to `local`, which must not be in use by another socket.
+#[cfg(unix)]
+pub async fn connect_local(server: &Path, local: &Path, options: ConnectOptions) -> anyhow::Result<RakNetConnection> {
+    let socket = crate::LocalSocket::bind(local)?;
+    let address = socket.peer_address(server);
+
+    connect_over(Arc::new(Socket::from(socket)), address, options).await
+}
+
+/// Performs the handshake with the server at `address` over an existing socket.
+async fn connect_over(socket: Arc<Socket>, address: SocketAddr, options: ConnectOptions) -> anyhow::Result<RakNetConnection> {
+    let (mtu, cookie) = discover_mtu(&socket, address, &options).await?;
+    let mtu = open_connection(&socket, address, mtu, cookie, &options).await?;
+
+    // The socket drops datagrams once its buffer is full anyway, so there is no point in waiting.
+    let (forward_tx, forward_rx) = forward_channel(FORWARD_CHANNEL_SIZE, BackpressurePolicy::DropNewest);
+    let (broadcast, _) = broadcast::channel(1);
+
+    let (client, receiver) = RakNetClient::new(
+        RakNetCreateDescription {
+            address,
+            mtu,
+            guid: options.guid,
+            socket: Arc::clone(&socket),
+            outgoing: Arc::new(DatagramBatch::new()),
+            compound_limits: options.compound_limits,
+            max_batch_frames: options.max_batch_frames,
+            order_channels: options.order_channels,
+            ack_interval: options.ack_interval,
+            send_rate: options.send_rate,
+            send_weights: options.send_weights,
+            session_timeout: options.session_timeout,
+            keepalive_interval: options.keepalive_interval,
+            capture: None,
+        },
+        broadcast,
+        forward_rx,
+    );
+
+    util::task::spawn_owned(
+        "raknet::connector",
+        address,
+        forward_datagrams(Arc::clone(&client), socket, forward_tx),
+    );
+
+    let request = ConnectionRequest { guid: options.guid as i64, time: client.latency.timestamp() };
+    let mut packet = RVec::alloc_with_capacity(request.size_hint());
+    request.serialize_into(&mut packet)?;
+    client.send_raw_buffer(packet);
+
+    let handshake_timeout = options.timeout * options.attempts;
+    if tokio::time::timeout(handshake_timeout, client.connected.cancelled()).await.is_err() {
+        client.active.cancel();
+        anyhow::bail!("Server did not accept the connection request within {handshake_timeout:?}");
+    }
+
+    Ok(RakNetConnection { client, receiver })
+}
+
+/// Sends a packet and waits for a reply with the given ID, retrying if it does not arrive in time.
+async fn request(
+    socket: &Socket,
+    address: SocketAddr,
+    serialized: &[u8],
+    reply_id: u8,
+    options: &ConnectOptions,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut buffer = vec![0; RECV_BUF_SIZE];
+    for _ in 0..options.attempts {
+        socket.send_to(serialized, address).await?;
+
+        let Ok(received) = tokio::time::timeout(options.timeout, socket.recv_from(&mut buffer)).await else {
+            continue;
+        };
+
+        let (n, from) = received?;
+        if from != address || !crate::accept_datagram(&buffer[..n], from) {
+            continue;
+        }
+
+        let reply = &buffer[..n];
+        match reply.first().copied() {
+            Some(id) if id == reply_id => return Ok(Some(reply.to_vec())),
+            Some(IncompatibleProtocol::ID) => {
+                let reply = IncompatibleProtocol::deserialize(reply)?;
+                anyhow::bail!(
+                    "Server does not support RakNet protocol version {RAKNET_VERSION}, it uses version {}",
+                    reply.protocol_version
+                )
+            }
+            _ => tracing::debug!("Ignoring unexpected packet during handshake"),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Finds the largest MTU that the server responds to.
+///
+/// Also returns the handshake cookie if the server sent one.
+async fn discover_mtu(socket: &Socket, address: SocketAddr, options: &ConnectOptions) -> anyhow::Result<(u16, Option<u32>)> {
+    let max_mtu = options.max_mtu.clamp(MIN_MTU, MAX_MTU);
+
+    for mtu in [max_mtu, 1200.min(max_mtu), MIN_MTU] {
+        let probe = OpenConnectionRequest1 { protocol_version: RAKNET_VERSION, mtu };
+        let mut serialized = Vec::with_capacity(probe.size_hint());
+        probe.serialize_into(&mut serialized)?;
+
+        if let Some(reply) = request(socket, address, &serialized, OpenConnectionReply1::ID, options).await? {
+            let reply = OpenConnectionReply1::deserialize(reply.as_slice())?;
+            return Ok((reply.mtu.clamp(MIN_MTU, mtu), reply.cookie));
+        }
+
+        tracing::debug!("Server did not respond to MTU probe of {mtu} bytes");
+    }
+
+    anyhow::bail!("Server did not respond to any MTU probes")
+}
+
+/// Opens the connection and returns the MTU that the server agreed on.
+async fn open_connection(
+    socket: &Socket,
+    address: SocketAddr,
+    mtu: u16,
+    cookie: Option<u32>,
+    options: &ConnectOptions,
+) -> anyhow::Result<u16> {
+    let open = OpenConnectionRequest2 { cookie, server_address: address, mtu, client_guid: options.guid };
+    let mut serialized = Vec::with_capacity(open.size_hint());
+    open.serialize_into(&mut serialized)?;
+
+    let Some(reply) = request(socket, address, &serialized, OpenConnectionReply2::ID, options).await? else {
+        anyhow::bail!("Server did not respond to the open connection request");
+    };
+
+    let reply = OpenConnectionReply2::deserialize(reply.as_slice())?;
+    if reply.mtu < MIN_MTU || reply.mtu > mtu {
+        anyhow::bail!("Server replied with invalid MTU {}", reply.mtu);
+    }
+
+    Ok(reply.mtu)
+}
+
+/// Forwards datagrams from the socket to the connection until it is closed.
+async fn forward_datagrams(client: Arc<RakNetClient>, socket: Arc<Socket>, forward: ForwardSender) {
+    let mut buffer = vec![0; RECV_BUF_SIZE];
+
+    loop {
+        let received = tokio::select! {
+            received = socket.recv_from(&mut buffer) => received,
+            () = client.active.cancelled() => break,
+        };
+
+        match received {
+            // The socket is not shared, but anyone can send datagrams to it.
+            Ok((_, from)) if from != client.address() => (),
+            Ok((n, from)) if !crate::accept_datagram(&buffer[..n], from) => (),
+            Ok((n, _)) => {
+                if forward.try_send(RVec::alloc_from_slice(&buffer[..n])) == Forwarded::Closed {
+                    break;
+                }
+            }
+            Err(err) => tracing::error!("Failed to receive datagram from server: {err}"),
+        }
+    }
+}
+
+impl RakNetClient {
+    /// Handles a [`ConnectionRequestAccepted`] packet.
+    ///
+    /// This is only received by connections that were created using [`connect`].
+    pub fn handle_connection_request_accepted(&self, mut packet: RVec) -> anyhow::Result<()> {
+        let accepted = ConnectionRequestAccepted::deserialize(packet.as_ref())?;
+        self.latency.record_pong(accepted.request_time);
+
+        let reply = NewIncomingConnection {
+            server_address: self.address(),
+            request_time: accepted.request_time,
+            time: self.latency.timestamp(),
+        };
+
+        packet.clear();
+        packet.reserve_to(reply.size_hint());
+        reply.serialize_into(&mut packet)?;
+
+        self.send_raw_buffer(packet);
+        self.connected.cancel();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+/// Cookies are valid for at least this long and at most twice as long.
+const COOKIE_LIFETIME: Duration = Duration::from_secs(15);
+
+/// Issues and verifies handshake cookies.
+///
+/// Without cookies, anyone can send an `OpenConnectionRequest2` with a spoofed source address and make the server
+/// create a session for it. The server then sends packets to an address that never asked for them, which can be
+/// abused for reflection attacks.
+///
+/// The server instead sends a cookie in `OpenConnectionReply1` that the client has to echo back in
+/// `OpenConnectionRequest2`. Only clients that can receive packets on their source address know the cookie.
+/// The cookie is an HMAC over the address of the client and the GUID of the server, so no state has
+/// to be stored for clients that never complete the handshake.
+#[derive(Debug)]
+pub struct HandshakeCookies {
+    /// Key that cookies are signed with. This is randomly generated on startup.
+    ///
+    /// This is the size of a SHA-256 block, which is the largest key that HMAC uses without hashing it first.
+    secret: [u8; 64],
+    /// Cookies are bound to the epoch they were issued in, counted from this moment.
+    created: Instant,
+}
+
+impl HandshakeCookies {
+    /// Creates a new cookie issuer with a random secret.
+    pub fn new() -> HandshakeCookies {
+        let mut secret = [0; 64];
+        rand::thread_rng().fill_bytes(&mut secret);
+
+        HandshakeCookies { secret, created: Instant::now() }
+    }
+
+    /// Issues a cookie for the given address.
+    pub fn issue(&self, address: SocketAddr, server_guid: u64) -> u32 {
+        self.compute(address, server_guid, self.epoch())
+    }
+
+    /// Verifies a cookie that was sent back by a client.
+    ///
+    /// Cookies issued in the previous epoch are accepted as well, so that handshakes that started right before the
+    /// epoch changed do not fail.
+    pub fn verify(&self, address: SocketAddr, server_guid: u64, cookie: u32) -> bool {
+        let epoch = self.epoch();
+
+        self.compute(address, server_guid, epoch) == cookie
+            || (epoch > 0 && self.compute(address, server_guid, epoch - 1) == cookie)
+    }
+
+    /// The current epoch.
+    fn epoch(&self) -> u64 {
+        self.created.elapsed().as_secs() / COOKIE_LIFETIME.as_secs()
+    }
+
+    /// Computes the cookie of an address in the given epoch.
+    fn compute(&self, address: SocketAddr, server_guid: u64, epoch: u64) -> u32 {
+        let mut mac = <Hmac<Sha256> as KeyInit>::new(&self.secret.into());
+
+        match address {
+            SocketAddr::V4(addr) => mac.update(&addr.ip().octets()),
+            SocketAddr::V6(addr) => mac.update(&addr.ip().octets()),
+        }
+        mac.update(&address.port().to_be_bytes());
+        mac.update(&server_guid.to_be_bytes());
+        mac.update(&epoch.to_be_bytes());
+
+        let digest = mac.finalize().into_bytes();
+        u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+    }
+}
+
+impl Default for HandshakeCookies {
+    fn default() -> Self {
+        Self::new()
+    }
+}
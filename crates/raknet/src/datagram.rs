@@ -0,0 +1,36 @@
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicU64;
+
+use lazy_static::lazy_static;
+use prometheus_client::metrics::counter::Counter;
+
+lazy_static! {
+    #[doc(hidden)]
+    pub static ref EMPTY_DATAGRAMS_METRIC: Counter::<u64, AtomicU64> = Counter::default();
+}
+
+/// Checks whether a datagram does not contain any data.
+///
+/// This is the case for zero-length datagrams and datagrams that only consist of zero bytes.
+/// The latter are sent by some NAT keepalive implementations and port scanners.
+/// No RakNet packet can consist of only zeroes, since they would be an unconnected ping without a magic.
+#[inline]
+pub fn is_empty_datagram(datagram: &[u8]) -> bool {
+    datagram.iter().all(|&byte| byte == 0)
+}
+
+/// Filters datagrams that were received from the network before they are processed.
+///
+/// Returns `false` and increments [`EMPTY_DATAGRAMS_METRIC`] if the datagram is empty or only contains padding.
+/// Everything past the receiver can therefore assume that datagrams contain at least a packet ID.
+#[inline]
+pub fn accept_datagram(datagram: &[u8], from: SocketAddr) -> bool {
+    if is_empty_datagram(datagram) {
+        EMPTY_DATAGRAMS_METRIC.inc();
+        tracing::trace!("Discarded empty datagram of {} bytes from {from}", datagram.len());
+
+        return false
+    }
+
+    true
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+
+use parking_lot::Mutex;
+
+/// Amount of reliable indices after the oldest missing index that are tracked.
+///
+/// Frames that are further ahead move the window forward. Any frame that is still missing from the part
+/// of the window that was moved past will be treated as a duplicate when it eventually arrives.
+pub const DEDUP_WINDOW_SIZE: usize = 2048;
+/// Reliable indices are encoded as 24-bit integers and wrap around.
+const RELIABLE_INDEX_MASK: u32 = 0x00ff_ffff;
+/// Indices that are more than half of the index space behind the window are considered to be ahead of it.
+const HALF_INDEX_RANGE: u32 = 1 << 23;
+
+/// Result of registering a reliable index with a [`ReliableWindow`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowInsert {
+    /// The index had not been received yet and the frame should be handled.
+    New,
+    /// The index lies within the window and has already been received.
+    Duplicate,
+    /// The index lies behind the window.
+    ///
+    /// It has either been received already or the window was moved past it while it was still missing.
+    /// The frame should be discarded in both cases.
+    Behind,
+}
+
+impl WindowInsert {
+    /// Whether the frame has not been received before and should be handled.
+    #[inline]
+    pub const fn is_new(self) -> bool {
+        matches!(self, WindowInsert::New)
+    }
+}
+
+/// Indices covered by the window.
+#[derive(Debug)]
+struct Window {
+    /// Oldest reliable index that has not been received yet.
+    start: u32,
+    /// Whether each index starting at `start` has been received.
+    received: VecDeque<bool>,
+}
+
+impl Window {
+    /// Moves the window forward by the given amount of indices.
+    fn advance(&mut self, amount: usize) {
+        let amount = amount.min(self.received.len());
+        self.received.drain(..amount);
+        self.received.resize(DEDUP_WINDOW_SIZE, false);
+        self.start = self.start.wrapping_add(amount as u32) & RELIABLE_INDEX_MASK;
+    }
+}
+
+/// Sliding window of reliable indices that have been received.
+///
+/// Clients retransmit reliable frames whose acknowledgement was lost or arrived late.
+/// Such frames have already been processed and must be dropped instead of being handled a second time.
+#[derive(Debug)]
+pub struct ReliableWindow {
+    window: Mutex<Window>,
+}
+
+impl ReliableWindow {
+    /// Creates an empty window.
+    pub fn new() -> ReliableWindow {
+        ReliableWindow {
+            window: Mutex::new(Window { start: 0, received: VecDeque::from(vec![false; DEDUP_WINDOW_SIZE]) }),
+        }
+    }
+
+    /// Registers a received reliable index.
+    ///
+    /// Frames should only be handled if this returns [`WindowInsert::New`].
+    pub fn insert(&self, index: u32) -> WindowInsert {
+        let mut window = self.window.lock();
+
+        let offset = index.wrapping_sub(window.start) & RELIABLE_INDEX_MASK;
+        if offset >= HALF_INDEX_RANGE {
+            return WindowInsert::Behind
+        }
+
+        let mut offset = offset as usize;
+        if offset >= DEDUP_WINDOW_SIZE {
+            let shift = offset - DEDUP_WINDOW_SIZE + 1;
+            if shift >= DEDUP_WINDOW_SIZE {
+                // The entire window is skipped, start over at the new index.
+                window.advance(DEDUP_WINDOW_SIZE);
+                window.start = index.wrapping_sub(DEDUP_WINDOW_SIZE as u32 - 1) & RELIABLE_INDEX_MASK;
+            } else {
+                window.advance(shift);
+            }
+            offset = DEDUP_WINDOW_SIZE - 1;
+        }
+
+        if window.received[offset] {
+            return WindowInsert::Duplicate
+        }
+        window.received[offset] = true;
+
+        let contiguous = window.received.iter().take_while(|&&received| received).count();
+        if contiguous > 0 {
+            window.advance(contiguous);
+        }
+
+        WindowInsert::New
+    }
+}
+
+impl Default for ReliableWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
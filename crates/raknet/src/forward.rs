@@ -0,0 +1,258 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+use util::RVec;
+
+/// Default amount of time that a datagram waits for room in a full queue.
+pub const DEFAULT_FORWARD_TIMEOUT: Duration = Duration::from_millis(10);
+/// Default amount of consecutive timeouts after which a session is considered overloaded.
+pub const DEFAULT_FORWARD_TIMEOUT_LIMIT: u32 = 100;
+
+/// What happens to datagrams that are forwarded to a session whose queue is full.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Discards the datagram that is being forwarded.
+    DropNewest,
+    /// Discards the oldest queued datagram to make room for the new one.
+    ///
+    /// This favours fresh data, such as movement, over datagrams that have been waiting for a while.
+    DropOldest,
+    /// Lets the queue grow beyond its capacity up to `max` datagrams, after which new datagrams are discarded.
+    GrowBounded {
+        /// Maximum amount of queued datagrams.
+        max: usize,
+    },
+    /// Waits up to `timeout` for room in the queue, discarding the datagram if none frees up.
+    ///
+    /// Sessions that time out `limit` times in a row are reported as [overloaded](Forwarded::Overloaded)
+    /// and should be disconnected.
+    DisconnectAfter {
+        /// How long to wait for room in the queue.
+        timeout: Duration,
+        /// Amount of consecutive timeouts after which the session is overloaded.
+        limit: u32,
+    },
+}
+
+impl Default for BackpressurePolicy {
+    fn default() -> Self {
+        Self::DisconnectAfter { timeout: DEFAULT_FORWARD_TIMEOUT, limit: DEFAULT_FORWARD_TIMEOUT_LIMIT }
+    }
+}
+
+/// Result of forwarding a datagram to a session.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub enum Forwarded {
+    /// The datagram was queued.
+    Queued,
+    /// The datagram was queued after discarding the oldest queued datagram.
+    DroppedOldest,
+    /// The datagram was discarded because the queue is full.
+    DroppedNewest,
+    /// The datagram was discarded because no room freed up in time.
+    TimedOut,
+    /// The datagram was discarded and the session has timed out too many times in a row.
+    Overloaded,
+    /// The session no longer receives datagrams.
+    Closed,
+}
+
+impl Forwarded {
+    /// Whether a datagram was discarded.
+    pub const fn is_dropped(self) -> bool {
+        matches!(self, Self::DroppedOldest | Self::DroppedNewest | Self::TimedOut | Self::Overloaded)
+    }
+}
+
+/// State shared between the sending and receiving half of a forward queue.
+#[derive(Debug)]
+struct ForwardQueue {
+    datagrams: Mutex<VecDeque<RVec>>,
+    capacity: usize,
+    policy: BackpressurePolicy,
+    /// Notified when a datagram is queued or the last sender is dropped.
+    readable: Notify,
+    /// Notified when a datagram is taken from the queue or the receiver is dropped.
+    writable: Notify,
+    senders: AtomicUsize,
+    closed: AtomicBool,
+    /// Amount of consecutive timeouts.
+    timeouts: AtomicU32,
+}
+
+impl ForwardQueue {
+    /// Maximum amount of datagrams that can be queued.
+    fn limit(&self) -> usize {
+        match self.policy {
+            BackpressurePolicy::GrowBounded { max } => max.max(self.capacity),
+            _ => self.capacity,
+        }
+    }
+
+    /// Queues the datagram if there is room, applying the policy otherwise unless `wait` is set.
+    ///
+    /// Returns the datagram if it should wait for room instead.
+    fn push(&self, datagram: RVec, wait: bool) -> Result<Forwarded, RVec> {
+        let mut datagrams = self.datagrams.lock();
+        let forwarded = if datagrams.len() < self.limit() {
+            datagrams.push_back(datagram);
+            Forwarded::Queued
+        } else if self.policy == BackpressurePolicy::DropOldest {
+            datagrams.pop_front();
+            datagrams.push_back(datagram);
+            Forwarded::DroppedOldest
+        } else if wait {
+            return Err(datagram)
+        } else {
+            return Ok(Forwarded::DroppedNewest)
+        };
+        drop(datagrams);
+
+        self.timeouts.store(0, Ordering::Relaxed);
+        self.readable.notify_one();
+
+        Ok(forwarded)
+    }
+}
+
+/// Creates a queue that forwards datagrams to a session.
+///
+/// The queue holds up to `capacity` datagrams, `policy` determines what happens to datagrams that do not fit.
+pub fn forward_channel(capacity: usize, policy: BackpressurePolicy) -> (ForwardSender, ForwardReceiver) {
+    let queue = Arc::new(ForwardQueue {
+        datagrams: Mutex::new(VecDeque::with_capacity(capacity)),
+        capacity,
+        policy,
+        readable: Notify::new(),
+        writable: Notify::new(),
+        senders: AtomicUsize::new(1),
+        closed: AtomicBool::new(false),
+        timeouts: AtomicU32::new(0),
+    });
+
+    (ForwardSender(Arc::clone(&queue)), ForwardReceiver(queue))
+}
+
+/// Sending half of a forward queue.
+#[derive(Debug)]
+pub struct ForwardSender(Arc<ForwardQueue>);
+
+impl ForwardSender {
+    /// Forwards a datagram, waiting for room if the policy requires it.
+    pub async fn send(&self, datagram: RVec) -> Forwarded {
+        if self.is_closed() {
+            return Forwarded::Closed
+        }
+
+        let BackpressurePolicy::DisconnectAfter { timeout, limit } = self.0.policy else {
+            return self.try_send(datagram)
+        };
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut datagram = datagram;
+        loop {
+            // Register interest before checking so that a datagram taken in between is not missed.
+            let writable = self.0.writable.notified();
+            datagram = match self.0.push(datagram, true) {
+                Ok(forwarded) => return forwarded,
+                Err(datagram) => datagram,
+            };
+
+            if tokio::time::timeout_at(deadline, writable).await.is_err() {
+                let timeouts = self.0.timeouts.fetch_add(1, Ordering::Relaxed) + 1;
+                return if timeouts >= limit { Forwarded::Overloaded } else { Forwarded::TimedOut }
+            }
+
+            if self.is_closed() {
+                return Forwarded::Closed
+            }
+        }
+    }
+
+    /// Forwards a datagram without waiting, discarding it if the queue is full.
+    pub fn try_send(&self, datagram: RVec) -> Forwarded {
+        if self.is_closed() {
+            return Forwarded::Closed
+        }
+
+        // Datagrams are only handed back when waiting.
+        self.0.push(datagram, false).unwrap_or(Forwarded::DroppedNewest)
+    }
+
+    /// Whether the receiver has been dropped.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.0.closed.load(Ordering::Acquire)
+    }
+
+    /// Amount of datagrams waiting to be received.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.datagrams.lock().len()
+    }
+
+    /// Whether no datagrams are waiting to be received.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether both senders forward to the same session.
+    #[inline]
+    pub fn same_channel(&self, other: &ForwardSender) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Clone for ForwardSender {
+    fn clone(&self) -> Self {
+        self.0.senders.fetch_add(1, Ordering::Relaxed);
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl Drop for ForwardSender {
+    fn drop(&mut self) {
+        if self.0.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.readable.notify_one();
+        }
+    }
+}
+
+/// Receiving half of a forward queue.
+#[derive(Debug)]
+pub struct ForwardReceiver(Arc<ForwardQueue>);
+
+impl ForwardReceiver {
+    /// Receives the next datagram.
+    ///
+    /// Returns `None` once all senders have been dropped and the queue is empty.
+    pub async fn recv(&mut self) -> Option<RVec> {
+        loop {
+            let datagram = self.0.datagrams.lock().pop_front();
+            if let Some(datagram) = datagram {
+                self.0.writable.notify_one();
+                return Some(datagram)
+            }
+
+            if self.0.senders.load(Ordering::Acquire) == 0 {
+                return None
+            }
+
+            // Permits are stored if nobody is waiting, so a datagram queued in between is not missed.
+            self.0.readable.notified().await;
+        }
+    }
+}
+
+impl Drop for ForwardReceiver {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Release);
+        self.0.writable.notify_waiters();
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use util::{RVec, BinaryRead, BinaryWrite, Deserialize, Serialize};
 
 use crate::Reliability;
@@ -17,6 +19,26 @@ pub const CONTINUOUS_SEND_BIT_FLAG: u8 = 0x08;
 /// Possibly used for Raknet congestion control.
 pub const NEEDS_B_AND_AS_BIT_FLAG: u8 = 0x04;
 
+/// Default maximum amount of frames in a single batch.
+///
+/// Clients only put a few frames in every batch. Without a limit, a single datagram full of empty frames
+/// would make the server process hundreds of frames.
+pub const DEFAULT_MAX_BATCH_FRAMES: usize = 128;
+
+/// Error returned when a client exceeds one of the limits on frames or compounds.
+///
+/// Well-behaved clients never do this, so the connection is closed when this error occurs.
+#[derive(Debug)]
+pub struct LimitExceeded(pub String);
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
 /// Contains a set of frames.
 #[derive(Debug)]
 pub struct FrameBatch {
@@ -32,6 +54,24 @@ impl FrameBatch {
     pub fn is_empty(&self) -> bool {
         self.frames.is_empty()
     }
+
+    /// Deserializes a batch, failing with [`LimitExceeded`] if it contains more than `max_frames` frames.
+    pub fn deserialize_limited<'a, R: BinaryRead<'a>>(mut reader: R, max_frames: usize) -> anyhow::Result<Self> {
+        reader.advance(1)?; // Skip batch ID
+
+        let batch_number = reader.read_u24_le()?;
+        let mut frames = Vec::new();
+
+        while !reader.eof() {
+            if frames.len() >= max_frames {
+                return Err(LimitExceeded(format!("Batch contains more than {max_frames} frames")).into())
+            }
+
+            frames.push(Frame::deserialize_from(&mut reader)?);
+        }
+
+        Ok(Self { sequence_number: batch_number, frames })
+    }
 }
 
 impl Serialize for FrameBatch {
@@ -60,21 +100,7 @@ impl Serialize for FrameBatch {
 impl<'a> Deserialize<'a> for FrameBatch {
     /// Deserializes a batch of frames from a buffer.
     fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
-        reader.advance(1)?; // Skip batch ID
-
-        let batch_number = reader.read_u24_le()?;
-        let mut frames = Vec::new();
-
-        while !reader.eof() {
-            frames.push(Frame::deserialize_from(reader)?);
-        }
-
-        #[cfg(debug_assertions)]
-        if reader.remaining() != 0 {
-            tracing::warn!("Not all bytes have been read from the packet buffer");
-        }
-
-        Ok(Self { sequence_number: batch_number, frames })
+        FrameBatch::deserialize_limited(reader, usize::MAX)
     }
 }
 
@@ -0,0 +1,92 @@
+use parking_lot::Mutex;
+
+use crate::{Frame, FrameBatch};
+
+/// Maximum amount of idle frame lists kept by a pool.
+///
+/// A session rarely has more batches in flight than this, larger bursts simply allocate.
+const MAX_IDLE_LISTS: usize = 64;
+/// Maximum amount of idle serialization buffers kept by a pool.
+const MAX_IDLE_BUFFERS: usize = 4;
+
+/// Reuses the allocations of the send path of a session.
+///
+/// Every flush collects frames into a list, groups them into batches and serializes each batch into a buffer.
+/// Batches with reliable frames are kept in the recovery queue until they are acknowledged. Instead of allocating
+/// these lists and buffers on every tick, they are returned to the pool once they are no longer needed and handed
+/// out again on the next flush. Frame bodies themselves are [`RVec`](util::RVec)s and already return to the global
+/// buffer pool when they are dropped.
+#[derive(Debug, Default)]
+pub struct FramePool {
+    /// Empty frame lists that still have their capacity.
+    lists: Mutex<Vec<Vec<Frame>>>,
+    /// Empty serialization buffers that still have their capacity.
+    buffers: Mutex<Vec<Vec<u8>>>,
+}
+
+impl FramePool {
+    /// Creates an empty pool.
+    #[inline]
+    pub fn new() -> FramePool {
+        FramePool::default()
+    }
+
+    /// Takes an empty frame list from the pool, or creates a new one if none are available.
+    #[inline]
+    pub fn take(&self) -> Vec<Frame> {
+        self.lists.lock().pop().unwrap_or_default()
+    }
+
+    /// Returns a frame list to the pool.
+    ///
+    /// Remaining frames are dropped. Lists without an allocation are not worth keeping and are discarded,
+    /// as are lists that exceed the maximum amount of idle lists.
+    pub fn recycle(&self, mut frames: Vec<Frame>) {
+        frames.clear();
+        if frames.capacity() == 0 {
+            return
+        }
+
+        let mut lists = self.lists.lock();
+        if lists.len() < MAX_IDLE_LISTS {
+            lists.push(frames);
+        }
+    }
+
+    /// Returns the frame list of a batch to the pool.
+    #[inline]
+    pub fn recycle_batch(&self, batch: FrameBatch) {
+        self.recycle(batch.frames);
+    }
+
+    /// Takes an empty serialization buffer from the pool, or creates a new one if none are available.
+    #[inline]
+    pub fn take_buffer(&self) -> Vec<u8> {
+        self.buffers.lock().pop().unwrap_or_default()
+    }
+
+    /// Returns a serialization buffer to the pool.
+    pub fn recycle_buffer(&self, mut buffer: Vec<u8>) {
+        buffer.clear();
+        if buffer.capacity() == 0 {
+            return
+        }
+
+        let mut buffers = self.buffers.lock();
+        if buffers.len() < MAX_IDLE_BUFFERS {
+            buffers.push(buffer);
+        }
+    }
+
+    /// Amount of idle frame lists in the pool.
+    #[inline]
+    pub fn idle_lists(&self) -> usize {
+        self.lists.lock().len()
+    }
+
+    /// Amount of idle serialization buffers in the pool.
+    #[inline]
+    pub fn idle_buffers(&self) -> usize {
+        self.buffers.lock().len()
+    }
+}
@@ -0,0 +1,111 @@
+use std::net::{IpAddr, SocketAddr};
+
+use proto::raknet::{
+    IncompatibleProtocol, OpenConnectionReply1, OpenConnectionReply2, OpenConnectionRequest1, OpenConnectionRequest2, UnconnectedPing,
+    UnconnectedPong, RAKNET_VERSION,
+};
+use util::{Deserialize, Serialize};
+
+use crate::{HandshakeCookies, ListenOptions, MtuDiscovery, RateLimiter};
+
+/// Performs the offline handshake that precedes every RakNet connection.
+///
+/// This answers unconnected pings, checks the protocol version, negotiates the MTU and verifies handshake cookies.
+/// Creating the session itself is left to the caller, so that the handshake can be shared by the [`Listener`](crate::Listener)
+/// and servers that keep track of their sessions themselves.
+#[derive(Debug)]
+pub struct OfflineHandshake {
+    guid: u64,
+    versions: Vec<u8>,
+    mtu_discovery: MtuDiscovery,
+    cookies: Option<HandshakeCookies>,
+    limiter: RateLimiter,
+}
+
+impl OfflineHandshake {
+    /// Creates a handshake using the GUID, versions, MTU, cookie and rate limit settings of `options`.
+    pub fn new(options: &ListenOptions) -> OfflineHandshake {
+        OfflineHandshake {
+            guid: options.guid,
+            versions: options.versions.clone(),
+            mtu_discovery: MtuDiscovery::new(options.max_mtu),
+            cookies: options.handshake_cookies.then(HandshakeCookies::new),
+            limiter: RateLimiter::new(options.unconnected_rate, options.unconnected_burst),
+        }
+    }
+
+    /// RakNet GUID of the server.
+    #[inline]
+    pub const fn guid(&self) -> u64 {
+        self.guid
+    }
+
+    /// Whether an unconnected packet from this address should be handled.
+    ///
+    /// This should be checked before doing any work for the packet, so that floods are stopped early.
+    #[inline]
+    pub fn allow(&self, address: IpAddr) -> bool {
+        self.limiter.allow(address)
+    }
+
+    /// Negotiates the MTU of a client that has opened a connection, see [`MtuDiscovery::negotiate`].
+    #[inline]
+    pub fn negotiate_mtu(&self, address: SocketAddr, requested: u16) -> anyhow::Result<u16> {
+        self.mtu_discovery.negotiate(address, requested)
+    }
+
+    /// Returns the reply to an unconnected packet.
+    ///
+    /// `metadata` is sent in response to pings. Once a client has sent a valid [`OpenConnectionRequest2`],
+    /// `open` is called with its GUID and requested MTU. It should create the session and return its MTU,
+    /// which is usually negotiated using [`negotiate_mtu`](Self::negotiate_mtu).
+    pub fn handle<F>(&self, datagram: &[u8], address: SocketAddr, metadata: &str, open: F) -> anyhow::Result<Vec<u8>>
+    where
+        F: FnOnce(u64, u16) -> anyhow::Result<u16>,
+    {
+        let guid = self.guid;
+        let mut reply = Vec::new();
+
+        match datagram.first().copied() {
+            Some(UnconnectedPing::ID) => {
+                let ping = UnconnectedPing::deserialize(datagram)?;
+                UnconnectedPong { time: ping.time, server_guid: guid, metadata }.serialize_into(&mut reply)?;
+            }
+            Some(OpenConnectionRequest1::ID) => {
+                let request = OpenConnectionRequest1::deserialize(datagram)?;
+                if self.versions.contains(&request.protocol_version) {
+                    let mtu = self.mtu_discovery.on_probe(address, request.mtu);
+                    let cookie = self.cookies.as_ref().map(|cookies| cookies.issue(address, guid));
+                    OpenConnectionReply1 { mtu, cookie, server_guid: guid }.serialize_into(&mut reply)?;
+                } else {
+                    tracing::debug!(
+                        "{address} uses unsupported RakNet version {}, accepted versions are {:?}",
+                        request.protocol_version,
+                        self.versions
+                    );
+
+                    let protocol_version = self.versions.iter().copied().max().unwrap_or(RAKNET_VERSION);
+                    IncompatibleProtocol { protocol_version, server_guid: guid }.serialize_into(&mut reply)?;
+                }
+            }
+            Some(OpenConnectionRequest2::ID) => {
+                let request = OpenConnectionRequest2::deserialize(datagram)?;
+                if let Some(cookies) = &self.cookies {
+                    // Nothing may be created for the client before it has proven that it owns its address.
+                    match request.cookie {
+                        Some(cookie) if cookies.verify(address, guid, cookie) => (),
+                        Some(_) => anyhow::bail!("{address} sent an invalid or expired handshake cookie"),
+                        None => anyhow::bail!("{address} did not send a handshake cookie"),
+                    }
+                }
+
+                let mtu = open(request.client_guid, request.mtu)?;
+                OpenConnectionReply2 { server_guid: guid, mtu, client_address: address }.serialize_into(&mut reply)?;
+            }
+            Some(id) => anyhow::bail!("Invalid unconnected packet ID: {id:x}"),
+            None => anyhow::bail!("Unconnected packet was empty"),
+        }
+
+        Ok(reply)
+    }
+}
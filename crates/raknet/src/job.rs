@@ -1,33 +1,55 @@
 use std::{
+    collections::HashSet,
+    fmt,
+    future::Future,
+    pin::Pin,
     sync::{Arc, atomic::{AtomicU64, Ordering}},
     time::{Duration, Instant},
 };
 
+use parking_lot::Mutex;
 use prometheus_client::metrics::counter::Counter;
-use tokio::sync::{mpsc, TryAcquireError};
-use util::RVec;
+use tokio::sync::TryAcquireError;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use util::Joinable;
 
-use crate::{RakNetCommand, RakNetClient};
+use crate::{CloseReason, ForwardReceiver, RakNetCommand, RakNetClient};
 
 use lazy_static::lazy_static;
 
 lazy_static! {
     #[doc(hidden)]
     pub static ref TOTAL_PACKETS_METRIC: Counter::<u64, AtomicU64> = Counter::default();
+    #[doc(hidden)]
+    pub static ref SESSION_TIMEOUTS_METRIC: Counter::<u64, AtomicU64> = Counter::default();
+    #[doc(hidden)]
+    pub static ref EXPIRED_COMPOUNDS_METRIC: Counter::<u64, AtomicU64> = Counter::default();
+    #[doc(hidden)]
+    pub static ref LIMIT_DISCONNECTS_METRIC: Counter::<u64, AtomicU64> = Counter::default();
 }
 
 /// Limit to the amount of packets a client is allowed to send per second.
 pub const BUDGET_SIZE: usize = 50;
 
 /// Tick interval of the internal session tick.
-const INTERNAL_TICK_INTERVAL: Duration = Duration::from_millis(1000 / 20);
-/// Inactivity timeout.
+pub const INTERNAL_TICK_INTERVAL: Duration = Duration::from_millis(1000 / 20);
+/// Amount of ticks between pings that are sent to measure the round trip time.
+const PING_INTERVAL: u64 = 40;
+/// Default inactivity timeout.
 ///
 /// Any sessions that do not respond within this specified timeout will be disconnect from the server.
 /// Timeouts can happen if a client's game crashed for example.
 /// They will stop responding to the server, but will not explicitly send a disconnect request.
 /// Hence, they have to be disconnected manually after the timeout passes.
-const SESSION_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum amount of time a closed session waits for the client to acknowledge its final frames.
+///
+/// This is further limited by the session timeout.
+pub const LINGER_TIMEOUT: Duration = Duration::from_secs(2);
+/// Default amount of time a session can be idle before a keepalive ping is sent.
+pub const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
 
 impl RakNetClient {
     /// Starts the ticker task which takes care of packet submission and general user management.
@@ -35,11 +57,11 @@ impl RakNetClient {
         skip_all,
         name = "RaknetUser::receiver",
         fields(
-            address = %self.address
+            address = %self.address()
         )
     )]
     pub async fn receiver(
-        self: Arc<Self>, mut receiver: mpsc::Receiver<RVec>
+        self: Arc<Self>, mut receiver: ForwardReceiver
     ) {
         let mut interval = tokio::time::interval(INTERNAL_TICK_INTERVAL);
 
@@ -53,10 +75,23 @@ impl RakNetClient {
                         tracing::error!("{err:#}");
                     }
                 },
+                _ = self.send.urgent() => {
+                    // Give other tasks the chance to queue more frames so that bursts are sent in a single batch.
+                    tokio::task::yield_now().await;
+                    if let Err(err) = self.flush_urgent().await {
+                        tracing::error!("{err:#}");
+                    }
+                },
                 packet = receiver.recv() => {
                     let Some(packet) = packet else {
                         // Receiver channel closed, shut down this session.
-                        break
+                        // Acknowledgements can no longer be received, so there is no point in lingering.
+                        if let Err(err) = self.flush_all().await {
+                            tracing::error!("Failed to flush client's final packets: {err:#}");
+                        }
+
+                        self.shutdown_token.cancel();
+                        return
                     };
 
                     match self.budget.try_acquire() {
@@ -87,11 +122,64 @@ impl RakNetClient {
             should_run = !self.active.is_cancelled();
         }
 
+        self.linger(&mut receiver).await;
+        self.shutdown_token.cancel();
+    }
+
+    /// Sends the final frames of a closed session and waits for the client to acknowledge them.
+    ///
+    /// Incoming datagrams are still processed so that acknowledgements and NAKs are handled, lost frames are
+    /// retransmitted as usual. The session stops lingering once all reliable frames have been acknowledged, the
+    /// client has left or [`LINGER_TIMEOUT`] has passed.
+    async fn linger(&self, receiver: &mut ForwardReceiver) {
+        if !self.peer_closed.load(Ordering::Acquire) {
+            // Let the client know that the session has ended if this was not done yet.
+            self.disconnect();
+        }
+
         if let Err(err) = self.flush_all().await {
             tracing::error!("Failed to flush client's final packets: {err:#}");
         }
 
-        self.shutdown_token.cancel();
+        let deadline = tokio::time::sleep(LINGER_TIMEOUT.min(self.session_timeout));
+        tokio::pin!(deadline);
+
+        let mut interval = tokio::time::interval(INTERNAL_TICK_INTERVAL);
+        loop {
+            let delivered = self.recovery.is_empty() && self.send.is_empty();
+            if delivered || self.peer_closed.load(Ordering::Acquire) {
+                break
+            }
+
+            tokio::select! {
+                () = &mut deadline => {
+                    tracing::debug!("Client did not acknowledge its final frames in time");
+                    break
+                },
+                _ = interval.tick() => {
+                    if let Err(err) = self.retransmit_timed_out() {
+                        tracing::error!("Failed to retransmit client's final packets: {err:#}");
+                    }
+
+                    if let Err(err) = self.flush_all().await {
+                        tracing::error!("Failed to flush client's final packets: {err:#}");
+                    }
+                },
+                packet = receiver.recv() => {
+                    let Some(packet) = packet else { break };
+                    if let Err(err) = self.handle_raw_packet(packet).await {
+                        tracing::debug!("{err:#}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether a keepalive ping should be sent to a session that has been idle for `idle`.
+    fn needs_keepalive(&self, idle: Duration) -> bool {
+        !self.keepalive_interval.is_zero()
+            && idle >= self.keepalive_interval
+            && self.last_keepalive.lock().elapsed() >= self.keepalive_interval
     }
 
     /// Performs tasks not related to packet processing
@@ -102,17 +190,340 @@ impl RakNetClient {
         if current_tick % 20 == 0 {
             // self.budget.add_permits(BUDGET_SIZE - self.budget.available_permits());
             self.refill_budget();
+
+            let expired = self.compounds.expire();
+            if expired > 0 {
+                EXPIRED_COMPOUNDS_METRIC.inc_by(expired as u64);
+                tracing::debug!("Discarded {expired} incomplete compounds");
+            }
+        }
+
+        if current_tick % PING_INTERVAL == 0 {
+            self.send_ping()?;
         }
 
-        // Session has timed out
-        if Instant::now().duration_since(*self.last_update.read())
-            > SESSION_TIMEOUT
-        {
+        let idle = Instant::now().duration_since(*self.last_update.read());
+        if idle > self.session_timeout {
+            // Session has timed out
+            SESSION_TIMEOUTS_METRIC.inc();
+            self.set_close_reason(CloseReason::TimedOut);
+            self.peer_closed.store(true, Ordering::Release);
             tracing::warn!("Client unresponsive, disconnecting them...");
             self.active.cancel();
+        } else if self.needs_keepalive(idle) {
+            // The pong resets the idle time, so an idle client that is still alive does not time out.
+            *self.last_keepalive.lock() = Instant::now();
+            self.send_ping()?;
         }
 
+        self.retransmit_timed_out()?;
         self.flush().await?;
+        self.submit_datagrams().await
+    }
+}
+
+/// Future returned by the body and shutdown hook of a [`Job`].
+pub type JobFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+
+/// What a job does while it is running.
+enum JobBody {
+    /// Called on every tick of the interval.
+    Periodic {
+        interval: Duration,
+        tick: Box<dyn FnMut() -> JobFuture + Send>,
+    },
+    /// Called once, the job stops when the future completes or the token is cancelled.
+    Task(Box<dyn FnOnce(CancellationToken) -> JobFuture + Send>),
+}
+
+/// A named background job managed by a [`JobScheduler`].
+///
+/// Jobs can depend on other jobs. A job is only started once its dependencies are running and is always
+/// stopped before any of its dependencies, so that it can rely on them for its entire lifetime, including its
+/// shutdown hook.
+pub struct Job {
+    name: &'static str,
+    dependencies: Vec<&'static str>,
+    body: JobBody,
+    on_shutdown: Option<Box<dyn FnOnce() -> JobFuture + Send>>,
+}
+
+impl Job {
+    /// Creates a job that runs `tick` at a fixed interval.
+    ///
+    /// Ticks are never run concurrently. If a tick takes longer than the interval, the next tick is delayed.
+    pub fn periodic<F, Fut>(name: &'static str, interval: Duration, mut tick: F) -> Job
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let tick = Box::new(move || Box::pin(tick()) as JobFuture);
+        Job { name, dependencies: Vec::new(), body: JobBody::Periodic { interval, tick }, on_shutdown: None }
+    }
+
+    /// Creates a long-running job.
+    ///
+    /// The job receives a token that is cancelled when it should stop.
+    pub fn task<F, Fut>(name: &'static str, run: F) -> Job
+    where
+        F: FnOnce(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let run = Box::new(move |token| Box::pin(run(token)) as JobFuture);
+        Job { name, dependencies: Vec::new(), body: JobBody::Task(run), on_shutdown: None }
+    }
+
+    /// Makes this job depend on the job with the given name.
+    pub fn after(mut self, dependency: &'static str) -> Job {
+        self.dependencies.push(dependency);
+        self
+    }
+
+    /// Sets a hook that runs after the job has stopped, such as persisting its state.
+    pub fn on_shutdown<F, Fut>(mut self, hook: F) -> Job
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.on_shutdown = Some(Box::new(move || Box::pin(hook()) as JobFuture));
+        self
+    }
+
+    /// Name of the job.
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Spawns the job.
+    fn spawn(self) -> RunningJob {
+        let token = CancellationToken::new();
+        let name = self.name;
+
+        let future = {
+            let token = token.clone();
+            async move {
+                match self.body {
+                    JobBody::Periodic { interval, mut tick } => {
+                        let mut interval = tokio::time::interval(interval);
+                        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+                        loop {
+                            tokio::select! {
+                                _ = interval.tick() => {
+                                    if let Err(err) = tick().await {
+                                        tracing::error!("Job {name} failed: {err:#}");
+                                    }
+                                }
+                                () = token.cancelled() => break
+                            }
+                        }
+                    }
+                    JobBody::Task(run) => {
+                        if let Err(err) = run(token).await {
+                            tracing::error!("Job {name} failed: {err:#}");
+                        }
+                    }
+                }
+            }
+        };
+
+        RunningJob {
+            name,
+            token,
+            handle: util::task::spawn_owned("job", name, future),
+            on_shutdown: self.on_shutdown,
+        }
+    }
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("name", &self.name)
+            .field("dependencies", &self.dependencies)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A job that has been spawned.
+struct RunningJob {
+    name: &'static str,
+    token: CancellationToken,
+    handle: JoinHandle<()>,
+    on_shutdown: Option<Box<dyn FnOnce() -> JobFuture + Send>>,
+}
+
+/// Runs named background jobs and shuts them down in dependency order.
+///
+/// Jobs registered before [`start`](Self::start) are started together once all of them have been registered.
+/// Jobs registered afterwards are started immediately, which requires their dependencies to be running already.
+///
+/// Once the token passed to [`new`](Self::new) is cancelled, the jobs are stopped one by one in the reverse
+/// order that they were started in. This means that every job is stopped before the jobs it depends on.
+/// [`join`](Joinable::join) waits for this sequence to complete.
+pub struct JobScheduler {
+    /// Jobs waiting for the scheduler to start.
+    pending: Mutex<Vec<Job>>,
+    /// Jobs that are running, in the order that they were started in.
+    running: Mutex<Vec<RunningJob>>,
+    /// Set once the scheduler has started.
+    started: CancellationToken,
+    /// Starts the shutdown sequence when cancelled.
+    token: CancellationToken,
+    /// Cancelled once every job has stopped.
+    finished: CancellationToken,
+}
+
+impl JobScheduler {
+    /// Creates a scheduler that shuts down its jobs when `token` is cancelled.
+    pub fn new(token: CancellationToken) -> Arc<JobScheduler> {
+        Arc::new(JobScheduler {
+            pending: Mutex::new(Vec::new()),
+            running: Mutex::new(Vec::new()),
+            started: CancellationToken::new(),
+            token,
+            finished: CancellationToken::new(),
+        })
+    }
+
+    /// Registers a job.
+    ///
+    /// This fails if a job with the same name exists, if the scheduler is shutting down or, once the scheduler has
+    /// started, if the dependencies of the job are not running.
+    pub fn register(&self, job: Job) -> anyhow::Result<()> {
+        if self.token.is_cancelled() {
+            anyhow::bail!("Cannot register job {}, the scheduler is shutting down", job.name);
+        }
+
+        // The scheduler is started while holding this lock, so the job cannot be missed by `start`.
+        let mut pending = self.pending.lock();
+        if !self.started.is_cancelled() {
+            if pending.iter().any(|other| other.name == job.name) {
+                anyhow::bail!("A job named {} has already been registered", job.name);
+            }
+
+            pending.push(job);
+            drop(pending);
+
+            return Ok(())
+        }
+        drop(pending);
+
+        let mut running = self.running.lock();
+        if running.iter().any(|other| other.name == job.name) {
+            anyhow::bail!("A job named {} has already been registered", job.name);
+        }
+
+        if let Some(missing) = job.dependencies.iter().find(|dep| !running.iter().any(|other| other.name == **dep)) {
+            anyhow::bail!("Job {} depends on {missing}, which is not running", job.name);
+        }
+
+        running.push(job.spawn());
+        drop(running);
+
+        Ok(())
+    }
+
+    /// Starts all registered jobs.
+    ///
+    /// No jobs are started if a dependency does not exist or the dependencies contain a cycle.
+    pub fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        let mut pending = self.pending.lock();
+        if self.started.is_cancelled() {
+            anyhow::bail!("The job scheduler has already been started");
+        }
+
+        let order = Self::resolve(&pending)?;
+        let mut jobs: Vec<Option<Job>> = pending.drain(..).map(Some).collect();
+        self.running.lock().extend(order.into_iter().filter_map(|index| jobs[index].take()).map(Job::spawn));
+        self.started.cancel();
+        drop(pending);
+
+        let this = Arc::clone(self);
+        util::task::spawn("job::scheduler", async move {
+            this.token.cancelled().await;
+            this.shutdown().await;
+        });
+
+        Ok(())
+    }
+
+    /// Orders the jobs so that every job comes after its dependencies.
+    fn resolve(jobs: &[Job]) -> anyhow::Result<Vec<usize>> {
+        for job in jobs {
+            if let Some(missing) = job.dependencies.iter().find(|dep| !jobs.iter().any(|other| other.name == **dep)) {
+                anyhow::bail!("Job {} depends on {missing}, which does not exist", job.name);
+            }
+        }
+
+        let mut order = Vec::with_capacity(jobs.len());
+        let mut resolved = HashSet::with_capacity(jobs.len());
+        while order.len() < jobs.len() {
+            let next = jobs.iter().enumerate().find(|(index, job)| {
+                !order.contains(index) && job.dependencies.iter().all(|dep| resolved.contains(dep))
+            });
+
+            let Some((index, job)) = next else {
+                let remaining = jobs.iter()
+                    .enumerate()
+                    .filter(|(index, _)| !order.contains(index))
+                    .map(|(_, job)| job.name)
+                    .collect::<Vec<_>>();
+
+                anyhow::bail!("Jobs {remaining:?} have cyclic dependencies");
+            };
+
+            order.push(index);
+            resolved.insert(job.name);
+        }
+
+        Ok(order)
+    }
+
+    /// Names of the jobs that are currently running, in the order that they were started in.
+    pub fn running(&self) -> Vec<&'static str> {
+        self.running.lock().iter().map(|job| job.name).collect()
+    }
+
+    /// Stops all jobs in the reverse order that they were started in.
+    ///
+    /// This is called automatically when the token of the scheduler is cancelled.
+    async fn shutdown(&self) {
+        loop {
+            let Some(job) = self.running.lock().pop() else {
+                break
+            };
+
+            tracing::debug!("Stopping job {}", job.name);
+            job.token.cancel();
+            if let Err(err) = job.handle.await {
+                tracing::error!("Job {} panicked: {err:#}", job.name);
+            }
+
+            if let Some(hook) = job.on_shutdown {
+                if let Err(err) = hook().await {
+                    tracing::error!("Shutdown hook of job {} failed: {err:#}", job.name);
+                }
+            }
+        }
+
+        self.finished.cancel();
+    }
+}
+
+impl fmt::Debug for JobScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobScheduler")
+            .field("running", &self.running())
+            .field("started", &self.started.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Joinable for JobScheduler {
+    async fn join(&self) -> anyhow::Result<()> {
+        self.finished.cancelled().await;
         Ok(())
     }
 }
@@ -0,0 +1,154 @@
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use proto::raknet::{ConnectedPing, ConnectedPong};
+use util::{RVec, Deserialize, Serialize};
+
+use crate::{RakNetClient, Reliability, RttEstimator, SendConfig, SendPriority};
+
+/// Pongs reporting a round trip longer than this are assumed to be bogus and are ignored.
+const MAX_PLAUSIBLE_RTT: Duration = Duration::from_secs(30);
+
+/// Round trip time statistics of a connection.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Most recently measured round trip time.
+    pub latest: Duration,
+    /// Exponentially weighted moving average of the round trip time.
+    pub smoothed: Duration,
+    /// Mean deviation of the round trip time. A high variance indicates an unstable connection.
+    pub variance: Duration,
+    /// Lowest round trip time measured so far.
+    pub min: Duration,
+    /// Highest round trip time measured so far.
+    pub max: Duration,
+    /// Amount of round trips that have been measured.
+    pub samples: u64,
+}
+
+/// Collects round trip time measurements from ping/pong exchanges and acknowledgements.
+#[derive(Debug)]
+pub struct LatencyTracker {
+    /// Ping timestamps are expressed in milliseconds since this instant.
+    epoch: Instant,
+    samples: Mutex<Samples>,
+}
+
+/// Measurements collected by a [`LatencyTracker`].
+#[derive(Debug, Default)]
+struct Samples {
+    /// Same estimator that is used for the retransmission timeout.
+    estimator: RttEstimator,
+    stats: LatencyStats,
+}
+
+impl Samples {
+    /// Adds a round trip time measurement to the estimate and statistics.
+    fn record(&mut self, rtt: Duration) {
+        self.estimator.sample(rtt);
+
+        let smoothed = self.estimator.smoothed().unwrap_or(rtt);
+        let variance = self.estimator.variance();
+        let stats = &mut self.stats;
+
+        if stats.samples == 0 {
+            stats.min = rtt;
+            stats.max = rtt;
+        } else {
+            stats.min = stats.min.min(rtt);
+            stats.max = stats.max.max(rtt);
+        }
+
+        stats.smoothed = smoothed;
+        stats.variance = variance;
+        stats.latest = rtt;
+        stats.samples += 1;
+    }
+}
+
+impl LatencyTracker {
+    /// Creates a tracker without any measurements.
+    pub fn new() -> LatencyTracker {
+        LatencyTracker { epoch: Instant::now(), samples: Mutex::new(Samples::default()) }
+    }
+
+    /// Current timestamp to put in a [`ConnectedPing`].
+    pub fn timestamp(&self) -> i64 {
+        self.epoch.elapsed().as_millis() as i64
+    }
+
+    /// Records a round trip time measurement.
+    pub fn record(&self, rtt: Duration) {
+        self.samples.lock().record(rtt);
+    }
+
+    /// Records the round trip of a ping that was sent at the given timestamp.
+    pub fn record_pong(&self, ping_time: i64) {
+        let Ok(elapsed) = u64::try_from(self.timestamp() - ping_time) else {
+            tracing::debug!("Received pong for a ping that has not been sent yet");
+            return;
+        };
+
+        let rtt = Duration::from_millis(elapsed);
+        if rtt <= MAX_PLAUSIBLE_RTT {
+            self.record(rtt);
+        }
+    }
+
+    /// Smoothed round trip time, or `None` if no measurements have been made yet.
+    pub fn latency(&self) -> Option<Duration> {
+        self.samples.lock().estimator.smoothed()
+    }
+
+    /// Returns a snapshot of the collected statistics.
+    pub fn stats(&self) -> LatencyStats {
+        self.samples.lock().stats
+    }
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RakNetClient {
+    /// Smoothed round trip time to the client, or `None` if it has not been measured yet.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency.latency()
+    }
+
+    /// Detailed round trip time statistics of the connection.
+    pub fn latency_stats(&self) -> LatencyStats {
+        self.latency.stats()
+    }
+
+    /// Sends a [`ConnectedPing`] to the client to measure the round trip time.
+    pub fn send_ping(&self) -> anyhow::Result<()> {
+        let ping = ConnectedPing { time: self.latency.timestamp() };
+
+        let mut packet = RVec::alloc_with_capacity(ping.size_hint());
+        ping.serialize_into(&mut packet)?;
+
+        // High priority prevents the queueing delay from ending up in the measurement.
+        self.send_raw_buffer_with_config(packet, SendConfig {
+            reliability: Reliability::Unreliable,
+            priority: SendPriority::High,
+            channel: 0,
+        });
+
+        Ok(())
+    }
+
+    /// Handles a [`ConnectedPong`] packet.
+    pub fn handle_connected_pong(&self, packet: RVec) -> anyhow::Result<()> {
+        let pong = ConnectedPong::deserialize(packet.as_ref())?;
+        if self.complete_migration(pong.ping_time) {
+            return Ok(())
+        }
+
+        self.latency.record_pong(pong.ping_time);
+        self.clock.record(pong.ping_time, pong.pong_time, self.latency.timestamp());
+        Ok(())
+    }
+}
@@ -44,16 +44,39 @@
 
 use util::glob_export;
 
+#[cfg(test)]
+mod test;
+
 glob_export!(ack);
+glob_export!(batch);
 glob_export!(broadcast);
+glob_export!(capture);
+glob_export!(clock);
 glob_export!(compound);
+glob_export!(congestion);
+glob_export!(connector);
+glob_export!(cookie);
+glob_export!(datagram);
+glob_export!(dedup);
+glob_export!(forward);
 glob_export!(frame);
+glob_export!(frame_pool);
+glob_export!(handshake);
+glob_export!(latency);
+glob_export!(listener);
 glob_export!(login);
+glob_export!(migration);
+glob_export!(mtu);
 glob_export!(order);
+glob_export!(pacing);
+glob_export!(rate_limit);
 glob_export!(receive);
 glob_export!(recovery);
 glob_export!(reliability);
+glob_export!(reuse_port);
 glob_export!(send_queue);
 glob_export!(send);
+glob_export!(socket);
+glob_export!(stats);
 glob_export!(client);
 glob_export!(job);
@@ -0,0 +1,455 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use proto::bedrock::CONNECTED_PACKET_ID;
+use proto::raknet::RAKNET_VERSION;
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use util::RVec;
+
+use crate::{
+    forward_channel, BackpressurePolicy, BroadcastPacket, CloseReason, CompoundLimits, DatagramBatch, ForwardSender, Forwarded, OfflineHandshake,
+    RakNetClient, RakNetCommand, RakNetConnection, RakNetCreateDescription, ReceiveBatch, SendConfig, SendWeights, SessionStats,
+    Socket, CONNECTED_PEER_BIT_FLAG, DEFAULT_ACK_INTERVAL, DEFAULT_KEEPALIVE_INTERVAL, DEFAULT_MAX_BATCH_FRAMES, DEFAULT_SEND_CONFIG,
+    DEFAULT_SESSION_TIMEOUT, MAX_MTU, MAX_ORDER_CHANNELS,
+};
+
+/// Amount of datagrams that are received at once.
+const RECV_BATCH_SIZE: usize = 32;
+/// Size of the buffers that datagrams are received into.
+const RECV_BUF_SIZE: usize = 2048;
+/// Capacity of the channel that forwards received datagrams to a connection.
+const FORWARD_CHANNEL_SIZE: usize = 64;
+
+/// Settings used when accepting connections with a [`Listener`].
+#[derive(Debug, Clone)]
+pub struct ListenOptions {
+    /// GUID of the listener.
+    pub guid: u64,
+    /// RakNet protocol versions that clients are allowed to use.
+    pub versions: Vec<u8>,
+    /// Largest MTU that is negotiated with clients.
+    pub max_mtu: u16,
+    /// Whether clients have to echo a handshake cookie before a session is created for them.
+    ///
+    /// See [`HandshakeCookies`] for why this should be enabled.
+    pub handshake_cookies: bool,
+    /// Amount of unconnected packets that every IP address is allowed to send per second, 0 disables the limit.
+    pub unconnected_rate: u32,
+    /// Amount of unconnected packets that an IP address can send in a burst.
+    pub unconnected_burst: u32,
+    /// Amount of connections that have completed the handshake but have not been accepted yet.
+    ///
+    /// Connections that complete their handshake while this queue is full are closed.
+    pub backlog: usize,
+    /// What happens to datagrams of a connection that is not keeping up with them.
+    pub backpressure: BackpressurePolicy,
+    /// Limits on the fragments that are buffered for each connection.
+    pub compound_limits: CompoundLimits,
+    /// Maximum amount of frames in a single batch received from a client.
+    pub max_batch_frames: usize,
+    /// Amount of order channels that clients are allowed to use.
+    pub order_channels: usize,
+    /// How often received batches are acknowledged.
+    pub ack_interval: Duration,
+    /// Maximum amount of bytes per second that is sent to each client, 0 disables the limit.
+    pub send_rate: u64,
+    /// Share of the send budget that each priority receives.
+    pub send_weights: SendWeights,
+    /// How long a client can be unresponsive before its connection is closed.
+    pub session_timeout: Duration,
+    /// How long a connection can be idle before a keepalive ping is sent, 0 disables keepalives.
+    pub keepalive_interval: Duration,
+}
+
+impl Default for ListenOptions {
+    fn default() -> Self {
+        let guid = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+
+        Self {
+            guid,
+            versions: vec![RAKNET_VERSION],
+            max_mtu: MAX_MTU,
+            handshake_cookies: true,
+            unconnected_rate: 20,
+            unconnected_burst: 40,
+            backlog: 32,
+            backpressure: BackpressurePolicy::default(),
+            compound_limits: CompoundLimits::default(),
+            max_batch_frames: DEFAULT_MAX_BATCH_FRAMES,
+            order_channels: MAX_ORDER_CHANNELS,
+            ack_interval: DEFAULT_ACK_INTERVAL,
+            send_rate: 0,
+            send_weights: SendWeights::default(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            keepalive_interval: DEFAULT_KEEPALIVE_INTERVAL,
+        }
+    }
+}
+
+/// A connection to a RakNet peer.
+///
+/// This wraps the reliability layer of a [`RakNetClient`] in a message based interface: every call to
+/// [`send`](Self::send) arrives as a single message at the [`recv`](Self::recv) of the peer.
+/// Messages are framed using the same game packet ID that Minecraft uses, so connections can talk to any
+/// RakNet implementation that speaks the Bedrock protocol.
+pub struct Connection {
+    client: Arc<RakNetClient>,
+    receiver: mpsc::Receiver<RakNetCommand>,
+}
+
+impl Connection {
+    /// Address of the peer.
+    #[inline]
+    pub fn address(&self) -> SocketAddr {
+        self.client.address()
+    }
+
+    /// RakNet GUID of the peer.
+    #[inline]
+    pub fn guid(&self) -> u64 {
+        self.client.guid
+    }
+
+    /// Maximum transfer unit of the connection.
+    #[inline]
+    pub fn mtu(&self) -> u16 {
+        self.client.mtu
+    }
+
+    /// Returns a snapshot of the network statistics of this connection.
+    #[inline]
+    pub fn stats(&self) -> SessionStats {
+        self.client.stats()
+    }
+
+    /// The underlying RakNet state, for anything not covered by this handle.
+    #[inline]
+    pub const fn client(&self) -> &Arc<RakNetClient> {
+        &self.client
+    }
+
+    /// Sends a message reliably and in order.
+    pub fn send(&self, message: &[u8]) {
+        self.send_with(message, DEFAULT_SEND_CONFIG);
+    }
+
+    /// Sends a message with custom reliability and priority.
+    pub fn send_with(&self, message: &[u8], config: SendConfig) {
+        let mut buffer = RVec::alloc_with_capacity(message.len() + 1);
+        buffer.push(CONNECTED_PACKET_ID);
+        buffer.extend_from_slice(message);
+
+        self.client.send_raw_buffer_with_config(buffer, config);
+    }
+
+    /// Receives the next message.
+    ///
+    /// Returns `None` once the connection has been closed and all messages received before that have been read.
+    pub async fn recv(&mut self) -> Option<RVec> {
+        loop {
+            let command = tokio::select! {
+                biased;
+
+                command = self.receiver.recv() => command?,
+                () = self.client.active.cancelled() => self.receiver.try_recv().ok()?,
+            };
+
+            match command {
+                RakNetCommand::Received(mut message) => {
+                    // Strip the game packet ID.
+                    message.remove(0);
+                    return Some(message)
+                }
+                RakNetCommand::BudgetExhausted => {
+                    tracing::warn!("{} exhausted its budget, closing connection", self.address());
+                    self.client.disconnect();
+                }
+                RakNetCommand::Disconnected => return None,
+            }
+        }
+    }
+
+    /// Closes the connection.
+    ///
+    /// Messages that were already sent are still delivered, see [`RakNetClient::disconnect`].
+    #[inline]
+    pub fn close(&self) {
+        self.client.disconnect();
+    }
+
+    /// Whether the connection has been closed by either side.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.client.active.is_cancelled()
+    }
+
+    /// Why the connection was closed, or `None` if it is still open.
+    #[inline]
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.client.close_reason()
+    }
+}
+
+impl From<RakNetConnection> for Connection {
+    fn from(connection: RakNetConnection) -> Connection {
+        Connection { client: connection.client, receiver: connection.receiver }
+    }
+}
+
+impl std::fmt::Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection")
+            .field("address", &self.address())
+            .field("guid", &self.guid())
+            .field("mtu", &self.mtu())
+            .finish_non_exhaustive()
+    }
+}
+
+/// State shared between a listener and its receiving task.
+struct ListenerState {
+    socket: Arc<Socket>,
+    /// Datagrams that the sessions queued for `socket`.
+    outgoing: Arc<DatagramBatch>,
+    options: ListenOptions,
+    /// Sent to clients in response to unconnected pings.
+    metadata: RwLock<String>,
+    /// Sessions that have not fully shut down yet, by address.
+    sessions: DashMap<SocketAddr, (Arc<RakNetClient>, ForwardSender)>,
+    handshake: OfflineHandshake,
+    /// Unused by the listener, but required by every session.
+    broadcast: broadcast::Sender<BroadcastPacket>,
+    /// Connections that completed their handshake.
+    accepted: mpsc::Sender<Connection>,
+    token: CancellationToken,
+}
+
+/// Accepts RakNet connections on a socket.
+///
+/// The listener performs the offline handshake with clients and runs the reliability layer of every connection
+/// in the background. Connections are handed out by [`accept`](Self::accept) once their handshake has completed.
+///
+/// ```ignore
+/// let mut listener = Listener::bind("0.0.0.0:19132".parse()?, ListenOptions::default()).await?;
+/// while let Some(mut connection) = listener.accept().await {
+///     tokio::spawn(async move {
+///         while let Some(message) = connection.recv().await {
+///             connection.send(&message);
+///         }
+///     });
+/// }
+/// ```
+///
+/// Dropping the listener stops receiving datagrams and closes all of its connections.
+pub struct Listener {
+    state: Arc<ListenerState>,
+    incoming: mpsc::Receiver<Connection>,
+}
+
+impl Listener {
+    /// Binds a UDP socket to `address` and starts listening on it.
+    pub async fn bind(address: SocketAddr, options: ListenOptions) -> anyhow::Result<Listener> {
+        let socket = Arc::new(Socket::from(UdpSocket::bind(address).await?));
+        Ok(Listener::from_socket(socket, options))
+    }
+
+    /// Starts listening on an existing socket.
+    ///
+    /// The listener must be the only reader of the socket.
+    pub fn from_socket(socket: Arc<Socket>, options: ListenOptions) -> Listener {
+        let (accepted, incoming) = mpsc::channel(options.backlog.max(1));
+
+        let state = Arc::new(ListenerState {
+            socket,
+            outgoing: Arc::new(DatagramBatch::new()),
+            metadata: RwLock::new(String::new()),
+            sessions: DashMap::new(),
+            handshake: OfflineHandshake::new(&options),
+            broadcast: broadcast::channel(1).0,
+            accepted,
+            token: CancellationToken::new(),
+            options,
+        });
+
+        util::task::spawn("raknet::listener", Arc::clone(&state).receiver());
+
+        Listener { state, incoming }
+    }
+
+    /// Waits for the next connection.
+    ///
+    /// Returns `None` once the listener has been closed.
+    pub async fn accept(&mut self) -> Option<Connection> {
+        tokio::select! {
+            connection = self.incoming.recv() => connection,
+            () = self.state.token.cancelled() => None,
+        }
+    }
+
+    /// Address that the listener is bound to.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.state.socket.local_addr()
+    }
+
+    /// Sets the metadata that is sent in response to unconnected pings.
+    ///
+    /// Minecraft clients expect the server banner here, other applications can use it for anything.
+    pub fn set_metadata(&self, metadata: String) {
+        *self.state.metadata.write() = metadata;
+    }
+
+    /// Amount of connections, including those that are still performing their handshake or shutting down.
+    pub fn len(&self) -> usize {
+        self.state.sessions.len()
+    }
+
+    /// Whether there are no connections.
+    pub fn is_empty(&self) -> bool {
+        self.state.sessions.is_empty()
+    }
+
+    /// Stops accepting connections and closes all existing ones.
+    pub fn close(&self) {
+        self.state.token.cancel();
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl std::fmt::Debug for Listener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Listener")
+            .field("local_addr", &self.local_addr().ok())
+            .field("connections", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ListenerState {
+    /// Receives datagrams until the listener is closed.
+    async fn receiver(self: Arc<Self>) {
+        let mut batch = ReceiveBatch::new(RECV_BATCH_SIZE, RECV_BUF_SIZE);
+
+        loop {
+            tokio::select! {
+                received = batch.recv(&self.socket) => {
+                    if let Err(err) = received {
+                        tracing::error!("Failed to receive datagram: {err}");
+                        continue
+                    }
+                },
+                () = self.token.cancelled() => break
+            }
+
+            for (datagram, address) in batch.iter() {
+                self.handle_datagram(datagram, address).await;
+            }
+        }
+
+        for session in &self.sessions {
+            session.0.disconnect();
+        }
+    }
+
+    /// Forwards a datagram to its session or handles it as an unconnected packet.
+    async fn handle_datagram(self: &Arc<Self>, datagram: &[u8], address: SocketAddr) {
+        if !crate::accept_datagram(datagram, address) {
+            return
+        }
+
+        let Some(&id) = datagram.first() else { return };
+        if id & CONNECTED_PEER_BIT_FLAG == 0 {
+            if !self.handshake.allow(address.ip()) {
+                tracing::trace!("Ignoring unconnected packet from rate limited address {address}");
+                return
+            }
+
+            let reply = self.handshake.handle(datagram, address, &self.metadata.read(), |guid, mtu| {
+                // The session already exists if the reply was lost and the client is retrying.
+                let existing = self.sessions.get(&address).map(|session| session.0.mtu);
+                existing.map_or_else(|| self.open_session(address, guid, mtu), Ok)
+            });
+
+            match reply {
+                Ok(reply) => {
+                    if let Err(err) = self.socket.send_to(&reply, address).await {
+                        tracing::error!("Unable to send unconnected packet to {address}: {err}");
+                    }
+                }
+                Err(err) => tracing::debug!("Invalid unconnected packet from {address}: {err:#}"),
+            }
+
+            return
+        }
+
+        // The entry must not be held while waiting for room in the queue.
+        let Some(session) = self.sessions.get(&address).map(|session| session.clone()) else { return };
+        let (client, forward) = session;
+
+        if forward.send(RVec::alloc_from_slice(datagram)).await == Forwarded::Overloaded {
+            tracing::warn!("{address} is not keeping up with its packets, closing connection");
+            client.disconnect();
+        }
+    }
+
+    /// Creates the session of a client that has opened a connection and returns its MTU.
+    fn open_session(self: &Arc<Self>, address: SocketAddr, guid: u64, requested_mtu: u16) -> anyhow::Result<u16> {
+        let mtu = self.handshake.negotiate_mtu(address, requested_mtu)?;
+        let options = &self.options;
+        let (forward_tx, forward_rx) = forward_channel(FORWARD_CHANNEL_SIZE, options.backpressure);
+
+        let (client, receiver) = RakNetClient::new(
+            RakNetCreateDescription {
+                address,
+                mtu,
+                guid,
+                socket: Arc::clone(&self.socket),
+                outgoing: Arc::clone(&self.outgoing),
+                compound_limits: options.compound_limits,
+                max_batch_frames: options.max_batch_frames,
+                order_channels: options.order_channels,
+                ack_interval: options.ack_interval,
+                send_rate: options.send_rate,
+                send_weights: options.send_weights,
+                session_timeout: options.session_timeout,
+                keepalive_interval: options.keepalive_interval,
+                capture: None,
+            },
+            self.broadcast.clone(),
+            forward_rx,
+        );
+
+        self.sessions.insert(address, (Arc::clone(&client), forward_tx));
+        util::task::spawn_owned("raknet::listener::session", address, Arc::clone(self).track_session(client, receiver));
+
+        Ok(mtu)
+    }
+
+    /// Hands the session out once it has connected and forgets it once it has shut down.
+    async fn track_session(self: Arc<Self>, client: Arc<RakNetClient>, receiver: mpsc::Receiver<RakNetCommand>) {
+        let address = client.address();
+
+        tokio::select! {
+            () = client.connected.cancelled() => {
+                let connection = Connection { client: Arc::clone(&client), receiver };
+                if self.accepted.try_send(connection).is_err() {
+                    tracing::warn!("Connection backlog is full, refusing {address}");
+                    client.disconnect();
+                }
+            },
+            () = client.active.cancelled() => ()
+        }
+
+        client.shutdown_token.cancelled().await;
+        self.sessions.remove_if(&address, |_, session| Arc::ptr_eq(&session.0, &client));
+    }
+}
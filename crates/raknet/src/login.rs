@@ -12,7 +12,7 @@ impl RakNetClient {
         tracing::debug!("{request:?}");
 
         let reply = ConnectionRequestAccepted {
-            client_address: self.address,
+            client_address: self.address(),
             request_time: request.time,
         };
 
@@ -31,6 +31,7 @@ impl RakNetClient {
         #[cfg(trace_raknet)]
         tracing::debug!("{_request:?}");
 
+        self.connected.cancel();
         Ok(())
     }
 
@@ -41,10 +42,15 @@ impl RakNetClient {
         #[cfg(trace_raknet)]
         tracing::debug!("{ping:?}");
 
+        let now = self.latency.timestamp();
+        if let Some(rtt) = self.latency() {
+            self.clock.record_one_way(ping.time, now, rtt);
+        }
+
         let pong = ConnectedPong {
             ping_time: ping.time,
-            pong_time: ping.time,
-        }; 
+            pong_time: now,
+        };
 
         packet.clear();
         packet.reserve_to(pong.size_hint());
@@ -55,6 +61,7 @@ impl RakNetClient {
             SendConfig {
                 reliability: Reliability::Unreliable,
                 priority: SendPriority::Low,
+                channel: 0,
             },
         );
 
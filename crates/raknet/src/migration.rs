@@ -0,0 +1,120 @@
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use proto::raknet::ConnectedPing;
+use util::{RVec, Serialize};
+
+use crate::{Direction, Frame, FrameBatch, RakNetClient, Reliability};
+
+/// How long a client has to answer the verification ping sent to its new address.
+const MIGRATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A move of a connection to a new address that is waiting for verification.
+#[derive(Debug, Copy, Clone)]
+pub struct PendingMigration {
+    /// Address that the connection is moving to.
+    pub address: SocketAddr,
+    /// Random value that the client has to echo in its pong.
+    nonce: i64,
+    /// When the verification ping was sent.
+    started: Instant,
+}
+
+impl RakNetClient {
+    /// Starts moving this connection to a new address.
+    ///
+    /// Mobile clients that switch networks continue from a different source address. Instead of treating them
+    /// as a new connection, a [`ConnectedPing`] containing a random nonce is framed using the state of this
+    /// connection and sent to the new address. Only a client that holds the connection state can answer it with
+    /// a matching pong, after which the connection is rebound to the new address.
+    ///
+    /// Datagrams from the new address must be forwarded to this connection while the migration is pending.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if migrations were disabled with [`lock_migration`](Self::lock_migration).
+    pub async fn begin_migration(&self, address: SocketAddr) -> anyhow::Result<()> {
+        if self.is_migration_locked() {
+            anyhow::bail!("Connection of {} cannot be migrated", self.address());
+        }
+
+        let nonce = rand::random::<i64>();
+        let ping = ConnectedPing { time: nonce };
+
+        let mut body = RVec::alloc_with_capacity(ping.size_hint());
+        ping.serialize_into(&mut body)?;
+
+        let batch = FrameBatch {
+            sequence_number: self.batch_number.fetch_add(1, Ordering::SeqCst),
+            frames: vec![Frame::new(Reliability::Unreliable, body)],
+        };
+        let mut serialized = Vec::new();
+        batch.serialize_into(&mut serialized)?;
+
+        *self.migration.lock() = Some(PendingMigration { address, nonce, started: Instant::now() });
+        self.socket.send_to(&serialized, address).await?;
+        self.counters.record_sent(serialized.len());
+        if let Some(capture) = &self.capture {
+            capture.record_datagram(Direction::Outbound, address, &serialized);
+        }
+
+        tracing::debug!("Verifying migration of {} to {address}", self.address());
+        Ok(())
+    }
+
+    /// Prevents this connection from moving to a new address and cancels any pending migration.
+    ///
+    /// The verification pong only proves that the client at the new address received the ping, not that it holds
+    /// the state of this connection. Anyone who knows the GUID could therefore take over the connection. This must
+    /// be called once the session is encrypted, since the client cannot prove that it knows the key at this layer.
+    pub fn lock_migration(&self) {
+        self.migration_locked.store(true, Ordering::Release);
+        *self.migration.lock() = None;
+    }
+
+    /// Whether migrations have been disabled by [`lock_migration`](Self::lock_migration).
+    pub fn is_migration_locked(&self) -> bool {
+        self.migration_locked.load(Ordering::Acquire)
+    }
+
+    /// Address that this connection is moving to, if a migration is pending.
+    pub fn migration_target(&self) -> Option<SocketAddr> {
+        self.migration
+            .lock()
+            .filter(|m| m.started.elapsed() <= MIGRATION_TIMEOUT)
+            .map(|m| m.address)
+    }
+
+    /// Completes a pending migration if the pong answers the verification ping.
+    ///
+    /// Returns whether the pong belonged to the migration, in which case it must not be used for latency measurements.
+    pub(crate) fn complete_migration(&self, ping_time: i64) -> bool {
+        let mut lock = self.migration.lock();
+        let Some(migration) = *lock else {
+            return false
+        };
+
+        if migration.nonce != ping_time {
+            return false
+        }
+
+        *lock = None;
+        drop(lock);
+
+        if self.is_migration_locked() {
+            tracing::warn!("Refused to migrate locked connection of {} to {}", self.address(), migration.address);
+            return true
+        }
+
+        if migration.started.elapsed() > MIGRATION_TIMEOUT {
+            tracing::debug!("Migration to {} was verified too late", migration.address);
+            return true
+        }
+
+        let old = self.rebind(migration.address);
+        tracing::info!("Connection migrated from {old} to {}", migration.address);
+
+        true
+    }
+}
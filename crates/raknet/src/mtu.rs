@@ -0,0 +1,127 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+/// Smallest MTU that a connection is allowed to use.
+///
+/// Every IPv4 host is required to accept datagrams of this size.
+pub const MIN_MTU: u16 = 576;
+/// Largest MTU that the server will negotiate by default.
+///
+/// This is the MTU of Ethernet links using PPPoE, which is the smallest MTU commonly found on home connections.
+pub const MAX_MTU: u16 = 1492;
+/// The MTU of a probe is reported as the size of its UDP payload plus an IPv4 and UDP header.
+/// IPv6 headers are this many bytes larger than IPv4 headers.
+const IPV6_EXTRA_OVERHEAD: u16 = 20;
+/// Probes that are not followed by an `OpenConnectionRequest2` within this time are forgotten.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum amount of handshakes that are tracked at the same time.
+const MAX_PENDING_PROBES: usize = 4096;
+
+/// Largest probe that has been received from an address.
+#[derive(Debug, Copy, Clone)]
+struct Probe {
+    /// MTU of the largest probe.
+    mtu: u16,
+    /// When the probe was received.
+    received: Instant,
+}
+
+/// Negotiates the MTU of new connections.
+///
+/// During the handshake the client sends `OpenConnectionRequest1` probes padded to decreasing sizes
+/// and uses the MTU of the first reply it receives. Any probe that arrives has proven that the path from the
+/// client can carry datagrams of that size. The final MTU is clamped to the largest probe that arrived and the MTU
+/// of the local interface, so that frame batches never have to be fragmented at the IP level.
+#[derive(Debug)]
+pub struct MtuDiscovery {
+    /// MTU of the network interface of the server.
+    max_mtu: u16,
+    /// Largest probe received from each address that is currently performing a handshake.
+    probes: DashMap<SocketAddr, Probe>,
+}
+
+impl MtuDiscovery {
+    /// Creates a new MTU negotiator for an interface with the given MTU.
+    ///
+    /// The MTU is clamped to the range [`MIN_MTU`]..=[`MAX_MTU`].
+    pub fn new(max_mtu: u16) -> MtuDiscovery {
+        MtuDiscovery { max_mtu: max_mtu.clamp(MIN_MTU, MAX_MTU), probes: DashMap::new() }
+    }
+
+    /// MTU of the local network interface.
+    pub const fn max_mtu(&self) -> u16 {
+        self.max_mtu
+    }
+
+    /// Largest MTU that can be used with the given address without exceeding the interface MTU.
+    const fn limit(&self, address: SocketAddr) -> u16 {
+        match address {
+            SocketAddr::V4(_) => self.max_mtu,
+            SocketAddr::V6(_) => self.max_mtu - IPV6_EXTRA_OVERHEAD,
+        }
+    }
+
+    /// Registers an `OpenConnectionRequest1` probe of the given size.
+    ///
+    /// Returns the MTU that should be sent back in the reply.
+    pub fn on_probe(&self, address: SocketAddr, mtu: u16) -> u16 {
+        let mtu = mtu.min(self.limit(address));
+
+        if self.probes.len() >= MAX_PENDING_PROBES {
+            self.prune();
+
+            if self.probes.len() >= MAX_PENDING_PROBES && !self.probes.contains_key(&address) {
+                // The final MTU is still clamped to the interface MTU, the probe just isn't remembered.
+                return mtu;
+            }
+        }
+
+        self.probes
+            .entry(address)
+            .and_modify(|probe| {
+                // Probes of an earlier handshake should not influence this one.
+                if probe.received.elapsed() > PROBE_TIMEOUT {
+                    probe.mtu = mtu;
+                } else {
+                    probe.mtu = probe.mtu.max(mtu);
+                }
+                probe.received = Instant::now();
+            })
+            .or_insert(Probe { mtu, received: Instant::now() });
+
+        mtu
+    }
+
+    /// Determines the final MTU of a connection from the MTU requested in `OpenConnectionRequest2`.
+    ///
+    /// This fails if the requested MTU is smaller than [`MIN_MTU`].
+    pub fn negotiate(&self, address: SocketAddr, requested: u16) -> anyhow::Result<u16> {
+        if requested < MIN_MTU {
+            anyhow::bail!("Client requested an MTU of {requested}, which is below the minimum of {MIN_MTU}");
+        }
+
+        let mut mtu = requested.min(self.limit(address));
+        match self.probes.remove(&address) {
+            Some((_, probe)) if probe.received.elapsed() <= PROBE_TIMEOUT => {
+                mtu = mtu.min(probe.mtu);
+            }
+            // Clients should always probe first, but there is no reason to refuse them if they do not.
+            _ => tracing::debug!("{address} did not probe the MTU before requesting one"),
+        }
+
+        Ok(mtu.max(MIN_MTU))
+    }
+
+    /// Forgets all probes of handshakes that have timed out.
+    pub fn prune(&self) {
+        self.probes.retain(|_, probe| probe.received.elapsed() <= PROBE_TIMEOUT);
+    }
+}
+
+impl Default for MtuDiscovery {
+    fn default() -> Self {
+        Self::new(MAX_MTU)
+    }
+}
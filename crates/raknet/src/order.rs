@@ -1,9 +1,24 @@
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
 
 use dashmap::DashMap;
+use parking_lot::Mutex;
+use util::bail;
 
 use crate::Frame;
 
+/// Maximum amount of order channels supported by the protocol.
+pub const MAX_ORDER_CHANNELS: usize = 32;
+/// Order and sequence indices are encoded as 24-bit integers and wrap around.
+const INDEX_MASK: u32 = 0x00ff_ffff;
+
+/// Whether the 24-bit index `a` comes after `b`, taking wrapping into account.
+#[inline]
+const fn is_after(a: u32, b: u32) -> bool {
+    let distance = a.wrapping_sub(b) & INDEX_MASK;
+    distance != 0 && distance < (1 << 23)
+}
+
 /// Ensures that frames are processed in the correct order.
 ///
 /// Frames that are marked as ordered, should be pushed into this channel.
@@ -16,6 +31,10 @@ pub struct OrderChannel {
     last_complete: AtomicU32,
     /// Next index to be used by the server.
     next_index: AtomicU32,
+    /// Next sequence index to be used by the server.
+    next_sequence: AtomicU32,
+    /// Order and sequence index of the newest sequenced frame received from the client.
+    newest_sequenced: Mutex<Option<(u32, u32)>>,
 }
 
 impl OrderChannel {
@@ -24,6 +43,12 @@ impl OrderChannel {
         OrderChannel::default()
     }
 
+    /// Amount of frames that are waiting for earlier frames to arrive.
+    #[inline]
+    pub fn buffered(&self) -> usize {
+        self.channel.len()
+    }
+
     /// Fetches a new index to assign to an ordered frame.
     ///
     /// Every time this is called, the index is increased by 1.
@@ -32,6 +57,41 @@ impl OrderChannel {
         self.next_index.fetch_add(1, Ordering::SeqCst)
     }
 
+    /// Assigns the order and sequence index of a sequenced frame.
+    ///
+    /// Sequenced frames do not take up an order index of their own. Instead they carry the index of the next ordered
+    /// frame, so that they are never processed before ordered frames that were sent earlier.
+    pub fn alloc_sequence(&self) -> (u32, u32) {
+        let order_index = self.next_index.load(Ordering::SeqCst);
+        let sequence_index = self.next_sequence.fetch_add(1, Ordering::SeqCst) & INDEX_MASK;
+
+        (order_index, sequence_index)
+    }
+
+    /// Checks whether a received sequenced frame is newer than all previously received sequenced frames
+    /// in this channel, and records it as the newest one if it is.
+    ///
+    /// Frames for which this returns `false` are stale and should be discarded.
+    pub fn accept_sequenced(&self, frame: &Frame) -> bool {
+        let mut newest = self.newest_sequenced.lock();
+        let current = (frame.order_index, frame.sequence_index);
+
+        // Peers may reset the sequence index whenever they send an ordered frame,
+        // so the order index is compared first.
+        let accept = match *newest {
+            None => true,
+            Some((order_index, sequence_index)) => {
+                is_after(current.0, order_index) || (current.0 == order_index && is_after(current.1, sequence_index))
+            }
+        };
+
+        if accept {
+            *newest = Some(current);
+        }
+
+        accept
+    }
+
     /// Inserts a frame into the order channel.
     ///
     /// In case a sequence of frames is completed, the ready frames will be returned.
@@ -73,3 +133,70 @@ impl OrderChannel {
         }
     }
 }
+
+/// The order channels of a connection.
+///
+/// Every channel is an independent ordering domain, so a frame that is missing from one channel
+/// does not hold up frames on other channels.
+/// Channels are only allocated once they are used for the first time.
+#[derive(Debug)]
+pub struct OrderChannels {
+    channels: [OnceLock<Box<OrderChannel>>; MAX_ORDER_CHANNELS],
+    /// Amount of channels that the connection is allowed to use.
+    count: usize,
+}
+
+impl OrderChannels {
+    /// Creates a new set of order channels, none of which are allocated yet.
+    ///
+    /// All [`MAX_ORDER_CHANNELS`] channels can be used.
+    pub fn new() -> OrderChannels {
+        OrderChannels::with_count(MAX_ORDER_CHANNELS)
+    }
+
+    /// Creates a new set of order channels that only accepts the first `count` channels.
+    ///
+    /// The count is clamped to the range `1..=MAX_ORDER_CHANNELS`.
+    pub fn with_count(count: usize) -> OrderChannels {
+        OrderChannels { channels: Default::default(), count: count.clamp(1, MAX_ORDER_CHANNELS) }
+    }
+
+    /// Amount of channels that can be used.
+    #[inline]
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Ensures that the given channel index can be used by this connection.
+    pub fn validate(&self, index: u8) -> anyhow::Result<()> {
+        if index as usize >= self.count {
+            bail!(Malformed, "Order channel {index} is out of range, the maximum is {}", self.count - 1);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the channel with the given index, allocating it if it has not been used before.
+    ///
+    /// This fails if the index is not below [`count`](Self::count).
+    pub fn get(&self, index: u8) -> anyhow::Result<&OrderChannel> {
+        self.validate(index)?;
+        Ok(self.channels[index as usize].get_or_init(Box::default))
+    }
+
+    /// Amount of frames waiting for earlier frames to arrive, summed over all channels.
+    pub fn buffered(&self) -> usize {
+        self.channels.iter().filter_map(OnceLock::get).map(|channel| channel.buffered()).sum()
+    }
+
+    /// Amount of channels that have been allocated.
+    pub fn allocated(&self) -> usize {
+        self.channels.iter().filter(|channel| channel.get().is_some()).count()
+    }
+}
+
+impl Default for OrderChannels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
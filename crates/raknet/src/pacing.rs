@@ -0,0 +1,74 @@
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+use crate::MAX_MTU;
+
+/// Fraction of a second worth of data that can be sent in a single burst.
+const BURST_FRACTION: f64 = 0.1;
+
+/// Token bucket holding the amount of bytes that can currently be sent.
+#[derive(Debug, Copy, Clone)]
+struct PacerState {
+    /// Bytes that can be sent right now.
+    ///
+    /// This becomes negative when a flush sends more than was available, which delays the next flush.
+    tokens: f64,
+    /// When the bucket was last refilled.
+    updated: Instant,
+}
+
+/// Limits the rate at which data is sent to a single connection.
+///
+/// Without a limit, a single client downloading chunks or resource packs is able to saturate the uplink of
+/// the server. The pacer holds a bucket of bytes that is refilled at the configured rate, frames are only taken
+/// from the send queues while the bucket is not empty.
+#[derive(Debug)]
+pub struct Pacer {
+    /// Bytes added per second.
+    rate: f64,
+    /// Capacity of the bucket.
+    burst: f64,
+    state: Mutex<PacerState>,
+}
+
+impl Pacer {
+    /// Creates a pacer that allows `rate` bytes per second.
+    ///
+    /// A rate of 0 disables pacing entirely.
+    pub fn new(rate: u64) -> Pacer {
+        let rate = rate as f64;
+        // The bucket must be able to hold at least a single datagram, otherwise nothing could ever be sent.
+        let burst = (rate * BURST_FRACTION).max(f64::from(MAX_MTU));
+
+        Pacer { rate, burst, state: Mutex::new(PacerState { tokens: burst, updated: Instant::now() }) }
+    }
+
+    /// Whether pacing is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.rate > 0.0
+    }
+
+    /// Amount of bytes that can be sent right now.
+    pub fn available(&self) -> usize {
+        if !self.is_enabled() {
+            return usize::MAX
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock();
+
+        let elapsed = now.saturating_duration_since(state.updated).as_secs_f64();
+        state.tokens = elapsed.mul_add(self.rate, state.tokens).min(self.burst);
+        state.updated = now;
+
+        state.tokens.max(0.0) as usize
+    }
+
+    /// Registers that `bytes` bytes have been sent.
+    pub fn consume(&self, bytes: usize) {
+        if self.is_enabled() {
+            self.state.lock().tokens -= bytes as f64;
+        }
+    }
+}
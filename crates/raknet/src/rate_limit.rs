@@ -0,0 +1,140 @@
+use std::net::IpAddr;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use prometheus_client::metrics::counter::Counter;
+
+lazy_static! {
+    #[doc(hidden)]
+    pub static ref RATE_LIMITED_METRIC: Counter::<u64, AtomicU64> = Counter::default();
+}
+
+/// Maximum amount of addresses that are tracked at the same time.
+///
+/// Packets from new addresses are dropped while the table is full. This prevents a flood from spoofed
+/// addresses from exhausting memory.
+pub const MAX_TRACKED_ADDRESSES: usize = 8192;
+/// Minimum time between two sweeps of a full table.
+///
+/// Sweeping visits every address, so doing it for every packet from a new address while the table is full
+/// would make a flood of spoofed addresses quadratic.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Token bucket of a single address.
+#[derive(Debug, Copy, Clone)]
+struct Bucket {
+    /// Amount of packets that can currently be sent.
+    tokens: f64,
+    /// When the bucket was last refilled.
+    updated: Instant,
+}
+
+/// Limits the rate at which each IP address is allowed to send packets.
+///
+/// Every address receives a bucket that holds at most `burst` tokens and is refilled with `rate` tokens per second.
+/// Each packet consumes a single token, packets that arrive while the bucket is empty are rejected.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Tokens added per second.
+    rate: f64,
+    /// Capacity of each bucket.
+    burst: f64,
+    /// Buckets of all addresses that have recently sent packets.
+    buckets: DashMap<IpAddr, Bucket>,
+    /// When the table may be swept again.
+    next_prune: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Creates a new rate limiter.
+    ///
+    /// A rate of 0 disables rate limiting entirely.
+    pub fn new(rate: u32, burst: u32) -> RateLimiter {
+        RateLimiter {
+            rate: f64::from(rate),
+            burst: f64::from(burst.max(1)),
+            buckets: DashMap::new(),
+            next_prune: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Whether rate limiting is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.rate > 0.0
+    }
+
+    /// Consumes a token from the bucket of the given address.
+    ///
+    /// Returns `false` and increments [`RATE_LIMITED_METRIC`] if the address has exceeded its limit.
+    pub fn allow(&self, address: IpAddr) -> bool {
+        if !self.is_enabled() {
+            return true
+        }
+
+        let now = Instant::now();
+        if self.buckets.len() >= MAX_TRACKED_ADDRESSES && !self.buckets.contains_key(&address) {
+            self.prune_if_due(now);
+
+            if self.buckets.len() >= MAX_TRACKED_ADDRESSES {
+                RATE_LIMITED_METRIC.inc();
+                return false
+            }
+        }
+
+        let mut bucket = self.buckets.entry(address).or_insert(Bucket { tokens: self.burst, updated: now });
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = elapsed.mul_add(self.rate, bucket.tokens).min(self.burst);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        drop(bucket);
+
+        if !allowed {
+            RATE_LIMITED_METRIC.inc();
+        }
+
+        allowed
+    }
+
+    /// Amount of addresses that are currently tracked.
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Whether no addresses are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Sweeps the table unless it has already been swept within the last [`PRUNE_INTERVAL`].
+    fn prune_if_due(&self, now: Instant) {
+        // Another thread that is sweeping the table right now has the same effect.
+        let Some(mut next_prune) = self.next_prune.try_lock() else { return };
+        if now < *next_prune {
+            return
+        }
+
+        *next_prune = now + PRUNE_INTERVAL;
+        drop(next_prune);
+
+        self.prune();
+    }
+
+    /// Forgets all addresses whose buckets have completely refilled.
+    ///
+    /// These addresses have not sent anything for a while and would receive a full bucket anyway.
+    pub fn prune(&self) {
+        let now = Instant::now();
+        self.buckets.retain(|_, bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            elapsed.mul_add(self.rate, bucket.tokens) < self.burst
+        });
+    }
+}
@@ -1,17 +1,15 @@
 
 use std::sync::atomic::Ordering;
-use std::time::{Instant, Duration};
+use std::time::Instant;
 
 use async_recursion::async_recursion;
 use proto::bedrock::CONNECTED_PACKET_ID;
-use proto::raknet::{Ack, ConnectedPing, ConnectionRequest, DisconnectNotification, Nak, NewIncomingConnection};
-use util::{RVec, Deserialize};
+use proto::raknet::{Ack, ConnectedPing, ConnectedPong, ConnectionRequest, ConnectionRequestAccepted, DisconnectNotification, Nak, NewIncomingConnection};
+use util::RVec;
 
-use tokio::sync::mpsc::error::SendTimeoutError;
+use tokio::sync::mpsc::error::TrySendError;
 
-use crate::{Frame, FrameBatch, RakNetCommand, RakNetClient};
-
-const RAKNET_OUTPUT_TIMEOUT: Duration = Duration::from_millis(10);
+use crate::{CloseReason, Direction, Frame, FrameBatch, LimitExceeded, RakNetCommand, RakNetClient, WindowInsert, LIMIT_DISCONNECTS_METRIC};
 
 impl RakNetClient {
     /// Processes the raw packet coming directly from the network.
@@ -22,7 +20,7 @@ impl RakNetClient {
         skip_all,
         name = "RaknetUser::handle_raw_packet",
         fields(
-            address = %self.address
+            address = %self.address()
         )
     )]
     pub async fn handle_raw_packet(&self, packet: RVec) -> anyhow::Result<bool> {
@@ -31,12 +29,26 @@ impl RakNetClient {
         let Some(pk_id) = packet.first().copied() else {
             tracing::warn!("Received raw packet is empty");
             anyhow::bail!("Raw packet is empty");
-        };  
+        };
+        self.counters.record_received(packet.len());
+        if let Some(capture) = &self.capture {
+            capture.record_datagram(Direction::Inbound, self.address(), &packet);
+        }
+
+        let result = match pk_id {
+            Ack::ID => self.handle_ack(packet.as_ref()),
+            Nak::ID => self.handle_nak(packet.as_ref()).await,
+            _ => self.handle_frame_batch(packet).await,
+        };
+
+        if let Err(err) = result {
+            if err.is::<LimitExceeded>() {
+                LIMIT_DISCONNECTS_METRIC.inc();
+                tracing::warn!("Client exceeded a protocol limit, disconnecting them: {err:#}");
+                self.disconnect();
+            }
 
-        match pk_id {
-            Ack::ID => self.handle_ack(packet.as_ref())?,
-            Nak::ID => self.handle_nak(packet.as_ref()).await?,
-            _ => self.handle_frame_batch(packet).await?,
+            return Err(err)
         }
 
         Ok(true)
@@ -50,12 +62,32 @@ impl RakNetClient {
     /// * Discarding old sequenced frames
     /// * Acknowledging reliable raknet
     async fn handle_frame_batch(&self, packet: RVec) -> anyhow::Result<()> {
-        let batch = FrameBatch::deserialize(packet.as_ref())?;
+        let batch = FrameBatch::deserialize_limited(packet.as_ref(), self.max_batch_frames)?;
         // self
         //     .batch_number
         //     .fetch_max(batch.sequence_number, Ordering::SeqCst);
 
         for frame in batch.frames {
+            // The channel is checked before anything is buffered for the frame.
+            if frame.reliability.is_ordered() || frame.reliability.is_sequenced() {
+                self.order.validate(frame.order_channel)?;
+            }
+
+            if frame.reliability.is_reliable() {
+                let insert = self.reliable_window.insert(frame.reliable_index);
+                if !insert.is_new() {
+                    // The client did not receive the acknowledgement, so it has to be sent again.
+                    self.acknowledged.lock().push(batch.sequence_number);
+                    self.counters.record_duplicate();
+                    if insert == WindowInsert::Behind {
+                        tracing::trace!("Discarding reliable frame {} from behind the window", frame.reliable_index);
+                    } else {
+                        tracing::trace!("Discarding duplicate reliable frame {}", frame.reliable_index);
+                    }
+                    continue;
+                }
+            }
+
             self.handle_frame(frame, batch.sequence_number).await?;
         }
 
@@ -68,16 +100,6 @@ impl RakNetClient {
         frame: Frame,
         batch_number: u32,
     ) -> anyhow::Result<()> {
-        if frame.reliability.is_sequenced()
-            && frame.sequence_index
-            < self.batch_number.load(Ordering::SeqCst)
-        {
-            tracing::warn!("Received old packet. Discarding it");
-
-            // Discard packet
-            return Ok(());
-        }
-
         if frame.reliability.is_reliable() {
             // Confirm packet
             let mut lock = self.acknowledged.lock();
@@ -95,15 +117,22 @@ impl RakNetClient {
             };
         }
 
-        // Sequenced implies ordered
-        if frame.reliability.is_ordered() || frame.reliability.is_sequenced() {
+        if frame.reliability.is_sequenced() {
+            // Only the newest sequenced frame matters, older ones are discarded instead of being waited for.
+            if !self.order.get(frame.order_channel)?.accept_sequenced(&frame) {
+                tracing::trace!("Discarding stale sequenced frame {} on channel {}", frame.sequence_index, frame.order_channel);
+                return Ok(());
+            }
+
+            return self.handle_frame_body(frame.body);
+        }
+
+        if frame.reliability.is_ordered() {
             // Add packet to order queue
-            if let Ok(ready) = self.order[frame.order_channel as usize]
-                .insert(frame)
-            {
+            if let Ok(ready) = self.order.get(frame.order_channel)?.insert(frame) {
                 if let Some(ready) = ready {
                     for packet in ready {
-                        self.handle_frame_body(packet.body).await?;
+                        self.handle_frame_body(packet.body)?;
                     }
                 }
             } else {
@@ -114,11 +143,11 @@ impl RakNetClient {
             return Ok(());
         }
 
-        self.handle_frame_body(frame.body).await
+        self.handle_frame_body(frame.body)
     }
 
     /// Processes an unencapsulated game packet.
-    async fn handle_frame_body(&self, packet: RVec) -> anyhow::Result<()> {
+    fn handle_frame_body(&self, packet: RVec) -> anyhow::Result<()> {
         let Some(packet_id) = packet.first().copied() else {
             tracing::warn!("Received packet is empty");
             anyhow::bail!("Packet was empty");
@@ -127,23 +156,29 @@ impl RakNetClient {
         match packet_id {
             // CONNECTED_PACKET_ID => self.handle_encrypted_frame(packet).await?,
             CONNECTED_PACKET_ID => {
-                if let Err(err) = self.output.send_timeout(RakNetCommand::Received(packet), RAKNET_OUTPUT_TIMEOUT).await {
-                    if matches!(err, SendTimeoutError::Closed(_)) {
+                // The queue is large enough to absorb stalls of the game layer, a full queue means that it is not keeping up.
+                if let Err(err) = self.output.try_send(RakNetCommand::Received(packet)) {
+                    if matches!(err, TrySendError::Closed(_)) {
                         // Output channel has been closed
                         tracing::warn!("RakNet layer output channel closed, disconnecting them...");
                     } else {
-                        // Forward timeout
-                        tracing::warn!("Client seems to be hanging server side, disconnecting them...")
+                        tracing::warn!("Client is not keeping up with its packets, disconnecting them...")
                     }
                     self.disconnect();
                 }
             },
-            DisconnectNotification::ID => self.active.cancel(),
+            DisconnectNotification::ID => {
+                self.set_close_reason(CloseReason::Peer);
+                self.peer_closed.store(true, Ordering::Release);
+                self.active.cancel();
+            }
             ConnectionRequest::ID => self.handle_connection_request(packet)?,
+            ConnectionRequestAccepted::ID => self.handle_connection_request_accepted(packet)?,
             NewIncomingConnection::ID => {
                 self.handle_new_incoming_connection(packet)?
             }
             ConnectedPing::ID => self.handle_connected_ping(packet)?,
+            ConnectedPong::ID => self.handle_connected_pong(packet)?,
             id => anyhow::bail!("Invalid Raknet packet ID: {}", id),
         }
 
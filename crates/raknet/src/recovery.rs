@@ -1,16 +1,36 @@
+use std::time::{Duration, Instant};
+
 use dashmap::DashMap;
 use proto::raknet::AckEntry;
 
-use crate::FrameBatch;
+use crate::{FrameBatch, FramePool};
+
+/// Upper bound of the retransmission timeout after backing off.
+const MAX_BACKOFF: Duration = Duration::from_secs(4);
+/// Amount of times a batch is retransmitted before the client is considered unreachable.
+pub const MAX_RETRANSMISSIONS: u32 = 10;
+
+/// A batch that has been sent but not acknowledged yet.
+#[derive(Debug)]
+struct PendingBatch {
+    batch: FrameBatch,
+    /// When the batch was last sent.
+    sent: Instant,
+    /// Amount of times the frames in this batch have been retransmitted.
+    attempts: u32,
+}
 
 /// Holds previously sent raknet to be able to recover them when packet loss occurs.
 ///
 /// This data structures keeps track of all raknet that have been sent by the server.
+/// Only the reliable frames of a batch are stored, unreliable frames are not worth recovering.
 /// When the client sends an ACK, the specified raknet are remove from the queue.
 /// If a NAK is received, the specified raknet can be recovered from the queue.
+/// Batches that are neither acknowledged nor NAKed within the retransmission timeout
+/// are resent as well, waiting twice as long after every attempt.
 #[derive(Default, Debug)]
 pub struct Recovery {
-    frames: DashMap<u32, FrameBatch>,
+    frames: DashMap<u32, PendingBatch>,
 }
 
 impl Recovery {
@@ -21,24 +41,44 @@ impl Recovery {
 
     /// Inserts a frame batch into the queue.
     ///
-    /// The frame batch will stay in the queue until it is acknowledged.
+    /// The unreliable frames are dropped from the batch, the reliable frames stay in the queue
+    /// until the batch is acknowledged.
     #[inline]
     pub fn insert(&self, batch: FrameBatch) {
-        self.frames.insert(batch.sequence_number, batch);
+        self.insert_attempt(batch, 0);
+    }
+
+    /// Inserts a frame batch whose frames have already been retransmitted `attempts` times.
+    #[inline]
+    pub fn insert_attempt(&self, mut batch: FrameBatch, attempts: u32) {
+        batch.frames.retain(|frame| frame.reliability.is_reliable());
+        self.frames.insert(batch.sequence_number, PendingBatch { batch, sent: Instant::now(), attempts });
+    }
+
+    /// Whether every batch that has been sent has also been acknowledged.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
     }
 
     /// Removes the specified raknet from the recovery queue.
     ///
     /// This method should be called when an ACK is received.
-    pub fn acknowledge(&self, records: &[AckEntry]) {
+    /// The frame lists of the acknowledged batches are returned to the pool.
+    pub fn acknowledge(&self, records: &[AckEntry], pool: &FramePool) {
         for record in records {
             match record {
                 AckEntry::Single(id) => {
-                    self.frames.remove(id);
+                    if let Some((_, pending)) = self.frames.remove(id) {
+                        pool.recycle_batch(pending.batch);
+                    }
                 }
+                // RakNet ranges include their upper bound.
                 AckEntry::Range(range) => {
-                    for id in range.clone() {
-                        self.frames.remove(&id);
+                    for id in range.start..=range.end {
+                        if let Some((_, pending)) = self.frames.remove(&id) {
+                            pool.recycle_batch(pending.batch);
+                        }
                     }
                 }
             }
@@ -48,24 +88,26 @@ impl Recovery {
     /// Recovers the specified raknet from the recovery queue.
     ///
     /// This method should be called when a NAK is received.
+    /// The returned batches only contain the reliable frames that were originally sent in them,
+    /// together with the amount of times these frames have already been retransmitted.
     #[tracing::instrument(
         skip(self),
         name = "Recovery::recover"
     )]
-    pub fn recover(&self, records: &[AckEntry]) -> Vec<FrameBatch> {
+    pub fn recover(&self, records: &[AckEntry]) -> Vec<(FrameBatch, u32)> {
         let mut recovered = Vec::new();
         for record in records {
             match record {
                 AckEntry::Single(id) => {
                     if let Some(frame) = self.frames.remove(id) {
-                        recovered.push(frame.1);
+                        recovered.push((frame.1.batch, frame.1.attempts));
                     }
                 }
                 AckEntry::Range(range) => {
-                    recovered.reserve(range.len());
-                    for id in range.clone() {
+                    recovered.reserve(range.len() + 1);
+                    for id in range.start..=range.end {
                         if let Some(frame) = self.frames.remove(&id) {
-                            recovered.push(frame.1);
+                            recovered.push((frame.1.batch, frame.1.attempts));
                         }
                     }
                 }
@@ -78,4 +120,33 @@ impl Recovery {
 
         recovered
     }
+
+    /// Removes all batches that have not been acknowledged in time.
+    ///
+    /// A batch times out after `rto * 2^attempts`, capped at [`MAX_BACKOFF`].
+    /// The batches are returned in the order they were originally sent, together with their
+    /// amount of previous attempts. They should be resent and reinserted using [`insert_attempt`](Self::insert_attempt).
+    pub fn take_timed_out(&self, rto: Duration) -> Vec<(FrameBatch, u32)> {
+        let now = Instant::now();
+        let timed_out = self
+            .frames
+            .iter()
+            .filter(|pending| now.duration_since(pending.sent) > backoff(rto, pending.attempts))
+            .map(|pending| *pending.key())
+            .collect::<Vec<_>>();
+
+        let mut batches = timed_out
+            .into_iter()
+            .filter_map(|id| self.frames.remove(&id))
+            .map(|(_, pending)| (pending.batch, pending.attempts))
+            .collect::<Vec<_>>();
+
+        batches.sort_unstable_by_key(|(batch, _)| batch.sequence_number);
+        batches
+    }
+}
+
+/// Returns the timeout of a batch that has already been retransmitted `attempts` times.
+fn backoff(rto: Duration, attempts: u32) -> Duration {
+    rto.saturating_mul(1 << attempts.min(16)).min(MAX_BACKOFF)
 }
@@ -0,0 +1,76 @@
+use std::io;
+use std::net::SocketAddr;
+
+use tokio::net::UdpSocket;
+
+/// Binds `count` UDP sockets to the same address.
+///
+/// On Linux the sockets are created with `SO_REUSEPORT`, which makes the kernel distribute incoming datagrams
+/// over the sockets by hashing the source address. Every client is therefore always received by the same socket,
+/// allowing each socket to be served by its own receiver task. If the port of `address` is 0, all sockets share
+/// the port that was assigned to the first one.
+///
+/// Other platforms only support binding a single socket.
+pub async fn bind_sharded(address: SocketAddr, count: usize) -> io::Result<Vec<UdpSocket>> {
+    if count <= 1 {
+        return Ok(vec![UdpSocket::bind(address).await?])
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let first = bind_reuse_port(address)?;
+        let address = first.local_addr()?;
+
+        let mut sockets = Vec::with_capacity(count);
+        sockets.push(first);
+        for _ in 1..count {
+            sockets.push(bind_reuse_port(address)?);
+        }
+
+        Ok(sockets)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Socket sharding is only supported on Linux"))
+}
+
+/// Binds a single socket with `SO_REUSEPORT` enabled.
+#[cfg(target_os = "linux")]
+fn bind_reuse_port(address: SocketAddr) -> io::Result<UdpSocket> {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    let domain = if address.is_ipv4() { libc::AF_INET } else { libc::AF_INET6 };
+
+    // SAFETY: `socket` has no memory safety requirements, the returned descriptor is checked before use.
+    let fd = unsafe { libc::socket(domain, libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error())
+    }
+
+    // SAFETY: `fd` is a freshly created descriptor that is not owned by anything else.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let enable: libc::c_int = 1;
+    // SAFETY: The option value points to a `c_int` that lives for the duration of the call and its size is passed along.
+    let result = unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            std::ptr::addr_of!(enable).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error())
+    }
+
+    let (storage, len) = crate::to_sockaddr(address);
+    // SAFETY: `storage` holds a valid socket address of length `len`.
+    let result = unsafe { libc::bind(fd.as_raw_fd(), std::ptr::addr_of!(storage).cast(), len) };
+    if result < 0 {
+        return Err(io::Error::last_os_error())
+    }
+
+    UdpSocket::from_std(std::net::UdpSocket::from(fd))
+}
@@ -1,11 +1,11 @@
 use std::sync::atomic::Ordering;
 
 use async_recursion::async_recursion;
-use proto::raknet::{Ack, AckEntry};
+use proto::raknet::Ack;
 
 use util::{RVec, Serialize};
 
-use crate::{SendPriority, RakNetClient, Reliability, Frame, FrameBatch};
+use crate::{Direction, SendPriority, RakNetClient, Reliability, Frame, FrameBatch};
 
 /// Specifies the reliability and priority of a packet.
 pub struct SendConfig {
@@ -13,12 +13,19 @@ pub struct SendConfig {
     pub reliability: Reliability,
     /// Priority specifies if this packet has sending priority over other raknet.
     pub priority: SendPriority,
+    /// Order channel that ordered and sequenced packets are sent on.
+    ///
+    /// Packets on different channels are ordered independently, which prevents a lost packet from
+    /// delaying unrelated packets. This must be below the order channel count of the connection.
+    /// In case encryption is enabled, all encrypted packets must be sent on the same channel.
+    pub channel: u8,
 }
 
 /// A default packet config that can be used for all raknet.
 pub const DEFAULT_SEND_CONFIG: SendConfig = SendConfig {
     reliability: Reliability::ReliableOrdered,
     priority: SendPriority::Medium,
+    channel: 0,
 };
 
 impl RakNetClient {
@@ -38,65 +45,90 @@ impl RakNetClient {
         config: SendConfig,
     ) where B: Into<RVec> {
         let buffer = buffer.into();
-        self.send.insert_raw(
-            config.priority,
-            Frame::new(config.reliability, buffer),
-        );
+
+        let mut frame = Frame::new(config.reliability, buffer);
+        if (config.channel as usize) < self.order.count() {
+            frame.order_channel = config.channel;
+        } else {
+            tracing::error!("Attempted to send packet on invalid order channel {}, using channel 0 instead", config.channel);
+        }
+
+        self.send.insert_raw(config.priority, frame);
     }
 
     /// Flushes the send queue.
+    ///
+    /// The amount of data sent is limited by the congestion window and the pacer. This budget is divided
+    /// over the priorities according to their [weights](crate::SendWeights). Frames that do not fit
+    /// in the window stay queued until the client has acknowledged earlier batches.
     pub async fn flush(&self) -> anyhow::Result<()> {
         let tick = self.tick.load(Ordering::SeqCst);
+        let mut budget = self.congestion.available().min(self.pacer.available());
 
-        if let Some(frames) = self.send.flush(SendPriority::High) {
-            self.send_raw_frames(frames).await?;
-        }
+        let mut frames = self.pool.take();
+        self.send.flush_weighted(&mut budget, &mut frames);
+        self.send_flushed(frames).await?;
 
-        if tick % 2 == 0 {
-            // Also flush broadcast raknet.
-            if let Some(frames) =
-                self.send.flush(SendPriority::Medium)
-            {
-                self.send_raw_frames(frames).await?;
-            }
+        // Send acknowledgements
+        if tick % self.ack_interval == 0 {
+            self.flush_acknowledgements()?;
         }
 
-        if tick % 4 == 0 {
-            if let Some(frames) =
-                self.send.flush(SendPriority::Low)
-            {
-                self.send_raw_frames(frames).await?;
-            }
-        }
+        Ok(())
+    }
 
-        // Send acknowledgements
-        if tick % 4 == 0 {
-            self.flush_acknowledgements().await?;
+    /// Immediately sends the high priority frames.
+    ///
+    /// This is called as soon as high priority frames are queued, so that latency-sensitive packets such as
+    /// movement do not have to wait for the next tick. Frames that do not fit in the congestion window or pacer
+    /// budget are sent by a later flush.
+    pub async fn flush_urgent(&self) -> anyhow::Result<()> {
+        let mut budget = self.congestion.available().min(self.pacer.available());
+
+        self.flush_limited(SendPriority::High, &mut budget).await?;
+        self.submit_datagrams().await
+    }
+
+    /// Sends as many frames from the given queue as the budget allows.
+    async fn flush_limited(&self, priority: SendPriority, budget: &mut usize) -> anyhow::Result<()> {
+        let mut frames = self.pool.take();
+        self.send.flush_limited(priority, budget, &mut frames);
+        self.send_flushed(frames).await
+    }
+
+    /// Sends frames that were taken from the send queues and charges them to the pacer.
+    async fn send_flushed(&self, frames: Vec<Frame>) -> anyhow::Result<()> {
+        if frames.is_empty() {
+            self.pool.recycle(frames);
+        } else {
+            self.pacer.consume(frames.iter().map(|f| f.body.len()).sum());
+            self.send_raw_frames(frames).await?;
         }
 
         Ok(())
     }
 
     /// Flushes both the frames and acknowledgements.
+    ///
+    /// This ignores the congestion window and should only be used when the connection is closing.
     pub async fn flush_all(&self) -> anyhow::Result<()> {
-        if let Some(frames) = self.send.flush(SendPriority::High) {
-            self.send_raw_frames(frames).await?;
-        }
-
-        if let Some(frames) = self.send.flush(SendPriority::Medium)
-        {
-            self.send_raw_frames(frames).await?;
-        }
+        for priority in SendPriority::ALL {
+            let mut frames = self.pool.take();
+            self.send.flush(priority, &mut frames);
 
-        if let Some(frames) = self.send.flush(SendPriority::Low) {
-            self.send_raw_frames(frames).await?;
+            if frames.is_empty() {
+                self.pool.recycle(frames);
+            } else {
+                self.send_raw_frames(frames).await?;
+            }
         }
 
-        self.flush_acknowledgements().await
+        self.flush_acknowledgements()?;
+        self.submit_datagrams().await
     }
 
     /// Flushes all of the pending acknowledgements.
-    pub async fn flush_acknowledgements(&self) -> anyhow::Result<()> {
+    pub fn flush_acknowledgements(&self) -> anyhow::Result<()> {
         let mut confirmed = {
             let mut lock = self.acknowledged.lock();
             if lock.is_empty() {
@@ -110,37 +142,37 @@ impl RakNetClient {
 
             confirmed
         };
-        confirmed.dedup();
-        confirmed.sort_unstable();
-
-        let mut records = Vec::new();
-        let mut consecutive = Vec::new();
-        for (index, id) in confirmed.iter().enumerate() {
-            let is_last = index == confirmed.len() - 1;
-
-            // Is range
-            if !is_last && id + 1 == confirmed[index + 1] {
-                consecutive.push(*id);
-            } else if consecutive.is_empty() {
-                records.push(AckEntry::Single(*id));
-            } else {
-                records.push(AckEntry::Range(consecutive[0]..*id));
-                consecutive.clear();
-            }
-        }
+        let records = crate::coalesce_acknowledgements(&mut confirmed);
 
         let ack = Ack { records };
         let mut serialized = RVec::alloc_with_capacity(ack.serialized_size());
         ack.serialize_into(&mut serialized)?;
 
-        self
-            .socket
-            .send_to(serialized.as_ref(), self.address)
-            .await?;
-
+        self.send_datagram(serialized.as_ref());
+        self.counters.record_ack_sent();
         Ok(())
     }
 
+    /// Queues a raw datagram for the client.
+    ///
+    /// The datagram is sent together with all other datagrams of this tick by [`submit_datagrams`](Self::submit_datagrams).
+    pub fn send_datagram(&self, datagram: &[u8]) {
+        self.counters.record_sent(datagram.len());
+        if let Some(capture) = &self.capture {
+            capture.record_datagram(Direction::Outbound, self.address(), datagram);
+        }
+        self.outgoing.push(self.address(), datagram);
+    }
+
+    /// Sends all queued datagrams in as few system calls as possible.
+    ///
+    /// Datagrams are always sent over the socket that the connection was established on,
+    /// which is the IPv6 socket for clients that connected over IPv6. The batch is shared with
+    /// the other connections on that socket, so their queued datagrams are sent as well.
+    pub async fn submit_datagrams(&self) -> anyhow::Result<()> {
+        self.outgoing.submit(&self.socket).await
+    }
+
     /// Send a list of frames. 
     ///
     /// These frames are not guaranteed to be sent in the same frame batch.
@@ -151,9 +183,12 @@ impl RakNetClient {
     ///
     /// In case the passed frames are already fragmented, there should at maximum one compound
     /// in the entire list.
+    ///
+    /// The list and the batches that are not kept for recovery are returned to the [`FramePool`](crate::FramePool).
+    #[allow(clippy::iter_with_drain)] // The emptied list is returned to the pool.
     #[async_recursion]
     async fn send_raw_frames(&self, mut frames: Vec<Frame>) -> anyhow::Result<()> {
-        let mut serialized = Vec::new();
+        let mut serialized = self.pool.take_buffer();
 
         // Process fragments first to prevent sequence number duplication.
         let mut index = 0;
@@ -180,71 +215,81 @@ impl RakNetClient {
 
         let mut batch = FrameBatch {
             sequence_number: 0,
-            frames: vec![],
+            frames: self.pool.take(),
         };
 
         let mut has_reliable_packet = false;    
 
         // Set to u32::MAX when unset, otherwise set to the compound's order index
         let mut compound_order_index = u32::MAX;
+        let mut compound_sequence_index = 0;
 
-        for mut frame in frames {
+        for mut frame in frames.drain(..) {
             let frame_size = frame.body.len() + std::mem::size_of::<Frame>();
 
-            if frame.reliability.is_ordered() && !frame.is_compound {
-                let order_index = self.order[frame.order_channel as usize]
-                    .alloc_index();
+            if frame.reliability.is_sequenced() {
+                // All fragments of a compound share the same indices.
+                if frame.is_compound && compound_order_index != u32::MAX {
+                    frame.order_index = compound_order_index;
+                    frame.sequence_index = compound_sequence_index;
+                } else {
+                    let (order_index, sequence_index) = self.order.get(frame.order_channel)?.alloc_sequence();
+                    frame.order_index = order_index;
+                    frame.sequence_index = sequence_index;
+
+                    if frame.is_compound {
+                        compound_order_index = order_index;
+                        compound_sequence_index = sequence_index;
+                    }
+                }
+            } else if frame.reliability.is_ordered() && !frame.is_compound {
+                let order_index = self.order.get(frame.order_channel)?.alloc_index();
 
                 frame.order_index = order_index;
             } else if frame.reliability.is_ordered() {
                 if compound_order_index == u32::MAX {
-                    let new_index = self.order[0].alloc_index();
+                    let new_index = self.order.get(frame.order_channel)?.alloc_index();
                     compound_order_index = new_index;
                 }
 
-                frame.order_channel = 0;
                 frame.order_index = compound_order_index;
             }
 
-            if frame.reliability.is_sequenced() {
-                let sequence_index =
-                    self.sequence_index.fetch_add(1, Ordering::SeqCst);
-
-                frame.sequence_index = sequence_index;
-            }
-
-            if frame.reliability.is_reliable() {
+            let is_reliable = frame.reliability.is_reliable();
+            if is_reliable {
                 frame.reliable_index =
                     self.acknowledge_index.fetch_add(1, Ordering::SeqCst);
-
-                has_reliable_packet = true;
             }
 
             #[allow(clippy::unwrap_used)]
             if batch.size_hint().unwrap() + frame_size <= self.mtu as usize {
                 batch.frames.push(frame);
+                has_reliable_packet |= is_reliable;
             } else if !batch.is_empty() {
                 serialized.clear();
 
                 batch.sequence_number = self.batch_number.fetch_add(1, Ordering::SeqCst);
                 batch.serialize_into(&mut serialized)?;
 
-                // TODO: Add IPv6 support
-                self.socket
-                    .send_to(serialized.as_ref(), self.address)
-                    .await?;
+                self.send_datagram(serialized.as_ref());
 
                 if has_reliable_packet {
+                    self.congestion.on_send(batch.sequence_number, serialized.len());
                     self.recovery.insert(batch);
+                } else {
+                    self.pool.recycle_batch(batch);
                 }
 
-                has_reliable_packet = false;
+                has_reliable_packet = is_reliable;
+
+                let mut frames = self.pool.take();
+                frames.push(frame);
                 batch = FrameBatch {
                     sequence_number: self
                         .batch_number
                         .fetch_add(0, Ordering::SeqCst),
 
-                    frames: vec![frame],
+                    frames,
                 };
             }
         }
@@ -256,18 +301,20 @@ impl RakNetClient {
             batch.sequence_number = self.batch_number.fetch_add(1, Ordering::SeqCst);
             batch.serialize_into(&mut serialized)?;
 
+            self.send_datagram(serialized.as_ref());
+
             if has_reliable_packet {
+                self.congestion.on_send(batch.sequence_number, serialized.len());
                 self.recovery.insert(batch);
+            } else {
+                self.pool.recycle_batch(batch);
             }
-
-            // TODO: Add IPv6 support
-            self.socket
-                .send_to(serialized.as_ref(), self.address)
-                .await?;
+        } else {
+            self.pool.recycle_batch(batch);
         }
-        // } else {
-        //     self.batch_number.fetch_sub(1, Ordering::SeqCst);
-        // }
+
+        self.pool.recycle(frames);
+        self.pool.recycle_buffer(serialized);
 
         Ok(())
     }
@@ -280,7 +327,8 @@ impl RakNetClient {
             - std::mem::size_of::<FrameBatch>();
 
         let compound_size = frame.body.len().div_ceil(chunk_max_size);
-        let mut compound = Vec::with_capacity(compound_size);
+        let mut compound = self.pool.take();
+        compound.reserve(compound_size);
         let chunks = frame.body.chunks(chunk_max_size);
 
         debug_assert_eq!(chunks.len(), compound_size, "Chunk count does not match compound size");
@@ -295,6 +343,7 @@ impl RakNetClient {
                 compound_index: i as u32,
                 compound_size: compound_size as u32,
                 compound_id,
+                order_channel: frame.order_channel,
                 body: RVec::alloc_from_slice(chunk),                
                 ..Default::default()
             };
@@ -2,38 +2,94 @@ use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use parking_lot::Mutex;
+use tokio::sync::Notify;
 
 use crate::Frame;
 
+/// Amount of bytes that a queue may send per round for every unit of weight.
+const WEIGHT_QUANTUM: usize = 512;
+
 /// Priority of the packet.
 /// This affects when they're sent.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SendPriority {
-    /// High priority is flushed every session tick.
+    /// High priority is sent as soon as possible, without waiting for the next session tick.
     High,
-    /// Medium priority is flushed every two session ticks.
+    /// Medium priority is flushed every session tick, sharing the budget with the other priorities.
     Medium,
-    /// Low priority is flushed every four session ticks.
+    /// Low priority is flushed every session tick, sharing the budget with the other priorities.
     Low,
 }
 
+impl SendPriority {
+    /// All priorities, in the order that they are served.
+    pub const ALL: [SendPriority; 3] = [SendPriority::High, SendPriority::Medium, SendPriority::Low];
+}
+
+/// Relative share of the send budget that each priority receives while the connection is congested.
+///
+/// With the default weights, high priority frames get four times and medium priority frames twice the bandwidth
+/// of low priority frames. Every priority always makes progress, a weight of 0 is treated as 1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SendWeights {
+    /// Weight of [`SendPriority::High`].
+    pub high: u32,
+    /// Weight of [`SendPriority::Medium`].
+    pub medium: u32,
+    /// Weight of [`SendPriority::Low`].
+    pub low: u32,
+}
+
+impl SendWeights {
+    /// Weight of the given priority.
+    #[inline]
+    pub const fn of(&self, priority: SendPriority) -> u32 {
+        match priority {
+            SendPriority::High => self.high,
+            SendPriority::Medium => self.medium,
+            SendPriority::Low => self.low,
+        }
+    }
+}
+
+impl Default for SendWeights {
+    fn default() -> Self {
+        Self { high: 4, medium: 2, low: 1 }
+    }
+}
+
+/// State of the deficit round robin scheduler that divides the budget over the queues.
+#[derive(Debug, Default)]
+struct Deficits {
+    /// Amount of bytes that each queue may still send in its current turn.
+    bytes: [usize; 3],
+    /// Queue whose turn it is.
+    current: usize,
+    /// Whether the current queue has already received its quantum for this turn.
+    credited: bool,
+}
+
 /// Contains three LIFO deques that each have a different priority assigned to them.
 /// The priority of a given frame determines which queue it enters and how the
 /// server prioritizes sending it.
 #[derive(Default, Debug)]
 pub struct SendQueues {
     /// Queue for high priority frames.
-    /// Flushed every session tick.
+    /// Flushed as soon as frames are inserted and at least every session tick.
     high_priority: Mutex<VecDeque<Frame>>,
     /// Queue for medium priority frames.
-    /// Flushed every 2 session ticks.
     medium_priority: Mutex<VecDeque<Frame>>,
     /// Queue for low priority frames.
-    /// Flushed every 4 session ticks.
     low_priority: Mutex<VecDeque<Frame>>,
+    /// Share of the budget that each queue receives.
+    weights: SendWeights,
+    /// Keeps track of how much each queue may send.
+    deficits: Mutex<Deficits>,
     /// It is faster to update a boolean on each read/write and check that,
     /// than to lock each of the three queues to check if they are empty.
     is_empty: AtomicBool,
+    /// Notified when a high priority frame is inserted.
+    urgent: Notify,
 }
 
 impl SendQueues {
@@ -43,6 +99,12 @@ impl SendQueues {
         SendQueues::default()
     }
 
+    /// Creates a new send queue that divides the budget according to the given weights.
+    #[inline]
+    pub fn with_weights(weights: SendWeights) -> SendQueues {
+        SendQueues { weights, ..SendQueues::default() }
+    }
+
     /// Whether all three priority queues are completely empty.
     #[inline]
     pub fn is_empty(&self) -> bool {
@@ -60,14 +122,19 @@ impl SendQueues {
         empty
     }
 
+    /// Total amount of frames in all three priority queues.
+    pub fn len(&self) -> usize {
+        self.high_priority.lock().len() + self.medium_priority.lock().len() + self.low_priority.lock().len()
+    }
+
     /// Inserts a new packet into the send queue.
     pub fn insert_raw(&self, priority: SendPriority, frame: Frame) {
         self.is_empty.store(false, Ordering::SeqCst);
 
         match priority {
             SendPriority::High => {
-                let mut lock = self.high_priority.lock();
-                lock.push_back(frame);
+                self.high_priority.lock().push_back(frame);
+                self.urgent.notify_one();
             }
             SendPriority::Medium => {
                 let mut lock = self.medium_priority.lock();
@@ -80,46 +147,121 @@ impl SendQueues {
         }
     }
 
-    /// Flushes the specified queue.
-    pub fn flush(&self, priority: SendPriority) -> Option<Vec<Frame>> {
-        // FIXME: This function can potentially return a reference instead of moving the frames
-        // to reduce allocations.
+    /// Waits until a high priority frame has been inserted.
+    ///
+    /// Inserting multiple frames before the waiter runs only wakes it once, so frames that are sent in quick
+    /// succession are still batched together.
+    pub async fn urgent(&self) {
+        self.urgent.notified().await;
+    }
 
-        // self.is_empty.store(true, Ordering::SeqCst);
-        match priority {
-            SendPriority::High => {
-                let is_empty = self.low_priority.lock().is_empty() && self.medium_priority.lock().is_empty();
-                self.is_empty.store(is_empty, Ordering::SeqCst);
-
-                let mut lock = self.high_priority.lock();
-                if lock.is_empty() {
-                    None
-                } else {
-                    Some(lock.drain(0..).collect::<Vec<_>>())
-                }
+    /// Moves frames from the front of the specified queue into `frames` until `budget` bytes have been taken.
+    ///
+    /// The amount of bytes taken is subtracted from the budget. If the budget is not yet exhausted,
+    /// at least one frame is taken even if it is larger than the remaining budget.
+    /// Frames that do not fit remain in the queue for the next flush.
+    pub fn flush_limited(&self, priority: SendPriority, budget: &mut usize, frames: &mut Vec<Frame>) {
+        let mut lock = self.queue(priority).lock();
+        while *budget > 0 {
+            let Some(frame) = lock.pop_front() else { break };
+
+            *budget = budget.saturating_sub(frame.body.len());
+            frames.push(frame);
+        }
+        drop(lock);
+
+        self.update_empty();
+    }
+
+    /// Moves frames from all queues into `frames` until `budget` bytes have been taken, using weighted fair queuing.
+    ///
+    /// Queues take turns in a deficit round robin. Every turn, a queue may send [`WEIGHT_QUANTUM`] bytes for
+    /// every unit of its weight, unused allowance carries over to its next turn as long as the queue has frames
+    /// waiting. This gives every priority a share of the budget that is proportional to its weight, so lower
+    /// priorities cannot be starved by a busy higher priority queue.
+    ///
+    /// If the budget runs out during a turn, that turn continues in the next flush.
+    pub fn flush_weighted(&self, budget: &mut usize, frames: &mut Vec<Frame>) {
+        let mut deficits = self.deficits.lock();
+        let mut idle = 0;
+
+        while *budget > 0 && idle < SendPriority::ALL.len() {
+            let current = deficits.current;
+            let priority = SendPriority::ALL[current];
+            let mut queue = self.queue(priority).lock();
+
+            if queue.is_empty() {
+                drop(queue);
+                deficits.bytes[current] = 0;
+                deficits.current = (current + 1) % SendPriority::ALL.len();
+                deficits.credited = false;
+
+                idle += 1;
+                continue
             }
-            SendPriority::Medium => {
-                let is_empty = self.low_priority.lock().is_empty() && self.medium_priority.lock().is_empty();
-                self.is_empty.store(is_empty, Ordering::SeqCst);
+            idle = 0;
 
-                let mut lock = self.medium_priority.lock();
-                if lock.is_empty() {
-                    None
-                } else {
-                    Some(lock.drain(0..).collect::<Vec<_>>())
-                }
+            if !deficits.credited {
+                deficits.bytes[current] += WEIGHT_QUANTUM * self.weights.of(priority).max(1) as usize;
+                deficits.credited = true;
             }
-            SendPriority::Low => {
-                let is_empty = self.low_priority.lock().is_empty() && self.medium_priority.lock().is_empty();
-                self.is_empty.store(is_empty, Ordering::SeqCst);
 
-                let mut lock = self.low_priority.lock();
-                if lock.is_empty() {
-                    None
-                } else {
-                    Some(lock.drain(0..).collect::<Vec<_>>())
+            while *budget > 0 {
+                let Some(size) = queue.front().map(|frame| frame.body.len()) else { break };
+                if size > deficits.bytes[current] {
+                    break
                 }
+
+                let Some(frame) = queue.pop_front() else { break };
+                deficits.bytes[current] -= size;
+                *budget = budget.saturating_sub(size);
+                frames.push(frame);
+            }
+
+            let interrupted = queue.front().is_some_and(|frame| frame.body.len() <= deficits.bytes[current]);
+            let drained = queue.is_empty();
+            drop(queue);
+
+            if interrupted {
+                // The budget ran out, continue this turn in the next flush.
+                break
+            }
+
+            if drained {
+                deficits.bytes[current] = 0;
             }
+            deficits.current = (current + 1) % SendPriority::ALL.len();
+            deficits.credited = false;
+        }
+        drop(deficits);
+
+        self.update_empty();
+    }
+
+    /// Moves all frames of the specified queue into `frames`.
+    ///
+    /// The list is usually taken from the [`FramePool`](crate::FramePool) of the session, so that flushing
+    /// does not allocate.
+    pub fn flush(&self, priority: SendPriority, frames: &mut Vec<Frame>) {
+        frames.extend(self.queue(priority).lock().drain(..));
+        self.update_empty();
+    }
+
+    /// Returns the queue of the given priority.
+    #[inline]
+    const fn queue(&self, priority: SendPriority) -> &Mutex<VecDeque<Frame>> {
+        match priority {
+            SendPriority::High => &self.high_priority,
+            SendPriority::Medium => &self.medium_priority,
+            SendPriority::Low => &self.low_priority,
         }
     }
-}
\ No newline at end of file
+
+    /// Recomputes whether all queues are empty.
+    fn update_empty(&self) {
+        let is_empty = self.high_priority.lock().is_empty()
+            && self.medium_priority.lock().is_empty()
+            && self.low_priority.lock().is_empty();
+        self.is_empty.store(is_empty, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,189 @@
+use std::io;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::{
+    net::{Ipv6Addr, SocketAddrV6},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+#[cfg(unix)]
+use dashmap::DashMap;
+#[cfg(unix)]
+use tokio::net::UnixDatagram;
+use tokio::net::UdpSocket;
+
+/// Socket that RakNet datagrams are sent and received on.
+#[derive(Debug)]
+pub enum Socket {
+    /// UDP socket, used for connections over the network.
+    Udp(UdpSocket),
+    /// Unix domain datagram socket, used by proxies running on the same host.
+    #[cfg(unix)]
+    Local(LocalSocket),
+}
+
+impl Socket {
+    /// Sends a single datagram to the given address.
+    pub async fn send_to(&self, buf: &[u8], address: SocketAddr) -> io::Result<usize> {
+        match self {
+            Self::Udp(socket) => socket.send_to(buf, address).await,
+            #[cfg(unix)]
+            Self::Local(socket) => socket.send_to(buf, address).await,
+        }
+    }
+
+    /// Waits for a single datagram and returns its length and source address.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        match self {
+            Self::Udp(socket) => socket.recv_from(buf).await,
+            #[cfg(unix)]
+            Self::Local(socket) => socket.recv_from(buf).await,
+        }
+    }
+
+    /// Receives a single datagram if one is available, without waiting.
+    pub fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        match self {
+            Self::Udp(socket) => socket.try_recv_from(buf),
+            #[cfg(unix)]
+            Self::Local(socket) => socket.try_recv_from(buf),
+        }
+    }
+
+    /// Address that the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            Self::Udp(socket) => socket.local_addr(),
+            #[cfg(unix)]
+            Self::Local(_) => Ok(LocalSocket::LOCAL_ADDRESS),
+        }
+    }
+}
+
+impl From<UdpSocket> for Socket {
+    fn from(socket: UdpSocket) -> Socket {
+        Socket::Udp(socket)
+    }
+}
+
+#[cfg(unix)]
+impl From<LocalSocket> for Socket {
+    fn from(socket: LocalSocket) -> Socket {
+        Socket::Local(socket)
+    }
+}
+
+/// Local transport over a Unix domain datagram socket.
+///
+/// When a proxy runs on the same host as the server, sending every datagram through the loopback interface
+/// only adds overhead and requires a free UDP port for every instance. This transport exchanges datagrams over
+/// a socket file instead.
+///
+/// The rest of RakNet identifies connections by their [`SocketAddr`], so every peer path is assigned a
+/// synthetic address from the `fd00::/8` range the first time it is seen. Peers must bind their own socket to a
+/// path, replies cannot be delivered to unbound sockets.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct LocalSocket {
+    socket: UnixDatagram,
+    /// Path that the socket was bound to, removed again when the socket is dropped.
+    path: PathBuf,
+    /// Paths of all peers indexed by their synthetic address.
+    peers: DashMap<SocketAddr, PathBuf>,
+    /// Synthetic addresses of all peers indexed by their path.
+    addresses: DashMap<PathBuf, SocketAddr>,
+    /// Identifier of the next peer.
+    next_peer: AtomicU32,
+}
+
+#[cfg(unix)]
+impl LocalSocket {
+    /// Address reported as the local address of every local socket.
+    pub const LOCAL_ADDRESS: SocketAddr = SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0), 0, 0, 0));
+
+    /// Binds a socket to the given path.
+    ///
+    /// A socket file left behind by a previous run is removed first.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<LocalSocket> {
+        let path = path.as_ref();
+        match std::fs::remove_file(path) {
+            Ok(()) => (),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+
+        Ok(LocalSocket {
+            socket: UnixDatagram::bind(path)?,
+            path: path.to_owned(),
+            peers: DashMap::new(),
+            addresses: DashMap::new(),
+            next_peer: AtomicU32::new(1),
+        })
+    }
+
+    /// Path that the socket is bound to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the synthetic address of the peer bound to the given path, assigning one if it is new.
+    pub fn peer_address<P: AsRef<Path>>(&self, path: P) -> SocketAddr {
+        let path = path.as_ref();
+        if let Some(address) = self.addresses.get(path) {
+            return *address
+        }
+
+        *self.addresses.entry(path.to_owned()).or_insert_with(|| {
+            let id = self.next_peer.fetch_add(1, Ordering::Relaxed);
+            let address = SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, (id >> 16) as u16, id as u16),
+                0,
+                0,
+                0,
+            ));
+
+            self.peers.insert(address, path.to_owned());
+            address
+        })
+    }
+
+    /// Sends a single datagram to the peer with the given synthetic address.
+    pub async fn send_to(&self, buf: &[u8], address: SocketAddr) -> io::Result<usize> {
+        let path = self
+            .peers
+            .get(&address)
+            .map(|p| p.clone())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No local peer with address {address}")))?;
+
+        self.socket.send_to(buf, path).await
+    }
+
+    /// Waits for a single datagram and returns its length and the synthetic address of the sender.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (n, from) = self.socket.recv_from(buf).await?;
+        Ok((n, self.resolve(&from)?))
+    }
+
+    /// Receives a single datagram if one is available, without waiting.
+    pub fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (n, from) = self.socket.try_recv_from(buf)?;
+        Ok((n, self.resolve(&from)?))
+    }
+
+    /// Converts the address of a sender to its synthetic address.
+    fn resolve(&self, from: &tokio::net::unix::SocketAddr) -> io::Result<SocketAddr> {
+        from.as_pathname()
+            .map(|path| self.peer_address(path))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Received datagram from an unbound local socket"))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for LocalSocket {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            tracing::warn!("Unable to remove local socket {}: {err}", self.path.display());
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::ops::{Add, AddAssign};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Network counters of a single connection.
+///
+/// The counters are updated by the connection itself, use [`RakNetClient::stats`](crate::RakNetClient::stats)
+/// to take a consistent snapshot.
+#[derive(Debug, Default)]
+pub struct SessionCounters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    datagrams_in: AtomicU64,
+    datagrams_out: AtomicU64,
+    retransmissions: AtomicU64,
+    duplicates: AtomicU64,
+    acks_in: AtomicU64,
+    acks_out: AtomicU64,
+    naks_in: AtomicU64,
+}
+
+impl SessionCounters {
+    /// Records a datagram that was received.
+    #[inline]
+    pub fn record_received(&self, bytes: usize) {
+        self.datagrams_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records a datagram that was sent.
+    #[inline]
+    pub fn record_sent(&self, bytes: usize) {
+        self.datagrams_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records a frame batch that was sent again.
+    #[inline]
+    pub fn record_retransmission(&self) {
+        self.retransmissions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a reliable frame that was received more than once.
+    #[inline]
+    pub fn record_duplicate(&self) {
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records an acknowledgement that was received.
+    #[inline]
+    pub fn record_ack_received(&self) {
+        self.acks_in.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records an acknowledgement that was sent.
+    #[inline]
+    pub fn record_ack_sent(&self) {
+        self.acks_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a negative acknowledgement that was received.
+    #[inline]
+    pub fn record_nak_received(&self) {
+        self.naks_in.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Takes a snapshot of the counters.
+    pub fn snapshot(&self, send_queue_depth: usize, order_queue_depth: usize) -> SessionStats {
+        SessionStats {
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            datagrams_in: self.datagrams_in.load(Ordering::Relaxed),
+            datagrams_out: self.datagrams_out.load(Ordering::Relaxed),
+            retransmissions: self.retransmissions.load(Ordering::Relaxed),
+            duplicates: self.duplicates.load(Ordering::Relaxed),
+            acks_in: self.acks_in.load(Ordering::Relaxed),
+            acks_out: self.acks_out.load(Ordering::Relaxed),
+            naks_in: self.naks_in.load(Ordering::Relaxed),
+            send_queue_depth,
+            order_queue_depth,
+        }
+    }
+}
+
+/// Snapshot of the network statistics of one or more connections.
+///
+/// Snapshots of multiple connections can be summed to get the totals of the entire server.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    /// Total size of all received datagrams.
+    pub bytes_in: u64,
+    /// Total size of all sent datagrams, including retransmissions and acknowledgements.
+    pub bytes_out: u64,
+    /// Amount of datagrams received.
+    pub datagrams_in: u64,
+    /// Amount of datagrams sent.
+    pub datagrams_out: u64,
+    /// Amount of frame batches that had to be sent again because they were lost.
+    pub retransmissions: u64,
+    /// Amount of reliable frames that were received more than once.
+    pub duplicates: u64,
+    /// Amount of acknowledgements received.
+    pub acks_in: u64,
+    /// Amount of acknowledgements sent.
+    pub acks_out: u64,
+    /// Amount of negative acknowledgements received.
+    pub naks_in: u64,
+    /// Amount of frames waiting in the send queues.
+    pub send_queue_depth: usize,
+    /// Amount of received frames waiting in the order channels for earlier frames.
+    pub order_queue_depth: usize,
+}
+
+impl Add for SessionStats {
+    type Output = SessionStats;
+
+    fn add(mut self, rhs: SessionStats) -> SessionStats {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for SessionStats {
+    fn add_assign(&mut self, rhs: SessionStats) {
+        self.bytes_in += rhs.bytes_in;
+        self.bytes_out += rhs.bytes_out;
+        self.datagrams_in += rhs.datagrams_in;
+        self.datagrams_out += rhs.datagrams_out;
+        self.retransmissions += rhs.retransmissions;
+        self.duplicates += rhs.duplicates;
+        self.acks_in += rhs.acks_in;
+        self.acks_out += rhs.acks_out;
+        self.naks_in += rhs.naks_in;
+        self.send_queue_depth += rhs.send_queue_depth;
+        self.order_queue_depth += rhs.order_queue_depth;
+    }
+}
+
+impl std::iter::Sum for SessionStats {
+    fn sum<I: Iterator<Item = SessionStats>>(iter: I) -> SessionStats {
+        iter.fold(SessionStats::default(), Add::add)
+    }
+}
@@ -1,20 +1,1244 @@
+#![allow(clippy::unwrap_used)]
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use proto::bedrock::{PermissionLevel, CONNECTED_PACKET_ID};
+use proto::types::Dimension;
+use proto::raknet::{Ack, ConnectedPing, ConnectedPong, Nak, OpenConnectionReply1, OpenConnectionReply2, OpenConnectionRequest1, OpenConnectionRequest2};
+use tokio::net::UdpSocket;
+use tokio::sync::broadcast;
+use util::corpus::check_corpus;
+use util::{Deserialize, RVec, Serialize, Vector};
+
+use crate::{
+    accept_datagram, coalesce_acknowledgements, connect, forward_channel, BackpressurePolicy, Forwarded, CloseReason, RecipientInfo, Recipients, ClockModel, CompoundLimits, Compounds, CongestionControl, ConnectOptions, Connection, LatencyTracker, DatagramBatch, Frame, FrameBatch,
+    HandshakeCookies, LimitExceeded, ListenOptions, Listener, MtuDiscovery, OfflineHandshake, OrderChannel, OrderChannels, Pacer, RakNetClient, RakNetCommand, RakNetCreateDescription, RateLimiter, ReceiveBatch, Reliability, SendPriority, SendQueues, SendWeights, SessionCounters, SessionStats, Socket, WindowInsert,
+    ReliableWindow, RttEstimator, DEDUP_WINDOW_SIZE, DEFAULT_ACK_INTERVAL, DEFAULT_SEND_CONFIG, DEFAULT_KEEPALIVE_INTERVAL, DEFAULT_MAX_BATCH_FRAMES, DEFAULT_SESSION_TIMEOUT, EMPTY_DATAGRAMS_METRIC, MAX_MTU, MAX_ORDER_CHANNELS, MAX_RETRANSMISSIONS, MAX_TRACKED_ADDRESSES, MIN_MTU, OUTPUT_QUEUE_SIZE,
+};
+
+/// Description of a session with the default settings, tests override the fields they exercise.
+fn client_description(address: SocketAddr, socket: Arc<Socket>) -> RakNetCreateDescription {
+    RakNetCreateDescription {
+        address,
+        mtu: MIN_MTU,
+        guid: 1,
+        socket,
+        outgoing: Arc::default(),
+        compound_limits: CompoundLimits::default(),
+        max_batch_frames: DEFAULT_MAX_BATCH_FRAMES,
+        order_channels: MAX_ORDER_CHANNELS,
+        ack_interval: DEFAULT_ACK_INTERVAL,
+        send_rate: 0,
+        send_weights: SendWeights::default(),
+        session_timeout: DEFAULT_SESSION_TIMEOUT,
+        keepalive_interval: DEFAULT_KEEPALIVE_INTERVAL,
+        capture: None,
+    }
+}
+
 #[test]
 fn order_channel() {
-    let mut channel = OrderChannel::new();
+    let channel = OrderChannel::new();
 
-    let mut test_frame = Frame::default();
-    test_frame.order_index = 0;
-    assert!(channel.insert(test_frame).is_some());
+    let test_frame = Frame { order_index: 0, ..Default::default() };
+    assert!(channel.insert(test_frame).unwrap().is_some());
 
-    let mut test_frame = Frame::default();
-    test_frame.order_index = 2;
-    assert!(channel.insert(test_frame).is_none());
+    let test_frame = Frame { order_index: 2, ..Default::default() };
+    assert!(channel.insert(test_frame).unwrap().is_none());
 
-    let mut test_frame = Frame::default();
-    test_frame.order_index = 1;
-    let output = channel.insert(test_frame).unwrap();
+    let test_frame = Frame { order_index: 1, ..Default::default() };
+    let output = channel.insert(test_frame).unwrap().unwrap();
 
     assert_eq!(output.len(), 2);
     assert_eq!(output[0].order_index, 1);
     assert_eq!(output[1].order_index, 2);
-}
\ No newline at end of file
+}
+
+#[test]
+fn order_channels() {
+    let channels = OrderChannels::new();
+    assert_eq!(channels.allocated(), 0);
+
+    // Channels are independent ordering domains.
+    assert_eq!(channels.get(3).unwrap().alloc_index(), 0);
+    assert_eq!(channels.get(3).unwrap().alloc_index(), 1);
+    assert_eq!(channels.get(0).unwrap().alloc_index(), 0);
+    assert_eq!(channels.allocated(), 2);
+
+    assert!(channels.get(MAX_ORDER_CHANNELS as u8 - 1).is_ok());
+    assert!(channels.get(MAX_ORDER_CHANNELS as u8).is_err());
+
+    // Connections can be limited to fewer channels.
+    let channels = OrderChannels::with_count(4);
+    assert!(channels.validate(3).is_ok());
+    assert!(channels.validate(4).is_err());
+    assert!(channels.get(4).is_err());
+    assert_eq!(OrderChannels::with_count(0).count(), 1);
+}
+
+#[test]
+fn reliable_dedup() {
+    let window = ReliableWindow::new();
+
+    assert_eq!(window.insert(1), WindowInsert::New);
+    assert_eq!(window.insert(0), WindowInsert::New);
+
+    // Both indices have been received, so the window has moved past them.
+    assert_eq!(window.insert(1), WindowInsert::Behind);
+    assert_eq!(window.insert(0), WindowInsert::Behind);
+
+    // Indices after a missing one stay in the window until the gap is filled.
+    assert_eq!(window.insert(3), WindowInsert::New);
+    assert_eq!(window.insert(3), WindowInsert::Duplicate);
+    assert_eq!(window.insert(2), WindowInsert::New);
+    assert_eq!(window.insert(3), WindowInsert::Behind);
+
+    // Frames far ahead move the window past older frames, even if they were never received.
+    let ahead = 5 + DEDUP_WINDOW_SIZE as u32;
+    assert_eq!(window.insert(ahead), WindowInsert::New);
+    assert_eq!(window.insert(ahead), WindowInsert::Duplicate);
+    assert_eq!(window.insert(4), WindowInsert::Behind);
+    assert_eq!(window.insert(ahead - 1), WindowInsert::New);
+
+    // Reliable indices wrap around at 24 bits.
+    let window = ReliableWindow::new();
+    for index in (0..=0x00ff_ffff).step_by(1024) {
+        assert!(window.insert(index).is_new());
+    }
+    assert_eq!(window.insert(0), WindowInsert::New);
+    assert_eq!(window.insert(0x00ff_fc00), WindowInsert::Duplicate);
+}
+
+#[test]
+fn mtu_negotiation() {
+    let discovery = MtuDiscovery::new(1400);
+    let v4 = "127.0.0.1:19132".parse().unwrap();
+    let v6 = "[::1]:19132".parse().unwrap();
+
+    // Probes are clamped to the interface MTU, taking the larger IPv6 header into account.
+    assert_eq!(discovery.on_probe(v4, 1492), 1400);
+    assert_eq!(discovery.on_probe(v6, 1492), 1380);
+
+    // The client cannot request more than the largest probe that arrived.
+    discovery.on_probe(v4, 1200);
+    assert_eq!(discovery.negotiate(v4, 1200).unwrap(), 1200);
+    assert_eq!(discovery.negotiate(v6, 1492).unwrap(), 1380);
+
+    assert!(discovery.negotiate(v4, MIN_MTU - 1).is_err());
+}
+
+#[test]
+fn unconnected_rate_limit() {
+    let limiter = RateLimiter::new(1, 3);
+    let first = "127.0.0.1".parse().unwrap();
+    let second = "::1".parse().unwrap();
+
+    assert!((0..3).all(|_| limiter.allow(first)));
+    assert!(!limiter.allow(first));
+
+    // Limits are tracked per address.
+    assert!(limiter.allow(second));
+
+    let disabled = RateLimiter::new(0, 0);
+    assert!((0..100).all(|_| disabled.allow(first)));
+}
+
+#[test]
+fn rate_limit_table() {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    let limiter = RateLimiter::new(1, 2);
+    let address = |index: usize| IpAddr::V4(Ipv4Addr::from(0x0a00_0000 + index as u32));
+
+    // None of these buckets refill during the test, so sweeping the table cannot make room.
+    assert!((0..MAX_TRACKED_ADDRESSES).all(|index| limiter.allow(address(index))));
+    assert_eq!(limiter.len(), MAX_TRACKED_ADDRESSES);
+
+    // New addresses are rejected while the table is full, known addresses keep their own limit.
+    assert!(!limiter.allow(address(MAX_TRACKED_ADDRESSES)));
+    assert!(!limiter.allow(address(MAX_TRACKED_ADDRESSES + 1)));
+    assert!(limiter.allow(address(0)));
+    assert!(!limiter.allow(address(0)));
+    assert_eq!(limiter.len(), MAX_TRACKED_ADDRESSES);
+}
+
+#[test]
+fn handshake_cookies() {
+    let cookies = HandshakeCookies::new();
+    let client = "127.0.0.1:50000".parse().unwrap();
+    let spoofed = "127.0.0.1:50001".parse().unwrap();
+
+    let cookie = cookies.issue(client, 1);
+    assert!(cookies.verify(client, 1, cookie));
+    assert!(!cookies.verify(spoofed, 1, cookie));
+    assert!(!cookies.verify(client, 2, cookie));
+    assert!(!HandshakeCookies::new().verify(client, 1, cookie));
+}
+
+/// Accepts a single connection on the socket, the way the server does.
+async fn accept_one(socket: Arc<Socket>) -> Arc<RakNetClient> {
+    let discovery = MtuDiscovery::default();
+    let cookies = HandshakeCookies::new();
+    let mut buffer = vec![0; 2048];
+    let mut forward = None;
+
+    loop {
+        let (n, address) = socket.recv_from(&mut buffer).await.unwrap();
+        let packet = &buffer[..n];
+
+        let mut reply = Vec::new();
+        match packet[0] {
+            OpenConnectionRequest1::ID => {
+                let request = OpenConnectionRequest1::deserialize(packet).unwrap();
+                let mtu = discovery.on_probe(address, request.mtu);
+                let cookie = Some(cookies.issue(address, 1));
+                OpenConnectionReply1 { server_guid: 1, cookie, mtu }.serialize_into(&mut reply).unwrap();
+            }
+            OpenConnectionRequest2::ID => {
+                let request = OpenConnectionRequest2::deserialize(packet).unwrap();
+                assert!(cookies.verify(address, 1, request.cookie.unwrap()));
+                let mtu = discovery.negotiate(address, request.mtu).unwrap();
+                OpenConnectionReply2 { server_guid: 1, client_address: address, mtu }.serialize_into(&mut reply).unwrap();
+
+                let (forward_tx, forward_rx) = forward_channel(16, BackpressurePolicy::default());
+                let description = RakNetCreateDescription { mtu, guid: request.client_guid, ..client_description(address, Arc::clone(&socket)) };
+                let (client, _) = RakNetClient::new(description, broadcast::channel(1).0, forward_rx);
+                forward = Some((client, forward_tx));
+            }
+            _ => {
+                let (client, forward_tx) = forward.as_ref().unwrap();
+                assert_eq!(forward_tx.send(RVec::alloc_from_slice(packet)).await, Forwarded::Queued);
+
+                if client.connected.is_cancelled() {
+                    return Arc::clone(client);
+                }
+                continue;
+            }
+        }
+
+        socket.send_to(&reply, address).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn connector_handshake() {
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let address = socket.local_addr().unwrap();
+    let server = tokio::spawn(accept_one(socket));
+
+    let connection = connect(address, ConnectOptions::default()).await.unwrap();
+    assert!(connection.client.connected.is_cancelled());
+    assert!(connection.client.mtu >= MIN_MTU);
+
+    let server_client = tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap();
+    tokio::time::timeout(Duration::from_secs(5), server_client.connected.cancelled()).await.unwrap();
+    assert_eq!(server_client.mtu, connection.client.mtu);
+}
+
+#[tokio::test]
+async fn listener() {
+    let mut listener = Listener::bind("127.0.0.1:0".parse().unwrap(), ListenOptions::default()).await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let mut client = Connection::from(connect(address, ConnectOptions::default()).await.unwrap());
+    let mut server = tokio::time::timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    assert_eq!(server.guid(), client.client().guid);
+    assert_eq!(listener.len(), 1);
+
+    client.send(b"ping");
+    let message = tokio::time::timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap();
+    assert_eq!(message.as_slice(), b"ping");
+
+    server.send(b"pong");
+    let message = tokio::time::timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap();
+    assert_eq!(message.as_slice(), b"pong");
+
+    client.close();
+    assert!(tokio::time::timeout(Duration::from_secs(5), server.recv()).await.unwrap().is_none());
+    assert_eq!(server.close_reason(), Some(CloseReason::Peer));
+}
+
+#[test]
+fn offline_handshake() {
+    use proto::raknet::{IncompatibleProtocol, RAKNET_VERSION};
+
+    let handshake = OfflineHandshake::new(&ListenOptions { guid: 7, ..ListenOptions::default() });
+    let address = "127.0.0.1:19132".parse().unwrap();
+    let never_open = |_, _| -> anyhow::Result<u16> { panic!("session opened before the handshake completed") };
+
+    // Clients using another RakNet version are told which version to use.
+    let request = OpenConnectionRequest1 { protocol_version: RAKNET_VERSION + 1, mtu: MIN_MTU }.serialize().unwrap();
+    let reply = handshake.handle(request.as_ref(), address, "", never_open).unwrap();
+    assert_eq!(IncompatibleProtocol::deserialize(reply.as_slice()).unwrap().protocol_version, RAKNET_VERSION);
+
+    let request = OpenConnectionRequest1 { protocol_version: RAKNET_VERSION, mtu: MIN_MTU }.serialize().unwrap();
+    let reply = handshake.handle(request.as_ref(), address, "", never_open).unwrap();
+    let reply = OpenConnectionReply1::deserialize(reply.as_slice()).unwrap();
+    assert_eq!(reply.server_guid, 7);
+    let Some(cookie) = reply.cookie else { panic!("handshake cookie was not issued") };
+
+    // A session is only opened once the client has echoed its cookie.
+    let request = |cookie| OpenConnectionRequest2 { cookie, server_address: address, mtu: MIN_MTU, client_guid: 3 }.serialize().unwrap();
+    assert!(handshake.handle(request(None).as_ref(), address, "", never_open).is_err());
+    assert!(handshake.handle(request(Some(cookie.wrapping_add(1))).as_ref(), address, "", never_open).is_err());
+
+    let mut opened = None;
+    let reply = handshake
+        .handle(request(Some(cookie)).as_ref(), address, "", |guid, mtu| {
+            opened = Some(guid);
+            handshake.negotiate_mtu(address, mtu)
+        })
+        .unwrap();
+    assert_eq!(opened, Some(3));
+    assert_eq!(OpenConnectionReply2::deserialize(reply.as_slice()).unwrap().mtu, MIN_MTU);
+
+    assert!(handshake.handle(&[0x42], address, "", never_open).is_err());
+}
+
+#[tokio::test]
+async fn output_queue() {
+    let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = RakNetCreateDescription { keepalive_interval: Duration::ZERO, ..client_description(peer.local_addr().unwrap(), socket) };
+    let (_forward, forward_rx) = forward_channel(1, BackpressurePolicy::default());
+    let (client, mut receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_rx);
+
+    let packet = |sequence_number: usize| {
+        let frames = vec![Frame::new(Reliability::Unreliable, RVec::alloc_from_slice(&[CONNECTED_PACKET_ID]))];
+        let mut buffer = Vec::new();
+        FrameBatch { sequence_number: sequence_number as u32, frames }.serialize_into(&mut buffer).unwrap();
+        RVec::alloc_from_slice(&buffer)
+    };
+
+    // Packets wait in the queue while the game layer is busy.
+    for sequence_number in 0..OUTPUT_QUEUE_SIZE {
+        client.handle_raw_packet(packet(sequence_number)).await.unwrap();
+    }
+    assert!(!client.active.is_cancelled(), "client was disconnected before the queue was full");
+
+    // The game layer is not keeping up once the queue is full.
+    client.handle_raw_packet(packet(OUTPUT_QUEUE_SIZE)).await.unwrap();
+    assert!(client.active.is_cancelled(), "client was not disconnected");
+    assert!(matches!(receiver.try_recv(), Ok(RakNetCommand::Received(_))));
+}
+
+#[tokio::test]
+async fn empty_datagrams() {
+    let address = "127.0.0.1:19132".parse().unwrap();
+
+    let discarded = EMPTY_DATAGRAMS_METRIC.get();
+    assert!(!accept_datagram(&[], address));
+    assert!(!accept_datagram(&[0; 32], address));
+    assert!(accept_datagram(&[0, 0, 0, 1], address));
+    assert_eq!(EMPTY_DATAGRAMS_METRIC.get(), discarded + 2);
+
+    // Nothing past the receiver may assume that buffers are non-empty.
+    assert!(FrameBatch::deserialize(&[][..]).is_err());
+    assert!(Ack::deserialize(&[][..]).is_err());
+    assert!(Nak::deserialize(&[][..]).is_err());
+
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = client_description(address, socket);
+    let (client, _receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_channel(1, BackpressurePolicy::default()).1);
+
+    assert!(client.handle_raw_packet(RVec::alloc()).await.is_err());
+    assert!(client.handle_raw_packet(RVec::alloc_from_slice(&[Ack::ID])).await.is_err());
+
+    // A frame without a body.
+    let batch = FrameBatch { sequence_number: 0, frames: vec![Frame::default()] };
+    let mut serialized = RVec::alloc();
+    batch.serialize_into(&mut serialized).unwrap();
+    assert!(client.handle_raw_packet(serialized).await.is_err());
+
+    client.active.cancel();
+}
+
+#[test]
+fn batch_frame_limit() {
+    let frame = || Frame::new(Reliability::Unreliable, RVec::alloc_from_slice(&[1]));
+    let batch = FrameBatch { sequence_number: 0, frames: (0..4).map(|_| frame()).collect() };
+    let serialized = batch.serialize().unwrap();
+
+    assert_eq!(FrameBatch::deserialize_limited(serialized.as_ref(), 4).unwrap().frames.len(), 4);
+    assert!(FrameBatch::deserialize_limited(serialized.as_ref(), 3).unwrap_err().is::<LimitExceeded>());
+}
+
+#[test]
+fn compound_limits() {
+    let fragment = |compound_id, compound_index, len| Frame {
+        is_compound: true,
+        compound_id,
+        compound_size: 2,
+        compound_index,
+        body: RVec::alloc_from_slice(&vec![1; len]),
+        ..Default::default()
+    };
+
+    let limits = CompoundLimits { max_compounds: 2, max_bytes: 100, max_fragments: 2, max_compound_bytes: 50, timeout: Duration::ZERO };
+    let compounds = Compounds::with_limits(limits);
+    assert!(compounds.insert(fragment(0, 0, 10)).unwrap().is_none());
+    assert!(compounds.insert(fragment(1, 0, 10)).unwrap().is_none());
+    assert_eq!(compounds.buffered_bytes(), 20);
+
+    // Too many incomplete compounds.
+    assert!(compounds.insert(fragment(2, 0, 10)).is_err());
+    // Too many buffered bytes.
+    assert!(compounds.insert(fragment(1, 1, 100)).unwrap_err().is::<LimitExceeded>());
+    // Too many bytes in a single compound.
+    assert!(compounds.insert(fragment(1, 1, 45)).unwrap_err().is::<LimitExceeded>());
+    // Too many fragments.
+    assert!(compounds.insert(Frame { compound_size: 3, ..fragment(0, 2, 1) }).unwrap_err().is::<LimitExceeded>());
+
+    let completed = compounds.insert(fragment(0, 1, 10)).unwrap().unwrap();
+    assert_eq!(completed.body.len(), 20);
+    assert_eq!(compounds.buffered_bytes(), 10);
+
+    std::thread::sleep(Duration::from_millis(1));
+    assert_eq!(compounds.expire(), 1);
+    assert!(compounds.is_empty());
+    assert_eq!(compounds.buffered_bytes(), 0);
+
+    // Batches with too many frames are rejected before they are processed.
+    let batch = FrameBatch { sequence_number: 0, frames: (0..3).map(|_| Frame::default()).collect() }.serialize().unwrap();
+    assert_eq!(FrameBatch::deserialize_limited(batch.as_ref(), 3).unwrap().frames.len(), 3);
+    assert!(FrameBatch::deserialize_limited(batch.as_ref(), 2).unwrap_err().is::<LimitExceeded>());
+}
+
+#[test]
+fn corpus_frame_batch() {
+    check_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/frame_batch"), |input| FrameBatch::deserialize(input).map(|_| ()));
+}
+
+#[test]
+fn corpus_compound() {
+    check_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/compound"), |input| {
+        let compounds = Compounds::new();
+        for frame in FrameBatch::deserialize(input)?.frames {
+            compounds.insert(frame)?;
+        }
+
+        Ok(())
+    });
+}
+
+#[test]
+fn clock_model() {
+    let clock = ClockModel::new();
+    assert!(!clock.is_synchronised());
+    assert_eq!(clock.to_local(1000), 1000);
+
+    // The remote clock is 5 seconds ahead and gains 100 ms every 1000 seconds.
+    let remote = |local: i64| local + 5000 + local / 10_000;
+    for i in 0..20 {
+        let sent = i * 10_000;
+        let received = sent + 40;
+        clock.record(sent, remote(sent + 20), received);
+    }
+
+    assert!(clock.is_synchronised());
+    assert!((clock.drift() - 0.0001).abs() < 0.00001);
+    assert!((clock.offset(300_000) - 5030).abs() <= 2);
+    assert!((clock.to_local(remote(300_000)) - 300_000).abs() <= 2);
+    assert!((clock.to_remote(300_000) - remote(300_000)).abs() <= 2);
+
+    // Samples with implausible round trips are ignored.
+    let before = clock.offset(0);
+    clock.record(0, 1_000_000, 60_000);
+    assert_eq!(clock.offset(0), before);
+}
+
+#[test]
+fn sequenced_frames() {
+    let channel = OrderChannel::new();
+    let sequenced = |order_index, sequence_index| Frame {
+        reliability: Reliability::UnreliableSequenced,
+        order_index,
+        sequence_index,
+        ..Default::default()
+    };
+
+    assert!(channel.accept_sequenced(&sequenced(0, 5)));
+    // Frames that arrive late are stale, gaps are skipped rather than waited for.
+    assert!(!channel.accept_sequenced(&sequenced(0, 3)));
+    assert!(!channel.accept_sequenced(&sequenced(0, 5)));
+    assert!(channel.accept_sequenced(&sequenced(0, 9)));
+
+    // A newer order index takes precedence over the sequence index.
+    assert!(channel.accept_sequenced(&sequenced(1, 0)));
+    assert!(!channel.accept_sequenced(&sequenced(0, 10)));
+
+    // Indices wrap around after 24 bits.
+    let wrapping = OrderChannel::new();
+    assert!(wrapping.accept_sequenced(&sequenced(0, 0x00ff_ffff)));
+    assert!(wrapping.accept_sequenced(&sequenced(0, 1)));
+    assert!(!wrapping.accept_sequenced(&sequenced(0, 0x00ff_fffe)));
+
+    // Sequenced frames carry the index of the next ordered frame without taking it up.
+    assert_eq!(channel.alloc_index(), 0);
+    assert_eq!(channel.alloc_sequence(), (1, 0));
+    assert_eq!(channel.alloc_sequence(), (1, 1));
+    assert_eq!(channel.alloc_index(), 1);
+}
+
+#[test]
+fn ack_coalescing() {
+    use proto::raknet::AckEntry;
+
+    let mut ids = vec![7, 3, 4, 3, 10, 5, 9, 12];
+    assert_eq!(
+        coalesce_acknowledgements(&mut ids),
+        [AckEntry::Range(3..5), AckEntry::Single(7), AckEntry::Range(9..10), AckEntry::Single(12)]
+    );
+
+    assert_eq!(coalesce_acknowledgements(&mut vec![1]), [AckEntry::Single(1)]);
+    assert!(coalesce_acknowledgements(&mut Vec::new()).is_empty());
+}
+
+#[test]
+fn congestion_sequence_wraparound() {
+    let congestion = CongestionControl::new(1000);
+
+    // Moves the highest sent sequence number up to the end of the 24-bit range.
+    for sequence in [0x40_0000, 0x80_0000, 0xc0_0000] {
+        congestion.on_send(sequence, 1000);
+        congestion.on_ack(sequence);
+    }
+    assert_eq!(congestion.window(), 7000);
+
+    for sequence in [0xff_fffe, 0xff_ffff, 0, 1] {
+        congestion.on_send(sequence, 1000);
+    }
+    assert_eq!(congestion.bytes_in_flight(), 4000);
+
+    // Batches sent after the wrap belong to the same congestion event as the batch before it.
+    congestion.on_loss(0xff_ffff);
+    assert_eq!(congestion.window(), 3500);
+    congestion.on_loss(0);
+    congestion.on_loss(1);
+    assert_eq!(congestion.window(), 3500);
+
+    // Acknowledgements may use the unmasked sequence number.
+    assert!(congestion.on_ack(0x1ff_fffe).is_some());
+    assert_eq!(congestion.bytes_in_flight(), 0);
+
+    congestion.on_send(2, 1000);
+    congestion.on_loss(2);
+    assert_eq!(congestion.window(), 2000);
+}
+
+#[test]
+fn latency_estimator() {
+    let mut estimator = RttEstimator::new();
+    assert_eq!(estimator.smoothed(), None);
+    assert_eq!(estimator.rto(), Duration::from_secs(1));
+
+    let tracker = LatencyTracker::new();
+    assert_eq!(tracker.latency(), None);
+
+    for ms in [100, 60, 140, 100] {
+        estimator.sample(Duration::from_millis(ms));
+        tracker.record(Duration::from_millis(ms));
+    }
+
+    // The tracker and the retransmission timeout share the same estimate.
+    let stats = tracker.stats();
+    assert_eq!(tracker.latency(), estimator.smoothed());
+    assert_eq!(Some(stats.smoothed), estimator.smoothed());
+    assert_eq!(stats.variance, estimator.variance());
+    assert_eq!((stats.min, stats.max, stats.latest), (Duration::from_millis(60), Duration::from_millis(140), Duration::from_millis(100)));
+    assert_eq!(stats.samples, 4);
+
+    // Deviation is measured in both directions.
+    let mut faster = RttEstimator::new();
+    faster.sample(Duration::from_millis(100));
+    faster.sample(Duration::from_millis(20));
+    assert_eq!(faster.variance(), Duration::from_millis(57) + Duration::from_micros(500));
+    assert_eq!(faster.smoothed(), Some(Duration::from_millis(90)));
+}
+
+#[test]
+fn pacing() {
+    let disabled = Pacer::new(0);
+    disabled.consume(1 << 20);
+    assert_eq!(disabled.available(), usize::MAX);
+
+    // The bucket always holds at least one datagram.
+    let pacer = Pacer::new(1000);
+    assert_eq!(pacer.available(), MAX_MTU as usize);
+
+    pacer.consume(4 * MAX_MTU as usize);
+    assert_eq!(pacer.available(), 0);
+
+    let pacer = Pacer::new(1_000_000);
+    assert_eq!(pacer.available(), 100_000);
+    pacer.consume(100_000);
+    assert!(pacer.available() < 50_000);
+}
+
+#[tokio::test]
+async fn paced_retransmission() {
+    use proto::raknet::AckEntry;
+
+    let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = RakNetCreateDescription { send_rate: 1000, keepalive_interval: Duration::ZERO, ..client_description(peer.local_addr().unwrap(), socket) };
+    let (_forward, forward_rx) = forward_channel(1, BackpressurePolicy::default());
+    let (client, _receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_rx);
+
+    let frames = vec![Frame::new(Reliability::Reliable, RVec::alloc_from_slice(&[0; 200]))];
+    client.recovery.insert(FrameBatch { sequence_number: 0, frames });
+    client.batch_number.store(1, std::sync::atomic::Ordering::SeqCst);
+
+    // Resent data counts towards the send rate like any other data.
+    let nak = Nak { records: vec![AckEntry::Single(0)] }.serialize().unwrap();
+    client.handle_nak(nak.as_ref()).await.unwrap();
+    assert!(client.pacer.available() <= MAX_MTU as usize - 200, "retransmission was not charged to the pacer");
+}
+
+#[tokio::test]
+async fn batched_send() {
+    let sender = Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+    let first = Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+    let second = Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+
+    // Datagrams of all connections on a socket share the same batch.
+    let batch = DatagramBatch::new();
+    for i in 0..100u8 {
+        batch.push(first.local_addr().unwrap(), &[i; 8]);
+        batch.push(second.local_addr().unwrap(), &[!i; 8]);
+    }
+    assert_eq!(batch.len(), 200);
+
+    batch.submit(&sender).await.unwrap();
+    assert!(batch.is_empty());
+
+    let received = receive_datagrams(&first, 100).await;
+    for (i, datagram) in received.iter().enumerate() {
+        assert_eq!(datagram, &[i as u8; 8]);
+    }
+
+    let received = receive_datagrams(&second, 100).await;
+    for (i, datagram) in received.iter().enumerate() {
+        assert_eq!(datagram, &[!(i as u8); 8]);
+    }
+
+    // A datagram that cannot be sent does not prevent the rest of the batch from being sent.
+    let address = first.local_addr().unwrap();
+    batch.push(address, &[1; 8]);
+    batch.push(address, &vec![0; 1 << 17]);
+    batch.push(address, &[2; 8]);
+    batch.push(address, &[3; 8]);
+    assert!(batch.submit(&sender).await.is_err());
+    assert!(batch.is_empty());
+
+    assert_eq!(receive_datagrams(&first, 3).await, [[1; 8], [2; 8], [3; 8]]);
+}
+
+#[tokio::test]
+async fn receive_batch() {
+    let sender = Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+    let receiver = Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+
+    // Loopback datagrams are queued on the receiving socket as soon as they have been sent.
+    for i in 0..10u8 {
+        sender.send_to(&[i; 8], receiver.local_addr().unwrap()).await.unwrap();
+    }
+
+    // A single receive drains everything that is queued, up to the capacity of the batch.
+    let mut batch = ReceiveBatch::new(4, 16);
+    let mut next = 0;
+    for expected in [4, 4, 2] {
+        let count = tokio::time::timeout(Duration::from_secs(5), batch.recv(&receiver)).await.unwrap().unwrap();
+        assert_eq!(count, expected);
+        assert_eq!(batch.iter().count(), expected, "datagrams of the previous receive were kept");
+
+        for (datagram, from) in batch.iter() {
+            assert_eq!(from, sender.local_addr().unwrap());
+            assert_eq!(datagram, &[next; 8]);
+            next += 1;
+        }
+    }
+}
+
+/// Receives `count` datagrams from the socket, failing the test if they take too long to arrive.
+async fn receive_datagrams(socket: &Socket, count: usize) -> Vec<Vec<u8>> {
+    // Bursts are drained in batches, but every datagram arrives exactly once and in order.
+    let mut batch = ReceiveBatch::new(32, 16);
+    let mut received = Vec::new();
+    while received.len() < count {
+        let Ok(result) = tokio::time::timeout(Duration::from_secs(5), batch.recv(socket)).await else {
+            panic!("only received {} of {count} datagrams", received.len());
+        };
+        let Ok(n) = result else {
+            panic!("failed to receive datagrams: {result:?}");
+        };
+        assert!((1..=32).contains(&n));
+
+        received.extend(batch.iter().map(|(datagram, _)| datagram.to_vec()));
+    }
+
+    received
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn local_transport() {
+    use crate::{connect_local, LocalSocket};
+
+    let directory = std::env::temp_dir().join(format!("mirai-raknet-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let server_path = directory.join("server.sock");
+    let client_path = directory.join("client.sock");
+
+    let socket = Arc::new(Socket::from(LocalSocket::bind(&server_path).unwrap()));
+    let server = tokio::spawn(accept_one(socket));
+
+    let connection = connect_local(&server_path, &client_path, ConnectOptions::default()).await.unwrap();
+    assert!(connection.client.connected.is_cancelled());
+
+    let server_client = tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap();
+    assert_eq!(server_client.mtu, connection.client.mtu);
+
+    connection.client.active.cancel();
+    server_client.active.cancel();
+    drop((connection, server_client));
+    std::fs::remove_dir_all(&directory).ok();
+}
+
+#[tokio::test]
+async fn session_stats() {
+    let address = "127.0.0.1:19132".parse().unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = client_description(address, socket);
+    let (client, _receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_channel(1, BackpressurePolicy::default()).1);
+
+    let ack = Ack { records: Vec::new() };
+    let mut serialized = RVec::alloc();
+    ack.serialize_into(&mut serialized).unwrap();
+    let len = serialized.len() as u64;
+    client.handle_raw_packet(serialized).await.unwrap();
+
+    client.send_raw_buffer(vec![0xfe, 1, 2, 3]);
+    assert_eq!(client.stats().send_queue_depth, 1);
+    client.flush_all().await.unwrap();
+
+    let stats = client.stats();
+    assert_eq!((stats.datagrams_in, stats.bytes_in, stats.acks_in), (1, len, 1));
+    assert_eq!(stats.datagrams_out, 1);
+    assert_eq!(stats.send_queue_depth, 0);
+
+    let other = SessionCounters::default();
+    other.record_sent(10);
+    other.record_retransmission();
+    let total: SessionStats = [stats, other.snapshot(2, 0)].into_iter().sum();
+    assert_eq!(total.datagrams_out, 2);
+    assert_eq!(total.bytes_out, stats.bytes_out + 10);
+    assert_eq!((total.retransmissions, total.send_queue_depth), (1, 2));
+
+    client.active.cancel();
+}
+
+#[tokio::test]
+async fn session_clock() {
+    let address = "127.0.0.1:19132".parse().unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = client_description(address, socket);
+    let (client, _receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_channel(1, BackpressurePolicy::default()).1);
+    assert_eq!(client.clock_offset(), None);
+    assert_eq!(client.timestamp_age(0), None);
+
+    // The client clock is 5 seconds ahead of the server.
+    let now = client.latency.timestamp();
+    client.clock.record(now - 10, now + 5000, now + 10);
+
+    let Some(offset) = client.clock_offset() else {
+        panic!("no clock offset after a ping exchange");
+    };
+    assert!((offset - 5000).abs() <= 1, "estimated offset {offset} instead of 5000");
+
+    let Some(age) = client.timestamp_age(now + 5000 - 200) else {
+        panic!("no timestamp age after a ping exchange");
+    };
+    let elapsed = client.latency.timestamp() - now;
+    assert!((age - 200 - elapsed).abs() <= 1, "timestamp is {age} ms old instead of {}", 200 + elapsed);
+
+    let Some(age) = client.timestamp_age(client.latency.timestamp() + 5000 + 1000) else {
+        panic!("no timestamp age after a ping exchange");
+    };
+    assert!(age < -900, "timestamp from the future has age {age}");
+
+    client.active.cancel();
+}
+
+#[tokio::test]
+async fn frame_pool() {
+    use proto::raknet::AckEntry;
+
+    let address = "127.0.0.1:19132".parse().unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = client_description(address, socket);
+    let (client, _receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_channel(1, BackpressurePolicy::default()).1);
+
+    // Three frames that each need their own batch.
+    for _ in 0..3 {
+        client.send_raw_buffer(vec![0xfe; MIN_MTU as usize / 2]);
+    }
+    client.flush_all().await.unwrap();
+    assert_eq!(client.batch_number.load(std::sync::atomic::Ordering::SeqCst), 3);
+    assert_eq!(client.pool.idle_buffers(), 1);
+
+    // Acknowledged batches return their frame lists to the pool.
+    let idle = client.pool.idle_lists();
+    let mut ack = RVec::alloc();
+    Ack { records: (0..3).map(AckEntry::Single).collect() }.serialize_into(&mut ack).unwrap();
+    client.handle_raw_packet(ack).await.unwrap();
+    assert!(client.recovery.is_empty());
+    assert_eq!(client.pool.idle_lists(), idle + 3);
+
+    // The next flush reuses them.
+    client.send_raw_buffer(vec![0xfe, 1, 2, 3]);
+    client.flush_all().await.unwrap();
+    assert_eq!(client.pool.idle_lists(), idle + 2);
+    assert_eq!(client.pool.idle_buffers(), 1);
+
+    client.active.cancel();
+}
+
+#[tokio::test]
+async fn keepalive() {
+    let address = "127.0.0.1:19132".parse().unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let create = |keepalive_interval| RakNetCreateDescription { session_timeout: Duration::from_secs(60), keepalive_interval, ..client_description(address, Arc::clone(&socket)) };
+
+    let (enabled, _receiver) = RakNetClient::new(create(Duration::from_millis(1)), broadcast::channel(1).0, forward_channel(1, BackpressurePolicy::default()).1);
+    let (disabled, _receiver) = RakNetClient::new(create(Duration::ZERO), broadcast::channel(1).0, forward_channel(1, BackpressurePolicy::default()).1);
+
+    // The first tick always measures the round trip time, keepalives are only sent afterwards.
+    enabled.tick().await.unwrap();
+    disabled.tick().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+
+    let before = (enabled.stats().datagrams_out, disabled.stats().datagrams_out);
+    enabled.tick().await.unwrap();
+    disabled.tick().await.unwrap();
+
+    assert_eq!(enabled.stats().datagrams_out, before.0 + 1);
+    assert_eq!(disabled.stats().datagrams_out, before.1);
+    assert!(!enabled.active.is_cancelled());
+
+    enabled.active.cancel();
+    disabled.active.cancel();
+}
+
+#[tokio::test]
+async fn connection_migration() {
+    let address = "127.0.0.1:19132".parse().unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = RakNetCreateDescription { guid: 7, ..client_description(address, socket) };
+    let (client, _receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_channel(1, BackpressurePolicy::default()).1);
+    assert_eq!(client.guid, 7);
+
+    let new = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let new_address = new.local_addr().unwrap();
+    client.begin_migration(new_address).await.unwrap();
+    assert_eq!(client.migration_target(), Some(new_address));
+
+    // The verification ping is framed with the state of the existing connection.
+    let mut buffer = vec![0; 2048];
+    let (n, _) = tokio::time::timeout(Duration::from_secs(5), new.recv_from(&mut buffer)).await.unwrap().unwrap();
+    let batch = FrameBatch::deserialize(&buffer[..n]).unwrap();
+    let ping = ConnectedPing::deserialize(batch.frames[0].body.as_ref()).unwrap();
+
+    let answer = |ping_time| {
+        let mut body = RVec::alloc();
+        ConnectedPong { ping_time, pong_time: 0 }.serialize_into(&mut body).unwrap();
+        let batch = FrameBatch { sequence_number: 0, frames: vec![Frame::new(Reliability::Unreliable, body)] };
+
+        let mut serialized = RVec::alloc();
+        batch.serialize_into(&mut serialized).unwrap();
+        serialized
+    };
+
+    // A pong with the wrong nonce does not move the connection.
+    client.handle_raw_packet(answer(ping.time.wrapping_add(1))).await.unwrap();
+    assert_eq!(client.address(), address);
+
+    client.handle_raw_packet(answer(ping.time)).await.unwrap();
+    assert_eq!(client.address(), new_address);
+    assert_eq!(client.migration_target(), None);
+
+    // Anyone who knows the GUID can answer the ping, so encrypted sessions refuse to move.
+    let spoofer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let spoofer_address = spoofer.local_addr().unwrap();
+    client.begin_migration(spoofer_address).await.unwrap();
+
+    let (n, _) = tokio::time::timeout(Duration::from_secs(5), spoofer.recv_from(&mut buffer)).await.unwrap().unwrap();
+    let batch = FrameBatch::deserialize(&buffer[..n]).unwrap();
+    let ping = ConnectedPing::deserialize(batch.frames[0].body.as_ref()).unwrap();
+
+    client.lock_migration();
+    assert_eq!(client.migration_target(), None);
+    client.handle_raw_packet(answer(ping.time)).await.unwrap();
+    assert_eq!(client.address(), new_address);
+
+    assert!(client.is_migration_locked());
+    assert!(client.begin_migration(spoofer_address).await.is_err());
+    assert_eq!(client.migration_target(), None);
+
+    client.active.cancel();
+}
+
+#[test]
+fn pcapng_capture() {
+    use crate::{Capture, Direction};
+
+    let path = std::env::temp_dir().join(format!("mirai-capture-{}.pcapng", std::process::id()));
+    let capture = Capture::create(&path, "127.0.0.1:19132".parse().unwrap()).unwrap().with_payloads(true);
+
+    capture.record_datagram(Direction::Inbound, "10.0.0.1:50000".parse().unwrap(), &[0x84, 1, 2, 3, 4]);
+    capture.record_datagram(Direction::Outbound, "[::1]:50000".parse().unwrap(), &[0xc0]);
+    capture.record_payload(Direction::Inbound, b"payload");
+    capture.flush().unwrap();
+
+    let file = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // Walk the blocks and check that the trailing length matches the leading length of every block.
+    let mut blocks = Vec::new();
+    let mut offset = 0;
+    while offset < file.len() {
+        let ty = u32::from_le_bytes(file[offset..offset + 4].try_into().unwrap());
+        let len = u32::from_le_bytes(file[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        let trailer = u32::from_le_bytes(file[offset + len - 4..offset + len].try_into().unwrap()) as usize;
+
+        assert_eq!(len % 4, 0, "blocks must be padded to 32 bits");
+        assert_eq!(len, trailer, "leading and trailing block lengths differ");
+        blocks.push((ty, &file[offset + 8..offset + len - 4]));
+        offset += len;
+    }
+
+    let types = blocks.iter().map(|(ty, _)| *ty).collect::<Vec<_>>();
+    assert_eq!(types, [0x0a0d_0d0a, 1, 1, 6, 6, 6]);
+
+    // The inbound datagram is wrapped in an IPv4 header with a valid checksum.
+    let ipv4 = &blocks[3].1[20..];
+    assert_eq!(ipv4[0], 0x45);
+    let sum = ipv4[..20].chunks_exact(2).map(|w| u32::from(u16::from_be_bytes([w[0], w[1]]))).sum::<u32>();
+    assert_eq!((sum & 0xffff) + (sum >> 16), 0xffff);
+    assert_eq!(&ipv4[28..33], &[0x84, 1, 2, 3, 4]);
+
+    // The outbound datagram to an IPv6 peer uses an IPv6 header.
+    assert_eq!(blocks[4].1[20] >> 4, 6);
+    assert_eq!(&blocks[5].1[20..28], b"\0payload");
+}
+
+#[tokio::test(start_paused = true)]
+async fn urgent_flush() {
+    use std::sync::atomic::Ordering;
+
+    use crate::SendConfig;
+
+    let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = RakNetCreateDescription { keepalive_interval: Duration::ZERO, ..client_description(peer.local_addr().unwrap(), socket) };
+    // The session stops when the forwarding channel is closed, so the sender has to be kept alive.
+    let (_forward, forward_rx) = forward_channel(1, BackpressurePolicy::default());
+    let (client, _receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_rx);
+
+    // The clock is paused, so the session only runs its first tick until time is advanced.
+    while client.tick.load(Ordering::SeqCst) == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    let config = SendConfig { priority: SendPriority::High, ..DEFAULT_SEND_CONFIG };
+    client.send_raw_buffer_with_config(RVec::alloc_from_slice(&[1, 2, 3]), config);
+
+    let mut buffer = vec![0; 2048];
+    loop {
+        let (n, _) = peer.recv_from(&mut buffer).await.unwrap();
+        let Ok(batch) = FrameBatch::deserialize(&buffer[..n]) else { continue };
+        if batch.frames.iter().any(|f| f.body.as_ref() == [1, 2, 3]) {
+            break
+        }
+    }
+
+    // The frame was sent without waiting for the next tick.
+    assert_eq!(client.tick.load(Ordering::SeqCst), 1, "high priority frame was only sent by a tick");
+
+    client.active.cancel();
+}
+
+#[test]
+fn broadcast_recipients() {
+    let address = "127.0.0.1:19132".parse().unwrap();
+    let other = "127.0.0.1:19133".parse().unwrap();
+    let info = RecipientInfo {
+        address,
+        dimension: Dimension::Overworld,
+        permission_level: PermissionLevel::Member,
+        chunk_x: 2,
+        chunk_z: -3,
+        render_distance: 4,
+    };
+
+    assert!(Recipients::All.includes(address, || None));
+    assert!(!Recipients::Except(address).includes(address, || None));
+    assert!(Recipients::Except(other).includes(address, || None));
+    assert!(Recipients::Only(Arc::from([address])).includes(address, || None));
+    assert!(!Recipients::Only(Arc::from([other])).includes(address, || None));
+
+    assert!(Recipients::in_dimension(Dimension::Overworld).includes(address, || Some(info)));
+    assert!(!Recipients::in_dimension(Dimension::Nether).includes(address, || Some(info)));
+    // Sessions without a player never match a predicate.
+    assert!(!Recipients::in_dimension(Dimension::Overworld).includes(address, || None));
+
+    assert!(Recipients::with_permission(PermissionLevel::Member).includes(address, || Some(info)));
+    assert!(!Recipients::with_permission(PermissionLevel::Operator).includes(address, || Some(info)));
+
+    let near = Vector::from([96.0, 64.0, -48.0]);
+    let far = Vector::from([112.0, 64.0, -48.0]);
+    assert!(Recipients::in_range(Dimension::Overworld, near.clone()).includes(address, || Some(info)));
+    assert!(!Recipients::in_range(Dimension::Overworld, far).includes(address, || Some(info)));
+    assert!(!Recipients::in_range(Dimension::End, near).includes(address, || Some(info)));
+}
+
+#[tokio::test]
+async fn disconnect_linger() {
+    use proto::raknet::{AckEntry, DisconnectNotification};
+
+    let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = RakNetCreateDescription { keepalive_interval: Duration::ZERO, ..client_description(peer.local_addr().unwrap(), socket) };
+    let (forward, forward_rx) = forward_channel(1, BackpressurePolicy::default());
+    let (client, _receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_rx);
+
+    assert_eq!(client.close_reason(), None);
+    client.disconnect();
+    client.disconnect();
+    assert_eq!(client.close_reason(), Some(CloseReason::Local));
+
+    let mut buffer = vec![0; 2048];
+    let sequence = loop {
+        let (n, _) = tokio::time::timeout(Duration::from_secs(1), peer.recv_from(&mut buffer)).await.unwrap().unwrap();
+        let Ok(batch) = FrameBatch::deserialize(&buffer[..n]) else { continue };
+        let notifications = batch.frames.iter().filter(|f| f.body.as_ref() == [DisconnectNotification::ID]).count();
+        if notifications > 0 {
+            assert_eq!(notifications, 1, "disconnect notification was queued twice");
+            break batch.sequence_number
+        }
+    };
+
+    // The session waits for the notification to be acknowledged.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!client.shutdown_token.is_cancelled(), "session shut down before the notification was acknowledged");
+
+    let mut ack = RVec::alloc();
+    Ack { records: vec![AckEntry::Single(sequence)] }.serialize_into(&mut ack).unwrap();
+    assert_eq!(forward.send(ack).await, Forwarded::Queued);
+
+    let closed = tokio::time::timeout(Duration::from_millis(500), client.shutdown_token.cancelled()).await;
+    assert!(closed.is_ok(), "session kept lingering after the notification was acknowledged");
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn sharded_sockets() {
+    let shards = crate::bind_sharded("127.0.0.1:0".parse().unwrap(), 4).await.unwrap();
+    assert_eq!(shards.len(), 4);
+
+    let address = shards[0].local_addr().unwrap();
+    assert!(shards.iter().all(|socket| socket.local_addr().unwrap() == address));
+
+    // Every client is received by exactly one of the shards.
+    let clients = 16;
+    for _ in 0..clients {
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(&[1], address).await.unwrap();
+    }
+
+    let mut received = 0;
+    let mut buffer = [0; 16];
+    for _ in 0..100 {
+        if received == clients {
+            break
+        }
+
+        for socket in &shards {
+            while let Ok((n, _)) = socket.try_recv_from(&mut buffer) {
+                assert_eq!(&buffer[..n], &[1]);
+                received += 1;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(received, clients);
+}
+
+#[tokio::test]
+async fn selective_retransmission() {
+    use proto::raknet::AckEntry;
+
+    let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = RakNetCreateDescription { keepalive_interval: Duration::ZERO, ..client_description(peer.local_addr().unwrap(), socket) };
+    let (_forward, forward_rx) = forward_channel(1, BackpressurePolicy::default());
+    let (client, _receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_rx);
+
+    // Pretend that three batches were sent, each containing a reliable and an unreliable frame.
+    for sequence_number in 0..3u8 {
+        let frames = vec![
+            Frame::new(Reliability::Reliable, RVec::alloc_from_slice(&[sequence_number])),
+            Frame::new(Reliability::Unreliable, RVec::alloc_from_slice(&[0xff])),
+        ];
+        client.recovery.insert(FrameBatch { sequence_number: u32::from(sequence_number), frames });
+    }
+    client.batch_number.store(3, std::sync::atomic::Ordering::SeqCst);
+
+    // Ranges include their upper bound, so this covers the first two batches.
+    let nak = Nak { records: vec![AckEntry::Range(0..1)] }.serialize().unwrap();
+    client.handle_nak(nak.as_ref()).await.unwrap();
+
+    let mut buffer = vec![0; 2048];
+    let (n, _) = tokio::time::timeout(Duration::from_millis(100), peer.recv_from(&mut buffer)).await.unwrap().unwrap();
+    let batch = FrameBatch::deserialize(&buffer[..n]).unwrap();
+
+    // Only the reliable frames are resent, together in a single new batch.
+    assert_eq!(batch.sequence_number, 3);
+    assert_eq!(batch.frames.iter().map(|f| f.body.as_ref().to_vec()).collect::<Vec<_>>(), [vec![0], vec![1]]);
+
+    // The third batch was not lost and is still waiting for an acknowledgement.
+    client.recovery.acknowledge(&[AckEntry::Single(3)], &client.pool);
+    assert_eq!(client.recovery.recover(&[AckEntry::Single(2)]).len(), 1);
+    assert!(client.recovery.is_empty());
+
+    client.active.cancel();
+}
+
+#[tokio::test]
+async fn retransmission_limit() {
+    use proto::raknet::AckEntry;
+
+    let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let socket = Arc::new(Socket::from(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+    let description = RakNetCreateDescription { keepalive_interval: Duration::ZERO, ..client_description(peer.local_addr().unwrap(), socket) };
+    let (_forward, forward_rx) = forward_channel(1, BackpressurePolicy::default());
+    let (client, _receiver) = RakNetClient::new(description, broadcast::channel(1).0, forward_rx);
+
+    let frames = vec![Frame::new(Reliability::Reliable, RVec::alloc_from_slice(&[0]))];
+    client.recovery.insert_attempt(FrameBatch { sequence_number: 0, frames }, 3);
+    client.batch_number.store(1, std::sync::atomic::Ordering::SeqCst);
+
+    // A NAK does not reset the amount of attempts of the lost frames.
+    let nak = Nak { records: vec![AckEntry::Single(0)] }.serialize().unwrap();
+    client.handle_nak(nak.as_ref()).await.unwrap();
+
+    let recovered = client.recovery.recover(&[AckEntry::Single(1)]);
+    assert_eq!(recovered.iter().map(|(_, attempts)| *attempts).collect::<Vec<_>>(), [4]);
+    assert!(!client.active.is_cancelled(), "client was disconnected too early");
+
+    // Frames that keep getting lost eventually disconnect the client.
+    let (batch, _) = recovered.into_iter().next().unwrap();
+    client.recovery.insert_attempt(FrameBatch { sequence_number: 1, frames: batch.frames }, MAX_RETRANSMISSIONS);
+
+    let nak = Nak { records: vec![AckEntry::Single(1)] }.serialize().unwrap();
+    client.handle_nak(nak.as_ref()).await.unwrap();
+
+    assert!(client.active.is_cancelled(), "client was not disconnected");
+    assert_eq!(client.close_reason(), Some(CloseReason::TimedOut));
+    assert!(client.recovery.is_empty());
+}
+
+#[tokio::test]
+async fn job_shutdown_order() {
+    use tokio_util::sync::CancellationToken;
+    use util::Joinable;
+
+    use crate::{Job, JobScheduler};
+
+    let token = CancellationToken::new();
+    let scheduler = JobScheduler::new(token.clone());
+    let stopped = Arc::new(parking_lot::Mutex::new(Vec::new()));
+
+    let record = |name: &'static str| {
+        let stopped = Arc::clone(&stopped);
+        move || async move {
+            stopped.lock().push(name);
+            Ok(())
+        }
+    };
+
+    // Registered out of order, the scheduler has to start `store` first.
+    scheduler.register(Job::task("sync", |token| async move {
+        token.cancelled().await;
+        Ok(())
+    }).after("store").on_shutdown(record("sync"))).unwrap();
+    scheduler.register(Job::periodic("store", Duration::from_millis(10), || async { Ok(()) }).on_shutdown(record("store"))).unwrap();
+    assert!(scheduler.register(Job::task("store", |_| async { Ok(()) })).is_err());
+
+    scheduler.start().unwrap();
+    assert_eq!(scheduler.running(), ["store", "sync"]);
+    assert!(scheduler.register(Job::task("late", |_| async { Ok(()) }).after("missing")).is_err());
+
+    token.cancel();
+    tokio::time::timeout(Duration::from_secs(5), scheduler.join()).await.unwrap().unwrap();
+    assert_eq!(*stopped.lock(), ["sync", "store"]);
+
+    // Cycles are rejected before any job is started.
+    let cyclic = JobScheduler::new(CancellationToken::new());
+    cyclic.register(Job::task("a", |_| async { Ok(()) }).after("b")).unwrap();
+    cyclic.register(Job::task("b", |_| async { Ok(()) }).after("a")).unwrap();
+    assert!(cyclic.start().is_err());
+    assert!(cyclic.running().is_empty());
+}
+
+#[tokio::test]
+async fn forward_backpressure() {
+    let datagram = |byte: u8| RVec::alloc_from_slice(&[byte]);
+
+    let (sender, mut receiver) = forward_channel(2, BackpressurePolicy::DropOldest);
+    for byte in 0..3 {
+        let _: Forwarded = sender.try_send(datagram(byte));
+    }
+    assert_eq!(receiver.recv().await.unwrap().as_ref(), [1]);
+    assert_eq!(receiver.recv().await.unwrap().as_ref(), [2]);
+
+    let (sender, _receiver) = forward_channel(1, BackpressurePolicy::GrowBounded { max: 2 });
+    assert_eq!(sender.try_send(datagram(0)), Forwarded::Queued);
+    assert_eq!(sender.try_send(datagram(1)), Forwarded::Queued);
+    assert_eq!(sender.try_send(datagram(2)), Forwarded::DroppedNewest);
+
+    // Consecutive timeouts eventually mark the session as overloaded.
+    let policy = BackpressurePolicy::DisconnectAfter { timeout: Duration::from_millis(1), limit: 2 };
+    let (sender, receiver) = forward_channel(1, policy);
+    assert_eq!(sender.send(datagram(0)).await, Forwarded::Queued);
+    assert_eq!(sender.send(datagram(1)).await, Forwarded::TimedOut);
+    assert_eq!(sender.send(datagram(2)).await, Forwarded::Overloaded);
+
+    drop(receiver);
+    assert_eq!(sender.send(datagram(3)).await, Forwarded::Closed);
+}
+
+#[test]
+fn weighted_fair_queuing() {
+    let queues = SendQueues::with_weights(SendWeights::default());
+    for priority in SendPriority::ALL {
+        for _ in 0..100 {
+            queues.insert_raw(priority, Frame { body: RVec::alloc_from_slice(&[0; 100]), ..Default::default() });
+        }
+    }
+
+    let mut budget = 7000;
+    let mut frames = Vec::new();
+    queues.flush_weighted(&mut budget, &mut frames);
+
+    // Every priority makes progress in proportion to its weight.
+    assert_eq!(budget, 0);
+    assert_eq!(frames.len(), 70);
+    for (priority, sent) in SendPriority::ALL.into_iter().zip([40, 20, 10]) {
+        let mut remaining = Vec::new();
+        queues.flush(priority, &mut remaining);
+        assert_eq!(remaining.len(), 100 - sent, "{priority:?} did not receive its share");
+    }
+}
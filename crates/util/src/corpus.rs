@@ -0,0 +1,42 @@
+//! Shared test helper for the regression corpora of the decoding crates.
+//!
+//! Only available with the `testing` feature, which crates enable in their dev-dependencies.
+
+use std::path::{Path, PathBuf};
+
+/// Feeds every file in the corpus directory `dir` to `decode` and asserts that each of them is rejected.
+///
+/// Callers usually pass `concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/<target>")`.
+///
+/// # Panics
+///
+/// Panics if the directory cannot be read or is empty, or if any input is accepted by `decode`.
+pub fn check_corpus<P, F>(dir: P, decode: F)
+where
+    P: AsRef<Path>,
+    F: Fn(&[u8]) -> anyhow::Result<()>,
+{
+    let dir = dir.as_ref();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => panic!("failed to read corpus directory {dir:?}: {err}"),
+    };
+
+    let mut paths = entries
+        .map(|entry| match entry {
+            Ok(entry) => entry.path(),
+            Err(err) => panic!("failed to read entry of corpus directory {dir:?}: {err}"),
+        })
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+
+    assert!(!paths.is_empty(), "corpus directory {dir:?} is empty");
+    for path in paths {
+        let input = match std::fs::read(&path) {
+            Ok(input) => input,
+            Err(err) => panic!("failed to read corpus entry {path:?}: {err}"),
+        };
+
+        assert!(decode(&input).is_err(), "{path:?} was accepted");
+    }
+}
@@ -196,3 +196,7 @@ glob_export!(bytes);
 glob_export!(traits);
 glob_export!(vector);
 glob_export!(sync);
+
+pub mod task;
+#[cfg(feature = "testing")]
+pub mod corpus;
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime};
+
+use parking_lot::Mutex;
+use tokio::task::JoinHandle;
+
+/// Identifier assigned to the next spawned task.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+/// All tasks that are currently running, indexed by their identifier.
+static REGISTRY: Mutex<BTreeMap<u64, TaskInfo>> = Mutex::new(BTreeMap::new());
+
+/// Information about a task spawned using [`spawn`] or [`spawn_owned`].
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    /// Identifier of the task, unique for the lifetime of the process.
+    pub id: u64,
+    /// What the task does, such as `level::ticker`.
+    pub purpose: &'static str,
+    /// The session or service that the task belongs to.
+    pub owner: Option<String>,
+    /// When the task was spawned.
+    pub spawned: SystemTime,
+    /// Used to compute the age of the task.
+    started: Instant,
+}
+
+impl TaskInfo {
+    /// How long the task has been running for.
+    #[inline]
+    pub fn age(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+}
+
+/// Returns all tasks that are currently running, ordered by spawn time.
+///
+/// Long-lived tasks that are still listed after their owner is gone usually indicate a leak.
+pub fn running() -> Vec<TaskInfo> {
+    REGISTRY.lock().values().cloned().collect()
+}
+
+/// Removes a task from the registry when it finishes or is aborted.
+struct Registration(u64);
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        REGISTRY.lock().remove(&self.0);
+    }
+}
+
+/// Spawns a named task on the current Tokio runtime.
+///
+/// Names show up in tokio-console and task dumps, which makes it possible to tell apart
+/// the many tasks that run the same function, such as the receivers of every connected client.
+/// The task is also recorded in the task registry until it completes, see [`running`].
+///
+/// Task names are only recorded when compiling with `--cfg tokio_unstable`, which is enabled in
+/// the workspace's Cargo config. Otherwise this is equivalent to [`tokio::spawn`].
+///
+/// # Panics
+///
+/// This function panics when called outside of a Tokio runtime.
+#[track_caller]
+pub fn spawn<F>(purpose: &'static str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    register(purpose, None, future)
+}
+
+/// Spawns a named task that belongs to a session or service.
+///
+/// The owner is appended to the task name in tokio-console, so that the tasks of a single client can be found.
+///
+/// # Panics
+///
+/// This function panics when called outside of a Tokio runtime.
+#[track_caller]
+pub fn spawn_owned<F, O>(purpose: &'static str, owner: O, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+    O: ToString,
+{
+    register(purpose, Some(owner.to_string()), future)
+}
+
+/// Records the task in the registry and spawns it.
+#[track_caller]
+fn register<F>(purpose: &'static str, owner: Option<String>, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let name = owner.as_ref().map_or_else(|| purpose.to_owned(), |owner| format!("{purpose} {owner}"));
+
+    REGISTRY.lock().insert(id, TaskInfo { id, purpose, owner, spawned: SystemTime::now(), started: Instant::now() });
+    let registration = Registration(id);
+    let future = async move {
+        let _registration = registration;
+        future.await
+    };
+
+    spawn_named(&name, future)
+}
+
+#[track_caller]
+#[cfg_attr(not(tokio_unstable), allow(unused_variables))]
+fn spawn_named<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(tokio_unstable)]
+    match tokio::task::Builder::new().name(name).spawn(future) {
+        Ok(handle) => handle,
+        // Only blocking tasks can fail to spawn.
+        Err(err) => unreachable!("Failed to spawn task {name}: {err}"),
+    }
+
+    #[cfg(not(tokio_unstable))]
+    tokio::spawn(future)
+}